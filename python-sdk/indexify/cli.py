@@ -163,6 +163,10 @@ def executor(
     executor_cache: Optional[str] = typer.Option(
         "~/.indexify/executor_cache", help="Path to the executor cache directory"
     ),
+    prefetch_window: Optional[int] = typer.Option(
+        None,
+        help="Most tasks sent ahead of their completion, bounded by the server",
+    ),
 ):
     id = nanoid.generate()
     console.print(
@@ -192,6 +196,7 @@ def executor(
         server_addr=server_addr,
         config_path=config_path,
         code_path=executor_cache,
+        prefetch_window=prefetch_window,
     )
 
     try:
@@ -1,3 +1,8 @@
+from typing import Optional
+
+
 class ApiException(Exception):
-    def __init__(self, message: str) -> None:
+    def __init__(self, message: str, code: Optional[str] = None) -> None:
         super().__init__(message)
+        # Code of the error returned by the server, e.g. COMPUTE_GRAPH_NOT_FOUND.
+        self.code = code
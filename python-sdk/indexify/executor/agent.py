@@ -18,8 +18,10 @@ from indexify.functions_sdk.data_objects import (
     IndexifyData,
     RouterOutput,
 )
+from indexify.settings import auth_headers
 
 from .api_objects import ExecutorMetadata, Task
+from .diagnostics import Diagnostics, record_crash
 from .downloader import DownloadedInputs, Downloader
 from .executor_tasks import DownloadGraphTask, DownloadInputTask, ExtractTask
 from .function_worker import FunctionWorker
@@ -57,8 +59,10 @@ class ExtractorAgent:
         function_worker: FunctionWorker,
         server_addr: str = "localhost:8900",
         config_path: Optional[str] = None,
+        prefetch_window: Optional[int] = None,
     ):
         self.num_workers = num_workers
+        self._prefetch_window = prefetch_window
         self._use_tls = False
         if config_path:
             with open(config_path, "r") as f:
@@ -99,6 +103,30 @@ class ExtractorAgent:
             base_url=self._base_url, executor_id=self._executor_id
         )
         self._probe = RuntimeProbes()
+        self._diagnostics = Diagnostics(
+            config={
+                "executor_id": executor_id,
+                "server_addr": server_addr,
+                "num_workers": num_workers,
+                "code_path": code_path,
+                "prefetch_window": prefetch_window,
+                "use_tls": self._use_tls,
+            },
+            code_path=code_path,
+        )
+
+    async def report_diagnostics(self):
+        url = f"{self._base_url}/internal/executors/{self._executor_id}/diagnostics"
+        try:
+            sections = await asyncio.to_thread(self._diagnostics.collect)
+            async with httpx.AsyncClient(headers=auth_headers()) as client:
+                response = await client.put(url, json={"sections": sections})
+                response.raise_for_status()
+        except Exception as e:
+            console.print(
+                Text("diagnostics Error: ", style="red bold")
+                + Text(f"failed to report diagnostics: {e}", style="red")
+            )
 
     async def task_completion_reporter(self):
         console.print(Text("Starting task completion reporter", style="bold cyan"))
@@ -270,7 +298,10 @@ class ExtractorAgent:
                             reducer=outputs.reducer,
                         )
                         self._task_store.complete(outcome=completed_task)
-                    except BrokenProcessPool:
+                    except BrokenProcessPool as e:
+                        record_crash(
+                            f"function worker pool broke running task {async_task.task.id}: {e}"
+                        )
                         self._task_store.retriable_failure(async_task.task.id)
                         continue
                     except Exception as e:
@@ -312,6 +343,7 @@ class ExtractorAgent:
                 addr="",
                 image_name=runtime_probe.image_name,
                 labels=runtime_probe.labels,
+                prefetch_window=self._prefetch_window,
             ).model_dump()
 
             panel_content = "\n".join(
@@ -326,7 +358,7 @@ class ExtractorAgent:
             )
 
             try:
-                async with httpx.AsyncClient() as client:
+                async with httpx.AsyncClient(headers=auth_headers()) as client:
                     async with aconnect_sse(
                         client,
                         "POST",
@@ -337,7 +369,12 @@ class ExtractorAgent:
                         console.print(
                             Text("executor registered successfully", style="bold green")
                         )
+                        asyncio.create_task(self.report_diagnostics())
                         async for sse in event_source.aiter_sse():
+                            # The server asks for fresh diagnostics.
+                            if sse.event == "diagnostics":
+                                asyncio.create_task(self.report_diagnostics())
+                                continue
                             data = json.loads(sse.data)
                             tasks = []
                             for task_dict in data:
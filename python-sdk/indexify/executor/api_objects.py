@@ -21,6 +21,7 @@ class ExecutorMetadata(BaseModel):
     addr: str
     image_name: str
     labels: Dict[str, Any]
+    prefetch_window: Optional[int] = None
 
 
 class RouterOutput(BaseModel):
@@ -41,3 +42,8 @@ class TaskResult(BaseModel):
     executor_id: str
     task_id: str
     reducer: bool = False
+
+
+class TaskResultAck(BaseModel):
+    task_id: str
+    result_sequence: int
@@ -0,0 +1,96 @@
+import os
+import platform
+import shutil
+import subprocess
+import sys
+import time
+from typing import Any, Dict, List
+
+CRASH_REPORTS_DIR = os.path.expanduser("~/.indexify/crash_reports")
+# Crash reports shipped with the diagnostics, the latest ones.
+MAX_CRASH_REPORTS = 5
+MAX_CRASH_REPORT_BYTES = 16 * 1024
+
+
+def record_crash(reason: str) -> None:
+    """Keeps a crash report for the diagnostics shipped to the server."""
+    os.makedirs(CRASH_REPORTS_DIR, exist_ok=True)
+    path = os.path.join(CRASH_REPORTS_DIR, f"{int(time.time() * 1000)}.txt")
+    with open(path, "w") as f:
+        f.write(reason)
+    reports = sorted(os.listdir(CRASH_REPORTS_DIR))
+    for name in reports[:-MAX_CRASH_REPORTS]:
+        os.remove(os.path.join(CRASH_REPORTS_DIR, name))
+
+
+class Diagnostics:
+    """Collects the diagnostics of the executor, by section."""
+
+    def __init__(self, config: Dict[str, Any], code_path: str):
+        self._config = config
+        self._code_path = code_path
+
+    def collect(self) -> Dict[str, Any]:
+        return {
+            "config": self._startup_config(),
+            "gpu": self._gpu(),
+            "caches": self._caches(),
+            "crash_reports": self._crash_reports(),
+        }
+
+    def _startup_config(self) -> Dict[str, Any]:
+        return {
+            **self._config,
+            "python_version": sys.version.split()[0],
+            "platform": platform.platform(),
+        }
+
+    def _gpu(self) -> Dict[str, Any]:
+        if shutil.which("nvidia-smi") is None:
+            return {"available": False}
+        try:
+            output = subprocess.run(
+                [
+                    "nvidia-smi",
+                    "--query-gpu=name,driver_version,memory.total",
+                    "--format=csv,noheader",
+                ],
+                capture_output=True,
+                text=True,
+                timeout=5,
+                check=True,
+            ).stdout
+        except Exception as e:
+            return {"available": False, "error": str(e)}
+        devices = []
+        for line in output.strip().splitlines():
+            name, driver_version, memory = [part.strip() for part in line.split(",")]
+            devices.append(
+                {"name": name, "driver_version": driver_version, "memory": memory}
+            )
+        return {"available": True, "devices": devices}
+
+    def _caches(self) -> Dict[str, Any]:
+        size_bytes = 0
+        files = 0
+        for root, _, names in os.walk(self._code_path):
+            for name in names:
+                try:
+                    size_bytes += os.path.getsize(os.path.join(root, name))
+                    files += 1
+                except OSError:
+                    continue
+        return {
+            "code": {"path": self._code_path, "files": files, "bytes": size_bytes}
+        }
+
+    def _crash_reports(self) -> List[Dict[str, Any]]:
+        if not os.path.isdir(CRASH_REPORTS_DIR):
+            return []
+        reports = []
+        for name in sorted(os.listdir(CRASH_REPORTS_DIR))[-MAX_CRASH_REPORTS:]:
+            with open(os.path.join(CRASH_REPORTS_DIR, name), "r") as f:
+                reports.append(
+                    {"name": name, "report": f.read(MAX_CRASH_REPORT_BYTES)}
+                )
+        return reports
@@ -9,6 +9,7 @@ from rich.theme import Theme
 
 from indexify.functions_sdk.data_objects import IndexifyData
 from indexify.functions_sdk.object_serializer import MsgPackSerializer
+from indexify.settings import auth_headers
 
 from .api_objects import Task
 
@@ -47,7 +48,9 @@ class Downloader:
         )
 
         response = httpx.get(
-            f"{self.base_url}/internal/namespaces/{namespace}/compute_graphs/{name}/code"
+            f"{self.base_url}/internal/namespaces/{namespace}/compute_graphs/{name}/code",
+            params={"version": version},
+            headers=auth_headers(),
         )
         try:
             response.raise_for_status()
@@ -85,7 +88,7 @@ class Downloader:
             )
         )
 
-        response = httpx.get(url)
+        response = httpx.get(url, headers=auth_headers())
         try:
             response.raise_for_status()
         except httpx.HTTPStatusError as e:
@@ -105,7 +108,7 @@ class Downloader:
 
         init_value = None
         if reducer_url:
-            init_value = httpx.get(reducer_url)
+            init_value = httpx.get(reducer_url, headers=auth_headers())
             try:
                 init_value.raise_for_status()
             except httpx.HTTPStatusError as e:
@@ -6,10 +6,11 @@ import nanoid
 from rich import print
 
 from indexify.executor.api_objects import RouterOutput as ApiRouterOutput
-from indexify.executor.api_objects import Task, TaskResult
+from indexify.executor.api_objects import Task, TaskResult, TaskResultAck
 from indexify.executor.task_store import CompletedTask
 from indexify.functions_sdk.data_objects import IndexifyData, RouterOutput
 from indexify.functions_sdk.object_serializer import MsgPackSerializer
+from indexify.settings import auth_headers
 
 
 # https://github.com/psf/requests/issues/1081#issuecomment-428504128
@@ -97,6 +98,7 @@ class TaskReporter:
         try:
             response = httpx.post(
                 url=f"{self._base_url}/internal/ingest_files",
+                headers={"Idempotency-Key": completed_task.report_id, **auth_headers()},
                 **kwargs,
             )
         except Exception as e:
@@ -108,3 +110,10 @@ class TaskReporter:
         except Exception as e:
             print(f"failed to report task outcome {response.text}")
             raise e
+
+        # The outcome is only committed once the server acknowledged it, it's
+        # kept and reported again until then.
+        ack = TaskResultAck.model_validate_json(response.text)
+        print(
+            f"[bold]task-reporter[/bold] task {ack.task_id} acknowledged with sequence {ack.result_sequence}"
+        )
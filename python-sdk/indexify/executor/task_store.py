@@ -1,7 +1,8 @@
 import asyncio
 from typing import Dict, List, Literal, Optional
 
-from pydantic import BaseModel
+import nanoid
+from pydantic import BaseModel, Field
 from rich import print
 
 from indexify.functions_sdk.data_objects import IndexifyData, RouterOutput
@@ -18,6 +19,9 @@ class CompletedTask(BaseModel):
     stdout: Optional[str] = None
     stderr: Optional[str] = None
     reducer: bool = False
+    # Sent with every report of the outcome so that the server applies it
+    # once however many times it's retried.
+    report_id: str = Field(default_factory=nanoid.generate)
 
 
 class TaskStore:
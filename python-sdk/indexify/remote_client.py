@@ -14,7 +14,11 @@ from indexify.base_client import IndexifyClient
 from indexify.error import ApiException
 from indexify.functions_sdk.data_objects import IndexifyData
 from indexify.functions_sdk.graph import ComputeGraphMetadata, Graph
-from indexify.settings import DEFAULT_SERVICE_URL, DEFAULT_SERVICE_URL_HTTPS
+from indexify.settings import (
+    DEFAULT_SERVICE_URL,
+    DEFAULT_SERVICE_URL_HTTPS,
+    auth_headers,
+)
 
 
 class InvocationEventPayload(BaseModel):
@@ -45,6 +49,7 @@ class RemoteClient(IndexifyClient):
         service_url: str = DEFAULT_SERVICE_URL,
         config_path: Optional[str] = None,
         namespace: str = "default",
+        api_key: Optional[str] = None,
         **kwargs,
     ):
         if os.environ.get("INDEXIFY_URL"):
@@ -65,6 +70,12 @@ class RemoteClient(IndexifyClient):
                     verify=tls_config.get("ca_bundle_path", True),
                 )
 
+        # Keys are sent with every request, the INDEXIFY_API_KEY environment
+        # variable is used when none is passed.
+        if api_key:
+            self._client.headers["Authorization"] = f"Bearer {api_key}"
+        else:
+            self._client.headers.update(auth_headers())
         self.namespace: str = namespace
         self.compute_graphs: List[Graph] = []
         self.labels: dict = {}
@@ -72,16 +83,31 @@ class RemoteClient(IndexifyClient):
         self._timeout = kwargs.get("timeout")
         self._graphs: Dict[str, Graph] = {}
 
+    @staticmethod
+    def _error_code(response: httpx.Response) -> Optional[str]:
+        if response.headers.get("content-type") != "application/problem+json":
+            return None
+        try:
+            return response.json().get("code")
+        except json.JSONDecodeError:
+            return None
+
     def _request(self, method: str, **kwargs) -> httpx.Response:
         try:
             response = self._client.request(method, timeout=self._timeout, **kwargs)
             status_code = str(response.status_code)
             if status_code.startswith("4"):
                 raise ApiException(
-                    "status code: " + status_code + " request args: " + str(kwargs)
+                    "status code: "
+                    + status_code
+                    + " request args: "
+                    + str(kwargs)
+                    + " error: "
+                    + response.text,
+                    code=self._error_code(response),
                 )
             if status_code.startswith("5"):
-                raise ApiException(response.text)
+                raise ApiException(response.text, code=self._error_code(response))
         except httpx.ConnectError:
             message = (
                 f"Make sure the server is running and accesible at {self._service_url}"
@@ -217,7 +243,7 @@ class RemoteClient(IndexifyClient):
     ) -> str:
         ser_input = cloudpickle.dumps(kwargs)
         params = {"block_until_finish": block_until_done}
-        with httpx.Client() as client:
+        with httpx.Client(headers=self._client.headers) as client:
             with connect_sse(
                 client,
                 "POST",
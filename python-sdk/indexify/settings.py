@@ -1,2 +1,15 @@
+import os
+from typing import Dict
+
 DEFAULT_SERVICE_URL = "http://localhost:8900"
 DEFAULT_SERVICE_URL_HTTPS = "https://localhost:8900"
+
+API_KEY_ENV = "INDEXIFY_API_KEY"
+
+
+def auth_headers() -> Dict[str, str]:
+    """Authorization header with the API key of the environment, if any."""
+    api_key = os.environ.get(API_KEY_ENV)
+    if not api_key:
+        return {}
+    return {"Authorization": f"Bearer {api_key}"}
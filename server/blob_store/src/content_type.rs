@@ -0,0 +1,87 @@
+/// Number of bytes at the start of a blob its content type is sniffed from.
+pub const SNIFF_LEN: usize = 512;
+
+const ZIP: &str = "application/zip";
+
+/// Content type of a blob given the type sniffed from its first bytes,
+/// falling back to the extension of its file name. Extensions also win over
+/// generic zip archives, which many document formats are stored as.
+pub fn detect(sniffed: Option<&str>, file_name: Option<&str>) -> Option<String> {
+    let guessed = file_name.and_then(|name| mime_guess::from_path(name).first_raw());
+    match (sniffed, guessed) {
+        (Some(ZIP), Some(guessed)) => Some(guessed.to_string()),
+        (Some(sniffed), _) => Some(sniffed.to_string()),
+        (None, guessed) => guessed.map(str::to_string),
+    }
+}
+
+/// Content type of well known formats identified by their magic bytes.
+pub fn sniff(head: &[u8]) -> Option<&'static str> {
+    const MAGIC: &[(&[u8], &str)] = &[
+        (b"%PDF-", "application/pdf"),
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"II*\x00", "image/tiff"),
+        (b"MM\x00*", "image/tiff"),
+        (b"PK\x03\x04", ZIP),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"ID3", "audio/mpeg"),
+        (b"OggS", "audio/ogg"),
+        (b"fLaC", "audio/flac"),
+        (b"\x1a\x45\xdf\xa3", "video/webm"),
+        (b"<?xml", "application/xml"),
+    ];
+    if let Some((_, content_type)) = MAGIC.iter().find(|(magic, _)| head.starts_with(magic)) {
+        return Some(content_type);
+    }
+    if head.len() >= 12 && &head[..4] == b"RIFF" {
+        match &head[8..12] {
+            b"WEBP" => return Some("image/webp"),
+            b"WAVE" => return Some("audio/wav"),
+            b"AVI " => return Some("video/x-msvideo"),
+            _ => {}
+        }
+    }
+    if head.len() >= 8 && &head[4..8] == b"ftyp" {
+        return Some("video/mp4");
+    }
+    let text = String::from_utf8_lossy(&head[..head.len().min(64)]).to_lowercase();
+    let text = text.trim_start();
+    if text.starts_with("<!doctype html") || text.starts_with("<html") {
+        return Some("text/html");
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let detect = |head: &[u8], file_name| detect(sniff(head), file_name);
+        assert_eq!(
+            detect(b"%PDF-1.7\n...", None).as_deref(),
+            Some("application/pdf")
+        );
+        assert_eq!(
+            detect(b"\x89PNG\r\n\x1a\n\x00", Some("image.jpg")).as_deref(),
+            Some("image/png")
+        );
+        assert_eq!(
+            detect(b"RIFF\x00\x00\x00\x00WEBPVP8 ", None).as_deref(),
+            Some("image/webp")
+        );
+        assert_eq!(
+            detect(b"PK\x03\x04", Some("report.docx")).as_deref(),
+            Some("application/vnd.openxmlformats-officedocument.wordprocessingml.document")
+        );
+        assert_eq!(
+            detect(b"a,b\n1,2\n", Some("table.csv")).as_deref(),
+            Some("text/csv")
+        );
+        assert_eq!(detect(b"\x00\x01", None), None);
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use object_store::{aws::AwsCredential, CredentialProvider};
+use tokio::sync::Mutex;
+use tracing::info;
+
+/// Lifetime requested for credentials obtained from STS.
+const SESSION_DURATION: Duration = Duration::from_secs(3600);
+
+/// Credentials are refreshed this long before they expire so that requests
+/// in flight never carry an expired session.
+const REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+struct CachedCredential {
+    credential: Arc<AwsCredential>,
+    refresh_at: Instant,
+}
+
+impl std::fmt::Debug for CachedCredential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CachedCredential")
+            .field("credential", &"<hidden>")
+            .field("refresh_at", &self.refresh_at)
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+pub struct WebIdentityCredentialProvider {
+    client: reqwest::Client,
+    endpoint: String,
+    role_arn: String,
+    token_file: String,
+    session_name: String,
+    cached: Mutex<Option<CachedCredential>>,
+}
+
+impl WebIdentityCredentialProvider {
+    pub fn new(region: &str, role_arn: &str, token_file: &str, session_name: Option<&str>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            endpoint: format!("https://sts.{}.amazonaws.com/", region),
+            role_arn: role_arn.to_string(),
+            token_file: token_file.to_string(),
+            session_name: session_name.unwrap_or("indexify").to_string(),
+            cached: Mutex::new(None),
+        }
+    }
+
+    async fn assume_role(&self) -> anyhow::Result<AwsCredential> {
+        let token = tokio::fs::read_to_string(&self.token_file)
+            .await
+            .map_err(|e| anyhow::anyhow!("unable to read {}: {}", self.token_file, e))?;
+        let duration = SESSION_DURATION.as_secs().to_string();
+        let response = self
+            .client
+            .post(&self.endpoint)
+            .form(&[
+                ("Action", "AssumeRoleWithWebIdentity"),
+                ("Version", "2011-06-15"),
+                ("RoleArn", self.role_arn.as_str()),
+                ("RoleSessionName", self.session_name.as_str()),
+                ("WebIdentityToken", token.trim()),
+                ("DurationSeconds", duration.as_str()),
+            ])
+            .send()
+            .await?;
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            return Err(anyhow::anyhow!(
+                "AssumeRoleWithWebIdentity for {} failed with {}: {}",
+                self.role_arn,
+                status,
+                xml_tag(&body, "Message").unwrap_or(body.as_str())
+            ));
+        }
+        let field = |name| {
+            xml_tag(&body, name)
+                .map(str::to_string)
+                .ok_or_else(|| anyhow::anyhow!("STS response is missing {}", name))
+        };
+        Ok(AwsCredential {
+            key_id: field("AccessKeyId")?,
+            secret_key: field("SecretAccessKey")?,
+            token: Some(field("SessionToken")?),
+        })
+    }
+}
+
+#[async_trait]
+impl CredentialProvider for WebIdentityCredentialProvider {
+    type Credential = AwsCredential;
+
+    async fn get_credential(&self) -> object_store::Result<Arc<AwsCredential>> {
+        let mut cached = self.cached.lock().await;
+        if let Some(cached) = cached.as_ref() {
+            if Instant::now() < cached.refresh_at {
+                return Ok(cached.credential.clone());
+            }
+        }
+        let requested_at = Instant::now();
+        let credential =
+            Arc::new(
+                self.assume_role()
+                    .await
+                    .map_err(|e| object_store::Error::Generic {
+                        store: "S3",
+                        source: e.into(),
+                    })?,
+            );
+        info!(
+            role_arn = self.role_arn,
+            "refreshed web identity credentials"
+        );
+        *cached = Some(CachedCredential {
+            credential: credential.clone(),
+            refresh_at: requested_at + SESSION_DURATION - REFRESH_MARGIN,
+        });
+        Ok(credential)
+    }
+}
+
+// STS only speaks XML, and the handful of flat fields needed here don't
+// warrant a parser.
+fn xml_tag<'a>(body: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let start = body.find(&open)? + open.len();
+    let end = start + body[start..].find(&close)?;
+    Some(body[start..end].trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xml_tag() {
+        let body = r#"<AssumeRoleWithWebIdentityResponse>
+  <AssumeRoleWithWebIdentityResult>
+    <Credentials>
+      <AccessKeyId>ASIAEXAMPLE</AccessKeyId>
+      <SecretAccessKey>secret</SecretAccessKey>
+      <SessionToken>token</SessionToken>
+    </Credentials>
+  </AssumeRoleWithWebIdentityResult>
+</AssumeRoleWithWebIdentityResponse>"#;
+        assert_eq!(xml_tag(body, "AccessKeyId"), Some("ASIAEXAMPLE"));
+        assert_eq!(xml_tag(body, "SessionToken"), Some("token"));
+        assert_eq!(xml_tag(body, "Expiration"), None);
+    }
+}
@@ -21,7 +21,7 @@ impl HttpReader {
 impl BlobStorageReader for HttpReader {
     async fn get(&self) -> Result<BoxStream<'static, Result<Bytes>>> {
         let client = reqwest::Client::new();
-        let response = client.get(&self.url).send().await?;
+        let response = client.get(&self.url).send().await?.error_for_status()?;
         let stream = async_stream::stream! {
             let mut stream = response.bytes_stream();
             while let Some(chunk) = stream.next().await {
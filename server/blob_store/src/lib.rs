@@ -1,31 +1,74 @@
 use std::{env, fmt::Debug, sync::Arc};
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
 use bytes::{Bytes, BytesMut};
 use futures::{stream::BoxStream, StreamExt};
-use object_store::{
-    aws::{AmazonS3, AmazonS3Builder},
-    local,
-    ObjectStore,
-    WriteMultipart,
-};
+#[cfg(feature = "s3")]
+use object_store::aws::AmazonS3;
+use object_store::{local, ObjectStore, WriteMultipart};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use tokio::io::AsyncWrite;
 
-use self::{disk::DiskFileReader, s3::S3FileReader};
+use self::disk::DiskFileReader;
+#[cfg(feature = "s3")]
+use self::s3::S3FileReader;
 
+pub mod content_type;
+#[cfg(feature = "s3")]
+mod credentials;
 pub mod disk;
+#[cfg(feature = "http")]
 pub mod http;
+pub mod registry;
+#[cfg(feature = "s3")]
 pub mod s3;
 
+const INLINE_URL_PREFIX: &str = "data:application/octet-stream;base64,";
+
+/// Whether the url holds its payload inline rather than pointing at a blob.
+pub fn is_inline(url: &str) -> bool {
+    url.starts_with("data:")
+}
+
+fn decode_inline(url: &str) -> Result<Bytes> {
+    let (_, data) = url
+        .split_once(";base64,")
+        .ok_or_else(|| anyhow!("invalid inline url"))?;
+    Ok(STANDARD.decode(data)?.into())
+}
+
 type BlobStorageReaderTS = Arc<dyn BlobStorageReader + Sync + Send>;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct S3Config {
     pub bucket: String,
     pub region: String,
+    #[serde(default)]
+    pub credentials: S3Credentials,
+}
+
+/// Where the S3 backend gets its credentials from.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum S3Credentials {
+    /// The standard AWS environment chain: static keys, the web identity
+    /// token injected by IRSA, ECS task roles and EC2 instance profiles.
+    #[default]
+    Environment,
+    /// Exchange a projected workload identity token for short-lived
+    /// credentials of a role. Covers IRSA as well as GKE workload identity
+    /// and Azure managed identities federated to an AWS role, without any
+    /// long-lived keys. The token file is re-read on every refresh because
+    /// the kubelet rotates it.
+    WebIdentity {
+        role_arn: String,
+        token_file: String,
+        #[serde(default)]
+        session_name: Option<String>,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -37,6 +80,14 @@ pub struct DiskStorageConfig {
 pub struct BlobStorageConfig {
     pub s3: Option<S3Config>,
     pub disk: Option<DiskStorageConfig>,
+    /// Payloads smaller than this are kept inline as `data:` urls instead of
+    /// being written to the backend. Unset disables inlining.
+    #[serde(default)]
+    pub inline_threshold_bytes: Option<u64>,
+    /// Directories under which namespaces can be given a disk placement
+    /// through the API. Disk placements are refused when empty.
+    #[serde(default)]
+    pub namespace_disk_roots: Vec<String>,
 }
 
 impl BlobStorageConfig {
@@ -46,6 +97,8 @@ impl BlobStorageConfig {
             disk: Some(DiskStorageConfig {
                 path: path.to_string(),
             }),
+            inline_threshold_bytes: None,
+            namespace_disk_roots: Vec::new(),
         }
     }
 }
@@ -58,6 +111,8 @@ impl Default for BlobStorageConfig {
             disk: Some(DiskStorageConfig {
                 path: blob_store_path.to_str().unwrap().to_string(),
             }),
+            inline_threshold_bytes: None,
+            namespace_disk_roots: Vec::new(),
         }
     }
 }
@@ -67,6 +122,8 @@ pub struct PutResult {
     pub url: String,
     pub size_bytes: u64,
     pub sha256_hash: String,
+    /// Sniffed from the first bytes of the data.
+    pub content_type: Option<String>,
 }
 
 #[async_trait]
@@ -109,13 +166,52 @@ impl std::fmt::Debug for StoragePartWriter {
     }
 }
 
+/// Names of the blob storage backends compiled into this build.
+pub fn compiled_backends() -> Vec<&'static str> {
+    #[allow(unused_mut)]
+    let mut backends = vec!["disk"];
+    #[cfg(feature = "s3")]
+    backends.push("s3");
+    #[cfg(feature = "http")]
+    backends.push("http");
+    backends
+}
+
+struct InlineReader {
+    url: String,
+}
+
+#[async_trait]
+impl BlobStorageReader for InlineReader {
+    async fn get(&self) -> Result<BoxStream<'static, Result<Bytes>>> {
+        let bytes = decode_inline(&self.url)?;
+        Ok(Box::pin(futures::stream::once(async move { Ok(bytes) })))
+    }
+}
+
+#[cfg(not(all(feature = "s3", feature = "http")))]
+struct UnsupportedReader {
+    backend: &'static str,
+}
+
+#[cfg(not(all(feature = "s3", feature = "http")))]
+#[async_trait]
+impl BlobStorageReader for UnsupportedReader {
+    async fn get(&self) -> Result<BoxStream<'static, Result<Bytes>>> {
+        Err(anyhow!(
+            "blob store was built without {} support",
+            self.backend
+        ))
+    }
+}
+
+#[cfg(feature = "s3")]
 fn s3_storage(s3: &S3Config) -> Result<AmazonS3> {
-    Ok(AmazonS3Builder::from_env()
-        .with_region(s3.region.as_str())
+    s3::s3_builder(s3)
         .with_allow_http(true)
         .with_bucket_name(s3.bucket.clone())
         .build()
-        .context("unable to build S3 builder")?)
+        .map_err(|e| anyhow!("unable to build S3 builder: {}", e))
 }
 
 fn file_storage(disk: DiskStorageConfig) -> Result<local::LocalFileSystem> {
@@ -126,9 +222,11 @@ fn file_storage(disk: DiskStorageConfig) -> Result<local::LocalFileSystem> {
 
 impl BlobStorage {
     pub fn new(config: BlobStorageConfig) -> Result<Self> {
-        let object_store: Arc<dyn ObjectStore> = if let Some(s3) = config.s3.as_ref() {
-            let s = s3_storage(s3)?;
-            Arc::new(s)
+        let object_store: Arc<dyn ObjectStore> = if let Some(_s3) = config.s3.as_ref() {
+            #[cfg(not(feature = "s3"))]
+            return Err(anyhow!("blob store was built without s3 support"));
+            #[cfg(feature = "s3")]
+            Arc::new(s3_storage(_s3)?)
         } else {
             // If it's not S3, assume it's a file
             let s = file_storage(config.disk.clone().unwrap_or_else(|| DiskStorageConfig {
@@ -143,14 +241,38 @@ impl BlobStorage {
     }
 
     pub async fn put(
+        &self,
+        key: &str,
+        mut data: impl futures::Stream<Item = Result<Bytes>> + Send + Unpin,
+    ) -> Result<PutResult, anyhow::Error> {
+        let Some(threshold) = self.config.inline_threshold_bytes else {
+            return self.put_blob(key, data).await;
+        };
+        let mut buffered = BytesMut::new();
+        while (buffered.len() as u64) < threshold {
+            match data.next().await {
+                Some(chunk) => buffered.extend_from_slice(&chunk?),
+                None => return Ok(inline_put(buffered.freeze())),
+            }
+        }
+        let head = futures::stream::iter([Ok(buffered.freeze())]);
+        self.put_blob(key, head.chain(data)).await
+    }
+
+    async fn put_blob(
         &self,
         key: &str,
         data: impl futures::Stream<Item = Result<Bytes>> + Send + Unpin,
     ) -> Result<PutResult, anyhow::Error> {
         let mut hasher = Sha256::new();
+        let mut head = Vec::new();
         let mut hashed_stream = data.map(|item| {
             item.map(|bytes| {
                 hasher.update(&bytes);
+                if head.len() < content_type::SNIFF_LEN {
+                    let len = bytes.len().min(content_type::SNIFF_LEN - head.len());
+                    head.extend_from_slice(&bytes[..len]);
+                }
                 bytes
             })
         });
@@ -172,6 +294,7 @@ impl BlobStorage {
             url: self.path_url(&path),
             size_bytes,
             sha256_hash: hash,
+            content_type: content_type::sniff(&head).map(str::to_string),
         })
     }
 
@@ -188,15 +311,44 @@ impl BlobStorage {
         }
     }
 
+    pub fn url_prefix(&self) -> String {
+        self.path_url(&object_store::path::Path::from(""))
+    }
+
     pub fn get(&self, key: &str) -> BlobStorageReaderTS {
+        if is_inline(key) {
+            return Arc::new(InlineReader {
+                url: key.to_string(),
+            });
+        }
+
         if key.starts_with("s3://") {
-            let (bucket, key) = parse_s3_url(key)
-                .map_err(|err| anyhow::anyhow!("unable to parse s3 url: {}", err))
-                .unwrap();
-            return Arc::new(S3FileReader::new(bucket, key, &self.config));
+            #[cfg(not(feature = "s3"))]
+            return Arc::new(UnsupportedReader { backend: "s3" });
+            #[cfg(feature = "s3")]
+            {
+                let (bucket, key) = parse_s3_url(key)
+                    .map_err(|err| anyhow::anyhow!("unable to parse s3 url: {}", err))
+                    .unwrap();
+                // Objects of our own bucket are read through the storage's
+                // client instead of building one, and fetching credentials,
+                // for every read.
+                if self
+                    .config
+                    .s3
+                    .as_ref()
+                    .is_some_and(|s3| s3.bucket == bucket)
+                {
+                    return Arc::new(S3FileReader::with_client(self.object_store.clone(), key));
+                }
+                return Arc::new(S3FileReader::new(bucket, key, &self.config));
+            }
         }
 
         if key.starts_with("http") {
+            #[cfg(not(feature = "http"))]
+            return Arc::new(UnsupportedReader { backend: "http" });
+            #[cfg(feature = "http")]
             return Arc::new(http::HttpReader::new(key));
         }
 
@@ -204,7 +356,20 @@ impl BlobStorage {
         Arc::new(DiskFileReader::new(key))
     }
 
+    /// Checks that the underlying object store can be reached. A missing
+    /// object is a successful answer from the store.
+    pub async fn check_reachable(&self) -> Result<()> {
+        let path = object_store::path::Path::from(".indexify_health");
+        match self.object_store.head(&path).await {
+            Ok(_) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(e) => Err(anyhow!("blob storage is unreachable: {}", e)),
+        }
+    }
+
     pub async fn delete(&self, key: &str) -> Result<()> {
+        if is_inline(key) {
+            return Ok(());
+        }
         if let Some(s3) = &self.config.s3 {
             let (bucket, key) = parse_s3_url(key)
                 .map_err(|err| anyhow::anyhow!("unable to parse s3 url: {}", err))?;
@@ -236,6 +401,16 @@ impl BlobStorage {
     }
 }
 
+fn inline_put(bytes: Bytes) -> PutResult {
+    let head = &bytes[..bytes.len().min(content_type::SNIFF_LEN)];
+    PutResult {
+        url: format!("{}{}", INLINE_URL_PREFIX, STANDARD.encode(&bytes)),
+        size_bytes: bytes.len() as u64,
+        sha256_hash: format!("{:x}", Sha256::digest(&bytes)),
+        content_type: content_type::sniff(head).map(str::to_string),
+    }
+}
+
 fn parse_s3_url(s3_url: &str) -> Result<(&str, &str), &str> {
     let Some(("s3", url)) = s3_url.split_once("://") else {
         return Err("Invalid S3 URL format");
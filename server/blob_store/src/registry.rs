@@ -0,0 +1,225 @@
+use std::{
+    collections::HashMap,
+    path::{Component, Path},
+    sync::{Arc, RwLock},
+};
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+
+use super::{BlobStorage, BlobStorageConfig, BlobStorageReaderTS};
+
+/// Blob storages of namespaces with their own storage placement. Namespaces
+/// without one use the default storage.
+pub struct BlobStorageRegistry {
+    default: Arc<BlobStorage>,
+    namespaces: RwLock<HashMap<String, Arc<BlobStorage>>>,
+}
+
+impl BlobStorageRegistry {
+    pub fn new(default: Arc<BlobStorage>) -> Self {
+        Self {
+            default,
+            namespaces: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Inlining and cloud credentials are server-wide settings, so namespace
+    /// storages take the threshold and the S3 credentials of the default
+    /// storage.
+    pub fn register(&self, namespace: &str, config: BlobStorageConfig) -> Result<()> {
+        let storage = self.build(config)?;
+        self.insert(namespace, storage);
+        Ok(())
+    }
+
+    /// Builds the storage of a namespace placement without registering it,
+    /// so that a placement can be checked before it's persisted.
+    pub fn build(&self, mut config: BlobStorageConfig) -> Result<Arc<BlobStorage>> {
+        config.inline_threshold_bytes = self.default.config.inline_threshold_bytes;
+        if let (Some(s3), Some(default_s3)) = (config.s3.as_mut(), &self.default.config.s3) {
+            s3.credentials = default_s3.credentials.clone();
+        }
+        Ok(Arc::new(BlobStorage::new(config)?))
+    }
+
+    pub fn insert(&self, namespace: &str, storage: Arc<BlobStorage>) {
+        self.namespaces
+            .write()
+            .unwrap()
+            .insert(namespace.to_string(), storage);
+    }
+
+    /// Checks that a disk placement requested through the API lies under one
+    /// of the configured `namespace_disk_roots`. Disk placements are refused
+    /// when no roots are configured.
+    pub fn check_disk_placement(&self, path: &str) -> Result<()> {
+        let path = Path::new(path);
+        let normal = path.is_absolute() &&
+            path.components()
+                .all(|c| !matches!(c, Component::ParentDir | Component::CurDir));
+        let allowed = normal &&
+            self.default
+                .config
+                .namespace_disk_roots
+                .iter()
+                .any(|root| path.starts_with(root) && path != Path::new(root));
+        if !allowed {
+            return Err(anyhow!(
+                "disk placements must be absolute paths under one of blob_storage.namespace_disk_roots"
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn default_storage(&self) -> Arc<BlobStorage> {
+        self.default.clone()
+    }
+
+    pub fn namespace_storages(&self) -> Vec<(String, Arc<BlobStorage>)> {
+        self.namespaces
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(namespace, storage)| (namespace.clone(), storage.clone()))
+            .collect()
+    }
+
+    /// S3 bucket of the namespace's own placement, if it has one.
+    pub fn placement_bucket(&self, namespace: &str) -> Option<String> {
+        let storage = self.namespaces.read().unwrap().get(namespace).cloned()?;
+        storage.config.s3.as_ref().map(|s3| s3.bucket.clone())
+    }
+
+    /// Storage that new blobs of the namespace are written to.
+    pub fn for_namespace(&self, namespace: &str) -> Arc<BlobStorage> {
+        self.namespaces
+            .read()
+            .unwrap()
+            .get(namespace)
+            .cloned()
+            .unwrap_or_else(|| self.default.clone())
+    }
+
+    // Stored blobs are only known by their url, so reads and deletes go to
+    // the storage that produced urls with the same prefix.
+    fn for_url(&self, url: &str) -> Arc<BlobStorage> {
+        self.namespaces
+            .read()
+            .unwrap()
+            .values()
+            .find(|storage| url.starts_with(&storage.url_prefix()))
+            .cloned()
+            .unwrap_or_else(|| self.default.clone())
+    }
+
+    pub fn get(&self, url: &str) -> BlobStorageReaderTS {
+        self.for_url(url).get(url)
+    }
+
+    pub async fn delete(&self, url: &str) -> Result<()> {
+        self.for_url(url).delete(url).await
+    }
+
+    pub async fn read_bytes(&self, url: &str) -> Result<Bytes> {
+        self.for_url(url).read_bytes(url).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+
+    use super::*;
+
+    #[test]
+    fn test_check_disk_placement() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let mut config = BlobStorageConfig::new_disk(temp_dir.path().to_str().unwrap());
+        let registry = BlobStorageRegistry::new(Arc::new(BlobStorage::new(config.clone())?));
+        assert!(registry.check_disk_placement("/srv/tenants/a").is_err());
+
+        config.namespace_disk_roots = vec!["/srv/tenants".to_string()];
+        let registry = BlobStorageRegistry::new(Arc::new(BlobStorage::new(config)?));
+        registry.check_disk_placement("/srv/tenants/a")?;
+        for path in [
+            "/srv/tenants",
+            "/srv/tenants-other/a",
+            "/srv/tenants/../etc",
+            "srv/tenants/a",
+            "/etc",
+        ] {
+            assert!(registry.check_disk_placement(path).is_err(), "{}", path);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_namespace_placement() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let default_path = temp_dir.path().join("default");
+        let eu_path = temp_dir.path().join("eu");
+        let registry = BlobStorageRegistry::new(Arc::new(BlobStorage::new(
+            BlobStorageConfig::new_disk(default_path.to_str().unwrap()),
+        )?));
+        registry.register("eu", BlobStorageConfig::new_disk(eu_path.to_str().unwrap()))?;
+
+        let data = Box::pin(stream::once(async { Ok(Bytes::from("hello")) }));
+        let res = registry.for_namespace("eu").put("key", data).await?;
+        assert!(res
+            .url
+            .starts_with(&format!("file://{}", eu_path.display())));
+        assert_eq!(registry.read_bytes(&res.url).await?, Bytes::from("hello"));
+
+        let data = Box::pin(stream::once(async { Ok(Bytes::from("world")) }));
+        let res = registry.for_namespace("us").put("key", data).await?;
+        assert!(res
+            .url
+            .starts_with(&format!("file://{}", default_path.display())));
+
+        registry.delete(&res.url).await?;
+        assert!(registry.read_bytes(&res.url).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_inline_small_payloads() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let default_path = temp_dir.path().join("default");
+        let eu_path = temp_dir.path().join("eu");
+        let mut config = BlobStorageConfig::new_disk(default_path.to_str().unwrap());
+        config.inline_threshold_bytes = Some(8);
+        let registry = BlobStorageRegistry::new(Arc::new(BlobStorage::new(config)?));
+        registry.register("eu", BlobStorageConfig::new_disk(eu_path.to_str().unwrap()))?;
+
+        let data = Box::pin(stream::iter([
+            Ok(Bytes::from_static(b"\x1f\x8b")),
+            Ok(Bytes::from("gzip")),
+        ]));
+        let res = registry.for_namespace("eu").put("small", data).await?;
+        assert!(crate::is_inline(&res.url));
+        assert_eq!(res.size_bytes, 6);
+        assert_eq!(res.content_type.as_deref(), Some("application/gzip"));
+        assert_eq!(
+            registry.read_bytes(&res.url).await?,
+            Bytes::from_static(b"\x1f\x8bgzip")
+        );
+        registry.delete(&res.url).await?;
+        assert!(std::fs::read_dir(&eu_path)?.next().is_none());
+
+        let data = Box::pin(stream::iter([
+            Ok(Bytes::from("hello")),
+            Ok(Bytes::from(" world")),
+        ]));
+        let res = registry.for_namespace("eu").put("large", data).await?;
+        assert!(res
+            .url
+            .starts_with(&format!("file://{}", eu_path.display())));
+        assert_eq!(res.size_bytes, 11);
+        assert_eq!(
+            registry.read_bytes(&res.url).await?,
+            Bytes::from("hello world")
+        );
+        Ok(())
+    }
+}
@@ -8,7 +8,33 @@ use object_store::{aws::AmazonS3Builder, ObjectStore};
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::UnboundedReceiverStream;
 
-use super::{BlobStorageConfig, BlobStorageReader};
+use super::{
+    credentials::WebIdentityCredentialProvider,
+    BlobStorageConfig,
+    BlobStorageReader,
+    S3Config,
+    S3Credentials,
+};
+
+/// Builder for clients of the configured bucket region and credentials.
+/// Credentials other than static keys are refreshed by the client before
+/// they expire, so a client can be kept for the life of the server.
+pub fn s3_builder(s3: &S3Config) -> AmazonS3Builder {
+    let builder = AmazonS3Builder::from_env().with_region(s3.region.as_str());
+    match &s3.credentials {
+        S3Credentials::Environment => builder,
+        S3Credentials::WebIdentity {
+            role_arn,
+            token_file,
+            session_name,
+        } => builder.with_credentials(Arc::new(WebIdentityCredentialProvider::new(
+            &s3.region,
+            role_arn,
+            token_file,
+            session_name.as_deref(),
+        ))),
+    }
+}
 
 pub struct S3FileReader {
     client: Arc<dyn ObjectStore>,
@@ -17,10 +43,10 @@ pub struct S3FileReader {
 
 impl S3FileReader {
     pub fn new(bucket: &str, key: &str, config: &BlobStorageConfig) -> Self {
-        let mut builder = AmazonS3Builder::from_env();
-        if let Some(s3) = &config.s3 {
-            builder = builder.with_region(&s3.region);
-        }
+        let mut builder = match &config.s3 {
+            Some(s3) => s3_builder(s3),
+            None => AmazonS3Builder::from_env(),
+        };
 
         // For supporting localstack/minio for testing
         if let Ok(val) = env::var("AWS_ENDPOINT_URL") {
@@ -30,8 +56,14 @@ impl S3FileReader {
             }
         }
         let client = builder.with_bucket_name(bucket).build().unwrap();
+        Self::with_client(Arc::new(client), key)
+    }
+
+    /// Reader sharing an existing client, and with it its cached
+    /// credentials.
+    pub fn with_client(client: Arc<dyn ObjectStore>, key: &str) -> Self {
         S3FileReader {
-            client: Arc::new(client),
+            client,
             key: key.to_string(),
         }
     }
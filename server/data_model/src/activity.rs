@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ExecutorId, GraphVersion};
+
+/// Notable event shown in the activity feed of a namespace. Events of the
+/// whole server, e.g. an executor joining, aren't tied to a namespace and
+/// show up in the feed of every namespace.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ActivityEvent {
+    pub id: String,
+    pub namespace: Option<String>,
+    pub recorded_at: u64,
+    pub activity: Activity,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Activity {
+    GraphRegistered {
+        compute_graph: String,
+        version: GraphVersion,
+    },
+    InvocationStarted {
+        compute_graph: String,
+        invocation_id: String,
+    },
+    InvocationFinished {
+        compute_graph: String,
+        invocation_id: String,
+    },
+    ExecutorJoined {
+        executor_id: ExecutorId,
+    },
+    /// The state store went over or back under one of its disk limits.
+    DiskQuota {
+        message: String,
+    },
+}
+
+impl ActivityEvent {
+    // <namespace>|<recorded_at>|<id>, with an empty namespace for the events
+    // of the whole server.
+    pub fn key(&self) -> String {
+        format!(
+            "{}{:020}|{}",
+            Self::key_prefix(self.namespace.as_deref()),
+            self.recorded_at,
+            self.id
+        )
+    }
+
+    pub fn key_prefix(namespace: Option<&str>) -> String {
+        format!("{}|", namespace.unwrap_or_default())
+    }
+}
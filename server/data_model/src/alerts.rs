@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+
+use crate::TaskId;
+
+/// Someone to notify about the problems of a graph.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GraphOwner {
+    pub contact: OwnerContact,
+    /// Severities the owner is notified of, all of them when empty.
+    #[serde(default)]
+    pub severities: Vec<Severity>,
+}
+
+impl GraphOwner {
+    pub fn receives(&self, severity: Severity) -> bool {
+        self.severities.is_empty() || self.severities.contains(&severity)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OwnerContact {
+    Email { address: String },
+    Webhook { url: String },
+    Slack { channel: String },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Warning,
+    Critical,
+}
+
+/// A problem of a graph waiting to be routed to its owners. Alerts are
+/// keyed by their id, which is monotonic, and removed once delivered.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Alert {
+    pub id: String,
+    pub namespace: String,
+    pub compute_graph: String,
+    pub severity: Severity,
+    pub raised_at: u64,
+    pub problem: Problem,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Problem {
+    TaskFailed {
+        invocation_id: String,
+        compute_fn: String,
+        task_id: TaskId,
+    },
+    OutputChecksViolated {
+        invocation_id: String,
+        compute_fn: String,
+        task_id: TaskId,
+        violations: usize,
+    },
+}
+
+impl Alert {
+    pub fn summary(&self) -> String {
+        let graph = format!("{}/{}", self.namespace, self.compute_graph);
+        match &self.problem {
+            Problem::TaskFailed {
+                invocation_id,
+                compute_fn,
+                task_id,
+            } => format!(
+                "task {} of {} failed in invocation {} of graph {}",
+                task_id, compute_fn, invocation_id, graph
+            ),
+            Problem::OutputChecksViolated {
+                invocation_id,
+                compute_fn,
+                task_id,
+                violations,
+            } => format!(
+                "{} outputs of task {} of {} violated their checks in invocation {} of graph {}",
+                violations, task_id, compute_fn, invocation_id, graph
+            ),
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ExecutorId, GraphVersion, Task, TaskErrorClass, TaskId, TaskOutcome};
+
+/// Finished task or invocation, kept in the state store until it's exported
+/// to the analytics store.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnalyticsRecord {
+    Task(TaskRecord),
+    Invocation(InvocationRecord),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskRecord {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub graph_version: GraphVersion,
+    pub invocation_id: String,
+    pub compute_fn: String,
+    pub task_id: TaskId,
+    pub executor_id: ExecutorId,
+    pub outcome: TaskOutcome,
+    pub error_class: Option<TaskErrorClass>,
+    pub created_at: u64,
+    pub finished_at: u64,
+    pub duration_ms: u64,
+    pub cpu_seconds: Option<f64>,
+    pub peak_memory_bytes: Option<u64>,
+    pub gpu_seconds: Option<f64>,
+}
+
+impl TaskRecord {
+    /// Record of a task whose outcome was just set, times are in
+    /// milliseconds since the epoch.
+    pub fn new(task: &Task, executor_id: &ExecutorId, finished_at: u64) -> Self {
+        let created_at = task
+            .creation_time
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        let diagnostics = task.diagnostics.as_ref();
+        let usage = diagnostics.and_then(|d| d.resource_usage.as_ref());
+        Self {
+            namespace: task.namespace.clone(),
+            compute_graph: task.compute_graph_name.clone(),
+            graph_version: task.graph_version,
+            invocation_id: task.invocation_id.clone(),
+            compute_fn: task.compute_fn_name.clone(),
+            task_id: task.id.clone(),
+            executor_id: executor_id.clone(),
+            outcome: task.outcome.clone(),
+            error_class: diagnostics
+                .and_then(|d| d.failure.as_ref())
+                .map(|f| f.error_class.clone()),
+            created_at,
+            finished_at,
+            duration_ms: finished_at.saturating_sub(created_at),
+            cpu_seconds: usage.map(|u| u.cpu_seconds),
+            peak_memory_bytes: usage.map(|u| u.peak_memory_bytes),
+            gpu_seconds: usage.map(|u| u.gpu_seconds),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InvocationRecord {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub graph_version: GraphVersion,
+    pub invocation_id: String,
+    pub created_at: u64,
+    pub finished_at: u64,
+    pub duration_ms: u64,
+    pub successful_tasks: u64,
+    pub failed_tasks: u64,
+}
@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+
+use crate::DataPayload;
+
+/// A named version of a model or another artifact a function loads, e.g.
+/// weights, which executors fetch ahead of running the function's tasks.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Artifact {
+    pub namespace: String,
+    pub name: String,
+    pub version: String,
+    pub payload: DataPayload,
+    pub created_at: u64,
+}
+
+impl Artifact {
+    pub fn key(&self) -> String {
+        Artifact::key_from(&self.namespace, &self.name, &self.version)
+    }
+
+    pub fn key_from(namespace: &str, name: &str, version: &str) -> String {
+        format!("{}|{}|{}", namespace, name, version)
+    }
+
+    pub fn key_prefix(namespace: &str) -> String {
+        format!("{}|", namespace)
+    }
+
+    pub fn reference(&self) -> ArtifactRef {
+        ArtifactRef {
+            name: self.name.clone(),
+            version: self.version.clone(),
+        }
+    }
+}
+
+/// An artifact of the graph's namespace a function needs.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct ArtifactRef {
+    pub name: String,
+    pub version: String,
+}
+
+impl ArtifactRef {
+    pub fn key(&self, namespace: &str) -> String {
+        Artifact::key_from(namespace, &self.name, &self.version)
+    }
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ExecutorId, TaskId};
+
+/// A manual change to a task made by an operator, e.g. to get a stuck task
+/// going again.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TaskIntervention {
+    /// Takes the task off its executor so that it's placed again.
+    Requeue,
+    /// Allocates the task to an executor, bypassing placement.
+    Assign {
+        executor_id: ExecutorId,
+    },
+    SetPriority {
+        priority: i32,
+    },
+    /// Finishes the task as failed without running it further.
+    Fail,
+}
+
+/// Record of an intervention in the audit log of a namespace.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditLogEntry {
+    pub id: String,
+    pub namespace: String,
+    pub recorded_at: u64,
+    pub compute_graph: String,
+    pub invocation_id: String,
+    pub compute_fn: String,
+    pub task_id: TaskId,
+    pub intervention: TaskIntervention,
+    /// Executor the task was allocated to before the intervention.
+    pub executor_id: Option<ExecutorId>,
+    pub reason: Option<String>,
+}
+
+impl AuditLogEntry {
+    pub fn key(&self) -> String {
+        format!("{}|{:020}|{}", self.namespace, self.recorded_at, self.id)
+    }
+
+    pub fn key_prefix(namespace: &str) -> String {
+        format!("{}|", namespace)
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ComputeGraph, GraphVersion, TaskOutcome};
+
+/// Rollout of a new version of a compute graph to a share of its new
+/// invocations, the others keep running the stable version. Invocations
+/// which started on the new version run the stable one once the rollout is
+/// rolled back, like invocations do when a graph is updated.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GraphCanary {
+    /// New version of the graph.
+    pub graph: ComputeGraph,
+    /// Share of new invocations which run the new version, in percent.
+    pub percent: u32,
+    pub started_at: u64,
+    pub stable: VersionStats,
+    pub canary: VersionStats,
+}
+
+impl GraphCanary {
+    pub fn new(
+        graph: ComputeGraph,
+        stable_version: GraphVersion,
+        percent: u32,
+        started_at: u64,
+    ) -> Self {
+        Self {
+            stable: VersionStats::new(stable_version),
+            canary: VersionStats::new(graph.version),
+            graph,
+            percent,
+            started_at,
+        }
+    }
+
+    pub fn key(&self) -> String {
+        ComputeGraph::key_from(&self.graph.namespace, &self.graph.name)
+    }
+
+    /// Whether the invocation runs the new version. Invocations are split by
+    /// a hash of their id so that the same share of them is routed whatever
+    /// their arrival order.
+    pub fn routes(&self, invocation_id: &str) -> bool {
+        // FNV-1a, stable across releases unlike the standard library's hasher.
+        let hash = invocation_id
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            });
+        hash % 100 < self.percent as u64
+    }
+
+    pub fn stats_mut(&mut self, version: GraphVersion) -> Option<&mut VersionStats> {
+        if version == self.canary.version {
+            Some(&mut self.canary)
+        } else if version == self.stable.version {
+            Some(&mut self.stable)
+        } else {
+            None
+        }
+    }
+}
+
+/// Outcomes and durations of the invocations of a version of a graph which
+/// finished since its rollout started.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VersionStats {
+    pub version: GraphVersion,
+    pub invocations: u64,
+    /// Invocations with at least one failed task.
+    pub failed_invocations: u64,
+    pub invocation_duration_ms: u64,
+    pub functions: HashMap<String, FnVersionStats>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct FnVersionStats {
+    pub successful_tasks: u64,
+    pub failed_tasks: u64,
+    pub task_duration_ms: u64,
+}
+
+impl VersionStats {
+    pub fn new(version: GraphVersion) -> Self {
+        Self {
+            version,
+            invocations: 0,
+            failed_invocations: 0,
+            invocation_duration_ms: 0,
+            functions: HashMap::new(),
+        }
+    }
+
+    pub fn task_finished(&mut self, compute_fn: &str, outcome: &TaskOutcome, duration_ms: u64) {
+        let stats = self.functions.entry(compute_fn.to_string()).or_default();
+        match outcome {
+            TaskOutcome::Success => stats.successful_tasks += 1,
+            TaskOutcome::Failure => stats.failed_tasks += 1,
+            TaskOutcome::Unknown => return,
+        }
+        stats.task_duration_ms += duration_ms;
+    }
+
+    pub fn invocation_finished(&mut self, failed: bool, duration_ms: u64) {
+        self.invocations += 1;
+        if failed {
+            self.failed_invocations += 1;
+        }
+        self.invocation_duration_ms += duration_ms;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_objects::tests::mock_graph_a;
+
+    #[test]
+    fn test_routes_share_of_invocations() {
+        let mut graph = mock_graph_a();
+        graph.version = GraphVersion::from(2);
+        let mut canary = GraphCanary::new(graph, GraphVersion::from(1), 20, 0);
+        let ids = (0..1000)
+            .map(|i| format!("invocation-{}", i))
+            .collect::<Vec<_>>();
+        let routed = ids.iter().filter(|id| canary.routes(id)).count();
+        assert!((150..250).contains(&routed), "routed {}", routed);
+        // Raising the share keeps the invocations routed before.
+        let routed_before = ids
+            .iter()
+            .filter(|id| canary.routes(id))
+            .cloned()
+            .collect::<Vec<_>>();
+        canary.percent = 50;
+        assert!(routed_before.iter().all(|id| canary.routes(id)));
+        canary.percent = 0;
+        assert!(!ids.iter().any(|id| canary.routes(id)));
+
+        canary
+            .stats_mut(GraphVersion::from(2))
+            .unwrap()
+            .task_finished("fn_a", &TaskOutcome::Failure, 10);
+        assert_eq!(canary.canary.functions["fn_a"].failed_tasks, 1);
+        assert!(canary.stats_mut(GraphVersion::from(3)).is_none());
+    }
+}
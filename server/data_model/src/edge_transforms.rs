@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Reshapes the JSON outputs of a function before the function at the other
+/// end of one of its edges reads them, instead of running a glue function.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EdgeTransform {
+    pub source: String,
+    pub target: String,
+    /// Applied in order, every step sees the value produced by the previous
+    /// one.
+    pub steps: Vec<TransformStep>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransformStep {
+    /// Replaces the value with the one found at a JSON pointer into it.
+    Select { pointer: String },
+    /// Keeps only the listed fields of an object.
+    Pick { fields: Vec<String> },
+    /// Renames fields of an object, from the key to the value.
+    Rename { fields: HashMap<String, String> },
+    /// Sets fields of an object to constants, replacing existing ones.
+    Set { fields: HashMap<String, Value> },
+}
+
+impl EdgeTransform {
+    pub fn apply(&self, value: Value) -> Result<Value> {
+        self.steps
+            .iter()
+            .try_fold(value, |value, step| step.apply(value))
+    }
+}
+
+impl TransformStep {
+    pub fn apply(&self, value: Value) -> Result<Value> {
+        match self {
+            TransformStep::Select { pointer } => value
+                .pointer(pointer)
+                .cloned()
+                .ok_or_else(|| anyhow!("no value at \"{}\"", pointer)),
+            TransformStep::Pick { fields } => {
+                let mut object = into_object(value, "pick")?;
+                object.retain(|name, _| fields.contains(name));
+                Ok(Value::Object(object))
+            }
+            TransformStep::Rename { fields } => {
+                let mut object = into_object(value, "rename")?;
+                let renamed = fields
+                    .iter()
+                    .filter_map(|(from, to)| object.remove(from).map(|v| (to.clone(), v)))
+                    .collect::<Vec<_>>();
+                object.extend(renamed);
+                Ok(Value::Object(object))
+            }
+            TransformStep::Set { fields } => {
+                let mut object = into_object(value, "set")?;
+                object.extend(fields.iter().map(|(name, v)| (name.clone(), v.clone())));
+                Ok(Value::Object(object))
+            }
+        }
+    }
+}
+
+fn into_object(value: Value, step: &str) -> Result<serde_json::Map<String, Value>> {
+    match value {
+        Value::Object(object) => Ok(object),
+        _ => Err(anyhow!("{} needs an object", step)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_edge_transform() -> Result<()> {
+        let transform: EdgeTransform = serde_json::from_value(json!({
+            "source": "fn_a",
+            "target": "fn_b",
+            "steps": [
+                {"type": "select", "pointer": "/result"},
+                {"type": "pick", "fields": ["text", "lang"]},
+                {"type": "rename", "fields": {"text": "content"}},
+                {"type": "set", "fields": {"source": "fn_a"}},
+            ],
+        }))?;
+        let output = json!({
+            "result": {"text": "hello", "lang": "en", "tokens": 1},
+            "elapsed_ms": 3,
+        });
+        assert_eq!(
+            transform.apply(output)?,
+            json!({"content": "hello", "lang": "en", "source": "fn_a"})
+        );
+        assert!(transform.apply(json!({"result": [1, 2]})).is_err());
+        assert!(transform.apply(json!({"elapsed_ms": 3})).is_err());
+        Ok(())
+    }
+}
@@ -96,6 +96,23 @@ impl Expression {
         }
         Err(anyhow::anyhow!("Invalid filter: {}", str))
     }
+
+    pub fn matches(&self, values: &HashMap<String, Value>) -> bool {
+        let Some(value) = values.get(&self.key) else {
+            return false;
+        };
+        match partial_cmp(value, &self.value) {
+            Some(ordering) => match self.operator {
+                Operator::Eq => ordering == Ordering::Equal,
+                Operator::Neq => ordering != Ordering::Equal,
+                Operator::Gt => ordering == Ordering::Greater,
+                Operator::Lt => ordering == Ordering::Less,
+                Operator::GtEq => ordering != Ordering::Less,
+                Operator::LtEq => ordering != Ordering::Greater,
+            },
+            None => false,
+        }
+    }
 }
 
 impl Display for Expression {
@@ -126,23 +143,12 @@ impl LabelsFilter {
     }
 
     pub fn matches(&self, values: &HashMap<String, Value>) -> bool {
-        self.0.iter().all(|expr| {
-            let value = values.get(&expr.key);
-            match value {
-                Some(value) => match partial_cmp(value, &expr.value) {
-                    Some(ordering) => match expr.operator {
-                        Operator::Eq => ordering == std::cmp::Ordering::Equal,
-                        Operator::Neq => ordering != std::cmp::Ordering::Equal,
-                        Operator::Gt => ordering == std::cmp::Ordering::Greater,
-                        Operator::Lt => ordering == std::cmp::Ordering::Less,
-                        Operator::GtEq => ordering != std::cmp::Ordering::Less,
-                        Operator::LtEq => ordering != std::cmp::Ordering::Greater,
-                    },
-                    None => false,
-                },
-                None => false,
-            }
-        })
+        self.0.iter().all(|expr| expr.matches(values))
+    }
+
+    /// The expressions the values don't satisfy.
+    pub fn unmet(&self, values: &HashMap<String, Value>) -> Vec<&Expression> {
+        self.0.iter().filter(|expr| !expr.matches(values)).collect()
     }
 }
 
@@ -0,0 +1,58 @@
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::{anyhow, Result};
+use ulid::Generator;
+
+/// Produces ids for invocations and tasks.
+pub trait IdGenerator: Send + Sync {
+    fn generate(&self) -> String;
+}
+
+/// Generates monotonic ULIDs so that keys ending in an id sort in creation
+/// order, even for ids created within the same millisecond.
+#[derive(Default)]
+pub struct UlidGenerator {
+    generator: Mutex<Generator>,
+}
+
+impl IdGenerator for UlidGenerator {
+    fn generate(&self) -> String {
+        let mut generator = self.generator.lock().unwrap();
+        match generator.generate() {
+            Ok(ulid) => ulid.to_string(),
+            // Random bits overflowed within the same millisecond
+            Err(_) => ulid::Ulid::new().to_string(),
+        }
+    }
+}
+
+static ID_GENERATOR: OnceLock<Box<dyn IdGenerator>> = OnceLock::new();
+
+/// Replaces the default ULID generator. Must be called before the first id
+/// is generated.
+pub fn set_id_generator(generator: Box<dyn IdGenerator>) -> Result<()> {
+    ID_GENERATOR
+        .set(generator)
+        .map_err(|_| anyhow!("id generator is already initialized"))
+}
+
+pub fn new_id() -> String {
+    ID_GENERATOR
+        .get_or_init(|| Box::new(UlidGenerator::default()))
+        .generate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ulids_are_time_ordered() {
+        let generator = UlidGenerator::default();
+        let ids: Vec<String> = (0..100).map(|_| generator.generate()).collect();
+        let mut sorted = ids.clone();
+        sorted.sort();
+        assert_eq!(ids, sorted);
+        assert_eq!(ids[0].len(), 26);
+    }
+}
@@ -1,5 +1,18 @@
+pub mod activity;
+pub mod alerts;
+pub mod analytics;
+pub mod artifacts;
+pub mod audit;
+pub mod canary;
+pub mod edge_transforms;
 pub mod filter;
+pub mod id_generator;
+pub mod near_duplicates;
+pub mod output_checks;
+pub mod provenance;
+pub mod subscriptions;
 pub mod test_objects;
+pub mod validation;
 
 use std::{
     collections::HashMap,
@@ -8,10 +21,17 @@ use std::{
     time::{SystemTime, UNIX_EPOCH},
 };
 
+use alerts::GraphOwner;
 use anyhow::{anyhow, Result};
+use artifacts::ArtifactRef;
 use derive_builder::Builder;
+use edge_transforms::EdgeTransform;
 use filter::LabelsFilter;
-use indexify_utils::default_creation_time;
+use id_generator::new_id;
+use indexify_utils::{default_creation_time, get_epoch_time_in_ms};
+use near_duplicates::NearDuplicatePolicy;
+use output_checks::{OutputCheck, OutputCheckViolation};
+use provenance::InvocationProvenance;
 use serde::{Deserialize, Serialize};
 
 // Invoke graph for all existing payloads
@@ -97,12 +117,68 @@ pub struct ComputeFn {
     pub reducer: bool,
     pub payload_encoder: String,
     pub image_name: String,
+    /// Number of tasks of the function expected to run at once during a
+    /// burst, used as a capacity hint for provisioning executors.
+    #[serde(default)]
+    pub expected_concurrency: Option<u32>,
+    #[serde(default)]
+    pub gpu: Option<GpuRequest>,
+    #[serde(default)]
+    pub external_dependency: Option<ExternalDependency>,
+    #[serde(default)]
+    pub output_checks: Vec<OutputCheck>,
+    /// Content types of the inputs the function runs on, e.g.
+    /// `application/pdf` or `image/*`. Inputs of other or unknown types
+    /// skip the function. Runs on every input when empty.
+    #[serde(default)]
+    pub input_content_types: Vec<String>,
+    /// Artifacts of the namespace the function loads, prefetched by the
+    /// executors which can run it.
+    #[serde(default)]
+    pub artifacts: Vec<ArtifactRef>,
 }
 
 impl ComputeFn {
     pub fn matches_executor(&self, executor: &ExecutorMetadata) -> bool {
         self.placement_constraints.matches(&executor.labels)
     }
+
+    pub fn accepts_content_type(&self, content_type: Option<&str>) -> bool {
+        if self.input_content_types.is_empty() {
+            return true;
+        }
+        let Some(content_type) = content_type else {
+            return false;
+        };
+        // Parameters such as the charset don't take part in matching.
+        let content_type = content_type.split(';').next().unwrap_or_default().trim();
+        let (kind, _) = content_type.split_once('/').unwrap_or((content_type, ""));
+        self.input_content_types.iter().any(|pattern| {
+            pattern == "*/*" ||
+                pattern.eq_ignore_ascii_case(content_type) ||
+                pattern
+                    .strip_suffix("/*")
+                    .is_some_and(|pattern_kind| pattern_kind.eq_ignore_ascii_case(kind))
+        })
+    }
+}
+
+/// Share of a single GPU device a task of a function needs. Fractions below
+/// one let several tasks, e.g. on MIG slices, be packed onto the same device.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+pub struct GpuRequest {
+    pub fraction: f64,
+    pub memory_bytes: u64,
+}
+
+/// A downstream service, e.g. a model API, called by functions of any graph.
+/// Tasks of all functions naming the same dependency share one cluster-wide
+/// count of concurrent calls, which each function keeps within the budget it
+/// declares.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct ExternalDependency {
+    pub name: String,
+    pub max_concurrent_calls: u32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -126,6 +202,13 @@ impl Node {
         }
     }
 
+    pub fn payload_encoder(&self) -> &str {
+        match self {
+            Node::Router(router) => &router.payload_encoder,
+            Node::Compute(compute) => &compute.payload_encoder,
+        }
+    }
+
     pub fn matches_executor(&self, executor: &ExecutorMetadata) -> bool {
         match self {
             Node::Router(_) => true,
@@ -139,6 +222,34 @@ impl Node {
             Node::Compute(compute) => compute.reducer,
         }
     }
+
+    pub fn gpu(&self) -> Option<GpuRequest> {
+        match self {
+            Node::Router(_) => None,
+            Node::Compute(compute) => compute.gpu,
+        }
+    }
+
+    pub fn external_dependency(&self) -> Option<&ExternalDependency> {
+        match self {
+            Node::Router(_) => None,
+            Node::Compute(compute) => compute.external_dependency.as_ref(),
+        }
+    }
+
+    pub fn accepts_content_type(&self, content_type: Option<&str>) -> bool {
+        match self {
+            Node::Router(_) => true,
+            Node::Compute(compute) => compute.accepts_content_type(content_type),
+        }
+    }
+
+    pub fn artifacts(&self) -> &[ArtifactRef] {
+        match self {
+            Node::Router(_) => &[],
+            Node::Compute(compute) => &compute.artifacts,
+        }
+    }
 }
 
 impl Node {
@@ -206,6 +317,18 @@ impl GraphVersion {
     }
 }
 
+impl From<u32> for GraphVersion {
+    fn from(version: u32) -> Self {
+        Self(version)
+    }
+}
+
+impl fmt::Display for GraphVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 impl Default for GraphVersion {
     fn default() -> Self {
         Self(1)
@@ -223,11 +346,113 @@ pub struct ComputeGraph {
     pub start_fn: Node,
     pub nodes: HashMap<String, Node>,
     pub edges: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub edge_transforms: Vec<EdgeTransform>,
+    /// Key-value pairs graphs can be listed by, e.g. team=ml.
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    /// Notified of the failures of the graph, instead of the server's
+    /// default channel.
+    #[serde(default)]
+    pub owners: Vec<GraphOwner>,
+    /// How long the outputs of the graph's invocations are kept.
+    #[serde(default)]
+    pub output_retention: OutputRetention,
+}
+
+/// How long the outputs of a graph's finished invocations are kept, counted
+/// from the creation of the invocation. Outputs are kept forever when unset.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct OutputRetention {
+    /// Outputs of the functions the graph ends with.
+    pub final_outputs_secs: Option<u64>,
+    /// Outputs of every other function.
+    pub intermediate_outputs_secs: Option<u64>,
+}
+
+impl OutputRetention {
+    pub fn is_set(&self) -> bool {
+        self.final_outputs_secs.is_some() || self.intermediate_outputs_secs.is_some()
+    }
+
+    /// Kinds of outputs of an invocation created at `created_at` which are
+    /// expired at `now_ms`.
+    pub fn expired(&self, created_at: u64, now_ms: u64) -> ExpiredOutputs {
+        let expired = |secs: Option<u64>| {
+            secs.is_some_and(|secs| created_at.saturating_add(secs * 1000) <= now_ms)
+        };
+        ExpiredOutputs {
+            final_outputs: expired(self.final_outputs_secs),
+            intermediate_outputs: expired(self.intermediate_outputs_secs),
+        }
+    }
 }
 
 impl ComputeGraph {
     pub fn key(&self) -> String {
-        format!("{}|{}", self.namespace, self.name)
+        Self::key_from(&self.namespace, &self.name)
+    }
+
+    pub fn key_from(namespace: &str, name: &str) -> String {
+        format!("{}|{}", namespace, name)
+    }
+
+    /// Key of the graph's definition at its version, which sorts by version.
+    pub fn version_key(&self) -> String {
+        Self::version_key_from(&self.namespace, &self.name, self.version)
+    }
+
+    pub fn version_key_from(namespace: &str, name: &str, version: GraphVersion) -> String {
+        format!("{}|{}|{:010}", namespace, name, version.0)
+    }
+
+    pub fn versions_prefix(namespace: &str, name: &str) -> String {
+        format!("{}|{}|", namespace, name)
+    }
+
+    /// Whether the graph runs differently than `other`, which warrants a new
+    /// version.
+    pub fn definition_changed(&self, other: &ComputeGraph) -> bool {
+        self.code.sha256_hash != other.code.sha256_hash ||
+            self.edges != other.edges ||
+            self.nodes != other.nodes ||
+            self.start_fn != other.start_fn ||
+            self.edge_transforms != other.edge_transforms
+    }
+
+    /// Whether the outputs of the function are outputs of the graph, the
+    /// function having no edges out of it.
+    pub fn is_final_fn(&self, compute_fn: &str) -> bool {
+        self.edges
+            .get(compute_fn)
+            .map_or(true, |edges| edges.is_empty())
+    }
+
+    pub fn edge_transform(&self, source: &str, target: &str) -> Option<&EdgeTransform> {
+        self.edge_transforms
+            .iter()
+            .find(|t| t.source == source && t.target == target)
+    }
+
+    pub fn label_key_prefix(ns: &str, label: &str, value: &str) -> String {
+        format!("{}|{}={}|", ns, label, value)
+    }
+
+    // <namespace>|<label>=<value>|<compute_graph>
+    pub fn label_keys(&self) -> Vec<String> {
+        self.labels
+            .iter()
+            .map(|(label, value)| {
+                let prefix = ComputeGraph::label_key_prefix(&self.namespace, label, value);
+                format!("{}{}", prefix, self.name)
+            })
+            .collect()
+    }
+
+    pub fn matches_labels(&self, labels: &HashMap<String, String>) -> bool {
+        labels
+            .iter()
+            .all(|(label, value)| self.labels.get(label) == Some(value))
     }
 }
 
@@ -241,6 +466,27 @@ pub struct DataPayload {
     pub path: String,
     pub size: u64,
     pub sha256_hash: String,
+    /// Detected when the data is ingested. For uploaded files, the content
+    /// type of the file.
+    #[serde(default)]
+    pub content_type: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub enum TaskErrorClass {
+    #[default]
+    Unknown,
+    UserCode,
+    OutOfMemory,
+    Timeout,
+    Infrastructure,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskFailure {
+    pub error_class: TaskErrorClass,
+    pub retryable: bool,
+    pub exit_code: Option<i32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -248,6 +494,105 @@ pub struct TaskDiagnostics {
     pub exception: Option<DataPayload>,
     pub stdout: Option<DataPayload>,
     pub stderr: Option<DataPayload>,
+    #[serde(default)]
+    pub failure: Option<TaskFailure>,
+    #[serde(default)]
+    pub resource_usage: Option<ResourceUsage>,
+    #[serde(default)]
+    pub output_check_violations: Vec<OutputCheckViolation>,
+}
+
+/// Resources consumed by a task, as measured by the executor which ran it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ResourceUsage {
+    pub cpu_seconds: f64,
+    pub peak_memory_bytes: u64,
+    pub gpu_seconds: f64,
+}
+
+// Weight of the latest task in the rolling averages.
+const RESOURCE_USAGE_SMOOTHING: f64 = 0.1;
+
+/// Resource usage of the tasks of a compute graph or of one of its functions.
+/// Totals are kept for chargeback, averages are exponentially weighted so that
+/// they follow changes to the function's code.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ResourceUsageStats {
+    pub tasks: u64,
+    pub total_cpu_seconds: f64,
+    pub total_gpu_seconds: f64,
+    pub avg_cpu_seconds: f64,
+    pub avg_peak_memory_bytes: f64,
+    pub avg_gpu_seconds: f64,
+    pub max_peak_memory_bytes: u64,
+}
+
+impl ResourceUsageStats {
+    pub fn graph_key(namespace: &str, compute_graph: &str) -> String {
+        format!("{}|{}", namespace, compute_graph)
+    }
+
+    pub fn fn_key(namespace: &str, compute_graph: &str, compute_fn: &str) -> String {
+        format!("{}|{}|{}", namespace, compute_graph, compute_fn)
+    }
+
+    pub fn record(&mut self, usage: &ResourceUsage) {
+        let weight = if self.tasks == 0 {
+            1.0
+        } else {
+            RESOURCE_USAGE_SMOOTHING
+        };
+        let average = |avg: f64, value: f64| avg + weight * (value - avg);
+        self.tasks += 1;
+        self.total_cpu_seconds += usage.cpu_seconds;
+        self.total_gpu_seconds += usage.gpu_seconds;
+        self.avg_cpu_seconds = average(self.avg_cpu_seconds, usage.cpu_seconds);
+        self.avg_peak_memory_bytes =
+            average(self.avg_peak_memory_bytes, usage.peak_memory_bytes as f64);
+        self.avg_gpu_seconds = average(self.avg_gpu_seconds, usage.gpu_seconds);
+        self.max_peak_memory_bytes = self.max_peak_memory_bytes.max(usage.peak_memory_bytes);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum TaskRejectionReason {
+    UnsupportedVersion,
+    MissingDependency,
+    OverCapacity,
+    Other,
+}
+
+/// GPU device of the executor a task was placed on and the share of it the
+/// task holds while allocated.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GpuAllocation {
+    pub device_id: String,
+    pub fraction: f64,
+    pub memory_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TaskRejection {
+    pub executor_id: ExecutorId,
+    pub reason: TaskRejectionReason,
+    pub message: Option<String>,
+    pub rejected_at: u64,
+}
+
+impl TaskRejection {
+    /// How long an executor over capacity is kept out of the placements of
+    /// the task. It may have room again afterwards, unlike an executor
+    /// rejecting the task for another reason.
+    pub const OVER_CAPACITY_TTL_MS: u64 = 60_000;
+
+    pub fn is_permanent(&self) -> bool {
+        self.reason != TaskRejectionReason::OverCapacity
+    }
+
+    /// Whether the rejection still keeps the task off its executor.
+    pub fn is_active(&self, now: u64) -> bool {
+        self.is_permanent() || now < self.rejected_at + Self::OVER_CAPACITY_TTL_MS
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -271,6 +616,13 @@ pub struct NodeOutput {
 }
 
 impl NodeOutput {
+    pub fn content_type(&self) -> Option<&str> {
+        match &self.payload {
+            OutputPayload::Fn(payload) => payload.content_type.as_deref(),
+            OutputPayload::Router(_) => None,
+        }
+    }
+
     pub fn key(&self, invocation_id: &str) -> String {
         NodeOutput::key_from(
             &self.namespace,
@@ -344,6 +696,16 @@ impl NodeOutputBuilder {
     }
 }
 
+/// Tracing metadata of the request which invoked a graph, carried by every
+/// task derived from the invocation so that logs across the server and the
+/// executors can be joined.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct TraceContext {
+    pub trace_id: Option<String>,
+    pub parent_span_id: Option<String>,
+    pub correlation_id: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder)]
 #[builder(build_fn(skip))]
 pub struct InvocationPayload {
@@ -351,6 +713,40 @@ pub struct InvocationPayload {
     pub namespace: String,
     pub compute_graph_name: String,
     pub payload: DataPayload,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+    #[serde(default)]
+    pub created_at: u64,
+    #[serde(default)]
+    pub trace: TraceContext,
+    /// Recorded by the server when the invocation starts running.
+    #[serde(default)]
+    pub provenance: Option<InvocationProvenance>,
+    /// Invocation this one reproduces.
+    #[serde(default)]
+    pub reproduced_from: Option<String>,
+    /// Where the input came from, for inputs ingested again when their
+    /// source changes.
+    #[serde(default)]
+    pub source: Option<ContentSource>,
+    /// Invocation with a newer version of the same source.
+    #[serde(default)]
+    pub superseded_by: Option<String>,
+    /// Test run of the graph, its tasks are placed after every other task
+    /// and it's deleted shortly after it finishes or times out.
+    #[serde(default)]
+    pub test: bool,
+    /// Simhash of the input's text, set at ingestion when the namespace
+    /// detects near-duplicates.
+    #[serde(default)]
+    pub fingerprint: Option<u64>,
+    /// Earlier invocation whose input this one's nearly duplicates.
+    #[serde(default)]
+    pub near_duplicate_of: Option<String>,
+    /// Version of the graph the invocation runs, rather than the current one
+    /// or its canary, e.g. to reproduce an earlier invocation.
+    #[serde(default)]
+    pub graph_version: Option<GraphVersion>,
 }
 
 impl InvocationPayload {
@@ -362,6 +758,31 @@ impl InvocationPayload {
         format!("{}|{}|{}", ns, cg, id)
     }
 
+    pub fn tag_key_prefix(ns: &str, cg: &str, tag: &str, value: &str) -> String {
+        format!("{}|{}|{}={}|", ns, cg, tag, value)
+    }
+
+    // <namespace>|<compute_graph>|<tag>=<value>|<invocation_id>
+    pub fn tag_keys(&self) -> Vec<String> {
+        self.tags
+            .iter()
+            .map(|(tag, value)| {
+                let prefix = InvocationPayload::tag_key_prefix(
+                    &self.namespace,
+                    &self.compute_graph_name,
+                    tag,
+                    value,
+                );
+                format!("{}{}", prefix, self.id)
+            })
+            .collect()
+    }
+
+    pub fn matches_tags(&self, tags: &HashMap<String, String>) -> bool {
+        tags.iter()
+            .all(|(tag, value)| self.tags.get(tag) == Some(value))
+    }
+
     pub fn invocation_context_key(&self) -> String {
         format!("{}|{}|{}", self.namespace, self.compute_graph_name, self.id)
     }
@@ -378,21 +799,121 @@ impl InvocationPayloadBuilder {
             .clone()
             .ok_or(anyhow!("compute_graph_name is required"))?;
         let payload = self.payload.clone().ok_or(anyhow!("payload is required"))?;
-        let mut hasher = DefaultHasher::new();
-        ns.hash(&mut hasher);
-        cg_name.hash(&mut hasher);
-        payload.sha256_hash.hash(&mut hasher);
-        payload.path.hash(&mut hasher);
-        let id = format!("{:x}", hasher.finish());
+        let id = self.id.clone().unwrap_or_else(new_id);
+        let tags = self.tags.clone().unwrap_or_default();
+        let created_at = self.created_at.unwrap_or_else(get_epoch_time_in_ms);
+        let trace = self.trace.clone().unwrap_or_default();
         Ok(InvocationPayload {
             id,
             namespace: ns,
             compute_graph_name: cg_name,
             payload,
+            tags,
+            created_at,
+            trace,
+            provenance: self.provenance.clone().flatten(),
+            reproduced_from: self.reproduced_from.clone().flatten(),
+            source: self.source.clone().flatten(),
+            superseded_by: self.superseded_by.clone().flatten(),
+            test: self.test.unwrap_or_default(),
+            fingerprint: self.fingerprint.flatten(),
+            near_duplicate_of: self.near_duplicate_of.clone().flatten(),
+            graph_version: self.graph_version.flatten(),
         })
     }
 }
 
+/// Identity and version of the content an invocation was ingested from,
+/// e.g. a file path along with its modification time.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ContentSource {
+    pub id: String,
+    pub sha256_hash: String,
+    /// Modification time of the source, in milliseconds since the epoch.
+    #[serde(default)]
+    pub modified_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContentChange {
+    /// Newer than the current version or with different content.
+    Newer,
+    Unchanged,
+    /// Older than the current version.
+    Stale,
+}
+
+impl ContentSource {
+    pub fn key(&self, namespace: &str, compute_graph: &str) -> String {
+        format!("{}|{}|{}", namespace, compute_graph, self.id)
+    }
+
+    /// Compares a version of the source with the current one. Modification
+    /// times are compared when both are known, content hashes otherwise.
+    pub fn compare(&self, current: &ContentSource) -> ContentChange {
+        match (self.modified_at, current.modified_at) {
+            (Some(modified_at), Some(current)) if modified_at < current => ContentChange::Stale,
+            (Some(modified_at), Some(current)) if modified_at > current => ContentChange::Newer,
+            _ if self.sha256_hash != current.sha256_hash => ContentChange::Newer,
+            _ => ContentChange::Unchanged,
+        }
+    }
+}
+
+/// Current version of a source of a compute graph's invocations.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ContentVersion {
+    pub invocation_id: String,
+    pub source: ContentSource,
+}
+
+/// An invocation whose input is downloaded from a url. It's recorded when
+/// the invocation is accepted and removed once the graph is invoked with the
+/// downloaded input.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UrlFetch {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub invocation_id: String,
+    pub url: String,
+    pub created_at: u64,
+    /// Why the graph wasn't invoked, the fetch is pending until it's set.
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl UrlFetch {
+    pub fn key(&self) -> String {
+        InvocationPayload::key_from(&self.namespace, &self.compute_graph, &self.invocation_id)
+    }
+}
+
+/// Index entry of an invocation whose records were moved out of the state
+/// store into an archive blob.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ArchivedInvocation {
+    pub namespace: String,
+    pub compute_graph_name: String,
+    pub invocation_id: String,
+    pub url: String,
+    pub created_at: u64,
+    pub archived_at: u64,
+}
+
+impl ArchivedInvocation {
+    pub fn key(&self) -> String {
+        ArchivedInvocation::key_from(
+            &self.namespace,
+            &self.compute_graph_name,
+            &self.invocation_id,
+        )
+    }
+
+    pub fn key_from(ns: &str, cg: &str, id: &str) -> String {
+        format!("{}|{}|{}", ns, cg, id)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Builder)]
 #[builder(build_fn(skip))]
 pub struct GraphInvocationCtx {
@@ -404,6 +925,34 @@ pub struct GraphInvocationCtx {
     pub outstanding_tasks: u64,
     pub fn_task_analytics: HashMap<String, TaskAnalytics>,
     pub is_system_task: bool,
+    /// Keeps the outputs of the invocation whatever the output retention of
+    /// its graph.
+    #[serde(default)]
+    pub outputs_pinned: bool,
+    /// Outputs deleted by the output retention of the graph.
+    #[serde(default)]
+    pub expired_outputs: ExpiredOutputs,
+}
+
+/// Kinds of outputs of an invocation, final or intermediate.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct ExpiredOutputs {
+    pub final_outputs: bool,
+    pub intermediate_outputs: bool,
+}
+
+impl ExpiredOutputs {
+    pub fn any(&self) -> bool {
+        self.final_outputs || self.intermediate_outputs
+    }
+
+    /// The kinds of outputs in `self` which aren't in `other`.
+    pub fn without(&self, other: &ExpiredOutputs) -> ExpiredOutputs {
+        ExpiredOutputs {
+            final_outputs: self.final_outputs && !other.final_outputs,
+            intermediate_outputs: self.intermediate_outputs && !other.intermediate_outputs,
+        }
+    }
 }
 
 impl GraphInvocationCtx {
@@ -448,6 +997,8 @@ impl GraphInvocationCtxBuilder {
             fn_task_analytics,
             outstanding_tasks: 1, // Starts with 1 for the initial state change event
             is_system_task,
+            outputs_pinned: false,
+            expired_outputs: ExpiredOutputs::default(),
         })
     }
 }
@@ -500,13 +1051,68 @@ pub struct Task {
     pub diagnostics: Option<TaskDiagnostics>,
     pub reducer_output_id: Option<String>,
     pub graph_version: GraphVersion,
+    #[serde(default)]
+    pub rejections: Vec<TaskRejection>,
+    #[serde(default)]
+    pub gpu_allocation: Option<GpuAllocation>,
+    /// External dependency the task counts against while it's allocated.
+    #[serde(default)]
+    pub external_dependency: Option<String>,
+    #[serde(default)]
+    pub trace: TraceContext,
+    /// Tasks with a higher priority are placed first.
+    #[serde(default)]
+    pub priority: i32,
+    /// Sequence number acknowledging the outcome reported by the executor
+    /// once it's committed, executors keep their results until they get it.
+    #[serde(default)]
+    pub result_sequence: Option<u64>,
+    /// Set once the outputs of the task are deleted by the output retention
+    /// of its graph.
+    #[serde(default)]
+    pub outputs_expired: bool,
 }
 
+/// Priority of the tasks of test invocations.
+pub const TEST_TASK_PRIORITY: i32 = -100;
+
 impl Task {
+    /// Orders tasks the way they're placed, higher priorities first. Tasks of
+    /// the same priority keep their order.
+    pub fn sort_for_placement(tasks: &mut [Task]) {
+        tasks.sort_by_key(|task| std::cmp::Reverse(task.priority));
+    }
+
     pub fn terminal_state(&self) -> bool {
         self.outcome != TaskOutcome::Unknown
     }
 
+    pub fn rejected_by(&self, executor_id: &ExecutorId, now: u64) -> bool {
+        self.rejections
+            .iter()
+            .any(|r| &r.executor_id == executor_id && r.is_active(now))
+    }
+
+    /// Whether every executor able to run the task has rejected it for good,
+    /// leaving it nowhere to run. A task no executor is able to run waits for
+    /// one to register instead.
+    pub fn rejected_by_all(&self, node: &Node, executors: &[ExecutorMetadata]) -> bool {
+        let able: Vec<&ExecutorMetadata> = executors
+            .iter()
+            .filter(|executor| {
+                executor.image_name == node.image_name() &&
+                    node.matches_executor(executor) &&
+                    (node.gpu().is_none() || !executor.gpus.is_empty())
+            })
+            .collect();
+        !able.is_empty() &&
+            able.iter().all(|executor| {
+                self.rejections
+                    .iter()
+                    .any(|r| r.executor_id == executor.id && r.is_permanent())
+            })
+    }
+
     pub fn key_prefix_for_fn(
         namespace: &str,
         compute_graph: &str,
@@ -593,7 +1199,8 @@ impl TaskBuilder {
             .clone()
             .ok_or(anyhow!("graph version is not present"))?;
         let reducer_output_id = self.reducer_output_id.clone().flatten();
-        let id = uuid::Uuid::new_v4().to_string();
+        let trace = self.trace.clone().unwrap_or_default();
+        let id = new_id();
         let task = Task {
             id: TaskId(id),
             compute_graph_name: cg_name,
@@ -606,6 +1213,13 @@ impl TaskBuilder {
             diagnostics: None,
             reducer_output_id,
             graph_version,
+            rejections: vec![],
+            gpu_allocation: None,
+            external_dependency: None,
+            trace,
+            priority: self.priority.unwrap_or_default(),
+            result_sequence: None,
+            outputs_expired: false,
         };
         Ok(task)
     }
@@ -645,12 +1259,84 @@ pub struct ExecutorMetadata {
     pub image_name: String,
     pub addr: String,
     pub labels: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub gpus: Vec<GpuDevice>,
+    /// Most tasks allocated to the executor at once, unlimited when unset.
+    #[serde(default)]
+    pub capacity: Option<u32>,
+    /// Version of the executor software.
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Hashes of the artifacts the executor has fetched.
+    #[serde(default)]
+    pub artifacts: Vec<String>,
+    /// Most tasks allocated to the executor ahead of their completion, as
+    /// granted by the server from the window the executor asked for.
+    #[serde(default)]
+    pub prefetch_window: Option<u32>,
 }
 
 impl ExecutorMetadata {
     pub fn key(&self) -> String {
         format!("{}", self.id)
     }
+
+    /// Most unfinished tasks allocated to the executor at once, unlimited
+    /// when unset.
+    pub fn max_allocated_tasks(&self) -> Option<u32> {
+        match (self.capacity, self.prefetch_window) {
+            (Some(capacity), Some(window)) => Some(capacity.min(window)),
+            (capacity, window) => capacity.or(window),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct GpuDevice {
+    pub id: String,
+    pub memory_bytes: u64,
+}
+
+/// GPU capacity held by the tasks allocated to a device.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct GpuDeviceUsage {
+    pub fraction: f64,
+    pub memory_bytes: u64,
+    pub tasks: u64,
+}
+
+impl GpuDeviceUsage {
+    // Tolerates rounding when fractions like 1/7 add up to a whole device.
+    const FRACTION_EPSILON: f64 = 1e-9;
+
+    pub fn fits(&self, device: &GpuDevice, request: &GpuRequest) -> bool {
+        self.fraction + request.fraction <= 1.0 + Self::FRACTION_EPSILON &&
+            self.memory_bytes + request.memory_bytes <= device.memory_bytes
+    }
+
+    pub fn add(&mut self, allocation: &GpuAllocation) {
+        self.fraction += allocation.fraction;
+        self.memory_bytes += allocation.memory_bytes;
+        self.tasks += 1;
+    }
+
+    pub fn remove(&mut self, allocation: &GpuAllocation) {
+        self.tasks = self.tasks.saturating_sub(1);
+        self.memory_bytes = self.memory_bytes.saturating_sub(allocation.memory_bytes);
+        // Fractions don't add up back to exactly zero.
+        self.fraction = if self.tasks == 0 {
+            0.0
+        } else {
+            (self.fraction - allocation.fraction).max(0.0)
+        };
+    }
+
+    /// Whether two usages agree, up to the rounding of their fractions.
+    pub fn approx_eq(&self, other: &GpuDeviceUsage) -> bool {
+        self.tasks == other.tasks &&
+            self.memory_bytes == other.memory_bytes &&
+            (self.fraction - other.fraction).abs() <= Self::FRACTION_EPSILON * self.tasks as f64
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -688,6 +1374,8 @@ pub enum ChangeType {
     ExecutorAdded,
     ExecutorRemoved,
     TaskCreated,
+    TaskRejected,
+    TaskRequeued,
 }
 
 impl fmt::Display for ChangeType {
@@ -700,6 +1388,8 @@ impl fmt::Display for ChangeType {
             ChangeType::ExecutorAdded => write!(f, "ExecutorAdded"),
             ChangeType::ExecutorRemoved => write!(f, "ExecutorRemoved"),
             ChangeType::TaskCreated => write!(f, "TaskCreated"),
+            ChangeType::TaskRejected => write!(f, "TaskRejected"),
+            ChangeType::TaskRequeued => write!(f, "TaskRequeued"),
         }
     }
 }
@@ -743,8 +1433,81 @@ pub struct StateChange {
     pub processed_at: Option<u64>,
 }
 
+impl StateChange {
+    /// Namespace whose work the change belongs to, none for changes which
+    /// concern every namespace such as executors joining or leaving.
+    pub fn namespace(&self) -> Option<&str> {
+        match &self.change_type {
+            ChangeType::InvokeComputeGraph(event) => Some(&event.namespace),
+            ChangeType::TaskFinished(event) => Some(&event.namespace),
+            _ => None,
+        }
+    }
+
+    pub fn queue_key(&self) -> Vec<u8> {
+        let mut key = format!("{}|", self.namespace().unwrap_or_default()).into_bytes();
+        key.extend(self.id.to_key());
+        key
+    }
+}
+
+/// Where the blobs of a namespace are stored, when they must not live in the
+/// server's default blob storage.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BlobStoragePlacement {
+    S3 { bucket: String, region: String },
+    Disk { path: String },
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Namespace {
     pub name: String,
     pub created_at: u64,
+    #[serde(default)]
+    pub blob_storage: Option<BlobStoragePlacement>,
+    /// Share of the scheduler's attention the namespace gets relative to
+    /// other namespaces with pending work, defaults to one.
+    #[serde(default)]
+    pub scheduling_weight: Option<u32>,
+    /// Set once the namespace has been marked for deletion, tracks how far
+    /// the deletion has progressed.
+    #[serde(default)]
+    pub deletion: Option<NamespaceDeletion>,
+    #[serde(default)]
+    pub reprocessing_policy: ReprocessingPolicy,
+    /// Detection of near-duplicate text content ingested into the
+    /// namespace's graphs, off when unset.
+    #[serde(default)]
+    pub near_duplicates: Option<NearDuplicatePolicy>,
+}
+
+/// What happens to an invocation when a newer version of its source is
+/// ingested. The newer version always runs the graph again.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ReprocessingPolicy {
+    /// The invocation and its outputs are kept, marked as superseded.
+    #[default]
+    KeepBoth,
+    /// The outputs of the invocation are deleted, the invocation is kept
+    /// marked as superseded.
+    Replace,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NamespaceDeletionStage {
+    /// Dropping pending state changes and deleting the tasks of the namespace.
+    CancelTasks,
+    /// Deleting compute graphs along with their invocations and outputs.
+    DeleteComputeGraphs,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NamespaceDeletion {
+    pub started_at: u64,
+    pub stage: NamespaceDeletionStage,
+    pub tasks_deleted: u64,
+    pub compute_graphs_deleted: u64,
 }
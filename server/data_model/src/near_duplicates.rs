@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+/// Number of consecutive words hashed together into a feature of the text.
+const SHINGLE_LEN: usize = 3;
+
+/// Detection of text content ingested again with trivial differences, e.g.
+/// the same page crawled with a different timestamp in its footer.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct NearDuplicatePolicy {
+    /// Minimum similarity, between 0 and 1, for content to be a
+    /// near-duplicate of content ingested before.
+    pub threshold: f64,
+    /// How long the fingerprints of ingested content are compared against.
+    pub window_secs: u64,
+    #[serde(default)]
+    pub action: NearDuplicateAction,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NearDuplicateAction {
+    /// The graph is invoked, the invocation records what it duplicates.
+    #[default]
+    Tag,
+    /// The graph isn't invoked, ingestion answers with the invocation of
+    /// the earlier content.
+    Skip,
+}
+
+/// Fingerprint of content ingested into a compute graph, kept for the
+/// window of the namespace's near-duplicate policy.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ContentFingerprint {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub invocation_id: String,
+    pub created_at: u64,
+    pub fingerprint: u64,
+}
+
+impl ContentFingerprint {
+    pub fn key(&self) -> String {
+        format!(
+            "{}|{}|{:020}|{}",
+            self.namespace, self.compute_graph, self.created_at, self.invocation_id
+        )
+    }
+
+    pub fn key_prefix(namespace: &str, compute_graph: &str) -> String {
+        format!("{}|{}|", namespace, compute_graph)
+    }
+}
+
+/// 64 bit simhash of the word shingles of a text. Similar texts get
+/// fingerprints that differ in few bits. Text without words has none.
+pub fn simhash(text: &str) -> Option<u64> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    if words.is_empty() {
+        return None;
+    }
+    let mut weights = [0i64; 64];
+    for shingle in words.windows(SHINGLE_LEN.min(words.len())) {
+        let hash = fnv1a(shingle);
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if hash & (1 << bit) != 0 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+    Some(
+        weights
+            .iter()
+            .enumerate()
+            .filter(|(_, weight)| **weight > 0)
+            .fold(0, |fingerprint, (bit, _)| fingerprint | (1 << bit)),
+    )
+}
+
+/// Share of the bits two fingerprints agree on.
+pub fn similarity(a: u64, b: u64) -> f64 {
+    1.0 - (a ^ b).count_ones() as f64 / 64.0
+}
+
+// Fingerprints are persisted, so features are hashed with a function that
+// is stable across builds rather than the std hasher.
+fn fnv1a(words: &[String]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for (i, word) in words.iter().enumerate() {
+        if i > 0 {
+            hash ^= b' ' as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        for byte in word.bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simhash_similarity() {
+        let page = "Indexify is a compute framework for building data-intensive \
+                    generative AI applications. It ingests documents, runs graphs \
+                    of functions over them and stores the outputs for retrieval. \
+                    Graphs are written in Python and deployed to a server which \
+                    schedules their functions on executors, retrying failed tasks \
+                    and keeping track of every output they produce.";
+        let crawled_again = format!("{} Last crawled at 2024-10-01 12:00.", page);
+        let other = "The quick brown fox jumps over the lazy dog while the cat \
+                     sleeps on the warm windowsill in the afternoon sun.";
+
+        let page_fp = simhash(page).unwrap();
+        assert_eq!(simhash(&page.to_uppercase()), Some(page_fp));
+        assert!(similarity(page_fp, simhash(&crawled_again).unwrap()) > 0.8);
+        assert!(similarity(page_fp, simhash(other).unwrap()) < 0.8);
+        assert_eq!(simhash(" -- "), None);
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::NodeOutput;
+
+/// A post-condition on every output of a function, evaluated when the
+/// executor reports the outputs of a task.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OutputCheck {
+    pub name: String,
+    pub condition: OutputCondition,
+    #[serde(default)]
+    pub action: OutputCheckAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputCondition {
+    /// The output is not empty, nor an empty JSON string, array or object.
+    NonEmpty,
+    /// The JSON output matches a schema. Supports the `type`, `enum`,
+    /// `const`, `required`, `properties`, `additionalProperties`, `items`,
+    /// `minimum`, `maximum`, `minLength`, `maxLength`, `minItems` and
+    /// `maxItems` keywords.
+    JsonSchema { schema: Value },
+    /// The number found at a JSON pointer into the output, the whole output
+    /// by default, lies within the bounds.
+    Range {
+        #[serde(default)]
+        pointer: String,
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+}
+
+/// What happens to an output violating a check.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputCheckAction {
+    /// Only count the violation in the function's output check stats.
+    #[default]
+    Record,
+    /// Keep the output out of the graph, downstream functions don't see it.
+    Quarantine,
+    /// Fail the task that produced the output.
+    FailTask,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OutputCheckViolation {
+    pub check: String,
+    pub action: OutputCheckAction,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QuarantinedOutput {
+    pub output: NodeOutput,
+    pub violations: Vec<OutputCheckViolation>,
+    pub quarantined_at: u64,
+}
+
+impl QuarantinedOutput {
+    pub fn key(&self) -> String {
+        self.output.key(&self.output.invocation_id)
+    }
+}
+
+/// Output check results of a function, accumulated over all of its tasks.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct OutputCheckStats {
+    pub outputs_checked: u64,
+    /// Number of violations by check name.
+    pub violations: HashMap<String, u64>,
+    pub quarantined_outputs: u64,
+    pub failed_tasks: u64,
+}
+
+impl OutputCheckStats {
+    pub fn key(namespace: &str, compute_graph: &str, compute_fn: &str) -> String {
+        format!("{}|{}|{}", namespace, compute_graph, compute_fn)
+    }
+}
+
+impl OutputCondition {
+    /// Whether the condition inspects the output as JSON.
+    pub fn needs_json(&self) -> bool {
+        !matches!(self, OutputCondition::NonEmpty)
+    }
+
+    /// Returns why the output violates the condition, if it does.
+    pub fn violation(&self, output: &[u8]) -> Option<String> {
+        if output.is_empty() {
+            return Some("output is empty".to_string());
+        }
+        let value = serde_json::from_slice::<Value>(output);
+        match self {
+            OutputCondition::NonEmpty => match value {
+                Ok(Value::Null) => Some("output is null".to_string()),
+                Ok(Value::String(s)) if s.is_empty() => Some("output is empty".to_string()),
+                Ok(Value::Array(a)) if a.is_empty() => Some("output is empty".to_string()),
+                Ok(Value::Object(o)) if o.is_empty() => Some("output is empty".to_string()),
+                _ => None,
+            },
+            OutputCondition::JsonSchema { schema } => match value {
+                Ok(value) => schema_violation(schema, &value, ""),
+                Err(e) => Some(format!("output is not JSON: {}", e)),
+            },
+            OutputCondition::Range { pointer, min, max } => {
+                let value = match value {
+                    Ok(value) => value,
+                    Err(e) => return Some(format!("output is not JSON: {}", e)),
+                };
+                let Some(number) = value.pointer(pointer).and_then(Value::as_f64) else {
+                    return Some(format!("no number at \"{}\"", pointer));
+                };
+                if min.is_some_and(|min| number < min) || max.is_some_and(|max| number > max) {
+                    return Some(format!("{} at \"{}\" is out of range", number, pointer));
+                }
+                None
+            }
+        }
+    }
+}
+
+fn schema_violation(schema: &Value, value: &Value, path: &str) -> Option<String> {
+    let fail = |message: String| Some(format!("{}: {}", display_path(path), message));
+    let schema = schema.as_object()?;
+    if let Some(expected) = schema.get("type") {
+        let types = match expected {
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            Value::String(t) => vec![t.as_str()],
+            _ => vec![],
+        };
+        if !types.is_empty() && !types.iter().any(|t| has_type(value, t)) {
+            return fail(format!("expected {}", types.join(" or ")));
+        }
+    }
+    if let Some(Value::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(value) {
+            return fail("not one of the allowed values".to_string());
+        }
+    }
+    if let Some(expected) = schema.get("const") {
+        if expected != value {
+            return fail(format!("expected {}", expected));
+        }
+    }
+    match value {
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+                if n < min {
+                    return fail(format!("{} is less than {}", n, min));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+                if n > max {
+                    return fail(format!("{} is greater than {}", n, max));
+                }
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if schema
+                .get("minLength")
+                .and_then(Value::as_u64)
+                .is_some_and(|min| len < min)
+            {
+                return fail("string is too short".to_string());
+            }
+            if schema
+                .get("maxLength")
+                .and_then(Value::as_u64)
+                .is_some_and(|max| len > max)
+            {
+                return fail("string is too long".to_string());
+            }
+        }
+        Value::Array(items) => {
+            let len = items.len() as u64;
+            if schema
+                .get("minItems")
+                .and_then(Value::as_u64)
+                .is_some_and(|min| len < min)
+            {
+                return fail("too few items".to_string());
+            }
+            if schema
+                .get("maxItems")
+                .and_then(Value::as_u64)
+                .is_some_and(|max| len > max)
+            {
+                return fail("too many items".to_string());
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    let path = format!("{}/{}", path, i);
+                    if let Some(violation) = schema_violation(item_schema, item, &path) {
+                        return Some(violation);
+                    }
+                }
+            }
+        }
+        Value::Object(fields) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for field in required.iter().filter_map(Value::as_str) {
+                    if !fields.contains_key(field) {
+                        return fail(format!("missing field {}", field));
+                    }
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (name, field) in fields {
+                let path = format!("{}/{}", path, name);
+                match properties.and_then(|p| p.get(name)) {
+                    Some(field_schema) => {
+                        if let Some(violation) = schema_violation(field_schema, field, &path) {
+                            return Some(violation);
+                        }
+                    }
+                    None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                        return fail(format!("unexpected field {}", name));
+                    }
+                    None => {}
+                }
+            }
+        }
+        _ => {}
+    }
+    None
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        _ => false,
+    }
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() {
+        "/"
+    } else {
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_output_conditions() {
+        let schema = OutputCondition::JsonSchema {
+            schema: json!({
+                "type": "object",
+                "required": ["label", "score"],
+                "properties": {
+                    "label": {"type": "string", "minLength": 1},
+                    "score": {"type": "number", "minimum": 0, "maximum": 1},
+                },
+            }),
+        };
+        assert_eq!(schema.violation(br#"{"label": "cat", "score": 0.5}"#), None);
+        assert_eq!(
+            schema.violation(br#"{"label": "cat", "score": 2}"#),
+            Some("/score: 2 is greater than 1".to_string())
+        );
+        assert_eq!(
+            schema.violation(br#"{"score": 0.5}"#),
+            Some("/: missing field label".to_string())
+        );
+        assert!(schema.violation(b"not json").is_some());
+
+        let range = OutputCondition::Range {
+            pointer: "/score".to_string(),
+            min: Some(0.0),
+            max: Some(1.0),
+        };
+        assert_eq!(range.violation(br#"{"score": 1}"#), None);
+        assert!(range.violation(br#"{"score": -1}"#).is_some());
+        assert!(range.violation(br#"{"label": "cat"}"#).is_some());
+
+        assert!(OutputCondition::NonEmpty.violation(b"").is_some());
+        assert!(OutputCondition::NonEmpty.violation(b"[]").is_some());
+        assert_eq!(
+            OutputCondition::NonEmpty.violation(b"\x80\x04pickled"),
+            None
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use indexify_utils::get_epoch_time_in_ms;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{BlobStoragePlacement, ComputeGraph, DataPayload, GraphVersion, Namespace, Node};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FunctionProvenance {
+    pub image_name: String,
+    /// Hash of the function's definition in the graph, changes whenever the
+    /// function is redefined.
+    pub definition_sha256: String,
+}
+
+/// What an invocation ran with, recorded when it starts running so that the
+/// run can be audited and reproduced later.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InvocationProvenance {
+    pub graph_version: GraphVersion,
+    pub graph_code_sha256: String,
+    /// Hash of the graph's start function, edges and edge transforms.
+    pub graph_structure_sha256: String,
+    pub functions: HashMap<String, FunctionProvenance>,
+    pub input_sha256: String,
+    pub namespace_blob_storage: Option<BlobStoragePlacement>,
+    pub namespace_scheduling_weight: Option<u32>,
+    pub recorded_at: u64,
+}
+
+impl InvocationProvenance {
+    pub fn new(
+        graph: &ComputeGraph,
+        namespace: Option<&Namespace>,
+        input: &DataPayload,
+    ) -> Result<Self> {
+        let mut functions = HashMap::new();
+        for (name, node) in &graph.nodes {
+            functions.insert(name.clone(), function_provenance(node)?);
+        }
+        Ok(Self {
+            graph_version: graph.version,
+            graph_code_sha256: graph.code.sha256_hash.clone(),
+            graph_structure_sha256: structure_sha256(graph)?,
+            functions,
+            input_sha256: input.sha256_hash.clone(),
+            namespace_blob_storage: namespace.and_then(|ns| ns.blob_storage.clone()),
+            namespace_scheduling_weight: namespace.and_then(|ns| ns.scheduling_weight),
+            recorded_at: get_epoch_time_in_ms(),
+        })
+    }
+
+    /// Describes how the graph differs from the one the invocation ran with.
+    /// Empty when the graph would run the invocation the same way again.
+    pub fn graph_drift(&self, graph: &ComputeGraph) -> Result<Vec<String>> {
+        let mut drift = Vec::new();
+        if graph.version != self.graph_version {
+            drift.push(format!(
+                "graph is at version {}, the invocation ran version {}",
+                graph.version.0, self.graph_version.0
+            ));
+        }
+        if graph.code.sha256_hash != self.graph_code_sha256 {
+            drift.push("graph code changed".to_string());
+        }
+        if structure_sha256(graph)? != self.graph_structure_sha256 {
+            drift.push("graph edges changed".to_string());
+        }
+        let mut names = self
+            .functions
+            .keys()
+            .chain(graph.nodes.keys())
+            .collect::<Vec<_>>();
+        names.sort();
+        names.dedup();
+        for name in names {
+            let current = graph.nodes.get(name).map(function_provenance).transpose()?;
+            match (self.functions.get(name), current) {
+                (Some(recorded), Some(current)) if *recorded == current => {}
+                (Some(_), Some(_)) => drift.push(format!("function {} changed", name)),
+                (Some(_), None) => drift.push(format!("function {} was removed", name)),
+                (None, _) => drift.push(format!("function {} was added", name)),
+            }
+        }
+        Ok(drift)
+    }
+}
+
+fn function_provenance(node: &Node) -> Result<FunctionProvenance> {
+    Ok(FunctionProvenance {
+        image_name: node.image_name().to_string(),
+        definition_sha256: sha256_json(node)?,
+    })
+}
+
+// Graphs without edge transforms hash as they did before transforms existed,
+// so invocations recorded earlier don't report drift.
+fn structure_sha256(graph: &ComputeGraph) -> Result<String> {
+    if graph.edge_transforms.is_empty() {
+        return sha256_json(&(graph.start_fn.name(), &graph.edges));
+    }
+    sha256_json(&(graph.start_fn.name(), &graph.edges, &graph.edge_transforms))
+}
+
+// Maps serialize with sorted keys once converted to a JSON value, so the hash
+// doesn't depend on their iteration order.
+fn sha256_json<T: Serialize>(value: &T) -> Result<String> {
+    let json = serde_json::to_value(value)?.to_string();
+    Ok(format!("{:x}", Sha256::digest(json.as_bytes())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_objects::tests::{mock_graph_a, mock_invocation_payload};
+
+    #[test]
+    fn test_graph_drift() -> Result<()> {
+        let graph = mock_graph_a();
+        let provenance =
+            InvocationProvenance::new(&graph, None, &mock_invocation_payload().payload)?;
+        assert!(provenance.graph_drift(&graph)?.is_empty());
+
+        let mut changed = graph.clone();
+        changed.version = changed.version.next();
+        if let Some(Node::Compute(compute_fn)) = changed.nodes.get_mut("fn_b") {
+            compute_fn.image_name = "image_v2".to_string();
+        }
+        changed.nodes.remove("fn_c");
+        assert_eq!(
+            provenance.graph_drift(&changed)?,
+            vec![
+                "graph is at version 2, the invocation ran version 1".to_string(),
+                "function fn_b changed".to_string(),
+                "function fn_c was removed".to_string(),
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{TaskId, TaskOutcome};
+
+/// Events acknowledged by the subscriber are removed once this many newer
+/// events were published, events not acknowledged yet are kept.
+pub const RETAINED_EVENTS: u64 = 10_000;
+
+/// Events of a namespace are delivered to the url of every subscription,
+/// in order, signed with the subscription's secret. Each event gets the next
+/// sequence number of the subscription and is kept so that subscribers can
+/// have them delivered again after downtime.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Subscription {
+    pub namespace: String,
+    pub id: String,
+    pub url: String,
+    pub secret: String,
+    pub created_at: u64,
+    /// Sequence number of the last event published to the subscription.
+    pub last_sequence: u64,
+    /// Sequence number of the last event the subscriber acknowledged.
+    pub delivered_sequence: u64,
+}
+
+impl Subscription {
+    pub fn key(&self) -> String {
+        Subscription::key_from(&self.namespace, &self.id)
+    }
+
+    pub fn key_from(namespace: &str, id: &str) -> String {
+        format!("{}|{}", namespace, id)
+    }
+
+    pub fn key_prefix(namespace: &str) -> String {
+        format!("{}|", namespace)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NamespaceEvent {
+    TaskCompleted {
+        compute_graph: String,
+        invocation_id: String,
+        compute_fn: String,
+        task_id: TaskId,
+        outcome: TaskOutcome,
+    },
+    InvocationFinished {
+        compute_graph: String,
+        invocation_id: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SubscriptionEvent {
+    pub namespace: String,
+    pub subscription_id: String,
+    pub sequence: u64,
+    pub created_at: u64,
+    pub event: NamespaceEvent,
+}
+
+impl SubscriptionEvent {
+    pub fn key(&self) -> String {
+        SubscriptionEvent::key_from(&self.namespace, &self.subscription_id, self.sequence)
+    }
+
+    pub fn key_from(namespace: &str, subscription_id: &str, sequence: u64) -> String {
+        format!("{}|{}|{:020}", namespace, subscription_id, sequence)
+    }
+
+    pub fn key_prefix(namespace: &str, subscription_id: &str) -> String {
+        format!("{}|{}|", namespace, subscription_id)
+    }
+}
@@ -94,6 +94,7 @@ pub mod tests {
                 sha256_hash: "3433".to_string(),
                 path,
                 size: 12,
+                content_type: None,
             }))
             .build()
             .unwrap()
@@ -115,12 +116,14 @@ pub mod tests {
 
     pub fn mock_invocation_payload() -> InvocationPayload {
         InvocationPayloadBuilder::default()
+            .id("invocation_graph_a".to_string())
             .namespace(TEST_NAMESPACE.to_string())
             .compute_graph_name("graph_A".to_string())
             .payload(DataPayload {
                 path: "test".to_string(),
                 size: 23,
                 sha256_hash: "hash1232".to_string(),
+                content_type: None,
             })
             .build()
             .unwrap()
@@ -128,12 +131,14 @@ pub mod tests {
 
     pub fn mock_invocation_payload_graph_b() -> InvocationPayload {
         InvocationPayloadBuilder::default()
+            .id("invocation_graph_b".to_string())
             .namespace(TEST_NAMESPACE.to_string())
             .compute_graph_name("graph_B".to_string())
             .payload(DataPayload {
                 path: "test".to_string(),
                 size: 23,
                 sha256_hash: "hash1232".to_string(),
+                content_type: None,
             })
             .build()
             .unwrap()
@@ -164,6 +169,10 @@ pub mod tests {
             },
             created_at: 5,
             start_fn: Compute(fn_a),
+            edge_transforms: vec![],
+            labels: Default::default(),
+            owners: vec![],
+            output_retention: Default::default(),
         }
     }
 
@@ -198,6 +207,10 @@ pub mod tests {
             },
             created_at: 5,
             start_fn: Compute(fn_a),
+            edge_transforms: vec![],
+            labels: Default::default(),
+            owners: vec![],
+            output_retention: Default::default(),
         }
     }
 
@@ -226,6 +239,10 @@ pub mod tests {
             version: crate::GraphVersion(1),
             created_at: 5,
             start_fn: Compute(fn_a),
+            edge_transforms: vec![],
+            labels: Default::default(),
+            owners: vec![],
+            output_retention: Default::default(),
         }
     }
 
@@ -239,6 +256,11 @@ pub mod tests {
             image_name: TEST_EXECUTOR_IMAGE_NAME.to_string(),
             addr: "".to_string(),
             labels: Default::default(),
+            gpus: vec![],
+            capacity: None,
+            version: None,
+            artifacts: vec![],
+            prefetch_window: None,
         }
     }
 }
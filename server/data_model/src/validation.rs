@@ -0,0 +1,654 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display},
+};
+
+use serde_json::Value;
+
+use crate::{
+    alerts::OwnerContact,
+    filter::{Expression, LabelsFilter},
+    output_checks::OutputCondition,
+    BlobStoragePlacement,
+    ComputeGraph,
+    ExecutorMetadata,
+    InvocationPayload,
+    Namespace,
+    Node,
+};
+
+pub const MAX_NAME_LENGTH: usize = 128;
+pub const RESERVED_NAME_PREFIX: &str = "__";
+pub const MAX_TAGS: usize = 32;
+pub const MAX_TAG_LENGTH: usize = 256;
+pub const MAX_PLACEMENT_CONSTRAINTS: usize = 32;
+pub const MAX_LABELS: usize = 64;
+pub const MAX_LABEL_LENGTH: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// All the problems found while validating a request, reported per field.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ValidationErrors(pub Vec<FieldError>);
+
+impl ValidationErrors {
+    pub fn add(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.0.push(FieldError {
+            field: field.into(),
+            message: message.into(),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn into_result(self) -> Result<(), ValidationErrors> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let errors = self
+            .0
+            .iter()
+            .map(|e| format!("{}: {}", e.field, e.message))
+            .collect::<Vec<_>>();
+        write!(f, "invalid request: {}", errors.join("; "))
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+pub trait Validate {
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+/// Names are used as components of state store keys, so they are limited to
+/// a character set that can't collide with key separators.
+pub fn validate_name(errors: &mut ValidationErrors, field: &str, name: &str) {
+    if name.is_empty() {
+        errors.add(field, "must not be empty");
+        return;
+    }
+    if name.len() > MAX_NAME_LENGTH {
+        errors.add(
+            field,
+            format!("must be at most {} characters", MAX_NAME_LENGTH),
+        );
+    }
+    if name.starts_with(RESERVED_NAME_PREFIX) {
+        errors.add(
+            field,
+            format!(
+                "must not start with reserved prefix {}",
+                RESERVED_NAME_PREFIX
+            ),
+        );
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')))
+    {
+        errors.add(
+            field,
+            format!(
+                "invalid character {:?}, only letters, digits, '_', '-' and '.' are allowed",
+                c
+            ),
+        );
+    }
+}
+
+/// Tags are indexed under `<tag>=<value>` key components.
+fn validate_tags(errors: &mut ValidationErrors, field: &str, tags: &HashMap<String, String>) {
+    if tags.len() > MAX_TAGS {
+        errors.add(field, format!("must have at most {} entries", MAX_TAGS));
+    }
+    for (key, value) in tags {
+        let field = format!("{}.{}", field, key);
+        if key.is_empty() || key.contains(['=', '|']) {
+            errors.add(&field, "key must be non empty and not contain '=' or '|'");
+        }
+        if value.contains('|') {
+            errors.add(&field, "value must not contain '|'");
+        }
+        if key.len() + value.len() > MAX_TAG_LENGTH {
+            errors.add(
+                &field,
+                format!("must be at most {} characters", MAX_TAG_LENGTH),
+            );
+        }
+    }
+}
+
+/// Label keys are the left hand side of filter expressions, so they can't
+/// contain the characters of an operator.
+fn validate_label_key(errors: &mut ValidationErrors, field: &str, key: &str) {
+    if key.is_empty() {
+        errors.add(field, "key must not be empty");
+    } else if let Some(c) = key
+        .chars()
+        .find(|c| c.is_whitespace() || matches!(c, '=' | '!' | '<' | '>' | '|'))
+    {
+        errors.add(field, format!("key must not contain {:?}", c));
+    }
+}
+
+/// Filters only compare strings, numbers and booleans.
+fn validate_label_value(errors: &mut ValidationErrors, field: &str, value: &Value) {
+    match value {
+        Value::String(s) if s.is_empty() => errors.add(field, "value must not be empty"),
+        Value::String(_) | Value::Number(_) | Value::Bool(_) => {}
+        _ => errors.add(field, "value must be a string, a number or a boolean"),
+    }
+}
+
+/// Executor labels are matched by the placement constraints of functions.
+pub fn validate_labels(
+    errors: &mut ValidationErrors,
+    field: &str,
+    labels: &HashMap<String, Value>,
+) {
+    if labels.len() > MAX_LABELS {
+        errors.add(field, format!("must have at most {} entries", MAX_LABELS));
+    }
+    let mut keys = labels.keys().collect::<Vec<_>>();
+    keys.sort();
+    for key in keys {
+        let value = &labels[key];
+        let field = format!("{}.{}", field, key);
+        validate_label_key(errors, &field, key);
+        validate_label_value(errors, &field, value);
+        let value_len = match value {
+            Value::String(value) => value.len(),
+            value => value.to_string().len(),
+        };
+        if key.len() + value_len > MAX_LABEL_LENGTH {
+            errors.add(
+                &field,
+                format!("must be at most {} characters", MAX_LABEL_LENGTH),
+            );
+        }
+    }
+}
+
+pub fn validate_filter(errors: &mut ValidationErrors, field: &str, filter: &LabelsFilter) {
+    if filter.0.len() > MAX_PLACEMENT_CONSTRAINTS {
+        errors.add(
+            field,
+            format!("must have at most {} filters", MAX_PLACEMENT_CONSTRAINTS),
+        );
+    }
+    for (i, expression) in filter.0.iter().enumerate() {
+        validate_expression(errors, &format!("{}.{}", field, i), expression);
+    }
+}
+
+fn validate_expression(errors: &mut ValidationErrors, field: &str, expression: &Expression) {
+    validate_label_key(errors, field, &expression.key);
+    validate_label_value(errors, field, &expression.value);
+}
+
+/// Parses filter expressions such as `zone=us-east-1` or `memory_gb>=16`,
+/// reporting the ones which don't parse or aren't valid.
+pub fn parse_filter(
+    errors: &mut ValidationErrors,
+    field: &str,
+    expressions: &[String],
+) -> LabelsFilter {
+    if expressions.len() > MAX_PLACEMENT_CONSTRAINTS {
+        errors.add(
+            field,
+            format!("must have at most {} filters", MAX_PLACEMENT_CONSTRAINTS),
+        );
+    }
+    let mut filter = LabelsFilter::default();
+    for (i, expression) in expressions.iter().enumerate() {
+        let field = format!("{}.{}", field, i);
+        match Expression::from_str(expression) {
+            Ok(expression) => {
+                validate_expression(errors, &field, &expression);
+                filter.0.push(expression);
+            }
+            Err(e) => errors.add(field, e.to_string()),
+        }
+    }
+    filter
+}
+
+pub fn validate_blob_storage_placement(
+    errors: &mut ValidationErrors,
+    field: &str,
+    placement: &BlobStoragePlacement,
+) {
+    match placement {
+        BlobStoragePlacement::S3 { bucket, region } => {
+            if bucket.is_empty() {
+                errors.add(format!("{}.s3.bucket", field), "must not be empty");
+            }
+            if region.is_empty() {
+                errors.add(format!("{}.s3.region", field), "must not be empty");
+            }
+        }
+        BlobStoragePlacement::Disk { path } => {
+            if path.is_empty() {
+                errors.add(format!("{}.disk.path", field), "must not be empty");
+            }
+        }
+    }
+}
+
+impl Validate for Namespace {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        validate_name(&mut errors, "name", &self.name);
+        if let Some(placement) = &self.blob_storage {
+            validate_blob_storage_placement(&mut errors, "blob_storage", placement);
+        }
+        if self.scheduling_weight == Some(0) {
+            errors.add("scheduling_weight", "must be greater than zero");
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for ComputeGraph {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        validate_name(&mut errors, "namespace", &self.namespace);
+        validate_name(&mut errors, "name", &self.name);
+        validate_tags(&mut errors, "labels", &self.labels);
+        for (field, secs) in [
+            (
+                "output_retention.final_outputs_secs",
+                self.output_retention.final_outputs_secs,
+            ),
+            (
+                "output_retention.intermediate_outputs_secs",
+                self.output_retention.intermediate_outputs_secs,
+            ),
+        ] {
+            if secs == Some(0) {
+                errors.add(field, "must be greater than zero");
+            }
+        }
+        for (key, node) in &self.nodes {
+            let field = format!("nodes.{}", key);
+            validate_name(&mut errors, &field, key);
+            if node.name() != key {
+                errors.add(&field, format!("node is named {}", node.name()));
+            }
+            if let Node::Compute(compute_fn) = node {
+                if compute_fn.expected_concurrency == Some(0) {
+                    errors.add(
+                        format!("{}.expected_concurrency", field),
+                        "must be greater than zero",
+                    );
+                }
+                if let Some(gpu) = &compute_fn.gpu {
+                    if !(gpu.fraction > 0.0 && gpu.fraction <= 1.0) {
+                        errors.add(
+                            format!("{}.gpu.fraction", field),
+                            "must be greater than zero and at most one",
+                        );
+                    }
+                }
+                if let Some(dependency) = &compute_fn.external_dependency {
+                    validate_name(
+                        &mut errors,
+                        &format!("{}.external_dependency.name", field),
+                        &dependency.name,
+                    );
+                    if dependency.max_concurrent_calls == 0 {
+                        errors.add(
+                            format!("{}.external_dependency.max_concurrent_calls", field),
+                            "must be greater than zero",
+                        );
+                    }
+                }
+                let mut check_names = HashSet::new();
+                for (i, check) in compute_fn.output_checks.iter().enumerate() {
+                    let check_field = format!("{}.output_checks.{}", field, i);
+                    validate_name(&mut errors, &format!("{}.name", check_field), &check.name);
+                    if !check_names.insert(&check.name) {
+                        errors.add(format!("{}.name", check_field), "is not unique");
+                    }
+                    if check.condition.needs_json() && compute_fn.payload_encoder != "json" {
+                        errors.add(
+                            format!("{}.condition", check_field),
+                            "needs the function to encode its outputs as json",
+                        );
+                    }
+                    if let OutputCondition::Range {
+                        min: Some(min),
+                        max: Some(max),
+                        ..
+                    } = check.condition
+                    {
+                        if min > max {
+                            errors.add(
+                                format!("{}.condition", check_field),
+                                "min must not be greater than max",
+                            );
+                        }
+                    }
+                }
+                for (i, pattern) in compute_fn.input_content_types.iter().enumerate() {
+                    let valid = pattern.split_once('/').is_some_and(|(kind, subtype)| {
+                        !kind.is_empty() &&
+                            !subtype.is_empty() &&
+                            !subtype.contains('/') &&
+                            (kind != "*" || subtype == "*")
+                    });
+                    if !valid {
+                        errors.add(
+                            format!("{}.input_content_types.{}", field, i),
+                            "must be a content type such as image/png, image/* or */*",
+                        );
+                    }
+                }
+                validate_filter(
+                    &mut errors,
+                    &format!("{}.placement_constraints", field),
+                    &compute_fn.placement_constraints,
+                );
+            }
+        }
+        if !self.nodes.contains_key(self.start_fn.name()) {
+            errors.add("start_fn", "must be one of the graph's nodes");
+        }
+        for (source, targets) in &self.edges {
+            for target in std::iter::once(source).chain(targets) {
+                if !self.nodes.contains_key(target) {
+                    errors.add(
+                        format!("edges.{}", source),
+                        format!("unknown node {}", target),
+                    );
+                }
+            }
+        }
+        let mut transformed_edges = HashSet::new();
+        for (i, transform) in self.edge_transforms.iter().enumerate() {
+            let field = format!("edge_transforms.{}", i);
+            let has_edge = self
+                .edges
+                .get(&transform.source)
+                .is_some_and(|targets| targets.contains(&transform.target));
+            if !has_edge {
+                errors.add(
+                    &field,
+                    format!("no edge from {} to {}", transform.source, transform.target),
+                );
+            } else if !transformed_edges.insert((&transform.source, &transform.target)) {
+                errors.add(&field, "edge is transformed more than once");
+            }
+            match self.nodes.get(&transform.source) {
+                Some(Node::Compute(compute_fn)) if compute_fn.payload_encoder != "json" => {
+                    errors.add(
+                        &field,
+                        "needs the source function to encode its outputs as json",
+                    );
+                }
+                Some(Node::Router(_)) => errors.add(&field, "source must not be a router"),
+                _ => {}
+            }
+            if transform.steps.is_empty() {
+                errors.add(format!("{}.steps", field), "must not be empty");
+            }
+        }
+        for (i, owner) in self.owners.iter().enumerate() {
+            if let OwnerContact::Webhook { url } = &owner.contact {
+                if !(url.starts_with("http://") || url.starts_with("https://")) {
+                    errors.add(
+                        format!("owners.{}.contact.url", i),
+                        "must use the http or https scheme",
+                    );
+                }
+            }
+        }
+        errors.into_result()
+    }
+}
+
+impl Validate for InvocationPayload {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        validate_name(&mut errors, "namespace", &self.namespace);
+        validate_name(&mut errors, "compute_graph_name", &self.compute_graph_name);
+        validate_tags(&mut errors, "tags", &self.tags);
+        errors.into_result()
+    }
+}
+
+impl Validate for ExecutorMetadata {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::default();
+        if self.image_name.is_empty() {
+            errors.add("image_name", "must not be empty");
+        }
+        validate_labels(&mut errors, "labels", &self.labels);
+        errors.into_result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        alerts::GraphOwner,
+        edge_transforms::{EdgeTransform, TransformStep},
+        test_objects::tests::{mock_executor, mock_graph_a, mock_graph_b, mock_invocation_payload},
+    };
+
+    #[test]
+    fn test_validate_compute_graph() {
+        assert!(mock_graph_a().validate().is_ok());
+        assert!(mock_graph_b().validate().is_ok());
+
+        let mut graph = mock_graph_a();
+        graph.name = "graph|A".to_string();
+        graph
+            .edges
+            .insert("fn_b".to_string(), vec!["fn_d".to_string()]);
+        let errors = graph.validate().unwrap_err();
+        let fields = errors
+            .0
+            .iter()
+            .map(|e| e.field.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(fields, vec!["name", "edges.fn_b"]);
+
+        let mut graph = mock_graph_a();
+        graph.owners = vec![GraphOwner {
+            contact: OwnerContact::Webhook {
+                url: "file:///etc/passwd".to_string(),
+            },
+            severities: vec![],
+        }];
+        let errors = graph.validate().unwrap_err();
+        assert_eq!(errors.0[0].field, "owners.0.contact.url");
+    }
+
+    #[test]
+    fn test_validate_input_content_types() {
+        let mut graph = mock_graph_a();
+        if let Some(Node::Compute(compute_fn)) = graph.nodes.get_mut("fn_b") {
+            compute_fn.input_content_types = vec![
+                "image/*".to_string(),
+                "*/*".to_string(),
+                "pdf".to_string(),
+                "*/png".to_string(),
+            ];
+        }
+        let errors = graph.validate().unwrap_err();
+        let fields = errors
+            .0
+            .iter()
+            .map(|e| e.field.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![
+                "nodes.fn_b.input_content_types.2",
+                "nodes.fn_b.input_content_types.3"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_edge_transforms() {
+        let mut graph = mock_graph_a();
+        let select = TransformStep::Select {
+            pointer: "/text".to_string(),
+        };
+        let transform = |source: &str, target: &str, steps: Vec<TransformStep>| EdgeTransform {
+            source: source.to_string(),
+            target: target.to_string(),
+            steps,
+        };
+        graph.edge_transforms = vec![
+            transform("fn_a", "fn_b", vec![select.clone()]),
+            transform("fn_b", "fn_c", vec![select.clone()]),
+            transform("fn_a", "fn_c", vec![]),
+        ];
+        let errors = graph.validate().unwrap_err();
+        let messages = errors
+            .0
+            .iter()
+            .map(|e| format!("{}: {}", e.field, e.message))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "edge_transforms.0: needs the source function to encode its outputs as json",
+                "edge_transforms.1: no edge from fn_b to fn_c",
+                "edge_transforms.1: needs the source function to encode its outputs as json",
+                "edge_transforms.2: needs the source function to encode its outputs as json",
+                "edge_transforms.2.steps: must not be empty",
+            ]
+        );
+
+        if let Some(Node::Compute(compute_fn)) = graph.nodes.get_mut("fn_a") {
+            compute_fn.payload_encoder = "json".to_string();
+        }
+        graph.edge_transforms = vec![transform("fn_a", "fn_b", vec![select])];
+        assert!(graph.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_invocation_tags() {
+        let mut invocation = mock_invocation_payload();
+        assert!(invocation.validate().is_ok());
+
+        invocation
+            .tags
+            .insert("__source".to_string(), "a|b".to_string());
+        invocation.namespace = "".to_string();
+        let errors = invocation.validate().unwrap_err();
+        assert_eq!(errors.0.len(), 2);
+        assert_eq!(errors.0[0].field, "namespace");
+        assert_eq!(errors.0[1].field, "tags.__source");
+
+        let mut graph = mock_graph_a();
+        graph.labels.insert("team=".to_string(), "ml".to_string());
+        let errors = graph.validate().unwrap_err();
+        assert_eq!(errors.0[0].field, "labels.team=");
+    }
+
+    #[test]
+    fn test_validate_executor_labels() {
+        let mut executor = mock_executor();
+        executor.labels = HashMap::from([
+            ("zone".to_string(), json!("us-east-1")),
+            ("memory_gb".to_string(), json!(16)),
+            ("gpu".to_string(), json!(true)),
+            ("k".to_string(), json!("x".repeat(MAX_LABEL_LENGTH - 1))),
+        ]);
+        assert!(executor.validate().is_ok());
+
+        executor.labels = HashMap::from([
+            ("a=b".to_string(), json!("c")),
+            ("empty".to_string(), json!("")),
+            ("list".to_string(), json!(["a"])),
+            ("long".to_string(), json!("x".repeat(MAX_LABEL_LENGTH))),
+        ]);
+        let errors = executor.validate().unwrap_err();
+        let messages = errors
+            .0
+            .iter()
+            .map(|e| format!("{}: {}", e.field, e.message))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "labels.a=b: key must not contain '='",
+                "labels.empty: value must not be empty",
+                "labels.list: value must be a string, a number or a boolean",
+                "labels.long: must be at most 256 characters",
+            ]
+        );
+
+        executor.labels = (0..=MAX_LABELS)
+            .map(|i| (format!("label_{}", i), json!(i)))
+            .collect();
+        let errors = executor.validate().unwrap_err();
+        assert_eq!(errors.0.len(), 1);
+        assert_eq!(errors.0[0].field, "labels");
+    }
+
+    #[test]
+    fn test_parse_filter() {
+        let mut errors = ValidationErrors::default();
+        let filter = parse_filter(
+            &mut errors,
+            "placement_constraints",
+            &["zone=us-east-1".to_string(), "memory_gb>=16".to_string()],
+        );
+        assert!(errors.is_empty());
+        assert_eq!(filter.0.len(), 2);
+
+        let expressions = ["zone", "=b", "zone=", "zone=null", "gpu count>1"]
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        parse_filter(&mut errors, "placement_constraints", &expressions);
+        let messages = errors
+            .0
+            .iter()
+            .map(|e| format!("{}: {}", e.field, e.message))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![
+                "placement_constraints.0: Invalid filter: zone",
+                "placement_constraints.1: key must not be empty",
+                "placement_constraints.2: value must not be empty",
+                "placement_constraints.3: value must be a string, a number or a boolean",
+                "placement_constraints.4: key must not contain ' '",
+            ]
+        );
+
+        let mut graph = mock_graph_a();
+        if let Some(Node::Compute(compute_fn)) = graph.nodes.get_mut("fn_b") {
+            compute_fn.placement_constraints = LabelsFilter(vec![Expression {
+                key: "zone|a".to_string(),
+                value: json!("b"),
+                operator: crate::filter::Operator::Eq,
+            }]);
+        }
+        let errors = graph.validate().unwrap_err();
+        assert_eq!(errors.0[0].field, "nodes.fn_b.placement_constraints.0");
+    }
+}
@@ -0,0 +1,261 @@
+use std::{collections::HashMap, fmt, time::Duration};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use data_model::ExecutorMetadata;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::config::{AdmissionWebhookConfig, ExecutorAdmissionConfig};
+
+#[derive(Debug, Clone)]
+pub enum AdmissionDecision {
+    /// Registers the executor, possibly changed by the hook.
+    Admit(ExecutorMetadata),
+    Reject(String),
+}
+
+/// Consulted before an executor is registered. Hooks run in order, every hook
+/// sees the executor as changed by the previous ones.
+#[async_trait]
+pub trait ExecutorAdmission: Send + Sync {
+    async fn admit(&self, executor: ExecutorMetadata) -> Result<AdmissionDecision>;
+}
+
+/// Returned by `ExecutorManager::register_executor` when a hook rejects the
+/// executor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutorRejected {
+    pub reason: String,
+}
+
+impl fmt::Display for ExecutorRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "executor registration rejected: {}", self.reason)
+    }
+}
+
+impl std::error::Error for ExecutorRejected {}
+
+/// Builds the hooks configured on the server, the label policy first.
+pub fn admission_hooks(
+    config: &ExecutorAdmissionConfig,
+) -> Result<Vec<Box<dyn ExecutorAdmission>>> {
+    let mut hooks: Vec<Box<dyn ExecutorAdmission>> = Vec::new();
+    if !config.required_labels.is_empty() || !config.default_labels.is_empty() {
+        hooks.push(Box::new(LabelPolicy {
+            required_labels: config.required_labels.clone(),
+            default_labels: config.default_labels.clone(),
+        }));
+    }
+    if let Some(webhook) = &config.webhook {
+        hooks.push(Box::new(WebhookAdmission::new(webhook)?));
+    }
+    Ok(hooks)
+}
+
+/// Fills in default labels and rejects executors missing a required label.
+pub struct LabelPolicy {
+    pub required_labels: Vec<String>,
+    pub default_labels: HashMap<String, serde_json::Value>,
+}
+
+#[async_trait]
+impl ExecutorAdmission for LabelPolicy {
+    async fn admit(&self, mut executor: ExecutorMetadata) -> Result<AdmissionDecision> {
+        for (label, value) in &self.default_labels {
+            executor
+                .labels
+                .entry(label.clone())
+                .or_insert_with(|| value.clone());
+        }
+        let missing = self
+            .required_labels
+            .iter()
+            .filter(|label| !executor.labels.contains_key(*label))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Ok(AdmissionDecision::Reject(format!(
+                "missing required labels: {}",
+                missing.join(", ")
+            )));
+        }
+        Ok(AdmissionDecision::Admit(executor))
+    }
+}
+
+#[derive(Serialize)]
+struct AdmissionReview<'a> {
+    executor: &'a ExecutorMetadata,
+}
+
+#[derive(Deserialize)]
+struct AdmissionResponse {
+    allowed: bool,
+    #[serde(default)]
+    reason: Option<String>,
+    /// Replaces the labels of the executor when set.
+    #[serde(default)]
+    labels: Option<HashMap<String, serde_json::Value>>,
+}
+
+/// Posts every registration to an external service which decides whether
+/// the executor is admitted and may rewrite its labels.
+pub struct WebhookAdmission {
+    client: reqwest::Client,
+    url: String,
+    fail_open: bool,
+}
+
+impl WebhookAdmission {
+    pub fn new(config: &AdmissionWebhookConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()?;
+        Ok(Self {
+            client,
+            url: config.url.clone(),
+            fail_open: config.fail_open,
+        })
+    }
+
+    async fn review(&self, executor: &ExecutorMetadata) -> Result<AdmissionResponse> {
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&AdmissionReview { executor })
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("admission webhook returned {}", response.status()));
+        }
+        Ok(response.json().await?)
+    }
+}
+
+#[async_trait]
+impl ExecutorAdmission for WebhookAdmission {
+    async fn admit(&self, mut executor: ExecutorMetadata) -> Result<AdmissionDecision> {
+        let response = match self.review(&executor).await {
+            Ok(response) => response,
+            Err(e) if self.fail_open => {
+                warn!(
+                    "admission webhook failed, admitting executor {}: {:?}",
+                    executor.id, e
+                );
+                return Ok(AdmissionDecision::Admit(executor));
+            }
+            Err(e) => return Err(e),
+        };
+        if !response.allowed {
+            return Ok(AdmissionDecision::Reject(
+                response
+                    .reason
+                    .unwrap_or_else(|| "denied by admission webhook".to_string()),
+            ));
+        }
+        if let Some(labels) = response.labels {
+            executor.labels = labels;
+        }
+        Ok(AdmissionDecision::Admit(executor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{routing::post, Json, Router};
+    use data_model::ExecutorId;
+    use serde_json::json;
+
+    use super::*;
+
+    fn executor(labels: serde_json::Value) -> ExecutorMetadata {
+        ExecutorMetadata {
+            id: ExecutorId::new("executor_1".to_string()),
+            image_name: "image".to_string(),
+            addr: "".to_string(),
+            labels: serde_json::from_value(labels).unwrap(),
+            gpus: vec![],
+            capacity: None,
+            version: None,
+            artifacts: vec![],
+            prefetch_window: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_label_policy() -> Result<()> {
+        let policy = LabelPolicy {
+            required_labels: vec!["team".to_string(), "pool".to_string()],
+            default_labels: HashMap::from([("pool".to_string(), json!("shared"))]),
+        };
+        let AdmissionDecision::Admit(admitted) =
+            policy.admit(executor(json!({"team": "search"}))).await?
+        else {
+            panic!("executor with the required labels was rejected");
+        };
+        assert_eq!(admitted.labels.get("pool"), Some(&json!("shared")));
+
+        let decision = policy.admit(executor(json!({}))).await?;
+        assert!(matches!(
+            decision,
+            AdmissionDecision::Reject(reason) if reason == "missing required labels: team"
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_webhook_admission() -> Result<()> {
+        let app = Router::new().route(
+            "/admit",
+            post(|Json(review): Json<serde_json::Value>| async move {
+                let labels = &review["executor"]["labels"];
+                if labels["team"] == json!("search") {
+                    Json(json!({"allowed": true, "labels": {"team": "search", "pool": "gpu"}}))
+                } else {
+                    Json(json!({"allowed": false, "reason": "unknown team"}))
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let webhook = WebhookAdmission::new(&AdmissionWebhookConfig {
+            url: format!("http://{}/admit", addr),
+            timeout_secs: 5,
+            fail_open: false,
+        })?;
+        let AdmissionDecision::Admit(admitted) =
+            webhook.admit(executor(json!({"team": "search"}))).await?
+        else {
+            panic!("executor was rejected by the webhook");
+        };
+        assert_eq!(admitted.labels.get("pool"), Some(&json!("gpu")));
+        assert!(matches!(
+            webhook.admit(executor(json!({"team": "ads"}))).await?,
+            AdmissionDecision::Reject(reason) if reason == "unknown team"
+        ));
+
+        // An unreachable webhook only admits executors when failing open.
+        let config = AdmissionWebhookConfig {
+            url: "http://127.0.0.1:1/admit".to_string(),
+            timeout_secs: 1,
+            fail_open: false,
+        };
+        assert!(WebhookAdmission::new(&config)?
+            .admit(executor(json!({})))
+            .await
+            .is_err());
+        let webhook = WebhookAdmission::new(&AdmissionWebhookConfig {
+            fail_open: true,
+            ..config
+        })?;
+        assert!(matches!(
+            webhook.admit(executor(json!({}))).await?,
+            AdmissionDecision::Admit(_)
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use data_model::{
+    alerts::{Alert, OwnerContact},
+    ComputeGraph,
+};
+use reqwest::Url;
+use serde::Serialize;
+use serde_json::json;
+use state_store::{
+    requests::{RequestPayload, StateMachineUpdateRequest},
+    state_machine::IndexifyObjectsColumns,
+    IndexifyState,
+};
+use tokio::sync::watch;
+use tracing::{error, warn};
+
+use crate::{
+    config::{AlertingConfig, WebhookConfig},
+    outbound,
+};
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize)]
+struct Notification<'a> {
+    summary: String,
+    #[serde(flatten)]
+    alert: &'a Alert,
+}
+
+/// Contacts an alert goes to: the owners of its graph who receive its
+/// severity, or the default webhook for graphs without owners.
+fn recipients(
+    graph: Option<&ComputeGraph>,
+    alert: &Alert,
+    config: &AlertingConfig,
+) -> Vec<OwnerContact> {
+    match graph {
+        Some(graph) if !graph.owners.is_empty() => graph
+            .owners
+            .iter()
+            .filter(|owner| owner.receives(alert.severity))
+            .map(|owner| owner.contact.clone())
+            .collect(),
+        _ => config
+            .default_webhook
+            .iter()
+            .map(|url| OwnerContact::Webhook { url: url.clone() })
+            .collect(),
+    }
+}
+
+/// Routes the alerts raised by the state store to the owners of their
+/// graphs. Alerts are removed once every recipient accepted them, so an
+/// alert can reach a recipient twice when another one failed. The webhooks
+/// of owners are supplied by tenants, they're only posted to on public
+/// addresses unless `webhooks` allows private ones.
+pub struct AlertRouter {
+    state: Arc<IndexifyState>,
+    config: AlertingConfig,
+    webhooks: WebhookConfig,
+}
+
+impl AlertRouter {
+    pub fn new(state: Arc<IndexifyState>, config: AlertingConfig, webhooks: WebhookConfig) -> Self {
+        Self {
+            state,
+            config,
+            webhooks,
+        }
+    }
+
+    /// Redirects aren't followed, whoever configured the url.
+    async fn post(
+        &self,
+        url: &str,
+        body: impl Serialize,
+        allow_private_addresses: bool,
+    ) -> Result<()> {
+        let url = Url::parse(url)?;
+        let response = outbound::client_for(&url, allow_private_addresses)
+            .await?
+            .timeout(DELIVERY_TIMEOUT)
+            .build()?
+            .post(url.clone())
+            .json(&body)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("{} returned {}", url, response.status()));
+        }
+        Ok(())
+    }
+
+    async fn notify(&self, contact: &OwnerContact, alert: &Alert) -> Result<()> {
+        let summary = alert.summary();
+        match contact {
+            OwnerContact::Webhook { url } => {
+                // The default webhook is the operator's, not a tenant's.
+                let trusted = self.config.default_webhook.as_ref() == Some(url);
+                let allow_private_addresses = trusted || self.webhooks.allow_private_addresses;
+                self.post(
+                    url,
+                    Notification { summary, alert },
+                    allow_private_addresses,
+                )
+                .await
+            }
+            OwnerContact::Slack { channel } => {
+                let Some(url) = &self.config.slack_webhook_url else {
+                    warn!(
+                        "no slack webhook configured, dropping alert for {}",
+                        channel
+                    );
+                    return Ok(());
+                };
+                self.post(url, json!({ "channel": channel, "text": summary }), true)
+                    .await
+            }
+            OwnerContact::Email { address } => {
+                let Some(url) = &self.config.email_relay_url else {
+                    warn!("no email relay configured, dropping alert for {}", address);
+                    return Ok(());
+                };
+                let subject = format!(
+                    "[{:?}] graph {}/{}",
+                    alert.severity, alert.namespace, alert.compute_graph
+                );
+                self.post(
+                    url,
+                    json!({ "to": address, "subject": subject, "body": summary }),
+                    true,
+                )
+                .await
+            }
+        }
+    }
+
+    /// Delivers the oldest pending alerts, returns how many were pending.
+    pub async fn deliver_batch(&self) -> Result<usize> {
+        let alerts = self.state.reader().alerts(self.config.batch_size)?;
+        // The graphs of the batch are read at once.
+        let graph_keys: Vec<String> = alerts
+            .iter()
+            .map(|alert| ComputeGraph::key_from(&alert.namespace, &alert.compute_graph))
+            .collect();
+        let graphs: Vec<Option<ComputeGraph>> = self
+            .state
+            .reader()
+            .multi_get(&IndexifyObjectsColumns::ComputeGraphs, &graph_keys)?;
+        let mut delivered = vec![];
+        for (alert, graph) in alerts.iter().zip(graphs) {
+            let mut accepted = true;
+            for contact in recipients(graph.as_ref(), alert, &self.config) {
+                if let Err(e) = self.notify(&contact, alert).await {
+                    warn!("failed to deliver alert {}: {:?}", alert.id, e);
+                    accepted = false;
+                }
+            }
+            if accepted {
+                delivered.push(alert.id.clone());
+            }
+        }
+        if !delivered.is_empty() {
+            self.state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::RemoveAlerts(delivered),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        Ok(alerts.len())
+    }
+
+    pub async fn start(&self, mut shutdown_rx: watch::Receiver<()>) -> Result<()> {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    if let Err(e) = self.deliver_batch().await {
+                        error!("error delivering alerts: {:?}", e);
+                    }
+                }
+                _ = shutdown_rx.changed() => {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic, Mutex};
+
+    use axum::{http::StatusCode, routing::post, Json, Router};
+    use data_model::{
+        alerts::{GraphOwner, Severity},
+        test_objects::tests::{mock_graph_a, TEST_NAMESPACE},
+        TaskOutcome,
+    };
+    use state_store::test_state_store::tests::TestStateStore;
+
+    use super::*;
+    use crate::scheduler::Scheduler;
+
+    #[derive(Default)]
+    struct Receiver {
+        fail: bool,
+        received: Vec<serde_json::Value>,
+    }
+
+    #[tokio::test]
+    async fn test_alert_routing() -> Result<()> {
+        let receiver = Arc::new(Mutex::new(Receiver::default()));
+        let server = receiver.clone();
+        let app = Router::new().route(
+            "/alerts",
+            post(move |Json(body): Json<serde_json::Value>| async move {
+                let mut receiver = server.lock().unwrap();
+                if receiver.fail {
+                    return StatusCode::SERVICE_UNAVAILABLE;
+                }
+                receiver.received.push(body);
+                StatusCode::OK
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let invocation_id = state_store.with_simple_graph().await;
+        let scheduler = Scheduler::new(indexify_state.clone());
+        scheduler.run_scheduler().await?;
+        let task = indexify_state
+            .reader()
+            .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", &invocation_id, None, None)?
+            .0
+            .remove(0);
+        // Graphs without owners only raise alerts with a default channel.
+        indexify_state
+            .default_alert_channel
+            .store(true, atomic::Ordering::Relaxed);
+        state_store
+            .finalize_task(&task, 1, TaskOutcome::Failure, false)
+            .await?;
+        let alerts = indexify_state.reader().alerts(10)?;
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].severity, Severity::Critical);
+
+        let config = AlertingConfig {
+            default_webhook: Some(format!("http://{}/alerts", addr)),
+            ..Default::default()
+        };
+        let router = AlertRouter::new(indexify_state.clone(), config.clone(), Default::default());
+        receiver.lock().unwrap().fail = true;
+        assert_eq!(router.deliver_batch().await?, 1);
+        // Alerts are kept until they're delivered.
+        assert_eq!(indexify_state.reader().alerts(10)?.len(), 1);
+        receiver.lock().unwrap().fail = false;
+        assert_eq!(router.deliver_batch().await?, 1);
+        assert!(indexify_state.reader().alerts(10)?.is_empty());
+        {
+            let received = &receiver.lock().unwrap().received;
+            assert_eq!(received.len(), 1);
+            assert_eq!(received[0]["severity"], "critical");
+            assert_eq!(received[0]["problem"]["task_id"], task.id.to_string());
+        }
+
+        // Owners replace the default channel, filtered by severity.
+        let mut graph = mock_graph_a();
+        graph.owners = vec![
+            GraphOwner {
+                contact: OwnerContact::Slack {
+                    channel: "#search".to_string(),
+                },
+                severities: vec![],
+            },
+            GraphOwner {
+                contact: OwnerContact::Email {
+                    address: "oncall@example.com".to_string(),
+                },
+                severities: vec![Severity::Critical],
+            },
+        ];
+        let mut alert = alerts[0].clone();
+        assert_eq!(recipients(Some(&graph), &alert, &config).len(), 2);
+        alert.severity = Severity::Warning;
+        assert_eq!(
+            recipients(Some(&graph), &alert, &config),
+            vec![OwnerContact::Slack {
+                channel: "#search".to_string()
+            }]
+        );
+        assert!(recipients(None, &alert, &AlertingConfig::default()).is_empty());
+
+        // The webhooks of owners aren't posted to on loopback addresses.
+        let contact = OwnerContact::Webhook {
+            url: format!("http://{}/owner", addr),
+        };
+        let err = router.notify(&contact, &alert).await.unwrap_err();
+        assert!(err.is::<outbound::UrlNotAllowed>());
+        assert_eq!(receiver.lock().unwrap().received.len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use data_model::analytics::AnalyticsRecord;
+use serde::Serialize;
+use state_store::{
+    requests::{RequestPayload, StateMachineUpdateRequest},
+    IndexifyState,
+};
+use tokio::sync::watch;
+use tracing::{error, info};
+
+use crate::config::AnalyticsExportConfig;
+
+const INSERT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Inserts the finished tasks and invocations recorded by the state store
+/// into ClickHouse, as JSONEachRow. Records are removed from the state store
+/// once they're inserted, so they are exported at least once: a record is
+/// inserted again if the server stops between its insert and its removal.
+pub struct AnalyticsExporter {
+    state: Arc<IndexifyState>,
+    config: AnalyticsExportConfig,
+    client: reqwest::Client,
+}
+
+impl AnalyticsExporter {
+    pub fn new(state: Arc<IndexifyState>, config: AnalyticsExportConfig) -> Result<Self> {
+        let client = reqwest::Client::builder().timeout(INSERT_TIMEOUT).build()?;
+        Ok(Self {
+            state,
+            config,
+            client,
+        })
+    }
+
+    async fn insert<T: Serialize>(&self, url: &str, table: &str, rows: &[&T]) -> Result<()> {
+        let mut body = Vec::new();
+        for row in rows {
+            serde_json::to_writer(&mut body, row)?;
+            body.push(b'\n');
+        }
+        let query = format!(
+            "INSERT INTO {}.{} FORMAT JSONEachRow",
+            self.config.database, table
+        );
+        let mut request = self.client.post(url).query(&[("query", query)]).body(body);
+        if let Some(user) = &self.config.user {
+            request = request.basic_auth(user, self.config.password.as_ref());
+        }
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(anyhow!(
+                "insert into {} returned {}: {}",
+                table,
+                status,
+                text
+            ));
+        }
+        Ok(())
+    }
+
+    async fn remove(&self, ids: Vec<String>) -> Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+        self.state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::RemoveAnalyticsRecords(ids),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
+    /// Exports the oldest pending records, returns how many were exported.
+    pub async fn export_batch(&self) -> Result<usize> {
+        let Some(url) = &self.config.clickhouse_url else {
+            return Ok(0);
+        };
+        let records = self
+            .state
+            .reader()
+            .analytics_records(self.config.batch_size)?;
+        let (mut task_ids, mut tasks) = (vec![], vec![]);
+        let (mut invocation_ids, mut invocations) = (vec![], vec![]);
+        for (id, record) in &records {
+            match record {
+                AnalyticsRecord::Task(task) => {
+                    task_ids.push(id.clone());
+                    tasks.push(task);
+                }
+                AnalyticsRecord::Invocation(invocation) => {
+                    invocation_ids.push(id.clone());
+                    invocations.push(invocation);
+                }
+            }
+        }
+        // Each table's records are removed as soon as they're inserted, so
+        // that a failed insert into the other one doesn't insert them again.
+        if !tasks.is_empty() {
+            self.insert(url, &self.config.tasks_table, &tasks).await?;
+            self.remove(task_ids).await?;
+        }
+        if !invocations.is_empty() {
+            self.insert(url, &self.config.invocations_table, &invocations)
+                .await?;
+            self.remove(invocation_ids).await?;
+        }
+        Ok(records.len())
+    }
+
+    pub async fn start(&self, mut shutdown_rx: watch::Receiver<()>) -> Result<()> {
+        if self.config.clickhouse_url.is_none() {
+            return Ok(());
+        }
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    // Full batches mean a backlog, it's exported right away.
+                    loop {
+                        match self.export_batch().await {
+                            Ok(exported) if exported == self.config.batch_size => {}
+                            Ok(0) => break,
+                            Ok(exported) => {
+                                info!("exported {} analytics records", exported);
+                                break;
+                            }
+                            Err(e) => {
+                                error!("error exporting analytics records: {:?}", e);
+                                break;
+                            }
+                        }
+                    }
+                }
+                _ = shutdown_rx.changed() => {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic, Mutex};
+
+    use axum::{extract::Query, http::StatusCode, routing::post, Router};
+    use data_model::{test_objects::tests::TEST_NAMESPACE, TaskOutcome};
+    use state_store::test_state_store::tests::TestStateStore;
+
+    use super::*;
+    use crate::scheduler::Scheduler;
+
+    #[derive(Default)]
+    struct ClickHouse {
+        fail: bool,
+        inserts: Vec<(String, Vec<serde_json::Value>)>,
+    }
+
+    #[tokio::test]
+    async fn test_export_batch() -> Result<()> {
+        let clickhouse = Arc::new(Mutex::new(ClickHouse::default()));
+        let server = clickhouse.clone();
+        let app = Router::new().route(
+            "/",
+            post(
+                move |Query(params): Query<std::collections::HashMap<String, String>>,
+                      body: String| async move {
+                    let mut clickhouse = server.lock().unwrap();
+                    if clickhouse.fail {
+                        return StatusCode::SERVICE_UNAVAILABLE;
+                    }
+                    let rows = body
+                        .lines()
+                        .map(|line| serde_json::from_str(line).unwrap())
+                        .collect();
+                    clickhouse.inserts.push((params["query"].clone(), rows));
+                    StatusCode::OK
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        indexify_state
+            .analytics_enabled
+            .store(true, atomic::Ordering::Relaxed);
+        let invocation_id = state_store.with_simple_graph().await;
+        let scheduler = Scheduler::new(indexify_state.clone());
+        scheduler.run_scheduler().await?;
+        let task = indexify_state
+            .reader()
+            .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", &invocation_id, None, None)?
+            .0
+            .remove(0);
+        state_store
+            .finalize_task(&task, 1, TaskOutcome::Failure, false)
+            .await?;
+        scheduler.run_scheduler().await?;
+        assert_eq!(indexify_state.reader().analytics_records(10)?.len(), 2);
+
+        let exporter = AnalyticsExporter::new(
+            indexify_state.clone(),
+            AnalyticsExportConfig {
+                clickhouse_url: Some(format!("http://{}/", addr)),
+                ..Default::default()
+            },
+        )?;
+        clickhouse.lock().unwrap().fail = true;
+        assert!(exporter.export_batch().await.is_err());
+        // Records are kept until they're inserted.
+        assert_eq!(indexify_state.reader().analytics_records(10)?.len(), 2);
+        clickhouse.lock().unwrap().fail = false;
+        assert_eq!(exporter.export_batch().await?, 2);
+        assert!(indexify_state.reader().analytics_records(10)?.is_empty());
+        assert_eq!(exporter.export_batch().await?, 0);
+
+        let inserts = &clickhouse.lock().unwrap().inserts;
+        assert_eq!(inserts.len(), 2);
+        assert_eq!(
+            inserts[0].0,
+            "INSERT INTO default.indexify_tasks FORMAT JSONEachRow"
+        );
+        assert_eq!(inserts[0].1[0]["task_id"], task.id.to_string());
+        assert_eq!(inserts[0].1[0]["outcome"], "Failure");
+        assert_eq!(
+            inserts[1].0,
+            "INSERT INTO default.indexify_invocations FORMAT JSONEachRow"
+        );
+        assert_eq!(inserts[1].1[0]["invocation_id"], invocation_id);
+        assert_eq!(inserts[1].1[0]["failed_tasks"], 1);
+        Ok(())
+    }
+}
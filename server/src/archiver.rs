@@ -0,0 +1,273 @@
+use std::{io::Write, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use blob_store::registry::BlobStorageRegistry;
+use bytes::Bytes;
+use data_model::{ArchivedInvocation, GraphInvocationCtx, InvocationPayload, NodeOutput, Task};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use futures::stream;
+use indexify_utils::clock::{Clock, SystemClock};
+use serde::{Deserialize, Serialize};
+use state_store::{
+    requests::{ArchiveInvocationRequest, RequestPayload, StateMachineUpdateRequest},
+    IndexifyState,
+};
+use tracing::{error, info};
+
+use crate::config::ArchivalConfig;
+
+const ARCHIVE_BATCH_SIZE: usize = 100;
+const MS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+/// Everything the state store knew about an invocation when it was archived.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InvocationArchive {
+    pub invocation: InvocationPayload,
+    pub context: GraphInvocationCtx,
+    pub tasks: Vec<Task>,
+    pub outputs: Vec<NodeOutput>,
+}
+
+impl InvocationArchive {
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        serde_json::to_writer(&mut encoder, self)?;
+        encoder.flush()?;
+        Ok(encoder.finish()?)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        Ok(serde_json::from_reader(GzDecoder::new(bytes))?)
+    }
+}
+
+pub fn archive_path(namespace: &str, compute_graph: &str, invocation_id: &str) -> String {
+    format!(
+        "archives/{}/{}/{}.json.gz",
+        namespace, compute_graph, invocation_id
+    )
+}
+
+/// Periodically moves finished invocations older than the configured age out
+/// of the state store and into compressed archives in blob storage.
+pub struct Archiver {
+    state: Arc<IndexifyState>,
+    storage: Arc<BlobStorageRegistry>,
+    config: ArchivalConfig,
+    shutdown_rx: tokio::sync::watch::Receiver<()>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Archiver {
+    pub fn new(
+        state: Arc<IndexifyState>,
+        storage: Arc<BlobStorageRegistry>,
+        config: ArchivalConfig,
+        shutdown_rx: tokio::sync::watch::Receiver<()>,
+    ) -> Self {
+        Self {
+            state,
+            storage,
+            config,
+            shutdown_rx,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub async fn start(&mut self) -> Result<()> {
+        let Some(max_age_days) = self.config.max_age_days else {
+            info!("invocation archival is disabled");
+            return Ok(());
+        };
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    let cutoff = self.clock.now_ms().saturating_sub(max_age_days * MS_PER_DAY);
+                    match self.archive_created_before(cutoff).await {
+                        Ok(0) => {}
+                        Ok(archived) => info!("archived {} invocations", archived),
+                        Err(e) => error!("error archiving invocations: {:?}", e),
+                    }
+                }
+                _ = self.shutdown_rx.changed() => {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Archives every finished invocation created before the cutoff. Stops at
+    /// the first failure so that an invocation which can't be archived isn't
+    /// retried in a tight loop.
+    pub async fn archive_created_before(&self, cutoff: u64) -> Result<usize> {
+        let mut archived = 0;
+        loop {
+            let invocations = self
+                .state
+                .reader()
+                .archivable_invocations(cutoff, ARCHIVE_BATCH_SIZE)?;
+            if invocations.is_empty() {
+                return Ok(archived);
+            }
+            for invocation in invocations {
+                self.archive_invocation(invocation).await?;
+                archived += 1;
+            }
+        }
+    }
+
+    async fn archive_invocation(&self, invocation: InvocationPayload) -> Result<()> {
+        let reader = self.state.snapshot_reader();
+        let (namespace, compute_graph, invocation_id) = (
+            invocation.namespace.clone(),
+            invocation.compute_graph_name.clone(),
+            invocation.id.clone(),
+        );
+        let context = reader.invocation_ctx(&namespace, &compute_graph, &invocation_id)?;
+        let (tasks, _) = reader.list_tasks_by_compute_graph(
+            &namespace,
+            &compute_graph,
+            &invocation_id,
+            None,
+            None,
+        )?;
+        let (outputs, _) = reader.list_outputs_by_compute_graph(
+            &namespace,
+            &compute_graph,
+            &invocation_id,
+            None,
+            None,
+        )?;
+        let created_at = invocation.created_at;
+        let archive = InvocationArchive {
+            invocation,
+            context,
+            tasks,
+            outputs,
+        };
+        let data = Bytes::from(archive.encode()?);
+        let put_result = self
+            .storage
+            .for_namespace(&namespace)
+            .put(
+                &archive_path(&namespace, &compute_graph, &invocation_id),
+                Box::pin(stream::once(async { Ok(data) })),
+            )
+            .await?;
+        self.state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::ArchiveInvocation(ArchiveInvocationRequest {
+                    archive: ArchivedInvocation {
+                        namespace,
+                        compute_graph_name: compute_graph,
+                        invocation_id,
+                        url: put_result.url,
+                        created_at,
+                        archived_at: self.clock.now_ms(),
+                    },
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use blob_store::{BlobStorage, BlobStorageConfig};
+    use data_model::test_objects::tests::{mock_graph_a, mock_invocation_payload, TEST_NAMESPACE};
+    use state_store::{
+        requests::{CreateComputeGraphRequest, InvokeComputeGraphRequest},
+        serializer::{JsonEncode, JsonEncoder},
+        state_machine::IndexifyObjectsColumns,
+    };
+    use tokio::sync::watch;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_archive_finished_invocation() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let storage = Arc::new(BlobStorageRegistry::new(Arc::new(BlobStorage::new(
+            BlobStorageConfig::new_disk(temp_dir.path().join("blobs").to_str().unwrap()),
+        )?)));
+        let (_tx, rx) = watch::channel(());
+        let archiver = Archiver::new(
+            state.clone(),
+            storage.clone(),
+            ArchivalConfig::default(),
+            rx,
+        );
+
+        state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                    precondition: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invocation = mock_invocation_payload();
+        state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: invocation.compute_graph_name.clone(),
+                    invocation_payload: invocation.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        // Running invocations are never archived.
+        assert_eq!(archiver.archive_created_before(u64::MAX).await?, 0);
+
+        let mut ctx = state
+            .reader()
+            .invocation_ctx(TEST_NAMESPACE, "graph_A", &invocation.id)?;
+        ctx.completed = true;
+        // The stored invocation carries the provenance recorded when it started.
+        let stored =
+            state
+                .reader()
+                .invocation_payload(TEST_NAMESPACE, "graph_A", &invocation.id)?;
+        assert!(stored.provenance.is_some());
+        state.db.put_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&state.db),
+            ctx.key(),
+            JsonEncoder::encode(IndexifyObjectsColumns::GraphInvocationCtx, ctx.key(), &ctx)?,
+        )?;
+        assert_eq!(
+            archiver
+                .archive_created_before(invocation.created_at)
+                .await?,
+            0
+        );
+        assert_eq!(archiver.archive_created_before(u64::MAX).await?, 1);
+
+        let reader = state.reader();
+        assert!(reader
+            .invocation_payload(TEST_NAMESPACE, "graph_A", &invocation.id)
+            .is_err());
+        assert!(reader
+            .invocation_ctx(TEST_NAMESPACE, "graph_A", &invocation.id)
+            .is_err());
+        let (archived, _) =
+            reader.list_archived_invocations(TEST_NAMESPACE, "graph_A", None, None)?;
+        assert_eq!(archived.len(), 1);
+
+        let archive = InvocationArchive::decode(&storage.read_bytes(&archived[0].url).await?)?;
+        assert_eq!(archive.invocation, stored);
+        assert!(archive.context.completed);
+        Ok(())
+    }
+}
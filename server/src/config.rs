@@ -1,18 +1,508 @@
-use std::{env, fmt::Debug, net::SocketAddr};
+use std::{
+    collections::HashMap,
+    env,
+    fmt::{Debug, Display, Formatter},
+    net::SocketAddr,
+    path::Path,
+};
 
-use anyhow::Result;
-use blob_store::BlobStorageConfig;
+use anyhow::{anyhow, Result};
+use blob_store::{BlobStorageConfig, S3Config, S3Credentials};
+use data_model::BlobStoragePlacement;
 use figment::{
     providers::{Format, Yaml},
     Figment,
 };
 use serde::{Deserialize, Serialize};
+use state_store::{options::StateStoreOptions, serializer::Encryption};
+
+/// Upper bound for `blob_storage.inline_threshold_bytes`.
+const MAX_INLINE_THRESHOLD_BYTES: u64 = 1024 * 1024;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub state_store_path: String,
+    #[serde(default)]
+    pub state_store: StateStoreOptions,
     pub listen_addr: String,
     pub blob_storage: BlobStorageConfig,
+    #[serde(default)]
+    pub fetcher: FetcherConfig,
+    #[serde(default)]
+    pub webhooks: WebhookConfig,
+    #[serde(default)]
+    pub archival: ArchivalConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    #[serde(default)]
+    pub executor_admission: ExecutorAdmissionConfig,
+    #[serde(default)]
+    pub invocation_admission: InvocationAdmissionConfig,
+    #[serde(default)]
+    pub disk_quota: DiskQuotaConfig,
+    #[serde(default)]
+    pub request_log: RequestLogConfig,
+    #[serde(default)]
+    pub backup: BackupConfig,
+    #[serde(default)]
+    pub test_invocations: TestInvocationConfig,
+    #[serde(default)]
+    pub analytics_export: AnalyticsExportConfig,
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+    #[serde(default)]
+    pub prefetch: PrefetchConfig,
+    #[serde(default)]
+    pub alerting: AlertingConfig,
+    #[serde(default)]
+    pub scheduler: SchedulerConfig,
+    #[serde(default)]
+    pub api_keys: Vec<ApiKeyConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FetcherConfig {
+    pub workers: usize,
+    pub queue_size: usize,
+    pub max_size_bytes: u64,
+    pub max_retries: u32,
+    /// S3 buckets any namespace can invoke graphs with objects of, besides
+    /// the bucket of its own blob storage placement. Objects are read with
+    /// the server's credentials.
+    pub allowed_buckets: Vec<String>,
+    /// Lets http urls resolve to loopback, private and link-local addresses,
+    /// for deployments where the objects are served from the same network.
+    pub allow_private_addresses: bool,
+}
+
+impl Default for FetcherConfig {
+    fn default() -> Self {
+        FetcherConfig {
+            workers: 4,
+            queue_size: 1024,
+            max_size_bytes: 1 << 30,
+            max_retries: 3,
+            allowed_buckets: Vec::new(),
+            allow_private_addresses: false,
+        }
+    }
+}
+
+/// Requests to the urls tenants supply, the ones of subscriptions and of the
+/// webhooks graph owners are alerted on. They are only sent to public
+/// addresses and redirects aren't followed.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// Lets the urls resolve to loopback, private and link-local addresses,
+    /// for deployments where the receivers run in the same network.
+    pub allow_private_addresses: bool,
+}
+
+/// Finished invocations older than `max_age_days` are moved out of the state
+/// store into compressed archives in blob storage. Archival is disabled when
+/// no maximum age is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArchivalConfig {
+    pub max_age_days: Option<u64>,
+    pub interval_secs: u64,
+}
+
+impl Default for ArchivalConfig {
+    fn default() -> Self {
+        ArchivalConfig {
+            max_age_days: None,
+            interval_secs: 3600,
+        }
+    }
+}
+
+/// Finished invocations created more than `ttl_secs` ago are deleted from
+/// the state store along with their tasks and outputs. Invocations are kept
+/// forever when no TTL is set. With archival enabled, invocations older than
+/// the archival age are archived before they can expire. The output
+/// retention of graphs is applied every `interval_secs` as well.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    pub ttl_secs: Option<u64>,
+    pub interval_secs: u64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        RetentionConfig {
+            ttl_secs: None,
+            interval_secs: 600,
+        }
+    }
+}
+
+/// Policy consulted whenever an executor registers. Default labels are added
+/// to executors which don't set them before the required labels are checked,
+/// then the webhook, if any, gets the final say.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ExecutorAdmissionConfig {
+    pub required_labels: Vec<String>,
+    pub default_labels: HashMap<String, serde_json::Value>,
+    pub webhook: Option<AdmissionWebhookConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdmissionWebhookConfig {
+    pub url: String,
+    #[serde(default = "default_webhook_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Admits executors when the webhook can't be reached instead of
+    /// rejecting them.
+    #[serde(default)]
+    pub fail_open: bool,
+}
+
+fn default_webhook_timeout_secs() -> u64 {
+    5
+}
+
+/// Turns new invocations away while the server is falling behind, instead of
+/// accepting work it can't schedule. Pressure is the highest ratio of a
+/// sampled signal to its limit: invocations are delayed once it passes
+/// `delay_threshold`, and rejected with a Retry-After hint once a limit is
+/// reached. Signals without a limit are ignored, so admission is disabled
+/// when none is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InvocationAdmissionConfig {
+    /// Mean latency of state store writes, which grows when RocksDB stalls
+    /// writes.
+    pub max_write_latency_ms: Option<u64>,
+    /// Tasks waiting for an executor and state changes waiting for the
+    /// scheduler.
+    pub max_queue_depth: Option<u64>,
+    pub delay_threshold: f64,
+    pub max_delay_ms: u64,
+    pub retry_after_secs: u64,
+    pub sample_interval_ms: u64,
+}
+
+impl Default for InvocationAdmissionConfig {
+    fn default() -> Self {
+        InvocationAdmissionConfig {
+            max_write_latency_ms: None,
+            max_queue_depth: None,
+            delay_threshold: 0.8,
+            max_delay_ms: 1000,
+            retry_after_secs: 5,
+            sample_interval_ms: 1000,
+        }
+    }
+}
+
+/// Limits of the disk space used by the state store. Crossing the soft limit
+/// logs a warning; past the hard limit only the writes needed to finish
+/// accepted work and free space are accepted, so that the disk doesn't fill
+/// up and corrupt the store. Both limits are disabled by default.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiskQuotaConfig {
+    pub soft_limit_bytes: Option<u64>,
+    pub hard_limit_bytes: Option<u64>,
+    pub check_interval_secs: u64,
+}
+
+impl Default for DiskQuotaConfig {
+    fn default() -> Self {
+        DiskQuotaConfig {
+            soft_limit_bytes: None,
+            hard_limit_bytes: None,
+            check_interval_secs: 30,
+        }
+    }
+}
+
+/// Logging of HTTP requests for debugging. Bodies of a sample of the requests
+/// up to `max_body_bytes` are logged at the debug level when `log_bodies` is
+/// set. Only authenticated requests are logged, with the credentials in their
+/// JSON bodies redacted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestLogConfig {
+    pub log_bodies: bool,
+    pub sample_rate: f64,
+    pub max_body_bytes: usize,
+}
+
+impl Default for RequestLogConfig {
+    fn default() -> Self {
+        RequestLogConfig {
+            log_bodies: false,
+            sample_rate: 0.01,
+            max_body_bytes: 4096,
+        }
+    }
+}
+
+/// Backups of the state store, taken into `path` through the API while the
+/// server runs. The oldest backups are removed past `max_backups`. Backups
+/// are disabled when no path is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupConfig {
+    pub path: Option<String>,
+    pub max_backups: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        BackupConfig {
+            path: None,
+            max_backups: 7,
+        }
+    }
+}
+
+/// Test invocations run a graph on sample inputs. Those which haven't
+/// finished within `timeout_secs` are deleted along with their tasks, the
+/// finished ones are kept for `retention_secs` for their outputs to be
+/// inspected.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TestInvocationConfig {
+    pub timeout_secs: u64,
+    pub retention_secs: u64,
+    /// Inputs of one test of a graph.
+    pub max_inputs: usize,
+    pub interval_secs: u64,
+}
+
+impl Default for TestInvocationConfig {
+    fn default() -> Self {
+        TestInvocationConfig {
+            timeout_secs: 300,
+            retention_secs: 3600,
+            max_inputs: 10,
+            interval_secs: 30,
+        }
+    }
+}
+
+/// Export of finished tasks and invocations to ClickHouse through its HTTP
+/// interface, for analytics which would be too heavy on the state store.
+/// Records are kept in the state store until they're inserted, in batches of
+/// up to `batch_size` every `interval_secs`. The export is disabled when no
+/// url is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalyticsExportConfig {
+    pub clickhouse_url: Option<String>,
+    pub database: String,
+    pub tasks_table: String,
+    pub invocations_table: String,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub batch_size: usize,
+    pub interval_secs: u64,
+}
+
+impl Default for AnalyticsExportConfig {
+    fn default() -> Self {
+        AnalyticsExportConfig {
+            clickhouse_url: None,
+            database: "default".to_string(),
+            tasks_table: "indexify_tasks".to_string(),
+            invocations_table: "indexify_invocations".to_string(),
+            user: None,
+            password: None,
+            batch_size: 1000,
+            interval_secs: 30,
+        }
+    }
+}
+
+/// Tasks allocated to an executor ahead of their completion. Executors ask
+/// for a window when they connect, `default_window` applies to the ones
+/// which don't, and windows are capped at `max_window`. Short tasks do best
+/// with a large window, sparing a round trip between tasks, long ones with a
+/// small one, leaving the tasks an executor won't start soon to the others.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrefetchConfig {
+    pub default_window: u32,
+    pub max_window: u32,
+}
+
+impl Default for PrefetchConfig {
+    fn default() -> Self {
+        PrefetchConfig {
+            default_window: 10,
+            max_window: 100,
+        }
+    }
+}
+
+impl PrefetchConfig {
+    /// Window granted to an executor asking for `requested`.
+    pub fn window(&self, requested: Option<u32>) -> u32 {
+        requested
+            .unwrap_or(self.default_window)
+            .clamp(1, self.max_window.max(1))
+    }
+}
+
+/// Backpressure of the scheduler. While `max_unallocated_tasks` tasks wait
+/// for an executor, the scheduler stops turning state changes into new tasks
+/// and only places the waiting ones, until the backlog falls under 80% of the
+/// limit. With a `target_write_latency_ms`, the scheduler processes up to
+/// `max_batch_size` state changes at a time while its writes commit within
+/// the target, and halves its batches and waits between them when they
+/// don't.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    pub max_unallocated_tasks: Option<u64>,
+    pub target_write_latency_ms: Option<u64>,
+    pub max_batch_size: usize,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        SchedulerConfig {
+            max_unallocated_tasks: None,
+            target_write_latency_ms: None,
+            max_batch_size: 100,
+        }
+    }
+}
+
+/// Delivery of the alerts raised by failing graphs. Alerts go to the owners
+/// declared by their graph, the ones of graphs without owners go to
+/// `default_webhook`, and aren't raised when it isn't set. Slack channels
+/// are posted to through `slack_webhook_url` and emails are handed to
+/// `email_relay_url`, as JSON with `to`, `subject` and `body`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AlertingConfig {
+    pub default_webhook: Option<String>,
+    pub slack_webhook_url: Option<String>,
+    pub email_relay_url: Option<String>,
+    pub batch_size: usize,
+    pub interval_secs: u64,
+}
+
+impl Default for AlertingConfig {
+    fn default() -> Self {
+        AlertingConfig {
+            default_webhook: None,
+            slack_webhook_url: None,
+            email_relay_url: None,
+            batch_size: 100,
+            interval_secs: 10,
+        }
+    }
+}
+
+/// Encryption at rest of the records of the state store with AES-256-GCM.
+/// The 32 bytes key, hex encoded, is read from the environment variable
+/// named by `key_env` or from `key_file`, e.g. where a KMS agent or a
+/// secrets manager mounts it. Encryption is disabled when neither is set.
+/// Records written before encryption was enabled stay readable.
+///
+/// Records store the `key_id` of the key that encrypted them. Keys are
+/// rotated by giving the new key another id and moving the old one to
+/// `previous_keys`, which only decrypt.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    pub key_env: Option<String>,
+    pub key_file: Option<String>,
+    pub key_id: u8,
+    pub previous_keys: Vec<PreviousEncryptionKey>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreviousEncryptionKey {
+    pub key_id: u8,
+    pub key_env: Option<String>,
+    pub key_file: Option<String>,
+}
+
+fn read_encryption_key(
+    key_env: &Option<String>,
+    key_file: &Option<String>,
+) -> Result<Option<String>> {
+    match (key_env, key_file) {
+        (Some(_), Some(_)) => Err(anyhow!("only one of key_env and key_file can be set")),
+        (Some(var), None) => env::var(var)
+            .map(Some)
+            .map_err(|e| anyhow!("unable to read environment variable {}: {}", var, e)),
+        (None, Some(path)) => std::fs::read_to_string(path)
+            .map(Some)
+            .map_err(|e| anyhow!("unable to read key file {}: {}", path, e)),
+        (None, None) => Ok(None),
+    }
+}
+
+impl EncryptionConfig {
+    pub fn key(&self) -> Result<Option<String>> {
+        read_encryption_key(&self.key_env, &self.key_file)
+    }
+
+    /// Encryption of the configured keys, none when no key is set.
+    pub fn encryption(&self) -> Result<Option<Encryption>> {
+        let Some(key) = self.key()? else {
+            if !self.previous_keys.is_empty() {
+                return Err(anyhow!("previous_keys are set without a current key"));
+            }
+            return Ok(None);
+        };
+        let mut encryption = Encryption::from_hex(self.key_id, &key)?;
+        for previous in &self.previous_keys {
+            let key =
+                read_encryption_key(&previous.key_env, &previous.key_file)?.ok_or_else(|| {
+                    anyhow!(
+                        "one of key_env and key_file must be set for key {}",
+                        previous.key_id
+                    )
+                })?;
+            encryption = encryption.with_previous_key(previous.key_id, &key)?;
+        }
+        Ok(Some(encryption))
+    }
+}
+
+/// A key clients pass in an `Authorization: Bearer` header, read from the
+/// environment variable named by `key_env` or from `key_file`. A key limited
+/// to `namespaces` only reaches the routes of those namespaces, others look
+/// like they don't exist. Keys without namespaces reach every route and are
+/// the ones executors use. The API is open when no keys are configured.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ApiKeyConfig {
+    pub key_env: Option<String>,
+    pub key_file: Option<String>,
+    pub namespaces: Option<Vec<String>>,
+}
+
+impl ApiKeyConfig {
+    pub fn key(&self) -> Result<String> {
+        let key = match (&self.key_env, &self.key_file) {
+            (Some(_), Some(_)) => {
+                return Err(anyhow!("only one of key_env and key_file can be set"))
+            }
+            (Some(var), None) => env::var(var)
+                .map_err(|e| anyhow!("unable to read environment variable {}: {}", var, e))?,
+            (None, Some(path)) => std::fs::read_to_string(path)
+                .map_err(|e| anyhow!("unable to read key file {}: {}", path, e))?,
+            (None, None) => return Err(anyhow!("one of key_env and key_file must be set")),
+        };
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(anyhow!("the key is empty"));
+        }
+        Ok(key.to_string())
+    }
 }
 
 impl Default for ServerConfig {
@@ -20,37 +510,560 @@ impl Default for ServerConfig {
         let state_store_path = env::current_dir().unwrap().join("indexify_storage/state");
         ServerConfig {
             state_store_path: state_store_path.to_str().unwrap().to_string(),
+            state_store: Default::default(),
             listen_addr: "0.0.0.0:8900".to_string(),
             blob_storage: Default::default(),
+            fetcher: Default::default(),
+            webhooks: Default::default(),
+            archival: Default::default(),
+            retention: Default::default(),
+            executor_admission: Default::default(),
+            invocation_admission: Default::default(),
+            disk_quota: Default::default(),
+            request_log: Default::default(),
+            backup: Default::default(),
+            test_invocations: Default::default(),
+            analytics_export: Default::default(),
+            encryption: Default::default(),
+            prefetch: Default::default(),
+            alerting: Default::default(),
+            scheduler: Default::default(),
+            api_keys: Default::default(),
         }
     }
 }
 
 impl ServerConfig {
     pub fn from_path(path: &str) -> Result<ServerConfig> {
-        let config_str = std::fs::read_to_string(path)?;
+        let config_str = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("unable to read config file {}: {}", path, e))?;
         let config: ServerConfig = Figment::new().merge(Yaml::string(&config_str)).extract()?;
         config.validate()?;
         Ok(config)
     }
 
+    /// Checks the whole configuration and reports every violation at once.
     pub fn validate(&self) -> Result<()> {
-        if self.blob_storage.s3.is_some() && self.blob_storage.disk.is_some() {
-            return Err(anyhow::anyhow!(
-                "cannot specify both s3 and disk blob storage"
+        let violations = self.violations();
+        if violations.is_empty() {
+            return Ok(());
+        }
+        let report = violations
+            .iter()
+            .map(|v| format!("  - {}", v))
+            .collect::<Vec<_>>()
+            .join("\n");
+        Err(anyhow!(
+            "invalid server configuration, {} problem(s) found:\n{}",
+            violations.len(),
+            report
+        ))
+    }
+
+    pub fn violations(&self) -> Vec<ConfigViolation> {
+        let mut violations = Vec::new();
+        if self.listen_addr.parse::<SocketAddr>().is_err() {
+            violations.push(ConfigViolation::new(
+                "listen_addr",
+                format!("invalid listen address: {}", self.listen_addr),
+                "use an <ip>:<port> pair such as 0.0.0.0:8900",
             ));
         }
-        if self.blob_storage.s3.is_none() && self.blob_storage.disk.is_none() {
-            return Err(anyhow::anyhow!(
-                "must specify one of s3 or disk blob storage"
+        if let Err(e) = check_writable_dir(&self.state_store_path) {
+            violations.push(ConfigViolation::new(
+                "state_store_path",
+                format!("{} is not usable: {}", self.state_store_path, e),
+                "point it at a directory the server can create and write to",
             ));
         }
-        if self.listen_addr.parse::<SocketAddr>().is_err() {
-            return Err(anyhow::anyhow!(
-                "invalid listen address: {}",
-                self.listen_addr
+        match (&self.blob_storage.s3, &self.blob_storage.disk) {
+            (Some(_), Some(_)) => violations.push(ConfigViolation::new(
+                "blob_storage",
+                "cannot specify both s3 and disk blob storage".to_string(),
+                "remove either the s3 or the disk section",
+            )),
+            (None, None) => violations.push(ConfigViolation::new(
+                "blob_storage",
+                "must specify one of s3 or disk blob storage".to_string(),
+                "add a disk section with a path or an s3 section with a bucket and region",
+            )),
+            (Some(s3), None) => {
+                if !blob_store::compiled_backends().contains(&"s3") {
+                    violations.push(ConfigViolation::new(
+                        "blob_storage.s3",
+                        "the server was built without s3 support".to_string(),
+                        "rebuild with the s3 feature or use disk blob storage",
+                    ));
+                }
+                if s3.bucket.is_empty() || s3.region.is_empty() {
+                    violations.push(ConfigViolation::new(
+                        "blob_storage.s3",
+                        "bucket and region must not be empty".to_string(),
+                        "set both blob_storage.s3.bucket and blob_storage.s3.region",
+                    ));
+                }
+                if let S3Credentials::WebIdentity {
+                    role_arn,
+                    token_file,
+                    ..
+                } = &s3.credentials
+                {
+                    if role_arn.is_empty() {
+                        violations.push(ConfigViolation::new(
+                            "blob_storage.s3.credentials.role_arn",
+                            "role_arn must not be empty".to_string(),
+                            "set it to the ARN of the role the workload identity assumes",
+                        ));
+                    }
+                    if let Err(e) = std::fs::metadata(token_file) {
+                        violations.push(ConfigViolation::new(
+                            "blob_storage.s3.credentials.token_file",
+                            format!("{} is not readable: {}", token_file, e),
+                            "point it at the projected service account token",
+                        ));
+                    }
+                }
+            }
+            (None, Some(disk)) => {
+                if let Err(e) = check_writable_dir(&disk.path) {
+                    violations.push(ConfigViolation::new(
+                        "blob_storage.disk.path",
+                        format!("{} is not usable: {}", disk.path, e),
+                        "point it at a directory the server can create and write to",
+                    ));
+                }
+            }
+        }
+        if let Some(threshold) = self.blob_storage.inline_threshold_bytes {
+            if threshold == 0 || threshold > MAX_INLINE_THRESHOLD_BYTES {
+                violations.push(ConfigViolation::new(
+                    "blob_storage.inline_threshold_bytes",
+                    format!("must be between 1 and {} bytes", MAX_INLINE_THRESHOLD_BYTES),
+                    "inline payloads live in the state store, keep it around 64KiB or remove it",
+                ));
+            }
+        }
+        if self.fetcher.workers == 0 {
+            violations.push(ConfigViolation::new(
+                "fetcher.workers",
+                "must be greater than zero".to_string(),
+                "remove the setting to use the default of 4 workers",
             ));
         }
-        Ok(())
+        if self.fetcher.queue_size == 0 {
+            violations.push(ConfigViolation::new(
+                "fetcher.queue_size",
+                "must be greater than zero".to_string(),
+                "remove the setting to use the default queue size",
+            ));
+        }
+        if self.fetcher.max_size_bytes == 0 {
+            violations.push(ConfigViolation::new(
+                "fetcher.max_size_bytes",
+                "must be greater than zero".to_string(),
+                "remove the setting to use the default limit of 1GiB",
+            ));
+        }
+        if self.archival.max_age_days == Some(0) {
+            violations.push(ConfigViolation::new(
+                "archival.max_age_days",
+                "must be greater than zero".to_string(),
+                "remove the setting to disable archival",
+            ));
+        }
+        if self.archival.interval_secs == 0 {
+            violations.push(ConfigViolation::new(
+                "archival.interval_secs",
+                "must be greater than zero".to_string(),
+                "remove the setting to archive once an hour",
+            ));
+        }
+        if self.retention.ttl_secs == Some(0) {
+            violations.push(ConfigViolation::new(
+                "retention.ttl_secs",
+                "must be greater than zero".to_string(),
+                "remove the setting to keep finished invocations",
+            ));
+        }
+        if self.retention.interval_secs == 0 {
+            violations.push(ConfigViolation::new(
+                "retention.interval_secs",
+                "must be greater than zero".to_string(),
+                "remove the setting to sweep every 10 minutes",
+            ));
+        }
+        if let Some(webhook) = &self.executor_admission.webhook {
+            if !webhook.url.starts_with("http://") && !webhook.url.starts_with("https://") {
+                violations.push(ConfigViolation::new(
+                    "executor_admission.webhook.url",
+                    format!("invalid webhook url: {}", webhook.url),
+                    "use an http:// or https:// url",
+                ));
+            }
+            if webhook.timeout_secs == 0 {
+                violations.push(ConfigViolation::new(
+                    "executor_admission.webhook.timeout_secs",
+                    "must be greater than zero".to_string(),
+                    "remove the setting to wait up to 5 seconds",
+                ));
+            }
+        }
+        let admission = &self.invocation_admission;
+        if admission.max_write_latency_ms == Some(0) {
+            violations.push(ConfigViolation::new(
+                "invocation_admission.max_write_latency_ms",
+                "must be greater than zero".to_string(),
+                "remove the setting to ignore write latency",
+            ));
+        }
+        if admission.max_queue_depth == Some(0) {
+            violations.push(ConfigViolation::new(
+                "invocation_admission.max_queue_depth",
+                "must be greater than zero".to_string(),
+                "remove the setting to ignore the queue depth",
+            ));
+        }
+        if !(admission.delay_threshold > 0.0 && admission.delay_threshold <= 1.0) {
+            violations.push(ConfigViolation::new(
+                "invocation_admission.delay_threshold",
+                format!("{} is not in (0, 1]", admission.delay_threshold),
+                "use a fraction of the limits such as 0.8, or 1 to never delay",
+            ));
+        }
+        if admission.sample_interval_ms == 0 {
+            violations.push(ConfigViolation::new(
+                "invocation_admission.sample_interval_ms",
+                "must be greater than zero".to_string(),
+                "remove the setting to sample once a second",
+            ));
+        }
+        let quota = &self.disk_quota;
+        if let (Some(soft), Some(hard)) = (quota.soft_limit_bytes, quota.hard_limit_bytes) {
+            if soft > hard {
+                violations.push(ConfigViolation::new(
+                    "disk_quota.soft_limit_bytes",
+                    format!("{} is above the hard limit of {}", soft, hard),
+                    "warn before writes are blocked with a soft limit below the hard limit",
+                ));
+            }
+        }
+        if quota.check_interval_secs == 0 {
+            violations.push(ConfigViolation::new(
+                "disk_quota.check_interval_secs",
+                "must be greater than zero".to_string(),
+                "remove the setting to check every 30 seconds",
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.request_log.sample_rate) {
+            violations.push(ConfigViolation::new(
+                "request_log.sample_rate",
+                format!("{} is not in [0, 1]", self.request_log.sample_rate),
+                "use the fraction of requests to log, such as 0.01",
+            ));
+        }
+        let state_store = &self.state_store;
+        for (field, value) in [
+            (
+                "state_store.block_cache_bytes",
+                state_store.block_cache_bytes,
+            ),
+            (
+                "state_store.write_buffer_bytes",
+                state_store.write_buffer_bytes,
+            ),
+        ] {
+            if value == Some(0) {
+                violations.push(ConfigViolation::new(
+                    field,
+                    "must be greater than zero".to_string(),
+                    "remove the setting to keep the default of RocksDB",
+                ));
+            }
+        }
+        if state_store
+            .max_background_jobs
+            .is_some_and(|jobs| jobs <= 0)
+        {
+            violations.push(ConfigViolation::new(
+                "state_store.max_background_jobs",
+                "must be greater than zero".to_string(),
+                "remove the setting to keep the default of RocksDB",
+            ));
+        }
+        if let Some(path) = &self.backup.path {
+            if let Err(e) = check_writable_dir(path) {
+                violations.push(ConfigViolation::new(
+                    "backup.path",
+                    format!("{} is not usable: {}", path, e),
+                    "point it at a directory the server can create and write to",
+                ));
+            }
+        }
+        if self.backup.max_backups == 0 {
+            violations.push(ConfigViolation::new(
+                "backup.max_backups",
+                "must be greater than zero".to_string(),
+                "remove the setting to keep the last 7 backups",
+            ));
+        }
+        let tests = &self.test_invocations;
+        if tests.max_inputs == 0 {
+            violations.push(ConfigViolation::new(
+                "test_invocations.max_inputs",
+                "must be greater than zero".to_string(),
+                "remove the setting to allow 10 inputs per test",
+            ));
+        }
+        if tests.interval_secs == 0 {
+            violations.push(ConfigViolation::new(
+                "test_invocations.interval_secs",
+                "must be greater than zero".to_string(),
+                "remove the setting to look for expired tests every 30 seconds",
+            ));
+        }
+        let analytics = &self.analytics_export;
+        if let Some(url) = &analytics.clickhouse_url {
+            if let Err(e) = reqwest::Url::parse(url) {
+                violations.push(ConfigViolation::new(
+                    "analytics_export.clickhouse_url",
+                    format!("{} is not a url: {}", url, e),
+                    "use the url of the HTTP interface, such as http://localhost:8123",
+                ));
+            }
+        }
+        if analytics.batch_size == 0 {
+            violations.push(ConfigViolation::new(
+                "analytics_export.batch_size",
+                "must be greater than zero".to_string(),
+                "remove the setting to insert up to 1000 records at a time",
+            ));
+        }
+        if analytics.interval_secs == 0 {
+            violations.push(ConfigViolation::new(
+                "analytics_export.interval_secs",
+                "must be greater than zero".to_string(),
+                "remove the setting to export every 30 seconds",
+            ));
+        }
+        let prefetch = &self.prefetch;
+        if prefetch.max_window == 0 {
+            violations.push(ConfigViolation::new(
+                "prefetch.max_window",
+                "must be greater than zero".to_string(),
+                "remove the setting to allow windows of up to 100 tasks",
+            ));
+        }
+        if prefetch.default_window == 0 || prefetch.default_window > prefetch.max_window {
+            violations.push(ConfigViolation::new(
+                "prefetch.default_window",
+                format!(
+                    "must be between 1 and prefetch.max_window ({})",
+                    prefetch.max_window
+                ),
+                "remove the setting to prefetch 10 tasks by default",
+            ));
+        }
+        let scheduler = &self.scheduler;
+        if scheduler.max_unallocated_tasks == Some(0) {
+            violations.push(ConfigViolation::new(
+                "scheduler.max_unallocated_tasks",
+                "must be greater than zero".to_string(),
+                "remove the setting to never pause the creation of tasks",
+            ));
+        }
+        if scheduler.target_write_latency_ms == Some(0) {
+            violations.push(ConfigViolation::new(
+                "scheduler.target_write_latency_ms",
+                "must be greater than zero".to_string(),
+                "remove the setting to process state changes in fixed batches",
+            ));
+        }
+        if scheduler.max_batch_size == 0 {
+            violations.push(ConfigViolation::new(
+                "scheduler.max_batch_size",
+                "must be greater than zero".to_string(),
+                "remove the setting to process up to 100 state changes at a time",
+            ));
+        }
+        let alerting = &self.alerting;
+        for (field, url) in [
+            ("alerting.default_webhook", &alerting.default_webhook),
+            ("alerting.slack_webhook_url", &alerting.slack_webhook_url),
+            ("alerting.email_relay_url", &alerting.email_relay_url),
+        ] {
+            if let Some(url) = url {
+                if let Err(e) = reqwest::Url::parse(url) {
+                    violations.push(ConfigViolation::new(
+                        field,
+                        format!("{} is not a url: {}", url, e),
+                        "use an http or https url",
+                    ));
+                }
+            }
+        }
+        if alerting.batch_size == 0 {
+            violations.push(ConfigViolation::new(
+                "alerting.batch_size",
+                "must be greater than zero".to_string(),
+                "remove the setting to deliver up to 100 alerts at a time",
+            ));
+        }
+        if alerting.interval_secs == 0 {
+            violations.push(ConfigViolation::new(
+                "alerting.interval_secs",
+                "must be greater than zero".to_string(),
+                "remove the setting to deliver alerts every 10 seconds",
+            ));
+        }
+        if let Err(e) = self.encryption.encryption() {
+            violations.push(ConfigViolation::new(
+                "encryption",
+                e.to_string(),
+                "set either key_env or key_file of every key to a readable key generated with \
+                 openssl rand -hex 32, each with its own key_id",
+            ));
+        }
+        for (i, api_key) in self.api_keys.iter().enumerate() {
+            if let Err(e) = api_key.key() {
+                violations.push(ConfigViolation::new(
+                    "api_keys",
+                    format!("key {}: {}", i, e),
+                    "set either key_env or key_file to a readable key",
+                ));
+            }
+            if api_key.namespaces.as_ref().is_some_and(Vec::is_empty) {
+                violations.push(ConfigViolation::new(
+                    "api_keys",
+                    format!("key {}: namespaces must not be empty", i),
+                    "list the namespaces of the key, or remove them to reach every namespace",
+                ));
+            }
+        }
+        violations
+    }
+}
+
+pub fn placement_blob_storage_config(placement: &BlobStoragePlacement) -> BlobStorageConfig {
+    match placement {
+        BlobStoragePlacement::S3 { bucket, region } => BlobStorageConfig {
+            s3: Some(S3Config {
+                bucket: bucket.clone(),
+                region: region.clone(),
+                credentials: Default::default(),
+            }),
+            disk: None,
+            inline_threshold_bytes: None,
+            namespace_disk_roots: Vec::new(),
+        },
+        BlobStoragePlacement::Disk { path } => BlobStorageConfig::new_disk(path),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigViolation {
+    pub field: &'static str,
+    pub message: String,
+    pub hint: &'static str,
+}
+
+impl ConfigViolation {
+    fn new(field: &'static str, message: String, hint: &'static str) -> Self {
+        Self {
+            field,
+            message,
+            hint,
+        }
+    }
+}
+
+impl Display for ConfigViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} (hint: {})", self.field, self.message, self.hint)
+    }
+}
+
+// The directory doesn't need to exist yet, but its closest existing ancestor
+// must be a writable directory so that it can be created on startup.
+fn check_writable_dir(path: &str) -> Result<()> {
+    if path.is_empty() {
+        return Err(anyhow!("path is empty"));
+    }
+    let mut current = Some(Path::new(path));
+    while let Some(dir) = current {
+        if let Ok(metadata) = std::fs::metadata(dir) {
+            if !metadata.is_dir() {
+                return Err(anyhow!("{} is not a directory", dir.display()));
+            }
+            if metadata.permissions().readonly() {
+                return Err(anyhow!("{} is read only", dir.display()));
+            }
+            return Ok(());
+        }
+        current = dir.parent().filter(|p| !p.as_os_str().is_empty());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_is_valid() {
+        assert!(ServerConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_reports_all_violations() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let file = temp_dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        let config = ServerConfig {
+            state_store_path: file.join("state").to_str().unwrap().to_string(),
+            state_store: Default::default(),
+            listen_addr: "localhost".to_string(),
+            blob_storage: BlobStorageConfig {
+                s3: None,
+                disk: None,
+                inline_threshold_bytes: Some(0),
+                namespace_disk_roots: Vec::new(),
+            },
+            fetcher: FetcherConfig {
+                workers: 0,
+                ..Default::default()
+            },
+            webhooks: Default::default(),
+            archival: Default::default(),
+            retention: Default::default(),
+            executor_admission: Default::default(),
+            invocation_admission: Default::default(),
+            disk_quota: Default::default(),
+            request_log: Default::default(),
+            backup: Default::default(),
+            test_invocations: Default::default(),
+            analytics_export: Default::default(),
+            encryption: Default::default(),
+            prefetch: Default::default(),
+            alerting: Default::default(),
+            scheduler: Default::default(),
+            api_keys: Default::default(),
+        };
+        let fields = config
+            .violations()
+            .iter()
+            .map(|v| v.field)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![
+                "listen_addr",
+                "state_store_path",
+                "blob_storage",
+                "blob_storage.inline_threshold_bytes",
+                "fetcher.workers"
+            ]
+        );
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("5 problem(s) found"));
     }
 }
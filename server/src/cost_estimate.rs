@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use data_model::{ComputeGraph, OutputPayload};
+use state_store::scanner::StateReader;
+
+/// Number of recent invocations of a graph whose history is used to
+/// estimate new ones.
+const HISTORY_SAMPLE_SIZE: usize = 100;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionEstimate {
+    pub compute_fn: String,
+    pub tasks: f64,
+    pub cpu_seconds: f64,
+    pub gpu_seconds: f64,
+    pub output_bytes: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CostEstimate {
+    pub invocations: u64,
+    pub sampled_invocations: u64,
+    pub functions: Vec<FunctionEstimate>,
+    pub tasks: f64,
+    pub cpu_seconds: f64,
+    pub gpu_seconds: f64,
+    pub storage_bytes: f64,
+}
+
+#[derive(Default)]
+struct FunctionHistory {
+    tasks: u64,
+    output_bytes: u64,
+}
+
+/// Estimates the cost of running a number of invocations of the graph from
+/// the finished invocations among its most recent ones. Without history every
+/// function is assumed to run once per invocation.
+///
+/// When an input size is given, compute and output storage are scaled by its
+/// ratio to the average input size seen so far; task counts are not.
+pub fn estimate_cost(
+    reader: &StateReader,
+    compute_graph: &ComputeGraph,
+    invocations: u64,
+    input_size_bytes: Option<u64>,
+) -> Result<CostEstimate> {
+    let (namespace, name) = (&compute_graph.namespace, &compute_graph.name);
+    let mut history: HashMap<String, FunctionHistory> = HashMap::new();
+    let mut sampled_invocations = 0;
+    let mut sampled_input_bytes = 0;
+    let (recent, _) = reader.list_invocations(namespace, name, None, Some(HISTORY_SAMPLE_SIZE))?;
+    for invocation in recent {
+        let ctx = reader.invocation_ctx(namespace, name, &invocation.id)?;
+        if !ctx.completed {
+            continue;
+        }
+        sampled_invocations += 1;
+        sampled_input_bytes += invocation.payload.size;
+        for (compute_fn, analytics) in ctx.fn_task_analytics {
+            history.entry(compute_fn).or_default().tasks +=
+                analytics.successful_tasks + analytics.failed_tasks;
+        }
+        let (outputs, _) =
+            reader.list_outputs_by_compute_graph(namespace, name, &invocation.id, None, None)?;
+        for output in outputs {
+            if let OutputPayload::Fn(payload) = output.payload {
+                history
+                    .entry(output.compute_fn_name)
+                    .or_default()
+                    .output_bytes += payload.size;
+            }
+        }
+    }
+
+    let avg_input_bytes = match sampled_invocations {
+        0 => None,
+        n => Some(sampled_input_bytes as f64 / n as f64),
+    };
+    let input_bytes = input_size_bytes
+        .map(|size| size as f64)
+        .or(avg_input_bytes)
+        .unwrap_or_default();
+    let input_scale = match avg_input_bytes {
+        Some(avg) if avg > 0.0 => input_bytes / avg,
+        _ => 1.0,
+    };
+
+    let usage = reader.fn_resource_usage(namespace, name)?;
+    let mut functions = Vec::new();
+    for compute_fn in compute_graph.nodes.keys() {
+        let fn_history = history.remove(compute_fn).unwrap_or_default();
+        let (tasks_per_invocation, output_bytes_per_invocation) = match sampled_invocations {
+            0 => (1.0, 0.0),
+            n => (
+                fn_history.tasks as f64 / n as f64,
+                fn_history.output_bytes as f64 / n as f64,
+            ),
+        };
+        let tasks = tasks_per_invocation * invocations as f64;
+        let fn_usage = usage.get(compute_fn).cloned().unwrap_or_default();
+        functions.push(FunctionEstimate {
+            compute_fn: compute_fn.clone(),
+            tasks,
+            cpu_seconds: tasks * fn_usage.avg_cpu_seconds * input_scale,
+            gpu_seconds: tasks * fn_usage.avg_gpu_seconds * input_scale,
+            output_bytes: output_bytes_per_invocation * invocations as f64 * input_scale,
+        });
+    }
+    functions.sort_by(|a, b| a.compute_fn.cmp(&b.compute_fn));
+
+    Ok(CostEstimate {
+        invocations,
+        sampled_invocations,
+        tasks: functions.iter().map(|f| f.tasks).sum(),
+        cpu_seconds: functions.iter().map(|f| f.cpu_seconds).sum(),
+        gpu_seconds: functions.iter().map(|f| f.gpu_seconds).sum(),
+        storage_bytes: input_bytes * invocations as f64 +
+            functions.iter().map(|f| f.output_bytes).sum::<f64>(),
+        functions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::{
+        test_objects::tests::{mock_graph_a, mock_invocation_payload, TEST_NAMESPACE},
+        TaskAnalytics,
+    };
+    use state_store::{
+        requests::{
+            CreateComputeGraphRequest,
+            InvokeComputeGraphRequest,
+            RequestPayload,
+            StateMachineUpdateRequest,
+        },
+        serializer::{JsonEncode, JsonEncoder},
+        state_machine::IndexifyObjectsColumns,
+        IndexifyState,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_estimate_from_history() -> Result<()> {
+        let state = IndexifyState::in_memory().await?;
+        let graph = mock_graph_a();
+        state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph.clone(),
+                    precondition: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        // Without history every function runs once per invocation.
+        let estimate = estimate_cost(&state.reader(), &graph, 10, Some(100))?;
+        assert_eq!(estimate.sampled_invocations, 0);
+        assert_eq!(estimate.tasks, 30.0);
+        assert_eq!(estimate.storage_bytes, 1000.0);
+
+        let invocation = mock_invocation_payload();
+        state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: invocation.compute_graph_name.clone(),
+                    invocation_payload: invocation.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let mut ctx = state
+            .reader()
+            .invocation_ctx(TEST_NAMESPACE, "graph_A", &invocation.id)?;
+        ctx.completed = true;
+        ctx.fn_task_analytics = HashMap::from([
+            (
+                "fn_a".to_string(),
+                TaskAnalytics {
+                    successful_tasks: 1,
+                    ..Default::default()
+                },
+            ),
+            (
+                "fn_b".to_string(),
+                TaskAnalytics {
+                    successful_tasks: 2,
+                    failed_tasks: 1,
+                    ..Default::default()
+                },
+            ),
+        ]);
+        state.db.put_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&state.db),
+            ctx.key(),
+            JsonEncoder::encode(IndexifyObjectsColumns::GraphInvocationCtx, ctx.key(), &ctx)?,
+        )?;
+
+        let estimate = estimate_cost(&state.reader(), &graph, 10, None)?;
+        assert_eq!(estimate.sampled_invocations, 1);
+        let tasks = estimate
+            .functions
+            .iter()
+            .map(|f| (f.compute_fn.as_str(), f.tasks))
+            .collect::<Vec<_>>();
+        assert_eq!(tasks, vec![("fn_a", 10.0), ("fn_b", 30.0), ("fn_c", 0.0)]);
+        assert_eq!(
+            estimate.storage_bytes,
+            10.0 * invocation.payload.size as f64
+        );
+        Ok(())
+    }
+}
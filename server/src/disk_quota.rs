@@ -0,0 +1,256 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::Result;
+use data_model::activity::Activity;
+use state_store::{
+    metrics::DiskUsageLabels,
+    requests::{RecordActivityRequest, RequestPayload, StateMachineUpdateRequest},
+    IndexifyState,
+};
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+use crate::config::DiskQuotaConfig;
+
+/// Bytes on disk of the state store: its write ahead log, its sorted tables
+/// and everything else, e.g. manifests and info logs.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DiskUsage {
+    pub wal_bytes: u64,
+    pub sst_bytes: u64,
+    pub other_bytes: u64,
+}
+
+impl DiskUsage {
+    pub fn measure(path: &Path) -> Result<DiskUsage> {
+        let mut usage = DiskUsage::default();
+        let mut dirs = vec![path.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let entry = entry?;
+                let metadata = entry.metadata()?;
+                if metadata.is_dir() {
+                    dirs.push(entry.path());
+                    continue;
+                }
+                let bytes = match entry.path().extension().and_then(|ext| ext.to_str()) {
+                    Some("log") => &mut usage.wal_bytes,
+                    Some("sst") => &mut usage.sst_bytes,
+                    _ => &mut usage.other_bytes,
+                };
+                *bytes += metadata.len();
+            }
+        }
+        Ok(usage)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.wal_bytes + self.sst_bytes + self.other_bytes
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuotaState {
+    Ok,
+    OverSoftLimit,
+    OverHardLimit,
+}
+
+/// Samples the disk usage of the state store, reports it as metrics and
+/// blocks writes bringing in new work past the hard limit, see
+/// `DiskQuotaConfig`.
+pub struct DiskQuota {
+    config: DiskQuotaConfig,
+    path: PathBuf,
+    state: Arc<IndexifyState>,
+    last_state: Mutex<QuotaState>,
+}
+
+impl DiskQuota {
+    pub fn new(config: DiskQuotaConfig, path: PathBuf, state: Arc<IndexifyState>) -> Self {
+        Self {
+            config,
+            path,
+            state,
+            last_state: Mutex::new(QuotaState::Ok),
+        }
+    }
+
+    pub fn quota_state(&self, usage: &DiskUsage) -> QuotaState {
+        let total = usage.total();
+        if self
+            .config
+            .hard_limit_bytes
+            .is_some_and(|limit| total >= limit)
+        {
+            QuotaState::OverHardLimit
+        } else if self
+            .config
+            .soft_limit_bytes
+            .is_some_and(|limit| total >= limit)
+        {
+            QuotaState::OverSoftLimit
+        } else {
+            QuotaState::Ok
+        }
+    }
+
+    /// Measures the usage and applies the limits.
+    pub async fn check(&self) -> Result<QuotaState> {
+        let path = self.path.clone();
+        let usage = tokio::task::spawn_blocking(move || DiskUsage::measure(&path)).await??;
+        let metrics = &self.state.metrics;
+        for (component, bytes) in [
+            ("wal", usage.wal_bytes),
+            ("sst", usage.sst_bytes),
+            ("other", usage.other_bytes),
+        ] {
+            metrics
+                .disk_usage
+                .get_or_create(&DiskUsageLabels {
+                    component: component.to_string(),
+                })
+                .set(bytes as i64);
+        }
+        let current = self.quota_state(&usage);
+        self.state
+            .writes_blocked
+            .store(current == QuotaState::OverHardLimit, Ordering::Release);
+        metrics
+            .disk_quota_exceeded
+            .set((current == QuotaState::OverHardLimit) as i64);
+        let changed = {
+            let mut last_state = self.last_state.lock().unwrap();
+            std::mem::replace(&mut *last_state, current) != current
+        };
+        if changed {
+            let message = match current {
+                QuotaState::OverHardLimit => format!(
+                    "state store uses {} bytes, over its hard limit, refusing new work",
+                    usage.total()
+                ),
+                QuotaState::OverSoftLimit => format!(
+                    "state store uses {} bytes, over its soft limit",
+                    usage.total()
+                ),
+                QuotaState::Ok => format!(
+                    "state store uses {} bytes, back under its disk quota",
+                    usage.total()
+                ),
+            };
+            match current {
+                QuotaState::Ok => info!("{}", message),
+                _ => warn!("{}: {:?}", message, usage),
+            }
+            self.state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::RecordActivity(RecordActivityRequest {
+                        namespace: None,
+                        activity: Activity::DiskQuota { message },
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        Ok(current)
+    }
+
+    pub async fn start(&self, mut shutdown_rx: watch::Receiver<()>) -> Result<()> {
+        // Usage is reported even without limits.
+        if self.config.soft_limit_bytes.is_none() && self.config.hard_limit_bytes.is_none() {
+            info!("disk quota of the state store is disabled");
+        }
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(self.config.check_interval_secs));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    if let Err(e) = self.check().await {
+                        error!("error checking the disk usage of the state store: {:?}", e);
+                    }
+                }
+                _ = shutdown_rx.changed() => {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use state_store::requests::{DiskQuotaExceeded, NamespaceRequest, RemoveSystemTaskRequest};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_disk_quota() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("state");
+        let state = IndexifyState::new(path.clone()).await?;
+        let usage = DiskUsage::measure(&path)?;
+        assert!(usage.wal_bytes > 0);
+        assert!(usage.other_bytes > 0);
+
+        let quota = |soft_limit_bytes, hard_limit_bytes| {
+            DiskQuota::new(
+                DiskQuotaConfig {
+                    soft_limit_bytes,
+                    hard_limit_bytes,
+                    ..Default::default()
+                },
+                path.clone(),
+                state.clone(),
+            )
+        };
+        let total = usage.total();
+        assert_eq!(quota(None, None).quota_state(&usage), QuotaState::Ok);
+        assert_eq!(
+            quota(Some(total), Some(total + 1)).quota_state(&usage),
+            QuotaState::OverSoftLimit
+        );
+
+        let create_namespace = || {
+            state.write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateNameSpace(NamespaceRequest {
+                    name: "test".to_string(),
+                    blob_storage: None,
+                    scheduling_weight: None,
+                    reprocessing_policy: Default::default(),
+                    near_duplicates: None,
+                }),
+                state_changes_processed: vec![],
+            })
+        };
+        let over_hard_limit = quota(Some(1), Some(1));
+        assert_eq!(over_hard_limit.check().await?, QuotaState::OverHardLimit);
+        let err = create_namespace().await.unwrap_err();
+        assert!(err.downcast_ref::<DiskQuotaExceeded>().is_some());
+        // Writes freeing space go through.
+        state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::RemoveSystemTask(RemoveSystemTaskRequest {
+                    namespace: "test".to_string(),
+                    compute_graph_name: "graph".to_string(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        assert_eq!(quota(None, None).check().await?, QuotaState::Ok);
+        create_namespace().await?;
+
+        let mut registry = prometheus_client::registry::Registry::default();
+        state.metrics.register(&mut registry);
+        let mut encoded = String::new();
+        prometheus_client::encoding::text::encode(&mut encoded, &registry)?;
+        assert!(encoded.contains("state_store_disk_usage_bytes{component=\"wal\"}"));
+        assert!(encoded.contains("state_store_disk_quota_exceeded 0"));
+        Ok(())
+    }
+}
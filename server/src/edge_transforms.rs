@@ -0,0 +1,86 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use blob_store::BlobStorage;
+use bytes::Bytes;
+use data_model::{edge_transforms::EdgeTransform, DataPayload};
+
+/// Reads a JSON output back from blob storage and reshapes it with the
+/// transform of the edge it flows along, returning the JSON the function at
+/// the other end of the edge reads.
+pub async fn transform_output(
+    storage: Arc<BlobStorage>,
+    transform: &EdgeTransform,
+    payload: &DataPayload,
+) -> Result<Bytes> {
+    let bytes = storage.read_bytes(&payload.path).await?;
+    let value = serde_json::from_slice(&bytes)
+        .map_err(|e| anyhow!("output of {} is not JSON: {}", transform.source, e))?;
+    let transformed = transform.apply(value).map_err(|e| {
+        anyhow!(
+            "failed to transform output of {} for {}: {}",
+            transform.source,
+            transform.target,
+            e
+        )
+    })?;
+    Ok(Bytes::from(serde_json::to_vec(&transformed)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use blob_store::BlobStorageConfig;
+    use data_model::edge_transforms::TransformStep;
+    use futures::stream;
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_transform_output() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let storage = Arc::new(BlobStorage::new(BlobStorageConfig::new_disk(
+            temp_dir.path().to_str().unwrap(),
+        ))?);
+        let data_stream = Box::pin(stream::once(async {
+            Ok(Bytes::from(
+                r#"{"result": {"text": "hello"}, "elapsed_ms": 3}"#,
+            ))
+        }));
+        let res = storage.put("output", data_stream).await?;
+        let payload = DataPayload {
+            path: res.url,
+            size: res.size_bytes,
+            sha256_hash: res.sha256_hash,
+            content_type: None,
+        };
+        let transform = EdgeTransform {
+            source: "fn_a".to_string(),
+            target: "fn_b".to_string(),
+            steps: vec![
+                TransformStep::Select {
+                    pointer: "/result".to_string(),
+                },
+                TransformStep::Set {
+                    fields: [("lang".to_string(), json!("en"))].into(),
+                },
+            ],
+        };
+        let transformed = transform_output(storage.clone(), &transform, &payload).await?;
+        assert_eq!(
+            serde_json::from_slice::<Value>(&transformed)?,
+            json!({"text": "hello", "lang": "en"})
+        );
+
+        let transform = EdgeTransform {
+            steps: vec![TransformStep::Select {
+                pointer: "/missing".to_string(),
+            }],
+            ..transform
+        };
+        assert!(transform_output(storage, &transform, &payload)
+            .await
+            .is_err());
+        Ok(())
+    }
+}
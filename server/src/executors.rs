@@ -1,7 +1,20 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-use anyhow::Result;
-use data_model::{ExecutorId, ExecutorMetadata};
+use anyhow::{anyhow, Result};
+use data_model::{
+    artifacts::Artifact,
+    validation::Validate,
+    ExecutorId,
+    ExecutorMetadata,
+    Node,
+    TaskOutcome,
+};
+use futures::Stream;
+use indexify_utils::clock::{Clock, SystemClock};
 use state_store::{
     requests::{
         DeregisterExecutorRequest,
@@ -11,10 +24,106 @@ use state_store::{
     },
     IndexifyState,
 };
+use tokio::sync::broadcast;
+
+use crate::{
+    admission::{AdmissionDecision, ExecutorAdmission, ExecutorRejected},
+    config::PrefetchConfig,
+};
 
 pub const EXECUTOR_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Number of task outcomes per executor the failure rate is computed over.
+const RECENT_TASK_OUTCOMES: usize = 50;
+/// Fewest recent outcomes from which an executor's failure rate affects its
+/// health.
+const MIN_TASK_OUTCOMES_FOR_HEALTH: usize = 5;
+const DEGRADED_FAILURE_RATE: f64 = 0.1;
+const QUARANTINED_FAILURE_RATE: f64 = 0.5;
+/// Largest diagnostics report kept for an executor, serialized.
+pub const MAX_DIAGNOSTICS_BYTES: usize = 256 * 1024;
+
+/// Every runner of an executor agent is scheduled as an executor of its own.
+pub fn runner_executor_id(agent_id: &str, runner_id: &str) -> ExecutorId {
+    ExecutorId::new(format!("{}.{}", agent_id, runner_id))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpuDeviceUtilization {
+    pub executor_id: ExecutorId,
+    pub device_id: String,
+    pub memory_bytes: u64,
+    pub allocated_memory_bytes: u64,
+    pub allocated_fraction: f64,
+    pub tasks: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionCapacity {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub image_name: String,
+    pub expected_concurrency: Option<u32>,
+    pub pending_tasks: u64,
+    pub desired_capacity: u64,
+    pub executors: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExecutorHealth {
+    Healthy,
+    /// Fails more of its recent tasks than usual.
+    Degraded,
+    /// Not connected to the server, it gets deregistered unless it
+    /// reconnects.
+    Stale,
+    /// Fails most of its recent tasks.
+    Quarantined,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExecutorOverview {
+    pub executor: ExecutorMetadata,
+    /// The executor's `pool` label.
+    pub pool: Option<String>,
+    pub in_flight_tasks: u64,
+    /// Unknown for executors which haven't connected since the server
+    /// started.
+    pub last_heartbeat_age_ms: Option<u64>,
+    pub recent_tasks: u64,
+    pub recent_failure_rate: f64,
+    pub health: ExecutorHealth,
+}
+
+/// Diagnostics an executor or executor agent reported about itself, e.g. its
+/// startup config, GPU drivers, cache sizes or crash reports, by section.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExecutorDiagnostics {
+    pub reported_at: u64,
+    pub sections: BTreeMap<String, serde_json::Value>,
+}
+
+/// What the server has seen of an executor since it started.
+#[derive(Debug, Default)]
+struct ExecutorActivity {
+    connected: bool,
+    last_seen: u64,
+    /// Whether each of the recent tasks failed, the latest last.
+    recent_failures: VecDeque<bool>,
+}
+
 pub struct ExecutorManager {
     indexify_state: Arc<IndexifyState>,
+    admission: Vec<Box<dyn ExecutorAdmission>>,
+    activity: Mutex<HashMap<ExecutorId, ExecutorActivity>>,
+    clock: Arc<dyn Clock>,
+    prefetch: PrefetchConfig,
+    // Latest report of each connected executor or agent, by the id it
+    // registered with. Agents report again when they reconnect, so these
+    // aren't persisted.
+    diagnostics: Mutex<HashMap<String, ExecutorDiagnostics>>,
+    diagnostics_requests: broadcast::Sender<String>,
 }
 
 impl ExecutorManager {
@@ -35,19 +144,106 @@ impl ExecutorManager {
             }
         });
 
-        ExecutorManager { indexify_state }
+        ExecutorManager {
+            indexify_state,
+            admission: Vec::new(),
+            activity: Mutex::new(HashMap::new()),
+            clock: Arc::new(SystemClock),
+            prefetch: PrefetchConfig::default(),
+            diagnostics: Mutex::new(HashMap::new()),
+            diagnostics_requests: broadcast::channel(16).0,
+        }
+    }
+
+    pub fn with_admission(mut self, admission: Vec<Box<dyn ExecutorAdmission>>) -> Self {
+        self.admission = admission;
+        self
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
     }
 
-    pub async fn register_executor(&self, executor: ExecutorMetadata) -> Result<()> {
+    pub fn with_prefetch(mut self, prefetch: PrefetchConfig) -> Self {
+        self.prefetch = prefetch;
+        self
+    }
+
+    /// Registers the executor as admitted by the admission hooks, with the
+    /// prefetch window granted to it, and returns it. Fails with
+    /// `ExecutorRejected` when a hook rejects it and with `ValidationErrors`
+    /// when the labels it's admitted with are invalid.
+    pub async fn register_executor(
+        &self,
+        mut executor: ExecutorMetadata,
+    ) -> Result<ExecutorMetadata> {
+        for hook in &self.admission {
+            let id = executor.id.clone();
+            match hook.admit(executor).await? {
+                AdmissionDecision::Admit(admitted) if admitted.id == id => executor = admitted,
+                AdmissionDecision::Admit(_) => {
+                    return Err(anyhow!("admission hook changed the id of executor {}", id));
+                }
+                AdmissionDecision::Reject(reason) => {
+                    return Err(ExecutorRejected { reason }.into());
+                }
+            }
+        }
+        executor.validate()?;
+        executor.prefetch_window = Some(self.prefetch.window(executor.prefetch_window));
         self.indexify_state
             .write(StateMachineUpdateRequest {
-                payload: RequestPayload::RegisterExecutor(RegisterExecutorRequest { executor }),
+                payload: RequestPayload::RegisterExecutor(RegisterExecutorRequest {
+                    executor: executor.clone(),
+                }),
                 state_changes_processed: vec![],
             })
-            .await
+            .await?;
+        let mut activity = self.activity.lock().unwrap();
+        let activity = activity.entry(executor.id.clone()).or_default();
+        activity.connected = true;
+        activity.last_seen = self.clock.now_ms();
+        Ok(executor)
+    }
+
+    /// Called when the task stream of an executor closes.
+    pub fn executor_disconnected(&self, executor_id: &ExecutorId) {
+        if let Some(activity) = self.activity.lock().unwrap().get_mut(executor_id) {
+            activity.connected = false;
+            activity.last_seen = self.clock.now_ms();
+        }
+    }
+
+    pub fn record_task_outcome(&self, executor_id: &ExecutorId, outcome: &TaskOutcome) {
+        let failed = match outcome {
+            TaskOutcome::Success => false,
+            TaskOutcome::Failure => true,
+            TaskOutcome::Unknown => return,
+        };
+        let mut activity = self.activity.lock().unwrap();
+        let recent = &mut activity
+            .entry(executor_id.clone())
+            .or_default()
+            .recent_failures;
+        recent.push_back(failed);
+        if recent.len() > RECENT_TASK_OUTCOMES {
+            recent.pop_front();
+        }
     }
 
     pub async fn deregister_executor(&self, executor_id: ExecutorId) -> Result<()> {
+        {
+            let mut activity = self.activity.lock().unwrap();
+            if activity.get(&executor_id).is_some_and(|a| !a.connected) {
+                activity.remove(&executor_id);
+            }
+            self.diagnostics.lock().unwrap().retain(|id, _| {
+                activity
+                    .keys()
+                    .any(|executor_id| reports_for(id, executor_id))
+            });
+        }
         self.indexify_state
             .write(StateMachineUpdateRequest {
                 payload: RequestPayload::DeregisterExecutor(DeregisterExecutorRequest {
@@ -58,12 +254,280 @@ impl ExecutorManager {
             .await
     }
 
+    /// Whether the executor, or a runner of the agent, with the id is
+    /// connected.
+    fn connected(&self, id: &str) -> bool {
+        self.activity
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(executor_id, activity)| activity.connected && reports_for(id, executor_id))
+    }
+
+    /// Records the diagnostics a connected executor or agent reported,
+    /// replacing its previous report.
+    pub fn report_diagnostics(
+        &self,
+        id: &str,
+        sections: BTreeMap<String, serde_json::Value>,
+    ) -> Result<()> {
+        if !self.connected(id) {
+            return Err(anyhow!("executor {} is not connected", id));
+        }
+        let size = serde_json::to_vec(&sections)?.len();
+        if size > MAX_DIAGNOSTICS_BYTES {
+            return Err(anyhow!(
+                "diagnostics of {} bytes exceed the limit of {} bytes",
+                size,
+                MAX_DIAGNOSTICS_BYTES
+            ));
+        }
+        self.diagnostics.lock().unwrap().insert(
+            id.to_string(),
+            ExecutorDiagnostics {
+                reported_at: self.clock.now_ms(),
+                sections,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn diagnostics(&self, id: &str) -> Option<ExecutorDiagnostics> {
+        self.diagnostics.lock().unwrap().get(id).cloned()
+    }
+
+    /// Asks a connected executor or agent to report its diagnostics again,
+    /// returns whether it's connected.
+    pub fn request_diagnostics(&self, id: &str) -> bool {
+        if !self.connected(id) {
+            return false;
+        }
+        let _ = self.diagnostics_requests.send(id.to_string());
+        true
+    }
+
+    /// Requests for the diagnostics of the executor or agent with the id,
+    /// sent over its task stream.
+    pub fn diagnostics_requests(&self, id: String) -> impl Stream<Item = ()> {
+        let mut rx = self.diagnostics_requests.subscribe();
+        async_stream::stream! {
+            loop {
+                match rx.recv().await {
+                    Ok(requested) if requested == id => yield (),
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        }
+    }
+
     pub async fn list_executors(&self) -> Result<Vec<ExecutorMetadata>> {
         self.indexify_state.reader().get_all_executors()
     }
+
+    /// Artifacts of the functions the executor can run which it hasn't
+    /// fetched yet, none when the executor isn't registered.
+    pub async fn prefetch_artifacts(
+        &self,
+        executor_id: &ExecutorId,
+    ) -> Result<Option<Vec<Artifact>>> {
+        let reader = self.indexify_state.snapshot_reader();
+        let Some(executor) = reader
+            .get_all_executors()?
+            .into_iter()
+            .find(|executor| &executor.id == executor_id)
+        else {
+            return Ok(None);
+        };
+        let mut artifacts = BTreeMap::new();
+        for namespace in reader.get_all_namespaces()? {
+            let (compute_graphs, _) = reader.list_compute_graphs(&namespace.name, None, None)?;
+            // Graphs of namespaces sharing the name as a prefix are listed too.
+            for compute_graph in compute_graphs
+                .iter()
+                .filter(|compute_graph| compute_graph.namespace == namespace.name)
+            {
+                for node in compute_graph.nodes.values() {
+                    if node.image_name() != executor.image_name || !node.matches_executor(&executor)
+                    {
+                        continue;
+                    }
+                    for artifact in node.artifacts() {
+                        let key = artifact.key(&namespace.name);
+                        if artifacts.contains_key(&key) {
+                            continue;
+                        }
+                        let Some(artifact) = reader.get_artifact(
+                            &namespace.name,
+                            &artifact.name,
+                            &artifact.version,
+                        )?
+                        else {
+                            continue;
+                        };
+                        if !executor.artifacts.contains(&artifact.payload.sha256_hash) {
+                            artifacts.insert(key, artifact);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(Some(artifacts.into_values().collect()))
+    }
+
+    /// Every registered executor along with its load, recent failures and
+    /// health.
+    pub async fn fleet_overview(&self) -> Result<Vec<ExecutorOverview>> {
+        let reader = self.indexify_state.snapshot_reader();
+        let in_flight = reader.allocated_task_counts()?;
+        let executors = reader.get_all_executors()?;
+        let activity = self.activity.lock().unwrap();
+        let now = self.clock.now_ms();
+        let mut overview = Vec::new();
+        for executor in executors {
+            let activity = activity.get(&executor.id);
+            let connected = activity.is_some_and(|a| a.connected);
+            let last_heartbeat_age_ms = activity.map(|a| {
+                // Connected executors are kept alive every second.
+                if a.connected {
+                    0
+                } else {
+                    now.saturating_sub(a.last_seen)
+                }
+            });
+            let recent = activity.map(|a| &a.recent_failures);
+            let recent_tasks = recent.map(|r| r.len()).unwrap_or_default();
+            let failures = recent
+                .map(|r| r.iter().filter(|failed| **failed).count())
+                .unwrap_or_default();
+            let recent_failure_rate = if recent_tasks == 0 {
+                0.0
+            } else {
+                failures as f64 / recent_tasks as f64
+            };
+            let health = if !connected {
+                ExecutorHealth::Stale
+            } else if recent_tasks < MIN_TASK_OUTCOMES_FOR_HEALTH {
+                ExecutorHealth::Healthy
+            } else if recent_failure_rate >= QUARANTINED_FAILURE_RATE {
+                ExecutorHealth::Quarantined
+            } else if recent_failure_rate >= DEGRADED_FAILURE_RATE {
+                ExecutorHealth::Degraded
+            } else {
+                ExecutorHealth::Healthy
+            };
+            overview.push(ExecutorOverview {
+                pool: executor
+                    .labels
+                    .get("pool")
+                    .and_then(|pool| pool.as_str())
+                    .map(str::to_string),
+                in_flight_tasks: in_flight.get(&executor.id).copied().unwrap_or_default(),
+                last_heartbeat_age_ms,
+                recent_tasks: recent_tasks as u64,
+                recent_failure_rate,
+                health,
+                executor,
+            });
+        }
+        Ok(overview)
+    }
+
+    /// Share of every GPU device of the registered executors held by the
+    /// tasks allocated to it.
+    pub async fn gpu_utilization(&self) -> Result<Vec<GpuDeviceUtilization>> {
+        let reader = self.indexify_state.snapshot_reader();
+        let usage = reader.gpu_usage()?;
+        let mut utilization = Vec::new();
+        for executor in reader.get_all_executors()? {
+            for device in executor.gpus {
+                let device_usage = usage
+                    .get(&(executor.id.clone(), device.id.clone()))
+                    .cloned()
+                    .unwrap_or_default();
+                utilization.push(GpuDeviceUtilization {
+                    executor_id: executor.id.clone(),
+                    device_id: device.id,
+                    memory_bytes: device.memory_bytes,
+                    allocated_memory_bytes: device_usage.memory_bytes,
+                    allocated_fraction: device_usage.fraction,
+                    tasks: device_usage.tasks,
+                });
+            }
+        }
+        Ok(utilization)
+    }
+
+    /// Capacity wanted by every function which declares an expected
+    /// concurrency or has tasks waiting for an executor. The desired capacity
+    /// is the larger of the two.
+    pub async fn desired_capacity(&self) -> Result<Vec<FunctionCapacity>> {
+        let reader = self.indexify_state.snapshot_reader();
+        let mut executors_by_image: HashMap<String, u64> = HashMap::new();
+        for executor in reader.get_all_executors()? {
+            *executors_by_image.entry(executor.image_name).or_default() += 1;
+        }
+        let mut pending_tasks: HashMap<(String, String, String), u64> = HashMap::new();
+        for task in reader.unallocated_tasks()? {
+            *pending_tasks
+                .entry((
+                    task.namespace,
+                    task.compute_graph_name,
+                    task.compute_fn_name,
+                ))
+                .or_default() += 1;
+        }
+        let mut capacity = Vec::new();
+        for namespace in reader.get_all_namespaces()? {
+            let (compute_graphs, _) = reader.list_compute_graphs(&namespace.name, None, None)?;
+            for compute_graph in compute_graphs {
+                for node in compute_graph.nodes.values() {
+                    let Node::Compute(compute_fn) = node else {
+                        continue;
+                    };
+                    let pending = pending_tasks
+                        .get(&(
+                            namespace.name.clone(),
+                            compute_graph.name.clone(),
+                            compute_fn.name.clone(),
+                        ))
+                        .copied()
+                        .unwrap_or_default();
+                    if compute_fn.expected_concurrency.is_none() && pending == 0 {
+                        continue;
+                    }
+                    let expected = compute_fn.expected_concurrency.unwrap_or_default() as u64;
+                    capacity.push(FunctionCapacity {
+                        namespace: namespace.name.clone(),
+                        compute_graph: compute_graph.name.clone(),
+                        compute_fn: compute_fn.name.clone(),
+                        image_name: compute_fn.image_name.clone(),
+                        expected_concurrency: compute_fn.expected_concurrency,
+                        pending_tasks: pending,
+                        desired_capacity: expected.max(pending),
+                        executors: executors_by_image
+                            .get(&compute_fn.image_name)
+                            .copied()
+                            .unwrap_or_default(),
+                    });
+                }
+            }
+        }
+        Ok(capacity)
+    }
+}
+
+// Reports of an agent cover all of its runners.
+fn reports_for(id: &str, executor_id: &ExecutorId) -> bool {
+    let executor_id = executor_id.get();
+    executor_id == id ||
+        executor_id
+            .strip_prefix(id)
+            .is_some_and(|runner| runner.starts_with('.'))
 }
 
 pub fn schedule_deregister(ex: Arc<ExecutorManager>, executor_id: ExecutorId, duration: Duration) {
+    ex.executor_disconnected(&executor_id);
     tokio::spawn(async move {
         tokio::time::sleep(duration).await;
         let ret = ex.deregister_executor(executor_id.clone()).await;
@@ -79,22 +543,26 @@ mod tests {
 
     use anyhow::Result;
     use data_model::{ExecutorId, ExecutorMetadata};
+    use indexify_utils::clock::TestClock;
     use state_store::IndexifyState;
 
     use super::*;
+    use crate::admission::LabelPolicy;
 
     #[tokio::test]
     async fn test_register_executor() -> Result<()> {
-        let temp_dir = tempfile::tempdir()?;
-        let indexify_state = IndexifyState::new(temp_dir.path().join("state"))
-            .await
-            .unwrap();
+        let indexify_state = IndexifyState::in_memory().await.unwrap();
         let ex = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
         let executor = ExecutorMetadata {
             id: ExecutorId::new("test".to_string()),
             image_name: "test".to_string(),
             addr: "".to_string(),
             labels: Default::default(),
+            gpus: vec![],
+            capacity: None,
+            version: None,
+            artifacts: vec![],
+            prefetch_window: None,
         };
         ex.register_executor(executor).await?;
 
@@ -106,17 +574,97 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_deregister_executor() -> Result<()> {
-        let temp_dir = tempfile::tempdir()?;
-        let indexify_state = IndexifyState::new(temp_dir.path().join("state"))
+    async fn test_register_executor_admission() -> Result<()> {
+        let indexify_state = IndexifyState::in_memory().await?;
+        let ex = ExecutorManager::new(indexify_state.clone())
             .await
-            .unwrap();
+            .with_admission(vec![Box::new(LabelPolicy {
+                required_labels: vec!["team".to_string()],
+                default_labels: HashMap::from([("pool".to_string(), "shared".into())]),
+            })]);
+        let mut executor = ExecutorMetadata {
+            id: ExecutorId::new("test".to_string()),
+            image_name: "test".to_string(),
+            addr: "".to_string(),
+            labels: Default::default(),
+            gpus: vec![],
+            capacity: None,
+            version: None,
+            artifacts: vec![],
+            prefetch_window: None,
+        };
+        let err = ex.register_executor(executor.clone()).await.unwrap_err();
+        assert!(err.downcast_ref::<ExecutorRejected>().is_some());
+        assert!(indexify_state.reader().get_all_executors()?.is_empty());
+
+        executor.labels.insert("team".to_string(), "search".into());
+        ex.register_executor(executor).await?;
+        let executors = indexify_state.reader().get_all_executors()?;
+        assert_eq!(executors[0].labels.get("pool"), Some(&"shared".into()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_diagnostics() -> Result<()> {
+        let indexify_state = IndexifyState::in_memory().await?;
+        let ex = ExecutorManager::new(indexify_state.clone()).await;
+        let runner = ExecutorMetadata {
+            id: runner_executor_id("agent", "gpu"),
+            image_name: "test".to_string(),
+            addr: "".to_string(),
+            labels: Default::default(),
+            gpus: vec![],
+            capacity: None,
+            version: None,
+            artifacts: vec![],
+            prefetch_window: None,
+        };
+        let sections =
+            BTreeMap::from([("gpu".to_string(), serde_json::json!({"driver": "550.54"}))]);
+        assert!(ex.report_diagnostics("agent", sections.clone()).is_err());
+        assert!(!ex.request_diagnostics("agent"));
+
+        ex.register_executor(runner.clone()).await?;
+        // An agent reports for its runners, other prefixes don't match.
+        assert!(ex.report_diagnostics("age", sections.clone()).is_err());
+        ex.report_diagnostics("agent", sections.clone())?;
+        assert_eq!(ex.diagnostics("agent").unwrap().sections, sections);
+        let large = BTreeMap::from([(
+            "crash_reports".to_string(),
+            "x".repeat(MAX_DIAGNOSTICS_BYTES).into(),
+        )]);
+        assert!(ex.report_diagnostics("agent", large).is_err());
+
+        let requests = ex.diagnostics_requests("agent".to_string());
+        futures::pin_mut!(requests);
+        assert!(ex.request_diagnostics("agent"));
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            futures::StreamExt::next(&mut requests),
+        )
+        .await?
+        .unwrap();
+
+        ex.executor_disconnected(&runner.id);
+        ex.deregister_executor(runner.id).await?;
+        assert!(ex.diagnostics("agent").is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_deregister_executor() -> Result<()> {
+        let indexify_state = IndexifyState::in_memory().await.unwrap();
         let ex = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
         let executor = ExecutorMetadata {
             id: ExecutorId::new("test".to_string()),
             image_name: "test".to_string(),
             addr: "".to_string(),
             labels: Default::default(),
+            gpus: vec![],
+            capacity: None,
+            version: None,
+            artifacts: vec![],
+            prefetch_window: None,
         };
         ex.register_executor(executor.clone()).await?;
 
@@ -151,4 +699,46 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_fleet_overview() -> Result<()> {
+        let indexify_state = IndexifyState::in_memory().await?;
+        let clock = Arc::new(TestClock::new(1_000));
+        let ex = Arc::new(
+            ExecutorManager::new(indexify_state.clone())
+                .await
+                .with_clock(clock.clone()),
+        );
+        for (id, pool) in [("executor_1", "gpu"), ("executor_2", "cpu")] {
+            ex.register_executor(ExecutorMetadata {
+                id: ExecutorId::new(id.to_string()),
+                labels: HashMap::from([("pool".to_string(), pool.into())]),
+                ..Default::default()
+            })
+            .await?;
+        }
+        let executor_1 = ExecutorId::new("executor_1".to_string());
+        let executor_2 = ExecutorId::new("executor_2".to_string());
+        for _ in 0..3 {
+            ex.record_task_outcome(&executor_1, &TaskOutcome::Success);
+            ex.record_task_outcome(&executor_1, &TaskOutcome::Failure);
+        }
+        ex.executor_disconnected(&executor_2);
+        clock.advance(Duration::from_secs(90));
+
+        let overview = ex.fleet_overview().await?;
+        let overview = overview
+            .iter()
+            .map(|o| (o.executor.id.get(), o))
+            .collect::<HashMap<_, _>>();
+        let executor_1 = overview["executor_1"];
+        assert_eq!(executor_1.pool.as_deref(), Some("gpu"));
+        assert_eq!(executor_1.recent_tasks, 6);
+        assert_eq!(executor_1.recent_failure_rate, 0.5);
+        assert_eq!(executor_1.last_heartbeat_age_ms, Some(0));
+        assert_eq!(executor_1.health, ExecutorHealth::Quarantined);
+        assert_eq!(overview["executor_2"].health, ExecutorHealth::Stale);
+        assert_eq!(overview["executor_2"].last_heartbeat_age_ms, Some(90_000));
+        Ok(())
+    }
 }
@@ -0,0 +1,558 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use blob_store::{content_type, registry::BlobStorageRegistry, PutResult};
+use bytes::Bytes;
+use data_model::{ContentSource, InvocationPayloadBuilder, TraceContext, UrlFetch};
+use futures::{stream, stream::BoxStream, StreamExt};
+use indexify_utils::get_epoch_time_in_ms;
+use reqwest::{header::LOCATION, Url};
+use state_store::{
+    requests::{
+        ContentVersionError,
+        InvokeComputeGraphRequest,
+        RequestPayload,
+        StateMachineUpdateRequest,
+    },
+    IndexifyState,
+};
+use tokio::sync::{mpsc, Mutex};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    config::FetcherConfig,
+    gc,
+    http_objects::GraphInputFile,
+    near_duplicates,
+    outbound::{self, UrlNotAllowed},
+};
+
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_REDIRECTS: usize = 5;
+
+#[derive(Debug, Clone)]
+pub struct FetchRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub invocation_id: String,
+    pub url: String,
+    pub tags: HashMap<String, String>,
+    pub trace: TraceContext,
+    pub source: Option<String>,
+    pub source_modified_at: Option<u64>,
+    pub created_at: u64,
+}
+
+impl FetchRequest {
+    fn url_fetch(&self, error: Option<String>) -> UrlFetch {
+        UrlFetch {
+            namespace: self.namespace.clone(),
+            compute_graph: self.compute_graph.clone(),
+            invocation_id: self.invocation_id.clone(),
+            url: self.url.clone(),
+            created_at: self.created_at,
+            error,
+        }
+    }
+}
+
+/// Downloads remote objects into the blob store and invokes the compute graph
+/// with them once the download has finished. Invocations waiting for their
+/// input are recorded in the state store, along with the reason when the
+/// graph couldn't be invoked.
+pub struct Fetcher {
+    tx: mpsc::Sender<FetchRequest>,
+    indexify_state: Arc<IndexifyState>,
+    blob_storage: Arc<BlobStorageRegistry>,
+    config: FetcherConfig,
+}
+
+impl Fetcher {
+    pub fn new(
+        indexify_state: Arc<IndexifyState>,
+        blob_storage: Arc<BlobStorageRegistry>,
+        config: FetcherConfig,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel(config.queue_size);
+        let rx = Arc::new(Mutex::new(rx));
+        for _ in 0..config.workers {
+            let worker = FetchWorker {
+                indexify_state: indexify_state.clone(),
+                blob_storage: blob_storage.clone(),
+                config: config.clone(),
+            };
+            let rx = rx.clone();
+            tokio::spawn(async move {
+                loop {
+                    let request = rx.lock().await.recv().await;
+                    match request {
+                        Some(request) => worker.process(request).await,
+                        None => break,
+                    }
+                }
+            });
+        }
+        tokio::spawn(fail_interrupted(
+            indexify_state.clone(),
+            get_epoch_time_in_ms(),
+        ));
+        Self {
+            tx,
+            indexify_state,
+            blob_storage,
+            config,
+        }
+    }
+
+    /// Checks that the url can be fetched for the namespace, before the
+    /// invocation is accepted.
+    pub fn check_url(&self, namespace: &str, url: &str) -> Result<()> {
+        check_url(&self.blob_storage, &self.config, namespace, url)
+    }
+
+    /// Records the invocation as waiting for its input, then queues the
+    /// download.
+    pub async fn enqueue(&self, request: FetchRequest) -> Result<()> {
+        self.put_url_fetch(request.url_fetch(None)).await?;
+        if let Err(e) = self.tx.try_send(request.clone()) {
+            let e = anyhow!("unable to queue fetch request: {}", e);
+            self.put_url_fetch(request.url_fetch(Some(e.to_string())))
+                .await?;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    async fn put_url_fetch(&self, fetch: UrlFetch) -> Result<()> {
+        self.indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::PutUrlFetch(fetch),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+}
+
+/// Queued downloads don't survive restarts, the invocations accepted before
+/// the server started are failed.
+async fn fail_interrupted(indexify_state: Arc<IndexifyState>, started_at: u64) {
+    let fetches = match indexify_state.reader().pending_url_fetches() {
+        Ok(fetches) => fetches,
+        Err(e) => {
+            error!("failed to read pending url fetches: {:?}", e);
+            return;
+        }
+    };
+    for mut fetch in fetches
+        .into_iter()
+        .filter(|fetch| fetch.created_at < started_at)
+    {
+        fetch.error = Some("the server restarted before the url was fetched".to_string());
+        let result = indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::PutUrlFetch(fetch),
+                state_changes_processed: vec![],
+            })
+            .await;
+        if let Err(e) = result {
+            error!("failed to record interrupted url fetch: {:?}", e);
+        }
+    }
+}
+
+/// Objects are fetched with the server's credentials, so s3 urls are limited
+/// to the bucket of the namespace's own placement and the configured ones.
+fn check_url(
+    blob_storage: &BlobStorageRegistry,
+    config: &FetcherConfig,
+    namespace: &str,
+    url: &str,
+) -> Result<()> {
+    let not_allowed = |message: String| Err(UrlNotAllowed(message).into());
+    let backend =
+        match url.split_once("://").map(|(scheme, _)| scheme) {
+            Some("http" | "https") => "http",
+            Some("s3") => "s3",
+            Some("gs") => return not_allowed(
+                "gs urls aren't supported, use the https url of the object, signed if it's private"
+                    .to_string(),
+            ),
+            _ => return not_allowed("url must use the http, https or s3 scheme".to_string()),
+        };
+    if !blob_store::compiled_backends().contains(&backend) {
+        return not_allowed(format!("server was built without {} support", backend));
+    }
+    if backend == "s3" {
+        let bucket = url["s3://".len()..].split('/').next().unwrap_or_default();
+        let own = blob_storage.placement_bucket(namespace);
+        if own.as_deref() != Some(bucket) && !config.allowed_buckets.iter().any(|b| b == bucket) {
+            return not_allowed(format!(
+                "s3 bucket {} is neither the namespace's blob storage nor in fetcher.allowed_buckets",
+                bucket
+            ));
+        }
+    }
+    Ok(())
+}
+
+struct FetchWorker {
+    indexify_state: Arc<IndexifyState>,
+    blob_storage: Arc<BlobStorageRegistry>,
+    config: FetcherConfig,
+}
+
+impl FetchWorker {
+    async fn process(&self, request: FetchRequest) {
+        info!(
+            "fetching {} for invocation {}",
+            request.url, request.invocation_id
+        );
+        let mut attempt = 0;
+        let put_result = loop {
+            match self.download(&request.namespace, &request.url).await {
+                Ok(put_result) => break put_result,
+                Err(e)
+                    if attempt < self.config.max_retries &&
+                        e.downcast_ref::<UrlNotAllowed>().is_none() =>
+                {
+                    warn!("failed to fetch {}, retrying: {:?}", request.url, e);
+                    tokio::time::sleep(RETRY_BACKOFF * 2u32.pow(attempt)).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    error!("failed to fetch {}: {:?}", request.url, e);
+                    self.fail(
+                        &request,
+                        format!("failed to fetch {}: {:#}", request.url, e),
+                    )
+                    .await;
+                    return;
+                }
+            }
+        };
+        let mut uploads = vec![put_result.url.clone()];
+        if let Err(e) = self.invoke(&request, put_result, &mut uploads).await {
+            // The invocation wasn't created, nothing references its uploads.
+            gc::collect_unreferenced(&self.indexify_state, uploads).await;
+            if let Some(
+                err @ (ContentVersionError::Unchanged { .. } |
+                ContentVersionError::NearDuplicate { .. }),
+            ) = e.downcast_ref()
+            {
+                info!("not invoking graph {}: {}", request.compute_graph, err);
+                self.fail(&request, err.to_string()).await;
+                return;
+            }
+            error!(
+                "failed to invoke graph {} with {}: {:?}",
+                request.compute_graph, request.url, e
+            );
+            self.fail(&request, format!("failed to invoke graph: {:#}", e))
+                .await;
+        }
+    }
+
+    async fn fail(&self, request: &FetchRequest, error: String) {
+        let result = self
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::PutUrlFetch(request.url_fetch(Some(error))),
+                state_changes_processed: vec![],
+            })
+            .await;
+        if let Err(e) = result {
+            error!(
+                "failed to record the failed fetch of invocation {}: {:?}",
+                request.invocation_id, e
+            );
+        }
+    }
+
+    async fn download(&self, namespace: &str, url: &str) -> Result<PutResult> {
+        check_url(&self.blob_storage, &self.config, namespace, url)?;
+        let stream = if url.starts_with("s3://") {
+            self.blob_storage.get(url).get().await?
+        } else {
+            self.http_get(url).await?
+        };
+        let max_size_bytes = self.config.max_size_bytes;
+        let mut size_bytes = 0;
+        let stream = stream.map(move |chunk| {
+            let chunk = chunk?;
+            size_bytes += chunk.len() as u64;
+            if size_bytes > max_size_bytes {
+                return Err(anyhow!("object exceeds {} bytes", max_size_bytes));
+            }
+            Ok(chunk)
+        });
+        self.blob_storage
+            .for_namespace(namespace)
+            .put(&Uuid::new_v4().to_string(), stream)
+            .await
+    }
+
+    /// Redirects are followed one at a time so that each location is checked
+    /// like the url itself.
+    async fn http_get(&self, url: &str) -> Result<BoxStream<'static, Result<Bytes>>> {
+        let mut url = Url::parse(url)?;
+        for _ in 0..=MAX_REDIRECTS {
+            let response = outbound::client_for(&url, self.config.allow_private_addresses)
+                .await?
+                .build()?
+                .get(url.clone())
+                .send()
+                .await?;
+            if !response.status().is_redirection() {
+                let stream = response
+                    .error_for_status()?
+                    .bytes_stream()
+                    .map(|chunk| chunk.map_err(|e| anyhow!("failed to read chunk: {}", e)));
+                return Ok(Box::pin(stream));
+            }
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .ok_or(anyhow!("redirect without a location"))?
+                .to_str()?;
+            url = url.join(location)?;
+            if !matches!(url.scheme(), "http" | "https") {
+                return Err(UrlNotAllowed(format!("redirected to {}", url)).into());
+            }
+        }
+        Err(anyhow!("more than {} redirects", MAX_REDIRECTS))
+    }
+
+    /// Invokes the graph with the downloaded input, adding the blobs it
+    /// uploads to `uploads`.
+    async fn invoke(
+        &self,
+        request: &FetchRequest,
+        put_result: PutResult,
+        uploads: &mut Vec<String>,
+    ) -> Result<()> {
+        let file_name = request.url.split(['?', '#']).next().unwrap_or_default();
+        let content_type =
+            content_type::detect(put_result.content_type.as_deref(), Some(file_name));
+        let source = request.source.as_ref().map(|id| ContentSource {
+            id: id.clone(),
+            sha256_hash: put_result.sha256_hash.clone(),
+            modified_at: request.source_modified_at,
+        });
+        let fingerprint = near_duplicates::fingerprint(
+            &self.indexify_state,
+            &self.blob_storage,
+            &request.namespace,
+            &put_result,
+            content_type.as_deref(),
+        )
+        .await?;
+        let payload = GraphInputFile {
+            metadata: serde_json::json!({ "source_url": request.url }),
+            url: put_result.url,
+            sha_256: put_result.sha256_hash,
+            size: put_result.size_bytes,
+            content_type: content_type.clone(),
+        };
+        let payload_json = serde_json::to_vec(&payload)?;
+        let payload_stream = stream::once(async move { Ok(payload_json.into()) });
+        let put_result = self
+            .blob_storage
+            .for_namespace(&request.namespace)
+            .put(&Uuid::new_v4().to_string(), Box::pin(payload_stream))
+            .await?;
+        uploads.push(put_result.url.clone());
+        let invocation_payload = InvocationPayloadBuilder::default()
+            .id(request.invocation_id.clone())
+            .namespace(request.namespace.clone())
+            .compute_graph_name(request.compute_graph.clone())
+            .payload(data_model::DataPayload {
+                path: put_result.url,
+                size: put_result.size_bytes,
+                sha256_hash: put_result.sha256_hash,
+                content_type,
+            })
+            .tags(request.tags.clone())
+            .trace(request.trace.clone())
+            .source(source)
+            .fingerprint(fingerprint)
+            .build()?;
+        self.indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: request.namespace.clone(),
+                    compute_graph_name: request.compute_graph.clone(),
+                    invocation_payload,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{routing::get, Router};
+    use blob_store::{BlobStorage, BlobStorageConfig};
+    use data_model::test_objects::tests::{mock_graph_a, TEST_NAMESPACE};
+    use state_store::requests::CreateComputeGraphRequest;
+
+    use super::*;
+    use crate::config::placement_blob_storage_config;
+
+    #[tokio::test]
+    async fn test_fetch_and_invoke() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let indexify_state = IndexifyState::in_memory().await?;
+        let blob_storage = Arc::new(BlobStorageRegistry::new(Arc::new(BlobStorage::new(
+            BlobStorageConfig::new_disk(temp_dir.path().join("blobs").to_str().unwrap()),
+        )?)));
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                    precondition: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let app = Router::new()
+            .route("/source.txt", get(|| async { "hello world" }))
+            .route(
+                "/redirect",
+                get(|| async { axum::response::Redirect::temporary("/source.txt") }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let worker = FetchWorker {
+            indexify_state: indexify_state.clone(),
+            blob_storage: blob_storage.clone(),
+            config: FetcherConfig {
+                allow_private_addresses: true,
+                ..Default::default()
+            },
+        };
+        let request = FetchRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: "graph_A".to_string(),
+            invocation_id: "invocation_1".to_string(),
+            url: format!("http://{}/redirect", addr),
+            tags: HashMap::new(),
+            trace: Default::default(),
+            source: None,
+            source_modified_at: None,
+            created_at: get_epoch_time_in_ms(),
+        };
+        worker
+            .indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::PutUrlFetch(request.url_fetch(None)),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        worker.process(request.clone()).await;
+
+        let reader = indexify_state.reader();
+        let invocation = reader.invocation_payload(TEST_NAMESPACE, "graph_A", "invocation_1")?;
+        let payload = blob_storage.read_bytes(&invocation.payload.path).await?;
+        let payload: GraphInputFile = serde_json::from_slice(&payload)?;
+        assert_eq!(payload.size, 11);
+        // The fetch is forgotten once the graph is invoked.
+        assert_eq!(
+            reader.url_fetch(TEST_NAMESPACE, "graph_A", "invocation_1")?,
+            None
+        );
+
+        // The uploads for an unchanged source aren't referenced by any
+        // invocation, they're garbage collected.
+        let from_source = FetchRequest {
+            invocation_id: "invocation_3".to_string(),
+            source: Some("source.txt".to_string()),
+            source_modified_at: Some(1),
+            ..request.clone()
+        };
+        worker.process(from_source.clone()).await;
+        assert!(reader.get_gc_urls(None)?.is_empty());
+        worker
+            .process(FetchRequest {
+                invocation_id: "invocation_4".to_string(),
+                ..from_source
+            })
+            .await;
+        assert_eq!(reader.get_gc_urls(None)?.len(), 2);
+        let fetch = reader
+            .url_fetch(TEST_NAMESPACE, "graph_A", "invocation_4")?
+            .unwrap();
+        assert!(fetch.error.is_some());
+
+        // Failures are recorded on the fetch.
+        let worker = FetchWorker {
+            config: FetcherConfig {
+                max_size_bytes: 5,
+                max_retries: 0,
+                allow_private_addresses: true,
+                ..Default::default()
+            },
+            ..worker
+        };
+        let request = FetchRequest {
+            invocation_id: "invocation_2".to_string(),
+            ..request
+        };
+        worker.process(request.clone()).await;
+        let fetch = reader
+            .url_fetch(TEST_NAMESPACE, "graph_A", "invocation_2")?
+            .unwrap();
+        assert!(fetch.error.unwrap().contains("exceeds 5 bytes"));
+
+        // Loopback addresses aren't fetched by default.
+        let worker = FetchWorker {
+            config: FetcherConfig::default(),
+            ..worker
+        };
+        let err = worker
+            .download(TEST_NAMESPACE, &request.url)
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<UrlNotAllowed>().is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_url() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let blob_storage = BlobStorageRegistry::new(Arc::new(BlobStorage::new(
+            BlobStorageConfig::new_disk(temp_dir.path().to_str().unwrap()),
+        )?));
+        blob_storage.insert(
+            "tenant",
+            blob_storage.build(placement_blob_storage_config(
+                &data_model::BlobStoragePlacement::S3 {
+                    bucket: "tenant-bucket".to_string(),
+                    region: "us-east-1".to_string(),
+                },
+            ))?,
+        );
+        let config = FetcherConfig {
+            allowed_buckets: vec!["shared".to_string()],
+            ..Default::default()
+        };
+        let check = |namespace: &str, url: &str| check_url(&blob_storage, &config, namespace, url);
+        check("tenant", "https://example.com/a.pdf")?;
+        check("tenant", "s3://tenant-bucket/a.pdf")?;
+        check("other", "s3://shared/a.pdf")?;
+        for (namespace, url) in [
+            ("other", "s3://tenant-bucket/a.pdf"),
+            ("tenant", "gs://bucket/a.pdf"),
+            ("tenant", "file:///etc/passwd"),
+            ("tenant", "/etc/passwd"),
+        ] {
+            let err = check(namespace, url).unwrap_err();
+            assert!(err.downcast_ref::<UrlNotAllowed>().is_some(), "{}", url);
+        }
+        Ok(())
+    }
+}
@@ -1,12 +1,29 @@
 use std::sync::Arc;
 
 use anyhow::Result;
-use blob_store::BlobStorage;
-use state_store::IndexifyState;
+use blob_store::registry::BlobStorageRegistry;
+use state_store::{
+    requests::{RequestPayload, StateMachineUpdateRequest},
+    IndexifyState,
+};
+
+/// Queues the deletion of blobs which nothing references, e.g. uploaded for
+/// an invocation which wasn't created.
+pub async fn collect_unreferenced(state: &IndexifyState, urls: Vec<String>) {
+    let result = state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::AddGcUrls(urls.clone()),
+            state_changes_processed: vec![],
+        })
+        .await;
+    if let Err(e) = result {
+        tracing::error!("failed to queue the deletion of {:?}: {:?}", urls, e);
+    }
+}
 
 pub struct Gc {
     state: Arc<IndexifyState>,
-    storage: Arc<BlobStorage>,
+    storage: Arc<BlobStorageRegistry>,
     rx: tokio::sync::watch::Receiver<()>,
     shutdown_rx: tokio::sync::watch::Receiver<()>,
 }
@@ -14,7 +31,7 @@ pub struct Gc {
 impl Gc {
     pub fn new(
         state: Arc<IndexifyState>,
-        storage: Arc<BlobStorage>,
+        storage: Arc<BlobStorageRegistry>,
         shutdown_rx: tokio::sync::watch::Receiver<()>,
     ) -> Self {
         let rx = state.get_gc_watcher();
@@ -97,7 +114,8 @@ mod tests {
             blob_store::BlobStorageConfig::new_disk(temp_dir.path().join("blob").to_str().unwrap());
         let storage = Arc::new(BlobStorage::new(config)?);
         let (tx, rx) = watch::channel(());
-        let mut gc = Gc::new(state.clone(), storage.clone(), rx);
+        let registry = Arc::new(BlobStorageRegistry::new(storage.clone()));
+        let mut gc = Gc::new(state.clone(), registry, rx);
 
         tokio::spawn(async move {
             info!("starting garbage collector");
@@ -111,6 +129,7 @@ mod tests {
                 payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
                     namespace: TEST_NAMESPACE.to_string(),
                     compute_graph: compute_graph.clone(),
+                    precondition: None,
                 }),
                 state_changes_processed: vec![],
             })
@@ -132,15 +151,17 @@ mod tests {
                 path: res.url.clone(),
                 size: res.size_bytes,
                 sha256_hash: res.sha256_hash,
+                content_type: None,
             }),
             errors: None,
             reduced_state: false,
         };
         let key = output.key(&output.invocation_id);
-        let serialized_output = JsonEncoder::encode(&output)?;
+        let serialized_output =
+            JsonEncoder::encode(IndexifyObjectsColumns::FnOutputs, &key, &output)?;
         state.db.put_cf(
             &IndexifyObjectsColumns::FnOutputs.cf_db(&state.db),
-            key,
+            &key,
             &serialized_output,
         )?;
 
@@ -1,33 +1,103 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use axum::{
-    http::StatusCode,
+    http::{
+        header::{CONTENT_TYPE, RETRY_AFTER},
+        HeaderMap,
+        StatusCode,
+    },
     response::{IntoResponse, Response},
 };
-use data_model::{ComputeGraphCode, GraphVersion};
+use data_model::{validation::ValidationErrors, ComputeGraphCode, GraphVersion};
 use indexify_utils::get_epoch_time_in_ms;
 use serde::{Deserialize, Serialize};
+use state_store::requests::DiskQuotaExceeded;
 use utoipa::ToSchema;
 
+/// Code of an API error, which clients can branch on instead of parsing
+/// messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    InvalidRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    NamespaceNotFound,
+    ComputeGraphNotFound,
+    InvocationNotFound,
+    Conflict,
+    /// The compute graph isn't at the version of the If-Match header.
+    GraphVersionConflict,
+    /// The state store is out of its disk quota.
+    QuotaExceeded,
+    /// The server is overloaded, retry after the Retry-After delay.
+    Overloaded,
+    Internal,
+}
+
+impl ErrorCode {
+    fn for_status(status_code: StatusCode) -> Self {
+        match status_code {
+            StatusCode::BAD_REQUEST => ErrorCode::InvalidRequest,
+            StatusCode::UNAUTHORIZED => ErrorCode::Unauthorized,
+            StatusCode::FORBIDDEN => ErrorCode::Forbidden,
+            StatusCode::NOT_FOUND => ErrorCode::NotFound,
+            StatusCode::CONFLICT => ErrorCode::Conflict,
+            StatusCode::INSUFFICIENT_STORAGE => ErrorCode::QuotaExceeded,
+            StatusCode::SERVICE_UNAVAILABLE => ErrorCode::Overloaded,
+            _ => ErrorCode::Internal,
+        }
+    }
+}
+
+/// Body of error responses, RFC 9457 problem details with the code of the
+/// error.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    pub code: ErrorCode,
+}
+
 #[derive(Debug, ToSchema)]
 pub struct IndexifyAPIError {
     status_code: StatusCode,
+    code: ErrorCode,
     message: String,
+    /// Seconds the client should wait before retrying.
+    retry_after: Option<u64>,
 }
 
 impl IndexifyAPIError {
     pub fn new(status_code: StatusCode, message: &str) -> Self {
         Self {
             status_code,
+            code: ErrorCode::for_status(status_code),
             message: message.to_string(),
+            retry_after: None,
         }
     }
 
+    /// Replaces the code derived from the status code with a more specific
+    /// one.
+    pub fn with_code(self, code: ErrorCode) -> Self {
+        Self { code, ..self }
+    }
+
     pub fn _bad_request(e: &str) -> Self {
         Self::new(StatusCode::BAD_REQUEST, e)
     }
 
     pub fn internal_error(e: anyhow::Error) -> Self {
+        // Writes refused for lack of disk surface from every route writing to
+        // the state store.
+        if e.downcast_ref::<DiskQuotaExceeded>().is_some() {
+            return Self::new(StatusCode::INSUFFICIENT_STORAGE, e.to_string().as_str());
+        }
         Self::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string().as_str())
     }
 
@@ -39,15 +109,79 @@ impl IndexifyAPIError {
         Self::new(StatusCode::NOT_FOUND, message)
     }
 
+    pub fn namespace_not_found() -> Self {
+        Self::not_found("namespace not found").with_code(ErrorCode::NamespaceNotFound)
+    }
+
+    pub fn compute_graph_not_found() -> Self {
+        Self::not_found("compute graph not found").with_code(ErrorCode::ComputeGraphNotFound)
+    }
+
+    pub fn invocation_not_found() -> Self {
+        Self::not_found("invocation not found").with_code(ErrorCode::InvocationNotFound)
+    }
+
     pub fn bad_request(message: &str) -> Self {
         Self::new(StatusCode::BAD_REQUEST, message)
     }
+
+    pub fn unauthorized(message: &str) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, message)
+    }
+
+    pub fn forbidden(message: &str) -> Self {
+        Self::new(StatusCode::FORBIDDEN, message)
+    }
+
+    pub fn conflict(message: &str) -> Self {
+        Self::new(StatusCode::CONFLICT, message)
+    }
+
+    pub fn unavailable(message: &str, retry_after_secs: u64) -> Self {
+        Self {
+            retry_after: Some(retry_after_secs),
+            ..Self::new(StatusCode::SERVICE_UNAVAILABLE, message)
+        }
+    }
 }
 
 impl IntoResponse for IndexifyAPIError {
     fn into_response(self) -> Response {
-        tracing::error!("API Error: {} - {}", self.status_code, self.message);
-        (self.status_code, self.message).into_response()
+        tracing::error!(
+            "API Error: {} {:?} - {}",
+            self.status_code,
+            self.code,
+            self.message
+        );
+        let problem = ProblemDetails {
+            problem_type: "about:blank".to_string(),
+            title: self
+                .status_code
+                .canonical_reason()
+                .unwrap_or_default()
+                .to_string(),
+            status: self.status_code.as_u16(),
+            detail: self.message,
+            code: self.code,
+        };
+        let mut response = (
+            self.status_code,
+            [(CONTENT_TYPE, "application/problem+json")],
+            serde_json::to_string(&problem).unwrap_or_default(),
+        )
+            .into_response();
+        if let Some(retry_after) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, retry_after.into());
+        }
+        response
+    }
+}
+
+impl From<ValidationErrors> for IndexifyAPIError {
+    fn from(e: ValidationErrors) -> Self {
+        Self::bad_request(&e.to_string())
     }
 }
 
@@ -60,13 +194,95 @@ impl From<serde_json::Error> for IndexifyAPIError {
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ListParams {
     pub limit: Option<usize>,
-    pub cursor: Option<Vec<u8>>,
+    /// Continuation token returned with the previous page.
+    pub cursor: Option<String>,
+}
+
+impl ListParams {
+    /// Key the listing resumes from.
+    pub fn cursor(&self) -> Result<Option<Vec<u8>>, IndexifyAPIError> {
+        self.cursor
+            .as_deref()
+            .map(hex::decode)
+            .transpose()
+            .map_err(|_| IndexifyAPIError::bad_request("invalid cursor"))
+    }
+}
+
+/// Continuation token of a page, from the key the next page starts at.
+/// Clients pass it back as is, without relying on its format.
+pub fn encode_cursor(key: Option<Vec<u8>>) -> Option<String> {
+    key.map(hex::encode)
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CountParams {
+    /// Count the objects instead of reading the maintained counters.
+    #[serde(default)]
+    pub exact: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExportParams {
+    /// Seconds since the epoch, exports the state as of the latest backup
+    /// taken at or before then instead of the current state.
+    pub as_of: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Count {
+    pub count: u64,
+    /// Whether the count was read from counters which may drift from the
+    /// objects, rather than counted.
+    pub approximate: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TaskCounts {
+    pub pending: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    pub approximate: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BlobStoragePlacement {
+    S3 { bucket: String, region: String },
+    Disk { path: String },
+}
+
+impl From<BlobStoragePlacement> for data_model::BlobStoragePlacement {
+    fn from(placement: BlobStoragePlacement) -> Self {
+        match placement {
+            BlobStoragePlacement::S3 { bucket, region } => {
+                data_model::BlobStoragePlacement::S3 { bucket, region }
+            }
+            BlobStoragePlacement::Disk { path } => data_model::BlobStoragePlacement::Disk { path },
+        }
+    }
+}
+
+impl From<data_model::BlobStoragePlacement> for BlobStoragePlacement {
+    fn from(placement: data_model::BlobStoragePlacement) -> Self {
+        match placement {
+            data_model::BlobStoragePlacement::S3 { bucket, region } => {
+                BlobStoragePlacement::S3 { bucket, region }
+            }
+            data_model::BlobStoragePlacement::Disk { path } => BlobStoragePlacement::Disk { path },
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct Namespace {
     name: String,
     created_at: u64,
+    blob_storage: Option<BlobStoragePlacement>,
+    scheduling_weight: Option<u32>,
+    deletion: Option<NamespaceDeletion>,
+    reprocessing_policy: ReprocessingPolicy,
+    near_duplicates: Option<NearDuplicatePolicy>,
 }
 
 impl From<data_model::Namespace> for Namespace {
@@ -74,6 +290,128 @@ impl From<data_model::Namespace> for Namespace {
         Self {
             name: namespace.name,
             created_at: namespace.created_at,
+            blob_storage: namespace.blob_storage.map(|p| p.into()),
+            scheduling_weight: namespace.scheduling_weight,
+            deletion: namespace.deletion.map(|d| d.into()),
+            reprocessing_policy: namespace.reprocessing_policy.into(),
+            near_duplicates: namespace.near_duplicates.map(|p| p.into()),
+        }
+    }
+}
+
+/// What happens to an invocation when a newer version of its source is
+/// ingested
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ReprocessingPolicy {
+    /// Keep the invocation and its outputs
+    #[default]
+    KeepBoth,
+    /// Delete the outputs of the invocation
+    Replace,
+}
+
+impl From<ReprocessingPolicy> for data_model::ReprocessingPolicy {
+    fn from(policy: ReprocessingPolicy) -> Self {
+        match policy {
+            ReprocessingPolicy::KeepBoth => data_model::ReprocessingPolicy::KeepBoth,
+            ReprocessingPolicy::Replace => data_model::ReprocessingPolicy::Replace,
+        }
+    }
+}
+
+impl From<data_model::ReprocessingPolicy> for ReprocessingPolicy {
+    fn from(policy: data_model::ReprocessingPolicy) -> Self {
+        match policy {
+            data_model::ReprocessingPolicy::KeepBoth => ReprocessingPolicy::KeepBoth,
+            data_model::ReprocessingPolicy::Replace => ReprocessingPolicy::Replace,
+        }
+    }
+}
+
+/// Detection of text content ingested again with trivial differences
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+pub struct NearDuplicatePolicy {
+    /// Minimum similarity, between 0 and 1, of the content to earlier
+    /// content for it to be a near-duplicate
+    pub threshold: f64,
+    /// How far back ingested content is compared against, in seconds
+    pub window_secs: u64,
+    /// What happens to a near-duplicate, defaults to tag
+    #[serde(default)]
+    pub action: NearDuplicateAction,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NearDuplicateAction {
+    /// Invoke the graph and record the earlier invocation on the new one
+    #[default]
+    Tag,
+    /// Don't invoke the graph, answer with the earlier invocation
+    Skip,
+}
+
+impl From<NearDuplicatePolicy> for data_model::near_duplicates::NearDuplicatePolicy {
+    fn from(policy: NearDuplicatePolicy) -> Self {
+        Self {
+            threshold: policy.threshold,
+            window_secs: policy.window_secs,
+            action: match policy.action {
+                NearDuplicateAction::Tag => data_model::near_duplicates::NearDuplicateAction::Tag,
+                NearDuplicateAction::Skip => data_model::near_duplicates::NearDuplicateAction::Skip,
+            },
+        }
+    }
+}
+
+impl From<data_model::near_duplicates::NearDuplicatePolicy> for NearDuplicatePolicy {
+    fn from(policy: data_model::near_duplicates::NearDuplicatePolicy) -> Self {
+        Self {
+            threshold: policy.threshold,
+            window_secs: policy.window_secs,
+            action: match policy.action {
+                data_model::near_duplicates::NearDuplicateAction::Tag => NearDuplicateAction::Tag,
+                data_model::near_duplicates::NearDuplicateAction::Skip => NearDuplicateAction::Skip,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NamespaceDeletionStage {
+    CancelTasks,
+    DeleteComputeGraphs,
+}
+
+impl From<data_model::NamespaceDeletionStage> for NamespaceDeletionStage {
+    fn from(stage: data_model::NamespaceDeletionStage) -> Self {
+        match stage {
+            data_model::NamespaceDeletionStage::CancelTasks => NamespaceDeletionStage::CancelTasks,
+            data_model::NamespaceDeletionStage::DeleteComputeGraphs => {
+                NamespaceDeletionStage::DeleteComputeGraphs
+            }
+        }
+    }
+}
+
+/// Progress of deleting a namespace
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct NamespaceDeletion {
+    pub started_at: u64,
+    pub stage: NamespaceDeletionStage,
+    pub tasks_deleted: u64,
+    pub compute_graphs_deleted: u64,
+}
+
+impl From<data_model::NamespaceDeletion> for NamespaceDeletion {
+    fn from(deletion: data_model::NamespaceDeletion) -> Self {
+        Self {
+            started_at: deletion.started_at,
+            stage: deletion.stage.into(),
+            tasks_deleted: deletion.tasks_deleted,
+            compute_graphs_deleted: deletion.compute_graphs_deleted,
         }
     }
 }
@@ -81,6 +419,7 @@ impl From<data_model::Namespace> for Namespace {
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct NamespaceList {
     pub namespaces: Vec<Namespace>,
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
@@ -91,6 +430,299 @@ pub struct ComputeFn {
     pub reducer: bool,
     pub payload_encoder: String,
     pub image_name: String,
+    #[serde(default)]
+    pub expected_concurrency: Option<u32>,
+    #[serde(default)]
+    pub gpu: Option<GpuRequest>,
+    /// Downstream service the function calls, limiting its concurrent calls
+    /// across all graphs
+    #[serde(default)]
+    pub external_dependency: Option<ExternalDependency>,
+    /// Post-conditions every output of the function is checked against
+    #[serde(default)]
+    pub output_checks: Vec<OutputCheck>,
+    /// Content types of the inputs the function runs on, e.g. image/*, every
+    /// input when empty
+    #[serde(default)]
+    pub input_content_types: Vec<String>,
+    /// Artifacts of the namespace the function loads, prefetched by
+    /// executors
+    #[serde(default)]
+    pub artifacts: Vec<ArtifactRef>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct OutputCheck {
+    pub name: String,
+    pub condition: OutputCondition,
+    #[serde(default)]
+    pub action: OutputCheckAction,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputCondition {
+    NonEmpty,
+    JsonSchema {
+        #[schema(value_type = Object)]
+        schema: serde_json::Value,
+    },
+    Range {
+        /// JSON pointer to the number, the whole output by default
+        #[serde(default)]
+        pointer: String,
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputCheckAction {
+    #[default]
+    Record,
+    Quarantine,
+    FailTask,
+}
+
+impl From<OutputCheck> for data_model::output_checks::OutputCheck {
+    fn from(check: OutputCheck) -> Self {
+        use data_model::output_checks::{
+            OutputCheckAction as Action,
+            OutputCondition as Condition,
+        };
+        Self {
+            name: check.name,
+            condition: match check.condition {
+                OutputCondition::NonEmpty => Condition::NonEmpty,
+                OutputCondition::JsonSchema { schema } => Condition::JsonSchema { schema },
+                OutputCondition::Range { pointer, min, max } => {
+                    Condition::Range { pointer, min, max }
+                }
+            },
+            action: match check.action {
+                OutputCheckAction::Record => Action::Record,
+                OutputCheckAction::Quarantine => Action::Quarantine,
+                OutputCheckAction::FailTask => Action::FailTask,
+            },
+        }
+    }
+}
+
+impl From<data_model::output_checks::OutputCheckAction> for OutputCheckAction {
+    fn from(action: data_model::output_checks::OutputCheckAction) -> Self {
+        match action {
+            data_model::output_checks::OutputCheckAction::Record => OutputCheckAction::Record,
+            data_model::output_checks::OutputCheckAction::Quarantine => {
+                OutputCheckAction::Quarantine
+            }
+            data_model::output_checks::OutputCheckAction::FailTask => OutputCheckAction::FailTask,
+        }
+    }
+}
+
+impl From<data_model::output_checks::OutputCheck> for OutputCheck {
+    fn from(check: data_model::output_checks::OutputCheck) -> Self {
+        use data_model::output_checks::OutputCondition as Condition;
+        Self {
+            name: check.name,
+            condition: match check.condition {
+                Condition::NonEmpty => OutputCondition::NonEmpty,
+                Condition::JsonSchema { schema } => OutputCondition::JsonSchema { schema },
+                Condition::Range { pointer, min, max } => {
+                    OutputCondition::Range { pointer, min, max }
+                }
+            },
+            action: check.action.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct EdgeTransform {
+    pub source: String,
+    pub target: String,
+    /// Applied in order to the source's outputs
+    pub steps: Vec<TransformStep>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TransformStep {
+    /// Replaces the value with the one at a JSON pointer
+    Select { pointer: String },
+    /// Keeps only the listed fields
+    Pick { fields: Vec<String> },
+    /// Renames fields, from the key to the value
+    Rename { fields: HashMap<String, String> },
+    /// Sets fields to constants
+    Set {
+        #[schema(value_type = Object)]
+        fields: HashMap<String, serde_json::Value>,
+    },
+}
+
+impl From<EdgeTransform> for data_model::edge_transforms::EdgeTransform {
+    fn from(transform: EdgeTransform) -> Self {
+        use data_model::edge_transforms::TransformStep as Step;
+        Self {
+            source: transform.source,
+            target: transform.target,
+            steps: transform
+                .steps
+                .into_iter()
+                .map(|step| match step {
+                    TransformStep::Select { pointer } => Step::Select { pointer },
+                    TransformStep::Pick { fields } => Step::Pick { fields },
+                    TransformStep::Rename { fields } => Step::Rename { fields },
+                    TransformStep::Set { fields } => Step::Set { fields },
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<data_model::edge_transforms::EdgeTransform> for EdgeTransform {
+    fn from(transform: data_model::edge_transforms::EdgeTransform) -> Self {
+        use data_model::edge_transforms::TransformStep as Step;
+        Self {
+            source: transform.source,
+            target: transform.target,
+            steps: transform
+                .steps
+                .into_iter()
+                .map(|step| match step {
+                    Step::Select { pointer } => TransformStep::Select { pointer },
+                    Step::Pick { fields } => TransformStep::Pick { fields },
+                    Step::Rename { fields } => TransformStep::Rename { fields },
+                    Step::Set { fields } => TransformStep::Set { fields },
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OutputCheckViolation {
+    pub check: String,
+    pub action: OutputCheckAction,
+    pub message: String,
+}
+
+impl From<data_model::output_checks::OutputCheckViolation> for OutputCheckViolation {
+    fn from(violation: data_model::output_checks::OutputCheckViolation) -> Self {
+        Self {
+            check: violation.check,
+            action: violation.action.into(),
+            message: violation.message,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OutputCheckStats {
+    pub outputs_checked: u64,
+    /// Number of violations by check name
+    pub violations: HashMap<String, u64>,
+    pub quarantined_outputs: u64,
+    pub failed_tasks: u64,
+}
+
+impl From<data_model::output_checks::OutputCheckStats> for OutputCheckStats {
+    fn from(stats: data_model::output_checks::OutputCheckStats) -> Self {
+        Self {
+            outputs_checked: stats.outputs_checked,
+            violations: stats.violations,
+            quarantined_outputs: stats.quarantined_outputs,
+            failed_tasks: stats.failed_tasks,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GraphOutputChecks {
+    pub functions: HashMap<String, OutputCheckStats>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct QuarantinedOutput {
+    pub id: String,
+    pub compute_fn: String,
+    pub payload_size: u64,
+    pub payload_sha_256: String,
+    pub violations: Vec<OutputCheckViolation>,
+    pub quarantined_at: u64,
+}
+
+impl From<data_model::output_checks::QuarantinedOutput> for QuarantinedOutput {
+    fn from(quarantined: data_model::output_checks::QuarantinedOutput) -> Self {
+        let (payload_size, payload_sha_256) = match quarantined.output.payload {
+            data_model::OutputPayload::Fn(payload) => (payload.size, payload.sha256_hash),
+            data_model::OutputPayload::Router(_) => (0, String::new()),
+        };
+        Self {
+            id: quarantined.output.id,
+            compute_fn: quarantined.output.compute_fn_name,
+            payload_size,
+            payload_sha_256,
+            violations: quarantined.violations.into_iter().map(Into::into).collect(),
+            quarantined_at: quarantined.quarantined_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct QuarantinedOutputs {
+    pub outputs: Vec<QuarantinedOutput>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct ExternalDependency {
+    pub name: String,
+    pub max_concurrent_calls: u32,
+}
+
+impl From<ExternalDependency> for data_model::ExternalDependency {
+    fn from(dependency: ExternalDependency) -> Self {
+        Self {
+            name: dependency.name,
+            max_concurrent_calls: dependency.max_concurrent_calls,
+        }
+    }
+}
+
+impl From<data_model::ExternalDependency> for ExternalDependency {
+    fn from(dependency: data_model::ExternalDependency) -> Self {
+        Self {
+            name: dependency.name,
+            max_concurrent_calls: dependency.max_concurrent_calls,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone, Copy)]
+pub struct GpuRequest {
+    /// Share of a device, e.g. 0.25 for a quarter of a GPU
+    pub fraction: f64,
+    pub memory_bytes: u64,
+}
+
+impl From<GpuRequest> for data_model::GpuRequest {
+    fn from(request: GpuRequest) -> Self {
+        Self {
+            fraction: request.fraction,
+            memory_bytes: request.memory_bytes,
+        }
+    }
+}
+
+impl From<data_model::GpuRequest> for GpuRequest {
+    fn from(request: data_model::GpuRequest) -> Self {
+        Self {
+            fraction: request.fraction,
+            memory_bytes: request.memory_bytes,
+        }
+    }
 }
 
 impl From<&ComputeFn> for data_model::ComputeFn {
@@ -103,6 +735,12 @@ impl From<&ComputeFn> for data_model::ComputeFn {
             reducer: val.reducer,
             payload_encoder: val.payload_encoder.clone(),
             image_name: val.image_name.clone(),
+            expected_concurrency: val.expected_concurrency,
+            gpu: val.gpu.map(Into::into),
+            external_dependency: val.external_dependency.clone().map(Into::into),
+            output_checks: val.output_checks.iter().cloned().map(Into::into).collect(),
+            input_content_types: val.input_content_types.clone(),
+            artifacts: val.artifacts.iter().cloned().map(Into::into).collect(),
         }
     }
 }
@@ -117,6 +755,12 @@ impl From<ComputeFn> for data_model::ComputeFn {
             reducer: val.reducer,
             payload_encoder: val.payload_encoder.clone(),
             image_name: val.image_name.clone(),
+            expected_concurrency: val.expected_concurrency,
+            gpu: val.gpu.map(Into::into),
+            external_dependency: val.external_dependency.clone().map(Into::into),
+            output_checks: val.output_checks.iter().cloned().map(Into::into).collect(),
+            input_content_types: val.input_content_types.clone(),
+            artifacts: val.artifacts.iter().cloned().map(Into::into).collect(),
         }
     }
 }
@@ -130,6 +774,96 @@ impl From<data_model::ComputeFn> for ComputeFn {
             reducer: c.reducer,
             payload_encoder: c.payload_encoder,
             image_name: c.image_name,
+            expected_concurrency: c.expected_concurrency,
+            gpu: c.gpu.map(Into::into),
+            external_dependency: c.external_dependency.map(Into::into),
+            output_checks: c.output_checks.into_iter().map(Into::into).collect(),
+            input_content_types: c.input_content_types,
+            artifacts: c.artifacts.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct ArtifactRef {
+    pub name: String,
+    pub version: String,
+}
+
+impl From<ArtifactRef> for data_model::artifacts::ArtifactRef {
+    fn from(artifact: ArtifactRef) -> Self {
+        Self {
+            name: artifact.name,
+            version: artifact.version,
+        }
+    }
+}
+
+impl From<data_model::artifacts::ArtifactRef> for ArtifactRef {
+    fn from(artifact: data_model::artifacts::ArtifactRef) -> Self {
+        Self {
+            name: artifact.name,
+            version: artifact.version,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Artifact {
+    pub namespace: String,
+    pub name: String,
+    pub version: String,
+    pub size: u64,
+    pub sha256_hash: String,
+    pub content_type: Option<String>,
+    pub created_at: u64,
+}
+
+impl From<data_model::artifacts::Artifact> for Artifact {
+    fn from(artifact: data_model::artifacts::Artifact) -> Self {
+        Self {
+            namespace: artifact.namespace,
+            name: artifact.name,
+            version: artifact.version,
+            size: artifact.payload.size,
+            sha256_hash: artifact.payload.sha256_hash,
+            content_type: artifact.payload.content_type,
+            created_at: artifact.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Artifacts {
+    pub artifacts: Vec<Artifact>,
+    pub cursor: Option<String>,
+}
+
+/// Hashes of the artifacts an executor has fetched
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExecutorArtifacts {
+    pub artifacts: Vec<String>,
+}
+
+/// Diagnostics an executor or executor agent reports about itself, by
+/// section, e.g. `config`, `gpu`, `caches` or `crash_reports`
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DiagnosticsReport {
+    pub sections: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExecutorDiagnostics {
+    /// Milliseconds since the epoch
+    pub reported_at: u64,
+    pub sections: BTreeMap<String, serde_json::Value>,
+}
+
+impl From<crate::executors::ExecutorDiagnostics> for ExecutorDiagnostics {
+    fn from(diagnostics: crate::executors::ExecutorDiagnostics) -> Self {
+        Self {
+            reported_at: diagnostics.reported_at,
+            sections: diagnostics.sections,
         }
     }
 }
@@ -213,14 +947,122 @@ pub struct ComputeGraph {
     pub start_node: Node,
     pub nodes: HashMap<String, Node>,
     pub edges: HashMap<String, Vec<String>>,
+    /// Reshape the JSON outputs flowing along edges
+    #[serde(default)]
+    pub edge_transforms: Vec<EdgeTransform>,
     #[serde(default = "get_epoch_time_in_ms")]
     pub created_at: u64,
+    /// Key-value pairs the graph can be listed by, e.g. team=ml
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
+    /// Notified of the failures of the graph instead of the server's default
+    /// channel
+    #[serde(default)]
+    pub owners: Vec<GraphOwner>,
+    /// How long the outputs of the graph's finished invocations are kept
+    #[serde(default)]
+    pub output_retention: OutputRetention,
 }
 
-impl ComputeGraph {
-    pub fn into_data_model(
-        self,
-        code_path: &str,
+/// Counted from the creation of the invocation, outputs are kept forever when
+/// unset. Invocations can be pinned to keep their outputs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct OutputRetention {
+    /// Seconds the outputs of the functions the graph ends with are kept
+    #[serde(default)]
+    pub final_outputs_secs: Option<u64>,
+    /// Seconds the outputs of the other functions are kept
+    #[serde(default)]
+    pub intermediate_outputs_secs: Option<u64>,
+}
+
+impl From<OutputRetention> for data_model::OutputRetention {
+    fn from(retention: OutputRetention) -> Self {
+        Self {
+            final_outputs_secs: retention.final_outputs_secs,
+            intermediate_outputs_secs: retention.intermediate_outputs_secs,
+        }
+    }
+}
+
+impl From<data_model::OutputRetention> for OutputRetention {
+    fn from(retention: data_model::OutputRetention) -> Self {
+        Self {
+            final_outputs_secs: retention.final_outputs_secs,
+            intermediate_outputs_secs: retention.intermediate_outputs_secs,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct GraphOwner {
+    pub contact: OwnerContact,
+    /// Severities the owner is notified of, all of them when empty
+    #[serde(default)]
+    pub severities: Vec<Severity>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OwnerContact {
+    Email { address: String },
+    Webhook { url: String },
+    Slack { channel: String },
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Warning,
+    Critical,
+}
+
+impl From<GraphOwner> for data_model::alerts::GraphOwner {
+    fn from(owner: GraphOwner) -> Self {
+        use data_model::alerts::{OwnerContact as Contact, Severity as S};
+        Self {
+            contact: match owner.contact {
+                OwnerContact::Email { address } => Contact::Email { address },
+                OwnerContact::Webhook { url } => Contact::Webhook { url },
+                OwnerContact::Slack { channel } => Contact::Slack { channel },
+            },
+            severities: owner
+                .severities
+                .into_iter()
+                .map(|severity| match severity {
+                    Severity::Warning => S::Warning,
+                    Severity::Critical => S::Critical,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<data_model::alerts::GraphOwner> for GraphOwner {
+    fn from(owner: data_model::alerts::GraphOwner) -> Self {
+        use data_model::alerts::{OwnerContact as Contact, Severity as S};
+        Self {
+            contact: match owner.contact {
+                Contact::Email { address } => OwnerContact::Email { address },
+                Contact::Webhook { url } => OwnerContact::Webhook { url },
+                Contact::Slack { channel } => OwnerContact::Slack { channel },
+            },
+            severities: owner
+                .severities
+                .into_iter()
+                .map(|severity| match severity {
+                    S::Warning => Severity::Warning,
+                    S::Critical => Severity::Critical,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ComputeGraph {
+    pub fn into_data_model(
+        self,
+        code_path: &str,
         sha256_hash: &str,
         size: u64,
     ) -> Result<data_model::ComputeGraph, IndexifyAPIError> {
@@ -242,7 +1084,16 @@ impl ComputeGraph {
             },
             nodes,
             edges: self.edges.clone(),
+            edge_transforms: self
+                .edge_transforms
+                .iter()
+                .cloned()
+                .map(Into::into)
+                .collect(),
             created_at: 0,
+            labels: self.labels,
+            owners: self.owners.into_iter().map(Into::into).collect(),
+            output_retention: self.output_retention.into(),
         };
         Ok(compute_graph)
     }
@@ -265,7 +1116,15 @@ impl From<data_model::ComputeGraph> for ComputeGraph {
             start_node: start_fn,
             nodes,
             edges: compute_graph.edges,
+            edge_transforms: compute_graph
+                .edge_transforms
+                .into_iter()
+                .map(Into::into)
+                .collect(),
             created_at: compute_graph.created_at,
+            labels: compute_graph.labels,
+            owners: compute_graph.owners.into_iter().map(Into::into).collect(),
+            output_retention: compute_graph.output_retention.into(),
         }
     }
 }
@@ -273,12 +1132,26 @@ impl From<data_model::ComputeGraph> for ComputeGraph {
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct CreateNamespace {
     pub name: String,
+    /// Store the namespace's blobs outside of the server's default blob storage
+    #[serde(default)]
+    pub blob_storage: Option<BlobStoragePlacement>,
+    /// Share of the scheduler the namespace gets relative to other busy
+    /// namespaces, defaults to 1
+    #[serde(default)]
+    pub scheduling_weight: Option<u32>,
+    /// What happens to an invocation when a newer version of its source is
+    /// ingested, defaults to keep_both
+    #[serde(default)]
+    pub reprocessing_policy: ReprocessingPolicy,
+    /// Detect near-duplicate text content at ingestion, off by default
+    #[serde(default)]
+    pub near_duplicates: Option<NearDuplicatePolicy>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ComputeGraphsList {
     pub compute_graphs: Vec<ComputeGraph>,
-    pub cursor: Option<Vec<u8>>,
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -286,6 +1159,295 @@ pub struct DataObject {
     pub id: String,
     pub payload_size: u64,
     pub payload_sha_256: String,
+    #[serde(default)]
+    pub content_type: Option<String>,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+    #[serde(default)]
+    pub reproduced_from: Option<String>,
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub superseded_by: Option<String>,
+    /// Earlier invocation whose input this one's nearly duplicates.
+    #[serde(default)]
+    pub near_duplicate_of: Option<String>,
+    /// Test invocation of the graph.
+    #[serde(default)]
+    pub test: bool,
+}
+
+/// Inputs to test a graph on, samples of its latest inputs are used when
+/// none are given
+#[derive(Debug, Serialize, Deserialize, ToSchema, Default)]
+pub struct TestComputeGraph {
+    /// Objects the graph is invoked with, serialized as JSON.
+    #[serde(default)]
+    pub inputs: Vec<serde_json::Value>,
+    /// Number of the latest inputs of the graph to run it on again when no
+    /// inputs are given, 1 by default.
+    #[serde(default)]
+    pub samples: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TestInvocations {
+    pub invocation_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CodeParams {
+    /// Version of the graph whose code to download, the one being rolled out
+    /// or the stable one otherwise.
+    pub version: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CanaryParams {
+    /// Share of new invocations which run the new version, in percent.
+    pub percent: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FnVersionStats {
+    pub successful_tasks: u64,
+    pub failed_tasks: u64,
+    pub failure_rate: f64,
+    pub avg_task_duration_ms: f64,
+}
+
+/// Outcomes of the invocations of a version of a graph which finished since
+/// its canary rollout started
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct VersionStats {
+    pub version: GraphVersion,
+    pub invocations: u64,
+    /// Invocations with at least one failed task.
+    pub failed_invocations: u64,
+    pub failure_rate: f64,
+    pub avg_invocation_duration_ms: f64,
+    pub functions: HashMap<String, FnVersionStats>,
+}
+
+fn ratio(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+impl From<data_model::canary::VersionStats> for VersionStats {
+    fn from(stats: data_model::canary::VersionStats) -> Self {
+        let functions = stats
+            .functions
+            .into_iter()
+            .map(|(name, fn_stats)| {
+                let tasks = fn_stats.successful_tasks + fn_stats.failed_tasks;
+                let fn_stats = FnVersionStats {
+                    successful_tasks: fn_stats.successful_tasks,
+                    failed_tasks: fn_stats.failed_tasks,
+                    failure_rate: ratio(fn_stats.failed_tasks, tasks),
+                    avg_task_duration_ms: ratio(fn_stats.task_duration_ms, tasks),
+                };
+                (name, fn_stats)
+            })
+            .collect();
+        Self {
+            version: stats.version,
+            invocations: stats.invocations,
+            failed_invocations: stats.failed_invocations,
+            failure_rate: ratio(stats.failed_invocations, stats.invocations),
+            avg_invocation_duration_ms: ratio(stats.invocation_duration_ms, stats.invocations),
+            functions,
+        }
+    }
+}
+
+/// Canary rollout of a new version of a compute graph, comparing it with the
+/// stable version
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GraphCanary {
+    pub compute_graph: String,
+    pub percent: u32,
+    pub started_at: u64,
+    pub stable: VersionStats,
+    pub canary: VersionStats,
+}
+
+impl From<data_model::canary::GraphCanary> for GraphCanary {
+    fn from(canary: data_model::canary::GraphCanary) -> Self {
+        Self {
+            compute_graph: canary.graph.name,
+            percent: canary.percent,
+            started_at: canary.started_at,
+            stable: canary.stable.into(),
+            canary: canary.canary.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FunctionProvenance {
+    pub image_name: String,
+    pub definition_sha256: String,
+}
+
+/// What an invocation ran with
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InvocationProvenance {
+    pub invocation_id: String,
+    pub reproduced_from: Option<String>,
+    pub graph_version: GraphVersion,
+    pub graph_code_sha256: String,
+    pub graph_structure_sha256: String,
+    pub functions: HashMap<String, FunctionProvenance>,
+    pub input_sha256: String,
+    pub namespace_blob_storage: Option<BlobStoragePlacement>,
+    pub namespace_scheduling_weight: Option<u32>,
+    pub recorded_at: u64,
+}
+
+impl InvocationProvenance {
+    pub fn new(
+        invocation: &data_model::InvocationPayload,
+        provenance: data_model::provenance::InvocationProvenance,
+    ) -> Self {
+        Self {
+            invocation_id: invocation.id.clone(),
+            reproduced_from: invocation.reproduced_from.clone(),
+            graph_version: provenance.graph_version,
+            graph_code_sha256: provenance.graph_code_sha256,
+            graph_structure_sha256: provenance.graph_structure_sha256,
+            functions: provenance
+                .functions
+                .into_iter()
+                .map(|(name, function)| {
+                    (
+                        name,
+                        FunctionProvenance {
+                            image_name: function.image_name,
+                            definition_sha256: function.definition_sha256,
+                        },
+                    )
+                })
+                .collect(),
+            input_sha256: provenance.input_sha256,
+            namespace_blob_storage: provenance.namespace_blob_storage.map(Into::into),
+            namespace_scheduling_weight: provenance.namespace_scheduling_weight,
+            recorded_at: provenance.recorded_at,
+        }
+    }
+}
+
+/// Collects invocation tags passed as `tags.<key>=<value>` query parameters.
+pub fn tags_from_query(
+    params: &HashMap<String, String>,
+) -> Result<HashMap<String, String>, IndexifyAPIError> {
+    prefixed_from_query(params, "tags.", "tag")
+}
+
+/// Collects compute graph label selectors passed as `labels.<key>=<value>`
+/// query parameters.
+pub fn labels_from_query(
+    params: &HashMap<String, String>,
+) -> Result<HashMap<String, String>, IndexifyAPIError> {
+    prefixed_from_query(params, "labels.", "label")
+}
+
+fn prefixed_from_query(
+    params: &HashMap<String, String>,
+    prefix: &str,
+    kind: &str,
+) -> Result<HashMap<String, String>, IndexifyAPIError> {
+    let mut entries = HashMap::new();
+    for (key, value) in params {
+        if let Some(key) = key.strip_prefix(prefix) {
+            if key.is_empty() || key.contains(['=', '|']) || value.contains('|') {
+                return Err(IndexifyAPIError::bad_request(&format!(
+                    "invalid {}: {}={}",
+                    kind, key, value
+                )));
+            }
+            entries.insert(key.to_string(), value.to_string());
+        }
+    }
+    Ok(entries)
+}
+
+/// Largest correlation id accepted on an invocation.
+const MAX_CORRELATION_ID_LEN: usize = 256;
+
+/// Largest idempotency key accepted on a request.
+const MAX_REQUEST_ID_LEN: usize = 256;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, Default)]
+pub struct TraceContext {
+    pub trace_id: Option<String>,
+    pub parent_span_id: Option<String>,
+    pub correlation_id: Option<String>,
+}
+
+impl From<data_model::TraceContext> for TraceContext {
+    fn from(trace: data_model::TraceContext) -> Self {
+        Self {
+            trace_id: trace.trace_id,
+            parent_span_id: trace.parent_span_id,
+            correlation_id: trace.correlation_id,
+        }
+    }
+}
+
+/// Reads the trace of an invocation from the W3C `traceparent` header and the
+/// `x-correlation-id` header. A malformed `traceparent` is ignored, as the
+/// spec asks, since it usually comes from a proxy rather than the caller.
+pub fn trace_from_headers(
+    headers: &HeaderMap,
+) -> Result<data_model::TraceContext, IndexifyAPIError> {
+    let mut trace = data_model::TraceContext::default();
+    if let Some(traceparent) = headers.get("traceparent").and_then(|v| v.to_str().ok()) {
+        let parts = traceparent.trim().split('-').collect::<Vec<_>>();
+        let is_hex = |s: &str, len: usize| {
+            s.len() == len && s.chars().all(|c| c.is_ascii_hexdigit()) && s.contains(|c| c != '0')
+        };
+        if let [_version, trace_id, span_id, _flags] = parts[..] {
+            if is_hex(trace_id, 32) && is_hex(span_id, 16) {
+                trace.trace_id = Some(trace_id.to_lowercase());
+                trace.parent_span_id = Some(span_id.to_lowercase());
+            }
+        }
+    }
+    if let Some(correlation_id) = headers.get("x-correlation-id") {
+        let correlation_id = correlation_id
+            .to_str()
+            .map_err(|_| IndexifyAPIError::bad_request("x-correlation-id must be ascii"))?;
+        if correlation_id.is_empty() || correlation_id.len() > MAX_CORRELATION_ID_LEN {
+            return Err(IndexifyAPIError::bad_request(&format!(
+                "x-correlation-id must be between 1 and {} characters",
+                MAX_CORRELATION_ID_LEN
+            )));
+        }
+        trace.correlation_id = Some(correlation_id.to_string());
+    }
+    Ok(trace)
+}
+
+/// Id of a request its client sends again when retrying it, from the
+/// `Idempotency-Key` header.
+pub fn request_id_from_headers(headers: &HeaderMap) -> Result<Option<String>, IndexifyAPIError> {
+    let Some(request_id) = headers.get("idempotency-key") else {
+        return Ok(None);
+    };
+    let request_id = request_id
+        .to_str()
+        .map_err(|_| IndexifyAPIError::bad_request("Idempotency-Key must be ascii"))?;
+    if request_id.is_empty() || request_id.len() > MAX_REQUEST_ID_LEN {
+        return Err(IndexifyAPIError::bad_request(&format!(
+            "Idempotency-Key must be between 1 and {} characters",
+            MAX_REQUEST_ID_LEN
+        )));
+    }
+    Ok(Some(request_id.to_string()))
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -309,7 +1471,45 @@ pub struct CreateNamespaceResponse {
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct GraphInvocations {
     pub invocations: Vec<DataObject>,
-    pub cursor: Option<Vec<u8>>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct DependencyHealth {
+    pub name: String,
+    pub healthy: bool,
+    pub error: Option<String>,
+    /// Whether the server isn't ready while the dependency is unhealthy
+    pub critical: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct HealthStatus {
+    pub healthy: bool,
+    pub dependencies: Vec<DependencyHealth>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ArchivedInvocation {
+    pub id: String,
+    pub created_at: u64,
+    pub archived_at: u64,
+}
+
+impl From<data_model::ArchivedInvocation> for ArchivedInvocation {
+    fn from(archive: data_model::ArchivedInvocation) -> Self {
+        Self {
+            id: archive.invocation_id,
+            created_at: archive.created_at,
+            archived_at: archive.archived_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ArchivedInvocations {
+    pub invocations: Vec<ArchivedInvocation>,
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -325,6 +1525,7 @@ pub struct GraphInputFile {
     pub metadata: serde_json::Value,
     pub sha_256: String,
     pub size: u64,
+    pub content_type: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -350,6 +1551,249 @@ impl From<data_model::TaskOutcome> for TaskOutcome {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub enum TaskErrorClass {
+    #[serde(rename = "unknown")]
+    Unknown,
+    #[serde(rename = "user_code")]
+    UserCode,
+    #[serde(rename = "out_of_memory")]
+    OutOfMemory,
+    #[serde(rename = "timeout")]
+    Timeout,
+    #[serde(rename = "infrastructure")]
+    Infrastructure,
+}
+
+impl From<TaskErrorClass> for data_model::TaskErrorClass {
+    fn from(val: TaskErrorClass) -> Self {
+        match val {
+            TaskErrorClass::Unknown => data_model::TaskErrorClass::Unknown,
+            TaskErrorClass::UserCode => data_model::TaskErrorClass::UserCode,
+            TaskErrorClass::OutOfMemory => data_model::TaskErrorClass::OutOfMemory,
+            TaskErrorClass::Timeout => data_model::TaskErrorClass::Timeout,
+            TaskErrorClass::Infrastructure => data_model::TaskErrorClass::Infrastructure,
+        }
+    }
+}
+
+impl From<data_model::TaskErrorClass> for TaskErrorClass {
+    fn from(class: data_model::TaskErrorClass) -> Self {
+        match class {
+            data_model::TaskErrorClass::Unknown => TaskErrorClass::Unknown,
+            data_model::TaskErrorClass::UserCode => TaskErrorClass::UserCode,
+            data_model::TaskErrorClass::OutOfMemory => TaskErrorClass::OutOfMemory,
+            data_model::TaskErrorClass::Timeout => TaskErrorClass::Timeout,
+            data_model::TaskErrorClass::Infrastructure => TaskErrorClass::Infrastructure,
+        }
+    }
+}
+
+/// Resources consumed by a task, as measured by the executor
+#[derive(Debug, Serialize, Deserialize, Clone, ToSchema)]
+pub struct ResourceUsage {
+    pub cpu_seconds: f64,
+    pub peak_memory_bytes: u64,
+    #[serde(default)]
+    pub gpu_seconds: f64,
+}
+
+impl From<ResourceUsage> for data_model::ResourceUsage {
+    fn from(usage: ResourceUsage) -> Self {
+        Self {
+            cpu_seconds: usage.cpu_seconds,
+            peak_memory_bytes: usage.peak_memory_bytes,
+            gpu_seconds: usage.gpu_seconds,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ResourceUsageStats {
+    pub tasks: u64,
+    pub total_cpu_seconds: f64,
+    pub total_gpu_seconds: f64,
+    pub avg_cpu_seconds: f64,
+    pub avg_peak_memory_bytes: f64,
+    pub avg_gpu_seconds: f64,
+    pub max_peak_memory_bytes: u64,
+}
+
+impl From<data_model::ResourceUsageStats> for ResourceUsageStats {
+    fn from(stats: data_model::ResourceUsageStats) -> Self {
+        Self {
+            tasks: stats.tasks,
+            total_cpu_seconds: stats.total_cpu_seconds,
+            total_gpu_seconds: stats.total_gpu_seconds,
+            avg_cpu_seconds: stats.avg_cpu_seconds,
+            avg_peak_memory_bytes: stats.avg_peak_memory_bytes,
+            avg_gpu_seconds: stats.avg_gpu_seconds,
+            max_peak_memory_bytes: stats.max_peak_memory_bytes,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GraphResourceUsage {
+    pub graph: Option<ResourceUsageStats>,
+    pub functions: HashMap<String, ResourceUsageStats>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CostEstimateRequest {
+    /// Number of invocations to estimate
+    pub invocations: u64,
+    /// Average size of an invocation's input, defaults to the size of past
+    /// inputs
+    #[serde(default)]
+    pub input_size_bytes: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FunctionEstimate {
+    pub compute_fn: String,
+    pub tasks: f64,
+    pub cpu_seconds: f64,
+    pub gpu_seconds: f64,
+    pub output_bytes: f64,
+}
+
+impl From<crate::cost_estimate::FunctionEstimate> for FunctionEstimate {
+    fn from(estimate: crate::cost_estimate::FunctionEstimate) -> Self {
+        Self {
+            compute_fn: estimate.compute_fn,
+            tasks: estimate.tasks,
+            cpu_seconds: estimate.cpu_seconds,
+            gpu_seconds: estimate.gpu_seconds,
+            output_bytes: estimate.output_bytes,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CostEstimate {
+    pub invocations: u64,
+    /// Past invocations the estimate is based on
+    pub sampled_invocations: u64,
+    pub functions: Vec<FunctionEstimate>,
+    pub tasks: f64,
+    pub cpu_seconds: f64,
+    pub gpu_seconds: f64,
+    pub storage_bytes: f64,
+}
+
+impl From<crate::cost_estimate::CostEstimate> for CostEstimate {
+    fn from(estimate: crate::cost_estimate::CostEstimate) -> Self {
+        Self {
+            invocations: estimate.invocations,
+            sampled_invocations: estimate.sampled_invocations,
+            functions: estimate.functions.into_iter().map(Into::into).collect(),
+            tasks: estimate.tasks,
+            cpu_seconds: estimate.cpu_seconds,
+            gpu_seconds: estimate.gpu_seconds,
+            storage_bytes: estimate.storage_bytes,
+        }
+    }
+}
+
+/// Structured failure information reported by the executor
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct TaskFailure {
+    pub error_class: TaskErrorClass,
+    #[serde(default)]
+    pub retryable: bool,
+    pub exit_code: Option<i32>,
+}
+
+impl From<TaskFailure> for data_model::TaskFailure {
+    fn from(val: TaskFailure) -> Self {
+        data_model::TaskFailure {
+            error_class: val.error_class.into(),
+            retryable: val.retryable,
+            exit_code: val.exit_code,
+        }
+    }
+}
+
+impl From<data_model::TaskFailure> for TaskFailure {
+    fn from(failure: data_model::TaskFailure) -> Self {
+        Self {
+            error_class: failure.error_class.into(),
+            retryable: failure.retryable,
+            exit_code: failure.exit_code,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskRejectionReason {
+    UnsupportedVersion,
+    MissingDependency,
+    OverCapacity,
+    Other,
+}
+
+impl From<TaskRejectionReason> for data_model::TaskRejectionReason {
+    fn from(reason: TaskRejectionReason) -> Self {
+        match reason {
+            TaskRejectionReason::UnsupportedVersion => {
+                data_model::TaskRejectionReason::UnsupportedVersion
+            }
+            TaskRejectionReason::MissingDependency => {
+                data_model::TaskRejectionReason::MissingDependency
+            }
+            TaskRejectionReason::OverCapacity => data_model::TaskRejectionReason::OverCapacity,
+            TaskRejectionReason::Other => data_model::TaskRejectionReason::Other,
+        }
+    }
+}
+
+impl From<data_model::TaskRejectionReason> for TaskRejectionReason {
+    fn from(reason: data_model::TaskRejectionReason) -> Self {
+        match reason {
+            data_model::TaskRejectionReason::UnsupportedVersion => {
+                TaskRejectionReason::UnsupportedVersion
+            }
+            data_model::TaskRejectionReason::MissingDependency => {
+                TaskRejectionReason::MissingDependency
+            }
+            data_model::TaskRejectionReason::OverCapacity => TaskRejectionReason::OverCapacity,
+            data_model::TaskRejectionReason::Other => TaskRejectionReason::Other,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TaskRejection {
+    pub executor_id: String,
+    pub reason: TaskRejectionReason,
+    pub message: Option<String>,
+    pub rejected_at: u64,
+}
+
+impl From<data_model::TaskRejection> for TaskRejection {
+    fn from(rejection: data_model::TaskRejection) -> Self {
+        Self {
+            executor_id: rejection.executor_id.to_string(),
+            reason: rejection.reason.into(),
+            message: rejection.message,
+            rejected_at: rejection.rejected_at,
+        }
+    }
+}
+
+/// Sent by an executor that can't run a task it was given
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RejectTask {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub invocation_id: String,
+    pub reason: TaskRejectionReason,
+    pub message: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct Task {
     pub id: String,
@@ -361,6 +1805,14 @@ pub struct Task {
     pub outcome: TaskOutcome,
     pub reducer_output_id: Option<String>,
     pub graph_version: GraphVersion,
+    pub failure: Option<TaskFailure>,
+    pub output_check_violations: Vec<OutputCheckViolation>,
+    pub rejections: Vec<TaskRejection>,
+    pub trace: TraceContext,
+    pub priority: i32,
+    /// The outputs of the task were deleted by the output retention of its
+    /// graph
+    pub outputs_expired: bool,
 }
 
 impl From<data_model::Task> for Task {
@@ -375,6 +1827,25 @@ impl From<data_model::Task> for Task {
             outcome: task.outcome.into(),
             reducer_output_id: task.reducer_output_id,
             graph_version: task.graph_version,
+            failure: task
+                .diagnostics
+                .as_ref()
+                .and_then(|diagnostics| diagnostics.failure.clone())
+                .map(Into::into),
+            output_check_violations: task
+                .diagnostics
+                .map(|diagnostics| {
+                    diagnostics
+                        .output_check_violations
+                        .into_iter()
+                        .map(Into::into)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            rejections: task.rejections.into_iter().map(Into::into).collect(),
+            trace: task.trace.into(),
+            priority: task.priority,
+            outputs_expired: task.outputs_expired,
         }
     }
 }
@@ -382,7 +1853,437 @@ impl From<data_model::Task> for Task {
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct Tasks {
     pub tasks: Vec<Task>,
-    pub cursor: Option<Vec<u8>>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct QueuedTask {
+    pub task: Task,
+    /// Executor the task is allocated to, none while it waits for placement
+    pub executor_id: Option<String>,
+}
+
+impl From<state_store::scanner::QueuedTask> for QueuedTask {
+    fn from(queued: state_store::scanner::QueuedTask) -> Self {
+        Self {
+            task: queued.task.into(),
+            executor_id: queued.executor_id.map(|id| id.to_string()),
+        }
+    }
+}
+
+/// Unfinished tasks of a namespace, the ones waiting for placement first in
+/// the order they're placed in
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct TaskQueue {
+    pub tasks: Vec<QueuedTask>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TaskIntervention {
+    /// Takes the task off its executor so that it's placed again
+    Requeue,
+    /// Allocates the task to an executor, bypassing placement
+    Assign {
+        executor_id: String,
+    },
+    SetPriority {
+        priority: i32,
+    },
+    /// Finishes the task as failed
+    Fail,
+}
+
+impl From<TaskIntervention> for data_model::audit::TaskIntervention {
+    fn from(intervention: TaskIntervention) -> Self {
+        match intervention {
+            TaskIntervention::Requeue => Self::Requeue,
+            TaskIntervention::Assign { executor_id } => Self::Assign {
+                executor_id: data_model::ExecutorId::new(executor_id),
+            },
+            TaskIntervention::SetPriority { priority } => Self::SetPriority { priority },
+            TaskIntervention::Fail => Self::Fail,
+        }
+    }
+}
+
+impl From<data_model::audit::TaskIntervention> for TaskIntervention {
+    fn from(intervention: data_model::audit::TaskIntervention) -> Self {
+        use data_model::audit::TaskIntervention as Intervention;
+        match intervention {
+            Intervention::Requeue => Self::Requeue,
+            Intervention::Assign { executor_id } => Self::Assign {
+                executor_id: executor_id.to_string(),
+            },
+            Intervention::SetPriority { priority } => Self::SetPriority { priority },
+            Intervention::Fail => Self::Fail,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct InterveneTask {
+    pub intervention: TaskIntervention,
+    /// Why the task is intervened on, recorded in the audit log
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AuditLogEntry {
+    pub id: String,
+    pub recorded_at: u64,
+    pub compute_graph: String,
+    pub invocation_id: String,
+    pub compute_fn: String,
+    pub task_id: String,
+    pub intervention: TaskIntervention,
+    /// Executor the task was allocated to before the intervention
+    pub executor_id: Option<String>,
+    pub reason: Option<String>,
+}
+
+impl From<data_model::audit::AuditLogEntry> for AuditLogEntry {
+    fn from(entry: data_model::audit::AuditLogEntry) -> Self {
+        Self {
+            id: entry.id,
+            recorded_at: entry.recorded_at,
+            compute_graph: entry.compute_graph,
+            invocation_id: entry.invocation_id,
+            compute_fn: entry.compute_fn,
+            task_id: entry.task_id.to_string(),
+            intervention: entry.intervention.into(),
+            executor_id: entry.executor_id.map(|id| id.to_string()),
+            reason: entry.reason,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AuditLog {
+    pub entries: Vec<AuditLogEntry>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Activity {
+    GraphRegistered {
+        compute_graph: String,
+        version: GraphVersion,
+    },
+    InvocationStarted {
+        compute_graph: String,
+        invocation_id: String,
+    },
+    InvocationFinished {
+        compute_graph: String,
+        invocation_id: String,
+    },
+    ExecutorJoined {
+        executor_id: String,
+    },
+    /// The state store went over or back under one of its disk limits
+    DiskQuota {
+        message: String,
+    },
+}
+
+impl From<data_model::activity::Activity> for Activity {
+    fn from(activity: data_model::activity::Activity) -> Self {
+        use data_model::activity::Activity as A;
+        match activity {
+            A::GraphRegistered {
+                compute_graph,
+                version,
+            } => Self::GraphRegistered {
+                compute_graph,
+                version,
+            },
+            A::InvocationStarted {
+                compute_graph,
+                invocation_id,
+            } => Self::InvocationStarted {
+                compute_graph,
+                invocation_id,
+            },
+            A::InvocationFinished {
+                compute_graph,
+                invocation_id,
+            } => Self::InvocationFinished {
+                compute_graph,
+                invocation_id,
+            },
+            A::ExecutorJoined { executor_id } => Self::ExecutorJoined {
+                executor_id: executor_id.to_string(),
+            },
+            A::DiskQuota { message } => Self::DiskQuota { message },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ActivityEvent {
+    pub id: String,
+    /// None for events of the whole server
+    pub namespace: Option<String>,
+    pub recorded_at: u64,
+    #[serde(flatten)]
+    pub activity: Activity,
+}
+
+impl From<data_model::activity::ActivityEvent> for ActivityEvent {
+    fn from(event: data_model::activity::ActivityEvent) -> Self {
+        Self {
+            id: event.id,
+            namespace: event.namespace,
+            recorded_at: event.recorded_at,
+            activity: event.activity.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ActivityFeed {
+    pub events: Vec<ActivityEvent>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct CreateSubscription {
+    /// Url the events of the namespace are posted to
+    pub url: String,
+    /// Key of the HMAC-SHA256 signature of the events
+    pub secret: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Subscription {
+    pub id: String,
+    pub url: String,
+    pub created_at: u64,
+    /// Sequence number of the last event published to the subscription
+    pub last_sequence: u64,
+    /// Sequence number of the last event the subscriber acknowledged
+    pub delivered_sequence: u64,
+}
+
+impl From<data_model::subscriptions::Subscription> for Subscription {
+    fn from(subscription: data_model::subscriptions::Subscription) -> Self {
+        Self {
+            id: subscription.id,
+            url: subscription.url,
+            created_at: subscription.created_at,
+            last_sequence: subscription.last_sequence,
+            delivered_sequence: subscription.delivered_sequence,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct Subscriptions {
+    pub subscriptions: Vec<Subscription>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StateStoreBackup {
+    pub backup_id: u32,
+    /// Seconds since the epoch.
+    pub timestamp: i64,
+    pub size_bytes: u64,
+    pub num_files: u32,
+}
+
+impl From<state_store::backup::BackupInfo> for StateStoreBackup {
+    fn from(info: state_store::backup::BackupInfo) -> Self {
+        Self {
+            backup_id: info.backup_id,
+            timestamp: info.timestamp,
+            size_bytes: info.size_bytes,
+            num_files: info.num_files,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct StateStoreBackups {
+    pub backups: Vec<StateStoreBackup>,
+}
+
+/// Records removed by a reconciliation of the orphans of deleted graphs and
+/// invocations.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct OrphansRemoved {
+    pub tasks: u64,
+    pub outputs: u64,
+    /// Deleted graphs whose tasks compaction doesn't need to drop anymore.
+    pub tombstones: u64,
+}
+
+impl From<state_store::orphans::OrphansRemoved> for OrphansRemoved {
+    fn from(removed: state_store::orphans::OrphansRemoved) -> Self {
+        Self {
+            tasks: removed.tasks,
+            outputs: removed.outputs,
+            tombstones: removed.tombstones,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FsckParams {
+    /// Quarantines unreadable records and removes dangling index entries and
+    /// orphans, rather than only reporting them. Nothing is repaired while
+    /// records don't decrypt with the configured keys.
+    #[serde(default)]
+    pub repair: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FsckProblemKind {
+    Unreadable,
+    Undecryptable,
+    DanglingIndexEntry,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FsckProblem {
+    pub kind: FsckProblemKind,
+    pub column: String,
+    pub key: String,
+    pub reason: String,
+}
+
+/// Problems found by a check of the state store, the first ones of which
+/// are listed.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FsckReport {
+    pub records_checked: u64,
+    pub unreadable: u64,
+    /// Records which don't decrypt with the configured keys, likely intact.
+    pub undecryptable: u64,
+    pub dangling_index_entries: u64,
+    /// Orphaned tasks and outputs, not checked while tasks or outputs are
+    /// unreadable unless repairing.
+    pub orphans: Option<OrphansRemoved>,
+    pub problems: Vec<FsckProblem>,
+    pub repaired: bool,
+}
+
+impl From<state_store::fsck::FsckReport> for FsckReport {
+    fn from(report: state_store::fsck::FsckReport) -> Self {
+        Self {
+            records_checked: report.records_checked,
+            unreadable: report.unreadable,
+            undecryptable: report.undecryptable,
+            dangling_index_entries: report.dangling_index_entries,
+            orphans: report.orphans.map(Into::into),
+            problems: report
+                .problems
+                .into_iter()
+                .map(|problem| FsckProblem {
+                    kind: match problem.kind {
+                        state_store::fsck::ProblemKind::Unreadable => FsckProblemKind::Unreadable,
+                        state_store::fsck::ProblemKind::Undecryptable => {
+                            FsckProblemKind::Undecryptable
+                        }
+                        state_store::fsck::ProblemKind::DanglingIndexEntry => {
+                            FsckProblemKind::DanglingIndexEntry
+                        }
+                    },
+                    column: problem.column,
+                    key: problem.key,
+                    reason: problem.reason,
+                })
+                .collect(),
+            repaired: report.repaired,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NamespaceEvent {
+    TaskCompleted {
+        compute_graph: String,
+        invocation_id: String,
+        compute_fn: String,
+        task_id: String,
+        outcome: TaskOutcome,
+    },
+    InvocationFinished {
+        compute_graph: String,
+        invocation_id: String,
+    },
+}
+
+impl From<data_model::subscriptions::NamespaceEvent> for NamespaceEvent {
+    fn from(event: data_model::subscriptions::NamespaceEvent) -> Self {
+        match event {
+            data_model::subscriptions::NamespaceEvent::TaskCompleted {
+                compute_graph,
+                invocation_id,
+                compute_fn,
+                task_id,
+                outcome,
+            } => NamespaceEvent::TaskCompleted {
+                compute_graph,
+                invocation_id,
+                compute_fn,
+                task_id: task_id.to_string(),
+                outcome: outcome.into(),
+            },
+            data_model::subscriptions::NamespaceEvent::InvocationFinished {
+                compute_graph,
+                invocation_id,
+            } => NamespaceEvent::InvocationFinished {
+                compute_graph,
+                invocation_id,
+            },
+        }
+    }
+}
+
+/// Body of the requests delivering events to subscribers, signed in the
+/// X-Indexify-Signature header
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SubscriptionEvent {
+    pub namespace: String,
+    pub subscription_id: String,
+    pub sequence: u64,
+    pub created_at: u64,
+    pub event: NamespaceEvent,
+}
+
+impl From<data_model::subscriptions::SubscriptionEvent> for SubscriptionEvent {
+    fn from(event: data_model::subscriptions::SubscriptionEvent) -> Self {
+        Self {
+            namespace: event.namespace,
+            subscription_id: event.subscription_id,
+            sequence: event.sequence,
+            created_at: event.created_at,
+            event: event.event.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SubscriptionEvents {
+    pub events: Vec<SubscriptionEvent>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct SubscriptionEventsParams {
+    pub from_sequence: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ReplaySubscription {
+    /// Sequence number of the first event to deliver again
+    pub from_sequence: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -403,7 +2304,36 @@ impl From<data_model::NodeOutput> for FnOutput {
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct FnOutputs {
     pub outputs: Vec<FnOutput>,
-    pub cursor: Option<Vec<u8>>,
+    pub cursor: Option<String>,
+}
+
+/// Executor capacity a compute function needs, for provisioning executors
+/// ahead of demand.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FunctionCapacity {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub image_name: String,
+    pub expected_concurrency: Option<u32>,
+    pub pending_tasks: u64,
+    pub desired_capacity: u64,
+    pub executors: u64,
+}
+
+impl From<crate::executors::FunctionCapacity> for FunctionCapacity {
+    fn from(capacity: crate::executors::FunctionCapacity) -> Self {
+        Self {
+            namespace: capacity.namespace,
+            compute_graph: capacity.compute_graph,
+            compute_fn: capacity.compute_fn,
+            image_name: capacity.image_name,
+            expected_concurrency: capacity.expected_concurrency,
+            pending_tasks: capacity.pending_tasks,
+            desired_capacity: capacity.desired_capacity,
+            executors: capacity.executors,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
@@ -411,12 +2341,58 @@ pub struct InvocationId {
     pub id: String,
 }
 
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UrlFetchStatus {
+    Pending,
+    Failed,
+}
+
+/// An invocation waiting for the download of its input, or which failed to
+/// be invoked with it.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct UrlFetch {
+    pub url: String,
+    pub status: UrlFetchStatus,
+    pub error: Option<String>,
+    pub created_at: u64,
+}
+
+impl From<data_model::UrlFetch> for UrlFetch {
+    fn from(fetch: data_model::UrlFetch) -> Self {
+        UrlFetch {
+            url: fetch.url,
+            status: match fetch.error {
+                Some(_) => UrlFetchStatus::Failed,
+                None => UrlFetchStatus::Pending,
+            },
+            error: fetch.error,
+            created_at: fetch.created_at,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct ExecutorMetadata {
     pub id: String,
     pub addr: String,
     pub image_name: String,
     pub labels: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub gpus: Vec<GpuDevice>,
+    /// Most tasks allocated to the executor at once
+    #[serde(default)]
+    pub capacity: Option<u32>,
+    /// Version of the executor software
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Hashes of the artifacts the executor has fetched
+    #[serde(default)]
+    pub artifacts: Vec<String>,
+    /// Most tasks allocated to the executor ahead of their completion,
+    /// bounded by the server
+    #[serde(default)]
+    pub prefetch_window: Option<u32>,
 }
 
 impl From<data_model::ExecutorMetadata> for ExecutorMetadata {
@@ -426,6 +2402,255 @@ impl From<data_model::ExecutorMetadata> for ExecutorMetadata {
             addr: executor.addr,
             image_name: executor.image_name,
             labels: executor.labels,
+            gpus: executor.gpus.into_iter().map(Into::into).collect(),
+            capacity: executor.capacity,
+            version: executor.version,
+            artifacts: executor.artifacts,
+            prefetch_window: executor.prefetch_window,
+        }
+    }
+}
+
+/// An executor agent running several runners, each with its own image, over a
+/// single connection.
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExecutorAgent {
+    pub addr: String,
+    /// Labels shared by all runners of the agent
+    #[serde(default)]
+    pub labels: HashMap<String, serde_json::Value>,
+    /// Version of the agent software, reported for all of its runners
+    #[serde(default)]
+    pub version: Option<String>,
+    pub runners: Vec<AgentRunner>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct AgentRunner {
+    pub id: String,
+    pub image_name: String,
+    #[serde(default)]
+    pub labels: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub gpus: Vec<GpuDevice>,
+    #[serde(default)]
+    pub capacity: Option<u32>,
+    /// Hashes of the artifacts the runner has fetched
+    #[serde(default)]
+    pub artifacts: Vec<String>,
+    /// Most tasks allocated to the runner ahead of their completion,
+    /// bounded by the server
+    #[serde(default)]
+    pub prefetch_window: Option<u32>,
+}
+
+/// Tasks allocated to one runner of an executor agent
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct RunnerTasks {
+    pub runner_id: String,
+    /// Executor id the runner reports task outcomes and rejections with
+    pub executor_id: String,
+    pub tasks: Vec<Task>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema, Clone)]
+pub struct GpuDevice {
+    pub id: String,
+    pub memory_bytes: u64,
+}
+
+impl From<GpuDevice> for data_model::GpuDevice {
+    fn from(device: GpuDevice) -> Self {
+        Self {
+            id: device.id,
+            memory_bytes: device.memory_bytes,
+        }
+    }
+}
+
+impl From<data_model::GpuDevice> for GpuDevice {
+    fn from(device: data_model::GpuDevice) -> Self {
+        Self {
+            id: device.id,
+            memory_bytes: device.memory_bytes,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct GpuDeviceUtilization {
+    pub executor_id: String,
+    pub device_id: String,
+    pub memory_bytes: u64,
+    pub allocated_memory_bytes: u64,
+    pub allocated_fraction: f64,
+    pub tasks: u64,
+}
+
+impl From<crate::executors::GpuDeviceUtilization> for GpuDeviceUtilization {
+    fn from(utilization: crate::executors::GpuDeviceUtilization) -> Self {
+        Self {
+            executor_id: utilization.executor_id.to_string(),
+            device_id: utilization.device_id,
+            memory_bytes: utilization.memory_bytes,
+            allocated_memory_bytes: utilization.allocated_memory_bytes,
+            allocated_fraction: utilization.allocated_fraction,
+            tasks: utilization.tasks,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutorHealth {
+    Healthy,
+    Degraded,
+    Stale,
+    Quarantined,
+}
+
+impl From<crate::executors::ExecutorHealth> for ExecutorHealth {
+    fn from(health: crate::executors::ExecutorHealth) -> Self {
+        match health {
+            crate::executors::ExecutorHealth::Healthy => ExecutorHealth::Healthy,
+            crate::executors::ExecutorHealth::Degraded => ExecutorHealth::Degraded,
+            crate::executors::ExecutorHealth::Stale => ExecutorHealth::Stale,
+            crate::executors::ExecutorHealth::Quarantined => ExecutorHealth::Quarantined,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExecutorOverview {
+    pub id: String,
+    pub addr: String,
+    pub image_name: String,
+    pub version: Option<String>,
+    pub labels: HashMap<String, serde_json::Value>,
+    pub pool: Option<String>,
+    /// Most tasks allocated to the executor at once, unlimited when unset
+    pub capacity: Option<u32>,
+    pub in_flight_tasks: u64,
+    /// Unset when the executor hasn't connected since the server started
+    pub last_heartbeat_age_ms: Option<u64>,
+    /// Number of recent tasks the failure rate is computed over
+    pub recent_tasks: u64,
+    pub recent_failure_rate: f64,
+    pub health: ExecutorHealth,
+}
+
+impl From<crate::executors::ExecutorOverview> for ExecutorOverview {
+    fn from(overview: crate::executors::ExecutorOverview) -> Self {
+        Self {
+            id: overview.executor.id.to_string(),
+            addr: overview.executor.addr,
+            image_name: overview.executor.image_name,
+            version: overview.executor.version,
+            labels: overview.executor.labels,
+            pool: overview.pool,
+            capacity: overview.executor.capacity,
+            in_flight_tasks: overview.in_flight_tasks,
+            last_heartbeat_age_ms: overview.last_heartbeat_age_ms,
+            recent_tasks: overview.recent_tasks,
+            recent_failure_rate: overview.recent_failure_rate,
+            health: overview.health.into(),
+        }
+    }
+}
+
+/// A hypothetical task to explain the placement of. It's a task of a
+/// deployed function, or of one described by the other fields, which also
+/// override the ones of the deployed function
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExplainPlacement {
+    pub function: Option<FunctionRef>,
+    /// Image the function runs in, required without a deployed function
+    pub image_name: Option<String>,
+    /// Constraints on the labels of executors, e.g. `zone="us-east-1a"`
+    pub placement_constraints: Option<Vec<String>>,
+    pub gpu: Option<GpuRequest>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FunctionRef {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct PlacementExplanation {
+    /// Executors a task would be placed on now
+    pub eligible: Vec<String>,
+    pub excluded: Vec<ExcludedExecutor>,
+    /// External dependency of the function without calls left, tasks wait
+    /// for one even with eligible executors
+    pub saturated_dependency: Option<String>,
+}
+
+impl From<task_scheduler::PlacementExplanation> for PlacementExplanation {
+    fn from(explanation: task_scheduler::PlacementExplanation) -> Self {
+        Self {
+            eligible: explanation
+                .eligible
+                .into_iter()
+                .map(|id| id.to_string())
+                .collect(),
+            excluded: explanation
+                .excluded
+                .into_iter()
+                .map(|(id, exclusions)| ExcludedExecutor {
+                    id: id.to_string(),
+                    reasons: exclusions.into_iter().map(Into::into).collect(),
+                })
+                .collect(),
+            saturated_dependency: explanation.saturated_dependency,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct ExcludedExecutor {
+    pub id: String,
+    pub reasons: Vec<Exclusion>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum Exclusion {
+    /// The executor runs another image
+    ImageMismatch { image_name: String },
+    /// Placement constraints the labels of the executor don't satisfy
+    UnmetConstraints { constraints: Vec<String> },
+    /// The executor already holds as many tasks as it takes
+    AtCapacity {
+        allocated_tasks: u64,
+        max_allocated_tasks: u32,
+    },
+    /// The task needs a GPU and the executor has none
+    NoGpu,
+    /// No GPU device of the executor has the share left the task needs
+    GpuExhausted,
+}
+
+impl From<task_scheduler::Exclusion> for Exclusion {
+    fn from(exclusion: task_scheduler::Exclusion) -> Self {
+        match exclusion {
+            task_scheduler::Exclusion::ImageMismatch { image_name } => {
+                Self::ImageMismatch { image_name }
+            }
+            task_scheduler::Exclusion::UnmetConstraints { constraints } => {
+                Self::UnmetConstraints { constraints }
+            }
+            task_scheduler::Exclusion::AtCapacity {
+                allocated_tasks,
+                max_allocated_tasks,
+            } => Self::AtCapacity {
+                allocated_tasks,
+                max_allocated_tasks,
+            },
+            task_scheduler::Exclusion::NoGpu => Self::NoGpu,
+            task_scheduler::Exclusion::GpuExhausted => Self::GpuExhausted,
         }
     }
 }
@@ -433,10 +2658,28 @@ impl From<data_model::ExecutorMetadata> for ExecutorMetadata {
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub struct InvocationQueryParams {
     pub block_until_finish: Option<bool>,
+    /// Identity of the source of the input, e.g. a file path. Ingesting a
+    /// newer version of the source supersedes the invocation of the previous
+    /// one.
+    pub source: Option<String>,
+    /// Modification time of the source in milliseconds since the epoch.
+    pub source_modified_at: Option<u64>,
+}
+
+impl InvocationQueryParams {
+    pub fn content_source(&self, sha256_hash: &str) -> Option<data_model::ContentSource> {
+        self.source.as_ref().map(|id| data_model::ContentSource {
+            id: id.clone(),
+            sha256_hash: sha256_hash.to_string(),
+            modified_at: self.source_modified_at,
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     #[test]
     fn test_compute_graph_deserialization() {
         // Don't delete this. It makes it easier
@@ -455,4 +2698,122 @@ mod tests {
         json_value["namespace"] = serde_json::Value::String("test".to_string());
         let _: super::ComputeGraph = serde_json::from_value(json_value).unwrap();
     }
+
+    #[test]
+    fn test_tags_from_query() {
+        let params = HashMap::from([
+            ("tags.source".to_string(), "backfill-2024".to_string()),
+            ("limit".to_string(), "10".to_string()),
+        ]);
+        let tags = super::tags_from_query(&params).unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags.get("source").unwrap(), "backfill-2024");
+
+        let params = HashMap::from([("tags.a|b".to_string(), "c".to_string())]);
+        assert!(super::tags_from_query(&params).is_err());
+
+        let params = HashMap::from([
+            ("labels.team".to_string(), "ml".to_string()),
+            ("tags.source".to_string(), "live".to_string()),
+        ]);
+        let labels = super::labels_from_query(&params).unwrap();
+        assert_eq!(
+            labels,
+            HashMap::from([("team".to_string(), "ml".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_trace_from_headers() {
+        let mut headers = super::HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01"
+                .parse()
+                .unwrap(),
+        );
+        headers.insert("x-correlation-id", "order-1234".parse().unwrap());
+        let trace = super::trace_from_headers(&headers).unwrap();
+        assert_eq!(
+            trace.trace_id.as_deref(),
+            Some("4bf92f3577b34da6a3ce929d0e0e4736")
+        );
+        assert_eq!(trace.parent_span_id.as_deref(), Some("00f067aa0ba902b7"));
+        assert_eq!(trace.correlation_id.as_deref(), Some("order-1234"));
+
+        // An all zero trace id is invalid and dropped.
+        headers.insert(
+            "traceparent",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01"
+                .parse()
+                .unwrap(),
+        );
+        let trace = super::trace_from_headers(&headers).unwrap();
+        assert_eq!(trace.trace_id, None);
+        assert_eq!(trace.correlation_id.as_deref(), Some("order-1234"));
+
+        headers.insert("x-correlation-id", "a".repeat(300).parse().unwrap());
+        assert!(super::trace_from_headers(&headers).is_err());
+    }
+
+    #[test]
+    fn test_task_failure_deserialization() {
+        let json = r#"{"error_class":"out_of_memory","exit_code":137}"#;
+        let failure: super::TaskFailure = serde_json::from_str(json).unwrap();
+        let failure: data_model::TaskFailure = failure.into();
+        assert_eq!(failure.error_class, data_model::TaskErrorClass::OutOfMemory);
+        assert!(!failure.retryable);
+        assert_eq!(failure.exit_code, Some(137));
+    }
+
+    #[tokio::test]
+    async fn test_error_problem_details() {
+        use axum::response::IntoResponse;
+
+        let response = super::IndexifyAPIError::compute_graph_not_found().into_response();
+        assert_eq!(response.status(), super::StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.headers()[super::CONTENT_TYPE],
+            "application/problem+json"
+        );
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let problem: super::ProblemDetails = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem.code, super::ErrorCode::ComputeGraphNotFound);
+        assert_eq!(problem.status, 404);
+        assert_eq!(problem.title, "Not Found");
+
+        // Without a specific code, the code follows the status code.
+        let response = super::IndexifyAPIError::unavailable("overloaded", 1).into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let problem: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(problem["code"], "OVERLOADED");
+        assert_eq!(problem["detail"], "overloaded");
+    }
+
+    #[test]
+    fn test_list_cursor() {
+        let cursor = super::encode_cursor(Some(b"ns|graph_A".to_vec()));
+        let params = super::ListParams {
+            limit: None,
+            cursor: cursor.clone(),
+        };
+        assert_eq!(params.cursor().unwrap(), Some(b"ns|graph_A".to_vec()));
+        let uri = format!("/namespaces?limit=10&cursor={}", cursor.unwrap())
+            .parse()
+            .unwrap();
+        let axum::extract::Query(params) =
+            axum::extract::Query::<super::ListParams>::try_from_uri(&uri).unwrap();
+        assert_eq!(params.limit, Some(10));
+        assert!(params.cursor().unwrap().is_some());
+
+        let params = super::ListParams {
+            limit: None,
+            cursor: Some("not a cursor".to_string()),
+        };
+        assert!(params.cursor().is_err());
+    }
 }
@@ -0,0 +1,187 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use state_store::IndexifyState;
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+use crate::{config::InvocationAdmissionConfig, http_objects::IndexifyAPIError};
+
+/// Signals of how far behind the server is, sampled periodically.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Pressure {
+    /// Mean latency of the writes since the previous sample, none when
+    /// nothing was written.
+    pub write_latency: Option<Duration>,
+    pub queue_depth: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Admission {
+    Admit,
+    Delay(Duration),
+    Reject,
+}
+
+/// Decides whether new invocations are accepted given the last sampled
+/// pressure, see `InvocationAdmissionConfig`.
+pub struct InvocationAdmission {
+    config: InvocationAdmissionConfig,
+    state: Arc<IndexifyState>,
+    pressure_tx: watch::Sender<Pressure>,
+}
+
+impl InvocationAdmission {
+    pub fn new(config: InvocationAdmissionConfig, state: Arc<IndexifyState>) -> Self {
+        let (pressure_tx, _) = watch::channel(Pressure::default());
+        Self {
+            config,
+            state,
+            pressure_tx,
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        self.config.max_write_latency_ms.is_some() || self.config.max_queue_depth.is_some()
+    }
+
+    /// Highest ratio of a signal to its limit, 1 when a limit is reached.
+    pub fn load(&self, pressure: &Pressure) -> f64 {
+        let write_latency = match (self.config.max_write_latency_ms, pressure.write_latency) {
+            (Some(max), Some(latency)) => latency.as_secs_f64() * 1000.0 / max as f64,
+            _ => 0.0,
+        };
+        let queue_depth = match self.config.max_queue_depth {
+            Some(max) => pressure.queue_depth as f64 / max as f64,
+            None => 0.0,
+        };
+        write_latency.max(queue_depth)
+    }
+
+    /// Delays grow linearly from nothing at the delay threshold to
+    /// `max_delay_ms` right below the limits.
+    pub fn decide(&self, pressure: &Pressure) -> Admission {
+        let load = self.load(pressure);
+        let threshold = self.config.delay_threshold;
+        if load >= 1.0 {
+            return Admission::Reject;
+        }
+        if load < threshold {
+            return Admission::Admit;
+        }
+        let delay_ms = self.config.max_delay_ms as f64 * (load - threshold) / (1.0 - threshold);
+        Admission::Delay(Duration::from_millis(delay_ms as u64))
+    }
+
+    /// Called before an invocation is accepted, waits out the delay or
+    /// rejects the invocation with a hint of when to retry.
+    pub async fn admit(&self) -> Result<(), IndexifyAPIError> {
+        let pressure = *self.pressure_tx.borrow();
+        match self.decide(&pressure) {
+            Admission::Admit => Ok(()),
+            Admission::Delay(delay) => {
+                tokio::time::sleep(delay).await;
+                Ok(())
+            }
+            Admission::Reject => Err(IndexifyAPIError::unavailable(
+                "server is overloaded, not accepting new invocations",
+                self.config.retry_after_secs,
+            )),
+        }
+    }
+
+    async fn sample(&self) -> Result<Pressure> {
+        let write_latency = self.state.take_write_latency();
+        let queue_depth = match self.config.max_queue_depth {
+            Some(_) => {
+                let state = self.state.clone();
+                tokio::task::spawn_blocking(move || state.reader().queue_depth()).await??
+            }
+            None => 0,
+        };
+        Ok(Pressure {
+            write_latency,
+            queue_depth,
+        })
+    }
+
+    pub async fn start(&self, mut shutdown_rx: watch::Receiver<()>) -> Result<()> {
+        if !self.enabled() {
+            info!("invocation admission is disabled");
+            return Ok(());
+        }
+        let mut interval =
+            tokio::time::interval(Duration::from_millis(self.config.sample_interval_ms));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    let pressure = match self.sample().await {
+                        Ok(pressure) => pressure,
+                        Err(e) => {
+                            error!("error sampling the server pressure: {:?}", e);
+                            continue;
+                        }
+                    };
+                    let was_rejecting =
+                        self.decide(&self.pressure_tx.borrow()) == Admission::Reject;
+                    let rejecting = self.decide(&pressure) == Admission::Reject;
+                    if rejecting && !was_rejecting {
+                        warn!("rejecting new invocations, server is overloaded: {:?}", pressure);
+                    } else if was_rejecting && !rejecting {
+                        info!("accepting new invocations again: {:?}", pressure);
+                    }
+                    self.pressure_tx.send_replace(pressure);
+                }
+                _ = shutdown_rx.changed() => {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_invocation_admission() -> Result<()> {
+        let state = IndexifyState::in_memory().await?;
+        let admission = InvocationAdmission::new(
+            InvocationAdmissionConfig {
+                max_write_latency_ms: Some(100),
+                max_queue_depth: Some(1000),
+                delay_threshold: 0.5,
+                max_delay_ms: 1000,
+                ..Default::default()
+            },
+            state,
+        );
+        let pressure = |write_latency_ms, queue_depth| Pressure {
+            write_latency: Some(Duration::from_millis(write_latency_ms)),
+            queue_depth,
+        };
+        assert_eq!(admission.decide(&pressure(10, 100)), Admission::Admit);
+        assert_eq!(
+            admission.decide(&pressure(75, 100)),
+            Admission::Delay(Duration::from_millis(500))
+        );
+        assert_eq!(admission.decide(&pressure(10, 1000)), Admission::Reject);
+        // Without writes since the last sample, latency doesn't count.
+        assert_eq!(
+            admission.decide(&Pressure {
+                write_latency: None,
+                queue_depth: 100,
+            }),
+            Admission::Admit
+        );
+
+        admission.pressure_tx.send_replace(pressure(200, 0));
+        assert!(admission.admit().await.is_err());
+        admission
+            .pressure_tx
+            .send_replace(admission.sample().await?);
+        assert!(admission.admit().await.is_ok());
+        Ok(())
+    }
+}
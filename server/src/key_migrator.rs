@@ -0,0 +1,107 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use state_store::{
+    key_migration::{self, KeyLayoutMigration, KEY_LAYOUT_MIGRATIONS},
+    metrics::KeyMigrationLabels,
+    IndexifyState,
+};
+use tokio::sync::watch;
+use tracing::{error, info};
+
+const BATCH_SIZE: usize = 1000;
+// Leaves room for the regular writes between batches.
+const BATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Copies the records of the columns being migrated to a new key layout,
+/// see `state_store::key_migration`.
+pub struct KeyMigrator {
+    state: Arc<IndexifyState>,
+}
+
+impl KeyMigrator {
+    pub fn new(state: Arc<IndexifyState>) -> Self {
+        Self { state }
+    }
+
+    /// Copies the next batch of records, returns whether the copy is done.
+    fn copy_batch(&self, migration: &KeyLayoutMigration) -> Result<bool> {
+        let copied = key_migration::progress(&self.state.db, migration)?.copied;
+        let progress = key_migration::copy_batch(&self.state.db, migration, BATCH_SIZE)?;
+        self.state
+            .metrics
+            .key_migration_copied
+            .get_or_create(&KeyMigrationLabels {
+                migration: migration.name.to_string(),
+            })
+            .inc_by(progress.copied - copied);
+        if progress.done {
+            info!(
+                "copied {} records of key layout migration {}",
+                progress.copied, migration.name
+            );
+        }
+        Ok(progress.done)
+    }
+
+    pub async fn start(&self, mut shutdown_rx: watch::Receiver<()>) -> Result<()> {
+        for migration in KEY_LAYOUT_MIGRATIONS {
+            loop {
+                match self.copy_batch(migration) {
+                    Ok(true) => break,
+                    Ok(false) => {}
+                    Err(e) => error!(
+                        "error copying records of key layout migration {}: {:?}",
+                        migration.name, e
+                    ),
+                }
+                tokio::select! {
+                    _ = tokio::time::sleep(BATCH_INTERVAL) => {}
+                    _ = shutdown_rx.changed() => {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use state_store::{
+        state_machine::IndexifyObjectsColumns,
+        test_state_store::tests::TestStateStore,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_copy_batches() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let migration = KeyLayoutMigration {
+            name: "test",
+            from: IndexifyObjectsColumns::Stats,
+            to: IndexifyObjectsColumns::AnalyticsRecords,
+            new_key: |key| [b"v2|", key].concat(),
+        };
+        let from = migration.from.cf_db(&indexify_state.db);
+        for i in 0..BATCH_SIZE + 1 {
+            indexify_state.db.put_cf(&from, format!("{:04}", i), "")?;
+        }
+
+        let migrator = KeyMigrator::new(indexify_state.clone());
+        assert!(!migrator.copy_batch(&migration)?);
+        assert!(migrator.copy_batch(&migration)?);
+        let copied = indexify_state
+            .metrics
+            .key_migration_copied
+            .get_or_create(&KeyMigrationLabels {
+                migration: "test".to_string(),
+            })
+            .get();
+        assert_eq!(copied, BATCH_SIZE as u64 + 1);
+        Ok(())
+    }
+}
@@ -5,21 +5,53 @@ use service::Service;
 use tracing::error;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
 
+mod admission;
+mod alerting;
+mod analytics_export;
+mod archiver;
 mod config;
+mod cost_estimate;
+mod disk_quota;
+mod edge_transforms;
 mod executors;
+mod fetcher;
 mod gc;
 mod http_objects;
+mod invocation_admission;
+mod key_migrator;
+mod namespace_deletion;
+mod near_duplicates;
+mod outbound;
+mod output_checks;
+mod retention;
 mod routes;
 mod scheduler;
 mod server;
 mod service;
+mod subscriptions;
 mod system_tasks;
+mod test_invocations;
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 struct Cli {
     #[arg(short, long, value_name = "config file")]
     config: Option<PathBuf>,
+    /// Restores the state store from a backup directory before starting, the
+    /// state store must not exist yet.
+    #[arg(long, value_name = "backup dir")]
+    restore_from: Option<PathBuf>,
+    /// Backup to restore, the latest by default.
+    #[arg(long, requires = "restore_from")]
+    restore_backup_id: Option<u32>,
+    /// Imports an export of the state store before starting, the state store
+    /// must not exist yet.
+    #[arg(long, value_name = "export file", conflicts_with = "restore_from")]
+    import_from: Option<PathBuf>,
+    /// Imports the export by ingesting SST files instead of writing its
+    /// records, for large exports.
+    #[arg(long, requires = "import_from")]
+    bulk_import: bool,
 }
 
 #[tokio::main]
@@ -32,9 +64,47 @@ async fn main() {
 
     let cli = Cli::parse();
     let config = match cli.config {
-        Some(path) => config::ServerConfig::from_path(path.to_str().unwrap()).unwrap(),
-        None => config::ServerConfig::default(),
+        Some(path) => config::ServerConfig::from_path(path.to_str().unwrap()),
+        None => {
+            let config = config::ServerConfig::default();
+            config.validate().map(|_| config)
+        }
     };
+    let config = match config {
+        Ok(config) => config,
+        Err(err) => {
+            error!("{}", err);
+            std::process::exit(1);
+        }
+    };
+    if let Some(backup_path) = cli.restore_from {
+        let restored = state_store::IndexifyState::restore(
+            &backup_path,
+            &PathBuf::from(&config.state_store_path),
+            cli.restore_backup_id,
+        );
+        if let Err(err) = restored {
+            error!("failed to restore the state store: {}", err);
+            std::process::exit(1);
+        }
+    }
+    if let Some(export_path) = cli.import_from {
+        let imported = std::fs::File::open(&export_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| {
+                let reader = std::io::BufReader::new(file);
+                let path = PathBuf::from(&config.state_store_path);
+                if cli.bulk_import {
+                    state_store::IndexifyState::bulk_import(reader, &path)
+                } else {
+                    state_store::IndexifyState::import(reader, &path)
+                }
+            });
+        if let Err(err) = imported {
+            error!("failed to import {}: {}", export_path.display(), err);
+            std::process::exit(1);
+        }
+    }
     let service = Service::new(config);
     if let Err(err) = service.start().await {
         error!("Error starting service: {}", err);
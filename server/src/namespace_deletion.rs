@@ -0,0 +1,201 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use state_store::{
+    requests::{DeleteNamespaceRequest, RequestPayload, StateMachineUpdateRequest},
+    IndexifyState,
+};
+use tracing::{error, info};
+
+/// Deletes the namespaces marked for deletion one step at a time. Progress is
+/// kept on the namespace, so deletions interrupted by a restart resume where
+/// they stopped.
+pub struct NamespaceDeleter {
+    state: Arc<IndexifyState>,
+    rx: tokio::sync::watch::Receiver<()>,
+    shutdown_rx: tokio::sync::watch::Receiver<()>,
+}
+
+impl NamespaceDeleter {
+    pub fn new(state: Arc<IndexifyState>, shutdown_rx: tokio::sync::watch::Receiver<()>) -> Self {
+        let rx = state.get_namespace_deletion_watcher();
+        Self {
+            state,
+            rx,
+            shutdown_rx,
+        }
+    }
+
+    pub async fn start(&mut self) -> Result<()> {
+        loop {
+            if self.shutdown_rx.has_changed().unwrap_or(false) {
+                return Ok(());
+            }
+            let deleting = self
+                .state
+                .reader()
+                .get_all_namespaces()?
+                .into_iter()
+                .filter(|namespace| namespace.deletion.is_some())
+                .collect::<Vec<_>>();
+            if deleting.is_empty() {
+                tokio::select! {
+                    _ = self.rx.changed() => { self.rx.borrow_and_update(); }
+                    _ = self.shutdown_rx.changed() => {
+                        return Ok(());
+                    }
+                }
+                continue;
+            }
+            for namespace in deleting {
+                let result = self
+                    .state
+                    .write(StateMachineUpdateRequest {
+                        payload: RequestPayload::AdvanceNamespaceDeletion(DeleteNamespaceRequest {
+                            name: namespace.name.clone(),
+                        }),
+                        state_changes_processed: vec![],
+                    })
+                    .await;
+                match result {
+                    Ok(()) => info!("advanced deletion of namespace {}", namespace.name),
+                    Err(err) => {
+                        error!("error deleting namespace {}: {:?}", namespace.name, err);
+                        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::{
+        test_objects::tests::{mock_graph_a, mock_invocation_payload, TEST_NAMESPACE},
+        NamespaceDeletionStage,
+    };
+    use state_store::requests::{
+        CreateComputeGraphRequest,
+        InvokeComputeGraphRequest,
+        NamespaceRequest,
+    };
+
+    use super::*;
+    use crate::scheduler::Scheduler;
+
+    async fn advance(state: &IndexifyState) -> Result<()> {
+        state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::AdvanceNamespaceDeletion(DeleteNamespaceRequest {
+                    name: TEST_NAMESPACE.to_string(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_namespace_deleted_in_stages() -> Result<()> {
+        let state = IndexifyState::in_memory().await?;
+        state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateNameSpace(NamespaceRequest {
+                    name: TEST_NAMESPACE.to_string(),
+                    blob_storage: None,
+                    scheduling_weight: None,
+                    reprocessing_policy: Default::default(),
+                    near_duplicates: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                    precondition: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invocation = mock_invocation_payload();
+        let invoke = || {
+            RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: invocation.compute_graph_name.clone(),
+                invocation_payload: invocation.clone(),
+            })
+        };
+        state
+            .write(StateMachineUpdateRequest {
+                payload: invoke(),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        Scheduler::new(state.clone()).run_scheduler().await?;
+        let (tasks, _) = state
+            .reader()
+            .list_tasks_by_namespace(TEST_NAMESPACE, None, None)?;
+        assert!(!tasks.is_empty());
+
+        state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::DeleteNamespace(DeleteNamespaceRequest {
+                    name: TEST_NAMESPACE.to_string(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let res = state
+            .write(StateMachineUpdateRequest {
+                payload: invoke(),
+                state_changes_processed: vec![],
+            })
+            .await;
+        assert!(res.is_err());
+
+        // The first step deletes the tasks, the second finds none left.
+        advance(&state).await?;
+        let (tasks, _) = state
+            .reader()
+            .list_tasks_by_namespace(TEST_NAMESPACE, None, None)?;
+        assert!(tasks.is_empty());
+        advance(&state).await?;
+        let deletion = state
+            .reader()
+            .get_namespace(TEST_NAMESPACE)?
+            .unwrap()
+            .deletion
+            .unwrap();
+        assert_eq!(deletion.stage, NamespaceDeletionStage::DeleteComputeGraphs);
+        assert_eq!(deletion.tasks_deleted, 1);
+
+        advance(&state).await?;
+        assert!(state
+            .reader()
+            .get_compute_graph(TEST_NAMESPACE, "graph_A")?
+            .is_none());
+        assert_eq!(
+            state
+                .reader()
+                .get_namespace(TEST_NAMESPACE)?
+                .unwrap()
+                .deletion
+                .unwrap()
+                .compute_graphs_deleted,
+            1
+        );
+        assert!(!state.reader().get_gc_urls(None)?.is_empty());
+
+        advance(&state).await?;
+        assert!(state.reader().get_namespace(TEST_NAMESPACE)?.is_none());
+        let (invocations, _) =
+            state
+                .reader()
+                .list_invocations(TEST_NAMESPACE, "graph_A", None, None)?;
+        assert!(invocations.is_empty());
+        Ok(())
+    }
+}
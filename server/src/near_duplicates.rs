@@ -0,0 +1,54 @@
+use anyhow::Result;
+use blob_store::{registry::BlobStorageRegistry, PutResult};
+use data_model::near_duplicates::simhash;
+use state_store::IndexifyState;
+
+/// Larger inputs aren't read back to be fingerprinted.
+const MAX_FINGERPRINT_BYTES: u64 = 4 * 1024 * 1024;
+
+fn is_text(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    essence.starts_with("text/") ||
+        matches!(
+            essence,
+            "application/json" | "application/xml" | "application/xhtml+xml"
+        )
+}
+
+/// Simhash of an ingested input for the state store to compare with the
+/// graph's recent inputs. Only text inputs of namespaces that detect
+/// near-duplicates are fingerprinted, which costs reading them back from
+/// the blob store.
+pub async fn fingerprint(
+    indexify_state: &IndexifyState,
+    blob_storage: &BlobStorageRegistry,
+    namespace: &str,
+    put_result: &PutResult,
+    content_type: Option<&str>,
+) -> Result<Option<u64>> {
+    if !content_type.is_some_and(is_text) || put_result.size_bytes > MAX_FINGERPRINT_BYTES {
+        return Ok(None);
+    }
+    let detects = indexify_state
+        .reader()
+        .get_namespace(namespace)?
+        .is_some_and(|ns| ns.near_duplicates.is_some());
+    if !detects {
+        return Ok(None);
+    }
+    let bytes = blob_storage.read_bytes(&put_result.url).await?;
+    Ok(simhash(&String::from_utf8_lossy(&bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_text() {
+        assert!(is_text("text/html; charset=utf-8"));
+        assert!(is_text("application/json"));
+        assert!(!is_text("application/pdf"));
+        assert!(!is_text("application/cbor"));
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+};
+
+use anyhow::{anyhow, Result};
+use reqwest::{redirect, Url};
+
+/// Returned for urls the server won't send requests to, whether because of
+/// their scheme, their bucket or the addresses they resolve to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrlNotAllowed(pub String);
+
+impl fmt::Display for UrlNotAllowed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for UrlNotAllowed {}
+
+/// Whether the address is reachable from the internet, rather than one of
+/// the server itself, its network or its cloud's metadata service.
+pub fn is_global(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified() ||
+                ip.is_loopback() ||
+                ip.is_private() ||
+                ip.is_link_local() ||
+                ip.is_broadcast() ||
+                ip.is_multicast() ||
+                ip.is_documentation() ||
+                a == 0 ||
+                // Shared address space of carrier-grade NAT.
+                (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_global(IpAddr::V4(ip));
+            }
+            let segments = ip.segments();
+            // NAT64 addresses embed an IPv4 one.
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                let [.., a, b, c, d] = ip.octets();
+                return is_global(IpAddr::V4(Ipv4Addr::new(a, b, c, d)));
+            }
+            !(ip.is_unspecified() ||
+                ip.is_loopback() ||
+                ip.is_multicast() ||
+                // Unique local and link-local addresses.
+                segments[0] & 0xfe00 == 0xfc00 ||
+                segments[0] & 0xffc0 == 0xfe80)
+        }
+    }
+}
+
+/// Addresses the host of the url resolves to, refused when one of them isn't
+/// public unless private addresses are allowed. Returns whether they had to
+/// be looked up, the host being an IP address otherwise.
+async fn resolve(url: &Url, allow_private_addresses: bool) -> Result<(Vec<SocketAddr>, bool)> {
+    let host = url.host_str().ok_or(anyhow!("url has no host"))?;
+    let port = url
+        .port_or_known_default()
+        .ok_or(anyhow!("url has no port"))?;
+    let (addrs, looked_up) = match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(ip) => (vec![SocketAddr::new(ip, port)], false),
+        Err(_) => (tokio::net::lookup_host((host, port)).await?.collect(), true),
+    };
+    if !allow_private_addresses {
+        if let Some(addr) = addrs.iter().find(|addr| !is_global(addr.ip())) {
+            return Err(UrlNotAllowed(format!(
+                "{} resolves to the non-public address {}",
+                host,
+                addr.ip()
+            ))
+            .into());
+        }
+    }
+    Ok((addrs, looked_up))
+}
+
+/// Client for requests to the url, connecting to the addresses its host was
+/// checked to resolve to so that a second lookup can't point it elsewhere.
+/// Redirects aren't followed, callers following them must check every
+/// location like the url itself.
+pub async fn client_for(
+    url: &Url,
+    allow_private_addresses: bool,
+) -> Result<reqwest::ClientBuilder> {
+    let (addrs, looked_up) = resolve(url, allow_private_addresses).await?;
+    let mut builder = reqwest::Client::builder().redirect(redirect::Policy::none());
+    if looked_up {
+        if let Some(host) = url.host_str() {
+            builder = builder.resolve_to_addrs(host, &addrs);
+        }
+    }
+    Ok(builder)
+}
+
+/// Checks a url supplied by a tenant before it's stored, requests to it are
+/// checked again when they're sent as the host can resolve elsewhere later.
+pub async fn check_url(url: &str, allow_private_addresses: bool) -> Result<()> {
+    let parsed =
+        Url::parse(url).map_err(|e| UrlNotAllowed(format!("invalid url {}: {}", url, e)))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(UrlNotAllowed("url must use the http or https scheme".to_string()).into());
+    }
+    match resolve(&parsed, allow_private_addresses).await {
+        Ok(_) => Ok(()),
+        Err(e) if e.is::<UrlNotAllowed>() => Err(e),
+        Err(e) => Err(UrlNotAllowed(format!("unable to resolve {}: {}", url, e)).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_global() {
+        for ip in ["93.184.216.34", "2606:2800:220:1::"] {
+            assert!(is_global(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00:ec2::254",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "64:ff9b::a9fe:a9fe",
+        ] {
+            assert!(!is_global(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_url() {
+        for url in [
+            "http://127.0.0.1:8900/events",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/events",
+            "http://localhost/events",
+        ] {
+            let err = check_url(url, false).await.unwrap_err();
+            assert!(err.is::<UrlNotAllowed>(), "{}", url);
+        }
+        assert!(check_url("ftp://example.com/events", true).await.is_err());
+        assert!(check_url("not a url", true).await.is_err());
+        assert!(check_url("http://127.0.0.1:8900/events", true)
+            .await
+            .is_ok());
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use blob_store::BlobStorage;
+use data_model::{
+    output_checks::{OutputCheckAction, OutputCheckViolation, QuarantinedOutput},
+    ComputeFn,
+    NodeOutput,
+    OutputPayload,
+};
+use indexify_utils::get_epoch_time_in_ms;
+use state_store::requests::OutputCheckReport;
+
+/// Evaluates the output checks of a function on the outputs of one of its
+/// tasks, reading every output back from blob storage. Returns the outputs
+/// which stay in the graph along with the report. Outputs violating a
+/// quarantining check are moved to the report, and when a check fails the
+/// task all of its outputs are.
+pub async fn check_outputs(
+    storage: Arc<BlobStorage>,
+    compute_fn: &ComputeFn,
+    outputs: Vec<NodeOutput>,
+) -> Result<(Vec<NodeOutput>, OutputCheckReport)> {
+    let mut report = OutputCheckReport::default();
+    let mut checked = Vec::new();
+    for output in outputs {
+        let OutputPayload::Fn(payload) = &output.payload else {
+            checked.push((output, vec![]));
+            continue;
+        };
+        let bytes = storage.read_bytes(&payload.path).await?;
+        let violations = compute_fn
+            .output_checks
+            .iter()
+            .filter_map(|check| {
+                check
+                    .condition
+                    .violation(&bytes)
+                    .map(|message| OutputCheckViolation {
+                        check: check.name.clone(),
+                        action: check.action,
+                        message,
+                    })
+            })
+            .collect::<Vec<_>>();
+        report.outputs_checked += 1;
+        report.violations.extend(violations.iter().cloned());
+        checked.push((output, violations));
+    }
+
+    let fail_task = report
+        .violations
+        .iter()
+        .any(|v| v.action == OutputCheckAction::FailTask);
+    let mut kept = Vec::new();
+    for (output, violations) in checked {
+        let quarantine = fail_task ||
+            violations
+                .iter()
+                .any(|v| v.action == OutputCheckAction::Quarantine);
+        if quarantine {
+            report.quarantined_outputs.push(QuarantinedOutput {
+                output,
+                violations,
+                quarantined_at: get_epoch_time_in_ms(),
+            });
+        } else {
+            kept.push(output);
+        }
+    }
+    Ok((kept, report))
+}
+
+#[cfg(test)]
+mod tests {
+    use blob_store::BlobStorageConfig;
+    use bytes::Bytes;
+    use data_model::{
+        output_checks::{OutputCheck, OutputCondition},
+        DataPayload,
+    };
+    use futures::stream;
+    use serde_json::json;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check_outputs() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let storage = Arc::new(BlobStorage::new(BlobStorageConfig::new_disk(
+            temp_dir.path().to_str().unwrap(),
+        ))?);
+        let mut outputs = Vec::new();
+        for (i, data) in [r#"{"score": 0.5}"#, r#"{"score": 7}"#, "[]"]
+            .into_iter()
+            .enumerate()
+        {
+            let data_stream = Box::pin(stream::once(async move { Ok(Bytes::from(data)) }));
+            let res = storage.put(&format!("output_{}", i), data_stream).await?;
+            outputs.push(NodeOutput {
+                id: i.to_string(),
+                graph_version: Default::default(),
+                namespace: "ns".to_string(),
+                compute_fn_name: "fn_a".to_string(),
+                compute_graph_name: "graph_A".to_string(),
+                invocation_id: "invocation".to_string(),
+                payload: OutputPayload::Fn(DataPayload {
+                    path: res.url,
+                    size: res.size_bytes,
+                    sha256_hash: res.sha256_hash,
+                    content_type: None,
+                }),
+                errors: None,
+                reduced_state: false,
+            });
+        }
+        let mut compute_fn = ComputeFn {
+            name: "fn_a".to_string(),
+            payload_encoder: "json".to_string(),
+            output_checks: vec![
+                OutputCheck {
+                    name: "score".to_string(),
+                    condition: OutputCondition::JsonSchema {
+                        schema: json!({
+                            "type": "object",
+                            "properties": {"score": {"maximum": 1}},
+                        }),
+                    },
+                    action: OutputCheckAction::Quarantine,
+                },
+                OutputCheck {
+                    name: "non_empty".to_string(),
+                    condition: OutputCondition::NonEmpty,
+                    action: OutputCheckAction::Record,
+                },
+            ],
+            ..Default::default()
+        };
+
+        let (kept, report) = check_outputs(storage.clone(), &compute_fn, outputs.clone()).await?;
+        assert_eq!(report.outputs_checked, 3);
+        // The empty array violates both checks but only the schema
+        // quarantines it.
+        assert_eq!(report.violations.len(), 3);
+        let kept_ids = kept.iter().map(|o| o.id.as_str()).collect::<Vec<_>>();
+        assert_eq!(kept_ids, vec!["0"]);
+        assert_eq!(report.quarantined_outputs.len(), 2);
+
+        compute_fn.output_checks[1].action = OutputCheckAction::FailTask;
+        let (kept, report) = check_outputs(storage, &compute_fn, outputs).await?;
+        assert!(kept.is_empty());
+        assert_eq!(report.quarantined_outputs.len(), 3);
+        Ok(())
+    }
+}
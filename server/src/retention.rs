@@ -0,0 +1,394 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use indexify_utils::clock::{Clock, SystemClock};
+use state_store::{
+    requests::{
+        DeleteInvocationRequest,
+        ExpireOutputsRequest,
+        PruneActivityRequest,
+        PruneAppliedRequestsRequest,
+        RequestPayload,
+        StateMachineUpdateRequest,
+    },
+    IndexifyState,
+    APPLIED_REQUESTS_RETENTION,
+};
+use tokio::sync::watch;
+use tracing::{error, info};
+
+use crate::config::RetentionConfig;
+
+const SWEEP_BATCH_SIZE: usize = 100;
+
+/// Deletes finished invocations once their TTL passes, see
+/// `RetentionConfig`. Their tasks, outputs and invocation context are deleted
+/// with them and the blobs of their outputs are garbage collected. Activity
+/// feed events older than the TTL are deleted as well.
+///
+/// Graphs can also declare an output retention, the outputs their finished
+/// invocations no longer retain are deleted whether or not a TTL is set. So
+/// are the ids of requests written at most once, after
+/// `APPLIED_REQUESTS_RETENTION`.
+pub struct RetentionSweeper {
+    state: Arc<IndexifyState>,
+    config: RetentionConfig,
+    clock: Arc<dyn Clock>,
+}
+
+impl RetentionSweeper {
+    pub fn new(state: Arc<IndexifyState>, config: RetentionConfig) -> Self {
+        Self {
+            state,
+            config,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Deletes the finished invocations created before `cutoff`, returns how
+    /// many were deleted.
+    pub async fn sweep(&self, cutoff: u64) -> Result<usize> {
+        let mut deleted = 0;
+        loop {
+            let invocations = self
+                .state
+                .reader()
+                .archivable_invocations(cutoff, SWEEP_BATCH_SIZE)?;
+            if invocations.is_empty() {
+                return Ok(deleted);
+            }
+            for invocation in invocations {
+                self.state
+                    .write(StateMachineUpdateRequest {
+                        payload: RequestPayload::ExpireInvocation(DeleteInvocationRequest {
+                            namespace: invocation.namespace,
+                            compute_graph: invocation.compute_graph_name,
+                            invocation_id: invocation.id,
+                        }),
+                        state_changes_processed: vec![],
+                    })
+                    .await?;
+                deleted += 1;
+            }
+        }
+    }
+
+    /// Deletes the outputs of finished invocations their graph no longer
+    /// retains at `now`, returns the number of invocations whose outputs
+    /// expired.
+    pub async fn expire_outputs(&self, now: u64) -> Result<usize> {
+        let mut expired = 0;
+        for namespace in self.state.reader().get_all_namespaces()? {
+            let (graphs, _) =
+                self.state
+                    .reader()
+                    .list_compute_graphs(&namespace.name, None, None)?;
+            for graph in graphs {
+                if !graph.output_retention.is_set() {
+                    continue;
+                }
+                loop {
+                    let invocations = self.state.reader().invocations_with_expired_outputs(
+                        &graph.namespace,
+                        &graph.name,
+                        &graph.output_retention,
+                        now,
+                        SWEEP_BATCH_SIZE,
+                    )?;
+                    if invocations.is_empty() {
+                        break;
+                    }
+                    for (invocation, outputs) in invocations {
+                        self.state
+                            .write(StateMachineUpdateRequest {
+                                payload: RequestPayload::ExpireOutputs(ExpireOutputsRequest {
+                                    namespace: invocation.namespace,
+                                    compute_graph: invocation.compute_graph_name,
+                                    invocation_id: invocation.id,
+                                    outputs,
+                                }),
+                                state_changes_processed: vec![],
+                            })
+                            .await?;
+                        expired += 1;
+                    }
+                }
+            }
+        }
+        Ok(expired)
+    }
+
+    async fn prune_activity(&self, cutoff: u64) -> Result<()> {
+        self.state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::PruneActivity(PruneActivityRequest {
+                    recorded_before: cutoff,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
+    async fn prune_applied_requests(&self, now: u64) -> Result<()> {
+        let retention_ms = APPLIED_REQUESTS_RETENTION.as_millis() as u64;
+        self.state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::PruneAppliedRequests(PruneAppliedRequestsRequest {
+                    applied_before: now.saturating_sub(retention_ms),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
+    pub async fn start(&self, mut shutdown_rx: watch::Receiver<()>) -> Result<()> {
+        if self.config.ttl_secs.is_none() {
+            info!("invocation retention is disabled");
+        }
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    let now = self.clock.now_ms();
+                    if let Some(ttl_secs) = self.config.ttl_secs {
+                        let cutoff = now.saturating_sub(ttl_secs * 1000);
+                        match self.sweep(cutoff).await {
+                            Ok(0) => {}
+                            Ok(deleted) => info!("deleted {} expired invocations", deleted),
+                            Err(e) => error!("error deleting expired invocations: {:?}", e),
+                        }
+                        if let Err(e) = self.prune_activity(cutoff).await {
+                            error!("error deleting expired activity: {:?}", e);
+                        }
+                    }
+                    match self.expire_outputs(now).await {
+                        Ok(0) => {}
+                        Ok(expired) => info!("expired the outputs of {} invocations", expired),
+                        Err(e) => error!("error expiring outputs: {:?}", e),
+                    }
+                    if let Err(e) = self.prune_applied_requests(now).await {
+                        error!("error deleting expired request ids: {:?}", e);
+                    }
+                }
+                _ = shutdown_rx.changed() => {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::{
+        test_objects::tests::{
+            mock_graph_a,
+            mock_invocation_payload,
+            mock_node_fn_output,
+            TEST_NAMESPACE,
+        },
+        InvocationPayloadBuilder,
+        OutputRetention,
+    };
+    use state_store::{
+        requests::{
+            CreateComputeGraphRequest,
+            InvokeComputeGraphRequest,
+            PinInvocationOutputsRequest,
+        },
+        serializer::{JsonEncode, JsonEncoder},
+        state_machine::IndexifyObjectsColumns,
+        test_state_store::tests::TestStateStore,
+    };
+
+    use super::*;
+    use crate::scheduler::Scheduler;
+
+    #[tokio::test]
+    async fn test_sweep_expired_invocations() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        state_store.with_simple_graph().await;
+        let invoke = |created_at| {
+            let invocation = InvocationPayloadBuilder::default()
+                .namespace(TEST_NAMESPACE.to_string())
+                .compute_graph_name("graph_A".to_string())
+                .payload(mock_invocation_payload().payload)
+                .created_at(created_at)
+                .build()
+                .unwrap();
+            let id = invocation.id.clone();
+            let write = indexify_state.write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload: invocation,
+                }),
+                state_changes_processed: vec![],
+            });
+            async move { write.await.map(|_| id) }
+        };
+        let old = invoke(1_000).await?;
+        let running = invoke(1_000).await?;
+        let recent = invoke(9_000).await?;
+        Scheduler::new(indexify_state.clone())
+            .run_scheduler()
+            .await?;
+        for invocation_id in [&old, &recent] {
+            let mut ctx =
+                indexify_state
+                    .reader()
+                    .invocation_ctx(TEST_NAMESPACE, "graph_A", invocation_id)?;
+            ctx.completed = true;
+            indexify_state.db.put_cf(
+                &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&indexify_state.db),
+                ctx.key(),
+                JsonEncoder::encode(IndexifyObjectsColumns::GraphInvocationCtx, ctx.key(), &ctx)?,
+            )?;
+        }
+        let exists = |invocation_id: &str| {
+            indexify_state
+                .reader()
+                .invocation_payload(TEST_NAMESPACE, "graph_A", invocation_id)
+                .is_ok()
+        };
+
+        let sweeper = RetentionSweeper::new(indexify_state.clone(), Default::default());
+        assert_eq!(sweeper.sweep(5_000).await?, 1);
+        assert!(!exists(&old));
+        assert!(indexify_state
+            .reader()
+            .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", &old, None, None)?
+            .0
+            .is_empty());
+        assert!(indexify_state
+            .reader()
+            .invocation_ctx(TEST_NAMESPACE, "graph_A", &old)
+            .is_err());
+        // Running invocations are kept whatever their age.
+        assert!(exists(&running));
+        assert!(exists(&recent));
+        assert_eq!(sweeper.sweep(5_000).await?, 0);
+        Ok(())
+    }
+    #[tokio::test]
+    async fn test_expire_outputs() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let mut graph = mock_graph_a();
+        graph.output_retention = OutputRetention {
+            final_outputs_secs: Some(100),
+            intermediate_outputs_secs: Some(10),
+        };
+        write(RequestPayload::CreateComputeGraph(
+            CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: graph,
+                precondition: None,
+            },
+        ))
+        .await?;
+        let invocation = InvocationPayloadBuilder::default()
+            .namespace(TEST_NAMESPACE.to_string())
+            .compute_graph_name("graph_A".to_string())
+            .payload(mock_invocation_payload().payload)
+            .created_at(1_000)
+            .build()
+            .unwrap();
+        let invocation_id = invocation.id.clone();
+        write(RequestPayload::InvokeComputeGraph(
+            InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: "graph_A".to_string(),
+                invocation_payload: invocation,
+            },
+        ))
+        .await?;
+        Scheduler::new(indexify_state.clone())
+            .run_scheduler()
+            .await?;
+        let mut ctx =
+            indexify_state
+                .reader()
+                .invocation_ctx(TEST_NAMESPACE, "graph_A", &invocation_id)?;
+        ctx.completed = true;
+        indexify_state.db.put_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&indexify_state.db),
+            ctx.key(),
+            JsonEncoder::encode(IndexifyObjectsColumns::GraphInvocationCtx, ctx.key(), &ctx)?,
+        )?;
+        // fn_a feeds fn_b, whose outputs are the graph's.
+        let intermediate = mock_node_fn_output(&invocation_id, "graph_A", "fn_a", None);
+        let last = mock_node_fn_output(&invocation_id, "graph_A", "fn_b", None);
+        for output in [&intermediate, &last] {
+            indexify_state.db.put_cf(
+                &IndexifyObjectsColumns::FnOutputs.cf_db(&indexify_state.db),
+                output.key(&invocation_id),
+                JsonEncoder::encode(
+                    IndexifyObjectsColumns::FnOutputs,
+                    output.key(&invocation_id),
+                    output,
+                )?,
+            )?;
+        }
+        let exists = |output: &data_model::NodeOutput| -> Result<bool> {
+            Ok(indexify_state
+                .reader()
+                .fn_output_payload(
+                    TEST_NAMESPACE,
+                    "graph_A",
+                    &invocation_id,
+                    &output.compute_fn_name,
+                    &output.id,
+                )?
+                .is_some())
+        };
+
+        let sweeper = RetentionSweeper::new(indexify_state.clone(), Default::default());
+        assert_eq!(sweeper.expire_outputs(5_000).await?, 0);
+        assert_eq!(sweeper.expire_outputs(50_000).await?, 1);
+        assert!(!exists(&intermediate)?);
+        assert!(exists(&last)?);
+        let (tasks, _) = indexify_state.reader().list_tasks_by_compute_graph(
+            TEST_NAMESPACE,
+            "graph_A",
+            &invocation_id,
+            None,
+            None,
+        )?;
+        assert!(tasks
+            .iter()
+            .all(|task| task.outputs_expired == (task.compute_fn_name == "fn_a")));
+        assert_eq!(sweeper.expire_outputs(50_000).await?, 0);
+
+        // Pinned invocations keep their outputs.
+        let pin = |pinned| {
+            write(RequestPayload::PinInvocationOutputs(
+                PinInvocationOutputsRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: "graph_A".to_string(),
+                    invocation_id: invocation_id.clone(),
+                    pinned,
+                },
+            ))
+        };
+        pin(true).await?;
+        assert_eq!(sweeper.expire_outputs(200_000).await?, 0);
+        assert!(exists(&last)?);
+        pin(false).await?;
+        assert_eq!(sweeper.expire_outputs(200_000).await?, 1);
+        assert!(!exists(&last)?);
+        Ok(())
+    }
+}
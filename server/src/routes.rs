@@ -1,29 +1,44 @@
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use anyhow::Result;
 use axum::{
     body::Body,
     extract::{DefaultBodyLimit, MatchedPath, Multipart, Path, Query, Request, State},
-    http::{Method, Response},
+    http::{
+        header::{ETAG, IF_MATCH},
+        HeaderMap,
+        Method,
+        Response,
+        StatusCode,
+    },
     response::{sse::Event, IntoResponse},
-    routing::{delete, get, post},
+    routing::{delete, get, post, put},
+    Extension,
     Json,
     Router,
 };
 use blob_store::PutResult;
-use data_model::ExecutorId;
+use data_model::{validation::ValidationErrors, ExecutorId, GraphVersion, TaskId};
 use futures::StreamExt;
 use indexify_ui::Assets as UiAssets;
 use indexify_utils::GuardStreamExt;
 use nanoid::nanoid;
+use prometheus_client::registry::Registry;
 use state_store::{
     requests::{
         CreateComputeGraphRequest,
         DeleteComputeGraphRequest,
         DeleteInvocationRequest,
+        DeleteNamespaceRequest,
+        GraphPrecondition,
+        GraphVersionConflict,
+        NamespacePlacementConflict,
         NamespaceRequest,
+        PinInvocationOutputsRequest,
+        RejectTaskRequest,
         RequestPayload,
         StateMachineUpdateRequest,
+        TaskRejectionError,
     },
     IndexifyState,
 };
@@ -35,43 +50,203 @@ use tracing::info;
 use utoipa::{OpenApi, ToSchema};
 use utoipa_swagger_ui::SwaggerUi;
 
-use crate::executors::{self, EXECUTOR_TIMEOUT};
+use crate::{
+    admission::ExecutorRejected,
+    executors::{self, runner_executor_id, EXECUTOR_TIMEOUT},
+};
 
+mod activity;
+pub mod api_keys;
+mod artifacts;
+mod backups;
+mod canaries;
+mod diagnostics;
 mod download;
+mod exports;
+mod fsck;
+mod health;
 mod internal_ingest;
 mod invoke;
 mod logs;
+mod metrics;
+mod orphans;
+mod placement;
+pub mod request_metrics;
+mod subscriptions;
+mod task_queue;
+mod test_invocations;
+use activity::activity_feed;
+use api_keys::{authorize, ApiKeyScope, ApiKeys};
+use artifacts::{
+    artifact_error,
+    delete_artifact,
+    download_artifact,
+    get_artifact,
+    list_artifacts,
+    prefetch_artifacts,
+    register_artifact,
+    report_artifacts,
+};
+use backups::{create_backup, list_backups};
+use canaries::{canary_error, get_canary, promote_canary, rollback_canary, start_canary};
+use diagnostics::{get_diagnostics, report_diagnostics, request_diagnostics};
 use download::{
     download_fn_output_by_key,
     download_fn_output_payload,
     download_invocation_payload,
 };
+use exports::export_state_store;
+use fsck::fsck_state_store;
+use health::{healthz, readyz};
 use internal_ingest::ingest_files_from_executor;
-use invoke::{invoke_with_file, invoke_with_object, rerun_compute_graph};
+use invoke::{
+    get_url_fetch,
+    invoke_with_file,
+    invoke_with_object,
+    invoke_with_url,
+    reproduce_invocation,
+    rerun_compute_graph,
+};
 use logs::download_logs;
+use metrics::metrics;
+use orphans::reconcile_orphans;
+use placement::explain_placement;
+use request_metrics::{log_request_bodies, track_requests, RequestTracking};
+use subscriptions::{
+    create_subscription,
+    delete_subscription,
+    list_subscription_events,
+    list_subscriptions,
+    replay_subscription,
+};
+use task_queue::{audit_log, intervene_task, task_queue};
+use test_invocations::test_compute_graph;
 
 use crate::{
+    archiver::InvocationArchive,
+    config::{placement_blob_storage_config, BackupConfig, TestInvocationConfig, WebhookConfig},
+    cost_estimate,
     executors::ExecutorManager,
+    fetcher::Fetcher,
     http_objects::{
+        encode_cursor,
+        labels_from_query,
+        tags_from_query,
+        Activity,
+        ActivityEvent,
+        ActivityFeed,
+        AgentRunner,
+        ArchivedInvocation,
+        ArchivedInvocations,
+        Artifact,
+        ArtifactRef,
+        Artifacts,
+        AuditLog,
+        AuditLogEntry,
+        BlobStoragePlacement,
+        CodeParams,
         ComputeFn,
         ComputeGraph,
         ComputeGraphsList,
+        CostEstimate,
+        CostEstimateRequest,
+        Count,
+        CountParams,
         CreateNamespace,
+        CreateSubscription,
         DataObject,
+        DependencyHealth,
+        DiagnosticsReport,
         DynamicRouter,
+        EdgeTransform,
+        ErrorCode,
+        ExcludedExecutor,
+        Exclusion,
+        ExecutorAgent,
+        ExecutorArtifacts,
+        ExecutorDiagnostics,
+        ExecutorHealth,
         ExecutorMetadata,
+        ExecutorOverview,
+        ExplainPlacement,
+        ExternalDependency,
         FnOutputs,
+        FnVersionStats,
+        FsckProblem,
+        FsckProblemKind,
+        FsckReport,
+        FunctionCapacity,
+        FunctionEstimate,
+        FunctionProvenance,
+        FunctionRef,
+        GpuDevice,
+        GpuDeviceUtilization,
+        GpuRequest,
+        GraphCanary,
         GraphInvocations,
+        GraphOutputChecks,
+        GraphOwner,
+        GraphResourceUsage,
+        HealthStatus,
         IndexifyAPIError,
+        InterveneTask,
+        InvocationProvenance,
         InvocationResult,
         ListParams,
         Namespace,
+        NamespaceDeletion,
+        NamespaceDeletionStage,
+        NamespaceEvent,
         NamespaceList,
+        NearDuplicateAction,
+        NearDuplicatePolicy,
         Node,
+        OrphansRemoved,
+        OutputCheck,
+        OutputCheckAction,
+        OutputCheckStats,
+        OutputCheckViolation,
+        OutputCondition,
+        OutputRetention,
+        OwnerContact,
+        PlacementExplanation,
+        ProblemDetails,
+        QuarantinedOutput,
+        QuarantinedOutputs,
+        QueuedTask,
+        RejectTask,
+        ReplaySubscription,
+        ReprocessingPolicy,
+        ResourceUsageStats,
+        RunnerTasks,
+        Severity,
+        StateStoreBackup,
+        StateStoreBackups,
+        Subscription,
+        SubscriptionEvent,
+        SubscriptionEvents,
+        SubscriptionEventsParams,
+        Subscriptions,
         Task,
+        TaskCounts,
+        TaskErrorClass,
+        TaskFailure,
+        TaskIntervention,
         TaskOutcome,
+        TaskQueue,
+        TaskRejection,
+        TaskRejectionReason,
         Tasks,
+        TestComputeGraph,
+        TestInvocations,
+        TraceContext,
+        TransformStep,
+        UrlFetch,
+        UrlFetchStatus,
+        VersionStats,
     },
+    invocation_admission::InvocationAdmission,
+    outbound,
 };
 
 #[derive(OpenApi)]
@@ -79,26 +254,90 @@ use crate::{
         paths(
             create_namespace,
             namespaces,
+            delete_namespace,
+            namespace_deletion,
             invoke::invoke_with_file,
             invoke::invoke_with_object,
+            invoke::invoke_with_url,
+            invoke::get_url_fetch,
             graph_invocations,
+            count_invocations,
+            count_tasks,
+            count_data_objects,
+            list_archived_invocations,
+            get_archived_invocation,
             create_compute_graph,
+            put_compute_graph,
             list_compute_graphs,
             get_compute_graph,
+            list_compute_graph_versions,
+            get_compute_graph_version,
             delete_compute_graph,
+            get_resource_usage,
+            get_output_checks,
+            list_quarantined_outputs,
+            estimate_cost,
             list_tasks,
             list_outputs,
             delete_invocation,
+            get_invocation_provenance,
+            pin_invocation_outputs,
+            unpin_invocation_outputs,
+            invoke::reproduce_invocation,
+            test_invocations::test_compute_graph,
+            canaries::start_canary,
+            canaries::get_canary,
+            canaries::promote_canary,
+            canaries::rollback_canary,
+            artifacts::register_artifact,
+            artifacts::list_artifacts,
+            artifacts::get_artifact,
+            artifacts::download_artifact,
+            artifacts::delete_artifact,
+            artifacts::prefetch_artifacts,
+            artifacts::report_artifacts,
+            diagnostics::report_diagnostics,
+            diagnostics::get_diagnostics,
+            diagnostics::request_diagnostics,
+            task_queue::task_queue,
+            task_queue::intervene_task,
+            task_queue::audit_log,
+            placement::explain_placement,
+            activity::activity_feed,
+            subscriptions::create_subscription,
+            subscriptions::list_subscriptions,
+            subscriptions::delete_subscription,
+            subscriptions::list_subscription_events,
+            subscriptions::replay_subscription,
+            backups::create_backup,
+            backups::list_backups,
+            orphans::reconcile_orphans,
+            fsck::fsck_state_store,
+            exports::export_state_store,
             logs::download_logs,
             list_executors,
+            fleet_overview,
+            desired_capacity,
+            gpu_utilization,
+            health::healthz,
+            health::readyz,
+            metrics::metrics,
         ),
         components(
             schemas(
                 CreateNamespace,
                 NamespaceList,
                 IndexifyAPIError,
+                ErrorCode,
+                ProblemDetails,
                 Namespace,
+                NamespaceDeletion,
+                NamespaceDeletionStage,
                 ComputeGraph,
+                GraphOwner,
+                OwnerContact,
+                Severity,
+                OutputRetention,
                 Node,
                 DynamicRouter,
                 ComputeFn,
@@ -106,11 +345,95 @@ use crate::{
                 ComputeGraphsList,
                 InvocationResult,
                 ExecutorMetadata,
+                ExecutorAgent,
+                ExecutorOverview,
+                ExecutorHealth,
+                ExplainPlacement,
+                FunctionRef,
+                PlacementExplanation,
+                ExcludedExecutor,
+                Exclusion,
+                AgentRunner,
+                RunnerTasks,
+                FunctionCapacity,
+                GpuDevice,
+                GpuDeviceUtilization,
+                GpuRequest,
+                ExternalDependency,
                 Task,
                 TaskOutcome,
+                TaskFailure,
+                TaskErrorClass,
+                TaskRejection,
+                TaskRejectionReason,
                 Tasks,
+                TraceContext,
                 GraphInvocations,
+                Count,
+                TaskCounts,
+                UrlFetch,
+                UrlFetchStatus,
+                CostEstimateRequest,
+                CostEstimate,
+                FunctionEstimate,
+                ArchivedInvocation,
+                ArchivedInvocations,
                 DataObject,
+                InvocationProvenance,
+                FunctionProvenance,
+                GraphCanary,
+                VersionStats,
+                FnVersionStats,
+                BlobStoragePlacement,
+                HealthStatus,
+                DependencyHealth,
+                GraphResourceUsage,
+                ResourceUsageStats,
+                OutputCheck,
+                OutputCondition,
+                OutputCheckAction,
+                OutputCheckViolation,
+                OutputCheckStats,
+                GraphOutputChecks,
+                QuarantinedOutput,
+                QuarantinedOutputs,
+                EdgeTransform,
+                TransformStep,
+                Artifact,
+                ArtifactRef,
+                Artifacts,
+                ExecutorArtifacts,
+                QueuedTask,
+                TaskQueue,
+                TaskIntervention,
+                InterveneTask,
+                AuditLogEntry,
+                AuditLog,
+                Activity,
+                ActivityEvent,
+                ActivityFeed,
+                CreateSubscription,
+                Subscription,
+                Subscriptions,
+                NamespaceEvent,
+                SubscriptionEvent,
+                SubscriptionEvents,
+                SubscriptionEventsParams,
+                ReplaySubscription,
+                ReprocessingPolicy,
+                NearDuplicatePolicy,
+                NearDuplicateAction,
+                TestComputeGraph,
+                TestInvocations,
+                StateStoreBackup,
+                StateStoreBackups,
+                OrphansRemoved,
+                DiagnosticsReport,
+                ExecutorDiagnostics,
+                FsckReport,
+                FsckProblem,
+                FsckProblemKind,
+                invoke::InvokeWithUrl,
             )
         ),
         tags(
@@ -123,19 +446,30 @@ struct ApiDoc;
 #[derive(Clone)]
 pub struct RouteState {
     pub indexify_state: Arc<IndexifyState>,
-    pub blob_storage: Arc<blob_store::BlobStorage>,
+    pub blob_storage: Arc<blob_store::registry::BlobStorageRegistry>,
     pub executor_manager: Arc<ExecutorManager>,
+    pub fetcher: Arc<Fetcher>,
+    pub invocation_admission: Arc<InvocationAdmission>,
+    pub metrics_registry: Arc<Registry>,
+    pub request_tracking: Arc<RequestTracking>,
+    pub api_keys: Arc<ApiKeys>,
+    pub backup: BackupConfig,
+    pub test_invocations: TestInvocationConfig,
+    pub webhooks: WebhookConfig,
 }
 
 pub fn create_routes(route_state: RouteState) -> Router {
     let cors = CorsLayer::new()
-        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
         .allow_origin(Any)
         .allow_headers(Any);
 
     Router::new()
         .merge(SwaggerUi::new("/docs/swagger").url("/docs/openapi.json", ApiDoc::openapi()))
         .route("/", get(index))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz).with_state(route_state.clone()))
+        .route("/metrics", get(metrics).with_state(route_state.clone()))
         .route(
             "/namespaces",
             get(namespaces).with_state(route_state.clone()),
@@ -144,6 +478,14 @@ pub fn create_routes(route_state: RouteState) -> Router {
             "/namespaces",
             post(create_namespace).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace",
+            delete(delete_namespace).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/deletion",
+            get(namespace_deletion).with_state(route_state.clone()),
+        )
         .route(
             "/namespaces/:namespace/compute_graphs",
             post(create_compute_graph).with_state(route_state.clone()),
@@ -160,6 +502,38 @@ pub fn create_routes(route_state: RouteState) -> Router {
             "/namespaces/:namespace/compute_graphs/:compute_graph",
             get(get_compute_graph).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph",
+            put(put_compute_graph).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/versions",
+            get(list_compute_graph_versions).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/versions/:version",
+            get(get_compute_graph_version).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph",
+            post(test_compute_graph).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/canary",
+            put(start_canary).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/canary",
+            get(get_canary).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/canary/promote",
+            post(promote_canary).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/canary/rollback",
+            post(rollback_canary).with_state(route_state.clone()),
+        )
         .route(
             "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/tasks",
             get(list_tasks).with_state(route_state.clone()),
@@ -172,10 +546,50 @@ pub fn create_routes(route_state: RouteState) -> Router {
             "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/context",
             get(get_context).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/fetch",
+            get(get_url_fetch).with_state(route_state.clone()),
+        )
         .route(
             "/namespaces/:namespace/compute_graphs/:compute_graph/invocations",
             get(graph_invocations).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/count",
+            get(count_invocations).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/tasks/count",
+            get(count_tasks).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/data_objects/count",
+            get(count_data_objects).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/resource_usage",
+            get(get_resource_usage).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/output_checks",
+            get(get_output_checks).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/quarantined_outputs",
+            get(list_quarantined_outputs).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/estimate",
+            post(estimate_cost).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/archived_invocations",
+            get(list_archived_invocations).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/archived_invocations/:invocation_id",
+            get(get_archived_invocation).with_state(route_state.clone()),
+        )
         .route(
             "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_file",
             post(invoke_with_file).with_state(route_state.clone()),
@@ -184,6 +598,10 @@ pub fn create_routes(route_state: RouteState) -> Router {
             "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_object",
             post(invoke_with_object).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invoke_url",
+            post(invoke_with_url).with_state(route_state.clone()),
+        )
         .route(
             "/namespaces/:namespace/compute_graphs/:compute_graph/rerun",
             post(rerun_compute_graph).with_state(route_state.clone()),
@@ -192,6 +610,76 @@ pub fn create_routes(route_state: RouteState) -> Router {
             "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id",
             delete(delete_invocation).with_state(route_state.clone()),
         )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/provenance",
+            get(get_invocation_provenance).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/pin",
+            put(pin_invocation_outputs)
+                .delete(unpin_invocation_outputs)
+                .with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/reproduce",
+            post(reproduce_invocation).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/task_queue",
+            get(task_queue).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/compute_graphs/:compute_graph/invocations/:invocation_id/fn/:fn_name/tasks/:task_id/interventions",
+            post(intervene_task).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/audit_log",
+            get(audit_log).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/activity",
+            get(activity_feed).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/subscriptions",
+            post(create_subscription).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/subscriptions",
+            get(list_subscriptions).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/subscriptions/:id",
+            delete(delete_subscription).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/subscriptions/:id/events",
+            get(list_subscription_events).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/subscriptions/:id/replay",
+            post(replay_subscription).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/artifacts",
+            get(list_artifacts).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/artifacts/:name/versions/:version",
+            post(register_artifact).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/artifacts/:name/versions/:version",
+            get(get_artifact).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/artifacts/:name/versions/:version",
+            delete(delete_artifact).with_state(route_state.clone()),
+        )
+        .route(
+            "/namespaces/:namespace/artifacts/:name/versions/:version/content",
+            get(download_artifact).with_state(route_state.clone()),
+        )
         .route(
             "/namespaces/:namespace/compute_graphs/:compute_graph/notify",
             get(notify_on_change).with_state(route_state.clone()),
@@ -216,17 +704,88 @@ pub fn create_routes(route_state: RouteState) -> Router {
             "/internal/ingest_files",
             post(ingest_files_from_executor).with_state(route_state.clone()),
         )
+        .route("/executors", get(fleet_overview).with_state(route_state.clone()))
+        .route(
+            "/scheduler/explain",
+            post(explain_placement).with_state(route_state.clone()),
+        )
         .route("/internal/executors", get(list_executors).with_state(route_state.clone()))
+        .route(
+            "/internal/executors/gpu_utilization",
+            get(gpu_utilization).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/desired_capacity",
+            get(desired_capacity).with_state(route_state.clone()),
+        )
         .route(
             "/internal/executors/:id/tasks",
             post(executor_tasks).with_state(route_state.clone()),
         )
+        .route(
+            "/internal/executors/:id/artifacts",
+            get(prefetch_artifacts).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/executors/:id/artifacts",
+            put(report_artifacts).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/executors/:id/diagnostics",
+            put(report_diagnostics)
+                .get(get_diagnostics)
+                .with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/executors/:id/diagnostics/request",
+            post(request_diagnostics).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/agents/:agent_id/tasks",
+            post(agent_tasks).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/executors/:id/tasks/:task_id/reject",
+            post(reject_task).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/state_store/backups",
+            post(create_backup).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/state_store/backups",
+            get(list_backups).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/state_store/orphans/reconcile",
+            post(reconcile_orphans).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/state_store/fsck",
+            post(fsck_state_store).with_state(route_state.clone()),
+        )
+        .route(
+            "/internal/state_store/export",
+            get(export_state_store).with_state(route_state.clone()),
+        )
         .route(
             "/internal/fn_outputs/:input_key",
             get(download_fn_output_by_key).with_state(route_state.clone()),
         )
         .route("/ui", get(ui_index_handler))
         .route("/ui/*rest", get(ui_handler))
+        .layer(axum::middleware::from_fn_with_state(
+            route_state.request_tracking.clone(),
+            log_request_bodies,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            route_state.api_keys.clone(),
+            authorize,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            route_state.request_tracking.clone(),
+            track_requests,
+        ))
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(|req: &Request| {
@@ -281,23 +840,65 @@ async fn ui_handler(Path(url): Path<String>) -> impl IntoResponse {
     tag = "operations",
     responses(
         (status = 200, description = "Namespace created successfully"),
+        (status = BAD_REQUEST, description = "Invalid blob storage placement"),
+        (status = CONFLICT, description = "Namespace exists with another blob storage placement"),
         (status = INTERNAL_SERVER_ERROR, description = "Unable to create namespace")
     ),
 )]
 async fn create_namespace(
     State(state): State<RouteState>,
+    scope: Option<Extension<ApiKeyScope>>,
     Json(namespace): Json<CreateNamespace>,
 ) -> Result<(), IndexifyAPIError> {
-    state
+    if scope.is_some_and(|Extension(scope)| !scope.allows(&namespace.name)) {
+        return Err(IndexifyAPIError::forbidden(
+            "the API key can't create this namespace",
+        ));
+    }
+    let blob_storage: Option<data_model::BlobStoragePlacement> =
+        namespace.blob_storage.map(|p| p.into());
+    let payload = RequestPayload::CreateNameSpace(NamespaceRequest {
+        name: namespace.name.clone(),
+        blob_storage: blob_storage.clone(),
+        scheduling_weight: namespace.scheduling_weight,
+        reprocessing_policy: namespace.reprocessing_policy.into(),
+        near_duplicates: namespace.near_duplicates.map(|p| p.into()),
+    });
+    payload.validate()?;
+    // The storage is checked before the namespace is written, but only
+    // registered once the write went through.
+    let storage = match &blob_storage {
+        Some(placement) => {
+            if let data_model::BlobStoragePlacement::Disk { path } = placement {
+                state
+                    .blob_storage
+                    .check_disk_placement(path)
+                    .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+            }
+            let storage = state
+                .blob_storage
+                .build(placement_blob_storage_config(placement))
+                .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+            Some(storage)
+        }
+        None => None,
+    };
+    let result = state
         .indexify_state
         .write(StateMachineUpdateRequest {
-            payload: RequestPayload::CreateNameSpace(NamespaceRequest {
-                name: namespace.name,
-            }),
+            payload,
             state_changes_processed: vec![],
         })
-        .await
-        .map_err(IndexifyAPIError::internal_error)?;
+        .await;
+    if let Err(e) = result {
+        return Err(match e.downcast_ref::<NamespacePlacementConflict>() {
+            Some(conflict) => IndexifyAPIError::conflict(&conflict.to_string()),
+            None => IndexifyAPIError::internal_error(e),
+        });
+    }
+    if let Some(storage) = storage {
+        state.blob_storage.insert(&namespace.name, storage);
+    }
     Ok(())
 }
 
@@ -306,20 +907,97 @@ async fn create_namespace(
     get,
     path = "/namespaces",
     tag = "operations",
+    params(
+        ("limit" = Option<usize>, Query, description = "Namespaces in a page, all of them by default"),
+        ("cursor" = Option<String>, Query, description = "Continuation token returned with the previous page"),
+    ),
     responses(
         (status = 200, description = "List all namespaces", body = NamespaceList),
+        (status = BAD_REQUEST, description = "Invalid cursor"),
         (status = INTERNAL_SERVER_ERROR, description = "Unable to list namespace")
     ),
 )]
 async fn namespaces(
+    Query(params): Query<ListParams>,
     State(state): State<RouteState>,
+    scope: Option<Extension<ApiKeyScope>>,
 ) -> Result<Json<NamespaceList>, IndexifyAPIError> {
     let reader = state.indexify_state.reader();
-    let namespaces = reader
-        .get_all_namespaces()
-        .map_err(IndexifyAPIError::internal_error)?;
+    // Keys limited to a few namespaces only list those.
+    let (namespaces, cursor) = match scope {
+        Some(Extension(ApiKeyScope::Namespaces(names))) => {
+            reader.list_namespaces_in(&names, params.cursor()?.as_deref(), params.limit)
+        }
+        _ => reader.list_namespaces(params.cursor()?.as_deref(), params.limit),
+    }
+    .map_err(IndexifyAPIError::internal_error)?;
     let namespaces: Vec<Namespace> = namespaces.into_iter().map(|n| n.into()).collect();
-    Ok(Json(NamespaceList { namespaces }))
+    Ok(Json(NamespaceList {
+        namespaces,
+        cursor: encode_cursor(cursor),
+    }))
+}
+
+/// Delete a namespace
+///
+/// The namespace stops accepting writes right away, its data is deleted in
+/// the background.
+#[utoipa::path(
+    delete,
+    path = "/namespaces/{namespace}",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Namespace marked for deletion"),
+        (status = NOT_FOUND, description = "Namespace not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn delete_namespace(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    state
+        .indexify_state
+        .reader()
+        .get_namespace(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::namespace_not_found())?;
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::DeleteNamespace(DeleteNamespaceRequest { name: namespace }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(())
+}
+
+/// Get the progress of deleting a namespace
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/deletion",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Progress of the deletion", body = NamespaceDeletion),
+        (status = NOT_FOUND, description = "Namespace not found or not being deleted"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn namespace_deletion(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<NamespaceDeletion>, IndexifyAPIError> {
+    let deletion = state
+        .indexify_state
+        .reader()
+        .get_namespace(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?
+        .and_then(|namespace| namespace.deletion)
+        .ok_or(IndexifyAPIError::not_found(
+            "namespace is not being deleted",
+        ))?;
+    Ok(Json(deletion.into()))
 }
 
 #[allow(dead_code)]
@@ -338,14 +1016,139 @@ struct ComputeGraphCreateType {
     request_body(content_type = "multipart/form-data", content = inline(ComputeGraphCreateType)),
     responses(
         (status = 200, description = "Create a Compute Graph"),
+        (status = CONFLICT, description = "A canary rollout of the graph is in progress"),
         (status = INTERNAL_SERVER_ERROR, description = "Unable to create compute graphs")
     ),
 )]
 async fn create_compute_graph(
     Path(namespace): Path<String>,
     State(state): State<RouteState>,
-    mut compute_graph_code: Multipart,
+    compute_graph_code: Multipart,
 ) -> Result<(), IndexifyAPIError> {
+    let compute_graph = read_compute_graph(&state, &namespace, compute_graph_code).await?;
+    let name = compute_graph.name.clone();
+    let request = RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+        namespace,
+        compute_graph,
+        precondition: None,
+    });
+    request.validate()?;
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: request,
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(canary_error)?;
+    info!("compute graph created: {}", name);
+    Ok(())
+}
+
+/// Create or update a compute graph if it's at the version in the If-Match
+/// header, or create it if it doesn't exist when the header is missing
+#[utoipa::path(
+    put,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}",
+    tag = "operations",
+    request_body(content_type = "multipart/form-data", content = inline(ComputeGraphCreateType)),
+    params(
+        ("If-Match" = Option<String>, Header, description = "Version the compute graph is expected at, or * for any version"),
+    ),
+    responses(
+        (status = 200, description = "Compute graph written, its version is in the ETag header"),
+        (status = BAD_REQUEST, description = "Invalid compute graph or If-Match header"),
+        (status = CONFLICT, description = "Compute graph is at another version, its current version is in the ETag header, or a canary rollout of it is in progress"),
+        (status = INTERNAL_SERVER_ERROR, description = "Unable to write compute graph")
+    ),
+)]
+async fn put_compute_graph(
+    Path((namespace, name)): Path<(String, String)>,
+    State(state): State<RouteState>,
+    headers: HeaderMap,
+    compute_graph_code: Multipart,
+) -> Result<axum::response::Response, IndexifyAPIError> {
+    let precondition = graph_precondition(&headers)?;
+    let compute_graph = read_compute_graph(&state, &namespace, compute_graph_code).await?;
+    if compute_graph.name != name {
+        return Err(IndexifyAPIError::bad_request(&format!(
+            "compute graph is named {}, expected {}",
+            compute_graph.name, name
+        )));
+    }
+    let request = RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+        namespace: namespace.clone(),
+        compute_graph,
+        precondition: Some(precondition),
+    });
+    request.validate()?;
+    let result = state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: request,
+            state_changes_processed: vec![],
+        })
+        .await;
+    if let Err(e) = result {
+        return match e.downcast_ref::<GraphVersionConflict>() {
+            Some(conflict) => {
+                let error = IndexifyAPIError::conflict(&conflict.to_string())
+                    .with_code(ErrorCode::GraphVersionConflict);
+                Ok(match conflict.current {
+                    Some(current) => ([(ETAG, format!("\"{}\"", current))], error).into_response(),
+                    None => error.into_response(),
+                })
+            }
+            None => Err(canary_error(e)),
+        };
+    }
+    let version = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?
+        .map(|graph| graph.version);
+    info!("compute graph written: {}", name);
+    Ok(version_response(StatusCode::OK, version, String::new()))
+}
+
+fn graph_precondition(headers: &HeaderMap) -> Result<GraphPrecondition, IndexifyAPIError> {
+    let Some(value) = headers.get(IF_MATCH) else {
+        return Ok(GraphPrecondition::Absent);
+    };
+    let value = value
+        .to_str()
+        .map_err(|_| IndexifyAPIError::bad_request("invalid If-Match header"))?
+        .trim();
+    if value == "*" {
+        return Ok(GraphPrecondition::Exists);
+    }
+    value
+        .trim_start_matches("W/")
+        .trim_matches('"')
+        .parse::<u32>()
+        .map(|version| GraphPrecondition::Version(version.into()))
+        .map_err(|_| IndexifyAPIError::bad_request("If-Match must be a compute graph version"))
+}
+
+fn version_response(
+    status: StatusCode,
+    version: Option<GraphVersion>,
+    body: String,
+) -> axum::response::Response {
+    match version {
+        Some(version) => (status, [(ETAG, format!("\"{}\"", version))], body).into_response(),
+        None => (status, body).into_response(),
+    }
+}
+
+/// Reads the definition and code of a compute graph from a multipart upload,
+/// storing the code in the namespace's blob storage.
+async fn read_compute_graph(
+    state: &RouteState,
+    namespace: &str,
+    mut compute_graph_code: Multipart,
+) -> Result<data_model::ComputeGraph, IndexifyAPIError> {
     let mut compute_graph_definition: Option<ComputeGraph> = Option::None;
     let mut put_result: Option<PutResult> = None;
     while let Some(field) = compute_graph_code.next_field().await.unwrap() {
@@ -356,6 +1159,7 @@ async fn create_compute_graph(
                 let file_name = format!("{}_{}", namespace, nanoid!());
                 let result = state
                     .blob_storage
+                    .for_namespace(namespace)
                     .put(&file_name, stream)
                     .await
                     .map_err(IndexifyAPIError::internal_error)?;
@@ -366,7 +1170,7 @@ async fn create_compute_graph(
                     .await
                     .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
                 let mut json_value: serde_json::Value = serde_json::from_str(&text)?;
-                json_value["namespace"] = serde_json::Value::String(namespace.clone());
+                json_value["namespace"] = serde_json::Value::String(namespace.to_string());
                 compute_graph_definition = Some(serde_json::from_value(json_value)?);
             }
         }
@@ -388,21 +1192,16 @@ async fn create_compute_graph(
         &put_result.sha256_hash,
         put_result.size_bytes,
     )?;
-    let name = compute_graph.name.clone();
-    let request = RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
-        namespace,
-        compute_graph,
-    });
-    state
-        .indexify_state
-        .write(StateMachineUpdateRequest {
-            payload: request,
-            state_changes_processed: vec![],
-        })
-        .await
-        .map_err(IndexifyAPIError::internal_error)?;
-    info!("compute graph created: {}", name);
-    Ok(())
+    for (i, owner) in compute_graph.owners.iter().enumerate() {
+        if let data_model::alerts::OwnerContact::Webhook { url } = &owner.contact {
+            outbound::check_url(url, state.webhooks.allow_private_addresses)
+                .await
+                .map_err(|e| {
+                    IndexifyAPIError::bad_request(&format!("owners.{}.contact.url: {}", i, e))
+                })?;
+        }
+    }
+    Ok(compute_graph)
 }
 
 /// Delete compute graph
@@ -434,7 +1233,8 @@ async fn delete_compute_graph(
     Ok(())
 }
 
-/// List compute graphs
+/// List compute graphs, optionally only the ones carrying all of the labels
+/// passed as `labels.<key>=<value>` query parameters
 #[utoipa::path(
     get,
     path = "/namespaces/{namespace}/compute_graphs",
@@ -447,16 +1247,23 @@ async fn delete_compute_graph(
 async fn list_compute_graphs(
     Path(namespace): Path<String>,
     Query(params): Query<ListParams>,
+    Query(filters): Query<HashMap<String, String>>,
     State(state): State<RouteState>,
 ) -> Result<Json<ComputeGraphsList>, IndexifyAPIError> {
+    let labels = labels_from_query(&filters)?;
     let (compute_graphs, cursor) = state
         .indexify_state
         .reader()
-        .list_compute_graphs(&namespace, params.cursor.as_deref(), params.limit)
+        .list_compute_graphs_by_labels(
+            &namespace,
+            &labels,
+            params.cursor()?.as_deref(),
+            params.limit,
+        )
         .map_err(IndexifyAPIError::internal_error)?;
     Ok(Json(ComputeGraphsList {
         compute_graphs: compute_graphs.into_iter().map(|c| c.into()).collect(),
-        cursor,
+        cursor: encode_cursor(cursor),
     }))
 }
 
@@ -466,47 +1273,115 @@ async fn list_compute_graphs(
     path = "/namespaces/{namespace}/compute_graphs/{compute_graph}",
     tag = "operations",
     responses(
-        (status = 200, description = "Compute Graph Definition", body = ComputeGraph),
+        (status = 200, description = "Compute Graph Definition, its version is in the ETag header", body = ComputeGraph),
         (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
     ),
 )]
 async fn get_compute_graph(
     Path((namespace, name)): Path<(String, String)>,
     State(state): State<RouteState>,
-) -> Result<Json<ComputeGraph>, IndexifyAPIError> {
+) -> Result<impl IntoResponse, IndexifyAPIError> {
     let compute_graph = state
         .indexify_state
         .reader()
         .get_compute_graph(&namespace, &name)
         .map_err(IndexifyAPIError::internal_error)?;
     if let Some(compute_graph) = compute_graph {
-        return Ok(Json(compute_graph.into()));
+        let etag = format!("\"{}\"", compute_graph.version);
+        return Ok(([(ETAG, etag)], Json(ComputeGraph::from(compute_graph))));
     }
-    Err(IndexifyAPIError::not_found("Compute Graph not found"))
+    Err(IndexifyAPIError::compute_graph_not_found())
 }
 
-/// List Graph invocations
+/// List the versions of a compute graph, oldest first
 #[utoipa::path(
     get,
-    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations",
-    tag = "ingestion",
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/versions",
+    tag = "operations",
     responses(
-        (status = 200, description = "Compute Graph Definition", body = GraphInvocations),
+        (status = 200, description = "Definitions of the graph by version", body = ComputeGraphsList),
+        (status = NOT_FOUND, description = "Compute Graph not found"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
     ),
 )]
-async fn graph_invocations(
-    Path((namespace, compute_graph)): Path<(String, String)>,
+async fn list_compute_graph_versions(
+    Path((namespace, name)): Path<(String, String)>,
     Query(params): Query<ListParams>,
     State(state): State<RouteState>,
-) -> Result<Json<GraphInvocations>, IndexifyAPIError> {
-    let (data_objects, cursor) = state
-        .indexify_state
-        .reader()
-        .list_invocations(
+) -> Result<Json<ComputeGraphsList>, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    reader
+        .get_compute_graph(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::compute_graph_not_found())?;
+    let (compute_graphs, cursor) = reader
+        .list_compute_graph_versions(&namespace, &name, params.cursor()?.as_deref(), params.limit)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(ComputeGraphsList {
+        compute_graphs: compute_graphs.into_iter().map(|c| c.into()).collect(),
+        cursor: encode_cursor(cursor),
+    }))
+}
+
+/// Get the definition of a compute graph at a version, the one invocations
+/// started on that version run
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/versions/{version}",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Compute Graph Definition at the version", body = ComputeGraph),
+        (status = NOT_FOUND, description = "Compute Graph or version not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn get_compute_graph_version(
+    Path((namespace, name, version)): Path<(String, String, u32)>,
+    State(state): State<RouteState>,
+) -> Result<impl IntoResponse, IndexifyAPIError> {
+    let compute_graph = state
+        .indexify_state
+        .reader()
+        .get_compute_graph_version(&namespace, &name, version.into())
+        .map_err(IndexifyAPIError::internal_error)?;
+    match compute_graph {
+        // Versions which weren't kept resolve to another one.
+        Some(compute_graph) if compute_graph.version == version.into() => {
+            let etag = format!("\"{}\"", compute_graph.version);
+            Ok(([(ETAG, etag)], Json(ComputeGraph::from(compute_graph))))
+        }
+        _ => Err(IndexifyAPIError::not_found(&format!(
+            "version {} of compute graph {} not found",
+            version, name
+        ))),
+    }
+}
+
+/// List Graph invocations
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations",
+    tag = "ingestion",
+    responses(
+        (status = 200, description = "Compute Graph Definition", body = GraphInvocations),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn graph_invocations(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    Query(params): Query<ListParams>,
+    Query(filters): Query<HashMap<String, String>>,
+    State(state): State<RouteState>,
+) -> Result<Json<GraphInvocations>, IndexifyAPIError> {
+    let tags = tags_from_query(&filters)?;
+    let (data_objects, cursor) = state
+        .indexify_state
+        .reader()
+        .list_invocations_by_tags(
             &namespace,
             &compute_graph,
-            params.cursor.as_deref(),
+            &tags,
+            params.cursor()?.as_deref(),
             params.limit,
         )
         .map_err(IndexifyAPIError::internal_error)?;
@@ -516,14 +1391,151 @@ async fn graph_invocations(
             id: data_object.id,
             payload_size: data_object.payload.size,
             payload_sha_256: data_object.payload.sha256_hash,
+            content_type: data_object.payload.content_type,
+            tags: data_object.tags,
+            reproduced_from: data_object.reproduced_from,
+            source: data_object.source.map(|source| source.id),
+            superseded_by: data_object.superseded_by,
+            near_duplicate_of: data_object.near_duplicate_of,
+            test: data_object.test,
         });
     }
     Ok(Json(GraphInvocations {
         invocations,
-        cursor,
+        cursor: encode_cursor(cursor),
+    }))
+}
+
+/// Reads the maintained counters of a graph, recounting when asked to or when
+/// the graph's objects aren't counted yet. Returns whether the counts are
+/// approximate.
+async fn graph_counts(
+    state: &RouteState,
+    namespace: &str,
+    compute_graph: &str,
+    exact: bool,
+) -> Result<(state_store::counters::GraphCounts, bool), IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    reader
+        .get_compute_graph(namespace, compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::compute_graph_not_found())?;
+    if !exact {
+        if let Some(counts) = reader
+            .graph_counts(namespace, compute_graph)
+            .map_err(IndexifyAPIError::internal_error)?
+        {
+            return Ok((counts, true));
+        }
+    }
+    let counts = state
+        .indexify_state
+        .recount_graph(namespace, compute_graph)
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok((counts, false))
+}
+
+/// Count Graph invocations
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/count",
+    tag = "ingestion",
+    params(
+        ("exact" = Option<bool>, Query, description = "Count the invocations instead of reading the maintained counter"),
+    ),
+    responses(
+        (status = 200, description = "Number of invocations of the graph", body = Count),
+        (status = NOT_FOUND, description = "Compute Graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn count_invocations(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    Query(params): Query<CountParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<Count>, IndexifyAPIError> {
+    let (counts, approximate) =
+        graph_counts(&state, &namespace, &compute_graph, params.exact).await?;
+    Ok(Json(Count {
+        count: counts.invocations,
+        approximate,
     }))
 }
 
+/// Count the tasks of a graph by state
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/tasks/count",
+    tag = "operations",
+    params(
+        ("exact" = Option<bool>, Query, description = "Count the tasks instead of reading the maintained counters"),
+    ),
+    responses(
+        (status = 200, description = "Number of tasks of the graph in each state", body = TaskCounts),
+        (status = NOT_FOUND, description = "Compute Graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn count_tasks(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    Query(params): Query<CountParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<TaskCounts>, IndexifyAPIError> {
+    let (counts, approximate) =
+        graph_counts(&state, &namespace, &compute_graph, params.exact).await?;
+    Ok(Json(TaskCounts {
+        pending: counts.pending_tasks,
+        succeeded: counts.succeeded_tasks,
+        failed: counts.failed_tasks,
+        approximate,
+    }))
+}
+
+/// Count the data objects of a namespace, invocation inputs and function
+/// outputs across its graphs
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/data_objects/count",
+    tag = "operations",
+    params(
+        ("exact" = Option<bool>, Query, description = "Count the data objects instead of reading the maintained counters"),
+    ),
+    responses(
+        (status = 200, description = "Number of data objects in the namespace", body = Count),
+        (status = NOT_FOUND, description = "Namespace not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn count_data_objects(
+    Path(namespace): Path<String>,
+    Query(params): Query<CountParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<Count>, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    reader
+        .get_namespace(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::namespace_not_found())?;
+    let (compute_graphs, _) = reader
+        .list_compute_graphs(&namespace, None, None)
+        .map_err(IndexifyAPIError::internal_error)?;
+    let mut count = Count {
+        count: 0,
+        approximate: false,
+    };
+    for compute_graph in compute_graphs
+        .iter()
+        .filter(|graph| graph.namespace == namespace)
+    {
+        let (counts, approximate) =
+            graph_counts(&state, &namespace, &compute_graph.name, params.exact).await?;
+        count.count += counts.data_objects;
+        count.approximate |= approximate;
+    }
+    Ok(Json(count))
+}
+
 async fn notify_on_change(
     Path((_namespace, _compute_graph)): Path<(String, String)>,
     State(state): State<RouteState>,
@@ -569,39 +1581,232 @@ async fn list_executors(
     Ok(Json(http_executors))
 }
 
+/// Executor fleet overview
+#[utoipa::path(
+    get,
+    path = "/executors",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Every executor with its load, recent failures and health", body = Vec<ExecutorOverview>),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn fleet_overview(
+    State(state): State<RouteState>,
+) -> Result<Json<Vec<ExecutorOverview>>, IndexifyAPIError> {
+    let overview = state
+        .executor_manager
+        .fleet_overview()
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(overview.into_iter().map(Into::into).collect()))
+}
+
+/// Allocated share of every GPU device of the executors
+#[utoipa::path(
+    get,
+    path = "/internal/executors/gpu_utilization",
+    tag = "operations",
+    responses(
+        (status = 200, description = "GPU utilization per executor device", body = Vec<GpuDeviceUtilization>),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn gpu_utilization(
+    State(state): State<RouteState>,
+) -> Result<Json<Vec<GpuDeviceUtilization>>, IndexifyAPIError> {
+    let utilization = state
+        .executor_manager
+        .gpu_utilization()
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(utilization.into_iter().map(Into::into).collect()))
+}
+
+/// Executor capacity wanted by each compute function
+#[utoipa::path(
+    get,
+    path = "/internal/desired_capacity",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Desired executor capacity per function", body = Vec<FunctionCapacity>),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn desired_capacity(
+    State(state): State<RouteState>,
+) -> Result<Json<Vec<FunctionCapacity>>, IndexifyAPIError> {
+    let capacity = state
+        .executor_manager
+        .desired_capacity()
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(capacity.into_iter().map(Into::into).collect()))
+}
+
+fn task_stream_limit(executor: &data_model::ExecutorMetadata) -> usize {
+    executor
+        .max_allocated_tasks()
+        .map_or(usize::MAX, |limit| limit as usize)
+}
+
+fn registration_error(e: anyhow::Error) -> IndexifyAPIError {
+    if let Some(errors) = e.downcast_ref::<ValidationErrors>() {
+        return errors.clone().into();
+    }
+    match e.downcast_ref::<ExecutorRejected>() {
+        Some(rejected) => IndexifyAPIError::forbidden(&rejected.to_string()),
+        None => IndexifyAPIError::internal_error_str(&e.to_string()),
+    }
+}
+
+/// Asks the executor on the other end of a task stream to report its
+/// diagnostics, see `diagnostics::request_diagnostics`.
+fn diagnostics_event() -> axum::response::sse::Event {
+    axum::response::sse::Event::default()
+        .event("diagnostics")
+        .data("{}")
+}
+
 async fn executor_tasks(
     Path(executor_id): Path<ExecutorId>,
     State(state): State<RouteState>,
     Json(payload): Json<ExecutorMetadata>,
 ) -> Result<impl IntoResponse, IndexifyAPIError> {
-    const TASK_LIMIT: usize = 10;
-    let err = state
+    let registered = state
         .executor_manager
         .register_executor(data_model::ExecutorMetadata {
             id: executor_id.clone(),
             image_name: payload.image_name.clone(),
             addr: payload.addr.clone(),
             labels: payload.labels.clone(),
+            gpus: payload.gpus.iter().cloned().map(Into::into).collect(),
+            capacity: payload.capacity,
+            version: payload.version.clone(),
+            artifacts: payload.artifacts.clone(),
+            prefetch_window: payload.prefetch_window,
         })
         .await;
-    if let Err(e) = err {
-        tracing::error!("failed to register executor {}: {:?}", executor_id, e);
-        return Err(IndexifyAPIError::internal_error_str(&e.to_string()));
-    }
-    let stream = state_store::task_stream(state.indexify_state, executor_id.clone(), TASK_LIMIT);
+    let executor = match registered {
+        Ok(executor) => executor,
+        Err(e) => {
+            tracing::error!("failed to register executor {}: {:?}", executor_id, e);
+            return Err(registration_error(e));
+        }
+    };
+    // Tasks are allocated up to the prefetch window, all of them are sent.
+    let stream = state_store::task_stream(
+        state.indexify_state,
+        executor_id.clone(),
+        task_stream_limit(&executor),
+    );
     let executor_manager = state.executor_manager.clone();
-    let stream = stream
-        .map(|item| match item {
-            Ok(item) => {
-                let item: Vec<Task> = item.into_iter().map(Into::into).collect();
-                axum::response::sse::Event::default().json_data(item)
-            }
+    let diagnostics_requests = executor_manager
+        .diagnostics_requests(executor_id.to_string())
+        .map(|_| Ok(diagnostics_event()));
+    let stream = stream.map(|item| match item {
+        Ok(item) => {
+            let item: Vec<Task> = item.into_iter().map(Into::into).collect();
+            axum::response::sse::Event::default().json_data(item)
+        }
+        Err(e) => {
+            tracing::error!("error in task stream: {}", e);
+            Err(axum::Error::new(e))
+        }
+    });
+    let stream = futures::stream::select(stream, diagnostics_requests)
+        .guard(|| executors::schedule_deregister(executor_manager, executor_id, EXECUTOR_TIMEOUT));
+    Ok(axum::response::Sse::new(stream).keep_alive(
+        axum::response::sse::KeepAlive::new()
+            .interval(Duration::from_secs(1))
+            .text("keep-alive-text"),
+    ))
+}
+
+/// Registers every runner of an executor agent and streams the tasks of all
+/// of them over one connection. Runners are deregistered together when the
+/// connection closes.
+async fn agent_tasks(
+    Path(agent_id): Path<String>,
+    State(state): State<RouteState>,
+    Json(agent): Json<ExecutorAgent>,
+) -> Result<impl IntoResponse, IndexifyAPIError> {
+    let mut runner_ids = std::collections::HashSet::new();
+    for runner in &agent.runners {
+        if runner.id.is_empty() || runner.id.contains('|') || !runner_ids.insert(&runner.id) {
+            return Err(IndexifyAPIError::bad_request(&format!(
+                "invalid or duplicate runner id {:?}",
+                runner.id
+            )));
+        }
+    }
+    let mut executor_ids = Vec::new();
+    let mut streams = Vec::new();
+    for runner in agent.runners {
+        let executor_id = runner_executor_id(&agent_id, &runner.id);
+        let mut labels = agent.labels.clone();
+        labels.extend(runner.labels);
+        let registered = state
+            .executor_manager
+            .register_executor(data_model::ExecutorMetadata {
+                id: executor_id.clone(),
+                image_name: runner.image_name,
+                addr: agent.addr.clone(),
+                labels,
+                gpus: runner.gpus.into_iter().map(Into::into).collect(),
+                capacity: runner.capacity,
+                version: agent.version.clone(),
+                artifacts: runner.artifacts,
+                prefetch_window: runner.prefetch_window,
+            })
+            .await;
+        let executor = match registered {
+            Ok(executor) => executor,
             Err(e) => {
-                tracing::error!("error in task stream: {}", e);
-                Err(axum::Error::new(e))
+                tracing::error!("failed to register runner {}: {:?}", executor_id, e);
+                for executor_id in executor_ids {
+                    executors::schedule_deregister(
+                        state.executor_manager.clone(),
+                        executor_id,
+                        Duration::ZERO,
+                    );
+                }
+                return Err(registration_error(e));
             }
-        })
-        .guard(|| executors::schedule_deregister(executor_manager, executor_id, EXECUTOR_TIMEOUT));
+        };
+        let runner_id = runner.id;
+        let stream_executor_id = executor_id.clone();
+        let stream = state_store::task_stream(
+            state.indexify_state.clone(),
+            executor_id.clone(),
+            task_stream_limit(&executor),
+        )
+        .map(move |item| {
+            item.map(|tasks| RunnerTasks {
+                runner_id: runner_id.clone(),
+                executor_id: stream_executor_id.to_string(),
+                tasks: tasks.into_iter().map(Into::into).collect(),
+            })
+        });
+        streams.push(stream);
+        executor_ids.push(executor_id);
+    }
+    let executor_manager = state.executor_manager.clone();
+    let diagnostics_requests = executor_manager
+        .diagnostics_requests(agent_id)
+        .map(|_| Ok(diagnostics_event()));
+    let stream = futures::stream::select_all(streams).map(|item| match item {
+        Ok(item) => axum::response::sse::Event::default().json_data(item),
+        Err(e) => {
+            tracing::error!("error in agent task stream: {}", e);
+            Err(axum::Error::new(e))
+        }
+    });
+    let stream = futures::stream::select(stream, diagnostics_requests).guard(move || {
+        for executor_id in executor_ids {
+            executors::schedule_deregister(executor_manager.clone(), executor_id, EXECUTOR_TIMEOUT);
+        }
+    });
     Ok(axum::response::Sse::new(stream).keep_alive(
         axum::response::sse::KeepAlive::new()
             .interval(Duration::from_secs(1))
@@ -609,6 +1814,42 @@ async fn executor_tasks(
     ))
 }
 
+async fn reject_task(
+    Path((executor_id, task_id)): Path<(ExecutorId, String)>,
+    State(state): State<RouteState>,
+    Json(rejection): Json<RejectTask>,
+) -> Result<(), IndexifyAPIError> {
+    info!(
+        "executor {} rejected task {}: {:?}",
+        executor_id, task_id, rejection.reason
+    );
+    let result = state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::RejectTask(RejectTaskRequest {
+                namespace: rejection.namespace,
+                compute_graph: rejection.compute_graph,
+                compute_fn: rejection.compute_fn,
+                invocation_id: rejection.invocation_id,
+                task_id: TaskId::new(task_id),
+                executor_id,
+                reason: rejection.reason.into(),
+                message: rejection.message,
+            }),
+            state_changes_processed: vec![],
+        })
+        .await;
+    if let Err(e) = result {
+        let message = e.to_string();
+        return Err(match e.downcast_ref::<TaskRejectionError>() {
+            Some(TaskRejectionError::TaskNotFound(_)) => IndexifyAPIError::not_found(&message),
+            Some(TaskRejectionError::NotAllocated(..)) => IndexifyAPIError::conflict(&message),
+            None => IndexifyAPIError::internal_error(e),
+        });
+    }
+    Ok(())
+}
+
 /// List tasks for a compute graph invocation
 #[utoipa::path(
     get,
@@ -632,12 +1873,191 @@ async fn list_tasks(
             &namespace,
             &compute_graph,
             &invocation_id,
-            params.cursor.as_deref(),
+            params.cursor()?.as_deref(),
             params.limit,
         )
         .map_err(IndexifyAPIError::internal_error)?;
     let tasks = tasks.into_iter().map(Into::into).collect();
-    Ok(Json(Tasks { tasks, cursor }))
+    Ok(Json(Tasks {
+        tasks,
+        cursor: encode_cursor(cursor),
+    }))
+}
+
+/// Get the resource usage of a compute graph and of each of its functions
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/resource_usage",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Resource usage of the graph and its functions", body = GraphResourceUsage),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn get_resource_usage(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<GraphResourceUsage>, IndexifyAPIError> {
+    let reader = state.indexify_state.snapshot_reader();
+    let graph = reader
+        .graph_resource_usage(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?;
+    let functions = reader
+        .fn_resource_usage(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(GraphResourceUsage {
+        graph: graph.map(Into::into),
+        functions: functions
+            .into_iter()
+            .map(|(compute_fn, stats)| (compute_fn, stats.into()))
+            .collect(),
+    }))
+}
+
+/// Get the output check results of each function of a compute graph
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/output_checks",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Output check results of the graph's functions", body = GraphOutputChecks),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn get_output_checks(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<GraphOutputChecks>, IndexifyAPIError> {
+    let functions = state
+        .indexify_state
+        .reader()
+        .output_check_stats(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(GraphOutputChecks {
+        functions: functions
+            .into_iter()
+            .map(|(compute_fn, stats)| (compute_fn, stats.into()))
+            .collect(),
+    }))
+}
+
+/// List the outputs of an invocation kept out of the graph by output checks
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/quarantined_outputs",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Quarantined outputs of the invocation", body = QuarantinedOutputs),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn list_quarantined_outputs(
+    Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<QuarantinedOutputs>, IndexifyAPIError> {
+    let outputs = state
+        .indexify_state
+        .reader()
+        .list_quarantined_outputs(&namespace, &compute_graph, &invocation_id)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(QuarantinedOutputs {
+        outputs: outputs.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Estimate the tasks, compute and storage of invocations of a compute graph
+/// from its past invocations
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/estimate",
+    request_body = CostEstimateRequest,
+    tag = "operations",
+    responses(
+        (status = 200, description = "Estimated cost of the invocations", body = CostEstimate),
+        (status = NOT_FOUND, description = "Compute Graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn estimate_cost(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    State(state): State<RouteState>,
+    Json(request): Json<CostEstimateRequest>,
+) -> Result<Json<CostEstimate>, IndexifyAPIError> {
+    let reader = state.indexify_state.snapshot_reader();
+    let compute_graph = reader
+        .get_compute_graph(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::compute_graph_not_found())?;
+    let estimate = cost_estimate::estimate_cost(
+        &reader,
+        &compute_graph,
+        request.invocations,
+        request.input_size_bytes,
+    )
+    .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(estimate.into()))
+}
+
+/// List archived invocations of a compute graph
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/archived_invocations",
+    tag = "operations",
+    responses(
+        (status = 200, description = "List archived invocations of a compute graph", body = ArchivedInvocations),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn list_archived_invocations(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    Query(params): Query<ListParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<ArchivedInvocations>, IndexifyAPIError> {
+    let (invocations, cursor) = state
+        .indexify_state
+        .reader()
+        .list_archived_invocations(
+            &namespace,
+            &compute_graph,
+            params.cursor()?.as_deref(),
+            params.limit,
+        )
+        .map_err(IndexifyAPIError::internal_error)?;
+    let invocations = invocations.into_iter().map(Into::into).collect();
+    Ok(Json(ArchivedInvocations {
+        invocations,
+        cursor: encode_cursor(cursor),
+    }))
+}
+
+/// Rehydrate an archived invocation with its context, tasks and outputs
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/archived_invocations/{invocation_id}",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Archived invocation with its context, tasks and outputs"),
+        (status = NOT_FOUND, description = "Invocation is not archived"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn get_archived_invocation(
+    Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<InvocationArchive>, IndexifyAPIError> {
+    let archived = state
+        .indexify_state
+        .reader()
+        .archived_invocation(&namespace, &compute_graph, &invocation_id)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("invocation is not archived"))?;
+    let data = state
+        .blob_storage
+        .read_bytes(&archived.url)
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    let archive = InvocationArchive::decode(&data).map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(archive))
 }
 
 /// Get accounting information for a compute graph invocation
@@ -685,12 +2105,15 @@ async fn list_outputs(
             &namespace,
             &compute_graph,
             &invocation_id,
-            params.cursor.as_deref(),
+            params.cursor()?.as_deref(),
             params.limit,
         )
         .map_err(IndexifyAPIError::internal_error)?;
     let outputs = outputs.into_iter().map(Into::into).collect();
-    Ok(Json(FnOutputs { outputs, cursor }))
+    Ok(Json(FnOutputs {
+        outputs,
+        cursor: encode_cursor(cursor),
+    }))
 }
 
 /// Delete a specific invocation  
@@ -724,17 +2147,113 @@ async fn delete_invocation(
     Ok(())
 }
 
+/// Get what an invocation ran with
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/provenance",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Graph, function and input versions the invocation ran with", body = InvocationProvenance),
+        (status = NOT_FOUND, description = "Invocation not found or not started"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn get_invocation_provenance(
+    Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<InvocationProvenance>, IndexifyAPIError> {
+    let mut invocation = state
+        .indexify_state
+        .reader()
+        .invocation_payload(&namespace, &compute_graph, &invocation_id)
+        .map_err(|_| IndexifyAPIError::invocation_not_found())?;
+    let provenance = invocation
+        .provenance
+        .take()
+        .ok_or(IndexifyAPIError::not_found("invocation has no provenance"))?;
+    Ok(Json(InvocationProvenance::new(&invocation, provenance)))
+}
+
+/// Keep the outputs of an invocation whatever the output retention of its
+/// graph
+#[utoipa::path(
+    put,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/pin",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Outputs of the invocation pinned"),
+        (status = NOT_FOUND, description = "Invocation not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn pin_invocation_outputs(
+    Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    set_outputs_pinned(&state, namespace, compute_graph, invocation_id, true).await
+}
+
+/// Let the output retention of the graph expire the outputs of an invocation
+/// again
+#[utoipa::path(
+    delete,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/pin",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Outputs of the invocation unpinned"),
+        (status = NOT_FOUND, description = "Invocation not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+async fn unpin_invocation_outputs(
+    Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    set_outputs_pinned(&state, namespace, compute_graph, invocation_id, false).await
+}
+
+async fn set_outputs_pinned(
+    state: &RouteState,
+    namespace: String,
+    compute_graph: String,
+    invocation_id: String,
+    pinned: bool,
+) -> Result<(), IndexifyAPIError> {
+    state
+        .indexify_state
+        .reader()
+        .invocation_ctx(&namespace, &compute_graph, &invocation_id)
+        .map_err(|_| IndexifyAPIError::invocation_not_found())?;
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::PinInvocationOutputs(PinInvocationOutputsRequest {
+                namespace,
+                compute_graph,
+                invocation_id,
+                pinned,
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)
+}
+
 async fn get_code(
     Path((namespace, compute_graph)): Path<(String, String)>,
+    Query(params): Query<CodeParams>,
     State(state): State<RouteState>,
 ) -> Result<impl IntoResponse, IndexifyAPIError> {
-    let compute_graph = state
-        .indexify_state
-        .reader()
-        .get_compute_graph(&namespace, &compute_graph)
-        .map_err(IndexifyAPIError::internal_error)?;
+    let reader = state.indexify_state.reader();
+    let compute_graph = match params.version {
+        Some(version) => {
+            reader.get_compute_graph_version(&namespace, &compute_graph, version.into())
+        }
+        None => reader.get_compute_graph(&namespace, &compute_graph),
+    }
+    .map_err(IndexifyAPIError::internal_error)?;
     if compute_graph.is_none() {
-        return Err(IndexifyAPIError::not_found("Compute Graph not found"));
+        return Err(IndexifyAPIError::compute_graph_not_found());
     }
     let compute_graph = compute_graph.unwrap();
     let storage_reader = state.blob_storage.get(&compute_graph.code.path);
@@ -0,0 +1,33 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+
+use super::RouteState;
+use crate::http_objects::{encode_cursor, ActivityFeed, IndexifyAPIError, ListParams};
+
+/// List the activity of a namespace and of the whole server, newest first
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/activity",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Events of the activity feed", body = ActivityFeed),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn activity_feed(
+    Path(namespace): Path<String>,
+    Query(params): Query<ListParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<ActivityFeed>, IndexifyAPIError> {
+    let (events, cursor) = state
+        .indexify_state
+        .reader()
+        .list_activity(&namespace, params.cursor()?.as_deref(), params.limit)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(ActivityFeed {
+        events: events.into_iter().map(Into::into).collect(),
+        cursor: encode_cursor(cursor),
+    }))
+}
@@ -0,0 +1,190 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::Arc,
+};
+
+use anyhow::Result;
+use axum::{
+    extract::{MatchedPath, Path, Request, State},
+    http::header::AUTHORIZATION,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    RequestExt,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{config::ApiKeyConfig, http_objects::IndexifyAPIError};
+
+/// Namespaces an API key reaches.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiKeyScope {
+    All,
+    Namespaces(BTreeSet<String>),
+}
+
+impl ApiKeyScope {
+    pub fn allows(&self, namespace: &str) -> bool {
+        match self {
+            ApiKeyScope::All => true,
+            ApiKeyScope::Namespaces(namespaces) => namespaces.contains(namespace),
+        }
+    }
+}
+
+/// The configured API keys, held by their SHA-256 hash.
+#[derive(Debug, Default)]
+pub struct ApiKeys {
+    scopes: HashMap<Vec<u8>, ApiKeyScope>,
+}
+
+impl ApiKeys {
+    pub fn new(config: &[ApiKeyConfig]) -> Result<Self> {
+        let mut scopes = HashMap::new();
+        for api_key in config {
+            let scope = match &api_key.namespaces {
+                Some(namespaces) => ApiKeyScope::Namespaces(namespaces.iter().cloned().collect()),
+                None => ApiKeyScope::All,
+            };
+            scopes.insert(Sha256::digest(api_key.key()?).to_vec(), scope);
+        }
+        Ok(Self { scopes })
+    }
+
+    fn scope(&self, key: &str) -> Option<&ApiKeyScope> {
+        self.scopes.get(Sha256::digest(key).as_slice())
+    }
+}
+
+// Probes, and the static assets of the UI and of the API docs.
+fn is_public(path: &str) -> bool {
+    matches!(path, "/" | "/healthz" | "/readyz" | "/ui") ||
+        path.starts_with("/ui/") ||
+        path.starts_with("/docs/")
+}
+
+/// Authenticates requests when API keys are configured. A key limited to
+/// some namespaces gets the same answer for every other namespace, whether
+/// it exists or not, and the routes outside of namespaces look missing.
+/// Handlers of `/namespaces` find the scope of the key in the request's
+/// extensions.
+pub async fn authorize(
+    State(api_keys): State<Arc<ApiKeys>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if api_keys.scopes.is_empty() || is_public(request.uri().path()) {
+        return next.run(request).await;
+    }
+    let scope = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|key| api_keys.scope(key.trim()))
+        .cloned();
+    let Some(scope) = scope else {
+        return IndexifyAPIError::unauthorized("missing or unknown API key").into_response();
+    };
+    if let ApiKeyScope::Namespaces(_) = &scope {
+        let route = request
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|path| path.as_str().to_string())
+            .unwrap_or_default();
+        if route.starts_with("/namespaces/:namespace") {
+            let namespace = request
+                .extract_parts::<Path<HashMap<String, String>>>()
+                .await
+                .ok()
+                .and_then(|Path(mut params)| params.remove("namespace"));
+            if !namespace.is_some_and(|namespace| scope.allows(&namespace)) {
+                return IndexifyAPIError::namespace_not_found().into_response();
+            }
+        } else if route != "/namespaces" {
+            return IndexifyAPIError::not_found("not found").into_response();
+        }
+    }
+    request.extensions_mut().insert(scope);
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{http::StatusCode, middleware, routing::get, Extension, Router};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_authorize() -> Result<()> {
+        std::env::set_var("TEST_AUTHORIZE_ADMIN_KEY", "admin");
+        std::env::set_var("TEST_AUTHORIZE_TENANT_KEY", "tenant");
+        let api_keys = Arc::new(ApiKeys::new(&[
+            ApiKeyConfig {
+                key_env: Some("TEST_AUTHORIZE_ADMIN_KEY".to_string()),
+                ..Default::default()
+            },
+            ApiKeyConfig {
+                key_env: Some("TEST_AUTHORIZE_TENANT_KEY".to_string()),
+                namespaces: Some(vec!["a".to_string()]),
+                ..Default::default()
+            },
+        ])?);
+        let app = Router::new()
+            .route("/healthz", get(|| async { "ok" }))
+            .route(
+                "/namespaces",
+                get(|Extension(scope): Extension<ApiKeyScope>| async move {
+                    scope.allows("b").to_string()
+                }),
+            )
+            .route(
+                "/namespaces/:namespace/compute_graphs",
+                get(|Path(namespace): Path<String>| async move { namespace }),
+            )
+            .route("/executors", get(|| async { "executors" }))
+            .layer(middleware::from_fn_with_state(api_keys, authorize));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = reqwest::Client::new();
+        let get = |path: &str, key: Option<&str>| {
+            let mut request = client.get(format!("http://{}{}", addr, path));
+            if let Some(key) = key {
+                request = request.bearer_auth(key);
+            }
+            request.send()
+        };
+        assert_eq!(get("/healthz", None).await?.status(), StatusCode::OK);
+        for key in [None, Some("unknown")] {
+            let response = get("/namespaces", key).await?;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        let response = get("/namespaces", Some("admin")).await?;
+        assert_eq!(response.text().await?, "true");
+        let response = get("/namespaces/b/compute_graphs", Some("admin")).await?;
+        assert_eq!(response.text().await?, "b");
+        assert_eq!(
+            get("/executors", Some("admin")).await?.status(),
+            StatusCode::OK
+        );
+
+        let response = get("/namespaces", Some("tenant")).await?;
+        assert_eq!(response.text().await?, "false");
+        let response = get("/namespaces/a/compute_graphs", Some("tenant")).await?;
+        assert_eq!(response.text().await?, "a");
+        // Other namespaces are indistinguishable from missing ones.
+        let missing = get("/namespaces/c/compute_graphs", Some("tenant")).await?;
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+        let missing = missing.text().await?;
+        let response = get("/namespaces/b/compute_graphs", Some("tenant")).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.text().await?, missing);
+        assert_eq!(
+            get("/executors", Some("tenant")).await?.status(),
+            StatusCode::NOT_FOUND
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+use anyhow::anyhow;
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header::CONTENT_TYPE, HeaderMap, Response},
+    Json,
+};
+use data_model::{
+    artifacts::ArtifactRef,
+    validation::{validate_name, ValidationErrors},
+    ExecutorId,
+};
+use futures::StreamExt;
+use indexify_utils::get_epoch_time_in_ms;
+use state_store::requests::{
+    ArtifactError,
+    DeleteArtifactRequest,
+    RegisterArtifactRequest,
+    RequestPayload,
+    StateMachineUpdateRequest,
+    UpdateExecutorArtifactsRequest,
+};
+use uuid::Uuid;
+
+use super::RouteState;
+use crate::http_objects::{
+    encode_cursor,
+    Artifact,
+    Artifacts,
+    ExecutorArtifacts,
+    IndexifyAPIError,
+    ListParams,
+};
+
+/// Maps errors of writes inconsistent with the registered artifacts to the
+/// client errors they are.
+pub(super) fn artifact_error(e: anyhow::Error) -> IndexifyAPIError {
+    match e.downcast_ref::<ArtifactError>() {
+        Some(err @ ArtifactError::Missing { .. }) => {
+            IndexifyAPIError::bad_request(&err.to_string())
+        }
+        Some(err) => IndexifyAPIError::conflict(&err.to_string()),
+        None => IndexifyAPIError::internal_error(e),
+    }
+}
+
+/// Upload a version of an artifact, e.g. model weights, functions load
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/artifacts/{name}/versions/{version}",
+    tag = "operations",
+    request_body(content_type = "application/octet-stream", content = inline(String)),
+    responses(
+        (status = 200, description = "Artifact registered", body = Artifact),
+        (status = BAD_REQUEST, description = "Invalid artifact name or version"),
+        (status = NOT_FOUND, description = "Namespace not found"),
+        (status = CONFLICT, description = "The version is already registered with different content"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn register_artifact(
+    Path((namespace, name, version)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Json<Artifact>, IndexifyAPIError> {
+    let mut errors = ValidationErrors::default();
+    validate_name(&mut errors, "name", &name);
+    validate_name(&mut errors, "version", &version);
+    errors.into_result()?;
+    state
+        .indexify_state
+        .reader()
+        .get_namespace(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::namespace_not_found())?;
+    let stream = body
+        .into_data_stream()
+        .map(|res| res.map_err(|err| anyhow!(err)));
+    let put_result = state
+        .blob_storage
+        .for_namespace(&namespace)
+        .put(&Uuid::new_v4().to_string(), Box::pin(stream))
+        .await
+        .map_err(|e| {
+            IndexifyAPIError::internal_error(anyhow!("failed to upload artifact: {}", e))
+        })?;
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .or(put_result.content_type);
+    let artifact = data_model::artifacts::Artifact {
+        namespace: namespace.clone(),
+        name: name.clone(),
+        version: version.clone(),
+        payload: data_model::DataPayload {
+            path: put_result.url,
+            size: put_result.size_bytes,
+            sha256_hash: put_result.sha256_hash,
+            content_type,
+        },
+        created_at: get_epoch_time_in_ms(),
+    };
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::RegisterArtifact(RegisterArtifactRequest { artifact }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(artifact_error)?;
+    let artifact = state
+        .indexify_state
+        .reader()
+        .get_artifact(&namespace, &name, &version)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("artifact not found"))?;
+    Ok(Json(artifact.into()))
+}
+
+/// List the artifacts of a namespace
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/artifacts",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Every version of every artifact", body = Artifacts),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_artifacts(
+    Path(namespace): Path<String>,
+    Query(params): Query<ListParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<Artifacts>, IndexifyAPIError> {
+    let (artifacts, cursor) = state
+        .indexify_state
+        .reader()
+        .list_artifacts(&namespace, params.cursor()?.as_deref(), params.limit)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(Artifacts {
+        artifacts: artifacts.into_iter().map(Into::into).collect(),
+        cursor: encode_cursor(cursor),
+    }))
+}
+
+/// Get the metadata of a version of an artifact
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/artifacts/{name}/versions/{version}",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Metadata of the artifact", body = Artifact),
+        (status = NOT_FOUND, description = "Artifact not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn get_artifact(
+    Path((namespace, name, version)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<Artifact>, IndexifyAPIError> {
+    let artifact = state
+        .indexify_state
+        .reader()
+        .get_artifact(&namespace, &name, &version)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("artifact not found"))?;
+    Ok(Json(artifact.into()))
+}
+
+/// Download the content of a version of an artifact
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/artifacts/{name}/versions/{version}/content",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Content of the artifact"),
+        (status = NOT_FOUND, description = "Artifact not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn download_artifact(
+    Path((namespace, name, version)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Response<Body>, IndexifyAPIError> {
+    let artifact = state
+        .indexify_state
+        .reader()
+        .get_artifact(&namespace, &name, &version)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("artifact not found"))?;
+    let payload_stream = state
+        .blob_storage
+        .get(&artifact.payload.path)
+        .get()
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Response::builder()
+        .header(
+            "Content-Type",
+            artifact
+                .payload
+                .content_type
+                .as_deref()
+                .unwrap_or("application/octet-stream"),
+        )
+        .header("Content-Length", artifact.payload.size.to_string())
+        .body(Body::from_stream(payload_stream))
+        .map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()))
+}
+
+/// Delete a version of an artifact no function references anymore
+#[utoipa::path(
+    delete,
+    path = "/namespaces/{namespace}/artifacts/{name}/versions/{version}",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Artifact deleted"),
+        (status = CONFLICT, description = "Functions of a compute graph reference the artifact"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn delete_artifact(
+    Path((namespace, name, version)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::DeleteArtifact(DeleteArtifactRequest {
+                namespace,
+                artifact: ArtifactRef { name, version },
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(artifact_error)?;
+    Ok(())
+}
+
+/// Artifacts of the functions an executor can run which it hasn't fetched
+/// yet, polled by executors to prefetch them
+#[utoipa::path(
+    get,
+    path = "/internal/executors/{id}/artifacts",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Artifacts to prefetch", body = Artifacts),
+        (status = NOT_FOUND, description = "Executor not registered"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn prefetch_artifacts(
+    Path(executor_id): Path<ExecutorId>,
+    State(state): State<RouteState>,
+) -> Result<Json<Artifacts>, IndexifyAPIError> {
+    let artifacts = state
+        .executor_manager
+        .prefetch_artifacts(&executor_id)
+        .await
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("executor not registered"))?;
+    Ok(Json(Artifacts {
+        artifacts: artifacts.into_iter().map(Into::into).collect(),
+        cursor: None,
+    }))
+}
+
+/// Report the artifacts an executor has fetched, replacing the ones reported
+/// before
+#[utoipa::path(
+    put,
+    path = "/internal/executors/{id}/artifacts",
+    tag = "operations",
+    request_body = ExecutorArtifacts,
+    responses(
+        (status = 200, description = "Artifacts recorded"),
+        (status = NOT_FOUND, description = "Executor not registered"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn report_artifacts(
+    Path(executor_id): Path<ExecutorId>,
+    State(state): State<RouteState>,
+    Json(report): Json<ExecutorArtifacts>,
+) -> Result<(), IndexifyAPIError> {
+    let registered = state
+        .executor_manager
+        .list_executors()
+        .await
+        .map_err(IndexifyAPIError::internal_error)?
+        .iter()
+        .any(|executor| executor.id == executor_id);
+    if !registered {
+        return Err(IndexifyAPIError::not_found("executor not registered"));
+    }
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::UpdateExecutorArtifacts(UpdateExecutorArtifactsRequest {
+                executor_id,
+                artifacts: report.artifacts,
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(())
+}
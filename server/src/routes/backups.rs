@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use axum::{extract::State, Json};
+use state_store::IndexifyState;
+
+use super::RouteState;
+use crate::http_objects::{IndexifyAPIError, StateStoreBackup, StateStoreBackups};
+
+pub(super) fn backup_path(state: &RouteState) -> Result<PathBuf, IndexifyAPIError> {
+    state
+        .backup
+        .path
+        .as_ref()
+        .map(PathBuf::from)
+        .ok_or(IndexifyAPIError::bad_request(
+            "backups are disabled, set backup.path in the server configuration",
+        ))
+}
+
+/// Back the state store up while the server keeps running
+#[utoipa::path(
+    post,
+    path = "/internal/state_store/backups",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Backup taken and verified", body = StateStoreBackup),
+        (status = BAD_REQUEST, description = "Backups are disabled"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn create_backup(
+    State(state): State<RouteState>,
+) -> Result<Json<StateStoreBackup>, IndexifyAPIError> {
+    let path = backup_path(&state)?;
+    let max_backups = state.backup.max_backups;
+    let indexify_state = state.indexify_state.clone();
+    let backup = tokio::task::spawn_blocking(move || {
+        let backup = indexify_state.backup(&path)?;
+        IndexifyState::purge_backups(&path, max_backups)?;
+        Ok::<_, anyhow::Error>(backup)
+    })
+    .await
+    .map_err(|e| IndexifyAPIError::internal_error(anyhow!(e)))?
+    .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(backup.into()))
+}
+
+/// List the backups of the state store
+#[utoipa::path(
+    get,
+    path = "/internal/state_store/backups",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Backups, oldest first", body = StateStoreBackups),
+        (status = BAD_REQUEST, description = "Backups are disabled"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_backups(
+    State(state): State<RouteState>,
+) -> Result<Json<StateStoreBackups>, IndexifyAPIError> {
+    let path = backup_path(&state)?;
+    let backups = tokio::task::spawn_blocking(move || IndexifyState::list_backups(&path))
+        .await
+        .map_err(|e| IndexifyAPIError::internal_error(anyhow!(e)))?
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(StateStoreBackups {
+        backups: backups.into_iter().map(Into::into).collect(),
+    }))
+}
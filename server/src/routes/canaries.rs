@@ -0,0 +1,174 @@
+use axum::{
+    extract::{Multipart, Path, Query, State},
+    Json,
+};
+use state_store::requests::{
+    CanaryError,
+    FinishCanaryRequest,
+    RequestPayload,
+    StartCanaryRequest,
+    StateMachineUpdateRequest,
+};
+use tracing::info;
+
+use super::{artifact_error, read_compute_graph, RouteState};
+use crate::http_objects::{CanaryParams, GraphCanary, IndexifyAPIError};
+
+pub(super) fn canary_error(e: anyhow::Error) -> IndexifyAPIError {
+    match e.downcast_ref::<CanaryError>() {
+        Some(CanaryError::GraphNotFound(_)) => IndexifyAPIError::compute_graph_not_found(),
+        Some(err @ CanaryError::NotFound(_)) => IndexifyAPIError::not_found(&err.to_string()),
+        Some(err @ CanaryError::InProgress(_)) => IndexifyAPIError::conflict(&err.to_string()),
+        Some(err @ CanaryError::Unchanged(_)) => IndexifyAPIError::bad_request(&err.to_string()),
+        None => artifact_error(e),
+    }
+}
+
+/// Roll out a new version of a compute graph to a share of its new
+/// invocations, the others keep running the current version. Uploading the
+/// version being rolled out again changes its share of invocations
+#[utoipa::path(
+    put,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/canary",
+    tag = "operations",
+    request_body(content_type = "multipart/form-data", content = inline(super::ComputeGraphCreateType)),
+    params(
+        ("percent" = u32, Query, description = "Share of new invocations which run the new version, in percent"),
+    ),
+    responses(
+        (status = 200, description = "Canary rollout started or updated", body = GraphCanary),
+        (status = BAD_REQUEST, description = "Invalid compute graph or percent, or the graph is unchanged"),
+        (status = NOT_FOUND, description = "Compute graph not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn start_canary(
+    Path((namespace, name)): Path<(String, String)>,
+    State(state): State<RouteState>,
+    Query(params): Query<CanaryParams>,
+    compute_graph_code: Multipart,
+) -> Result<Json<GraphCanary>, IndexifyAPIError> {
+    let compute_graph = read_compute_graph(&state, &namespace, compute_graph_code).await?;
+    if compute_graph.name != name {
+        return Err(IndexifyAPIError::bad_request(&format!(
+            "compute graph is named {}, expected {}",
+            compute_graph.name, name
+        )));
+    }
+    let request = RequestPayload::StartCanary(StartCanaryRequest {
+        namespace: namespace.clone(),
+        compute_graph,
+        percent: params.percent,
+    });
+    request.validate()?;
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: request,
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(canary_error)?;
+    let canary = state
+        .indexify_state
+        .reader()
+        .get_canary(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("canary rollout not found"))?;
+    info!(
+        "canary rollout of compute graph {} version {} to {}% of invocations",
+        name, canary.graph.version, canary.percent
+    );
+    Ok(Json(canary.into()))
+}
+
+/// Get the canary rollout of a compute graph, comparing the outcomes and
+/// durations of the invocations of the new version with the stable one
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/canary",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Canary rollout", body = GraphCanary),
+        (status = NOT_FOUND, description = "Compute graph has no canary rollout in progress"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn get_canary(
+    Path((namespace, name)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<GraphCanary>, IndexifyAPIError> {
+    let canary = state
+        .indexify_state
+        .reader()
+        .get_canary(&namespace, &name)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found(
+            "compute graph has no canary rollout in progress",
+        ))?;
+    Ok(Json(canary.into()))
+}
+
+async fn finish_canary(
+    state: RouteState,
+    namespace: String,
+    compute_graph: String,
+    promote: bool,
+) -> Result<(), IndexifyAPIError> {
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::FinishCanary(FinishCanaryRequest {
+                namespace,
+                compute_graph: compute_graph.clone(),
+                promote,
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(canary_error)?;
+    info!(
+        "canary rollout of compute graph {} {}",
+        compute_graph,
+        if promote { "promoted" } else { "rolled back" }
+    );
+    Ok(())
+}
+
+/// Make the version being rolled out the stable version of the compute
+/// graph, all new invocations run it
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/canary/promote",
+    tag = "operations",
+    responses(
+        (status = 200, description = "New version promoted"),
+        (status = NOT_FOUND, description = "Compute graph has no canary rollout in progress"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn promote_canary(
+    Path((namespace, name)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    finish_canary(state, namespace, name, true).await
+}
+
+/// Drop the version being rolled out, all new invocations run the stable
+/// version and the running ones continue on it
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/canary/rollback",
+    tag = "operations",
+    responses(
+        (status = 200, description = "New version rolled back"),
+        (status = NOT_FOUND, description = "Compute graph has no canary rollout in progress"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn rollback_canary(
+    Path((namespace, name)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    finish_canary(state, namespace, name, false).await
+}
@@ -0,0 +1,72 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+
+use super::RouteState;
+use crate::http_objects::{DiagnosticsReport, ExecutorDiagnostics, IndexifyAPIError};
+
+/// Report the diagnostics of an executor, or of an executor agent by its
+/// agent id, replacing the ones reported before
+#[utoipa::path(
+    put,
+    path = "/internal/executors/{id}/diagnostics",
+    tag = "operations",
+    request_body = DiagnosticsReport,
+    responses(
+        (status = 200, description = "Diagnostics recorded"),
+        (status = BAD_REQUEST, description = "Executor not connected or diagnostics too large"),
+    ),
+)]
+pub async fn report_diagnostics(
+    Path(id): Path<String>,
+    State(state): State<RouteState>,
+    Json(report): Json<DiagnosticsReport>,
+) -> Result<(), IndexifyAPIError> {
+    state
+        .executor_manager
+        .report_diagnostics(&id, report.sections)
+        .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))
+}
+
+/// Get the diagnostics an executor or executor agent reported last
+#[utoipa::path(
+    get,
+    path = "/internal/executors/{id}/diagnostics",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Latest diagnostics of the executor", body = ExecutorDiagnostics),
+        (status = NOT_FOUND, description = "No diagnostics reported"),
+    ),
+)]
+pub async fn get_diagnostics(
+    Path(id): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<ExecutorDiagnostics>, IndexifyAPIError> {
+    let diagnostics = state
+        .executor_manager
+        .diagnostics(&id)
+        .ok_or(IndexifyAPIError::not_found("no diagnostics reported"))?;
+    Ok(Json(diagnostics.into()))
+}
+
+/// Ask a connected executor or executor agent to report its diagnostics
+/// again, it's sent a `diagnostics` event over its task stream
+#[utoipa::path(
+    post,
+    path = "/internal/executors/{id}/diagnostics/request",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Diagnostics requested"),
+        (status = NOT_FOUND, description = "Executor not connected"),
+    ),
+)]
+pub async fn request_diagnostics(
+    Path(id): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    if !state.executor_manager.request_diagnostics(&id) {
+        return Err(IndexifyAPIError::not_found("executor not connected"));
+    }
+    Ok(())
+}
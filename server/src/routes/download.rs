@@ -1,12 +1,20 @@
 use anyhow::anyhow;
 use axum::{
     body::Body,
-    extract::{Path, State},
+    extract::{Path, Query, State},
     response::Response,
 };
+use serde::Deserialize;
 
 use super::RouteState;
-use crate::http_objects::IndexifyAPIError;
+use crate::{edge_transforms::transform_output, http_objects::IndexifyAPIError};
+
+#[derive(Debug, Deserialize)]
+pub struct FnOutputParams {
+    /// Function reading the output, applies the transform of the edge from
+    /// the output's function to it.
+    pub compute_fn: Option<String>,
+}
 
 pub async fn download_invocation_payload(
     Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
@@ -86,6 +94,7 @@ pub async fn download_fn_output_payload(
 
 pub async fn download_fn_output_by_key(
     Path(output_key): Path<String>,
+    Query(params): Query<FnOutputParams>,
     State(state): State<RouteState>,
 ) -> Result<Response<Body>, IndexifyAPIError> {
     let output = state
@@ -107,6 +116,34 @@ pub async fn download_fn_output_by_key(
             )))
         }
     };
+    if let Some(compute_fn) = &params.compute_fn {
+        let graph = state
+            .indexify_state
+            .reader()
+            .get_compute_graph_version(
+                &output.namespace,
+                &output.compute_graph_name,
+                output.graph_version,
+            )
+            .map_err(IndexifyAPIError::internal_error)?;
+        let transform = graph
+            .as_ref()
+            .and_then(|graph| graph.edge_transform(&output.compute_fn_name, compute_fn));
+        if let Some(transform) = transform {
+            let transformed = transform_output(
+                state.blob_storage.for_namespace(&output.namespace),
+                transform,
+                &payload,
+            )
+            .await
+            .map_err(IndexifyAPIError::internal_error)?;
+            return Response::builder()
+                .header("Content-Type", "application/json")
+                .header("Content-Length", transformed.len().to_string())
+                .body(Body::from(transformed))
+                .map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()));
+        }
+    }
     let storage_reader = state.blob_storage.get(&payload.path);
     let payload_stream = storage_reader
         .get()
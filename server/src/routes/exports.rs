@@ -0,0 +1,118 @@
+use std::io::{self, Write};
+
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    response::Response,
+};
+use bytes::Bytes;
+use state_store::IndexifyState;
+use tokio::sync::mpsc;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use super::{backups::backup_path, RouteState};
+use crate::http_objects::{ExportParams, IndexifyAPIError};
+
+// Bytes of the export sent to the client at a time.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Sends what's written to it in chunks to the body of a response.
+struct ChunkWriter {
+    tx: mpsc::Sender<io::Result<Bytes>>,
+    chunk: Vec<u8>,
+}
+
+impl ChunkWriter {
+    fn send(&mut self) -> io::Result<()> {
+        let chunk = Bytes::from(std::mem::take(&mut self.chunk));
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "export request cancelled"))
+    }
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.chunk.extend_from_slice(buf);
+        if self.chunk.len() >= CHUNK_SIZE {
+            self.send()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.chunk.is_empty() {
+            self.send()?;
+        }
+        Ok(())
+    }
+}
+
+/// Export the state store as JSON lines, which can be imported by any
+/// version of the server with --import-from
+#[utoipa::path(
+    get,
+    path = "/internal/state_store/export",
+    tag = "operations",
+    params(
+        ("as_of" = Option<i64>, Query, description = "Seconds since the epoch, exports the latest backup taken at or before then instead of the current state"),
+    ),
+    responses(
+        (status = 200, description = "Export of the state store", content_type = "application/x-ndjson"),
+        (status = BAD_REQUEST, description = "Backups are disabled, or none was taken before as_of"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn export_state_store(
+    State(state): State<RouteState>,
+    Query(params): Query<ExportParams>,
+) -> Result<Response<Body>, IndexifyAPIError> {
+    let (indexify_state, scratch_path) = match params.as_of {
+        Some(as_of) => {
+            let backup_path = backup_path(&state)?;
+            let scratch_path = backup_path.join("scratch").join(Uuid::new_v4().to_string());
+            let (backup, indexify_state) =
+                IndexifyState::open_backup_as_of(&backup_path, &scratch_path, as_of)
+                    .await
+                    .map_err(|e| {
+                        let _ = std::fs::remove_dir_all(&scratch_path);
+                        IndexifyAPIError::bad_request(&e.to_string())
+                    })?;
+            info!("exporting backup {} as of {}", backup.backup_id, as_of);
+            (indexify_state, Some(scratch_path))
+        }
+        None => (state.indexify_state.clone(), None),
+    };
+    let (tx, mut rx) = mpsc::channel(4);
+    tokio::task::spawn_blocking(move || {
+        let mut writer = ChunkWriter {
+            tx: tx.clone(),
+            chunk: Vec::with_capacity(CHUNK_SIZE),
+        };
+        if let Err(e) = indexify_state.export(&mut writer) {
+            error!("failed to export the state store: {:?}", e);
+            // Ends the response early, the export then has no end line and
+            // isn't imported.
+            let _ = tx.blocking_send(Err(io::Error::new(io::ErrorKind::Other, e.to_string())));
+        }
+        if let Some(scratch_path) = scratch_path {
+            // The restored backup is closed once the last reference to it is
+            // dropped.
+            drop(indexify_state);
+            if let Err(e) = std::fs::remove_dir_all(&scratch_path) {
+                error!("failed to remove {}: {:?}", scratch_path.display(), e);
+            }
+        }
+    });
+    let export_stream = async_stream::stream! {
+        while let Some(chunk) = rx.recv().await {
+            yield chunk;
+        }
+    };
+
+    Response::builder()
+        .header("Content-Type", "application/x-ndjson")
+        .body(Body::from_stream(export_stream))
+        .map_err(|e| IndexifyAPIError::internal_error_str(&e.to_string()))
+}
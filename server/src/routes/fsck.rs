@@ -0,0 +1,33 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+
+use super::RouteState;
+use crate::http_objects::{FsckParams, FsckReport, IndexifyAPIError};
+
+/// Check the records and indexes of the state store, optionally repairing
+/// them
+#[utoipa::path(
+    post,
+    path = "/internal/state_store/fsck",
+    tag = "operations",
+    params(
+        ("repair" = Option<bool>, Query, description = "Quarantine unreadable records and remove dangling index entries and orphans, unless records don't decrypt with the configured keys"),
+    ),
+    responses(
+        (status = 200, description = "Problems found in the state store", body = FsckReport),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn fsck_state_store(
+    Query(params): Query<FsckParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<FsckReport>, IndexifyAPIError> {
+    let report = state
+        .indexify_state
+        .fsck(params.repair)
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(report.into()))
+}
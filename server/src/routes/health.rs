@@ -0,0 +1,199 @@
+use std::{future::Future, time::Duration};
+
+use anyhow::{anyhow, Result};
+use axum::{extract::State, http::StatusCode, Json};
+use futures::future::join_all;
+use tracing::warn;
+
+use super::RouteState;
+use crate::http_objects::{DependencyHealth, HealthStatus};
+
+// Probes are expected to answer within a few seconds, a storage which
+// doesn't answer in time counts as unreachable.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+async fn check(
+    name: &str,
+    critical: bool,
+    check: impl Future<Output = Result<()>>,
+) -> DependencyHealth {
+    let result = match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result,
+        Err(_) => Err(anyhow!("no answer within {:?}", CHECK_TIMEOUT)),
+    };
+    DependencyHealth {
+        name: name.to_string(),
+        healthy: result.is_ok(),
+        error: result.err().map(|e| e.to_string()),
+        critical,
+    }
+}
+
+/// Liveness probe, succeeds as long as the server is serving requests
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Server is alive", body = HealthStatus),
+    ),
+)]
+pub async fn healthz() -> Json<HealthStatus> {
+    Json(HealthStatus {
+        healthy: true,
+        dependencies: vec![],
+    })
+}
+
+/// Readiness probe, checks the state store and the default blob storage. The
+/// blob storages of namespaces are reported, but only fail the requests of
+/// their namespace, so they don't take the server out of rotation.
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Server is ready to serve traffic", body = HealthStatus),
+        (status = SERVICE_UNAVAILABLE, description = "A critical dependency is unhealthy", body = HealthStatus),
+    ),
+)]
+pub async fn readyz(State(state): State<RouteState>) -> (StatusCode, Json<HealthStatus>) {
+    let default_storage = state.blob_storage.default_storage();
+    let storages = state.blob_storage.namespace_storages();
+    let (state_store, blob_storage, namespace_results) = tokio::join!(
+        check("state_store", true, async {
+            state.indexify_state.reader().check_readable()
+        }),
+        check("blob_storage", true, default_storage.check_reachable()),
+        join_all(storages.iter().map(|(namespace, storage)| async move {
+            let result = check(namespace, false, storage.check_reachable()).await;
+            if let Some(error) = &result.error {
+                warn!(
+                    "blob storage of namespace {} is unhealthy: {}",
+                    namespace, error
+                );
+            }
+            result
+        })),
+    );
+    // The probe is public, so the storages of namespaces are reported
+    // together without naming them.
+    let unreachable = namespace_results.iter().filter(|d| !d.healthy).count();
+    let dependencies = vec![
+        state_store,
+        blob_storage,
+        DependencyHealth {
+            name: "blob_storage.namespaces".to_string(),
+            healthy: unreachable == 0,
+            error: (unreachable > 0).then(|| {
+                format!(
+                    "{} of {} namespace blob storages are unreachable",
+                    unreachable,
+                    storages.len()
+                )
+            }),
+            critical: false,
+        },
+    ];
+    let healthy = dependencies.iter().all(|d| d.healthy || !d.critical);
+    let status_code = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status_code,
+        Json(HealthStatus {
+            healthy,
+            dependencies,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use blob_store::{registry::BlobStorageRegistry, BlobStorage, BlobStorageConfig};
+    use prometheus_client::registry::Registry;
+    use state_store::IndexifyState;
+
+    use super::*;
+    use crate::{
+        config::ApiKeyConfig,
+        executors::ExecutorManager,
+        fetcher::Fetcher,
+        invocation_admission::InvocationAdmission,
+        routes::{api_keys::ApiKeys, create_routes, request_metrics::RequestTracking},
+    };
+
+    #[tokio::test]
+    async fn test_readyz_without_api_key() -> anyhow::Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let indexify_state = IndexifyState::in_memory().await?;
+        let blob_storage = Arc::new(BlobStorageRegistry::new(Arc::new(BlobStorage::new(
+            BlobStorageConfig::new_disk(temp_dir.path().join("blobs").to_str().unwrap()),
+        )?)));
+        let tenant_path = temp_dir.path().join("tenant");
+        blob_storage.register(
+            "secret-tenant",
+            BlobStorageConfig::new_disk(tenant_path.to_str().unwrap()),
+        )?;
+        // The storage of the namespace breaks once it's registered.
+        std::fs::remove_dir(&tenant_path)?;
+        std::fs::write(&tenant_path, "")?;
+        std::env::set_var("TEST_READYZ_API_KEY", "admin");
+        let route_state = RouteState {
+            indexify_state: indexify_state.clone(),
+            blob_storage: blob_storage.clone(),
+            executor_manager: Arc::new(ExecutorManager::new(indexify_state.clone()).await),
+            fetcher: Arc::new(Fetcher::new(
+                indexify_state.clone(),
+                blob_storage,
+                Default::default(),
+            )),
+            invocation_admission: Arc::new(InvocationAdmission::new(
+                Default::default(),
+                indexify_state,
+            )),
+            metrics_registry: Arc::new(Registry::default()),
+            request_tracking: Arc::new(RequestTracking::new(Default::default())),
+            api_keys: Arc::new(ApiKeys::new(&[ApiKeyConfig {
+                key_env: Some("TEST_READYZ_API_KEY".to_string()),
+                ..Default::default()
+            }])?),
+            backup: Default::default(),
+            test_invocations: Default::default(),
+            webhooks: Default::default(),
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let app = create_routes(route_state);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let response = reqwest::get(format!("http://{}/readyz", addr)).await?;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.text().await?;
+        assert!(!body.contains("secret-tenant"), "{}", body);
+        let health: HealthStatus = serde_json::from_str(&body)?;
+        let names: Vec<_> = health
+            .dependencies
+            .iter()
+            .map(|d| d.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["state_store", "blob_storage", "blob_storage.namespaces"]
+        );
+        // An unreachable namespace storage doesn't take the server out of
+        // rotation.
+        assert!(health.healthy);
+        let namespaces = &health.dependencies[2];
+        assert!(!namespaces.healthy && !namespaces.critical);
+        assert_eq!(
+            namespaces.error.as_deref(),
+            Some("1 of 1 namespace blob storages are unreachable")
+        );
+        Ok(())
+    }
+}
@@ -1,14 +1,21 @@
 use std::{collections::HashMap, sync::Arc, vec};
 
 use anyhow::{anyhow, Result};
-use axum::extract::{multipart::Field, Multipart, State};
+use axum::{
+    extract::{multipart::Field, Multipart, State},
+    http::HeaderMap,
+    Json,
+};
 use blob_store::{BlobStorage, PutResult};
 use data_model::{
+    output_checks::OutputCheckAction,
     DataPayload,
     ExecutorId,
+    Node,
     NodeOutput,
     NodeOutputBuilder,
     OutputPayload,
+    Task,
     TaskDiagnostics,
     TaskId,
 };
@@ -19,7 +26,16 @@ use tracing::{error, info};
 use utoipa::ToSchema;
 
 use super::RouteState;
-use crate::http_objects::IndexifyAPIError;
+use crate::{
+    http_objects::{
+        request_id_from_headers,
+        IndexifyAPIError,
+        ResourceUsage,
+        TaskFailure,
+        TraceContext,
+    },
+    output_checks::check_outputs,
+};
 
 #[derive(Serialize, Deserialize)]
 pub enum TaskOutput {
@@ -57,6 +73,49 @@ pub struct TaskResult {
     invocation_id: String,
     executor_id: String,
     reducer: bool,
+    #[serde(default)]
+    failure: Option<TaskFailure>,
+    #[serde(default)]
+    resource_usage: Option<ResourceUsage>,
+    /// Trace the executor received with the task, echoed back.
+    #[serde(default)]
+    trace: Option<TraceContext>,
+}
+
+/// Acknowledges a task result once it's committed. Executors keep the
+/// outputs of a task until they get it, and report them again otherwise.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TaskResultAck {
+    pub task_id: String,
+    /// Sequence number assigned to the result when it was committed, reports
+    /// of an acknowledged result get the same one.
+    pub result_sequence: u64,
+}
+
+impl TaskResultAck {
+    fn for_task(task: &Task) -> Option<Self> {
+        task.result_sequence.map(|result_sequence| TaskResultAck {
+            task_id: task.id.to_string(),
+            result_sequence,
+        })
+    }
+}
+
+fn get_task(
+    state: &RouteState,
+    task_result: &TaskResult,
+) -> Result<Option<Task>, IndexifyAPIError> {
+    state
+        .indexify_state
+        .reader()
+        .get_task(
+            &task_result.namespace,
+            &task_result.compute_graph,
+            &task_result.invocation_id,
+            &task_result.compute_fn,
+            &task_result.task_id,
+        )
+        .map_err(IndexifyAPIError::internal_error)
 }
 
 #[derive(Serialize, Deserialize)]
@@ -92,8 +151,10 @@ pub struct InvokeWithFile {
 )]
 pub async fn ingest_files_from_executor(
     State(state): State<RouteState>,
+    headers: HeaderMap,
     mut files: Multipart,
-) -> Result<(), IndexifyAPIError> {
+) -> Result<Json<TaskResultAck>, IndexifyAPIError> {
+    let request_id = request_id_from_headers(&headers)?;
     let mut output_objects: Vec<PutResult> = vec![];
     let mut exception_msg: Option<PutResult> = None;
     let mut stdout_msg: Option<PutResult> = None;
@@ -125,7 +186,12 @@ pub async fn ingest_files_from_executor(
                         task_result.task_id, node_output_sequence
                     ));
                 };
-                let res = write_to_disk(state.clone().blob_storage, &mut field, &file_name).await?;
+                let res = write_to_disk(
+                    state.blob_storage.for_namespace(&task_result.namespace),
+                    &mut field,
+                    &file_name,
+                )
+                .await?;
                 node_output_sequence += 1;
                 output_objects.push(res.clone());
             } else if diagnostics_keys.iter().any(|e| name_ref.contains(e)) {
@@ -140,7 +206,12 @@ pub async fn ingest_files_from_executor(
                     task_result.invocation_id,
                     name,
                 );
-                let res = write_to_disk(state.clone().blob_storage, &mut field, &file_name).await?;
+                let res = write_to_disk(
+                    state.blob_storage.for_namespace(&task_result.namespace),
+                    &mut field,
+                    &file_name,
+                )
+                .await?;
                 match name_ref.as_str() {
                     "exception_msg" => exception_msg = Some(res),
                     "stdout" => stdout_msg = Some(res),
@@ -162,6 +233,26 @@ pub async fn ingest_files_from_executor(
     // Save metadata in rocksdb for the objects in the blob store.
     let task_result =
         task_result.ok_or(IndexifyAPIError::bad_request("task_result is required"))?;
+    let trace = task_result.trace.clone().unwrap_or_default();
+    info!(
+        trace_id = trace.trace_id,
+        correlation_id = trace.correlation_id,
+        "task {} of invocation {} reported {:?}",
+        task_result.task_id,
+        task_result.invocation_id,
+        task_result.outcome
+    );
+    // The executor didn't get the acknowledgement of a result it reported.
+    if let Some(ack) = get_task(&state, &task_result)?
+        .as_ref()
+        .and_then(TaskResultAck::for_task)
+    {
+        info!(
+            "result of task {} was already committed with sequence {}",
+            ack.task_id, ack.result_sequence
+        );
+        return Ok(Json(ack));
+    }
     let mut node_outputs: Vec<NodeOutput> = vec![];
 
     for put_result in output_objects {
@@ -169,6 +260,7 @@ pub async fn ingest_files_from_executor(
             path: put_result.url,
             size: put_result.size_bytes,
             sha256_hash: put_result.sha256_hash,
+            content_type: put_result.content_type,
         };
         let node_output = NodeOutputBuilder::default()
             .namespace(task_result.namespace.to_string())
@@ -184,6 +276,49 @@ pub async fn ingest_files_from_executor(
         node_outputs.push(node_output);
     }
 
+    let mut task_outcome: data_model::TaskOutcome = task_result.outcome.clone().into();
+    let mut failure: Option<data_model::TaskFailure> = task_result.failure.clone().map(Into::into);
+    let compute_graph = state
+        .indexify_state
+        .reader()
+        .get_compute_graph_for_invocation(
+            &task_result.namespace,
+            &task_result.compute_graph,
+            &task_result.invocation_id,
+        )
+        .map_err(IndexifyAPIError::internal_error)?;
+    let compute_fn =
+        compute_graph.and_then(|graph| match graph.nodes.get(&task_result.compute_fn) {
+            Some(Node::Compute(compute_fn)) if !compute_fn.output_checks.is_empty() => {
+                Some(compute_fn.clone())
+            }
+            _ => None,
+        });
+    let mut output_checks = None;
+    if let Some(compute_fn) = compute_fn {
+        let (kept, report) = check_outputs(
+            state.blob_storage.for_namespace(&task_result.namespace),
+            &compute_fn,
+            node_outputs,
+        )
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+        node_outputs = kept;
+        if report
+            .violations
+            .iter()
+            .any(|v| v.action == OutputCheckAction::FailTask)
+        {
+            task_outcome = data_model::TaskOutcome::Failure;
+            failure = Some(data_model::TaskFailure {
+                error_class: data_model::TaskErrorClass::UserCode,
+                retryable: false,
+                exit_code: None,
+            });
+        }
+        output_checks = Some(report);
+    }
+
     let exception_payload = prepare_data_payload(exception_msg);
     let stdout_payload = prepare_data_payload(stdout_msg);
     let stderr_payload = prepare_data_payload(stderr_msg);
@@ -192,9 +327,15 @@ pub async fn ingest_files_from_executor(
         exception: exception_payload,
         stdout: stdout_payload,
         stderr: stderr_payload,
+        failure,
+        resource_usage: task_result.resource_usage.clone().map(Into::into),
+        output_check_violations: output_checks
+            .as_ref()
+            .map(|report| report.violations.clone())
+            .unwrap_or_default(),
     };
 
-    if let Some(router_output) = task_result.router_output {
+    if let Some(router_output) = &task_result.router_output {
         let node_output = NodeOutputBuilder::default()
             .namespace(task_result.namespace.to_string())
             .graph_version(Default::default())
@@ -211,6 +352,7 @@ pub async fn ingest_files_from_executor(
         node_outputs.push(node_output);
     }
 
+    let executor_id = ExecutorId::new(task_result.executor_id.clone());
     let request = RequestPayload::FinalizeTask(FinalizeTaskRequest {
         namespace: task_result.namespace.to_string(),
         compute_graph: task_result.compute_graph.to_string(),
@@ -218,22 +360,38 @@ pub async fn ingest_files_from_executor(
         invocation_id: task_result.invocation_id.to_string(),
         task_id: TaskId::new(task_result.task_id.to_string()),
         node_outputs,
-        task_outcome: task_result.outcome.clone().into(),
-        executor_id: ExecutorId::new(task_result.executor_id.clone()),
+        task_outcome: task_outcome.clone(),
+        executor_id: executor_id.clone(),
         diagnostics: Some(task_diagnostic),
+        output_checks,
     });
 
-    state
-        .indexify_state
-        .write(StateMachineUpdateRequest {
-            payload: request,
-            state_changes_processed: vec![],
-        })
-        .await
-        .map_err(|e| {
-            IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
+    let request = StateMachineUpdateRequest {
+        payload: request,
+        state_changes_processed: vec![],
+    };
+    // Executors retry reports they didn't get an acknowledgement for, a
+    // report applied before is acknowledged again without applying it.
+    let applied = match &request_id {
+        Some(request_id) => state.indexify_state.write_once(request_id, request).await,
+        None => state.indexify_state.write(request).await.map(|_| true),
+    }
+    .map_err(|e| IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e)))?;
+    if applied {
+        state
+            .executor_manager
+            .record_task_outcome(&executor_id, &task_outcome);
+    }
+    let ack = get_task(&state, &task_result)?
+        .as_ref()
+        .and_then(TaskResultAck::for_task)
+        .ok_or_else(|| {
+            IndexifyAPIError::internal_error(anyhow!(
+                "result of task {} wasn't acknowledged",
+                task_result.task_id
+            ))
         })?;
-    Ok(())
+    Ok(Json(ack))
 }
 
 async fn write_to_disk<'a>(
@@ -258,5 +416,6 @@ fn prepare_data_payload(msg: Option<PutResult>) -> Option<DataPayload> {
         path: msg.url,
         size: msg.size_bytes,
         sha256_hash: msg.sha256_hash,
+        content_type: None,
     })
 }
@@ -4,15 +4,19 @@ use anyhow::anyhow;
 use axum::{
     body::Body,
     extract::{Multipart, Path, Query, State},
+    http::{header::CONTENT_TYPE, HeaderMap},
     response::{sse::Event, IntoResponse},
     Json,
 };
-use blob_store::PutResult;
-use data_model::InvocationPayloadBuilder;
+use blob_store::{content_type, PutResult};
+use data_model::{id_generator::new_id, InvocationPayloadBuilder};
 use futures::{stream, StreamExt};
+use indexify_utils::get_epoch_time_in_ms;
+use serde::Deserialize;
 use state_store::{
     invocation_events::{InvocationFinishedEvent, InvocationStateChangeEvent},
     requests::{
+        ContentVersionError,
         InvokeComputeGraphRequest,
         RequestPayload,
         RerunComputeGraphRequest,
@@ -25,7 +29,49 @@ use utoipa::ToSchema;
 use uuid::Uuid;
 
 use super::RouteState;
-use crate::http_objects::{GraphInputFile, IndexifyAPIError, InvocationId, InvocationQueryParams};
+use crate::{
+    fetcher::FetchRequest,
+    gc,
+    http_objects::{
+        tags_from_query,
+        trace_from_headers,
+        GraphInputFile,
+        IndexifyAPIError,
+        InvocationId,
+        InvocationQueryParams,
+        UrlFetch,
+    },
+    near_duplicates,
+};
+
+/// Id of the current invocation of an unchanged source or of a skipped
+/// near-duplicate, the error of other failures to invoke a graph. Nothing
+/// references the blobs uploaded for the invocation which wasn't created,
+/// they're garbage collected.
+async fn current_invocation(
+    state: &RouteState,
+    uploads: Vec<String>,
+    e: anyhow::Error,
+) -> Result<String, IndexifyAPIError> {
+    gc::collect_unreferenced(&state.indexify_state, uploads).await;
+    match e.downcast_ref::<ContentVersionError>() {
+        Some(
+            ContentVersionError::Unchanged {
+                current_invocation, ..
+            } |
+            ContentVersionError::NearDuplicate {
+                current_invocation, ..
+            },
+        ) => Ok(current_invocation.clone()),
+        Some(err @ ContentVersionError::Stale { .. }) => {
+            Err(IndexifyAPIError::conflict(&err.to_string()))
+        }
+        None => Err(IndexifyAPIError::internal_error(anyhow!(
+            "failed to upload content: {}",
+            e
+        ))),
+    }
+}
 
 #[allow(dead_code)]
 #[derive(ToSchema)]
@@ -47,30 +93,44 @@ pub struct InvokeWithFile {
     responses(
         (status = 200, description = "upload successful"),
         (status = 400, description = "bad request"),
+        (status = SERVICE_UNAVAILABLE, description = "server is overloaded, retry after the Retry-After delay"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
     ),
 )]
 pub async fn invoke_with_file(
     Path((namespace, compute_graph)): Path<(String, String)>,
     State(state): State<RouteState>,
-    Query(_params): Query<InvocationQueryParams>,
+    Query(params): Query<InvocationQueryParams>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
     mut files: Multipart,
 ) -> Result<Json<InvocationId>, IndexifyAPIError> {
+    state.invocation_admission.admit().await?;
+    let tags = tags_from_query(&query)?;
+    let trace = trace_from_headers(&headers)?;
     let mut metadata: Option<serde_json::Value> = None;
     let mut put_result: Option<PutResult> = None;
+    let mut file_name: Option<String> = None;
+    let mut mime_type: Option<String> = None;
 
     while let Some(field) = files.next_field().await.unwrap() {
         if let Some(name) = field.name() {
             if name == "file" {
+                file_name = field.file_name().map(str::to_string);
                 let name = Uuid::new_v4().to_string();
                 info!("writing to blob store, file name = {:?}", name);
                 let stream = field.map(|res| res.map_err(|err| anyhow::anyhow!(err)));
-                let res = state.blob_storage.put(&name, stream).await.map_err(|e| {
-                    IndexifyAPIError::internal_error(anyhow!(
-                        "failed to write to blob store: {}",
-                        e
-                    ))
-                })?;
+                let res = state
+                    .blob_storage
+                    .for_namespace(&namespace)
+                    .put(&name, stream)
+                    .await
+                    .map_err(|e| {
+                        IndexifyAPIError::internal_error(anyhow!(
+                            "failed to write to blob store: {}",
+                            e
+                        ))
+                    })?;
                 put_result = Some(res);
             } else if name == "metadata" {
                 let text = field
@@ -79,6 +139,12 @@ pub async fn invoke_with_file(
                     .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
                 let file_metadata = serde_json::from_str(&text)?;
                 metadata = Some(file_metadata);
+            } else if name == "mime_type" {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+                mime_type = Some(text);
             }
         }
     }
@@ -86,11 +152,26 @@ pub async fn invoke_with_file(
         return Err(IndexifyAPIError::bad_request("file is required"));
     }
     let put_result = put_result.unwrap();
+    let source = params.content_source(&put_result.sha256_hash);
+    // A mime type set by the caller wins over the detected one.
+    let content_type = mime_type
+        .or_else(|| content_type::detect(put_result.content_type.as_deref(), file_name.as_deref()));
+    let fingerprint = near_duplicates::fingerprint(
+        &state.indexify_state,
+        &state.blob_storage,
+        &namespace,
+        &put_result,
+        content_type.as_deref(),
+    )
+    .await
+    .map_err(IndexifyAPIError::internal_error)?;
+    let mut uploads = vec![put_result.url.clone()];
     let payload = GraphInputFile {
         metadata: metadata.unwrap_or_default(),
         url: put_result.url.clone(),
         sha_256: put_result.sha256_hash.clone(),
         size: put_result.size_bytes,
+        content_type: content_type.clone(),
     };
     let payload_key = Uuid::new_v4().to_string();
     let payload_stream = stream::once(async move {
@@ -99,20 +180,27 @@ pub async fn invoke_with_file(
     });
     let put_result = state
         .blob_storage
+        .for_namespace(&namespace)
         .put(&payload_key, Box::pin(payload_stream))
         .await
         .map_err(|e| {
             IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
         })?;
+    uploads.push(put_result.url.clone());
     let data_payload = data_model::DataPayload {
         path: put_result.url,
         size: put_result.size_bytes,
         sha256_hash: put_result.sha256_hash,
+        content_type,
     };
     let invocation_payload = InvocationPayloadBuilder::default()
         .namespace(namespace.clone())
         .compute_graph_name(compute_graph.clone())
         .payload(data_payload)
+        .tags(tags)
+        .trace(trace)
+        .source(source)
+        .fingerprint(fingerprint)
         .build()
         .map_err(|e| {
             IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
@@ -124,16 +212,18 @@ pub async fn invoke_with_file(
         compute_graph_name: compute_graph.clone(),
         invocation_payload,
     });
-    state
+    request.validate()?;
+    let result = state
         .indexify_state
         .write(StateMachineUpdateRequest {
             payload: request,
             state_changes_processed: vec![],
         })
-        .await
-        .map_err(|e| {
-            IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
-        })?;
+        .await;
+    let id = match result {
+        Ok(()) => id,
+        Err(e) => current_invocation(&state, uploads, e).await?,
+    };
     Ok(Json(InvocationId { id }))
 }
 
@@ -146,15 +236,21 @@ pub async fn invoke_with_file(
     responses(
         (status = 200, description = "invocation successful"),
         (status = 400, description = "bad request"),
+        (status = SERVICE_UNAVAILABLE, description = "server is overloaded, retry after the Retry-After delay"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
     ),
 )]
 pub async fn invoke_with_object(
     Path((namespace, compute_graph)): Path<(String, String)>,
     Query(params): Query<InvocationQueryParams>,
+    Query(query): Query<HashMap<String, String>>,
     State(state): State<RouteState>,
+    headers: HeaderMap,
     body: Body,
 ) -> Result<impl IntoResponse, IndexifyAPIError> {
+    state.invocation_admission.admit().await?;
+    let tags = tags_from_query(&query)?;
+    let trace = trace_from_headers(&headers)?;
     let should_block = params.block_until_finish.unwrap_or(false);
     let payload_key = Uuid::new_v4().to_string();
     let payload_stream = body
@@ -162,20 +258,45 @@ pub async fn invoke_with_object(
         .map(|res| res.map_err(|err| anyhow::anyhow!(err)));
     let put_result = state
         .blob_storage
+        .for_namespace(&namespace)
         .put(&payload_key, Box::pin(payload_stream))
         .await
         .map_err(|e| {
             IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
         })?;
+    // Objects are usually serialized by the SDK, the declared content type
+    // says how when nothing more specific is detected.
+    let content_type = put_result.content_type.clone().or_else(|| {
+        headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    });
+    let source = params.content_source(&put_result.sha256_hash);
+    let fingerprint = near_duplicates::fingerprint(
+        &state.indexify_state,
+        &state.blob_storage,
+        &namespace,
+        &put_result,
+        content_type.as_deref(),
+    )
+    .await
+    .map_err(IndexifyAPIError::internal_error)?;
+    let uploads = vec![put_result.url.clone()];
     let data_payload = data_model::DataPayload {
         path: put_result.url,
         size: put_result.size_bytes,
         sha256_hash: put_result.sha256_hash,
+        content_type,
     };
     let invocation_payload = InvocationPayloadBuilder::default()
         .namespace(namespace.clone())
         .compute_graph_name(compute_graph.clone())
         .payload(data_payload)
+        .tags(tags)
+        .trace(trace)
+        .source(source)
+        .fingerprint(fingerprint)
         .build()
         .map_err(|e| {
             IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
@@ -190,16 +311,20 @@ pub async fn invoke_with_object(
         compute_graph_name: compute_graph.clone(),
         invocation_payload,
     });
-    state
+    request.validate()?;
+    let result = state
         .indexify_state
         .write(StateMachineUpdateRequest {
             payload: request,
             state_changes_processed: vec![],
         })
-        .await
-        .map_err(|e| {
-            IndexifyAPIError::internal_error(anyhow!("failed to upload content: {}", e))
-        })?;
+        .await;
+    // The invocation of an unchanged source can have finished long ago, it
+    // isn't waited for.
+    let (id, should_block) = match result {
+        Ok(()) => (id, should_block),
+        Err(e) => (current_invocation(&state, uploads, e).await?, false),
+    };
 
     let invocation_event_stream = async_stream::stream! {
         if !should_block {
@@ -231,6 +356,94 @@ pub async fn invoke_with_object(
     )
 }
 
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct InvokeWithUrl {
+    /// http(s) or s3 url of the object to invoke the graph with. s3 objects
+    /// must be in the namespace's bucket or in one the server allows.
+    url: String,
+}
+
+/// Invoke a compute graph with an object fetched from a remote url
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invoke_url",
+    request_body = InvokeWithUrl,
+    tag = "ingestion",
+    responses(
+        (status = 200, description = "fetch queued, its state is at the fetch route of the invocation"),
+        (status = 400, description = "bad request"),
+        (status = 404, description = "compute graph not found"),
+        (status = SERVICE_UNAVAILABLE, description = "server is overloaded, retry after the Retry-After delay"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn invoke_with_url(
+    Path((namespace, compute_graph)): Path<(String, String)>,
+    Query(params): Query<InvocationQueryParams>,
+    Query(query): Query<HashMap<String, String>>,
+    State(state): State<RouteState>,
+    headers: HeaderMap,
+    Json(request): Json<InvokeWithUrl>,
+) -> Result<Json<InvocationId>, IndexifyAPIError> {
+    state.invocation_admission.admit().await?;
+    let tags = tags_from_query(&query)?;
+    let trace = trace_from_headers(&headers)?;
+    state
+        .fetcher
+        .check_url(&namespace, &request.url)
+        .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+    let graph = state
+        .indexify_state
+        .reader()
+        .get_compute_graph(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?;
+    if graph.is_none() {
+        return Err(IndexifyAPIError::compute_graph_not_found());
+    }
+    let id = new_id();
+    state
+        .fetcher
+        .enqueue(FetchRequest {
+            namespace,
+            compute_graph,
+            invocation_id: id.clone(),
+            url: request.url,
+            tags,
+            trace,
+            source: params.source,
+            source_modified_at: params.source_modified_at,
+            created_at: get_epoch_time_in_ms(),
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(InvocationId { id }))
+}
+
+/// Get the state of the fetch of an invocation's input, until the graph is
+/// invoked with it
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/fetch",
+    tag = "ingestion",
+    responses(
+        (status = 200, description = "Pending or failed fetch", body = UrlFetch),
+        (status = NOT_FOUND, description = "No fetch for the invocation, the graph was invoked if the invocation exists"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn get_url_fetch(
+    Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<UrlFetch>, IndexifyAPIError> {
+    let fetch = state
+        .indexify_state
+        .reader()
+        .url_fetch(&namespace, &compute_graph, &invocation_id)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("no fetch for the invocation"))?;
+    Ok(Json(fetch.into()))
+}
+
 /// Rerun compute graph with all existing payloads
 #[utoipa::path(
     post,
@@ -240,6 +453,7 @@ pub async fn invoke_with_object(
     responses(
         (status = 200, description = "invocation successful"),
         (status = 400, description = "bad request"),
+        (status = SERVICE_UNAVAILABLE, description = "server is overloaded, retry after the Retry-After delay"),
         (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
     ),
 )]
@@ -247,6 +461,7 @@ pub async fn rerun_compute_graph(
     Path((namespace, compute_graph)): Path<(String, String)>,
     State(state): State<RouteState>,
 ) -> Result<(), IndexifyAPIError> {
+    state.invocation_admission.admit().await?;
     let request = RequestPayload::RerunComputeGraph(RerunComputeGraphRequest {
         namespace: namespace.clone(),
         compute_graph_name: compute_graph.clone(),
@@ -263,3 +478,73 @@ pub async fn rerun_compute_graph(
         })?;
     Ok(())
 }
+
+/// Invoke a compute graph again with the input of an earlier invocation
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/reproduce",
+    tag = "ingestion",
+    responses(
+        (status = 200, description = "invocation successful", body = InvocationId),
+        (status = NOT_FOUND, description = "invocation or compute graph not found"),
+        (status = CONFLICT, description = "version of the compute graph the invocation ran is no longer kept"),
+        (status = SERVICE_UNAVAILABLE, description = "server is overloaded, retry after the Retry-After delay"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn reproduce_invocation(
+    Path((namespace, compute_graph, invocation_id)): Path<(String, String, String)>,
+    State(state): State<RouteState>,
+) -> Result<Json<InvocationId>, IndexifyAPIError> {
+    state.invocation_admission.admit().await?;
+    let reader = state.indexify_state.snapshot_reader();
+    let original = reader
+        .invocation_payload(&namespace, &compute_graph, &invocation_id)
+        .map_err(|_| IndexifyAPIError::invocation_not_found())?;
+    let provenance = original
+        .provenance
+        .as_ref()
+        .ok_or(IndexifyAPIError::not_found("invocation has no provenance"))?;
+    reader
+        .get_compute_graph(&namespace, &compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::compute_graph_not_found())?;
+    // The invocation runs the version of the graph the earlier one ran.
+    // Versions replaced before they were kept resolve to another version.
+    let version = provenance.graph_version;
+    reader
+        .get_compute_graph_version(&namespace, &compute_graph, version)
+        .map_err(IndexifyAPIError::internal_error)?
+        .filter(|graph| graph.version == version)
+        .ok_or(IndexifyAPIError::conflict(&format!(
+            "version {} of the compute graph the invocation ran is no longer kept",
+            version
+        )))?;
+
+    let invocation_payload = InvocationPayloadBuilder::default()
+        .namespace(namespace.clone())
+        .compute_graph_name(compute_graph.clone())
+        .payload(original.payload.clone())
+        .tags(original.tags.clone())
+        .trace(original.trace.clone())
+        .reproduced_from(Some(invocation_id))
+        .graph_version(Some(version))
+        .build()
+        .map_err(IndexifyAPIError::internal_error)?;
+    let id = invocation_payload.id.clone();
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                namespace,
+                compute_graph_name: compute_graph,
+                invocation_payload,
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(|e| {
+            IndexifyAPIError::internal_error(anyhow!("failed to reproduce invocation: {}", e))
+        })?;
+    Ok(Json(InvocationId { id }))
+}
@@ -0,0 +1,29 @@
+use anyhow::anyhow;
+use axum::{extract::State, http::header::CONTENT_TYPE, response::IntoResponse};
+use prometheus_client::encoding::text::encode;
+
+use super::RouteState;
+use crate::http_objects::IndexifyAPIError;
+
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Metrics of the server in the OpenMetrics text format, latency histograms
+/// carry the trace id of a representative request as an exemplar
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Metrics in the OpenMetrics text format"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn metrics(
+    State(state): State<RouteState>,
+) -> Result<impl IntoResponse, IndexifyAPIError> {
+    let mut body = String::new();
+    encode(&mut body, &state.metrics_registry).map_err(|e| {
+        IndexifyAPIError::internal_error(anyhow!("failed to encode metrics: {}", e))
+    })?;
+    Ok(([(CONTENT_TYPE, OPENMETRICS_CONTENT_TYPE)], body))
+}
@@ -0,0 +1,25 @@
+use axum::{extract::State, Json};
+
+use super::RouteState;
+use crate::http_objects::{IndexifyAPIError, OrphansRemoved};
+
+/// Remove the tasks and outputs left by deleted graphs and invocations
+#[utoipa::path(
+    post,
+    path = "/internal/state_store/orphans/reconcile",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Orphaned records removed", body = OrphansRemoved),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn reconcile_orphans(
+    State(state): State<RouteState>,
+) -> Result<Json<OrphansRemoved>, IndexifyAPIError> {
+    let removed = state
+        .indexify_state
+        .reconcile_orphans()
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(removed.into()))
+}
@@ -0,0 +1,68 @@
+use axum::{extract::State, Json};
+use data_model::{
+    validation::{parse_filter, ValidationErrors},
+    ComputeFn,
+    Node,
+};
+use task_scheduler::TaskScheduler;
+
+use super::RouteState;
+use crate::http_objects::{ExplainPlacement, IndexifyAPIError, PlacementExplanation};
+
+/// Explain which executors a task would be placed on right now, and why the
+/// others are excluded. Nothing is created or allocated
+#[utoipa::path(
+    post,
+    path = "/scheduler/explain",
+    tag = "operations",
+    request_body = ExplainPlacement,
+    responses(
+        (status = 200, description = "Eligible and excluded executors", body = PlacementExplanation),
+        (status = BAD_REQUEST, description = "Invalid task"),
+        (status = NOT_FOUND, description = "Function not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn explain_placement(
+    State(state): State<RouteState>,
+    Json(request): Json<ExplainPlacement>,
+) -> Result<Json<PlacementExplanation>, IndexifyAPIError> {
+    let mut node = match &request.function {
+        Some(function) => state
+            .indexify_state
+            .reader()
+            .get_compute_graph(&function.namespace, &function.compute_graph)
+            .map_err(IndexifyAPIError::internal_error)?
+            .and_then(|graph| graph.nodes.get(&function.compute_fn).cloned())
+            .ok_or(IndexifyAPIError::not_found("function not found"))?,
+        None => {
+            let Some(image_name) = &request.image_name else {
+                return Err(IndexifyAPIError::bad_request(
+                    "image_name is required without a function",
+                ));
+            };
+            Node::Compute(ComputeFn {
+                image_name: image_name.clone(),
+                ..Default::default()
+            })
+        }
+    };
+    if let Node::Compute(compute_fn) = &mut node {
+        if let Some(image_name) = request.image_name {
+            compute_fn.image_name = image_name;
+        }
+        if let Some(constraints) = request.placement_constraints {
+            let mut errors = ValidationErrors::default();
+            compute_fn.placement_constraints =
+                parse_filter(&mut errors, "placement_constraints", &constraints);
+            errors.into_result()?;
+        }
+        if let Some(gpu) = request.gpu {
+            compute_fn.gpu = Some(gpu.into());
+        }
+    }
+    let explanation = TaskScheduler::new(state.indexify_state.clone())
+        .explain_placement(&node)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(explanation.into()))
+}
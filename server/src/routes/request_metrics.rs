@@ -0,0 +1,323 @@
+use std::{sync::Arc, time::Instant};
+
+use axum::{
+    body::Body,
+    extract::{MatchedPath, Request, State},
+    http::{header::CONTENT_LENGTH, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{
+        counter::Counter,
+        family::Family,
+        histogram::{exponential_buckets, Histogram},
+    },
+    registry::{Registry, Unit},
+};
+use tracing::{debug, warn};
+
+use crate::config::RequestLogConfig;
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct RouteLabels {
+    pub method: String,
+    /// Path the request was routed to, e.g. `/namespaces/:namespace`.
+    pub route: String,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ResponseLabels {
+    pub method: String,
+    pub route: String,
+    pub status: u16,
+}
+
+fn latency_histogram() -> Histogram {
+    Histogram::new(exponential_buckets(0.001, 2.0, 16))
+}
+
+fn size_histogram() -> Histogram {
+    Histogram::new(exponential_buckets(64.0, 4.0, 12))
+}
+
+/// Metrics of the requests served by every route of the API. Sizes are
+/// those of the Content-Length headers, streamed bodies aren't measured.
+#[derive(Debug, Clone)]
+pub struct HttpMetrics {
+    pub requests: Family<ResponseLabels, Counter>,
+    pub latency: Family<RouteLabels, Histogram>,
+    pub request_size: Family<RouteLabels, Histogram>,
+    pub response_size: Family<RouteLabels, Histogram>,
+}
+
+impl HttpMetrics {
+    pub fn new() -> Self {
+        Self {
+            requests: Family::default(),
+            latency: Family::new_with_constructor(latency_histogram),
+            request_size: Family::new_with_constructor(size_histogram),
+            response_size: Family::new_with_constructor(size_histogram),
+        }
+    }
+
+    pub fn register(&self, registry: &mut Registry) {
+        registry.register(
+            "http_requests",
+            "Requests served by route and status code",
+            self.requests.clone(),
+        );
+        registry.register_with_unit(
+            "http_request_latency",
+            "Time to serve a request, until its response headers are sent",
+            Unit::Seconds,
+            self.latency.clone(),
+        );
+        registry.register_with_unit(
+            "http_request_size",
+            "Size of request bodies",
+            Unit::Bytes,
+            self.request_size.clone(),
+        );
+        registry.register_with_unit(
+            "http_response_size",
+            "Size of response bodies",
+            Unit::Bytes,
+            self.response_size.clone(),
+        );
+    }
+}
+
+impl Default for HttpMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct RequestTracking {
+    pub metrics: HttpMetrics,
+    pub config: RequestLogConfig,
+}
+
+impl RequestTracking {
+    pub fn new(config: RequestLogConfig) -> Self {
+        Self {
+            metrics: HttpMetrics::new(),
+            config,
+        }
+    }
+
+    fn sampled(&self) -> bool {
+        self.config.log_bodies && rand::random::<f64>() < self.config.sample_rate
+    }
+}
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers.get(CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+}
+
+/// Fields of JSON bodies which hold credentials, e.g. the secret signing the
+/// events of a subscription, matched by the end of their name.
+const SECRET_FIELDS: [&str; 5] = ["secret", "password", "token", "key", "authorization"];
+
+fn redact_value(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(fields) => {
+            for (name, value) in fields.iter_mut() {
+                let name = name.to_ascii_lowercase();
+                if SECRET_FIELDS.iter().any(|field| name.ends_with(field)) {
+                    *value = serde_json::Value::String("[redacted]".to_string());
+                } else {
+                    redact_value(value);
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(redact_value),
+        _ => {}
+    }
+}
+
+/// The body as logged, with the credentials of JSON bodies redacted.
+fn redacted(body: &[u8]) -> String {
+    match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(mut value) => {
+            redact_value(&mut value);
+            value.to_string()
+        }
+        Err(_) => String::from_utf8_lossy(body).to_string(),
+    }
+}
+
+/// Logs the body of a request which fits in `max_body_bytes`, the body is
+/// buffered to do so.
+async fn log_request_body(request: Request, route: &str, max_body_bytes: usize) -> Request {
+    if content_length(request.headers()).map_or(true, |len| len > max_body_bytes as u64) {
+        return request;
+    }
+    let (parts, body) = request.into_parts();
+    match axum::body::to_bytes(body, max_body_bytes).await {
+        Ok(bytes) => {
+            debug!(
+                "{} {} ({}) request body: {}",
+                parts.method,
+                parts.uri,
+                route,
+                redacted(&bytes)
+            );
+            Request::from_parts(parts, Body::from(bytes))
+        }
+        Err(e) => {
+            warn!(
+                "failed to read the body of {} {}: {}",
+                parts.method, parts.uri, e
+            );
+            Request::from_parts(parts, Body::empty())
+        }
+    }
+}
+
+/// Logs the bodies of a sample of the requests when enabled. Layered inside
+/// of `authorize`, so that only the bodies of authenticated requests are
+/// logged.
+pub async fn log_request_bodies(
+    State(tracking): State<Arc<RequestTracking>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !tracking.sampled() {
+        return next.run(request).await;
+    }
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let request = log_request_body(request, &route, tracking.config.max_body_bytes).await;
+    next.run(request).await
+}
+
+/// Records the metrics of every request, including the ones rejected before
+/// they reach their handler.
+pub async fn track_requests(
+    State(tracking): State<Arc<RequestTracking>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let labels = RouteLabels {
+        method: request.method().to_string(),
+        route,
+    };
+    let metrics = &tracking.metrics;
+    if let Some(size) = content_length(request.headers()) {
+        metrics
+            .request_size
+            .get_or_create(&labels)
+            .observe(size as f64);
+    }
+
+    let start = Instant::now();
+    let response = next.run(request).await;
+    metrics
+        .latency
+        .get_or_create(&labels)
+        .observe(start.elapsed().as_secs_f64());
+    if let Some(size) = content_length(response.headers()) {
+        metrics
+            .response_size
+            .get_or_create(&labels)
+            .observe(size as f64);
+    }
+    metrics
+        .requests
+        .get_or_create(&ResponseLabels {
+            method: labels.method,
+            route: labels.route,
+            status: response.status().as_u16(),
+        })
+        .inc();
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use axum::{extract::Path, http::StatusCode, middleware, routing::post, Router};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_track_requests() -> Result<()> {
+        let tracking = Arc::new(RequestTracking::new(RequestLogConfig {
+            log_bodies: true,
+            sample_rate: 1.0,
+            ..Default::default()
+        }));
+        let app = Router::new()
+            .route(
+                "/echo/:status",
+                post(|Path(status): Path<u16>, body: String| async move {
+                    (StatusCode::from_u16(status).unwrap(), body)
+                }),
+            )
+            .layer(middleware::from_fn_with_state(
+                tracking.clone(),
+                log_request_bodies,
+            ))
+            .layer(middleware::from_fn_with_state(
+                tracking.clone(),
+                track_requests,
+            ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let client = reqwest::Client::new();
+        for status in [200, 200, 500] {
+            let response = client
+                .post(format!("http://{}/echo/{}", addr, status))
+                .body("hello")
+                .send()
+                .await?;
+            assert_eq!(response.status().as_u16(), status);
+            // Logged bodies are passed on to the handler.
+            assert_eq!(response.text().await?, "hello");
+        }
+
+        let mut registry = Registry::default();
+        tracking.metrics.register(&mut registry);
+        let mut encoded = String::new();
+        prometheus_client::encoding::text::encode(&mut encoded, &registry)?;
+        assert!(encoded.contains(
+            "http_requests_total{method=\"POST\",route=\"/echo/:status\",status=\"200\"} 2"
+        ));
+        assert!(encoded.contains(
+            "http_requests_total{method=\"POST\",route=\"/echo/:status\",status=\"500\"} 1"
+        ));
+        assert!(encoded
+            .contains("http_request_size_bytes_count{method=\"POST\",route=\"/echo/:status\"} 3"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_redacted() {
+        let body = serde_json::json!({
+            "url": "https://example.com/events",
+            "secret": "s3cr3t",
+            "nested": [{ "api_key": "k3y", "name": "n" }],
+        });
+        let logged = redacted(body.to_string().as_bytes());
+        assert!(
+            !logged.contains("s3cr3t") && !logged.contains("k3y"),
+            "{}",
+            logged
+        );
+        assert!(logged.contains("https://example.com/events") && logged.contains("\"n\""));
+        assert_eq!(redacted(b"hello"), "hello");
+    }
+}
@@ -0,0 +1,211 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use data_model::id_generator::new_id;
+use indexify_utils::get_epoch_time_in_ms;
+use state_store::requests::{
+    CreateSubscriptionRequest,
+    DeleteSubscriptionRequest,
+    ReplaySubscriptionRequest,
+    RequestPayload,
+    StateMachineUpdateRequest,
+    SubscriptionError,
+};
+
+use super::RouteState;
+use crate::{
+    http_objects::{
+        CreateSubscription,
+        IndexifyAPIError,
+        ReplaySubscription,
+        Subscription,
+        SubscriptionEvents,
+        SubscriptionEventsParams,
+        Subscriptions,
+    },
+    outbound,
+};
+
+fn subscription_error(e: anyhow::Error) -> IndexifyAPIError {
+    match e.downcast_ref::<SubscriptionError>() {
+        Some(err @ SubscriptionError::NotFound(_)) => IndexifyAPIError::not_found(&err.to_string()),
+        Some(err) => IndexifyAPIError::bad_request(&err.to_string()),
+        None => IndexifyAPIError::internal_error(e),
+    }
+}
+
+/// Subscribe a url to the events of a namespace
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/subscriptions",
+    tag = "operations",
+    request_body = CreateSubscription,
+    responses(
+        (status = 200, description = "Subscription created", body = Subscription),
+        (status = BAD_REQUEST, description = "Invalid url or secret"),
+        (status = NOT_FOUND, description = "Namespace not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn create_subscription(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+    Json(request): Json<CreateSubscription>,
+) -> Result<Json<Subscription>, IndexifyAPIError> {
+    state
+        .indexify_state
+        .reader()
+        .get_namespace(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::namespace_not_found())?;
+    let subscription = data_model::subscriptions::Subscription {
+        namespace,
+        id: new_id(),
+        url: request.url,
+        secret: request.secret,
+        created_at: get_epoch_time_in_ms(),
+        last_sequence: 0,
+        delivered_sequence: 0,
+    };
+    let payload = RequestPayload::CreateSubscription(CreateSubscriptionRequest {
+        subscription: subscription.clone(),
+    });
+    payload.validate()?;
+    outbound::check_url(&subscription.url, state.webhooks.allow_private_addresses)
+        .await
+        .map_err(|e| IndexifyAPIError::bad_request(&format!("url: {}", e)))?;
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload,
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(subscription.into()))
+}
+
+/// List the subscriptions of a namespace
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/subscriptions",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Subscriptions of the namespace", body = Subscriptions),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_subscriptions(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<Subscriptions>, IndexifyAPIError> {
+    let subscriptions = state
+        .indexify_state
+        .reader()
+        .list_subscriptions(Some(&namespace))
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(Subscriptions {
+        subscriptions: subscriptions.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Delete a subscription and its events
+#[utoipa::path(
+    delete,
+    path = "/namespaces/{namespace}/subscriptions/{id}",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Subscription deleted"),
+        (status = NOT_FOUND, description = "Subscription not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn delete_subscription(
+    Path((namespace, id)): Path<(String, String)>,
+    State(state): State<RouteState>,
+) -> Result<(), IndexifyAPIError> {
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::DeleteSubscription(DeleteSubscriptionRequest {
+                namespace,
+                id,
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(subscription_error)?;
+    Ok(())
+}
+
+/// Events of a subscription from a sequence number on, for subscribers to
+/// catch up on events without waiting for them to be delivered
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/subscriptions/{id}/events",
+    tag = "operations",
+    params(
+        ("from_sequence" = Option<u64>, Query, description = "Sequence number of the first event, the first event not acknowledged by default"),
+        ("limit" = Option<usize>, Query, description = "Maximum number of events"),
+    ),
+    responses(
+        (status = 200, description = "Events of the subscription", body = SubscriptionEvents),
+        (status = NOT_FOUND, description = "Subscription not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn list_subscription_events(
+    Path((namespace, id)): Path<(String, String)>,
+    Query(params): Query<SubscriptionEventsParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<SubscriptionEvents>, IndexifyAPIError> {
+    let reader = state.indexify_state.reader();
+    let subscription = reader
+        .get_subscription(&namespace, &id)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::not_found("subscription not found"))?;
+    let from_sequence = params
+        .from_sequence
+        .unwrap_or(subscription.delivered_sequence + 1);
+    let events = reader
+        .list_subscription_events(&namespace, &id, from_sequence, params.limit)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(SubscriptionEvents {
+        events: events.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Deliver the events of a subscription again from a sequence number on,
+/// e.g. those a subscriber acknowledged but lost during downtime
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/subscriptions/{id}/replay",
+    tag = "operations",
+    request_body = ReplaySubscription,
+    responses(
+        (status = 200, description = "Events will be delivered again"),
+        (status = BAD_REQUEST, description = "The events from the sequence number aren't available"),
+        (status = NOT_FOUND, description = "Subscription not found"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn replay_subscription(
+    Path((namespace, id)): Path<(String, String)>,
+    State(state): State<RouteState>,
+    Json(request): Json<ReplaySubscription>,
+) -> Result<(), IndexifyAPIError> {
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::ReplaySubscription(ReplaySubscriptionRequest {
+                namespace,
+                id,
+                from_sequence: request.from_sequence,
+            }),
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(subscription_error)?;
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use data_model::TaskId;
+use state_store::requests::{
+    InterveneTaskRequest,
+    RequestPayload,
+    StateMachineUpdateRequest,
+    TaskInterventionError,
+};
+use tracing::info;
+
+use super::RouteState;
+use crate::http_objects::{
+    encode_cursor,
+    AuditLog,
+    IndexifyAPIError,
+    InterveneTask,
+    ListParams,
+    TaskQueue,
+};
+
+/// Inspect the task queue of a namespace
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/task_queue",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Unfinished tasks, the ones waiting for placement first", body = TaskQueue),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn task_queue(
+    Path(namespace): Path<String>,
+    State(state): State<RouteState>,
+) -> Result<Json<TaskQueue>, IndexifyAPIError> {
+    let tasks = state
+        .indexify_state
+        .reader()
+        .task_queue(&namespace)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(TaskQueue {
+        tasks: tasks.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Requeue a task, assign it to an executor, change its priority or fail it.
+/// The intervention is recorded in the audit log of the namespace
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}/invocations/{invocation_id}/fn/{fn_name}/tasks/{task_id}/interventions",
+    tag = "operations",
+    request_body = InterveneTask,
+    responses(
+        (status = 200, description = "Intervention applied"),
+        (status = BAD_REQUEST, description = "The executor to assign the task to is not registered"),
+        (status = NOT_FOUND, description = "Task not found"),
+        (status = CONFLICT, description = "The task is already finished"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn intervene_task(
+    Path((namespace, compute_graph, invocation_id, fn_name, task_id)): Path<(
+        String,
+        String,
+        String,
+        String,
+        String,
+    )>,
+    State(state): State<RouteState>,
+    Json(request): Json<InterveneTask>,
+) -> Result<(), IndexifyAPIError> {
+    info!(
+        "intervening on task {}: {:?}, reason: {:?}",
+        task_id, request.intervention, request.reason
+    );
+    let result = state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload: RequestPayload::InterveneTask(InterveneTaskRequest {
+                namespace,
+                compute_graph,
+                invocation_id,
+                compute_fn: fn_name,
+                task_id: TaskId::new(task_id),
+                intervention: request.intervention.into(),
+                reason: request.reason,
+            }),
+            state_changes_processed: vec![],
+        })
+        .await;
+    if let Err(e) = result {
+        let message = e.to_string();
+        return Err(match e.downcast_ref::<TaskInterventionError>() {
+            Some(TaskInterventionError::TaskNotFound(_)) => IndexifyAPIError::not_found(&message),
+            Some(TaskInterventionError::TaskFinished(_)) => IndexifyAPIError::conflict(&message),
+            Some(TaskInterventionError::ExecutorNotFound(_)) => {
+                IndexifyAPIError::bad_request(&message)
+            }
+            None => IndexifyAPIError::internal_error(e),
+        });
+    }
+    Ok(())
+}
+
+/// List the interventions made on tasks of a namespace, oldest first
+#[utoipa::path(
+    get,
+    path = "/namespaces/{namespace}/audit_log",
+    tag = "operations",
+    responses(
+        (status = 200, description = "Entries of the audit log", body = AuditLog),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn audit_log(
+    Path(namespace): Path<String>,
+    Query(params): Query<ListParams>,
+    State(state): State<RouteState>,
+) -> Result<Json<AuditLog>, IndexifyAPIError> {
+    let (entries, cursor) = state
+        .indexify_state
+        .reader()
+        .list_audit_log(&namespace, params.cursor()?.as_deref(), params.limit)
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(AuditLog {
+        entries: entries.into_iter().map(Into::into).collect(),
+        cursor: encode_cursor(cursor),
+    }))
+}
@@ -0,0 +1,170 @@
+use anyhow::anyhow;
+use axum::{
+    extract::{Path, State},
+    http::HeaderMap,
+    Json,
+};
+use bytes::Bytes;
+use data_model::{DataPayload, InvocationPayloadBuilder};
+use futures::stream;
+use state_store::requests::{
+    InvokeComputeGraphRequest,
+    RequestPayload,
+    StateMachineUpdateRequest,
+    WriteBatchRequest,
+};
+use uuid::Uuid;
+
+use super::RouteState;
+use crate::http_objects::{
+    trace_from_headers,
+    IndexifyAPIError,
+    TestComputeGraph,
+    TestInvocations,
+};
+
+async fn upload(
+    state: &RouteState,
+    namespace: &str,
+    data: Bytes,
+    content_type: Option<String>,
+) -> Result<DataPayload, IndexifyAPIError> {
+    let put_result = state
+        .blob_storage
+        .for_namespace(namespace)
+        .put(
+            &Uuid::new_v4().to_string(),
+            Box::pin(stream::once(async { Ok(data) })),
+        )
+        .await
+        .map_err(|e| {
+            IndexifyAPIError::internal_error(anyhow!("failed to upload test input: {}", e))
+        })?;
+    Ok(DataPayload {
+        path: put_result.url,
+        size: put_result.size_bytes,
+        sha256_hash: put_result.sha256_hash,
+        content_type,
+    })
+}
+
+/// Inputs of the test, copies of the latest inputs of the graph when none
+/// are given. Copies are made since deleting the test deletes its inputs.
+async fn test_inputs(
+    state: &RouteState,
+    namespace: &str,
+    compute_graph: &str,
+    request: TestComputeGraph,
+) -> Result<Vec<DataPayload>, IndexifyAPIError> {
+    let max_inputs = state.test_invocations.max_inputs;
+    let mut inputs = vec![];
+    if !request.inputs.is_empty() {
+        if request.inputs.len() > max_inputs {
+            return Err(IndexifyAPIError::bad_request(&format!(
+                "a test runs on at most {} inputs",
+                max_inputs
+            )));
+        }
+        for input in request.inputs {
+            let data = serde_json::to_vec(&input)
+                .map_err(|e| IndexifyAPIError::bad_request(&e.to_string()))?;
+            inputs.push(
+                upload(
+                    state,
+                    namespace,
+                    data.into(),
+                    Some("application/json".to_string()),
+                )
+                .await?,
+            );
+        }
+        return Ok(inputs);
+    }
+    let samples = request.samples.unwrap_or(1).min(max_inputs);
+    let latest = state
+        .indexify_state
+        .reader()
+        .latest_invocations(namespace, compute_graph, samples)
+        .map_err(IndexifyAPIError::internal_error)?;
+    if latest.is_empty() {
+        return Err(IndexifyAPIError::bad_request(
+            "the graph has no inputs to sample, provide inputs to test it",
+        ));
+    }
+    let storage = state.blob_storage.for_namespace(namespace);
+    for invocation in latest {
+        let data = storage
+            .read_bytes(&invocation.payload.path)
+            .await
+            .map_err(IndexifyAPIError::internal_error)?;
+        inputs.push(upload(state, namespace, data, invocation.payload.content_type).await?);
+    }
+    Ok(inputs)
+}
+
+/// Run a graph on sample inputs. Test invocations run after every other
+/// task, and are deleted shortly after they finish or once they time out
+#[utoipa::path(
+    post,
+    path = "/namespaces/{namespace}/compute_graphs/{compute_graph}:test",
+    tag = "ingestion",
+    request_body = TestComputeGraph,
+    responses(
+        (status = 200, description = "Test invocations created", body = TestInvocations),
+        (status = BAD_REQUEST, description = "Too many inputs, or no inputs to sample"),
+        (status = NOT_FOUND, description = "Compute graph not found"),
+        (status = SERVICE_UNAVAILABLE, description = "server is overloaded, retry after the Retry-After delay"),
+        (status = INTERNAL_SERVER_ERROR, description = "Internal Server Error")
+    ),
+)]
+pub async fn test_compute_graph(
+    Path((namespace, method)): Path<(String, String)>,
+    State(state): State<RouteState>,
+    headers: HeaderMap,
+    Json(request): Json<TestComputeGraph>,
+) -> Result<Json<TestInvocations>, IndexifyAPIError> {
+    // The router doesn't match a suffix of a path segment.
+    let compute_graph = method
+        .strip_suffix(":test")
+        .ok_or(IndexifyAPIError::not_found("unknown compute graph method"))?;
+    state
+        .indexify_state
+        .reader()
+        .get_compute_graph(&namespace, compute_graph)
+        .map_err(IndexifyAPIError::internal_error)?
+        .ok_or(IndexifyAPIError::compute_graph_not_found())?;
+    state.invocation_admission.admit().await?;
+    let trace = trace_from_headers(&headers)?;
+
+    let mut payloads = vec![];
+    let mut invocation_ids = vec![];
+    for input in test_inputs(&state, &namespace, compute_graph, request).await? {
+        let invocation_payload = InvocationPayloadBuilder::default()
+            .namespace(namespace.clone())
+            .compute_graph_name(compute_graph.to_string())
+            .payload(input)
+            .trace(trace.clone())
+            .test(true)
+            .build()
+            .map_err(IndexifyAPIError::internal_error)?;
+        invocation_ids.push(invocation_payload.id.clone());
+        payloads.push(RequestPayload::InvokeComputeGraph(
+            InvokeComputeGraphRequest {
+                namespace: namespace.clone(),
+                compute_graph_name: compute_graph.to_string(),
+                invocation_payload,
+            },
+        ));
+    }
+    let payload = RequestPayload::WriteBatch(WriteBatchRequest { payloads });
+    payload.validate()?;
+    state
+        .indexify_state
+        .write(StateMachineUpdateRequest {
+            payload,
+            state_changes_processed: vec![],
+        })
+        .await
+        .map_err(IndexifyAPIError::internal_error)?;
+    Ok(Json(TestInvocations { invocation_ids }))
+}
@@ -1,4 +1,8 @@
-use std::{sync::Arc, vec};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+    vec,
+};
 
 use anyhow::{anyhow, Result};
 use data_model::{ChangeType, StateChangeId};
@@ -10,6 +14,7 @@ use state_store::{
         SchedulerUpdateRequest,
         StateMachineUpdateRequest,
     },
+    scanner::STATE_CHANGE_BATCH_SIZE,
     IndexifyState,
 };
 use task_scheduler::{
@@ -17,11 +22,43 @@ use task_scheduler::{
     TaskScheduler,
 };
 use tokio::{self, sync::watch::Receiver};
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+use crate::config::SchedulerConfig;
+
+/// How the scheduler currently consumes state changes, see
+/// `SchedulerConfig`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Pacing {
+    batch_size: usize,
+    /// Wait before the next batch, while writes are slower than the target.
+    backoff: Option<Duration>,
+    /// Set while too many tasks wait for an executor.
+    paused: bool,
+}
+
+impl Pacing {
+    /// Halves the batches while writes take longer than the target, grows
+    /// them by one while they don't.
+    fn adapt(&mut self, config: &SchedulerConfig, write_latency: Duration) {
+        let Some(target) = config.target_write_latency_ms.map(Duration::from_millis) else {
+            return;
+        };
+        if write_latency > target {
+            self.batch_size = (self.batch_size / 2).max(1);
+            self.backoff = Some(write_latency - target);
+        } else {
+            self.batch_size = (self.batch_size + 1).min(config.max_batch_size);
+            self.backoff = None;
+        }
+    }
+}
 
 pub struct Scheduler {
     indexify_state: Arc<IndexifyState>,
     task_allocator: Arc<TaskScheduler>,
+    config: SchedulerConfig,
+    pacing: Mutex<Pacing>,
 }
 
 impl Scheduler {
@@ -30,14 +67,59 @@ impl Scheduler {
         Self {
             indexify_state,
             task_allocator,
+            config: SchedulerConfig::default(),
+            pacing: Mutex::new(Pacing {
+                batch_size: STATE_CHANGE_BATCH_SIZE,
+                backoff: None,
+                paused: false,
+            }),
         }
     }
 
+    pub fn with_config(mut self, config: SchedulerConfig) -> Self {
+        self.pacing.get_mut().unwrap().batch_size =
+            STATE_CHANGE_BATCH_SIZE.min(config.max_batch_size);
+        self.config = config;
+        self
+    }
+
+    /// Whether too many tasks wait for an executor to create more. Resumes
+    /// once the backlog is under 80% of the limit, so that it doesn't flap
+    /// around it.
+    fn saturated(&self) -> Result<bool> {
+        let Some(max) = self.config.max_unallocated_tasks else {
+            return Ok(false);
+        };
+        let backlog = self.indexify_state.reader().count_unallocated_tasks(max)?;
+        let mut pacing = self.pacing.lock().unwrap();
+        if !pacing.paused && backlog >= max {
+            warn!(
+                "{} tasks are waiting for an executor, pausing the creation of tasks",
+                backlog
+            );
+            pacing.paused = true;
+        } else if pacing.paused && backlog * 10 < max * 8 {
+            info!(
+                "{} tasks are waiting for an executor, resuming the creation of tasks",
+                backlog
+            );
+            pacing.paused = false;
+        }
+        Ok(pacing.paused)
+    }
+
     pub async fn run_scheduler(&self) -> Result<()> {
-        let state_changes = self
-            .indexify_state
-            .reader()
-            .get_unprocessed_state_changes()?;
+        let saturated = self.saturated()?;
+        let batch_size = self.pacing.lock().unwrap().batch_size;
+        // While saturated, state changes wait and the waiting tasks are placed
+        // as executors free up.
+        let state_changes = if saturated {
+            vec![]
+        } else {
+            self.indexify_state
+                .reader()
+                .unprocessed_state_changes(batch_size)?
+        };
         let mut create_task_requests = vec![];
         let mut processed_state_changes = vec![];
         let mut new_reduction_tasks = vec![];
@@ -61,7 +143,11 @@ impl Scheduler {
                     let compute_graph = self
                         .indexify_state
                         .reader()
-                        .get_compute_graph(&task.namespace, &task.compute_graph_name)?
+                        .get_compute_graph_version(
+                            &task.namespace,
+                            &task.compute_graph_name,
+                            task.graph_version,
+                        )?
                         .ok_or(anyhow!("compute graph not found"))?;
                     Some(
                         handle_task_finished(self.indexify_state.clone(), task, compute_graph)
@@ -82,17 +168,28 @@ impl Scheduler {
                 processed_reduction_tasks.extend(result.processed_reduction_tasks);
             }
         }
-        let mut new_allocations = vec![];
-        for state_change in &state_changes {
-            let allocations = match state_change.change_type {
-                ChangeType::TaskCreated |
-                ChangeType::ExecutorAdded |
-                ChangeType::ExecutorRemoved => Some(self.task_allocator.schedule_unplaced_tasks()?),
-                _ => None,
-            };
-            if let Some(allocations) = allocations {
-                new_allocations.extend(allocations);
-            }
+        // A single pass places every unallocated task, so the capacity it hands
+        // out isn't counted twice when several changes in the batch need it.
+        // Finished tasks free up GPU capacity for tasks waiting on it.
+        let needs_placement = saturated ||
+            state_changes.iter().any(|state_change| {
+                matches!(
+                    state_change.change_type,
+                    ChangeType::TaskCreated |
+                        ChangeType::TaskFinished(_) |
+                        ChangeType::TaskRejected |
+                        ChangeType::TaskRequeued |
+                        ChangeType::ExecutorAdded |
+                        ChangeType::ExecutorRemoved
+                )
+            });
+        let new_allocations = if needs_placement {
+            self.task_allocator.schedule_unplaced_tasks()?
+        } else {
+            vec![]
+        };
+        if saturated && new_allocations.is_empty() {
+            return Ok(());
         }
 
         let scheduler_update_request = StateMachineUpdateRequest {
@@ -106,7 +203,13 @@ impl Scheduler {
             }),
             state_changes_processed: processed_state_changes,
         };
-        self.indexify_state.write(scheduler_update_request).await
+        let started = Instant::now();
+        self.indexify_state.write(scheduler_update_request).await?;
+        self.pacing
+            .lock()
+            .unwrap()
+            .adapt(&self.config, started.elapsed());
+        Ok(())
     }
 
     pub async fn start(
@@ -118,6 +221,10 @@ impl Scheduler {
             tokio::select! {
                 _ = state_watcher_rx.changed() => {
                        let _state_change = *state_watcher_rx.borrow_and_update();
+                       let backoff = self.pacing.lock().unwrap().backoff;
+                       if let Some(backoff) = backoff {
+                           tokio::time::sleep(backoff).await;
+                       }
                        if let Err(err) = self.run_scheduler().await {
                               error!("error processing and distributing work: {:?}", err);
                        }
@@ -134,22 +241,56 @@ impl Scheduler {
 
 #[cfg(test)]
 mod tests {
-    use std::time::Duration;
+    use std::{collections::HashMap, time::Duration};
 
     use data_model::{
+        artifacts::{Artifact, ArtifactRef},
+        audit::TaskIntervention,
+        filter::{Expression, LabelsFilter},
         test_objects::tests::{
+            create_mock_task,
             mock_executor,
             mock_executor_id,
+            mock_graph_a,
+            mock_invocation_payload,
             mock_invocation_payload_graph_b,
             TEST_NAMESPACE,
         },
+        DataPayload,
         ExecutorId,
+        ExternalDependency,
+        GpuDevice,
+        GpuRequest,
+        GraphVersion,
+        InvocationPayloadBuilder,
+        Node,
         TaskOutcome,
+        TraceContext,
     };
-    use state_store::test_state_store::tests::TestStateStore;
+    use state_store::{
+        requests::{
+            ArtifactError,
+            CanaryError,
+            CreateComputeGraphRequest,
+            DeleteArtifactRequest,
+            FinishCanaryRequest,
+            InterveneTaskRequest,
+            InvokeComputeGraphRequest,
+            NamespaceRequest,
+            RegisterArtifactRequest,
+            StartCanaryRequest,
+            TaskInterventionError,
+            UpdateExecutorArtifactsRequest,
+        },
+        test_state_store::tests::TestStateStore,
+    };
+    use task_scheduler::Exclusion;
 
     use super::*;
-    use crate::executors::{self, ExecutorManager};
+    use crate::{
+        config::PrefetchConfig,
+        executors::{self, ExecutorManager},
+    };
 
     #[tokio::test]
     async fn test_invoke_compute_graph_event_creates_tasks() -> Result<()> {
@@ -209,6 +350,185 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_task_results_acknowledged_once() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let invocation_id = state_store.with_simple_graph().await;
+        scheduler.run_scheduler().await?;
+        let list_tasks = || {
+            indexify_state
+                .reader()
+                .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", &invocation_id, None, None)
+                .unwrap()
+                .0
+        };
+        let task = list_tasks().remove(0);
+        assert_eq!(task.result_sequence, None);
+        state_store
+            .finalize_task(&task, 1, TaskOutcome::Success, false)
+            .await?;
+        // The executor reports the result again, e.g. after losing the
+        // acknowledgement, it's ignored.
+        state_store
+            .finalize_task(&task, 1, TaskOutcome::Success, false)
+            .await?;
+        // The task created and the task finished changes.
+        assert_eq!(
+            indexify_state
+                .reader()
+                .get_unprocessed_state_changes()?
+                .len(),
+            2
+        );
+        assert_eq!(
+            indexify_state
+                .reader()
+                .get_task_outputs(TEST_NAMESPACE, &task.id.to_string())?
+                .len(),
+            1
+        );
+
+        scheduler.run_scheduler().await?;
+        for t in list_tasks().into_iter().filter(|t| t.id != task.id) {
+            state_store
+                .finalize_task(&t, 1, TaskOutcome::Success, false)
+                .await?;
+        }
+        let mut sequences = list_tasks()
+            .into_iter()
+            .map(|t| t.result_sequence.unwrap())
+            .collect::<Vec<_>>();
+        sequences.sort();
+        assert_eq!(sequences, vec![1, 2, 3]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_derived_tasks_carry_invocation_trace() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                    precondition: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let trace = TraceContext {
+            trace_id: Some("4bf92f3577b34da6a3ce929d0e0e4736".to_string()),
+            parent_span_id: Some("00f067aa0ba902b7".to_string()),
+            correlation_id: Some("order-1234".to_string()),
+        };
+        let mut invocation = mock_invocation_payload();
+        invocation.trace = trace.clone();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload: invocation.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        scheduler.run_scheduler().await?;
+        let tasks = indexify_state
+            .reader()
+            .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", &invocation.id, None, None)?
+            .0;
+        state_store
+            .finalize_task(&tasks[0], 1, TaskOutcome::Success, false)
+            .await?;
+        scheduler.run_scheduler().await?;
+        let tasks = indexify_state
+            .reader()
+            .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", &invocation.id, None, None)?
+            .0;
+        assert_eq!(tasks.len(), 3);
+        assert!(tasks.iter().all(|task| task.trace == trace));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tasks_routed_by_content_type() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let mut graph = mock_graph_a();
+        for (name, content_types) in [
+            ("fn_a", vec!["application/pdf", "image/*"]),
+            ("fn_b", vec!["application/pdf"]),
+        ] {
+            if let Some(Node::Compute(compute_fn)) = graph.nodes.get_mut(name) {
+                compute_fn.input_content_types =
+                    content_types.into_iter().map(str::to_string).collect();
+            }
+        }
+        graph.start_fn = graph.nodes["fn_a"].clone();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph,
+                    precondition: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invoke = |id: &str, content_type: &str| {
+            let mut invocation = mock_invocation_payload();
+            invocation.id = id.to_string();
+            invocation.payload.content_type = Some(content_type.to_string());
+            StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload: invocation,
+                }),
+                state_changes_processed: vec![],
+            }
+        };
+        indexify_state.write(invoke("text", "text/plain")).await?;
+        indexify_state.write(invoke("image", "image/png")).await?;
+        scheduler.run_scheduler().await?;
+
+        // The start function doesn't take text, the invocation finishes
+        // without running anything.
+        let reader = indexify_state.reader();
+        let (tasks, _) =
+            reader.list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", "text", None, None)?;
+        assert!(tasks.is_empty());
+        assert!(
+            reader
+                .invocation_ctx(TEST_NAMESPACE, "graph_A", "text")?
+                .completed
+        );
+
+        // Outputs of unknown type only reach the function taking any input.
+        let (tasks, _) =
+            reader.list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", "image", None, None)?;
+        assert_eq!(tasks.len(), 1);
+        state_store
+            .finalize_task(&tasks[0], 1, TaskOutcome::Success, false)
+            .await?;
+        scheduler.run_scheduler().await?;
+        let (tasks, _) =
+            reader.list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", "image", None, None)?;
+        let mut fns = tasks
+            .iter()
+            .map(|task| task.compute_fn_name.as_str())
+            .collect::<Vec<_>>();
+        fns.sort();
+        assert_eq!(fns, vec!["fn_a", "fn_c"]);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn handle_failed_tasks() -> Result<()> {
         let state_store = TestStateStore::new().await?;
@@ -253,6 +573,274 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_gpu_tasks_packed_per_device() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let mut graph = mock_graph_a();
+        if let Some(Node::Compute(compute_fn)) = graph.nodes.get_mut("fn_a") {
+            compute_fn.gpu = Some(GpuRequest {
+                fraction: 0.5,
+                memory_bytes: 8,
+            });
+        }
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph.clone(),
+                    precondition: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
+        let mut executor = mock_executor();
+        executor.gpus = vec![
+            GpuDevice {
+                id: "gpu0".to_string(),
+                memory_bytes: 16,
+            },
+            GpuDevice {
+                id: "gpu1".to_string(),
+                memory_bytes: 8,
+            },
+        ];
+        ex.register_executor(executor).await?;
+
+        let tasks = (0..4)
+            .map(|i| create_mock_task(&graph, "fn_a", &format!("input_{}", i), "invocation"))
+            .collect();
+        let placements = scheduler.task_allocator.schedule_tasks(tasks)?;
+        // The smaller device fills up first, then two tasks share the larger
+        // one and the last task doesn't fit anywhere.
+        let devices = placements
+            .iter()
+            .map(|p| p.task.gpu_allocation.as_ref().unwrap().device_id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(devices, vec!["gpu1", "gpu0", "gpu0"]);
+
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![],
+                    allocations: placements,
+                    reduction_tasks: ReductionTasks::default(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let utilization = ex.gpu_utilization().await?;
+        assert_eq!(utilization.len(), 2);
+        assert_eq!(utilization[0].device_id, "gpu0");
+        assert_eq!(utilization[0].allocated_memory_bytes, 16);
+        assert_eq!(utilization[0].tasks, 2);
+        assert_eq!(utilization[1].allocated_fraction, 0.5);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_executor_capacity() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let graph = mock_graph_a();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph.clone(),
+                    precondition: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
+        let mut executor = mock_executor();
+        executor.capacity = Some(2);
+        ex.register_executor(executor).await?;
+
+        let tasks = (0..3)
+            .map(|i| create_mock_task(&graph, "fn_a", &format!("input_{}", i), "invocation"))
+            .collect();
+        let placements = scheduler.task_allocator.schedule_tasks(tasks)?;
+        assert_eq!(placements.len(), 2);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![],
+                    allocations: placements,
+                    reduction_tasks: ReductionTasks::default(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        // Allocations already stored count against the capacity too.
+        let task = create_mock_task(&graph, "fn_a", "input_3", "invocation");
+        assert!(scheduler
+            .task_allocator
+            .schedule_tasks(vec![task])?
+            .is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_explain_placement() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
+        let mut executor = mock_executor();
+        executor.labels = HashMap::from([("zone".to_string(), serde_json::json!("a"))]);
+        executor.gpus = vec![GpuDevice {
+            id: "gpu0".to_string(),
+            memory_bytes: 16,
+        }];
+        ex.register_executor(executor).await?;
+        let mut other = mock_executor();
+        other.id = ExecutorId::new("other".to_string());
+        other.image_name = "other_image".to_string();
+        ex.register_executor(other).await?;
+
+        let graph = mock_graph_a();
+        let mut node = graph.nodes["fn_a"].clone();
+        let explanation = scheduler.task_allocator.explain_placement(&node)?;
+        assert_eq!(explanation.eligible, vec![mock_executor_id()]);
+        assert_eq!(
+            explanation.excluded,
+            vec![(
+                ExecutorId::new("other".to_string()),
+                vec![Exclusion::ImageMismatch {
+                    image_name: "other_image".to_string()
+                }]
+            )]
+        );
+
+        if let Node::Compute(compute_fn) = &mut node {
+            compute_fn.placement_constraints = LabelsFilter(vec![Expression::from_str("zone=b")?]);
+            compute_fn.gpu = Some(GpuRequest {
+                fraction: 0.5,
+                memory_bytes: 32,
+            });
+        }
+        let explanation = scheduler.task_allocator.explain_placement(&node)?;
+        assert!(explanation.eligible.is_empty());
+        let exclusions: HashMap<_, _> = explanation.excluded.into_iter().collect();
+        assert_eq!(
+            exclusions[&mock_executor_id()],
+            vec![
+                Exclusion::UnmetConstraints {
+                    constraints: vec!["zone=\"b\"".to_string()]
+                },
+                Exclusion::GpuExhausted,
+            ]
+        );
+        assert!(exclusions[&ExecutorId::new("other".to_string())].contains(&Exclusion::NoGpu));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_window() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let graph = mock_graph_a();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph.clone(),
+                    precondition: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let prefetch = PrefetchConfig {
+            default_window: 2,
+            max_window: 3,
+        };
+        assert_eq!(prefetch.window(None), 2);
+        assert_eq!(prefetch.window(Some(0)), 1);
+        let ex = ExecutorManager::new(indexify_state.clone())
+            .await
+            .with_prefetch(prefetch);
+        let mut executor = mock_executor();
+        executor.prefetch_window = Some(10);
+        let executor = ex.register_executor(executor).await?;
+        assert_eq!(executor.prefetch_window, Some(3));
+
+        let tasks = (0..5)
+            .map(|i| create_mock_task(&graph, "fn_a", &format!("input_{}", i), "invocation"))
+            .collect();
+        let placements = scheduler.task_allocator.schedule_tasks(tasks)?;
+        assert_eq!(placements.len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_external_dependency_budget_shared_between_graphs() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let graph_with_budget = |name: &str, max_concurrent_calls| {
+            let mut graph = mock_graph_a();
+            graph.name = name.to_string();
+            if let Some(Node::Compute(compute_fn)) = graph.nodes.get_mut("fn_a") {
+                compute_fn.external_dependency = Some(ExternalDependency {
+                    name: "ocr".to_string(),
+                    max_concurrent_calls,
+                });
+            }
+            graph
+        };
+        let graph_a = graph_with_budget("graph_A", 2);
+        let graph_b = graph_with_budget("graph_B", 3);
+        for graph in [&graph_a, &graph_b] {
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph: graph.clone(),
+                        precondition: None,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
+        ex.register_executor(mock_executor()).await?;
+
+        let tasks = (0..3)
+            .map(|i| create_mock_task(&graph_a, "fn_a", &format!("input_{}", i), "invocation"))
+            .collect();
+        let placements = scheduler.task_allocator.schedule_tasks(tasks)?;
+        assert_eq!(placements.len(), 2);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![],
+                    allocations: placements,
+                    reduction_tasks: ReductionTasks::default(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        // The other graph's calls count against the same dependency.
+        let tasks = (0..2)
+            .map(|i| create_mock_task(&graph_b, "fn_a", &format!("input_{}", i), "invocation"))
+            .collect();
+        let placements = scheduler.task_allocator.schedule_tasks(tasks)?;
+        assert_eq!(placements.len(), 1);
+        assert_eq!(
+            placements[0].task.external_dependency.as_deref(),
+            Some("ocr")
+        );
+        Ok(())
+    }
+
     pub async fn schedule_all(indexify_state: &IndexifyState, scheduler: &Scheduler) -> Result<()> {
         let time = std::time::Instant::now();
         loop {
@@ -268,6 +856,111 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_tasks_placed_on_artifact_holders() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let weights = ArtifactRef {
+            name: "weights".to_string(),
+            version: "v1".to_string(),
+        };
+        let mut graph = mock_graph_a();
+        if let Some(Node::Compute(compute_fn)) = graph.nodes.get_mut("fn_a") {
+            compute_fn.artifacts = vec![weights.clone()];
+        }
+        let create_graph = || {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: graph.clone(),
+                    precondition: None,
+                }),
+                state_changes_processed: vec![],
+            })
+        };
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateNameSpace(NamespaceRequest {
+                    name: TEST_NAMESPACE.to_string(),
+                    blob_storage: None,
+                    scheduling_weight: None,
+                    reprocessing_policy: Default::default(),
+                    near_duplicates: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let err = create_graph().await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ArtifactError>(),
+            Some(ArtifactError::Missing { .. })
+        ));
+
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::RegisterArtifact(RegisterArtifactRequest {
+                    artifact: Artifact {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        name: weights.name.clone(),
+                        version: weights.version.clone(),
+                        payload: DataPayload {
+                            path: "weights_v1".to_string(),
+                            size: 12,
+                            sha256_hash: "weights_hash".to_string(),
+                            content_type: None,
+                        },
+                        created_at: 0,
+                    },
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        create_graph().await?;
+
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
+        let holder = ExecutorId::new("holder".to_string());
+        for id in ["other", "holder"] {
+            let mut executor = mock_executor();
+            executor.id = ExecutorId::new(id.to_string());
+            ex.register_executor(executor).await?;
+        }
+        assert_eq!(ex.prefetch_artifacts(&holder).await?.unwrap().len(), 1);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::UpdateExecutorArtifacts(UpdateExecutorArtifactsRequest {
+                    executor_id: holder.clone(),
+                    artifacts: vec!["weights_hash".to_string()],
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        assert!(ex.prefetch_artifacts(&holder).await?.unwrap().is_empty());
+
+        let tasks = (0..5)
+            .map(|i| create_mock_task(&graph, "fn_a", &format!("input_{}", i), "invocation"))
+            .collect();
+        let placements = scheduler.task_allocator.schedule_tasks(tasks)?;
+        assert_eq!(placements.len(), 5);
+        assert!(placements.iter().all(|p| p.executor == holder));
+
+        let err = indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::DeleteArtifact(DeleteArtifactRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    artifact: weights.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ArtifactError>(),
+            Some(ArtifactError::InUse { .. })
+        ));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_task_remove() -> Result<()> {
         let state_store = TestStateStore::new().await?;
@@ -349,6 +1042,122 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_task_interventions() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
+        let invocation_id = state_store.with_simple_graph().await;
+        ex.register_executor(mock_executor()).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+
+        let task = indexify_state
+            .reader()
+            .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", &invocation_id, None, None)?
+            .0
+            .remove(0);
+        let intervene = |intervention: TaskIntervention| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload: RequestPayload::InterveneTask(InterveneTaskRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: "graph_A".to_string(),
+                    invocation_id: invocation_id.clone(),
+                    compute_fn: task.compute_fn_name.clone(),
+                    task_id: task.id.clone(),
+                    intervention,
+                    reason: Some("stuck".to_string()),
+                }),
+                state_changes_processed: vec![],
+            })
+        };
+
+        intervene(TaskIntervention::Requeue).await?;
+        let queue = indexify_state.reader().task_queue(TEST_NAMESPACE)?;
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue[0].executor_id, None);
+        schedule_all(&indexify_state, &scheduler).await?;
+        let queue = indexify_state.reader().task_queue(TEST_NAMESPACE)?;
+        assert_eq!(queue[0].executor_id, Some(mock_executor_id()));
+
+        let other = ExecutorId::new("other".to_string());
+        let mut executor = mock_executor();
+        executor.id = other.clone();
+        ex.register_executor(executor).await?;
+        intervene(TaskIntervention::Assign {
+            executor_id: other.clone(),
+        })
+        .await?;
+        let reader = indexify_state.reader();
+        assert!(reader
+            .get_tasks_by_executor(&mock_executor_id(), 10)?
+            .is_empty());
+        assert_eq!(reader.get_tasks_by_executor(&other, 10)?.len(), 1);
+
+        let err = intervene(TaskIntervention::Assign {
+            executor_id: ExecutorId::new("unknown".to_string()),
+        })
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TaskInterventionError>(),
+            Some(TaskInterventionError::ExecutorNotFound(_))
+        ));
+
+        intervene(TaskIntervention::SetPriority { priority: 5 }).await?;
+        let queue = indexify_state.reader().task_queue(TEST_NAMESPACE)?;
+        assert_eq!(queue[0].task.priority, 5);
+        assert_eq!(queue[0].executor_id, Some(other.clone()));
+
+        intervene(TaskIntervention::Fail).await?;
+        let task = indexify_state
+            .reader()
+            .get_task(
+                TEST_NAMESPACE,
+                "graph_A",
+                &invocation_id,
+                &task.compute_fn_name,
+                &task.id.to_string(),
+            )?
+            .unwrap();
+        assert_eq!(task.outcome, TaskOutcome::Failure);
+        assert!(indexify_state
+            .reader()
+            .task_queue(TEST_NAMESPACE)?
+            .is_empty());
+        let err = intervene(TaskIntervention::Requeue).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<TaskInterventionError>(),
+            Some(TaskInterventionError::TaskFinished(_))
+        ));
+
+        let (entries, _) = indexify_state
+            .reader()
+            .list_audit_log(TEST_NAMESPACE, None, None)?;
+        let recorded: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.intervention.clone(), entry.executor_id.clone()))
+            .collect();
+        assert_eq!(
+            recorded,
+            vec![
+                (TaskIntervention::Requeue, Some(mock_executor_id())),
+                (
+                    TaskIntervention::Assign {
+                        executor_id: other.clone()
+                    },
+                    Some(mock_executor_id())
+                ),
+                (
+                    TaskIntervention::SetPriority { priority: 5 },
+                    Some(other.clone())
+                ),
+                (TaskIntervention::Fail, Some(other)),
+            ]
+        );
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_add_executor() -> Result<()> {
         let state_store = TestStateStore::new().await?;
@@ -408,6 +1217,54 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_executor_catalog_refreshed_on_registration() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let invocation_id = state_store.with_simple_graph().await;
+        let ex = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
+
+        schedule_all(&indexify_state, &scheduler).await?;
+        assert_eq!(indexify_state.reader().unallocated_tasks()?.len(), 1);
+        let catalog = scheduler.task_allocator.executors()?;
+        assert!(catalog.is_empty());
+
+        // Passes without a registration in between reuse the catalog.
+        let mut invocation = mock_invocation_payload();
+        invocation.id = format!("{}_2", invocation_id);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload: invocation,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        assert_eq!(indexify_state.reader().unallocated_tasks()?.len(), 2);
+        assert!(Arc::ptr_eq(
+            &catalog,
+            &scheduler.task_allocator.executors()?
+        ));
+
+        // The next pass sees the executor registered since the last one.
+        ex.register_executor(mock_executor()).await?;
+        schedule_all(&indexify_state, &scheduler).await?;
+        let catalog = scheduler.task_allocator.executors()?;
+        assert_eq!(catalog.len(), 1);
+        assert_eq!(catalog[0].id, mock_executor_id());
+        assert!(indexify_state.reader().unallocated_tasks()?.is_empty());
+        let executor_tasks = indexify_state
+            .reader()
+            .get_tasks_by_executor(&mock_executor_id(), 10)?;
+        assert_eq!(executor_tasks.len(), 2);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_create_tasks_for_router_tasks() {
         let state_store = TestStateStore::new().await.unwrap();
@@ -457,4 +1314,191 @@ mod tests {
             .0;
         assert_eq!(tasks.len(), 3);
     }
+
+    #[tokio::test]
+    async fn test_canary_rollout() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone());
+        let stable_invocation = state_store.with_simple_graph().await;
+        let mut graph = mock_graph_a();
+        graph.code.sha256_hash = "hash456".to_string();
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        write(RequestPayload::StartCanary(StartCanaryRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: graph.clone(),
+            percent: 100,
+        }))
+        .await?;
+        let invocation = InvocationPayloadBuilder::default()
+            .namespace(TEST_NAMESPACE.to_string())
+            .compute_graph_name("graph_A".to_string())
+            .payload(mock_invocation_payload().payload)
+            .build()?;
+        let canary_invocation = invocation.id.clone();
+        write(RequestPayload::InvokeComputeGraph(
+            InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: "graph_A".to_string(),
+                invocation_payload: invocation,
+            },
+        ))
+        .await?;
+        let graph_version = |invocation_id: &str| {
+            indexify_state
+                .reader()
+                .get_compute_graph_for_invocation(TEST_NAMESPACE, "graph_A", invocation_id)
+                .unwrap()
+                .unwrap()
+                .version
+        };
+        assert_eq!(graph_version(&stable_invocation), GraphVersion::from(1));
+        assert_eq!(graph_version(&canary_invocation), GraphVersion::from(2));
+
+        scheduler.run_scheduler().await?;
+        let task = indexify_state
+            .reader()
+            .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", &canary_invocation, None, None)?
+            .0
+            .remove(0);
+        assert_eq!(task.graph_version, GraphVersion::from(2));
+        state_store
+            .finalize_task(&task, 1, TaskOutcome::Failure, false)
+            .await?;
+        scheduler.run_scheduler().await?;
+        let canary = indexify_state
+            .reader()
+            .get_canary(TEST_NAMESPACE, "graph_A")?
+            .unwrap();
+        assert_eq!(canary.canary.invocations, 1);
+        assert_eq!(canary.canary.failed_invocations, 1);
+        assert_eq!(canary.canary.functions["fn_a"].failed_tasks, 1);
+        assert_eq!(canary.stable.invocations, 0);
+
+        // The graph can't be updated during the rollout.
+        let err = write(RequestPayload::CreateComputeGraph(
+            CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: graph.clone(),
+                precondition: None,
+            },
+        ))
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CanaryError>(),
+            Some(CanaryError::InProgress(_))
+        ));
+
+        let finish = |promote| {
+            RequestPayload::FinishCanary(FinishCanaryRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: "graph_A".to_string(),
+                promote,
+            })
+        };
+        write(finish(true)).await?;
+        let stable = indexify_state
+            .reader()
+            .get_compute_graph(TEST_NAMESPACE, "graph_A")?
+            .unwrap();
+        assert_eq!(stable.version, GraphVersion::from(2));
+        assert_eq!(stable.code.sha256_hash, "hash456");
+        assert!(indexify_state
+            .reader()
+            .get_canary(TEST_NAMESPACE, "graph_A")?
+            .is_none());
+        let err = write(finish(false)).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CanaryError>(),
+            Some(CanaryError::NotFound(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pause_on_unallocated_backlog() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let scheduler = Scheduler::new(indexify_state.clone()).with_config(SchedulerConfig {
+            max_unallocated_tasks: Some(1),
+            ..Default::default()
+        });
+        let first_invocation = state_store.with_simple_graph().await;
+        scheduler.run_scheduler().await?;
+        assert_eq!(indexify_state.reader().count_unallocated_tasks(10)?, 1);
+
+        let invocation = InvocationPayloadBuilder::default()
+            .namespace(TEST_NAMESPACE.to_string())
+            .compute_graph_name("graph_A".to_string())
+            .payload(mock_invocation_payload().payload)
+            .build()?;
+        let second_invocation = invocation.id.clone();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload: invocation,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let tasks_of = |invocation_id: &str| -> Result<Vec<data_model::Task>> {
+            Ok(indexify_state
+                .reader()
+                .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", invocation_id, None, None)?
+                .0)
+        };
+        // The backlog is full, the second invocation waits.
+        scheduler.run_scheduler().await?;
+        assert!(tasks_of(&second_invocation)?.is_empty());
+        let unprocessed = indexify_state.reader().get_unprocessed_state_changes()?;
+        assert_eq!(unprocessed.len(), 2);
+
+        // Placement keeps running while paused and drains the backlog.
+        let ex = ExecutorManager::new(indexify_state.clone()).await;
+        ex.register_executor(mock_executor()).await?;
+        scheduler.run_scheduler().await?;
+        assert_eq!(indexify_state.reader().count_unallocated_tasks(10)?, 0);
+        assert_eq!(tasks_of(&first_invocation)?.len(), 1);
+
+        scheduler.run_scheduler().await?;
+        assert_eq!(tasks_of(&second_invocation)?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_size_follows_write_latency() {
+        let config = SchedulerConfig {
+            target_write_latency_ms: Some(10),
+            max_batch_size: 12,
+            ..Default::default()
+        };
+        let mut pacing = Pacing {
+            batch_size: 10,
+            backoff: None,
+            paused: false,
+        };
+        for _ in 0..5 {
+            pacing.adapt(&config, Duration::from_millis(5));
+        }
+        assert_eq!(pacing.batch_size, 12);
+        assert_eq!(pacing.backoff, None);
+        pacing.adapt(&config, Duration::from_millis(25));
+        assert_eq!(pacing.batch_size, 6);
+        assert_eq!(pacing.backoff, Some(Duration::from_millis(15)));
+        for _ in 0..5 {
+            pacing.adapt(&config, Duration::from_millis(25));
+        }
+        assert_eq!(pacing.batch_size, 1);
+        pacing.adapt(&config, Duration::from_millis(5));
+        assert_eq!(pacing.batch_size, 2);
+        assert_eq!(pacing.backoff, None);
+    }
 }
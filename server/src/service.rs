@@ -1,19 +1,36 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    net::SocketAddr,
+    sync::{atomic, Arc},
+};
 
 use anyhow::Result;
 use axum_server::Handle;
-use blob_store::BlobStorage;
-use state_store::IndexifyState;
+use blob_store::{registry::BlobStorageRegistry, BlobStorage};
+use indexify_utils::clock::{Clock, SystemClock};
+use prometheus_client::registry::Registry;
+use state_store::{serializer, IndexifyState};
 use tokio::{self, signal, sync::watch};
-use tracing::info;
+use tracing::{error, info};
 
 use super::{routes::RouteState, scheduler::Scheduler};
 use crate::{
-    config::ServerConfig,
+    admission::admission_hooks,
+    alerting::AlertRouter,
+    analytics_export::AnalyticsExporter,
+    archiver::Archiver,
+    config::{placement_blob_storage_config, ServerConfig},
+    disk_quota::DiskQuota,
     executors::ExecutorManager,
+    fetcher::Fetcher,
     gc::Gc,
-    routes::create_routes,
+    invocation_admission::InvocationAdmission,
+    key_migrator::KeyMigrator,
+    namespace_deletion::NamespaceDeleter,
+    retention::RetentionSweeper,
+    routes::{api_keys::ApiKeys, create_routes, request_metrics::RequestTracking},
+    subscriptions::EventDelivery,
     system_tasks::SystemTasksExecutor,
+    test_invocations::TestInvocationReaper,
 };
 
 pub struct Service {
@@ -27,22 +44,121 @@ impl Service {
 
     pub async fn start(&self) -> Result<()> {
         let (shutdown_tx, shutdown_rx) = watch::channel(());
-        let indexify_state = IndexifyState::new(self.config.state_store_path.parse()?).await?;
-        let blob_storage = Arc::new(BlobStorage::new(self.config.blob_storage.clone())?);
-        let executor_manager = Arc::new(ExecutorManager::new(indexify_state.clone()).await);
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+        if let Some(encryption) = self.config.encryption.encryption()? {
+            serializer::enable_encryption(encryption)?;
+            info!("encryption of the state store is enabled");
+        }
+        if self.config.state_store.record_checksums {
+            serializer::enable_checksums();
+        }
+        let indexify_state = IndexifyState::open(
+            self.config.state_store_path.parse()?,
+            self.config.state_store.clone(),
+        )
+        .await?;
+        info!(
+            "blob storage backends: {}",
+            blob_store::compiled_backends().join(", ")
+        );
+        let blob_storage = Arc::new(BlobStorageRegistry::new(Arc::new(BlobStorage::new(
+            self.config.blob_storage.clone(),
+        )?)));
+        for namespace in indexify_state.reader().get_all_namespaces()? {
+            if let Some(placement) = &namespace.blob_storage {
+                blob_storage.register(&namespace.name, placement_blob_storage_config(placement))?;
+            }
+        }
+        let executor_manager = Arc::new(
+            ExecutorManager::new(indexify_state.clone())
+                .await
+                .with_admission(admission_hooks(&self.config.executor_admission)?)
+                .with_clock(clock.clone())
+                .with_prefetch(self.config.prefetch.clone()),
+        );
+        let fetcher = Arc::new(Fetcher::new(
+            indexify_state.clone(),
+            blob_storage.clone(),
+            self.config.fetcher.clone(),
+        ));
+        let invocation_admission = Arc::new(InvocationAdmission::new(
+            self.config.invocation_admission.clone(),
+            indexify_state.clone(),
+        ));
+        let disk_quota = DiskQuota::new(
+            self.config.disk_quota.clone(),
+            self.config.state_store_path.parse()?,
+            indexify_state.clone(),
+        );
+        let request_tracking = Arc::new(RequestTracking::new(self.config.request_log.clone()));
+        let mut metrics_registry = Registry::with_prefix("indexify");
+        indexify_state.metrics.register(&mut metrics_registry);
+        request_tracking.metrics.register(&mut metrics_registry);
         let route_state = RouteState {
             indexify_state: indexify_state.clone(),
             blob_storage: blob_storage.clone(),
             executor_manager,
+            fetcher,
+            invocation_admission: invocation_admission.clone(),
+            metrics_registry: Arc::new(metrics_registry),
+            request_tracking,
+            api_keys: Arc::new(ApiKeys::new(&self.config.api_keys)?),
+            backup: self.config.backup.clone(),
+            test_invocations: self.config.test_invocations.clone(),
+            webhooks: self.config.webhooks.clone(),
         };
         let app = create_routes(route_state);
         let handle = Handle::new();
         let handle_sh = handle.clone();
-        let scheduler = Scheduler::new(indexify_state.clone());
+        let scheduler =
+            Scheduler::new(indexify_state.clone()).with_config(self.config.scheduler.clone());
 
+        let mut archiver = Archiver::new(
+            indexify_state.clone(),
+            blob_storage.clone(),
+            self.config.archival.clone(),
+            shutdown_rx.clone(),
+        )
+        .with_clock(clock.clone());
         let mut gc = Gc::new(indexify_state.clone(), blob_storage, shutdown_rx.clone());
+        let mut namespace_deleter =
+            NamespaceDeleter::new(indexify_state.clone(), shutdown_rx.clone());
         let mut system_tasks_executor =
             SystemTasksExecutor::new(indexify_state.clone(), shutdown_rx.clone());
+        let mut event_delivery = EventDelivery::new(
+            indexify_state.clone(),
+            self.config.webhooks.clone(),
+            shutdown_rx.clone(),
+        );
+        let admission_shutdown_rx = shutdown_rx.clone();
+        let disk_quota_shutdown_rx = shutdown_rx.clone();
+        let test_invocation_reaper =
+            TestInvocationReaper::new(indexify_state.clone(), self.config.test_invocations.clone())
+                .with_clock(clock.clone());
+        let test_invocations_shutdown_rx = shutdown_rx.clone();
+        let retention_sweeper =
+            RetentionSweeper::new(indexify_state.clone(), self.config.retention.clone())
+                .with_clock(clock);
+        let retention_shutdown_rx = shutdown_rx.clone();
+        let key_migrator = KeyMigrator::new(indexify_state.clone());
+        let key_migrator_shutdown_rx = shutdown_rx.clone();
+        indexify_state.analytics_enabled.store(
+            self.config.analytics_export.clickhouse_url.is_some(),
+            atomic::Ordering::Relaxed,
+        );
+        let analytics_exporter =
+            AnalyticsExporter::new(indexify_state.clone(), self.config.analytics_export.clone())?;
+        let analytics_shutdown_rx = shutdown_rx.clone();
+        indexify_state.default_alert_channel.store(
+            self.config.alerting.default_webhook.is_some(),
+            atomic::Ordering::Relaxed,
+        );
+        let alert_router = AlertRouter::new(
+            indexify_state.clone(),
+            self.config.alerting.clone(),
+            self.config.webhooks.clone(),
+        );
+        let alerting_shutdown_rx = shutdown_rx.clone();
 
         let state_watcher_rx = indexify_state.get_state_change_watcher();
         tokio::spawn(async move {
@@ -50,11 +166,71 @@ impl Service {
             let _ = scheduler.start(shutdown_rx, state_watcher_rx).await;
             info!("scheduler shutdown");
         });
+        // Scheduling resumes from the persisted index right away, it's checked
+        // against the allocated tasks in the background.
+        let verified_state = indexify_state.clone();
+        tokio::spawn(async move {
+            match verified_state.verify_scheduler_index().await {
+                Ok(true) => info!("rebuilt the scheduler index"),
+                Ok(false) => info!("scheduler index is consistent with the allocated tasks"),
+                Err(e) => error!("failed to verify the scheduler index: {:?}", e),
+            }
+        });
+        tokio::spawn(async move {
+            info!("starting invocation admission");
+            let _ = invocation_admission.start(admission_shutdown_rx).await;
+            info!("invocation admission shutdown");
+        });
+        tokio::spawn(async move {
+            info!("starting disk quota monitor");
+            let _ = disk_quota.start(disk_quota_shutdown_rx).await;
+            info!("disk quota monitor shutdown");
+        });
+        tokio::spawn(async move {
+            info!("starting test invocation reaper");
+            let _ = test_invocation_reaper
+                .start(test_invocations_shutdown_rx)
+                .await;
+            info!("test invocation reaper shutdown");
+        });
+        tokio::spawn(async move {
+            info!("starting retention sweeper");
+            let _ = retention_sweeper.start(retention_shutdown_rx).await;
+            info!("retention sweeper shutdown");
+        });
+        tokio::spawn(async move {
+            let _ = key_migrator.start(key_migrator_shutdown_rx).await;
+        });
+        tokio::spawn(async move {
+            info!("starting analytics exporter");
+            let _ = analytics_exporter.start(analytics_shutdown_rx).await;
+            info!("analytics exporter shutdown");
+        });
+        tokio::spawn(async move {
+            info!("starting alert router");
+            let _ = alert_router.start(alerting_shutdown_rx).await;
+            info!("alert router shutdown");
+        });
+        tokio::spawn(async move {
+            info!("starting subscription event delivery");
+            let _ = event_delivery.start().await;
+            info!("subscription event delivery shutdown");
+        });
         tokio::spawn(async move {
             info!("starting garbage collector");
             let _ = gc.start().await;
             info!("garbage collector shutdown");
         });
+        tokio::spawn(async move {
+            info!("starting invocation archiver");
+            let _ = archiver.start().await;
+            info!("invocation archiver shutdown");
+        });
+        tokio::spawn(async move {
+            info!("starting namespace deleter");
+            let _ = namespace_deleter.start().await;
+            info!("namespace deleter shutdown");
+        });
         tokio::spawn(async move {
             info!("starting system tasks executor");
             let _ = system_tasks_executor.start().await;
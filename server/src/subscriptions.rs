@@ -0,0 +1,307 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use data_model::subscriptions::Subscription;
+use reqwest::Url;
+use ring::hmac;
+use state_store::{
+    requests::{AckSubscriptionEventsRequest, RequestPayload, StateMachineUpdateRequest},
+    IndexifyState,
+};
+use tokio::sync::watch;
+use tracing::{error, warn};
+
+use crate::{config::WebhookConfig, http_objects::SubscriptionEvent, outbound};
+
+pub const SIGNATURE_HEADER: &str = "X-Indexify-Signature";
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+const DELIVERY_BATCH_SIZE: usize = 100;
+
+/// Signature of a delivered body, `sha256=` followed by the hex encoded
+/// HMAC-SHA256 of the body keyed with the subscription's secret.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    format!("sha256={}", hex::encode(hmac::sign(&key, body).as_ref()))
+}
+
+/// Posts the events of every subscription to its url, one at a time and in
+/// order. The cursor of a subscription is only moved past events its url
+/// accepted, so events are delivered at least once: a failed event is
+/// retried, along with the ones after it, until it is accepted. Urls are
+/// supplied by tenants, so events are only posted to public addresses and
+/// redirects are refused.
+pub struct EventDelivery {
+    state: Arc<IndexifyState>,
+    config: WebhookConfig,
+    rx: watch::Receiver<()>,
+    shutdown_rx: watch::Receiver<()>,
+}
+
+impl EventDelivery {
+    pub fn new(
+        state: Arc<IndexifyState>,
+        config: WebhookConfig,
+        shutdown_rx: watch::Receiver<()>,
+    ) -> Self {
+        let rx = state.get_subscription_events_watcher();
+        Self {
+            state,
+            config,
+            rx,
+            shutdown_rx,
+        }
+    }
+
+    async fn post(&self, subscription: &Subscription, event: SubscriptionEvent) -> Result<()> {
+        let body = serde_json::to_vec(&event)?;
+        let url = Url::parse(&subscription.url)?;
+        let response = outbound::client_for(&url, self.config.allow_private_addresses)
+            .await?
+            .timeout(DELIVERY_TIMEOUT)
+            .build()?
+            .post(url)
+            .header(SIGNATURE_HEADER, sign(&subscription.secret, &body))
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("subscriber returned {}", response.status()));
+        }
+        Ok(())
+    }
+
+    /// Delivers the pending events of a subscription, returns whether they
+    /// were all accepted.
+    async fn deliver(&self, subscription: &Subscription) -> Result<bool> {
+        let mut delivered = subscription.delivered_sequence;
+        let mut accepted = true;
+        while delivered < subscription.last_sequence {
+            let events = self.state.reader().list_subscription_events(
+                &subscription.namespace,
+                &subscription.id,
+                delivered + 1,
+                Some(DELIVERY_BATCH_SIZE),
+            )?;
+            if events.is_empty() {
+                break;
+            }
+            for event in events {
+                let sequence = event.sequence;
+                if let Err(e) = self.post(subscription, event.into()).await {
+                    warn!(
+                        "failed to deliver event {} of subscription {} to {}: {:?}",
+                        sequence, subscription.id, subscription.url, e
+                    );
+                    accepted = false;
+                    break;
+                }
+                delivered = sequence;
+            }
+            if !accepted {
+                break;
+            }
+        }
+        if delivered != subscription.delivered_sequence {
+            self.state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::AckSubscriptionEvents(AckSubscriptionEventsRequest {
+                        namespace: subscription.namespace.clone(),
+                        id: subscription.id.clone(),
+                        previous_sequence: subscription.delivered_sequence,
+                        sequence: delivered,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        Ok(accepted)
+    }
+
+    /// Delivers the pending events of every subscription, returns whether
+    /// they were all accepted.
+    pub async fn deliver_pending(&self) -> Result<bool> {
+        let mut accepted = true;
+        for subscription in self.state.reader().list_subscriptions(None)? {
+            if subscription.delivered_sequence < subscription.last_sequence {
+                accepted &= self.deliver(&subscription).await?;
+            }
+        }
+        Ok(accepted)
+    }
+
+    pub async fn start(&mut self) -> Result<()> {
+        loop {
+            let retry = match self.deliver_pending().await {
+                Ok(accepted) => !accepted,
+                Err(e) => {
+                    error!("error delivering subscription events: {:?}", e);
+                    true
+                }
+            };
+            let retry_after = async {
+                if retry {
+                    tokio::time::sleep(RETRY_INTERVAL).await;
+                } else {
+                    std::future::pending::<()>().await;
+                }
+            };
+            tokio::select! {
+                _ = self.rx.changed() => { self.rx.borrow_and_update(); }
+                _ = retry_after => {}
+                _ = self.shutdown_rx.changed() => {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use axum::{
+        http::{HeaderMap, StatusCode},
+        routing::post,
+        Router,
+    };
+    use data_model::{
+        subscriptions::NamespaceEvent,
+        test_objects::tests::TEST_NAMESPACE,
+        TaskOutcome,
+    };
+    use state_store::{
+        requests::{CreateSubscriptionRequest, ReplaySubscriptionRequest, SubscriptionError},
+        test_state_store::tests::TestStateStore,
+    };
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Subscriber {
+        fail: bool,
+        received: Vec<u64>,
+    }
+
+    #[tokio::test]
+    async fn test_event_delivery() -> Result<()> {
+        let subscriber = Arc::new(Mutex::new(Subscriber::default()));
+        let receiver = subscriber.clone();
+        let app = Router::new().route(
+            "/events",
+            post(
+                move |headers: HeaderMap, body: axum::body::Bytes| async move {
+                    let signature = headers[SIGNATURE_HEADER].to_str().unwrap().to_string();
+                    assert_eq!(signature, sign("secret", &body));
+                    let event: SubscriptionEvent = serde_json::from_slice(&body).unwrap();
+                    let mut subscriber = receiver.lock().unwrap();
+                    if subscriber.fail {
+                        return StatusCode::SERVICE_UNAVAILABLE;
+                    }
+                    subscriber.received.push(event.sequence);
+                    StatusCode::OK
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateSubscription(CreateSubscriptionRequest {
+                    subscription: Subscription {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        id: "subscription".to_string(),
+                        url: format!("http://{}/events", addr),
+                        secret: "secret".to_string(),
+                        created_at: 0,
+                        last_sequence: 0,
+                        delivered_sequence: 0,
+                    },
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invocation_id = state_store.with_simple_graph().await;
+        let scheduler = crate::scheduler::Scheduler::new(indexify_state.clone());
+        scheduler.run_scheduler().await?;
+        let task = indexify_state
+            .reader()
+            .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", &invocation_id, None, None)?
+            .0
+            .remove(0);
+        state_store
+            .finalize_task(&task, 1, TaskOutcome::Success, false)
+            .await?;
+        let events = indexify_state.reader().list_subscription_events(
+            TEST_NAMESPACE,
+            "subscription",
+            1,
+            None,
+        )?;
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            &events[0].event,
+            NamespaceEvent::TaskCompleted { task_id, outcome: TaskOutcome::Success, .. }
+                if *task_id == task.id
+        ));
+
+        let (_, shutdown_rx) = watch::channel(());
+        let delivery = EventDelivery::new(
+            indexify_state.clone(),
+            WebhookConfig {
+                allow_private_addresses: true,
+            },
+            shutdown_rx.clone(),
+        );
+        let delivered_sequence = || {
+            indexify_state
+                .reader()
+                .get_subscription(TEST_NAMESPACE, "subscription")
+                .unwrap()
+                .unwrap()
+                .delivered_sequence
+        };
+        subscriber.lock().unwrap().fail = true;
+        assert!(!delivery.deliver_pending().await?);
+        assert_eq!(delivered_sequence(), 0);
+        subscriber.lock().unwrap().fail = false;
+        assert!(delivery.deliver_pending().await?);
+        assert_eq!(delivered_sequence(), 1);
+        // Nothing is left to deliver.
+        assert!(delivery.deliver_pending().await?);
+        assert_eq!(subscriber.lock().unwrap().received, vec![1]);
+
+        let replay = |from_sequence| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload: RequestPayload::ReplaySubscription(ReplaySubscriptionRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    id: "subscription".to_string(),
+                    from_sequence,
+                }),
+                state_changes_processed: vec![],
+            })
+        };
+        replay(1).await?;
+        assert!(delivery.deliver_pending().await?);
+        assert_eq!(subscriber.lock().unwrap().received, vec![1, 1]);
+        let err = replay(3).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<SubscriptionError>(),
+            Some(SubscriptionError::SequenceOutOfRange { .. })
+        ));
+
+        // The subscriber is on a loopback address, which isn't posted to by
+        // default.
+        replay(1).await?;
+        let delivery = EventDelivery::new(indexify_state.clone(), Default::default(), shutdown_rx);
+        assert!(!delivery.deliver_pending().await?);
+        assert_eq!(subscriber.lock().unwrap().received, vec![1, 1]);
+        Ok(())
+    }
+}
@@ -172,6 +172,7 @@ mod tests {
                 sha256_hash: generate_random_hash(),
                 path: Uuid::new_v4().to_string(),
                 size: 12,
+                content_type: None,
             }))
             .build()
             .unwrap()
@@ -198,6 +199,7 @@ mod tests {
             task_outcome: TaskOutcome::Success,
             executor_id: ExecutorId::new(TEST_EXECUTOR_ID.to_string()),
             diagnostics: None,
+            output_checks: None,
         }
     }
 
@@ -210,10 +212,7 @@ mod tests {
                 .with(tracing_subscriber::fmt::layer().with_filter(env_filter)),
         );
 
-        let temp_dir = tempfile::tempdir().unwrap();
-        let state = IndexifyState::new(temp_dir.path().join("state"))
-            .await
-            .unwrap();
+        let state = IndexifyState::in_memory().await.unwrap();
         let shutdown_rx = tokio::sync::watch::channel(()).1;
         let scheduler = Scheduler::new(state.clone());
         let mut executor = SystemTasksExecutor::new(state.clone(), shutdown_rx);
@@ -222,6 +221,7 @@ mod tests {
         let cg_request = CreateComputeGraphRequest {
             namespace: graph.namespace.clone(),
             compute_graph: graph.clone(),
+            precondition: None,
         };
         state
             .write(StateMachineUpdateRequest {
@@ -365,6 +365,7 @@ mod tests {
         let cg_request = CreateComputeGraphRequest {
             namespace: graph.namespace.clone(),
             compute_graph: graph.clone(),
+            precondition: None,
         };
         state
             .write(StateMachineUpdateRequest {
@@ -512,6 +513,7 @@ mod tests {
                 path: "test".to_string(),
                 size: 23,
                 sha256_hash: generate_random_hash(),
+                content_type: None,
             })
             .build()
             .unwrap()
@@ -558,10 +560,7 @@ mod tests {
                 .with(tracing_subscriber::fmt::layer().with_filter(env_filter)),
         );
 
-        let temp_dir = tempfile::tempdir().unwrap();
-        let state = IndexifyState::new(temp_dir.path().join("state"))
-            .await
-            .unwrap();
+        let state = IndexifyState::in_memory().await.unwrap();
         let shutdown_rx = tokio::sync::watch::channel(()).1;
         let scheduler = Scheduler::new(state.clone());
         let mut executor = SystemTasksExecutor::new(state.clone(), shutdown_rx);
@@ -570,6 +569,7 @@ mod tests {
         let cg_request = CreateComputeGraphRequest {
             namespace: graph.namespace.clone(),
             compute_graph: graph.clone(),
+            precondition: None,
         };
         state
             .write(StateMachineUpdateRequest {
@@ -620,6 +620,7 @@ mod tests {
         let cg_request = CreateComputeGraphRequest {
             namespace: graph.namespace.clone(),
             compute_graph: graph.clone(),
+            precondition: None,
         };
         state
             .write(StateMachineUpdateRequest {
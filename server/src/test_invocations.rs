@@ -0,0 +1,181 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use indexify_utils::clock::{Clock, SystemClock};
+use state_store::{
+    requests::{DeleteInvocationRequest, RequestPayload, StateMachineUpdateRequest},
+    IndexifyState,
+};
+use tokio::sync::watch;
+use tracing::{error, info};
+
+use crate::config::TestInvocationConfig;
+
+const DELETE_BATCH_SIZE: usize = 100;
+
+/// Deletes test invocations once they time out or their retention passes,
+/// see `TestInvocationConfig`. Their tasks and outputs are deleted with them.
+pub struct TestInvocationReaper {
+    state: Arc<IndexifyState>,
+    config: TestInvocationConfig,
+    clock: Arc<dyn Clock>,
+}
+
+impl TestInvocationReaper {
+    pub fn new(state: Arc<IndexifyState>, config: TestInvocationConfig) -> Self {
+        Self {
+            state,
+            config,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Deletes the test invocations expired at `now`, returns how many were
+    /// deleted.
+    pub async fn delete_expired(&self, now: u64) -> Result<usize> {
+        let mut deleted = 0;
+        loop {
+            let invocations = self.state.reader().expired_test_invocations(
+                now,
+                self.config.timeout_secs * 1000,
+                self.config.retention_secs * 1000,
+                DELETE_BATCH_SIZE,
+            )?;
+            if invocations.is_empty() {
+                return Ok(deleted);
+            }
+            for invocation in invocations {
+                self.state
+                    .write(StateMachineUpdateRequest {
+                        payload: RequestPayload::DeleteInvocation(DeleteInvocationRequest {
+                            namespace: invocation.namespace,
+                            compute_graph: invocation.compute_graph_name,
+                            invocation_id: invocation.id,
+                        }),
+                        state_changes_processed: vec![],
+                    })
+                    .await?;
+                deleted += 1;
+            }
+        }
+    }
+
+    pub async fn start(&self, mut shutdown_rx: watch::Receiver<()>) -> Result<()> {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    match self.delete_expired(self.clock.now_ms()).await {
+                        Ok(0) => {}
+                        Ok(deleted) => info!("deleted {} expired test invocations", deleted),
+                        Err(e) => error!("error deleting expired test invocations: {:?}", e),
+                    }
+                }
+                _ = shutdown_rx.changed() => {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::{
+        test_objects::tests::{mock_invocation_payload, TEST_NAMESPACE},
+        InvocationPayloadBuilder,
+        TEST_TASK_PRIORITY,
+    };
+    use state_store::{
+        requests::InvokeComputeGraphRequest,
+        serializer::{JsonEncode, JsonEncoder},
+        state_machine::IndexifyObjectsColumns,
+        test_state_store::tests::TestStateStore,
+    };
+
+    use super::*;
+    use crate::scheduler::Scheduler;
+
+    #[tokio::test]
+    async fn test_expired_test_invocations_deleted() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let standard_invocation = state_store.with_simple_graph().await;
+        let invoke_test = |created_at| {
+            let invocation = InvocationPayloadBuilder::default()
+                .namespace(TEST_NAMESPACE.to_string())
+                .compute_graph_name("graph_A".to_string())
+                .payload(mock_invocation_payload().payload)
+                .created_at(created_at)
+                .test(true)
+                .build()
+                .unwrap();
+            let id = invocation.id.clone();
+            let write = indexify_state.write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload: invocation,
+                }),
+                state_changes_processed: vec![],
+            });
+            async move { write.await.map(|_| id) }
+        };
+        let finished = invoke_test(1_000).await?;
+        let running = invoke_test(1_000).await?;
+        Scheduler::new(indexify_state.clone())
+            .run_scheduler()
+            .await?;
+        let tasks = |invocation_id: &str| {
+            indexify_state
+                .reader()
+                .list_tasks_by_compute_graph(TEST_NAMESPACE, "graph_A", invocation_id, None, None)
+                .unwrap()
+                .0
+        };
+        assert_eq!(tasks(&running)[0].priority, TEST_TASK_PRIORITY);
+        assert_eq!(tasks(&standard_invocation)[0].priority, 0);
+        let mut ctx =
+            indexify_state
+                .reader()
+                .invocation_ctx(TEST_NAMESPACE, "graph_A", &finished)?;
+        ctx.completed = true;
+        indexify_state.db.put_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&indexify_state.db),
+            ctx.key(),
+            JsonEncoder::encode(IndexifyObjectsColumns::GraphInvocationCtx, ctx.key(), &ctx)?,
+        )?;
+        let exists = |invocation_id: &str| {
+            indexify_state
+                .reader()
+                .invocation_payload(TEST_NAMESPACE, "graph_A", invocation_id)
+                .is_ok()
+        };
+
+        let reaper = TestInvocationReaper::new(
+            indexify_state.clone(),
+            TestInvocationConfig {
+                timeout_secs: 10,
+                retention_secs: 5,
+                ..Default::default()
+            },
+        );
+        assert_eq!(reaper.delete_expired(1_000).await?, 0);
+        // The finished test is past its retention, the running one is still
+        // within its timeout.
+        assert_eq!(reaper.delete_expired(6_000).await?, 1);
+        assert!(!exists(&finished));
+        assert!(exists(&running));
+        assert_eq!(reaper.delete_expired(11_000).await?, 1);
+        assert!(!exists(&running));
+        assert!(tasks(&running).is_empty());
+        // Invocations other than tests are left alone.
+        assert!(exists(&standard_invocation));
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+use std::{cmp::Ordering, fs, path::Path, sync::Arc};
+
+use anyhow::{anyhow, Result};
+use rocksdb::{
+    backup::{BackupEngine, BackupEngineOptions, RestoreOptions},
+    ColumnFamilyDescriptor,
+    Env,
+    IteratorMode,
+    Options,
+    ReadOptions,
+    WriteBatch,
+    DB,
+};
+use strum::IntoEnumIterator;
+use tracing::info;
+
+use crate::{state_machine::IndexifyObjectsColumns, IndexifyState};
+
+// Keys written to the mirror in one batch while it's synced.
+const SYNC_BATCH_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackupInfo {
+    pub backup_id: u32,
+    /// Seconds since the epoch.
+    pub timestamp: i64,
+    /// Bytes of the files of the backup, some of which are shared with other
+    /// backups.
+    pub size_bytes: u64,
+    pub num_files: u32,
+}
+
+impl From<rocksdb::backup::BackupEngineInfo> for BackupInfo {
+    fn from(info: rocksdb::backup::BackupEngineInfo) -> Self {
+        BackupInfo {
+            backup_id: info.backup_id,
+            timestamp: info.timestamp,
+            size_bytes: info.size,
+            num_files: info.num_files,
+        }
+    }
+}
+
+fn open_engine(path: &Path) -> Result<BackupEngine> {
+    let options = BackupEngineOptions::new(path.join("backups"))?;
+    Ok(BackupEngine::open(&options, &Env::new()?)?)
+}
+
+/// RocksDB can't back up a transaction database through the bindings, the
+/// state store is copied to a regular database kept next to the backups,
+/// which is backed up instead. Only the keys which changed since the last
+/// backup are written to it, so most of its files, and the backups sharing
+/// them, stay the same.
+fn open_mirror(path: &Path) -> Result<DB> {
+    let mut options = Options::default();
+    options.create_if_missing(true);
+    options.create_missing_column_families(true);
+    let column_families = IndexifyObjectsColumns::iter()
+        .map(|cf| ColumnFamilyDescriptor::new(cf.to_string(), Options::default()));
+    // Opening the mirror locks it, backups of the same directory don't run
+    // concurrently.
+    DB::open_cf_descriptors(&options, path.join("mirror"), column_families)
+        .map_err(|e| anyhow!("failed to open the mirror of {}: {}", path.display(), e))
+}
+
+impl IndexifyState {
+    /// Backs the state store up to `path` while it keeps serving writes. The
+    /// backup is consistent as of the moment it starts, is incremental and
+    /// is verified once written.
+    pub fn backup(&self, path: &Path) -> Result<BackupInfo> {
+        fs::create_dir_all(path)?;
+        let mirror = open_mirror(path)?;
+        let snapshot = self.db.snapshot();
+        let mut changed = 0;
+        for column in IndexifyObjectsColumns::iter() {
+            let source_cf = column.cf_db(&self.db);
+            let mirror_cf = mirror
+                .cf_handle(&column.to_string())
+                .ok_or(anyhow!("column family {} not in the mirror", column))?;
+            let mut read_options = ReadOptions::default();
+            read_options.set_snapshot(&snapshot);
+            let mut source = self
+                .db
+                .iterator_cf_opt(&source_cf, read_options, IteratorMode::Start)
+                .peekable();
+            let mut mirrored = mirror
+                .iterator_cf(&mirror_cf, IteratorMode::Start)
+                .peekable();
+            let mut batch = WriteBatch::default();
+            loop {
+                let order = match (source.peek(), mirrored.peek()) {
+                    (None, None) => break,
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (Some(Err(_)), _) | (_, Some(Err(_))) => Ordering::Equal,
+                    (Some(Ok((key, _))), Some(Ok((mirrored_key, _)))) => key.cmp(mirrored_key),
+                };
+                match order {
+                    Ordering::Less => {
+                        let (key, value) = source.next().unwrap()?;
+                        batch.put_cf(&mirror_cf, key, value);
+                    }
+                    Ordering::Greater => {
+                        let (key, _) = mirrored.next().unwrap()?;
+                        batch.delete_cf(&mirror_cf, key);
+                    }
+                    Ordering::Equal => {
+                        let (key, value) = source.next().unwrap()?;
+                        let (_, mirrored_value) = mirrored.next().unwrap()?;
+                        if value != mirrored_value {
+                            batch.put_cf(&mirror_cf, key, value);
+                        }
+                    }
+                }
+                if batch.len() >= SYNC_BATCH_SIZE {
+                    changed += batch.len();
+                    mirror.write(std::mem::take(&mut batch))?;
+                }
+            }
+            changed += batch.len();
+            mirror.write(batch)?;
+        }
+
+        let mut engine = open_engine(path)?;
+        engine.create_new_backup_flush(&mirror, true)?;
+        let info: BackupInfo = engine
+            .get_backup_info()
+            .into_iter()
+            .max_by_key(|info| info.backup_id)
+            .ok_or(anyhow!("backup not found after creating it"))?
+            .into();
+        engine.verify_backup(info.backup_id)?;
+        info!(
+            "backed up the state store to {}, backup {} with {} changed keys",
+            path.display(),
+            info.backup_id,
+            changed
+        );
+        Ok(info)
+    }
+
+    /// Backups in `path`, oldest first.
+    pub fn list_backups(path: &Path) -> Result<Vec<BackupInfo>> {
+        let mut backups: Vec<BackupInfo> = open_engine(path)?
+            .get_backup_info()
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        backups.sort_by_key(|info| info.backup_id);
+        Ok(backups)
+    }
+
+    /// Checks that the files of a backup are all there with their sizes.
+    pub fn verify_backup(path: &Path, backup_id: u32) -> Result<()> {
+        Ok(open_engine(path)?.verify_backup(backup_id)?)
+    }
+
+    /// Removes the oldest backups in `path`, keeping `keep` of them.
+    pub fn purge_backups(path: &Path, keep: usize) -> Result<()> {
+        Ok(open_engine(path)?.purge_old_backups(keep)?)
+    }
+
+    /// Opens the state as of `timestamp`, in seconds since the epoch, that of
+    /// the latest backup taken at or before it. The backup is restored to
+    /// `scratch_path`, which must not exist yet, so that it's queried without
+    /// touching the state store serving requests.
+    pub async fn open_backup_as_of(
+        backup_path: &Path,
+        scratch_path: &Path,
+        timestamp: i64,
+    ) -> Result<(BackupInfo, Arc<IndexifyState>)> {
+        let backup = Self::list_backups(backup_path)?
+            .into_iter()
+            .filter(|info| info.timestamp <= timestamp)
+            .max_by_key(|info| info.timestamp)
+            .ok_or(anyhow!(
+                "no backup in {} taken before {}",
+                backup_path.display(),
+                timestamp
+            ))?;
+        Self::restore(backup_path, scratch_path, Some(backup.backup_id))?;
+        let state = IndexifyState::new(scratch_path.to_path_buf()).await?;
+        Ok((backup, state))
+    }
+
+    /// Restores a backup in `backup_path`, the latest by default, as the
+    /// state store at `path`. It's done before opening the state store, which
+    /// must not exist yet.
+    pub fn restore(backup_path: &Path, path: &Path, backup_id: Option<u32>) -> Result<()> {
+        if path.join("CURRENT").exists() {
+            return Err(anyhow!(
+                "a state store already exists at {}, move it away to restore a backup",
+                path.display()
+            ));
+        }
+        let mut engine = open_engine(backup_path)?;
+        let backup_id = match backup_id {
+            Some(backup_id) => backup_id,
+            None => engine
+                .get_backup_info()
+                .into_iter()
+                .map(|info| info.backup_id)
+                .max()
+                .ok_or(anyhow!("no backup in {}", backup_path.display()))?,
+        };
+        engine.verify_backup(backup_id)?;
+        fs::create_dir_all(path)?;
+        engine.restore_from_backup(path, path, &RestoreOptions::default(), backup_id)?;
+        info!(
+            "restored backup {} of {} to {}",
+            backup_id,
+            backup_path.display(),
+            path.display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::test_objects::tests::TEST_NAMESPACE;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::requests::{NamespaceRequest, RequestPayload, StateMachineUpdateRequest};
+
+    #[tokio::test]
+    async fn test_backup_and_restore() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let backup_path = temp_dir.path().join("backup");
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let create_namespace = |name: &str| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateNameSpace(NamespaceRequest {
+                    name: name.to_string(),
+                    blob_storage: None,
+                    scheduling_weight: None,
+                    reprocessing_policy: Default::default(),
+                    near_duplicates: None,
+                }),
+                state_changes_processed: vec![],
+            })
+        };
+        create_namespace(TEST_NAMESPACE).await?;
+        let first = indexify_state.backup(&backup_path)?;
+        create_namespace("second").await?;
+        let second = indexify_state.backup(&backup_path)?;
+        assert!(second.backup_id > first.backup_id);
+        assert_eq!(
+            IndexifyState::list_backups(&backup_path)?,
+            vec![first.clone(), second.clone()]
+        );
+        IndexifyState::verify_backup(&backup_path, first.backup_id)?;
+
+        let restored_path = temp_dir.path().join("restored");
+        IndexifyState::restore(&backup_path, &restored_path, Some(first.backup_id))?;
+        // The state store can't be restored over an existing one.
+        assert!(IndexifyState::restore(&backup_path, &restored_path, None).is_err());
+        let restored = IndexifyState::new(restored_path).await?;
+        let namespaces = restored.reader().get_all_namespaces()?;
+        assert_eq!(namespaces.len(), 1);
+        assert_eq!(namespaces[0].name, TEST_NAMESPACE);
+
+        let latest_path = temp_dir.path().join("latest");
+        IndexifyState::restore(&backup_path, &latest_path, None)?;
+        let latest = IndexifyState::new(latest_path).await?;
+        assert_eq!(latest.reader().get_all_namespaces()?.len(), 2);
+
+        let (backup, as_of) = IndexifyState::open_backup_as_of(
+            &backup_path,
+            &temp_dir.path().join("as_of"),
+            second.timestamp,
+        )
+        .await?;
+        assert_eq!(backup, second);
+        assert_eq!(as_of.reader().get_all_namespaces()?.len(), 2);
+        assert!(IndexifyState::open_backup_as_of(
+            &backup_path,
+            &temp_dir.path().join("before"),
+            first.timestamp - 1,
+        )
+        .await
+        .is_err());
+
+        IndexifyState::purge_backups(&backup_path, 1)?;
+        assert_eq!(IndexifyState::list_backups(&backup_path)?, vec![second]);
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::{anyhow, Result};
+use data_model::{ComputeGraph, InvocationPayload, Task, TaskId};
+use futures::Stream;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::scanner::StateReader;
+
+// Changes buffered for each watcher before it lags.
+pub const CHANGE_FEED_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    Deleted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    ComputeGraph,
+    Invocation,
+    Task,
+}
+
+/// A committed change of a compute graph, invocation or task. Deleting a
+/// compute graph or an invocation deletes the objects under it without a
+/// change for each of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectChange {
+    pub kind: ChangeKind,
+    pub object: ObjectKind,
+    /// Key of the object in the state store, e.g.
+    /// `<namespace>|<compute_graph>|<invocation_id>` for an invocation.
+    pub key: String,
+}
+
+impl ObjectChange {
+    pub fn compute_graph(kind: ChangeKind, namespace: &str, name: &str) -> Self {
+        Self {
+            kind,
+            object: ObjectKind::ComputeGraph,
+            key: ComputeGraph::key_from(namespace, name),
+        }
+    }
+
+    pub fn invocation(
+        kind: ChangeKind,
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+    ) -> Self {
+        Self {
+            kind,
+            object: ObjectKind::Invocation,
+            key: InvocationPayload::key_from(namespace, compute_graph, invocation_id),
+        }
+    }
+
+    pub fn task(
+        kind: ChangeKind,
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+        compute_fn: &str,
+        task_id: &TaskId,
+    ) -> Self {
+        Self {
+            kind,
+            object: ObjectKind::Task,
+            key: format!(
+                "{}|{}",
+                Task::key_prefix_for_fn(namespace, compute_graph, invocation_id, compute_fn),
+                task_id
+            ),
+        }
+    }
+
+    pub fn for_task(kind: ChangeKind, task: &Task) -> Self {
+        Self {
+            kind,
+            object: ObjectKind::Task,
+            key: task.key(),
+        }
+    }
+}
+
+impl<'a> StateReader<'a> {
+    /// Changes committed from now on to the objects whose key starts with
+    /// `prefix`, e.g. `<namespace>|` for those of a namespace. The stream
+    /// yields an error when the watcher falls behind and changes were
+    /// dropped, the objects should be read again then.
+    pub fn watch(&self, prefix: &str) -> Result<impl Stream<Item = Result<ObjectChange>>> {
+        let mut rx = self
+            .change_feed()
+            .ok_or(anyhow!("the reader has no change feed"))?
+            .subscribe();
+        let prefix = prefix.to_string();
+        Ok(async_stream::stream! {
+            loop {
+                match rx.recv().await {
+                    Ok(change) if change.key.starts_with(&prefix) => yield Ok(change),
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(dropped)) => {
+                        yield Err(anyhow!("change feed lagged, {} changes were dropped", dropped))
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        })
+    }
+}
+
+pub fn new_change_feed() -> broadcast::Sender<ObjectChange> {
+    broadcast::channel(CHANGE_FEED_CAPACITY).0
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::test_objects::tests::TEST_NAMESPACE;
+    use futures::{FutureExt, StreamExt};
+
+    use super::*;
+    use crate::{
+        requests::{DeleteComputeGraphRequest, RequestPayload, StateMachineUpdateRequest},
+        test_state_store::tests::TestStateStore,
+    };
+
+    #[tokio::test]
+    async fn test_watch() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        let indexify_state = state_store.indexify_state.clone();
+        let reader = indexify_state.reader();
+        let graph_changes = reader.watch(&format!("{}|graph_A", TEST_NAMESPACE))?;
+        let other_changes = reader.watch("other|")?;
+        let invocation_id = state_store.with_simple_graph().await;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::DeleteComputeGraph(DeleteComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    name: "graph_A".to_string(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let changes: Vec<ObjectChange> = graph_changes
+            .take(3)
+            .map(|change| change.unwrap())
+            .collect()
+            .await;
+        assert_eq!(
+            changes,
+            vec![
+                ObjectChange::compute_graph(ChangeKind::Created, TEST_NAMESPACE, "graph_A"),
+                ObjectChange::invocation(
+                    ChangeKind::Created,
+                    TEST_NAMESPACE,
+                    "graph_A",
+                    &invocation_id
+                ),
+                ObjectChange::compute_graph(ChangeKind::Deleted, TEST_NAMESPACE, "graph_A"),
+            ]
+        );
+        // Changes outside of the prefix aren't seen.
+        assert!(Box::pin(other_changes).next().now_or_never().is_none());
+        Ok(())
+    }
+}
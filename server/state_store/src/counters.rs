@@ -0,0 +1,174 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use data_model::{Task, TaskOutcome};
+use rocksdb::{Transaction, TransactionDB};
+use strum::{AsRefStr, EnumIter, IntoEnumIterator};
+
+use crate::{
+    serializer::{JsonEncode, JsonEncoder},
+    state_machine::{make_prefix_iterator, IndexifyObjectsColumns},
+};
+
+/// Totals of the objects of a graph, maintained in the transactions writing
+/// the objects so that they're read without listing the objects. Writes
+/// which drop objects in bulk, like rerunning an invocation, may leave them
+/// approximate until they're recounted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, AsRefStr, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum Counter {
+    Invocations,
+    /// Invocation inputs and function outputs.
+    DataObjects,
+    PendingTasks,
+    SucceededTasks,
+    FailedTasks,
+}
+
+impl Counter {
+    pub fn key(&self, namespace: &str, compute_graph: &str) -> String {
+        format!("counter|{}|{}|{}", namespace, compute_graph, self.as_ref())
+    }
+
+    pub fn graph_prefix(namespace: &str, compute_graph: &str) -> String {
+        format!("counter|{}|{}|", namespace, compute_graph)
+    }
+
+    /// The task counter a task counts against.
+    pub fn for_task(task: &Task) -> Counter {
+        match task.outcome {
+            TaskOutcome::Unknown => Counter::PendingTasks,
+            TaskOutcome::Success => Counter::SucceededTasks,
+            TaskOutcome::Failure => Counter::FailedTasks,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GraphCounts {
+    pub invocations: u64,
+    pub data_objects: u64,
+    pub pending_tasks: u64,
+    pub succeeded_tasks: u64,
+    pub failed_tasks: u64,
+}
+
+impl GraphCounts {
+    pub fn get(&self, counter: Counter) -> u64 {
+        match counter {
+            Counter::Invocations => self.invocations,
+            Counter::DataObjects => self.data_objects,
+            Counter::PendingTasks => self.pending_tasks,
+            Counter::SucceededTasks => self.succeeded_tasks,
+            Counter::FailedTasks => self.failed_tasks,
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self, counter: Counter) -> &mut u64 {
+        match counter {
+            Counter::Invocations => &mut self.invocations,
+            Counter::DataObjects => &mut self.data_objects,
+            Counter::PendingTasks => &mut self.pending_tasks,
+            Counter::SucceededTasks => &mut self.succeeded_tasks,
+            Counter::FailedTasks => &mut self.failed_tasks,
+        }
+    }
+}
+
+pub(crate) fn decode_count(value: &[u8]) -> Result<u64> {
+    let bytes: [u8; 8] = value
+        .try_into()
+        .map_err(|_| anyhow!("invalid length for a counter"))?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/// Starts counting the objects of a new graph. Graphs created before their
+/// objects were counted have no counters until they're recounted.
+pub(crate) fn initialize(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    compute_graph: &str,
+) -> Result<()> {
+    let cf = IndexifyObjectsColumns::Stats.cf_db(db);
+    for counter in Counter::iter() {
+        txn.put_cf(
+            &cf,
+            counter.key(namespace, compute_graph),
+            0u64.to_be_bytes(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Adds to a counter within the transaction, without going below zero.
+/// Counters which were never initialized are left alone.
+pub(crate) fn add(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    compute_graph: &str,
+    counter: Counter,
+    delta: i64,
+) -> Result<()> {
+    if delta == 0 {
+        return Ok(());
+    }
+    let cf = IndexifyObjectsColumns::Stats.cf_db(db);
+    let key = counter.key(namespace, compute_graph);
+    let Some(value) = txn.get_for_update_cf(&cf, &key, true)? else {
+        return Ok(());
+    };
+    let count = decode_count(&value)?;
+    let count = count.saturating_add_signed(delta);
+    txn.put_cf(&cf, &key, count.to_be_bytes())?;
+    Ok(())
+}
+
+/// Counts the objects of a graph and resets its counters to the counts. The
+/// counters are locked while counting, so that writes made meanwhile aren't
+/// lost.
+pub(crate) fn recount(
+    db: &Arc<TransactionDB>,
+    namespace: &str,
+    compute_graph: &str,
+) -> Result<GraphCounts> {
+    let txn = db.transaction();
+    let cf = IndexifyObjectsColumns::Stats.cf_db(db);
+    for counter in Counter::iter() {
+        txn.get_for_update_cf(&cf, counter.key(namespace, compute_graph), true)?;
+    }
+    let prefix = format!("{}|{}|", namespace, compute_graph);
+    let count_rows = |column: IndexifyObjectsColumns| -> Result<u64> {
+        let mut count = 0;
+        for row in make_prefix_iterator(&txn, &column.cf_db(db), prefix.as_bytes(), &None) {
+            row?;
+            count += 1;
+        }
+        Ok(count)
+    };
+    let mut counts = GraphCounts {
+        invocations: count_rows(IndexifyObjectsColumns::GraphInvocations)?,
+        ..Default::default()
+    };
+    counts.data_objects = counts.invocations + count_rows(IndexifyObjectsColumns::FnOutputs)?;
+    for row in make_prefix_iterator(
+        &txn,
+        &IndexifyObjectsColumns::Tasks.cf_db(db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (key, value) = row?;
+        let task = JsonEncoder::decode::<Task>(IndexifyObjectsColumns::Tasks, &key, &value)?;
+        *counts.get_mut(Counter::for_task(&task)) += 1;
+    }
+    for counter in Counter::iter() {
+        txn.put_cf(
+            &cf,
+            counter.key(namespace, compute_graph),
+            counts.get(counter).to_be_bytes(),
+        )?;
+    }
+    txn.commit()?;
+    Ok(counts)
+}
@@ -0,0 +1,444 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufRead, Lines, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use rocksdb::{
+    ColumnFamilyDescriptor,
+    IngestExternalFileOptions,
+    IteratorMode,
+    Options,
+    ReadOptions,
+    SstFileWriter,
+    WriteBatch,
+    DB,
+};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use tracing::info;
+
+use crate::{options::StateStoreOptions, state_machine::IndexifyObjectsColumns, IndexifyState};
+
+const EXPORT_VERSION: u32 = 1;
+
+// Records written to the imported state store in one batch.
+const IMPORT_BATCH_SIZE: usize = 1000;
+
+// Records of a column family sorted in memory and written to one SST file
+// by bulk imports.
+const BULK_IMPORT_RECORDS_PER_FILE: usize = 100_000;
+
+/// A line of an export. Keys and values are kept as text when they are
+/// UTF-8 and JSON respectively, which they almost always are, and are hex
+/// encoded otherwise.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ExportLine {
+    Header {
+        version: u32,
+    },
+    Record {
+        cf: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        key: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        key_hex: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        value: Option<serde_json::Value>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        value_hex: Option<String>,
+    },
+    /// Last line, tells a complete export from a truncated one.
+    End {
+        records: u64,
+    },
+}
+
+impl ExportLine {
+    fn record(cf: &IndexifyObjectsColumns, key: &[u8], value: &[u8]) -> Self {
+        let (key, key_hex) = match std::str::from_utf8(key) {
+            Ok(key) => (Some(key.to_string()), None),
+            Err(_) => (None, Some(hex::encode(key))),
+        };
+        // Values are only kept as JSON when they are encoded back to the same
+        // bytes, so that imports are exact.
+        let json = serde_json::from_slice::<serde_json::Value>(value)
+            .ok()
+            .filter(|json| serde_json::to_vec(json).is_ok_and(|encoded| encoded == value));
+        let (value, value_hex) = match json {
+            Some(json) => (Some(json), None),
+            None => (None, Some(hex::encode(value))),
+        };
+        ExportLine::Record {
+            cf: cf.to_string(),
+            key,
+            key_hex,
+            value,
+            value_hex,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportStats {
+    pub records: u64,
+}
+
+impl IndexifyState {
+    /// Writes every column family of the state store to `writer` as JSON
+    /// lines, as of the moment the export starts. Unlike backups, exports
+    /// don't depend on the RocksDB version and can be imported by other
+    /// versions of the server.
+    pub fn export(&self, mut writer: impl Write) -> Result<ExportStats> {
+        let snapshot = self.db.snapshot();
+        let mut write_line = |line: &ExportLine| -> Result<()> {
+            serde_json::to_writer(&mut writer, line)?;
+            writer.write_all(b"\n")?;
+            Ok(())
+        };
+        write_line(&ExportLine::Header {
+            version: EXPORT_VERSION,
+        })?;
+        let mut records = 0;
+        for column in IndexifyObjectsColumns::iter() {
+            let mut read_options = ReadOptions::default();
+            read_options.set_snapshot(&snapshot);
+            for kv in
+                self.db
+                    .iterator_cf_opt(&column.cf_db(&self.db), read_options, IteratorMode::Start)
+            {
+                let (key, value) = kv?;
+                write_line(&ExportLine::record(&column, &key, &value))?;
+                records += 1;
+            }
+        }
+        write_line(&ExportLine::End { records })?;
+        writer.flush()?;
+        Ok(ExportStats { records })
+    }
+
+    /// Imports an export as the state store at `path`. It's done before
+    /// opening the state store, which must not exist yet.
+    pub fn import(reader: impl BufRead, path: &Path) -> Result<ExportStats> {
+        let db = open_import_target(path)?;
+        let imported = import_lines(&db, reader);
+        finish_import(db, path, imported)
+    }
+
+    /// Imports an export as the state store at `path` like `import`, but
+    /// writes the records to SST files which are then ingested, bypassing
+    /// the write path. Much faster for large exports, at the cost of keeping
+    /// a copy of the records on disk while importing.
+    pub fn bulk_import(reader: impl BufRead, path: &Path) -> Result<ExportStats> {
+        let db = open_import_target(path)?;
+        let imported = bulk_import_lines(&db, reader, path, BULK_IMPORT_RECORDS_PER_FILE);
+        finish_import(db, path, imported)
+    }
+}
+
+fn open_import_target(path: &Path) -> Result<DB> {
+    if path.join("CURRENT").exists() {
+        return Err(anyhow!(
+            "a state store already exists at {}, move it away to import an export",
+            path.display()
+        ));
+    }
+    fs::create_dir_all(path)?;
+    let mut options = Options::default();
+    options.create_if_missing(true);
+    options.create_missing_column_families(true);
+    let cf_options = StateStoreOptions::default();
+    let column_families = IndexifyObjectsColumns::iter().map(|cf| {
+        ColumnFamilyDescriptor::new(cf.to_string(), cf_options.column_family_options(&cf, None))
+    });
+    DB::open_cf_descriptors(&options, path, column_families)
+        .map_err(|e| anyhow!("failed to open db: {}", e))
+}
+
+fn finish_import(db: DB, path: &Path, imported: Result<ExportStats>) -> Result<ExportStats> {
+    drop(db);
+    match imported {
+        Ok(stats) => {
+            info!("imported {} records to {}", stats.records, path.display());
+            Ok(stats)
+        }
+        Err(e) => {
+            // Leaves no partial state store behind.
+            fs::remove_dir_all(path)?;
+            Err(e)
+        }
+    }
+}
+
+fn read_header(lines: &mut Lines<impl BufRead>) -> Result<()> {
+    match lines.next().transpose()? {
+        Some(line) => match serde_json::from_str(&line)? {
+            ExportLine::Header { version } if version == EXPORT_VERSION => Ok(()),
+            ExportLine::Header { version } => {
+                Err(anyhow!("unsupported export version {}", version))
+            }
+            _ => Err(anyhow!("export doesn't start with a header")),
+        },
+        None => Err(anyhow!("export is empty")),
+    }
+}
+
+fn decode_record(
+    cf: &str,
+    key: Option<String>,
+    key_hex: Option<String>,
+    value: Option<serde_json::Value>,
+    value_hex: Option<String>,
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let key = match (key, key_hex) {
+        (Some(key), None) => key.into_bytes(),
+        (None, Some(key_hex)) => hex::decode(key_hex)?,
+        _ => return Err(anyhow!("record of {} without a single key", cf)),
+    };
+    let value = match (value, value_hex) {
+        (Some(value), None) => serde_json::to_vec(&value)?,
+        (None, Some(value_hex)) => hex::decode(value_hex)?,
+        _ => return Err(anyhow!("record of {} without a single value", cf)),
+    };
+    Ok((key, value))
+}
+
+fn import_lines(db: &DB, reader: impl BufRead) -> Result<ExportStats> {
+    let mut lines = reader.lines();
+    read_header(&mut lines)?;
+    let mut records = 0;
+    let mut batch = WriteBatch::default();
+    for line in lines {
+        match serde_json::from_str(&line?)? {
+            ExportLine::Record {
+                cf,
+                key,
+                key_hex,
+                value,
+                value_hex,
+            } => {
+                let cf_handle = db
+                    .cf_handle(&cf)
+                    .ok_or(anyhow!("unknown column family {} in export", cf))?;
+                let (key, value) = decode_record(&cf, key, key_hex, value, value_hex)?;
+                batch.put_cf(&cf_handle, key, value);
+                records += 1;
+                if batch.len() >= IMPORT_BATCH_SIZE {
+                    db.write(std::mem::take(&mut batch))?;
+                }
+            }
+            ExportLine::End { records: expected } => {
+                if expected != records {
+                    return Err(anyhow!(
+                        "export has {} records, expected {}",
+                        records,
+                        expected
+                    ));
+                }
+                db.write(batch)?;
+                db.flush()?;
+                return Ok(ExportStats { records });
+            }
+            ExportLine::Header { .. } => return Err(anyhow!("unexpected header in export")),
+        }
+    }
+    Err(anyhow!("export is truncated, it has no end"))
+}
+
+/// SST files written by a bulk import, by column family, in the order they
+/// are ingested.
+struct SstFiles {
+    dir: PathBuf,
+    files: HashMap<String, Vec<PathBuf>>,
+    written: usize,
+}
+
+impl SstFiles {
+    /// Sorts the records of a column family and writes them to a new SST
+    /// file. Of records with the same key, the last one read is kept.
+    fn write(&mut self, cf: &str, mut records: Vec<(Vec<u8>, Vec<u8>)>) -> Result<()> {
+        if records.is_empty() {
+            return Ok(());
+        }
+        // Sorting is stable, the last of the records with the same key is
+        // the last of its run.
+        records.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let path = self.dir.join(format!("{}.sst", self.written));
+        let options = Options::default();
+        let mut writer = SstFileWriter::create(&options);
+        writer.open(&path)?;
+        let mut records = records.into_iter().peekable();
+        while let Some((key, value)) = records.next() {
+            if records.peek().is_some_and(|(next, _)| *next == key) {
+                continue;
+            }
+            writer.put(key, value)?;
+        }
+        writer.finish()?;
+        self.written += 1;
+        self.files.entry(cf.to_string()).or_default().push(path);
+        Ok(())
+    }
+}
+
+fn bulk_import_lines(
+    db: &DB,
+    reader: impl BufRead,
+    path: &Path,
+    records_per_file: usize,
+) -> Result<ExportStats> {
+    let mut lines = reader.lines();
+    read_header(&mut lines)?;
+    let dir = path.join("bulk_import");
+    fs::create_dir_all(&dir)?;
+    let mut sst_files = SstFiles {
+        dir,
+        files: HashMap::new(),
+        written: 0,
+    };
+    let mut pending: HashMap<String, Vec<(Vec<u8>, Vec<u8>)>> = HashMap::new();
+    let mut records = 0;
+    for line in lines {
+        match serde_json::from_str(&line?)? {
+            ExportLine::Record {
+                cf,
+                key,
+                key_hex,
+                value,
+                value_hex,
+            } => {
+                if db.cf_handle(&cf).is_none() {
+                    return Err(anyhow!("unknown column family {} in export", cf));
+                }
+                let record = decode_record(&cf, key, key_hex, value, value_hex)?;
+                let cf_records = pending.entry(cf.clone()).or_default();
+                cf_records.push(record);
+                records += 1;
+                if cf_records.len() >= records_per_file {
+                    let cf_records = std::mem::take(cf_records);
+                    sst_files.write(&cf, cf_records)?;
+                }
+            }
+            ExportLine::End { records: expected } => {
+                if expected != records {
+                    return Err(anyhow!(
+                        "export has {} records, expected {}",
+                        records,
+                        expected
+                    ));
+                }
+                for (cf, cf_records) in pending {
+                    sst_files.write(&cf, cf_records)?;
+                }
+                let mut ingest_options = IngestExternalFileOptions::default();
+                ingest_options.set_move_files(true);
+                for (cf, files) in &sst_files.files {
+                    let cf_handle = db
+                        .cf_handle(cf)
+                        .ok_or(anyhow!("column family {} not in the state store", cf))?;
+                    // Files of a column family may overlap when the export
+                    // isn't sorted, ingesting them one by one lets the later
+                    // ones win.
+                    for file in files {
+                        db.ingest_external_file_cf_opts(&cf_handle, &ingest_options, vec![file])?;
+                    }
+                }
+                fs::remove_dir_all(&sst_files.dir)?;
+                info!(
+                    "ingested {} SST files with {} records",
+                    sst_files.written, records
+                );
+                return Ok(ExportStats { records });
+            }
+            ExportLine::Header { .. } => return Err(anyhow!("unexpected header in export")),
+        }
+    }
+    Err(anyhow!("export is truncated, it has no end"))
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::test_objects::tests::TEST_NAMESPACE;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::test_state_store::tests::TestStateStore;
+
+    #[tokio::test]
+    async fn test_export_and_import() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        state_store.with_simple_graph().await;
+        let mut export = Vec::new();
+        let stats = state_store.indexify_state.export(&mut export)?;
+        assert!(stats.records > 0);
+
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("imported");
+        assert_eq!(IndexifyState::import(export.as_slice(), &path)?, stats);
+        // Nothing is imported over an existing state store.
+        assert!(IndexifyState::import(export.as_slice(), &path).is_err());
+        let imported = IndexifyState::new(path).await?;
+        let mut reexport = Vec::new();
+        imported.export(&mut reexport)?;
+        assert_eq!(reexport, export);
+        assert!(imported
+            .reader()
+            .get_compute_graph(TEST_NAMESPACE, "graph_A")?
+            .is_some());
+
+        // Truncated exports are refused.
+        let last_line = export[..export.len() - 1]
+            .iter()
+            .rposition(|b| *b == b'\n')
+            .unwrap();
+        let truncated = &export[..last_line + 1];
+        let truncated_path = temp_dir.path().join("truncated");
+        assert!(IndexifyState::import(truncated, &truncated_path).is_err());
+        assert!(!truncated_path.exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bulk_import() -> Result<()> {
+        let state_store = TestStateStore::new().await?;
+        state_store.with_simple_graph().await;
+        let mut export = Vec::new();
+        let stats = state_store.indexify_state.export(&mut export)?;
+
+        // A record repeated out of order, the last one read wins.
+        let mut lines: Vec<&[u8]> = export.split_inclusive(|b| *b == b'\n').collect();
+        let first_record = lines[1];
+        let end_line = lines.pop().unwrap();
+        lines.push(first_record);
+        let end = String::from_utf8(end_line.to_vec())?.replace(
+            &format!("{}", stats.records),
+            &format!("{}", stats.records + 1),
+        );
+        lines.push(end.as_bytes());
+        let reordered = lines.concat();
+
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("imported");
+        let db = open_import_target(&path)?;
+        // Small files so that the records span several of them.
+        let imported = bulk_import_lines(&db, reordered.as_slice(), &path, 2);
+        assert_eq!(
+            finish_import(db, &path, imported)?.records,
+            stats.records + 1
+        );
+        assert!(!path.join("bulk_import").exists());
+        let imported = IndexifyState::new(path).await?;
+        let mut reexport = Vec::new();
+        imported.export(&mut reexport)?;
+        assert_eq!(reexport, export);
+
+        let truncated = &export[..export.len() - end_line.len()];
+        let truncated_path = temp_dir.path().join("truncated");
+        assert!(IndexifyState::bulk_import(truncated, &truncated_path).is_err());
+        assert!(!truncated_path.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,416 @@
+use std::{collections::BTreeSet, sync::Arc};
+
+use anyhow::Result;
+use data_model::Task;
+use rocksdb::{IteratorMode, TransactionDB};
+use strum::IntoEnumIterator;
+use tracing::{info, warn};
+
+use crate::{
+    counters,
+    orphans::{self, OrphansRemoved, Tombstones},
+    scheduler_index,
+    serializer::{self, KeyError},
+    state_machine::IndexifyObjectsColumns,
+};
+
+/// Most problems listed in a report, the counts cover all of them.
+const MAX_LISTED_PROBLEMS: usize = 100;
+
+/// Records quarantined, or index entries removed, per transaction.
+const REPAIR_BATCH_SIZE: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProblemKind {
+    /// The record fails its checksum, doesn't decrypt or doesn't parse.
+    Unreadable,
+    /// The record is encrypted with a key which isn't configured, or doesn't
+    /// open with the configured key of its id. It's likely intact and only
+    /// the configuration is wrong, so it's never quarantined.
+    Undecryptable,
+    /// The index entry points at a record which doesn't exist.
+    DanglingIndexEntry,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub kind: ProblemKind,
+    pub column: String,
+    pub key: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FsckReport {
+    pub records_checked: u64,
+    pub unreadable: u64,
+    pub undecryptable: u64,
+    pub dangling_index_entries: u64,
+    /// Tasks and outputs left by deleted graphs and invocations. Not checked
+    /// while tasks or outputs are unreadable, unless repairing.
+    pub orphans: Option<OrphansRemoved>,
+    /// The first problems found.
+    pub problems: Vec<Problem>,
+    pub repaired: bool,
+}
+
+impl FsckReport {
+    pub fn is_clean(&self) -> bool {
+        self.unreadable == 0 &&
+            self.undecryptable == 0 &&
+            self.dangling_index_entries == 0 &&
+            self.orphans
+                .as_ref()
+                .map_or(true, |orphans| orphans.tasks == 0 && orphans.outputs == 0)
+    }
+
+    fn found(
+        &mut self,
+        kind: ProblemKind,
+        column: &IndexifyObjectsColumns,
+        key: &[u8],
+        reason: String,
+    ) {
+        match kind {
+            ProblemKind::Unreadable => self.unreadable += 1,
+            ProblemKind::Undecryptable => self.undecryptable += 1,
+            ProblemKind::DanglingIndexEntry => self.dangling_index_entries += 1,
+        }
+        if self.problems.len() < MAX_LISTED_PROBLEMS {
+            self.problems.push(Problem {
+                kind,
+                column: column.to_string(),
+                key: String::from_utf8_lossy(key).to_string(),
+                reason,
+            });
+        }
+    }
+}
+
+/// Key of a record quarantined to `CorruptRecords`.
+pub fn corrupt_record_key(column: &IndexifyObjectsColumns, key: &[u8]) -> Vec<u8> {
+    [column.as_ref().as_bytes(), b"|", key].concat()
+}
+
+/// Problem of the record stored under `key` of `column`, if it can't be
+/// read.
+fn check_record(
+    column: &IndexifyObjectsColumns,
+    key: &[u8],
+    value: &[u8],
+) -> Option<(ProblemKind, String)> {
+    let e = serializer::verify(column.as_ref(), key, value).err()?;
+    let kind = match e.downcast_ref::<KeyError>() {
+        Some(_) => ProblemKind::Undecryptable,
+        None => ProblemKind::Unreadable,
+    };
+    Some((kind, e.to_string()))
+}
+
+/// Moves unreadable records of `column` to `CorruptRecords`.
+fn quarantine(
+    db: &TransactionDB,
+    column: &IndexifyObjectsColumns,
+    records: &mut Vec<(Box<[u8]>, Box<[u8]>)>,
+) -> Result<()> {
+    let txn = db.transaction();
+    let corrupt_cf = IndexifyObjectsColumns::CorruptRecords.cf_db(db);
+    for (key, value) in records.drain(..) {
+        txn.put_cf(&corrupt_cf, corrupt_record_key(column, &key), value)?;
+        txn.delete_cf(&column.cf_db(db), key)?;
+    }
+    txn.commit()?;
+    Ok(())
+}
+
+/// Scans every column family for records which can't be read and index
+/// entries pointing at missing records, along with orphaned tasks and
+/// outputs. Repairing moves unreadable records to `CorruptRecords`, where
+/// they can be inspected, deletes dangling index entries and orphans, and
+/// recounts the graphs whose records were removed.
+///
+/// Records which don't decrypt with the configured keys are reported apart
+/// from corrupt ones, a missing or wrong key would otherwise have every
+/// encrypted record quarantined. Nothing is repaired while there are any.
+pub(crate) fn fsck(
+    db: &Arc<TransactionDB>,
+    tombstones: &Tombstones,
+    repair: bool,
+) -> Result<FsckReport> {
+    let mut report = FsckReport::default();
+    let mut orphans_readable = true;
+    for column in IndexifyObjectsColumns::iter().filter(|column| column.holds_records()) {
+        for row in db.iterator_cf(&column.cf_db(db), IteratorMode::Start) {
+            let (key, value) = row?;
+            report.records_checked += 1;
+            if let Some((kind, reason)) = check_record(&column, &key, &value) {
+                report.found(kind, &column, &key, reason);
+                orphans_readable &= !matches!(
+                    column,
+                    IndexifyObjectsColumns::Tasks | IndexifyObjectsColumns::FnOutputs
+                );
+            }
+        }
+    }
+    // Tasks about to be quarantined are as good as missing to their indexes.
+    let tasks_cf = IndexifyObjectsColumns::Tasks.cf_db(db);
+    let task_exists = |key: &[u8]| -> Result<bool> {
+        Ok(match db.get_cf(&tasks_cf, key)? {
+            Some(value) => !matches!(
+                check_record(&IndexifyObjectsColumns::Tasks, key, &value),
+                Some((ProblemKind::Unreadable, _))
+            ),
+            None => false,
+        })
+    };
+
+    let mut dangling = Vec::new();
+    for row in db.iterator_cf(
+        &IndexifyObjectsColumns::UnallocatedTasks.cf_db(db),
+        IteratorMode::Start,
+    ) {
+        let (key, _) = row?;
+        if !task_exists(&key)? {
+            dangling.push((IndexifyObjectsColumns::UnallocatedTasks, key, "task"));
+        }
+    }
+    for row in db.iterator_cf(
+        &IndexifyObjectsColumns::TaskAllocations.cf_db(db),
+        IteratorMode::Start,
+    ) {
+        let (key, _) = row?;
+        let exists = match Task::key_from_allocation_key(&key) {
+            Ok(task_key) => task_exists(&task_key)?,
+            Err(_) => false,
+        };
+        if !exists {
+            dangling.push((IndexifyObjectsColumns::TaskAllocations, key, "task"));
+        }
+    }
+    for row in db.iterator_cf(
+        &IndexifyObjectsColumns::UnprocessedStateChanges.cf_db(db),
+        IteratorMode::Start,
+    ) {
+        let (key, _) = row?;
+        if db
+            .get_cf(&IndexifyObjectsColumns::StateChanges.cf_db(db), &key)?
+            .is_none()
+        {
+            dangling.push((
+                IndexifyObjectsColumns::UnprocessedStateChanges,
+                key,
+                "state change",
+            ));
+        }
+    }
+    for (column, key, target) in &dangling {
+        report.found(
+            ProblemKind::DanglingIndexEntry,
+            column,
+            key,
+            format!("{} does not exist", target),
+        );
+    }
+
+    if repair && report.undecryptable > 0 {
+        warn!(
+            "not repairing the state store, {} records don't decrypt with the configured keys",
+            report.undecryptable
+        );
+    }
+    if !repair || report.undecryptable > 0 {
+        if orphans_readable {
+            report.orphans = Some(orphans::reconcile(db, tombstones, true)?);
+        }
+        return Ok(report);
+    }
+
+    // Records are checked again as they're quarantined, in batches, rather
+    // than kept from the scan above.
+    let mut quarantined = 0;
+    let mut recount = BTreeSet::new();
+    for column in IndexifyObjectsColumns::iter().filter(|column| column.holds_records()) {
+        let mut batch = Vec::new();
+        for row in db.iterator_cf(&column.cf_db(db), IteratorMode::Start) {
+            let (key, value) = row?;
+            if !matches!(
+                check_record(&column, &key, &value),
+                Some((ProblemKind::Unreadable, _))
+            ) {
+                continue;
+            }
+            if matches!(
+                column,
+                IndexifyObjectsColumns::Tasks |
+                    IndexifyObjectsColumns::FnOutputs |
+                    IndexifyObjectsColumns::GraphInvocations
+            ) {
+                let key = String::from_utf8_lossy(&key);
+                let mut parts = key.splitn(3, '|');
+                if let (Some(namespace), Some(compute_graph), Some(_)) =
+                    (parts.next(), parts.next(), parts.next())
+                {
+                    recount.insert((namespace.to_string(), compute_graph.to_string()));
+                }
+            }
+            batch.push((key, value));
+            quarantined += 1;
+            if batch.len() == REPAIR_BATCH_SIZE {
+                quarantine(db, &column, &mut batch)?;
+            }
+        }
+        if !batch.is_empty() {
+            quarantine(db, &column, &mut batch)?;
+        }
+    }
+    for entries in dangling.chunks(REPAIR_BATCH_SIZE) {
+        let txn = db.transaction();
+        for (column, key, _) in entries {
+            txn.delete_cf(&column.cf_db(db), key)?;
+        }
+        txn.commit()?;
+    }
+    for (namespace, compute_graph) in &recount {
+        counters::recount(db, namespace, compute_graph)?;
+    }
+    if !dangling.is_empty() && scheduler_index::verify(db)? {
+        info!("rebuilt the scheduler index after removing dangling allocations");
+    }
+    report.orphans = Some(orphans::reconcile(db, tombstones, false)?);
+    report.repaired = true;
+    if quarantined > 0 {
+        warn!(
+            "quarantined {} unreadable records to {}",
+            quarantined,
+            IndexifyObjectsColumns::CorruptRecords.as_ref()
+        );
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::test_objects::tests::{
+        create_mock_task,
+        mock_graph_a,
+        mock_invocation_payload,
+        TEST_NAMESPACE,
+    };
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        requests::{
+            CreateComputeGraphRequest,
+            InvokeComputeGraphRequest,
+            RequestPayload,
+            StateMachineUpdateRequest,
+        },
+        serializer::{JsonEncode, JsonEncoder},
+        IndexifyState,
+    };
+
+    async fn write(state: &IndexifyState, payload: RequestPayload) -> Result<()> {
+        state
+            .write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_fsck() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        write(
+            &state,
+            RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph: mock_graph_a(),
+                precondition: None,
+            }),
+        )
+        .await?;
+        let invocation = mock_invocation_payload();
+        write(
+            &state,
+            RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: "graph_A".to_string(),
+                invocation_payload: invocation.clone(),
+            }),
+        )
+        .await?;
+        let report = state.fsck(false).await?;
+        assert!(report.is_clean(), "{:?}", report);
+        assert!(report.records_checked > 0);
+
+        let db = &state.db;
+        let artifacts = IndexifyObjectsColumns::Artifacts.cf_db(db);
+        db.put_cf(&artifacts, b"test|artifact|1", b"{\"name\":")?;
+        db.put_cf(
+            &IndexifyObjectsColumns::UnallocatedTasks.cf_db(db),
+            b"test|graph_A|missing|fn_a|task",
+            [],
+        )?;
+        let orphan = create_mock_task(&mock_graph_a(), "fn_a", "input", "deleted");
+        db.put_cf(
+            &IndexifyObjectsColumns::Tasks.cf_db(db),
+            orphan.key(),
+            JsonEncoder::encode(IndexifyObjectsColumns::Tasks, orphan.key(), &orphan)?,
+        )?;
+
+        let report = state.fsck(false).await?;
+        assert_eq!((report.unreadable, report.dangling_index_entries), (1, 1));
+        assert_eq!(
+            report.orphans.as_ref().map(|orphans| orphans.tasks),
+            Some(1)
+        );
+        assert_eq!(report.problems[0].kind, ProblemKind::Unreadable);
+        assert_eq!(report.problems[0].column, "Artifacts");
+        assert!(!report.repaired);
+        // Checking doesn't change anything.
+        assert_eq!(state.fsck(false).await?, report);
+
+        let report = state.fsck(true).await?;
+        assert!(report.repaired);
+        assert_eq!(report.unreadable, 1);
+        assert_eq!(report.orphans.map(|orphans| orphans.tasks), Some(1));
+        assert_eq!(
+            db.get_cf(
+                &IndexifyObjectsColumns::CorruptRecords.cf_db(db),
+                corrupt_record_key(&IndexifyObjectsColumns::Artifacts, b"test|artifact|1"),
+            )?,
+            Some(b"{\"name\":".to_vec())
+        );
+        assert!(db.get_cf(&artifacts, b"test|artifact|1")?.is_none());
+        assert!(state.fsck(false).await?.is_clean());
+
+        // A record encrypted while no key is configured isn't corrupt, the
+        // configuration is wrong: nothing is repaired until it's fixed.
+        let encrypted = [&[0x03, 1][..], &[0; 12], b"sealed record"].concat();
+        db.put_cf(&artifacts, b"test|artifact|2", &encrypted)?;
+        db.put_cf(
+            &IndexifyObjectsColumns::UnallocatedTasks.cf_db(db),
+            b"test|graph_A|missing|fn_a|task",
+            [],
+        )?;
+        let report = state.fsck(true).await?;
+        assert_eq!(
+            (
+                report.unreadable,
+                report.undecryptable,
+                report.dangling_index_entries
+            ),
+            (0, 1, 1)
+        );
+        assert_eq!(report.problems[0].kind, ProblemKind::Undecryptable);
+        assert!(!report.repaired);
+        assert_eq!(
+            db.get_cf(&artifacts, b"test|artifact|2")?,
+            Some(encrypted.clone())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+//! Online migration of the keys of a column to a new encoding, for stores
+//! too large to rewrite at startup, see `migrations` for those.
+//!
+//! The records are rewritten into a new column while the old one stays
+//! authoritative:
+//! - writers of the column write through `put` and `delete`, which also write
+//!   the new layout;
+//! - `KeyMigrator` copies the existing records in batches, its progress is kept
+//!   in the metadata column so that it resumes after a restart;
+//! - keyed reads of the column also read the new layout and report records
+//!   which differ as divergences in the metrics.
+//!
+//! Once the copy is done and no divergence is reported, the next release
+//! reads and writes the new column and drops the old one with its
+//! migration.
+
+use std::sync::Arc;
+
+use anyhow::Result;
+use rocksdb::{Direction, IteratorMode, Transaction, TransactionDB};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    serializer::{self, JsonEncode, JsonEncoder},
+    state_machine::IndexifyObjectsColumns,
+};
+
+pub struct KeyLayoutMigration {
+    pub name: &'static str,
+    pub from: IndexifyObjectsColumns,
+    pub to: IndexifyObjectsColumns,
+    /// Key of a record in the new layout, given its key in the old one.
+    pub new_key: fn(&[u8]) -> Vec<u8>,
+}
+
+/// Migrations in progress, at most one per column.
+pub const KEY_LAYOUT_MIGRATIONS: &[KeyLayoutMigration] = &[];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KeyMigrationProgress {
+    /// Last key of the old layout copied.
+    pub last_key: Option<Vec<u8>>,
+    pub copied: u64,
+    pub done: bool,
+}
+
+impl KeyLayoutMigration {
+    fn progress_key(&self) -> String {
+        format!("key_layout_migration|{}", self.name)
+    }
+
+    /// The record stored under `key` of the old layout, for the new one.
+    fn copy(&self, key: &[u8], value: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+        let new_key = (self.new_key)(key);
+        let value = serializer::rebind(
+            (self.from.as_ref(), key),
+            (self.to.as_ref(), &new_key),
+            value,
+        )?;
+        Ok((new_key, value))
+    }
+}
+
+/// Migration of the column, if it's being migrated.
+pub fn migrating(column: &IndexifyObjectsColumns) -> Option<&'static KeyLayoutMigration> {
+    KEY_LAYOUT_MIGRATIONS
+        .iter()
+        .find(|migration| migration.from == *column)
+}
+
+pub fn put(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    column: &IndexifyObjectsColumns,
+    key: &[u8],
+    value: &[u8],
+) -> Result<()> {
+    txn.put_cf(&column.cf_db(db), key, value)?;
+    if let Some(migration) = migrating(column) {
+        let (new_key, value) = migration.copy(key, value)?;
+        txn.put_cf(&migration.to.cf_db(db), new_key, value)?;
+    }
+    Ok(())
+}
+
+pub fn delete(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    column: &IndexifyObjectsColumns,
+    key: &[u8],
+) -> Result<()> {
+    txn.delete_cf(&column.cf_db(db), key)?;
+    if let Some(migration) = migrating(column) {
+        txn.delete_cf(&migration.to.cf_db(db), (migration.new_key)(key))?;
+    }
+    Ok(())
+}
+
+pub fn progress(
+    db: &Arc<TransactionDB>,
+    migration: &KeyLayoutMigration,
+) -> Result<KeyMigrationProgress> {
+    let key = migration.progress_key();
+    db.get_cf(
+        &IndexifyObjectsColumns::StateMachineMetadata.cf_db(db),
+        &key,
+    )?
+    .map(|value| JsonEncoder::decode(IndexifyObjectsColumns::StateMachineMetadata, &key, &value))
+    .transpose()
+    .map(Option::unwrap_or_default)
+}
+
+/// Copies the next `batch_size` records to the new layout. The records are
+/// locked while they're copied, so a concurrent write isn't overwritten
+/// with its previous value.
+pub fn copy_batch(
+    db: &Arc<TransactionDB>,
+    migration: &KeyLayoutMigration,
+    batch_size: usize,
+) -> Result<KeyMigrationProgress> {
+    let txn = db.transaction();
+    let metadata = IndexifyObjectsColumns::StateMachineMetadata.cf_db(db);
+    let progress_key = migration.progress_key();
+    let mut progress: KeyMigrationProgress = txn
+        .get_for_update_cf(&metadata, &progress_key, true)?
+        .map(|value| {
+            JsonEncoder::decode(
+                IndexifyObjectsColumns::StateMachineMetadata,
+                &progress_key,
+                &value,
+            )
+        })
+        .transpose()?
+        .unwrap_or_default();
+    if progress.done {
+        return Ok(progress);
+    }
+    let (from, to) = (migration.from.cf_db(db), migration.to.cf_db(db));
+    let mode = match &progress.last_key {
+        Some(last_key) => IteratorMode::From(last_key, Direction::Forward),
+        None => IteratorMode::Start,
+    };
+    let mut keys = Vec::new();
+    let mut exhausted = true;
+    for kv in txn.iterator_cf(&from, mode) {
+        let (key, _) = kv?;
+        if Some(&*key) == progress.last_key.as_deref() {
+            continue;
+        }
+        if keys.len() == batch_size {
+            exhausted = false;
+            break;
+        }
+        keys.push(key);
+    }
+    for key in &keys {
+        if let Some(value) = txn.get_for_update_cf(&from, key, true)? {
+            let (new_key, value) = migration.copy(key, &value)?;
+            txn.put_cf(&to, new_key, value)?;
+        }
+    }
+    progress.copied += keys.len() as u64;
+    if let Some(last_key) = keys.pop() {
+        progress.last_key = Some(last_key.to_vec());
+    }
+    progress.done = exhausted;
+    txn.put_cf(
+        &metadata,
+        &progress_key,
+        JsonEncoder::encode(
+            IndexifyObjectsColumns::StateMachineMetadata,
+            &progress_key,
+            &progress,
+        )?,
+    )?;
+    txn.commit()?;
+    Ok(progress)
+}
+
+/// Reads the copy of a record in the new layout, returns whether it differs
+/// from `value`, the record read from the old layout. Encrypted records are
+/// compared decrypted, their nonces differ. Records the copy hasn't reached
+/// yet only diverge if they're in the new layout.
+pub fn shadow_read(
+    db: &Arc<TransactionDB>,
+    migration: &KeyLayoutMigration,
+    key: &[u8],
+    value: Option<&[u8]>,
+) -> Result<bool> {
+    let new_key = (migration.new_key)(key);
+    let shadow = db.get_cf(&migration.to.cf_db(db), &new_key)?;
+    Ok(match shadow {
+        Some(shadow) => match value {
+            Some(value) => {
+                serializer::unseal(migration.from.as_ref(), key, value)? !=
+                    serializer::unseal(migration.to.as_ref(), &new_key, &shadow)?
+            }
+            None => true,
+        },
+        None if value.is_some() => {
+            let progress = progress(db, migration)?;
+            progress.done ||
+                progress
+                    .last_key
+                    .is_some_and(|last_key| key <= last_key.as_slice())
+        }
+        None => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::IndexifyState;
+
+    // Any two columns do in an empty store.
+    const TEST_MIGRATION: KeyLayoutMigration = KeyLayoutMigration {
+        name: "test",
+        from: IndexifyObjectsColumns::Stats,
+        to: IndexifyObjectsColumns::AnalyticsRecords,
+        new_key: |key| [b"v2|", key].concat(),
+    };
+
+    #[tokio::test]
+    async fn test_copy_and_shadow_read() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let db = &indexify_state.db;
+        let from = TEST_MIGRATION.from.cf_db(db);
+        let to = TEST_MIGRATION.to.cf_db(db);
+        for key in ["a", "b", "c"] {
+            db.put_cf(&from, key, key)?;
+        }
+
+        let progress = copy_batch(db, &TEST_MIGRATION, 2)?;
+        assert_eq!(progress.last_key.as_deref(), Some(&b"b"[..]));
+        assert!(!progress.done);
+        assert_eq!(db.get_cf(&to, "v2|a")?.unwrap(), b"a");
+        // Not copied yet.
+        assert!(!shadow_read(db, &TEST_MIGRATION, b"c", Some(b"c"))?);
+
+        // A write bypassing the new layout diverges.
+        db.put_cf(&from, "a", "changed")?;
+        assert!(shadow_read(db, &TEST_MIGRATION, b"a", Some(b"changed"))?);
+        assert!(!shadow_read(db, &TEST_MIGRATION, b"b", Some(b"b"))?);
+
+        let progress = copy_batch(db, &TEST_MIGRATION, 2)?;
+        assert!(progress.done);
+        assert_eq!(progress.copied, 3);
+        assert_eq!(db.get_cf(&to, "v2|c")?.unwrap(), b"c");
+        db.delete_cf(&to, "v2|c")?;
+        assert!(shadow_read(db, &TEST_MIGRATION, b"c", Some(b"c"))?);
+        assert_eq!(copy_batch(db, &TEST_MIGRATION, 2)?, progress);
+        Ok(())
+    }
+}
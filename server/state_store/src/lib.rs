@@ -4,29 +4,41 @@ use std::{
     path::PathBuf,
     pin::Pin,
     sync::{
-        atomic::{self, AtomicU64},
+        atomic::{self, AtomicBool, AtomicU64},
         Arc,
     },
+    time::{Duration, Instant, SystemTime},
     vec,
 };
 
 use anyhow::{anyhow, Result};
+use change_feed::{ChangeKind, ObjectChange};
 use data_model::{
+    activity::Activity,
+    audit::TaskIntervention,
+    subscriptions::NamespaceEvent,
     ChangeType,
+    ComputeGraph,
     ExecutorId,
+    GraphVersion,
     InvokeComputeGraphEvent,
     StateChange,
     StateChangeBuilder,
     StateChangeId,
     Task,
+    TaskDiagnostics,
+    TaskErrorClass,
+    TaskFailure,
     TaskFinishedEvent,
     TaskId,
+    TaskOutcome,
 };
 use futures::Stream;
 use indexify_utils::get_epoch_time_in_ms;
 use invocation_events::{InvocationFinishedEvent, InvocationStateChangeEvent};
+use options::StateStoreOptions;
 use requests::StateMachineUpdateRequest;
-use rocksdb::{ColumnFamilyDescriptor, Options, TransactionDB, TransactionDBOptions};
+use rocksdb::{ColumnFamilyDescriptor, Env, Transaction, TransactionDB, TransactionDBOptions};
 use state_machine::{IndexifyObjectsColumns, InvocationCompletion};
 use strum::IntoEnumIterator;
 use tokio::sync::{
@@ -35,9 +47,22 @@ use tokio::sync::{
     RwLock,
 };
 
+pub mod backup;
+pub mod change_feed;
+pub mod counters;
+pub mod export;
+pub mod fsck;
 pub mod invocation_events;
+pub mod key_migration;
+pub mod metrics;
+mod migrations;
+pub mod options;
+pub mod orphans;
+pub mod read_only;
 pub mod requests;
 pub mod scanner;
+pub mod scheduler_index;
+mod secondary_index;
 pub mod serializer;
 pub mod state_machine;
 pub mod test_state_store;
@@ -92,6 +117,10 @@ pub type StateChangeStream =
 
 pub struct InvocationChangeSubscriber {}
 
+/// How long the ids of requests written with `IndexifyState::write_once` are
+/// remembered, longer than clients keep retrying a request.
+pub const APPLIED_REQUESTS_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
 pub struct IndexifyState {
     pub db: Arc<TransactionDB>,
     pub executor_states: RwLock<HashMap<ExecutorId, ExecutorState>>,
@@ -101,29 +130,107 @@ pub struct IndexifyState {
     pub task_event_tx: tokio::sync::broadcast::Sender<InvocationStateChangeEvent>,
     pub gc_tx: tokio::sync::watch::Sender<()>,
     pub gc_rx: tokio::sync::watch::Receiver<()>,
+    pub subscription_events_tx: tokio::sync::watch::Sender<()>,
+    pub subscription_events_rx: tokio::sync::watch::Receiver<()>,
     pub system_tasks_tx: tokio::sync::watch::Sender<()>,
     pub system_tasks_rx: tokio::sync::watch::Receiver<()>,
+    pub namespace_deletion_tx: tokio::sync::watch::Sender<()>,
+    pub namespace_deletion_rx: tokio::sync::watch::Receiver<()>,
+    pub change_feed: broadcast::Sender<ObjectChange>,
+    // Bumped every time the set of registered executors changes so that
+    // readers can cache the executor catalog between registrations.
+    pub executors_version: AtomicU64,
+    // Time spent in the writes committed since the last call to
+    // `take_write_latency`, and their number.
+    pub write_latency_us: AtomicU64,
+    pub writes: AtomicU64,
+    // Set while the state store is over its disk quota, only essential
+    // writes are accepted then.
+    pub writes_blocked: AtomicBool,
+    // Set when finished tasks and invocations are exported to an analytics
+    // store, they are only recorded for the export then.
+    pub analytics_enabled: AtomicBool,
+    // Set when the alerts of graphs without owners go to a default channel,
+    // they are only raised then.
+    pub default_alert_channel: AtomicBool,
+    // Deleted graphs whose tasks are dropped by compaction.
+    pub tombstones: Arc<orphans::Tombstones>,
+    pub metrics: metrics::Metrics,
+}
+
+/// Side effects of the requests of a write, acted upon once it is committed.
+#[derive(Default)]
+struct WriteEffects<'a> {
+    allocated_tasks_by_executor: Vec<ExecutorId>,
+    executors_changed: bool,
+    tasks_finalized: HashMap<ExecutorId, Vec<TaskId>>,
+    invocations_finished: Vec<&'a requests::CreateTasksRequest>,
+    events_published: bool,
+    object_changes: Vec<ObjectChange>,
+    graphs_buried: Vec<String>,
 }
 
 impl IndexifyState {
     pub async fn new(path: PathBuf) -> Result<Arc<Self>> {
-        let (tx, rx) = tokio::sync::watch::channel(StateChangeId::new(std::u64::MAX));
+        Self::open(path, StateStoreOptions::default()).await
+    }
+
+    /// Opens the state store another process has open for writes as a
+    /// read-only follower, for tools which inspect a live server.
+    pub fn open_read_only(path: PathBuf) -> Result<read_only::ReadOnlyState> {
+        read_only::ReadOnlyState::open(&path)
+    }
+
+    pub async fn open(path: PathBuf, options: StateStoreOptions) -> Result<Arc<Self>> {
         fs::create_dir_all(path.clone())?;
-        let sm_column_families = IndexifyObjectsColumns::iter()
-            .map(|cf| ColumnFamilyDescriptor::new(cf.to_string(), Options::default()));
-        let mut db_opts = Options::default();
-        db_opts.create_missing_column_families(true);
-        db_opts.create_if_missing(true);
+        Self::open_in_env(path, options, None).await
+    }
+
+    /// Opens an empty state store whose files are kept in memory, for tests
+    /// which don't need a directory. Its state is lost once it's dropped.
+    pub async fn in_memory() -> Result<Arc<Self>> {
+        let env = Env::mem_env().map_err(|e| anyhow!("failed to create memory env: {}", e))?;
+        // Every memory env is a file system of its own, so the path of one
+        // store doesn't clash with another's.
+        Self::open_in_env(
+            PathBuf::from("/state"),
+            StateStoreOptions::default(),
+            Some(env),
+        )
+        .await
+    }
+
+    async fn open_in_env(
+        path: PathBuf,
+        options: StateStoreOptions,
+        env: Option<Env>,
+    ) -> Result<Arc<Self>> {
+        let (tx, rx) = tokio::sync::watch::channel(StateChangeId::new(std::u64::MAX));
+        let block_cache = options.block_cache();
+        let tombstones = Arc::new(orphans::Tombstones::default());
+        let sm_column_families = IndexifyObjectsColumns::iter().map(|cf| {
+            let mut cf_options = options.column_family_options(&cf, block_cache.as_ref());
+            if let Some(filter) = orphans::compaction_filter(&cf, tombstones.clone()) {
+                cf_options.set_compaction_filter("orphans", filter);
+            }
+            ColumnFamilyDescriptor::new(cf.to_string(), cf_options)
+        });
+        let mut db_options = options.db_options();
+        if let Some(env) = &env {
+            db_options.set_env(env);
+        }
         let db: TransactionDB = TransactionDB::open_cf_descriptors(
-            &db_opts,
+            &db_options,
             &TransactionDBOptions::default(),
             path,
             sm_column_families,
         )
         .map_err(|e| anyhow!("failed to open db: {}", e))?;
         let (gc_tx, gc_rx) = tokio::sync::watch::channel(());
+        let (subscription_events_tx, subscription_events_rx) = tokio::sync::watch::channel(());
         let (task_event_tx, _) = tokio::sync::broadcast::channel(100);
         let (system_tasks_tx, system_tasks_rx) = tokio::sync::watch::channel(());
+        let (namespace_deletion_tx, namespace_deletion_rx) = tokio::sync::watch::channel(());
         let s = Arc::new(Self {
             db: Arc::new(db),
             state_change_tx: tx,
@@ -133,10 +240,34 @@ impl IndexifyState {
             task_event_tx,
             gc_tx,
             gc_rx,
+            subscription_events_tx,
+            subscription_events_rx,
             system_tasks_tx,
             system_tasks_rx,
+            namespace_deletion_tx,
+            namespace_deletion_rx,
+            change_feed: change_feed::new_change_feed(),
+            executors_version: AtomicU64::new(0),
+            write_latency_us: AtomicU64::new(0),
+            writes: AtomicU64::new(0),
+            writes_blocked: AtomicBool::new(false),
+            analytics_enabled: AtomicBool::new(false),
+            default_alert_channel: AtomicBool::new(false),
+            tombstones,
+            metrics: metrics::Metrics::new(),
         });
 
+        migrations::migrate(&s.db)?;
+        s.tombstones.load(&s.db)?;
+        state_machine::queue_unprocessed_state_changes(s.db.clone())?;
+        // Stores written before the index existed derive it once, later
+        // restarts resume from the persisted one.
+        if s.reader().scheduler_index_persisted()? {
+            tracing::info!("resuming scheduling from the persisted scheduler index");
+        } else {
+            scheduler_index::verify(&s.db)?;
+        }
+
         let executors = s.reader().get_all_executors()?;
         for executor in executors.iter() {
             s.executor_states
@@ -157,15 +288,222 @@ impl IndexifyState {
         self.gc_rx.clone()
     }
 
+    pub fn get_subscription_events_watcher(&self) -> Receiver<()> {
+        self.subscription_events_rx.clone()
+    }
+
     pub fn get_system_tasks_watcher(&self) -> Receiver<()> {
         self.system_tasks_rx.clone()
     }
 
+    pub fn get_namespace_deletion_watcher(&self) -> Receiver<()> {
+        self.namespace_deletion_rx.clone()
+    }
+
+    /// Checks the persisted scheduler index against the allocated tasks and
+    /// rebuilds it when they disagree. Scans every allocation, so it runs off
+    /// the async runtime. Returns whether the index was rebuilt.
+    pub async fn verify_scheduler_index(&self) -> Result<bool> {
+        let db = self.db.clone();
+        tokio::task::spawn_blocking(move || scheduler_index::verify(&db)).await?
+    }
+
+    /// Counts the objects of a graph, correcting its maintained counters.
+    /// Scans all of the graph's invocations, outputs and tasks.
+    pub async fn recount_graph(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+    ) -> Result<counters::GraphCounts> {
+        let db = self.db.clone();
+        let (namespace, compute_graph) = (namespace.to_string(), compute_graph.to_string());
+        tokio::task::spawn_blocking(move || counters::recount(&db, &namespace, &compute_graph))
+            .await?
+    }
+
+    /// Checks the records and indexes of the state store, repairing what's
+    /// wrong when `repair` is set, see `fsck::fsck`.
+    pub async fn fsck(&self, repair: bool) -> Result<fsck::FsckReport> {
+        let db = self.db.clone();
+        let tombstones = self.tombstones.clone();
+        tokio::task::spawn_blocking(move || fsck::fsck(&db, &tombstones, repair)).await?
+    }
+
+    /// Removes the tasks and outputs left by deleted graphs and invocations,
+    /// see `orphans::reconcile`.
+    pub async fn reconcile_orphans(&self) -> Result<orphans::OrphansRemoved> {
+        let db = self.db.clone();
+        let tombstones = self.tombstones.clone();
+        tokio::task::spawn_blocking(move || orphans::reconcile(&db, &tombstones, false)).await?
+    }
+
+    pub fn executors_version(&self) -> u64 {
+        self.executors_version.load(atomic::Ordering::Acquire)
+    }
+
+    /// Mean latency of the writes committed since the last call, none when
+    /// nothing was written meanwhile.
+    pub fn take_write_latency(&self) -> Option<Duration> {
+        let writes = self.writes.swap(0, atomic::Ordering::AcqRel);
+        let latency_us = self.write_latency_us.swap(0, atomic::Ordering::AcqRel);
+        if writes == 0 {
+            return None;
+        }
+        Some(Duration::from_micros(latency_us / writes))
+    }
+
     pub async fn write(&self, request: StateMachineUpdateRequest) -> Result<()> {
-        let mut allocated_tasks_by_executor = Vec::new();
-        let mut tasks_finalized: HashMap<ExecutorId, Vec<TaskId>> = HashMap::new();
-        let txn = self.db.transaction();
-        let new_state_changes = match &request.payload {
+        self.write_request(request, None).await.map(|_| ())
+    }
+
+    /// Writes a request identified by the client that sent it, at most once.
+    /// Retries of a request whose id was applied before are acknowledged
+    /// without being applied again, returns whether this one was applied.
+    /// Ids are remembered for `APPLIED_REQUESTS_RETENTION`.
+    pub async fn write_once(
+        &self,
+        request_id: &str,
+        request: StateMachineUpdateRequest,
+    ) -> Result<bool> {
+        self.write_request(request, Some(request_id)).await
+    }
+
+    /// Replaces the definition of a compute graph if it's still at the
+    /// expected version, the optimistic counterpart of writing a
+    /// `CreateComputeGraph` request without a precondition. Fails with a
+    /// `GraphVersionConflict` carrying the current version when another
+    /// writer changed or deleted the graph meanwhile.
+    pub async fn update_compute_graph(
+        &self,
+        expected_version: GraphVersion,
+        compute_graph: ComputeGraph,
+    ) -> Result<()> {
+        self.write(StateMachineUpdateRequest {
+            payload: requests::RequestPayload::CreateComputeGraph(
+                requests::CreateComputeGraphRequest {
+                    namespace: compute_graph.namespace.clone(),
+                    compute_graph,
+                    precondition: Some(requests::GraphPrecondition::Version(expected_version)),
+                },
+            ),
+            state_changes_processed: vec![],
+        })
+        .await
+    }
+
+    async fn write_request(
+        &self,
+        request: StateMachineUpdateRequest,
+        request_id: Option<&str>,
+    ) -> Result<bool> {
+        request.payload.validate()?;
+        if self.writes_blocked.load(atomic::Ordering::Acquire) && !request.payload.is_essential() {
+            return Err(requests::DiskQuotaExceeded.into());
+        }
+        let payloads = request.payload.payloads();
+        for namespace in payloads.iter().filter_map(|p| p.written_namespace()) {
+            if let Some(namespace) = self.reader().get_namespace(namespace)? {
+                if namespace.deletion.is_some() {
+                    return Err(anyhow!("namespace {} is being deleted", namespace.name));
+                }
+            }
+        }
+        let mut effects = WriteEffects::default();
+        let started = Instant::now();
+        let mut txn = self.db.transaction();
+        if let Some(request_id) = request_id {
+            let request_name = request.payload.as_ref();
+            if !state_machine::record_applied_request(&self.db, &txn, request_id, request_name)? {
+                tracing::info!("request {} was applied before, skipping it", request_id);
+                return Ok(false);
+            }
+        }
+        let mut new_state_changes = Vec::new();
+        // The transaction isn't Sync, it is moved through the requests rather
+        // than borrowed across their awaits.
+        for payload in &payloads {
+            let state_changes;
+            (txn, state_changes) = self.apply(txn, payload, &mut effects).await?;
+            new_state_changes.extend(state_changes);
+        }
+        let WriteEffects {
+            allocated_tasks_by_executor,
+            executors_changed,
+            tasks_finalized,
+            invocations_finished,
+            events_published,
+            object_changes,
+            graphs_buried,
+        } = effects;
+        if !new_state_changes.is_empty() {
+            state_machine::save_state_changes(self.db.clone(), &txn, &new_state_changes)?;
+        }
+        state_machine::mark_state_changes_processed(
+            self.db.clone(),
+            &txn,
+            &request.state_changes_processed.clone(),
+        )?;
+        txn.commit()?;
+        // Compaction only drops the tasks of a deleted graph once its deletion
+        // is committed.
+        for prefix in graphs_buried {
+            self.tombstones.insert(&prefix);
+        }
+        let write_latency = started.elapsed();
+        self.write_latency_us
+            .fetch_add(write_latency.as_micros() as u64, atomic::Ordering::AcqRel);
+        self.writes.fetch_add(1, atomic::Ordering::AcqRel);
+        metrics::observe(
+            &self.metrics.write_latency,
+            write_latency,
+            request.payload.trace(),
+        );
+        self.metrics
+            .observe_write(request.payload.as_ref(), write_latency);
+        self.observe_scheduling(&payloads, &invocations_finished);
+        if events_published {
+            let _ = self.subscription_events_tx.send(());
+        }
+        if executors_changed {
+            self.executors_version
+                .fetch_add(1, atomic::Ordering::AcqRel);
+        }
+        for executor_id in allocated_tasks_by_executor {
+            if let Some(executor_state) = self.executor_states.write().await.get_mut(&executor_id) {
+                executor_state.notify();
+            }
+        }
+        for (executor_id, tasks) in tasks_finalized {
+            if let Some(executor_state) = self.executor_states.write().await.get_mut(&executor_id) {
+                for task_id in tasks {
+                    executor_state.removed(task_id);
+                }
+            }
+        }
+        for change in object_changes {
+            let _ = self.change_feed.send(change);
+        }
+        for payload in &payloads {
+            self.handle_invocation_state_changes(payload).await;
+        }
+        for state_change in new_state_changes {
+            self.state_change_tx.send(state_change.id).unwrap();
+        }
+        Ok(true)
+    }
+
+    /// Applies a request to the transaction, returns the state changes it
+    /// causes.
+    async fn apply<'a, 't>(
+        &self,
+        txn: Transaction<'t, TransactionDB>,
+        payload: &'a requests::RequestPayload,
+        effects: &mut WriteEffects<'a>,
+    ) -> Result<(Transaction<'t, TransactionDB>, Vec<StateChange>)> {
+        let state_changes = match payload {
+            requests::RequestPayload::WriteBatch(_) => {
+                return Err(anyhow!("write batches can't be nested"));
+            }
             requests::RequestPayload::InvokeComputeGraph(invoke_compute_graph_request) => {
                 let state_changes = self
                     .invoke_compute_graph(&invoke_compute_graph_request)
@@ -175,6 +513,12 @@ impl IndexifyState {
                     &txn,
                     &invoke_compute_graph_request,
                 )?;
+                effects.object_changes.push(ObjectChange::invocation(
+                    ChangeKind::Created,
+                    &invoke_compute_graph_request.namespace,
+                    &invoke_compute_graph_request.compute_graph_name,
+                    &invoke_compute_graph_request.invocation_payload.id,
+                ));
                 state_changes
             }
             requests::RequestPayload::RerunComputeGraph(rerun_compute_graph_request) => {
@@ -212,6 +556,12 @@ impl IndexifyState {
                     &txn,
                     rerun_invocation_request.clone(),
                 )?;
+                effects.object_changes.push(ObjectChange::invocation(
+                    ChangeKind::Updated,
+                    &rerun_invocation_request.namespace,
+                    &rerun_invocation_request.compute_graph_name,
+                    &rerun_invocation_request.invocation_id,
+                ));
                 for state_change in &mut state_changes {
                     let last_change_id = self
                         .last_state_change_id
@@ -221,20 +571,132 @@ impl IndexifyState {
                 state_changes
             }
             requests::RequestPayload::FinalizeTask(finalize_task) => {
+                if state_machine::task_result_acknowledged(self.db.clone(), &txn, finalize_task)? {
+                    tracing::info!(
+                        "result of task {} was already committed, ignoring it",
+                        finalize_task.task_id
+                    );
+                    return Ok((txn, vec![]));
+                }
                 let state_changes = self.finalize_task(&finalize_task).await?;
-                state_machine::mark_task_completed(self.db.clone(), &txn, finalize_task.clone())?;
-                tasks_finalized
+                let task = state_machine::mark_task_completed(
+                    self.db.clone(),
+                    &txn,
+                    finalize_task.clone(),
+                )?;
+                if self.analytics_enabled.load(atomic::Ordering::Relaxed) {
+                    state_machine::record_task_analytics(
+                        self.db.clone(),
+                        &txn,
+                        &task,
+                        &finalize_task.executor_id,
+                    )?;
+                }
+                state_machine::raise_task_alerts(
+                    self.db.clone(),
+                    &txn,
+                    finalize_task,
+                    self.default_alert_channel.load(atomic::Ordering::Relaxed),
+                )?;
+                effects.events_published |=
+                    state_machine::publish_task_completed(self.db.clone(), &txn, finalize_task)?;
+                effects
+                    .tasks_finalized
                     .entry(finalize_task.executor_id.clone())
                     .or_default()
                     .push(finalize_task.task_id.clone());
+                effects.object_changes.push(ObjectChange::task(
+                    ChangeKind::Updated,
+                    &finalize_task.namespace,
+                    &finalize_task.compute_graph,
+                    &finalize_task.invocation_id,
+                    &finalize_task.compute_fn,
+                    &finalize_task.task_id,
+                ));
                 state_changes
             }
+            requests::RequestPayload::RejectTask(request) => {
+                let rejected_by_all = state_machine::reject_task(self.db.clone(), &txn, request)?;
+                effects
+                    .tasks_finalized
+                    .entry(request.executor_id.clone())
+                    .or_default()
+                    .push(request.task_id.clone());
+                effects.object_changes.push(ObjectChange::task(
+                    ChangeKind::Updated,
+                    &request.namespace,
+                    &request.compute_graph,
+                    &request.invocation_id,
+                    &request.compute_fn,
+                    &request.task_id,
+                ));
+                if rejected_by_all {
+                    let finalize_task = requests::FinalizeTaskRequest {
+                        namespace: request.namespace.clone(),
+                        compute_graph: request.compute_graph.clone(),
+                        compute_fn: request.compute_fn.clone(),
+                        invocation_id: request.invocation_id.clone(),
+                        task_id: request.task_id.clone(),
+                        node_outputs: vec![],
+                        task_outcome: TaskOutcome::Failure,
+                        executor_id: request.executor_id.clone(),
+                        diagnostics: Some(unretryable_failure(TaskErrorClass::Infrastructure)),
+                        output_checks: None,
+                    };
+                    self.fail_task(&txn, effects, &finalize_task).await?
+                } else {
+                    self.reject_task_events(request)
+                }
+            }
             requests::RequestPayload::CreateNameSpace(namespace_request) => {
-                state_machine::create_namespace(self.db.clone(), &namespace_request)?;
+                state_machine::create_namespace(self.db.clone(), &txn, namespace_request)?;
+                vec![]
+            }
+            requests::RequestPayload::DeleteNamespace(request) => {
+                state_machine::mark_namespace_deleting(self.db.clone(), &txn, request)?;
+                let _ = self.namespace_deletion_tx.send(());
+                vec![]
+            }
+            requests::RequestPayload::AdvanceNamespaceDeletion(request) => {
+                let cancelled =
+                    state_machine::advance_namespace_deletion(self.db.clone(), &txn, request)?;
+                for (executor_id, task_id) in cancelled {
+                    effects
+                        .tasks_finalized
+                        .entry(executor_id)
+                        .or_default()
+                        .push(task_id);
+                }
+                let _ = self.gc_tx.send(());
                 vec![]
             }
             requests::RequestPayload::CreateComputeGraph(req) => {
-                state_machine::create_compute_graph(self.db.clone(), req.compute_graph.clone())?;
+                let key =
+                    data_model::ComputeGraph::key_from(&req.namespace, &req.compute_graph.name);
+                let kind = match txn
+                    .get_cf(&IndexifyObjectsColumns::ComputeGraphs.cf_db(&self.db), &key)?
+                {
+                    Some(_) => ChangeKind::Updated,
+                    None => ChangeKind::Created,
+                };
+                effects.object_changes.push(ObjectChange::compute_graph(
+                    kind,
+                    &req.namespace,
+                    &req.compute_graph.name,
+                ));
+                // Stops compaction from dropping the tasks of the new graph even
+                // when the write fails, the old tasks are then left to
+                // `reconcile_orphans`.
+                self.tombstones.remove(
+                    orphans::Tombstones::graph_prefix(&req.namespace, &req.compute_graph.name)
+                        .as_bytes(),
+                );
+                state_machine::create_compute_graph(
+                    self.db.clone(),
+                    &txn,
+                    req.compute_graph.clone(),
+                    req.precondition,
+                )?;
                 vec![]
             }
             requests::RequestPayload::DeleteComputeGraph(request) => {
@@ -244,16 +706,94 @@ impl IndexifyState {
                     &request.namespace,
                     &request.name,
                 )?;
+                effects.object_changes.push(ObjectChange::compute_graph(
+                    ChangeKind::Deleted,
+                    &request.namespace,
+                    &request.name,
+                ));
+                effects
+                    .graphs_buried
+                    .push(orphans::Tombstones::graph_prefix(
+                        &request.namespace,
+                        &request.name,
+                    ));
                 self.gc_tx.send(()).unwrap();
                 vec![]
             }
+            requests::RequestPayload::StartCanary(request) => {
+                state_machine::start_canary(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::FinishCanary(request) => {
+                if state_machine::finish_canary(self.db.clone(), &txn, request)?.is_some() {
+                    effects.object_changes.push(ObjectChange::compute_graph(
+                        ChangeKind::Updated,
+                        &request.namespace,
+                        &request.compute_graph,
+                    ));
+                }
+                vec![]
+            }
             requests::RequestPayload::DeleteInvocation(request) => {
-                state_machine::delete_input_data_object(self.db.clone(), &request)?;
+                let cancelled =
+                    state_machine::delete_input_data_object(self.db.clone(), &txn, request)?;
+                for (executor_id, task_id) in cancelled {
+                    effects
+                        .tasks_finalized
+                        .entry(executor_id)
+                        .or_default()
+                        .push(task_id);
+                }
+                effects.object_changes.push(ObjectChange::invocation(
+                    ChangeKind::Deleted,
+                    &request.namespace,
+                    &request.compute_graph,
+                    &request.invocation_id,
+                ));
+                let _ = self.gc_tx.send(());
+                vec![]
+            }
+            requests::RequestPayload::ExpireInvocation(request) => {
+                if state_machine::expire_invocation(self.db.clone(), &txn, request)? {
+                    effects.object_changes.push(ObjectChange::invocation(
+                        ChangeKind::Deleted,
+                        &request.namespace,
+                        &request.compute_graph,
+                        &request.invocation_id,
+                    ));
+                    let _ = self.gc_tx.send(());
+                }
+                vec![]
+            }
+            requests::RequestPayload::ExpireOutputs(request) => {
+                if state_machine::expire_outputs(self.db.clone(), &txn, request)? > 0 {
+                    let _ = self.gc_tx.send(());
+                }
+                vec![]
+            }
+            requests::RequestPayload::PinInvocationOutputs(request) => {
+                state_machine::pin_invocation_outputs(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::ArchiveInvocation(request) => {
+                state_machine::archive_invocation(self.db.clone(), &txn, &request)?;
+                let archive = &request.archive;
+                effects.object_changes.push(ObjectChange::invocation(
+                    ChangeKind::Deleted,
+                    &archive.namespace,
+                    &archive.compute_graph_name,
+                    &archive.invocation_id,
+                ));
                 vec![]
             }
             requests::RequestPayload::SchedulerUpdate(request) => {
                 let new_state_changes = self.change_events_for_scheduler_update(&request);
                 for req in &request.task_requests {
+                    effects.object_changes.extend(
+                        req.tasks
+                            .iter()
+                            .map(|task| ObjectChange::for_task(ChangeKind::Created, task)),
+                    );
                     match state_machine::create_tasks(self.db.clone(), &txn, req)? {
                         Some(completion) => {
                             if let Err(err) = self.task_event_tx.send(
@@ -272,6 +812,35 @@ impl IndexifyState {
                                 // Notify the system task handler that it can start new tasks since
                                 // a task was completed
                                 let _ = self.system_tasks_tx.send(());
+                            } else {
+                                effects.events_published |= state_machine::publish_event(
+                                    self.db.clone(),
+                                    &txn,
+                                    &req.namespace,
+                                    NamespaceEvent::InvocationFinished {
+                                        compute_graph: req.compute_graph.clone(),
+                                        invocation_id: req.invocation_id.clone(),
+                                    },
+                                )?;
+                                state_machine::record_activity(
+                                    &self.db,
+                                    &txn,
+                                    Some(&req.namespace),
+                                    Activity::InvocationFinished {
+                                        compute_graph: req.compute_graph.clone(),
+                                        invocation_id: req.invocation_id.clone(),
+                                    },
+                                )?;
+                                if self.analytics_enabled.load(atomic::Ordering::Relaxed) {
+                                    state_machine::record_invocation_analytics(
+                                        self.db.clone(),
+                                        &txn,
+                                        &req.namespace,
+                                        &req.compute_graph,
+                                        &req.invocation_id,
+                                    )?;
+                                }
+                                effects.invocations_finished.push(req);
                             }
                         }
                         None => {}
@@ -289,7 +858,13 @@ impl IndexifyState {
                         &allocation.task,
                         &allocation.executor,
                     )?;
-                    allocated_tasks_by_executor.push(allocation.executor.clone());
+                    effects
+                        .allocated_tasks_by_executor
+                        .push(allocation.executor.clone());
+                    effects.object_changes.push(ObjectChange::for_task(
+                        ChangeKind::Updated,
+                        &allocation.task,
+                    ));
                 }
                 new_state_changes
             }
@@ -300,6 +875,7 @@ impl IndexifyState {
                     entry.num_registered += 1;
                 }
                 state_machine::register_executor(self.db.clone(), &txn, &request)?;
+                effects.executors_changed = true;
                 self.register_executor(&request)
             }
             requests::RequestPayload::DeregisterExecutor(request) => {
@@ -321,55 +897,183 @@ impl IndexifyState {
                 if removed {
                     tracing::info!("de-registering executor: {}", request.executor_id);
                     state_machine::deregister_executor(self.db.clone(), &txn, &request)?;
+                    effects.executors_changed = true;
                 }
                 state_changes
             }
+            requests::RequestPayload::AddGcUrls(urls) => {
+                state_machine::add_gc_urls(self.db.clone(), &txn, urls)?;
+                let _ = self.gc_tx.send(());
+                vec![]
+            }
             requests::RequestPayload::RemoveGcUrls(urls) => {
                 state_machine::remove_gc_urls(self.db.clone(), &txn, urls.clone())?;
                 vec![]
             }
-        };
-        if !new_state_changes.is_empty() {
-            state_machine::save_state_changes(self.db.clone(), &txn, &new_state_changes)?;
-        }
-        state_machine::mark_state_changes_processed(
-            self.db.clone(),
-            &txn,
-            &request.state_changes_processed.clone(),
-        )?;
-        txn.commit()?;
-        for executor_id in allocated_tasks_by_executor {
-            self.executor_states
-                .write()
-                .await
-                .get_mut(&executor_id)
-                .map(|executor_state| {
-                    executor_state.notify();
-                });
-        }
-        for (executor_id, tasks) in tasks_finalized {
-            self.executor_states
-                .write()
-                .await
-                .get_mut(&executor_id)
-                .map(|executor_state| {
-                    for task_id in tasks {
-                        executor_state.removed(task_id);
+            requests::RequestPayload::RemoveAnalyticsRecords(ids) => {
+                state_machine::remove_analytics_records(self.db.clone(), &txn, ids)?;
+                vec![]
+            }
+            requests::RequestPayload::RemoveAlerts(ids) => {
+                state_machine::remove_alerts(self.db.clone(), &txn, ids)?;
+                vec![]
+            }
+            requests::RequestPayload::PutUrlFetch(fetch) => {
+                state_machine::put_url_fetch(self.db.clone(), &txn, fetch)?;
+                vec![]
+            }
+            requests::RequestPayload::RecordActivity(request) => {
+                state_machine::record_activity(
+                    &self.db,
+                    &txn,
+                    request.namespace.as_deref(),
+                    request.activity.clone(),
+                )?;
+                vec![]
+            }
+            requests::RequestPayload::PruneActivity(request) => {
+                state_machine::prune_activity(self.db.clone(), &txn, request.recorded_before)?;
+                vec![]
+            }
+            requests::RequestPayload::PruneAppliedRequests(request) => {
+                state_machine::prune_applied_requests(
+                    self.db.clone(),
+                    &txn,
+                    request.applied_before,
+                )?;
+                vec![]
+            }
+            requests::RequestPayload::RegisterArtifact(request) => {
+                state_machine::register_artifact(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::DeleteArtifact(request) => {
+                state_machine::delete_artifact(self.db.clone(), &txn, request)?;
+                let _ = self.gc_tx.send(());
+                vec![]
+            }
+            requests::RequestPayload::InterveneTask(request) => {
+                let allocated_to = state_machine::intervene_task(self.db.clone(), &txn, request)?;
+                effects.object_changes.push(ObjectChange::task(
+                    ChangeKind::Updated,
+                    &request.namespace,
+                    &request.compute_graph,
+                    &request.invocation_id,
+                    &request.compute_fn,
+                    &request.task_id,
+                ));
+                let reallocated = match &request.intervention {
+                    TaskIntervention::SetPriority { .. } => false,
+                    TaskIntervention::Assign { executor_id } => {
+                        effects
+                            .allocated_tasks_by_executor
+                            .push(executor_id.clone());
+                        allocated_to.as_ref() != Some(executor_id)
+                    }
+                    _ => true,
+                };
+                if let (true, Some(executor_id)) = (reallocated, &allocated_to) {
+                    effects
+                        .tasks_finalized
+                        .entry(executor_id.clone())
+                        .or_default()
+                        .push(request.task_id.clone());
+                }
+                match &request.intervention {
+                    TaskIntervention::Fail => {
+                        let finalize_task = requests::FinalizeTaskRequest {
+                            namespace: request.namespace.clone(),
+                            compute_graph: request.compute_graph.clone(),
+                            compute_fn: request.compute_fn.clone(),
+                            invocation_id: request.invocation_id.clone(),
+                            task_id: request.task_id.clone(),
+                            node_outputs: vec![],
+                            task_outcome: TaskOutcome::Failure,
+                            executor_id: allocated_to.unwrap_or_default(),
+                            diagnostics: Some(unretryable_failure(TaskErrorClass::Unknown)),
+                            output_checks: None,
+                        };
+                        self.fail_task(&txn, effects, &finalize_task).await?
                     }
-                });
+                    TaskIntervention::Assign { .. } => vec![],
+                    _ => self.task_requeued_events(request),
+                }
+            }
+            requests::RequestPayload::UpdateExecutorArtifacts(request) => {
+                effects.executors_changed =
+                    state_machine::update_executor_artifacts(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::CreateSubscription(request) => {
+                state_machine::create_subscription(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::DeleteSubscription(request) => {
+                state_machine::delete_subscription(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::AckSubscriptionEvents(request) => {
+                state_machine::ack_subscription_events(self.db.clone(), &txn, request)?;
+                vec![]
+            }
+            requests::RequestPayload::ReplaySubscription(request) => {
+                state_machine::replay_subscription(self.db.clone(), &txn, request)?;
+                effects.events_published = true;
+                vec![]
+            }
+        };
+        Ok((txn, state_changes))
+    }
+
+    fn observe_scheduling(
+        &self,
+        payloads: &[&requests::RequestPayload],
+        invocations_finished: &[&requests::CreateTasksRequest],
+    ) {
+        let placements = payloads.iter().flat_map(|payload| match payload {
+            requests::RequestPayload::SchedulerUpdate(update) => update.allocations.as_slice(),
+            _ => &[],
+        });
+        for placement in placements {
+            let delay = SystemTime::now()
+                .duration_since(placement.task.creation_time)
+                .unwrap_or_default();
+            metrics::observe(
+                &self.metrics.task_scheduling_delay,
+                delay,
+                Some(&placement.task.trace),
+            );
         }
-        self.handle_invocation_state_changes(&request).await;
-        for state_change in new_state_changes {
-            self.state_change_tx.send(state_change.id).unwrap();
+        for finished in invocations_finished {
+            let invocation = match self.reader().invocation_payload(
+                &finished.namespace,
+                &finished.compute_graph,
+                &finished.invocation_id,
+            ) {
+                Ok(invocation) => invocation,
+                Err(e) => {
+                    tracing::warn!(
+                        "unable to read finished invocation {}: {:?}",
+                        finished.invocation_id,
+                        e
+                    );
+                    continue;
+                }
+            };
+            let latency = get_epoch_time_in_ms().saturating_sub(invocation.created_at);
+            metrics::observe(
+                &self.metrics.invocation_latency,
+                Duration::from_millis(latency),
+                Some(&invocation.trace),
+            );
         }
-        Ok(())
     }
 
-    async fn handle_invocation_state_changes(&self, update_request: &StateMachineUpdateRequest) {
+    async fn handle_invocation_state_changes(&self, payload: &requests::RequestPayload) {
         if self.task_event_tx.receiver_count() == 0 {
             return;
         }
-        match &update_request.payload {
+        match payload {
             requests::RequestPayload::FinalizeTask(task_finished_event) => {
                 let ev =
                     InvocationStateChangeEvent::from_task_finished(task_finished_event.clone());
@@ -500,6 +1204,58 @@ impl IndexifyState {
         vec![state_change]
     }
 
+    /// Fails a task which didn't run, without retrying it.
+    async fn fail_task(
+        &self,
+        txn: &Transaction<'_, TransactionDB>,
+        effects: &mut WriteEffects<'_>,
+        finalize_task: &requests::FinalizeTaskRequest,
+    ) -> Result<Vec<StateChange>> {
+        let state_changes = self.finalize_task(finalize_task).await?;
+        effects.events_published |=
+            state_machine::publish_task_completed(self.db.clone(), txn, finalize_task)?;
+        let task = state_machine::mark_task_completed(self.db.clone(), txn, finalize_task.clone())?;
+        if self.analytics_enabled.load(atomic::Ordering::Relaxed) {
+            state_machine::record_task_analytics(
+                self.db.clone(),
+                txn,
+                &task,
+                &finalize_task.executor_id,
+            )?;
+        }
+        Ok(state_changes)
+    }
+
+    fn reject_task_events(&self, request: &requests::RejectTaskRequest) -> Vec<StateChange> {
+        let last_change_id = self
+            .last_state_change_id
+            .fetch_add(1, atomic::Ordering::Relaxed);
+        let state_change = StateChangeBuilder::default()
+            .change_type(ChangeType::TaskRejected)
+            .created_at(get_epoch_time_in_ms())
+            .object_id(request.task_id.to_string())
+            .id(StateChangeId::new(last_change_id))
+            .processed_at(None)
+            .build()
+            .unwrap();
+        vec![state_change]
+    }
+
+    fn task_requeued_events(&self, request: &requests::InterveneTaskRequest) -> Vec<StateChange> {
+        let last_change_id = self
+            .last_state_change_id
+            .fetch_add(1, atomic::Ordering::Relaxed);
+        let state_change = StateChangeBuilder::default()
+            .change_type(ChangeType::TaskRequeued)
+            .created_at(get_epoch_time_in_ms())
+            .object_id(request.task_id.to_string())
+            .id(StateChangeId::new(last_change_id))
+            .processed_at(None)
+            .build()
+            .unwrap();
+        vec![state_change]
+    }
+
     fn register_executor(&self, request: &requests::RegisterExecutorRequest) -> Vec<StateChange> {
         let last_change_id = self
             .last_state_change_id
@@ -515,8 +1271,15 @@ impl IndexifyState {
         vec![state_change]
     }
 
-    pub fn reader(&self) -> scanner::StateReader {
+    pub fn reader(&self) -> scanner::StateReader<'static> {
         scanner::StateReader::new(self.db.clone())
+            .with_change_feed(self.change_feed.clone())
+            .with_metrics(self.metrics.clone())
+    }
+
+    /// Reader over a point-in-time view of the state.
+    pub fn snapshot_reader(&self) -> scanner::StateReader<'_> {
+        scanner::StateReader::with_snapshot(&self.db)
     }
 
     pub fn task_event_stream(&self) -> broadcast::Receiver<InvocationStateChangeEvent> {
@@ -524,6 +1287,21 @@ impl IndexifyState {
     }
 }
 
+fn unretryable_failure(error_class: TaskErrorClass) -> TaskDiagnostics {
+    TaskDiagnostics {
+        exception: None,
+        stdout: None,
+        stderr: None,
+        failure: Some(TaskFailure {
+            error_class,
+            retryable: false,
+            exit_code: None,
+        }),
+        resource_usage: None,
+        output_check_violations: vec![],
+    }
+}
+
 pub fn task_stream(state: Arc<IndexifyState>, executor: ExecutorId, limit: usize) -> TaskStream {
     let stream = async_stream::stream! {
         let mut rx = state
@@ -568,21 +1346,48 @@ pub fn task_stream(state: Arc<IndexifyState>, executor: ExecutorId, limit: usize
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use std::collections::{BTreeSet, HashMap};
 
     use data_model::{
-        test_objects::tests::{create_mock_task, mock_graph_a, TEST_NAMESPACE},
+        edge_transforms::{EdgeTransform, TransformStep},
+        near_duplicates::{ContentFingerprint, NearDuplicateAction, NearDuplicatePolicy},
+        test_objects::tests::{
+            create_mock_task,
+            mock_executor,
+            mock_graph_a,
+            mock_invocation_payload,
+            mock_node_fn_output_fn_a,
+            TEST_NAMESPACE,
+        },
         ComputeGraph,
+        ContentSource,
+        GpuAllocation,
         GraphInvocationCtxBuilder,
         Namespace,
+        Node,
+        OutputPayload,
+        ReprocessingPolicy,
+        ResourceUsage,
+        Task,
+        TaskDiagnostics,
+        TaskOutcome,
+        TraceContext,
     };
     use futures::StreamExt;
     use requests::{
+        ContentVersionError,
         CreateComputeGraphRequest,
         DeleteComputeGraphRequest,
+        FinishCanaryRequest,
+        GraphPrecondition,
+        GraphVersionConflict,
+        InvokeComputeGraphRequest,
+        NamespacePlacementConflict,
         ReductionTasks,
         SchedulerUpdateRequest,
+        StartCanaryRequest,
         TaskPlacement,
+        WriteBatchRequest,
     };
     use tempfile::TempDir;
     use tokio;
@@ -603,6 +1408,10 @@ mod tests {
             .write(StateMachineUpdateRequest {
                 payload: RequestPayload::CreateNameSpace(NamespaceRequest {
                     name: "namespace1".to_string(),
+                    blob_storage: None,
+                    scheduling_weight: None,
+                    reprocessing_policy: Default::default(),
+                    near_duplicates: None,
                 }),
                 state_changes_processed: vec![],
             })
@@ -611,6 +1420,10 @@ mod tests {
             .write(StateMachineUpdateRequest {
                 payload: RequestPayload::CreateNameSpace(NamespaceRequest {
                     name: "namespace2".to_string(),
+                    blob_storage: None,
+                    scheduling_weight: None,
+                    reprocessing_policy: Default::default(),
+                    near_duplicates: None,
                 }),
                 state_changes_processed: vec![],
             })
@@ -630,35 +1443,297 @@ mod tests {
         assert!(namespaces.iter().any(|ns| ns.name == "namespace1"));
         assert!(namespaces.iter().any(|ns| ns.name == "namespace2"));
 
-        Ok(())
-    }
+        // Listing resumes from the cursor of the previous page
+        let (page, cursor) = reader.list_namespaces(None, Some(1))?;
+        assert_eq!(page[0].name, "namespace1");
+        let (page, cursor) = reader.list_namespaces(cursor.as_deref(), Some(1))?;
+        assert_eq!(page[0].name, "namespace2");
+        assert!(cursor.is_none());
 
-    #[tokio::test]
-    async fn test_create_read_and_delete_compute_graph() -> Result<()> {
-        let temp_dir = TempDir::new()?;
-        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        // Listing a subset skips namespaces that don't exist
+        let names = BTreeSet::from(["missing".to_string(), "namespace2".to_string()]);
+        let (page, cursor) = reader.list_namespaces_in(&names, None, Some(1))?;
+        assert_eq!(page[0].name, "namespace2");
+        assert!(cursor.is_none());
+        let names = BTreeSet::from(["namespace1".to_string(), "namespace2".to_string()]);
+        let (page, cursor) = reader.list_namespaces_in(&names, None, Some(1))?;
+        assert_eq!(page[0].name, "namespace1");
+        let (page, cursor) = reader.list_namespaces_in(&names, cursor.as_deref(), Some(1))?;
+        assert_eq!(page[0].name, "namespace2");
+        assert!(cursor.is_none());
 
-        // Create a compute graph
-        let compute_graph = mock_graph_a();
+        // Creating an existing namespace updates its settings
+        let created_at = reader.get_namespace("namespace1")?.unwrap().created_at;
         indexify_state
             .write(StateMachineUpdateRequest {
-                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
-                    namespace: TEST_NAMESPACE.to_string(),
-                    compute_graph: compute_graph.clone(),
+                payload: RequestPayload::CreateNameSpace(NamespaceRequest {
+                    name: "namespace1".to_string(),
+                    blob_storage: None,
+                    scheduling_weight: Some(2),
+                    reprocessing_policy: Default::default(),
+                    near_duplicates: None,
                 }),
                 state_changes_processed: vec![],
             })
             .await?;
-
-        // Read the compute graph
-        let reader = indexify_state.reader();
-        let result = reader
-            .get_all_rows_from_cf::<ComputeGraph>(IndexifyObjectsColumns::ComputeGraphs)
+        let namespace = indexify_state
+            .reader()
+            .get_namespace("namespace1")?
             .unwrap();
-        let compute_graphs = result
-            .iter()
-            .map(|(_, cg)| cg.clone())
-            .collect::<Vec<ComputeGraph>>();
+        assert_eq!(namespace.created_at, created_at);
+        assert_eq!(namespace.scheduling_weight, Some(2));
+
+        // The blob storage placement can't change
+        let result = indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateNameSpace(NamespaceRequest {
+                    name: "namespace1".to_string(),
+                    blob_storage: Some(data_model::BlobStoragePlacement::Disk {
+                        path: "/tmp/namespace1".to_string(),
+                    }),
+                    scheduling_weight: Some(2),
+                    reprocessing_policy: Default::default(),
+                    near_duplicates: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await;
+        assert_eq!(
+            result
+                .unwrap_err()
+                .downcast_ref::<NamespacePlacementConflict>(),
+            Some(&NamespacePlacementConflict("namespace1".to_string()))
+        );
+        let namespace = indexify_state
+            .reader()
+            .get_namespace("namespace1")?
+            .unwrap();
+        assert_eq!(namespace.blob_storage, None);
+
+        // Namespaces survive restarts
+        drop(reader);
+        drop(indexify_state);
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let namespaces = indexify_state.reader().get_all_namespaces()?;
+        assert_eq!(
+            namespaces
+                .iter()
+                .map(|ns| ns.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["namespace1", "namespace2"]
+        );
+        assert_eq!(namespaces[0].scheduling_weight, Some(2));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_open_with_options() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("state");
+        let options = StateStoreOptions {
+            block_cache_bytes: Some(8 << 20),
+            write_buffer_bytes: Some(1 << 20),
+            compression: options::Compression::None,
+            compaction_style: options::CompactionStyle::Universal,
+            max_background_jobs: Some(2),
+            record_checksums: false,
+        };
+        let indexify_state = IndexifyState::open(path.clone(), options).await?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateNameSpace(NamespaceRequest {
+                    name: TEST_NAMESPACE.to_string(),
+                    blob_storage: None,
+                    scheduling_weight: None,
+                    reprocessing_policy: Default::default(),
+                    near_duplicates: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        drop(indexify_state);
+
+        // RocksDB records the options it runs with.
+        let mut recorded = String::new();
+        for entry in fs::read_dir(&path)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with("OPTIONS-") {
+                recorded = fs::read_to_string(entry.path())?;
+            }
+        }
+        assert!(recorded.contains("compaction_style=kCompactionStyleUniversal"));
+        assert!(recorded.contains("write_buffer_size=1048576"));
+        assert!(recorded.contains("max_background_jobs=2"));
+
+        let indexify_state = IndexifyState::new(path).await?;
+        assert_eq!(indexify_state.reader().get_all_namespaces()?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_column_families() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("state");
+        drop(IndexifyState::new(path.clone()).await?);
+        let column_families = rocksdb::DB::list_cf(&rocksdb::Options::default(), &path)?;
+        for cf in IndexifyObjectsColumns::iter() {
+            assert!(
+                column_families.contains(&cf.to_string()),
+                "{} is missing",
+                cf
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_in_memory() -> Result<()> {
+        let indexify_state = IndexifyState::in_memory().await?;
+        let other = IndexifyState::in_memory().await?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateNameSpace(NamespaceRequest {
+                    name: TEST_NAMESPACE.to_string(),
+                    blob_storage: None,
+                    scheduling_weight: None,
+                    reprocessing_policy: Default::default(),
+                    near_duplicates: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        assert!(indexify_state
+            .reader()
+            .get_namespace(TEST_NAMESPACE)?
+            .is_some());
+        assert!(other.reader().get_namespace(TEST_NAMESPACE)?.is_none());
+        assert!(!PathBuf::from("/state").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_batch() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let create_namespace = || {
+            RequestPayload::CreateNameSpace(NamespaceRequest {
+                name: TEST_NAMESPACE.to_string(),
+                blob_storage: None,
+                scheduling_weight: None,
+                reprocessing_policy: Default::default(),
+                near_duplicates: None,
+            })
+        };
+        let invoke = || {
+            RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: "graph_A".to_string(),
+                invocation_payload: mock_invocation_payload(),
+            })
+        };
+        let write_batch = |payloads| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload: RequestPayload::WriteBatch(WriteBatchRequest { payloads }),
+                state_changes_processed: vec![],
+            })
+        };
+
+        // The graph doesn't exist, so the namespace isn't created either.
+        assert!(write_batch(vec![create_namespace(), invoke()])
+            .await
+            .is_err());
+        assert!(indexify_state
+            .reader()
+            .get_namespace(TEST_NAMESPACE)?
+            .is_none());
+
+        let create_graph = RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: mock_graph_a(),
+            precondition: None,
+        });
+        write_batch(vec![create_namespace(), create_graph, invoke()]).await?;
+        let reader = indexify_state.reader();
+        assert!(reader.get_namespace(TEST_NAMESPACE)?.is_some());
+        reader.invocation_payload(TEST_NAMESPACE, "graph_A", &mock_invocation_payload().id)?;
+        assert_eq!(reader.get_unprocessed_state_changes()?.len(), 1);
+
+        let nested = RequestPayload::WriteBatch(WriteBatchRequest { payloads: vec![] });
+        let err = write_batch(vec![nested]).await.unwrap_err();
+        assert!(err
+            .downcast_ref::<data_model::validation::ValidationErrors>()
+            .is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_executors_version_changes_on_registration() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let executor = data_model::ExecutorMetadata {
+            id: ExecutorId::new("executor_1".to_string()),
+            image_name: "test".to_string(),
+            addr: "".to_string(),
+            labels: Default::default(),
+            gpus: vec![],
+            capacity: None,
+            version: None,
+            artifacts: vec![],
+            prefetch_window: None,
+        };
+        let version = indexify_state.executors_version();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::RegisterExecutor(requests::RegisterExecutorRequest {
+                    executor: executor.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let registered_version = indexify_state.executors_version();
+        assert!(registered_version > version);
+
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::DeregisterExecutor(requests::DeregisterExecutorRequest {
+                    executor_id: executor.id.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        assert!(indexify_state.executors_version() > registered_version);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_read_and_delete_compute_graph() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+
+        // Create a compute graph
+        let compute_graph = mock_graph_a();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: compute_graph.clone(),
+                    precondition: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        // Read the compute graph
+        let reader = indexify_state.reader();
+        let result = reader
+            .get_all_rows_from_cf::<ComputeGraph>(IndexifyObjectsColumns::ComputeGraphs)
+            .unwrap();
+        let compute_graphs = result
+            .iter()
+            .map(|(_, cg)| cg.clone())
+            .collect::<Vec<ComputeGraph>>();
 
         // Check if the compute graph was created
         assert!(compute_graphs.iter().any(|cg| cg.name == "graph_A"));
@@ -711,7 +1786,11 @@ mod tests {
         indexify_state.db.put_cf(
             &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&indexify_state.db),
             graph_invocation_ctx.key(),
-            &JsonEncoder::encode(&graph_invocation_ctx)?,
+            &JsonEncoder::encode(
+                IndexifyObjectsColumns::GraphInvocationCtx,
+                graph_invocation_ctx.key(),
+                &graph_invocation_ctx,
+            )?,
         )?;
 
         let create_tasks_request = requests::CreateTasksRequest {
@@ -763,7 +1842,11 @@ mod tests {
         indexify_state.db.put_cf(
             &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&indexify_state.db),
             graph_invocation_ctx.key(),
-            &JsonEncoder::encode(&graph_invocation_ctx)?,
+            &JsonEncoder::encode(
+                IndexifyObjectsColumns::GraphInvocationCtx,
+                graph_invocation_ctx.key(),
+                &graph_invocation_ctx,
+            )?,
         )?;
 
         let request = SchedulerUpdateRequest {
@@ -800,4 +1883,1020 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_reject_task() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+
+        let executor = mock_executor();
+        let executor_id = executor.id.clone();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::RegisterExecutor(requests::RegisterExecutorRequest {
+                    executor,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let cg = mock_graph_a();
+        indexify_state.db.put_cf(
+            &IndexifyObjectsColumns::ComputeGraphs.cf_db(&indexify_state.db),
+            cg.key(),
+            &JsonEncoder::encode(IndexifyObjectsColumns::ComputeGraphs, cg.key(), &cg)?,
+        )?;
+        let task = create_mock_task(&cg, "fn_a", "input_key", "ingested_id");
+        let graph_invocation_ctx = GraphInvocationCtxBuilder::default()
+            .namespace(task.namespace.clone())
+            .compute_graph_name(task.compute_graph_name.clone())
+            .invocation_id(task.invocation_id.clone())
+            .fn_task_analytics(HashMap::new())
+            .build(cg.clone())?;
+        indexify_state.db.put_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&indexify_state.db),
+            graph_invocation_ctx.key(),
+            &JsonEncoder::encode(
+                IndexifyObjectsColumns::GraphInvocationCtx,
+                graph_invocation_ctx.key(),
+                &graph_invocation_ctx,
+            )?,
+        )?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: requests::RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![requests::CreateTasksRequest {
+                        namespace: task.namespace.clone(),
+                        compute_graph: task.compute_graph_name.clone(),
+                        invocation_id: task.invocation_id.clone(),
+                        tasks: vec![task.clone()],
+                    }],
+                    allocations: vec![TaskPlacement {
+                        task: task.clone(),
+                        executor: executor_id.clone(),
+                    }],
+                    reduction_tasks: ReductionTasks::default(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let reject_request = requests::RejectTaskRequest {
+            namespace: task.namespace.clone(),
+            compute_graph: task.compute_graph_name.clone(),
+            compute_fn: task.compute_fn_name.clone(),
+            invocation_id: task.invocation_id.clone(),
+            task_id: task.id.clone(),
+            executor_id: executor_id.clone(),
+            reason: data_model::TaskRejectionReason::OverCapacity,
+            message: None,
+        };
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: requests::RequestPayload::RejectTask(reject_request.clone()),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        // Being over capacity keeps the task off the executor for a while only,
+        // so it isn't failed even though the executor is the only one.
+        let reader = indexify_state.reader();
+        assert!(reader.get_tasks_by_executor(&executor_id, 10)?.is_empty());
+        let unallocated_tasks = reader.unallocated_tasks()?;
+        assert_eq!(unallocated_tasks.len(), 1);
+        let rejected_at = unallocated_tasks[0].rejections[0].rejected_at;
+        assert!(unallocated_tasks[0].rejected_by(&executor_id, rejected_at));
+        assert!(!unallocated_tasks[0].rejected_by(
+            &executor_id,
+            rejected_at + data_model::TaskRejection::OVER_CAPACITY_TTL_MS
+        ));
+
+        // The task is no longer allocated to the executor
+        let err = indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: requests::RequestPayload::RejectTask(reject_request.clone()),
+                state_changes_processed: vec![],
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<requests::TaskRejectionError>(),
+            Some(&requests::TaskRejectionError::NotAllocated(
+                task.id.clone(),
+                executor_id.clone()
+            ))
+        );
+
+        // Once the only executor able to run it rejects it for good, the task
+        // is failed rather than left waiting.
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: requests::RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![],
+                    allocations: vec![TaskPlacement {
+                        task: unallocated_tasks[0].clone(),
+                        executor: executor_id.clone(),
+                    }],
+                    reduction_tasks: ReductionTasks::default(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: requests::RequestPayload::RejectTask(requests::RejectTaskRequest {
+                    reason: data_model::TaskRejectionReason::UnsupportedVersion,
+                    ..reject_request.clone()
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let reader = indexify_state.reader();
+        assert!(reader.unallocated_tasks()?.is_empty());
+        let failed = reader
+            .get_task(
+                &task.namespace,
+                &task.compute_graph_name,
+                &task.invocation_id,
+                &task.compute_fn_name,
+                &task.id.to_string(),
+            )?
+            .unwrap();
+        assert_eq!(failed.outcome, TaskOutcome::Failure);
+        assert_eq!(failed.rejections.len(), 2);
+
+        let err = indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: requests::RequestPayload::RejectTask(requests::RejectTaskRequest {
+                    task_id: TaskId::from("missing"),
+                    ..reject_request
+                }),
+                state_changes_processed: vec![],
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<requests::TaskRejectionError>(),
+            Some(&requests::TaskRejectionError::TaskNotFound(TaskId::from(
+                "missing"
+            )))
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_index() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let executor = mock_executor();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::RegisterExecutor(requests::RegisterExecutorRequest {
+                    executor: executor.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let cg = mock_graph_a();
+        let mut gpu_task = create_mock_task(&cg, "fn_a", "input_key", "ingested_id");
+        gpu_task.gpu_allocation = Some(GpuAllocation {
+            device_id: "gpu0".to_string(),
+            fraction: 0.5,
+            memory_bytes: 1024,
+        });
+        let mut dependency_task = create_mock_task(&cg, "fn_b", "input_key", "ingested_id");
+        dependency_task.external_dependency = Some("search_api".to_string());
+        let graph_invocation_ctx = GraphInvocationCtxBuilder::default()
+            .namespace(gpu_task.namespace.clone())
+            .compute_graph_name(gpu_task.compute_graph_name.clone())
+            .invocation_id(gpu_task.invocation_id.clone())
+            .fn_task_analytics(HashMap::new())
+            .build(cg.clone())?;
+        indexify_state.db.put_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&indexify_state.db),
+            graph_invocation_ctx.key(),
+            &JsonEncoder::encode(
+                IndexifyObjectsColumns::GraphInvocationCtx,
+                graph_invocation_ctx.key(),
+                &graph_invocation_ctx,
+            )?,
+        )?;
+        let tasks = vec![gpu_task.clone(), dependency_task.clone()];
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![requests::CreateTasksRequest {
+                        namespace: gpu_task.namespace.clone(),
+                        compute_graph: gpu_task.compute_graph_name.clone(),
+                        invocation_id: gpu_task.invocation_id.clone(),
+                        tasks: tasks.clone(),
+                    }],
+                    allocations: tasks
+                        .into_iter()
+                        .map(|task| TaskPlacement {
+                            task,
+                            executor: executor.id.clone(),
+                        })
+                        .collect(),
+                    reduction_tasks: ReductionTasks::default(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let reader = indexify_state.reader();
+        let index = reader.scheduler_index()?;
+        assert_eq!(
+            index.allocated_tasks,
+            HashMap::from([(executor.id.clone(), 2)])
+        );
+        assert_eq!(
+            index.dependency_usage,
+            HashMap::from([("search_api".to_string(), 1)])
+        );
+        assert_eq!(
+            reader.gpu_usage()?[&(executor.id.clone(), "gpu0".to_string())].memory_bytes,
+            1024
+        );
+        assert!(!indexify_state.verify_scheduler_index().await?);
+
+        // An index which drifted from the allocations is rebuilt from them.
+        let mut drifted = index.clone();
+        drifted.allocated_tasks.clear();
+        indexify_state.db.put_cf(
+            &IndexifyObjectsColumns::StateMachineMetadata.cf_db(&indexify_state.db),
+            scheduler_index::SCHEDULER_INDEX_KEY,
+            JsonEncoder::encode(
+                IndexifyObjectsColumns::StateMachineMetadata,
+                scheduler_index::SCHEDULER_INDEX_KEY,
+                &drifted,
+            )?,
+        )?;
+        assert!(indexify_state.verify_scheduler_index().await?);
+        assert_eq!(indexify_state.reader().scheduler_index()?, index);
+
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::DeregisterExecutor(requests::DeregisterExecutorRequest {
+                    executor_id: executor.id.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        assert_eq!(
+            indexify_state.reader().scheduler_index()?,
+            scheduler_index::SchedulerIndex::default()
+        );
+        assert!(!indexify_state.verify_scheduler_index().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_graph_counters() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let cg = mock_graph_a();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: cg.clone(),
+                    precondition: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invocation = mock_invocation_payload();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: cg.name.clone(),
+                    invocation_payload: invocation.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let tasks = vec![
+            create_mock_task(&cg, "fn_a", "input_key", &invocation.id),
+            create_mock_task(&cg, "fn_b", "input_key", &invocation.id),
+        ];
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![requests::CreateTasksRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph: cg.name.clone(),
+                        invocation_id: invocation.id.clone(),
+                        tasks,
+                    }],
+                    allocations: vec![],
+                    reduction_tasks: ReductionTasks::default(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let expected = counters::GraphCounts {
+            invocations: 1,
+            data_objects: 1,
+            pending_tasks: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            indexify_state
+                .reader()
+                .graph_counts(TEST_NAMESPACE, &cg.name)?,
+            Some(expected.clone())
+        );
+
+        // Graphs whose objects aren't counted get their counters from a recount.
+        let cf = IndexifyObjectsColumns::Stats.cf_db(&indexify_state.db);
+        indexify_state.db.delete_cf(
+            &cf,
+            counters::Counter::PendingTasks.key(TEST_NAMESPACE, &cg.name),
+        )?;
+        assert_eq!(
+            indexify_state
+                .reader()
+                .graph_counts(TEST_NAMESPACE, &cg.name)?,
+            None
+        );
+        assert_eq!(
+            indexify_state
+                .recount_graph(TEST_NAMESPACE, &cg.name)
+                .await?,
+            expected
+        );
+        assert_eq!(
+            indexify_state
+                .reader()
+                .graph_counts(TEST_NAMESPACE, &cg.name)?,
+            Some(expected)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reprocess_changed_content() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateNameSpace(NamespaceRequest {
+                    name: TEST_NAMESPACE.to_string(),
+                    blob_storage: None,
+                    scheduling_weight: None,
+                    reprocessing_policy: ReprocessingPolicy::Replace,
+                    near_duplicates: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                    precondition: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invoke = |id: &str, sha256_hash: &str, modified_at: u64| {
+            let mut invocation = mock_invocation_payload();
+            invocation.id = id.to_string();
+            invocation.source = Some(ContentSource {
+                id: "docs/report.pdf".to_string(),
+                sha256_hash: sha256_hash.to_string(),
+                modified_at: Some(modified_at),
+            });
+            indexify_state.write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload: invocation,
+                }),
+                state_changes_processed: vec![],
+            })
+        };
+        invoke("v1", "hash1", 10).await?;
+        let output = mock_node_fn_output_fn_a("v1", "graph_A", None);
+        indexify_state.db.put_cf(
+            &IndexifyObjectsColumns::FnOutputs.cf_db(&indexify_state.db),
+            output.key("v1"),
+            JsonEncoder::encode(IndexifyObjectsColumns::FnOutputs, output.key("v1"), &output)?,
+        )?;
+
+        let err = invoke("v0", "hash0", 5).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ContentVersionError>(),
+            Some(ContentVersionError::Stale { current_invocation, .. }) if current_invocation == "v1"
+        ));
+        let err = invoke("v1_again", "hash1", 10).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ContentVersionError>(),
+            Some(ContentVersionError::Unchanged { .. })
+        ));
+
+        // Same modification time with other content is a new version too.
+        invoke("v2", "hash2", 10).await?;
+        let reader = indexify_state.reader();
+        let v1 = reader.invocation_payload(TEST_NAMESPACE, "graph_A", "v1")?;
+        assert_eq!(v1.superseded_by, Some("v2".to_string()));
+        let (outputs, _) =
+            reader.list_outputs_by_compute_graph(TEST_NAMESPACE, "graph_A", "v1", None, None)?;
+        assert!(outputs.is_empty());
+        let OutputPayload::Fn(payload) = &output.payload else {
+            panic!("expected a function output");
+        };
+        assert!(reader.get_gc_urls(None)?.contains(&payload.path));
+        let v2 = reader.invocation_payload(TEST_NAMESPACE, "graph_A", "v2")?;
+        assert_eq!(v2.superseded_by, None);
+
+        // Deleting the current version lets the source be ingested again.
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::DeleteInvocation(requests::DeleteInvocationRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: "graph_A".to_string(),
+                    invocation_id: "v2".to_string(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        invoke("v2_again", "hash2", 10).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_once() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                    precondition: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invoke = |id: &str| {
+            let mut invocation = mock_invocation_payload();
+            invocation.id = id.to_string();
+            StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload: invocation,
+                }),
+                state_changes_processed: vec![],
+            }
+        };
+        assert!(indexify_state.write_once("req-1", invoke("first")).await?);
+        // A retry is acknowledged without being applied again.
+        assert!(!indexify_state.write_once("req-1", invoke("retry")).await?);
+        let reader = indexify_state.reader();
+        assert!(reader
+            .invocation_payload(TEST_NAMESPACE, "graph_A", "retry")
+            .is_err());
+        let err = indexify_state
+            .write_once(
+                "req-1",
+                StateMachineUpdateRequest {
+                    payload: RequestPayload::PruneActivity(requests::PruneActivityRequest {
+                        recorded_before: 0,
+                    }),
+                    state_changes_processed: vec![],
+                },
+            )
+            .await
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("was used for another request, InvokeComputeGraph"));
+
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::PruneAppliedRequests(
+                    requests::PruneAppliedRequestsRequest {
+                        applied_before: get_epoch_time_in_ms() + 1,
+                    },
+                ),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        assert!(indexify_state.write_once("req-1", invoke("later")).await?);
+        reader.invocation_payload(TEST_NAMESPACE, "graph_A", "later")?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_near_duplicates() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let create_namespace = |action: NearDuplicateAction| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateNameSpace(NamespaceRequest {
+                    name: TEST_NAMESPACE.to_string(),
+                    blob_storage: None,
+                    scheduling_weight: None,
+                    reprocessing_policy: Default::default(),
+                    near_duplicates: Some(NearDuplicatePolicy {
+                        threshold: 0.9,
+                        window_secs: 60,
+                        action,
+                    }),
+                }),
+                state_changes_processed: vec![],
+            })
+        };
+        create_namespace(NearDuplicateAction::Tag).await?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                    precondition: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let invoke = |id: &str, fingerprint: u64, created_at: u64| {
+            let mut invocation = mock_invocation_payload();
+            invocation.id = id.to_string();
+            invocation.fingerprint = Some(fingerprint);
+            invocation.created_at = created_at;
+            indexify_state.write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: "graph_A".to_string(),
+                    invocation_payload: invocation,
+                }),
+                state_changes_processed: vec![],
+            })
+        };
+        let fingerprint = 0xf0f0_f0f0_f0f0_f0f0;
+        invoke("first", fingerprint, 1_000).await?;
+        // Two differing bits are within the threshold, eight aren't.
+        invoke("near", fingerprint ^ 0b11, 2_000).await?;
+        invoke("other", fingerprint ^ 0xff00, 3_000).await?;
+        let reader = indexify_state.reader();
+        let near = reader.invocation_payload(TEST_NAMESPACE, "graph_A", "near")?;
+        assert_eq!(near.near_duplicate_of, Some("first".to_string()));
+        let other = reader.invocation_payload(TEST_NAMESPACE, "graph_A", "other")?;
+        assert_eq!(other.near_duplicate_of, None);
+
+        create_namespace(NearDuplicateAction::Skip).await?;
+        let err = invoke("skipped", fingerprint, 4_000).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ContentVersionError>(),
+            Some(ContentVersionError::NearDuplicate { current_invocation, .. })
+                if current_invocation == "first"
+        ));
+
+        // Fingerprints out of the window are forgotten.
+        invoke("later", fingerprint, 100_000).await?;
+        let fingerprints = reader.get_all_rows_from_cf::<ContentFingerprint>(
+            IndexifyObjectsColumns::ContentFingerprints,
+        )?;
+        let ids: Vec<_> = fingerprints
+            .into_iter()
+            .map(|(_, fp)| fp.invocation_id)
+            .collect();
+        assert_eq!(ids, vec!["later".to_string()]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_latency_exemplars() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let executor = mock_executor();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::RegisterExecutor(requests::RegisterExecutorRequest {
+                    executor: executor.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let cg = mock_graph_a();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: cg.clone(),
+                    precondition: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let trace = TraceContext {
+            trace_id: Some("trace-1".to_string()),
+            ..Default::default()
+        };
+        let mut invocation = mock_invocation_payload();
+        invocation.trace = trace.clone();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: cg.name.clone(),
+                    invocation_payload: invocation.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        let mut task = create_mock_task(&cg, "fn_a", "input_key", &invocation.id);
+        task.trace = trace;
+        let scheduler_update = |tasks: Vec<Task>| StateMachineUpdateRequest {
+            payload: RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                task_requests: vec![requests::CreateTasksRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: cg.name.clone(),
+                    invocation_id: invocation.id.clone(),
+                    tasks: tasks.clone(),
+                }],
+                allocations: tasks
+                    .into_iter()
+                    .map(|task| TaskPlacement {
+                        task,
+                        executor: executor.id.clone(),
+                    })
+                    .collect(),
+                reduction_tasks: ReductionTasks::default(),
+            }),
+            state_changes_processed: vec![],
+        };
+        indexify_state.write(scheduler_update(vec![task])).await?;
+        // No more tasks, which finishes the invocation.
+        indexify_state.write(scheduler_update(vec![])).await?;
+
+        let mut registry = prometheus_client::registry::Registry::default();
+        indexify_state.metrics.register(&mut registry);
+        let mut encoded = String::new();
+        prometheus_client::encoding::text::encode(&mut encoded, &registry)?;
+        for name in [
+            "invocation_latency_seconds_bucket",
+            "task_scheduling_delay_seconds_bucket",
+            "state_store_write_latency_seconds_bucket",
+        ] {
+            assert!(
+                encoded.lines().any(|line| line.starts_with(name) &&
+                    line.contains("# {trace_id=\"trace-1\"}")),
+                "{} has no exemplar:\n{}",
+                name,
+                encoded
+            );
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_record_resource_usage() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+
+        let cg = mock_graph_a();
+        let tasks = vec![
+            create_mock_task(&cg, "fn_a", "input_key", "ingested_id"),
+            create_mock_task(&cg, "fn_b", "input_key", "ingested_id"),
+        ];
+        let graph_invocation_ctx = GraphInvocationCtxBuilder::default()
+            .namespace(TEST_NAMESPACE.to_string())
+            .compute_graph_name(cg.name.clone())
+            .invocation_id("ingested_id".to_string())
+            .fn_task_analytics(HashMap::new())
+            .build(cg.clone())?;
+        indexify_state.db.put_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&indexify_state.db),
+            graph_invocation_ctx.key(),
+            &JsonEncoder::encode(
+                IndexifyObjectsColumns::GraphInvocationCtx,
+                graph_invocation_ctx.key(),
+                &graph_invocation_ctx,
+            )?,
+        )?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: requests::RequestPayload::SchedulerUpdate(SchedulerUpdateRequest {
+                    task_requests: vec![requests::CreateTasksRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph: cg.name.clone(),
+                        invocation_id: "ingested_id".to_string(),
+                        tasks: tasks.clone(),
+                    }],
+                    allocations: vec![],
+                    reduction_tasks: ReductionTasks::default(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        for (task, cpu_seconds) in tasks.iter().zip([2.0, 4.0]) {
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: requests::RequestPayload::FinalizeTask(
+                        requests::FinalizeTaskRequest {
+                            namespace: task.namespace.clone(),
+                            compute_graph: task.compute_graph_name.clone(),
+                            compute_fn: task.compute_fn_name.clone(),
+                            invocation_id: task.invocation_id.clone(),
+                            task_id: task.id.clone(),
+                            node_outputs: vec![],
+                            task_outcome: TaskOutcome::Success,
+                            executor_id: ExecutorId::new("executor1".to_string()),
+                            diagnostics: Some(TaskDiagnostics {
+                                exception: None,
+                                stdout: None,
+                                stderr: None,
+                                failure: None,
+                                resource_usage: Some(ResourceUsage {
+                                    cpu_seconds,
+                                    peak_memory_bytes: 1024,
+                                    gpu_seconds: 0.0,
+                                }),
+                                output_check_violations: vec![],
+                            }),
+                            output_checks: None,
+                        },
+                    ),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        let reader = indexify_state.reader();
+        let graph_usage = reader
+            .graph_resource_usage(TEST_NAMESPACE, &cg.name)?
+            .unwrap();
+        assert_eq!(graph_usage.tasks, 2);
+        assert_eq!(graph_usage.total_cpu_seconds, 6.0);
+        assert_eq!(graph_usage.max_peak_memory_bytes, 1024);
+        let fn_usage = reader.fn_resource_usage(TEST_NAMESPACE, &cg.name)?;
+        assert_eq!(fn_usage.len(), 2);
+        assert_eq!(fn_usage["fn_b"].avg_cpu_seconds, 4.0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invocation_provenance() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let create_graph = |compute_graph: ComputeGraph| StateMachineUpdateRequest {
+            payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph,
+                precondition: None,
+            }),
+            state_changes_processed: vec![],
+        };
+        let mut graph = mock_graph_a();
+        indexify_state.write(create_graph(graph.clone())).await?;
+        let invocation = mock_invocation_payload();
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph_name: graph.name.clone(),
+                    invocation_payload: invocation.clone(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+
+        let reader = indexify_state.reader();
+        let provenance = reader
+            .invocation_payload(TEST_NAMESPACE, &graph.name, &invocation.id)?
+            .provenance
+            .unwrap();
+        assert_eq!(provenance.input_sha256, invocation.payload.sha256_hash);
+        assert_eq!(provenance.functions.len(), graph.nodes.len());
+        let stored = reader
+            .get_compute_graph(TEST_NAMESPACE, &graph.name)?
+            .unwrap();
+        assert!(provenance.graph_drift(&stored)?.is_empty());
+
+        graph.code.sha256_hash = "new_code".to_string();
+        indexify_state.write(create_graph(graph.clone())).await?;
+        let stored = reader
+            .get_compute_graph(TEST_NAMESPACE, &graph.name)?
+            .unwrap();
+        assert_eq!(
+            provenance.graph_drift(&stored)?,
+            vec![
+                "graph is at version 2, the invocation ran version 1".to_string(),
+                "graph code changed".to_string(),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compute_graph_preconditions() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let put_graph = |compute_graph: ComputeGraph, precondition| StateMachineUpdateRequest {
+            payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph,
+                precondition: Some(precondition),
+            }),
+            state_changes_processed: vec![],
+        };
+        let conflict = |err: anyhow::Error| err.downcast::<GraphVersionConflict>().unwrap();
+
+        let mut graph = mock_graph_a();
+        if let Some(Node::Compute(compute_fn)) = graph.nodes.get_mut("fn_a") {
+            compute_fn.payload_encoder = "json".to_string();
+        }
+        let err = indexify_state
+            .write(put_graph(graph.clone(), GraphPrecondition::Exists))
+            .await
+            .unwrap_err();
+        assert_eq!(conflict(err).current, None);
+        indexify_state
+            .write(put_graph(graph.clone(), GraphPrecondition::Absent))
+            .await?;
+        let err = indexify_state
+            .write(put_graph(graph.clone(), GraphPrecondition::Absent))
+            .await
+            .unwrap_err();
+        assert_eq!(conflict(err).current, Some(1.into()));
+
+        graph.code.sha256_hash = "new_code".to_string();
+        indexify_state
+            .write(put_graph(
+                graph.clone(),
+                GraphPrecondition::Version(1.into()),
+            ))
+            .await?;
+        // A writer still holding the first version loses.
+        let err = indexify_state
+            .write(put_graph(
+                graph.clone(),
+                GraphPrecondition::Version(1.into()),
+            ))
+            .await
+            .unwrap_err();
+        assert_eq!(conflict(err).current, Some(2.into()));
+        let stored = indexify_state
+            .reader()
+            .get_compute_graph(TEST_NAMESPACE, &graph.name)?
+            .unwrap();
+        assert_eq!(stored.version, 2.into());
+        assert_eq!(stored.code.sha256_hash, "new_code");
+
+        // Changing only how outputs flow along an edge is a new version too.
+        graph.edge_transforms = vec![EdgeTransform {
+            source: "fn_a".to_string(),
+            target: "fn_b".to_string(),
+            steps: vec![TransformStep::Select {
+                pointer: "/result".to_string(),
+            }],
+        }];
+        indexify_state
+            .write(put_graph(
+                graph.clone(),
+                GraphPrecondition::Version(2.into()),
+            ))
+            .await?;
+        let stored = indexify_state
+            .reader()
+            .get_compute_graph(TEST_NAMESPACE, &graph.name)?
+            .unwrap();
+        assert_eq!(stored.version, 3.into());
+
+        graph.code.sha256_hash = "newer_code".to_string();
+        let err = indexify_state
+            .update_compute_graph(2.into(), graph.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(conflict(err).current, Some(3.into()));
+        indexify_state
+            .update_compute_graph(3.into(), graph.clone())
+            .await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compute_graph_history() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        let put_graph = |compute_graph| {
+            RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph,
+                precondition: None,
+            })
+        };
+        let mut graph = mock_graph_a();
+        write(put_graph(graph.clone())).await?;
+        let invocation = mock_invocation_payload();
+        write(RequestPayload::InvokeComputeGraph(
+            InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: graph.name.clone(),
+                invocation_payload: invocation.clone(),
+            },
+        ))
+        .await?;
+
+        // Updates don't change the graph of the invocations in flight.
+        graph.code.sha256_hash = "v2".to_string();
+        write(put_graph(graph.clone())).await?;
+        let reader = indexify_state.reader();
+        let running = reader
+            .get_compute_graph_for_invocation(TEST_NAMESPACE, &graph.name, &invocation.id)?
+            .unwrap();
+        assert_eq!(running.version, 1.into());
+        assert_eq!(running.code.sha256_hash, mock_graph_a().code.sha256_hash);
+
+        // The version of a rolled back canary isn't reused.
+        graph.code.sha256_hash = "canary".to_string();
+        write(RequestPayload::StartCanary(StartCanaryRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: graph.clone(),
+            percent: 10,
+        }))
+        .await?;
+        write(RequestPayload::FinishCanary(FinishCanaryRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: graph.name.clone(),
+            promote: false,
+        }))
+        .await?;
+        graph.code.sha256_hash = "v4".to_string();
+        write(put_graph(graph.clone())).await?;
+        let canary = reader
+            .get_compute_graph_version(TEST_NAMESPACE, &graph.name, 3.into())?
+            .unwrap();
+        assert_eq!(canary.code.sha256_hash, "canary");
+        let (versions, _) =
+            reader.list_compute_graph_versions(TEST_NAMESPACE, &graph.name, None, None)?;
+        let versions: Vec<_> = versions
+            .iter()
+            .map(|graph| (graph.version, graph.code.sha256_hash.as_str()))
+            .collect();
+        assert_eq!(
+            versions,
+            vec![
+                (1.into(), mock_graph_a().code.sha256_hash.as_str()),
+                (2.into(), "v2"),
+                (3.into(), "canary"),
+                (4.into(), "v4"),
+            ]
+        );
+
+        // Invocations pinned to a version run it rather than the current one.
+        let invoke = |invocation_payload| {
+            RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: graph.name.clone(),
+                invocation_payload,
+            })
+        };
+        let mut pinned = mock_invocation_payload();
+        pinned.id = "pinned".to_string();
+        pinned.graph_version = Some(1.into());
+        write(invoke(pinned.clone())).await?;
+        let running = reader
+            .get_compute_graph_for_invocation(TEST_NAMESPACE, &graph.name, &pinned.id)?
+            .unwrap();
+        assert_eq!(running.version, 1.into());
+        let provenance = reader
+            .invocation_payload(TEST_NAMESPACE, &graph.name, &pinned.id)?
+            .provenance
+            .unwrap();
+        assert_eq!(provenance.graph_version, 1.into());
+        pinned.id = "unknown_version".to_string();
+        pinned.graph_version = Some(9.into());
+        assert!(write(invoke(pinned)).await.is_err());
+
+        write(RequestPayload::DeleteComputeGraph(
+            DeleteComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                name: graph.name.clone(),
+            },
+        ))
+        .await?;
+        assert!(reader
+            .list_compute_graph_versions(TEST_NAMESPACE, &graph.name, None, None)?
+            .0
+            .is_empty());
+        Ok(())
+    }
 }
@@ -0,0 +1,234 @@
+use std::time::{Duration, Instant};
+
+use data_model::TraceContext;
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{
+        counter::Counter,
+        exemplar::HistogramWithExemplars,
+        family::Family,
+        gauge::Gauge,
+        histogram::{exponential_buckets, Histogram},
+    },
+    registry::{Registry, Unit},
+};
+
+/// Links an observation to a representative trace of the request it was
+/// made for.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct TraceExemplar {
+    pub trace_id: String,
+}
+
+/// Part of the state store's files the disk usage is reported for, e.g.
+/// `wal` or `sst`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct DiskUsageLabels {
+    pub component: String,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct KeyMigrationLabels {
+    pub migration: String,
+}
+
+/// Reads of a column by operation: `get`, `multi_get` or `scan`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ReadLabels {
+    pub column: String,
+    pub op: String,
+}
+
+/// Writes by kind of request, e.g. `FinalizeTask`.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct WriteLabels {
+    pub request: String,
+}
+
+type HistogramFamily<L> = Family<L, Histogram, fn() -> Histogram>;
+
+fn read_latency_histogram() -> Histogram {
+    Histogram::new(exponential_buckets(0.00001, 2.0, 20))
+}
+
+fn write_latency_histogram() -> Histogram {
+    Histogram::new(exponential_buckets(0.0005, 2.0, 16))
+}
+
+/// Metrics of the state store. Latency observations carry the trace id of
+/// the invocation they were made for as an exemplar, so that a latency spike
+/// on a dashboard leads to a trace which caused it.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    /// From the creation of an invocation to the completion of its last task.
+    pub invocation_latency: HistogramWithExemplars<TraceExemplar>,
+    /// From the creation of a task to its allocation to an executor.
+    pub task_scheduling_delay: HistogramWithExemplars<TraceExemplar>,
+    /// Time to apply a write to the state store and commit it.
+    pub write_latency: HistogramWithExemplars<TraceExemplar>,
+    /// Bytes on disk of the state store, sampled by the disk quota monitor.
+    pub disk_usage: Family<DiskUsageLabels, Gauge>,
+    /// 1 while writes bringing in new work are refused for lack of disk.
+    pub disk_quota_exceeded: Gauge,
+    /// Keyed reads of a column being migrated to a new key layout which
+    /// also read the new layout.
+    pub key_migration_shadow_reads: Family<KeyMigrationLabels, Counter>,
+    /// Shadow reads which found a record of the new layout differing from
+    /// the old one.
+    pub key_migration_divergences: Family<KeyMigrationLabels, Counter>,
+    /// Records copied to the new layout.
+    pub key_migration_copied: Family<KeyMigrationLabels, Counter>,
+    /// Records returned by the reads of the state reader. Reads made while
+    /// applying a write count towards the latency of the write instead.
+    pub records_read: Family<ReadLabels, Counter>,
+    /// For scans, from the creation of the iterator to its drop.
+    pub read_latency: HistogramFamily<ReadLabels>,
+    pub writes: Family<WriteLabels, Counter>,
+    pub request_write_latency: HistogramFamily<WriteLabels>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            invocation_latency: HistogramWithExemplars::new(exponential_buckets(0.1, 2.0, 16)),
+            task_scheduling_delay: HistogramWithExemplars::new(exponential_buckets(0.005, 2.0, 16)),
+            write_latency: HistogramWithExemplars::new(exponential_buckets(0.0005, 2.0, 16)),
+            disk_usage: Family::default(),
+            disk_quota_exceeded: Gauge::default(),
+            key_migration_shadow_reads: Family::default(),
+            key_migration_divergences: Family::default(),
+            key_migration_copied: Family::default(),
+            records_read: Family::default(),
+            read_latency: Family::new_with_constructor(read_latency_histogram),
+            writes: Family::default(),
+            request_write_latency: Family::new_with_constructor(write_latency_histogram),
+        }
+    }
+
+    pub fn register(&self, registry: &mut Registry) {
+        registry.register_with_unit(
+            "invocation_latency",
+            "Time from the creation of an invocation to its completion",
+            Unit::Seconds,
+            self.invocation_latency.clone(),
+        );
+        registry.register_with_unit(
+            "task_scheduling_delay",
+            "Time from the creation of a task to its allocation to an executor",
+            Unit::Seconds,
+            self.task_scheduling_delay.clone(),
+        );
+        registry.register_with_unit(
+            "state_store_write_latency",
+            "Time to apply a write to the state store and commit it",
+            Unit::Seconds,
+            self.write_latency.clone(),
+        );
+        registry.register_with_unit(
+            "state_store_disk_usage",
+            "Bytes on disk of the state store by component",
+            Unit::Bytes,
+            self.disk_usage.clone(),
+        );
+        registry.register(
+            "state_store_disk_quota_exceeded",
+            "Whether writes bringing in new work are refused for lack of disk",
+            self.disk_quota_exceeded.clone(),
+        );
+        registry.register(
+            "state_store_key_migration_shadow_reads",
+            "Reads of a column being migrated which compared the old and new key layouts",
+            self.key_migration_shadow_reads.clone(),
+        );
+        registry.register(
+            "state_store_key_migration_divergences",
+            "Records differing between the old and new key layouts of a column",
+            self.key_migration_divergences.clone(),
+        );
+        registry.register(
+            "state_store_key_migration_copied",
+            "Records copied to the new key layout of a column",
+            self.key_migration_copied.clone(),
+        );
+        registry.register(
+            "state_store_records_read",
+            "Records read from the state store by column and operation",
+            self.records_read.clone(),
+        );
+        registry.register_with_unit(
+            "state_store_read_latency",
+            "Time to read from a column of the state store by operation",
+            Unit::Seconds,
+            self.read_latency.clone(),
+        );
+        registry.register(
+            "state_store_writes",
+            "Writes committed to the state store by kind of request",
+            self.writes.clone(),
+        );
+        registry.register_with_unit(
+            "state_store_request_write_latency",
+            "Time to apply and commit a write to the state store by kind of request",
+            Unit::Seconds,
+            self.request_write_latency.clone(),
+        );
+    }
+
+    /// Starts timing a read of a column, accounted when the timer is dropped.
+    pub(crate) fn read(&self, column: &str, op: &str) -> ReadTimer {
+        let labels = ReadLabels {
+            column: column.to_string(),
+            op: op.to_string(),
+        };
+        ReadTimer {
+            records_read: self.records_read.get_or_create(&labels).clone(),
+            latency: self.read_latency.get_or_create(&labels).clone(),
+            started: Instant::now(),
+        }
+    }
+
+    pub(crate) fn observe_write(&self, request: &str, latency: Duration) {
+        let labels = WriteLabels {
+            request: request.to_string(),
+        };
+        self.writes.get_or_create(&labels).inc();
+        self.request_write_latency
+            .get_or_create(&labels)
+            .observe(latency.as_secs_f64());
+    }
+}
+
+pub(crate) struct ReadTimer {
+    records_read: Counter,
+    latency: Histogram,
+    started: Instant,
+}
+
+impl ReadTimer {
+    pub(crate) fn records(&self, records: u64) {
+        self.records_read.inc_by(records);
+    }
+}
+
+impl Drop for ReadTimer {
+    fn drop(&mut self) {
+        self.latency.observe(self.started.elapsed().as_secs_f64());
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(crate) fn observe(
+    histogram: &HistogramWithExemplars<TraceExemplar>,
+    latency: Duration,
+    trace: Option<&TraceContext>,
+) {
+    let exemplar = trace
+        .and_then(|trace| trace.trace_id.clone())
+        .map(|trace_id| TraceExemplar { trace_id });
+    histogram.observe(latency.as_secs_f64(), exemplar);
+}
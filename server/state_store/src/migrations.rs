@@ -0,0 +1,226 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use data_model::{canary::GraphCanary, ComputeGraph};
+use rocksdb::{IteratorMode, Transaction, TransactionDB};
+use strum::IntoEnumIterator;
+use tracing::info;
+
+use crate::{
+    serializer::{JsonEncode, JsonEncoder},
+    state_machine::{self, IndexifyObjectsColumns},
+};
+
+pub(crate) const SCHEMA_VERSION_KEY: &[u8] = b"schema_version";
+
+/// Step upgrading the on-disk layout from `version - 1` to `version`, e.g.
+/// rewriting the keys or values of a column after a serialized struct
+/// changed in an incompatible way.
+pub(crate) struct Migration {
+    pub version: u64,
+    pub name: &'static str,
+    pub apply: fn(&Arc<TransactionDB>, &Transaction<TransactionDB>) -> Result<()>,
+}
+
+/// Ordered by version, changes to the layout append a migration with the
+/// next version.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "version the existing layout",
+        apply: version_existing_layout,
+    },
+    Migration {
+        version: 2,
+        name: "keep the definitions of the graphs by version",
+        apply: store_graph_versions,
+    },
+];
+
+fn version_existing_layout(_: &Arc<TransactionDB>, _: &Transaction<TransactionDB>) -> Result<()> {
+    Ok(())
+}
+
+/// Versions replaced before they were kept can't be recovered, their
+/// invocations keep running the stable version.
+fn store_graph_versions(db: &Arc<TransactionDB>, txn: &Transaction<TransactionDB>) -> Result<()> {
+    for row in txn.iterator_cf(
+        &IndexifyObjectsColumns::ComputeGraphs.cf_db(db),
+        IteratorMode::Start,
+    ) {
+        let (key, value) = row?;
+        let graph = JsonEncoder::decode::<ComputeGraph>(
+            IndexifyObjectsColumns::ComputeGraphs,
+            &key,
+            &value,
+        )?;
+        state_machine::put_graph_version(db, txn, &graph)?;
+    }
+    for row in txn.iterator_cf(
+        &IndexifyObjectsColumns::GraphCanaries.cf_db(db),
+        IteratorMode::Start,
+    ) {
+        let (key, value) = row?;
+        let canary = JsonEncoder::decode::<GraphCanary>(
+            IndexifyObjectsColumns::GraphCanaries,
+            &key,
+            &value,
+        )?;
+        state_machine::put_graph_version(db, txn, &canary.graph)?;
+    }
+    Ok(())
+}
+
+/// Brings the store up to the version of this server, called when it's
+/// opened before anything reads it.
+pub(crate) fn migrate(db: &Arc<TransactionDB>) -> Result<()> {
+    run(db, MIGRATIONS)
+}
+
+fn run(db: &Arc<TransactionDB>, migrations: &[Migration]) -> Result<()> {
+    let latest = migrations.last().map_or(0, |migration| migration.version);
+    let cf = IndexifyObjectsColumns::StateMachineMetadata.cf_db(db);
+    let version = match db.get_cf(&cf, SCHEMA_VERSION_KEY)? {
+        Some(value) => JsonEncoder::decode::<u64>(
+            IndexifyObjectsColumns::StateMachineMetadata,
+            SCHEMA_VERSION_KEY,
+            &value,
+        )?,
+        // Stores written before versioning have data but no version.
+        None if is_empty(db)? => {
+            db.put_cf(
+                &cf,
+                SCHEMA_VERSION_KEY,
+                JsonEncoder::encode(
+                    IndexifyObjectsColumns::StateMachineMetadata,
+                    SCHEMA_VERSION_KEY,
+                    &latest,
+                )?,
+            )?;
+            return Ok(());
+        }
+        None => 0,
+    };
+    if version > latest {
+        bail!(
+            "state store has schema version {}, newer than the version {} of this server",
+            version,
+            latest
+        );
+    }
+    // Each migration commits with its version, so an interrupted upgrade
+    // resumes from the last migration applied.
+    for migration in migrations
+        .iter()
+        .filter(|migration| migration.version > version)
+    {
+        info!(
+            "migrating the state store to schema version {}: {}",
+            migration.version, migration.name
+        );
+        let txn = db.transaction();
+        (migration.apply)(db, &txn)?;
+        txn.put_cf(
+            &cf,
+            SCHEMA_VERSION_KEY,
+            JsonEncoder::encode(
+                IndexifyObjectsColumns::StateMachineMetadata,
+                SCHEMA_VERSION_KEY,
+                &migration.version,
+            )?,
+        )?;
+        txn.commit()?;
+    }
+    Ok(())
+}
+
+fn is_empty(db: &Arc<TransactionDB>) -> Result<bool> {
+    for column in IndexifyObjectsColumns::iter() {
+        if db
+            .iterator_cf(&column.cf_db(db), IteratorMode::Start)
+            .next()
+            .transpose()?
+            .is_some()
+        {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::IndexifyState;
+
+    const TEST_KEY: &[u8] = b"migrated";
+
+    fn append(txn: &Transaction<TransactionDB>, db: &Arc<TransactionDB>, step: &str) -> Result<()> {
+        let cf = IndexifyObjectsColumns::StateMachineMetadata.cf_db(db);
+        let mut value = txn.get_cf(&cf, TEST_KEY)?.unwrap_or_default();
+        value.extend_from_slice(step.as_bytes());
+        txn.put_cf(&cf, TEST_KEY, value)?;
+        Ok(())
+    }
+
+    const TEST_MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: 1,
+            name: "first",
+            apply: |db, txn| append(txn, db, "1"),
+        },
+        Migration {
+            version: 2,
+            name: "second",
+            apply: |db, txn| append(txn, db, "2"),
+        },
+    ];
+
+    #[tokio::test]
+    async fn test_migrate() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let db = &indexify_state.db;
+        let cf = IndexifyObjectsColumns::StateMachineMetadata.cf_db(db);
+        let version = || -> Result<u64> {
+            Ok(JsonEncoder::decode(
+                IndexifyObjectsColumns::StateMachineMetadata,
+                SCHEMA_VERSION_KEY,
+                &db.get_cf(&cf, SCHEMA_VERSION_KEY)?.unwrap(),
+            )?)
+        };
+        // New stores start at the latest version.
+        assert_eq!(version()?, MIGRATIONS.last().unwrap().version);
+
+        // A store written before versioning runs every migration, in order.
+        db.delete_cf(&cf, SCHEMA_VERSION_KEY)?;
+        db.put_cf(
+            &IndexifyObjectsColumns::Namespaces.cf_db(db),
+            "legacy",
+            b"{}",
+        )?;
+        run(db, TEST_MIGRATIONS)?;
+        assert_eq!(version()?, 2);
+        assert_eq!(db.get_cf(&cf, TEST_KEY)?.unwrap(), b"12");
+
+        // Only the migrations past the stored version run.
+        run(db, TEST_MIGRATIONS)?;
+        db.put_cf(
+            &cf,
+            SCHEMA_VERSION_KEY,
+            JsonEncoder::encode(
+                IndexifyObjectsColumns::StateMachineMetadata,
+                SCHEMA_VERSION_KEY,
+                &1u64,
+            )?,
+        )?;
+        run(db, TEST_MIGRATIONS)?;
+        assert_eq!(db.get_cf(&cf, TEST_KEY)?.unwrap(), b"122");
+
+        // Stores written by a newer server are refused.
+        assert!(run(db, &TEST_MIGRATIONS[..1]).is_err());
+        Ok(())
+    }
+}
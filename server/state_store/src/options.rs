@@ -0,0 +1,119 @@
+use rocksdb::{BlockBasedOptions, Cache, DBCompactionStyle, DBCompressionType, Options};
+use serde::{Deserialize, Serialize};
+
+use crate::state_machine::IndexifyObjectsColumns;
+
+// An SST file is marked for compaction when at least DELETION_TRIGGER of
+// DELETION_WINDOW consecutive entries, or half of all its entries, are
+// tombstones. Deleting graphs and archiving invocations remove large key
+// ranges, and this lets RocksDB reclaim their space in the background.
+const DELETION_WINDOW: usize = 1024;
+const DELETION_TRIGGER: usize = 512;
+const DELETION_RATIO: f64 = 0.5;
+
+// Bits per key of the bloom filters of column families read by key.
+const BLOOM_FILTER_BITS_PER_KEY: f64 = 10.0;
+
+/// Tuning of RocksDB. Unset sizes keep the defaults of RocksDB, which suit
+/// development rather than production.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct StateStoreOptions {
+    /// Bytes of the cache of uncompressed blocks shared by all column
+    /// families.
+    pub block_cache_bytes: Option<usize>,
+    /// Bytes of the memtable of each column family before it's flushed.
+    pub write_buffer_bytes: Option<usize>,
+    pub compression: Compression,
+    pub compaction_style: CompactionStyle,
+    /// Flushes and compactions run concurrently.
+    pub max_background_jobs: Option<i32>,
+    /// Prefix the records written with their CRC32 so that corruption is
+    /// detected when they are read. Binaries without checksum support can't
+    /// read them back.
+    pub record_checksums: bool,
+}
+
+impl Default for StateStoreOptions {
+    fn default() -> Self {
+        StateStoreOptions {
+            block_cache_bytes: None,
+            write_buffer_bytes: None,
+            compression: Compression::Snappy,
+            compaction_style: CompactionStyle::Level,
+            max_background_jobs: None,
+            record_checksums: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    None,
+    Snappy,
+    Lz4,
+    Zstd,
+}
+
+/// FIFO compaction isn't offered, it drops the oldest files of the state.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CompactionStyle {
+    Level,
+    Universal,
+}
+
+impl StateStoreOptions {
+    pub(crate) fn db_options(&self) -> Options {
+        let mut options = Options::default();
+        options.create_missing_column_families(true);
+        options.create_if_missing(true);
+        if let Some(jobs) = self.max_background_jobs {
+            options.set_max_background_jobs(jobs);
+        }
+        options
+    }
+
+    /// Options of a column family, `cache` is the shared block cache.
+    pub(crate) fn column_family_options(
+        &self,
+        cf: &IndexifyObjectsColumns,
+        cache: Option<&Cache>,
+    ) -> Options {
+        let mut options = Options::default();
+        options.add_compact_on_deletion_collector_factory(
+            DELETION_WINDOW,
+            DELETION_TRIGGER,
+            DELETION_RATIO,
+        );
+        let mut table_options = BlockBasedOptions::default();
+        // Bloom filters spare reading blocks of keys that aren't there,
+        // prefix scanned column families don't benefit from them.
+        if cf.is_point_lookup() {
+            table_options.set_bloom_filter(BLOOM_FILTER_BITS_PER_KEY, false);
+        }
+        if let Some(cache) = cache {
+            table_options.set_block_cache(cache);
+        }
+        options.set_block_based_table_factory(&table_options);
+        if let Some(bytes) = self.write_buffer_bytes {
+            options.set_write_buffer_size(bytes);
+        }
+        options.set_compression_type(match self.compression {
+            Compression::None => DBCompressionType::None,
+            Compression::Snappy => DBCompressionType::Snappy,
+            Compression::Lz4 => DBCompressionType::Lz4,
+            Compression::Zstd => DBCompressionType::Zstd,
+        });
+        options.set_compaction_style(match self.compaction_style {
+            CompactionStyle::Level => DBCompactionStyle::Level,
+            CompactionStyle::Universal => DBCompactionStyle::Universal,
+        });
+        options
+    }
+
+    pub(crate) fn block_cache(&self) -> Option<Cache> {
+        self.block_cache_bytes.map(Cache::new_lru_cache)
+    }
+}
@@ -0,0 +1,473 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+};
+
+use anyhow::Result;
+use data_model::{ComputeGraph, ExecutorId, InvocationPayload, NodeOutput, OutputPayload, Task};
+use rocksdb::{compaction_filter::Decision, IteratorMode, ReadOptions, Transaction, TransactionDB};
+
+use crate::{
+    counters::{self, Counter},
+    scheduler_index,
+    serializer::{JsonEncode, JsonEncoder},
+    state_machine::{delete_cf_prefix, IndexifyObjectsColumns},
+};
+
+/// Prefixes of the deleted graphs, `Ns_Graph_`, whose tasks are left for
+/// compaction to drop rather than deleted along with the graph.
+#[derive(Default)]
+pub struct Tombstones {
+    prefixes: RwLock<HashSet<Vec<u8>>>,
+}
+
+impl Tombstones {
+    pub fn graph_prefix(namespace: &str, compute_graph: &str) -> String {
+        format!("{}|{}|", namespace, compute_graph)
+    }
+
+    pub(crate) fn load(&self, db: &TransactionDB) -> Result<()> {
+        let mut prefixes = self.prefixes.write().unwrap();
+        for row in db.iterator_cf(
+            &IndexifyObjectsColumns::Tombstones.cf_db(db),
+            IteratorMode::Start,
+        ) {
+            let (prefix, _) = row?;
+            prefixes.insert(prefix.to_vec());
+        }
+        Ok(())
+    }
+
+    /// Whether the record at `key`, keyed by its graph first, belongs to a
+    /// deleted graph.
+    pub fn covers(&self, key: &[u8]) -> bool {
+        let Some(end) = key
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| **b == b'|')
+            .nth(1)
+            .map(|(i, _)| i + 1)
+        else {
+            return false;
+        };
+        self.prefixes.read().unwrap().contains(&key[..end])
+    }
+
+    pub(crate) fn insert(&self, prefix: &str) {
+        self.prefixes
+            .write()
+            .unwrap()
+            .insert(prefix.as_bytes().to_vec());
+    }
+
+    pub(crate) fn remove(&self, prefix: &[u8]) {
+        self.prefixes.write().unwrap().remove(prefix);
+    }
+
+    pub fn len(&self) -> usize {
+        self.prefixes.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Drops the tasks of deleted graphs when compacting the column families
+/// keyed by task. Outputs aren't dropped here, their blobs must be queued for
+/// deletion first, which `reconcile` does.
+pub(crate) fn compaction_filter(
+    cf: &IndexifyObjectsColumns,
+    tombstones: Arc<Tombstones>,
+) -> Option<impl FnMut(u32, &[u8], &[u8]) -> Decision + Send + 'static> {
+    if !matches!(
+        cf,
+        IndexifyObjectsColumns::Tasks | IndexifyObjectsColumns::UnallocatedTasks
+    ) {
+        return None;
+    }
+    Some(move |_level: u32, key: &[u8], _value: &[u8]| {
+        if tombstones.covers(key) {
+            Decision::Remove
+        } else {
+            Decision::Keep
+        }
+    })
+}
+
+/// Orphaned records removed by `reconcile`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrphansRemoved {
+    pub tasks: u64,
+    pub outputs: u64,
+    pub tombstones: u64,
+}
+
+/// Whether the graph and invocation of the records keyed `Ns_Graph_Id_` still
+/// exist, looked up once per invocation.
+struct Parents<'a, 't> {
+    db: &'a TransactionDB,
+    txn: &'a Transaction<'t, TransactionDB>,
+    graphs: HashMap<String, bool>,
+    invocations: HashMap<String, bool>,
+}
+
+impl<'a, 't> Parents<'a, 't> {
+    /// The namespace and graph of an orphaned record, with whether the graph
+    /// still exists, none when the record isn't orphaned.
+    fn orphaned(&mut self, key: &[u8]) -> Result<Option<(String, String, bool)>> {
+        let key = String::from_utf8_lossy(key);
+        let mut parts = key.splitn(4, '|');
+        let (Some(namespace), Some(compute_graph), Some(invocation_id)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Ok(None);
+        };
+        let graph_key = ComputeGraph::key_from(namespace, compute_graph);
+        let graph_exists = match self.graphs.get(&graph_key) {
+            Some(exists) => *exists,
+            None => {
+                let exists = self
+                    .txn
+                    .get_for_update_cf(
+                        &IndexifyObjectsColumns::ComputeGraphs.cf_db(self.db),
+                        &graph_key,
+                        false,
+                    )?
+                    .is_some();
+                self.graphs.insert(graph_key, exists);
+                exists
+            }
+        };
+        let invocation_key = InvocationPayload::key_from(namespace, compute_graph, invocation_id);
+        let invocation_exists = graph_exists &&
+            match self.invocations.get(&invocation_key) {
+                Some(exists) => *exists,
+                None => {
+                    let exists = self
+                        .txn
+                        .get_for_update_cf(
+                            &IndexifyObjectsColumns::GraphInvocations.cf_db(self.db),
+                            &invocation_key,
+                            false,
+                        )?
+                        .is_some();
+                    self.invocations.insert(invocation_key, exists);
+                    exists
+                }
+            };
+        if invocation_exists {
+            return Ok(None);
+        }
+        Ok(Some((
+            namespace.to_string(),
+            compute_graph.to_string(),
+            graph_exists,
+        )))
+    }
+}
+
+/// Removes the tasks and outputs whose graph or invocation doesn't exist
+/// anymore, along with their allocations, and queues the blobs of the
+/// outputs for deletion. Clears the tombstones of the graphs deleted before
+/// it started. Scans every task, allocation and output. A dry run only counts
+/// what would be removed.
+pub(crate) fn reconcile(
+    db: &Arc<TransactionDB>,
+    tombstones: &Tombstones,
+    dry_run: bool,
+) -> Result<OrphansRemoved> {
+    let txn = db.transaction();
+    let mut removed = OrphansRemoved::default();
+    let tombstones_cf = IndexifyObjectsColumns::Tombstones.cf_db(db);
+    let mut cleared = vec![];
+    for row in txn.iterator_cf(&tombstones_cf, IteratorMode::Start) {
+        let (prefix, _) = row?;
+        txn.get_for_update_cf(&tombstones_cf, &prefix, true)?;
+        cleared.push(prefix);
+    }
+
+    let mut parents = Parents {
+        db,
+        txn: &txn,
+        graphs: HashMap::new(),
+        invocations: HashMap::new(),
+    };
+    let mut orphaned_tasks = HashMap::new();
+    for row in txn.iterator_cf_opt(
+        &IndexifyObjectsColumns::Tasks.cf_db(db),
+        ReadOptions::default(),
+        IteratorMode::Start,
+    ) {
+        let (key, value) = row?;
+        let Some((namespace, compute_graph, graph_exists)) = parents.orphaned(&key)? else {
+            continue;
+        };
+        let task = JsonEncoder::decode::<Task>(IndexifyObjectsColumns::Tasks, &key, &value)?;
+        delete_cf_prefix(
+            &txn,
+            &IndexifyObjectsColumns::TaskOutputs.cf_db(db),
+            format!("{}|{}|", task.namespace, task.id).as_bytes(),
+        )?;
+        txn.delete_cf(&IndexifyObjectsColumns::UnallocatedTasks.cf_db(db), &key)?;
+        txn.delete_cf(&IndexifyObjectsColumns::Tasks.cf_db(db), &key)?;
+        // The counters of deleted graphs were deleted with them.
+        if graph_exists {
+            counters::add(
+                db,
+                &txn,
+                &namespace,
+                &compute_graph,
+                Counter::for_task(&task),
+                -1,
+            )?;
+        }
+        orphaned_tasks.insert(key.to_vec(), task);
+        removed.tasks += 1;
+    }
+    if !orphaned_tasks.is_empty() {
+        for row in txn.iterator_cf_opt(
+            &IndexifyObjectsColumns::TaskAllocations.cf_db(db),
+            ReadOptions::default(),
+            IteratorMode::Start,
+        ) {
+            let (allocation_key, _) = row?;
+            let task_key = Task::key_from_allocation_key(&allocation_key)?;
+            let Some(task) = orphaned_tasks.get(&task_key) else {
+                continue;
+            };
+            txn.delete_cf(
+                &IndexifyObjectsColumns::TaskAllocations.cf_db(db),
+                &allocation_key,
+            )?;
+            let allocation_key = String::from_utf8(allocation_key.to_vec())?;
+            if let Some((executor_id, _)) = allocation_key.split_once('|') {
+                let executor_id = ExecutorId::new(executor_id.to_string());
+                scheduler_index::update(db, &txn, |index| index.remove(&executor_id, task))?;
+            }
+        }
+    }
+
+    for row in txn.iterator_cf_opt(
+        &IndexifyObjectsColumns::FnOutputs.cf_db(db),
+        ReadOptions::default(),
+        IteratorMode::Start,
+    ) {
+        let (key, value) = row?;
+        let Some((namespace, compute_graph, graph_exists)) = parents.orphaned(&key)? else {
+            continue;
+        };
+        let output =
+            JsonEncoder::decode::<NodeOutput>(IndexifyObjectsColumns::FnOutputs, &key, &value)?;
+        if let OutputPayload::Fn(payload) = &output.payload {
+            txn.put_cf(
+                &IndexifyObjectsColumns::GcUrls.cf_db(db),
+                payload.path.as_bytes(),
+                [],
+            )?;
+        }
+        txn.delete_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(db), &key)?;
+        if graph_exists {
+            counters::add(
+                db,
+                &txn,
+                &namespace,
+                &compute_graph,
+                Counter::DataObjects,
+                -1,
+            )?;
+        }
+        removed.outputs += 1;
+    }
+
+    removed.tombstones = cleared.len() as u64;
+    if dry_run {
+        txn.rollback()?;
+        return Ok(removed);
+    }
+    for prefix in &cleared {
+        txn.delete_cf(&tombstones_cf, prefix)?;
+    }
+    txn.commit()?;
+    for prefix in &cleared {
+        tombstones.remove(prefix);
+    }
+    Ok(removed)
+}
+
+/// Records a tombstone for a deleted graph, see `Tombstones`.
+pub(crate) fn bury_graph(
+    db: &TransactionDB,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    compute_graph: &str,
+) -> Result<()> {
+    txn.put_cf(
+        &IndexifyObjectsColumns::Tombstones.cf_db(db),
+        Tombstones::graph_prefix(namespace, compute_graph),
+        [],
+    )?;
+    Ok(())
+}
+
+/// Removes the tombstone of a graph created again, so that compaction
+/// doesn't drop the tasks of the new graph. The tasks left by the previous
+/// one wait for `reconcile`.
+pub(crate) fn exhume_graph(
+    db: &TransactionDB,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    compute_graph: &str,
+) -> Result<()> {
+    txn.delete_cf(
+        &IndexifyObjectsColumns::Tombstones.cf_db(db),
+        Tombstones::graph_prefix(namespace, compute_graph),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use data_model::test_objects::tests::{
+        create_mock_task,
+        mock_graph_a,
+        mock_invocation_payload,
+        mock_node_fn_output_fn_a,
+        TEST_NAMESPACE,
+    };
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        requests::{
+            CreateComputeGraphRequest,
+            DeleteComputeGraphRequest,
+            InvokeComputeGraphRequest,
+            RequestPayload,
+            StateMachineUpdateRequest,
+        },
+        IndexifyState,
+    };
+
+    async fn write(state: &IndexifyState, payload: RequestPayload) -> Result<()> {
+        state
+            .write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+            .await
+    }
+
+    fn task_exists(state: &IndexifyState, task: &Task) -> Result<bool> {
+        Ok(state
+            .db
+            .get_cf(&IndexifyObjectsColumns::Tasks.cf_db(&state.db), task.key())?
+            .is_some())
+    }
+
+    fn create_graph() -> RequestPayload {
+        RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            compute_graph: mock_graph_a(),
+            precondition: None,
+        })
+    }
+
+    fn delete_graph() -> RequestPayload {
+        RequestPayload::DeleteComputeGraph(DeleteComputeGraphRequest {
+            namespace: TEST_NAMESPACE.to_string(),
+            name: "graph_A".to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_orphans() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("state");
+        let state = IndexifyState::new(path.clone()).await?;
+        write(&state, create_graph()).await?;
+        let invocation = mock_invocation_payload();
+        write(
+            &state,
+            RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: "graph_A".to_string(),
+                invocation_payload: invocation.clone(),
+            }),
+        )
+        .await?;
+
+        // Records of an invocation deleted without cascading.
+        let graph = mock_graph_a();
+        let live = create_mock_task(&graph, "fn_a", "input", &invocation.id);
+        let orphan = create_mock_task(&graph, "fn_a", "input", "deleted");
+        for task in [&live, &orphan] {
+            state.db.put_cf(
+                &IndexifyObjectsColumns::Tasks.cf_db(&state.db),
+                task.key(),
+                JsonEncoder::encode(IndexifyObjectsColumns::Tasks, task.key(), task)?,
+            )?;
+        }
+        state.db.put_cf(
+            &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&state.db),
+            orphan.key(),
+            [],
+        )?;
+        let output = mock_node_fn_output_fn_a("deleted", "graph_A", None);
+        let output_key = output.key(&output.invocation_id);
+        state.db.put_cf(
+            &IndexifyObjectsColumns::FnOutputs.cf_db(&state.db),
+            &output_key,
+            JsonEncoder::encode(IndexifyObjectsColumns::FnOutputs, &output_key, &output)?,
+        )?;
+
+        let removed = state.reconcile_orphans().await?;
+        assert_eq!(
+            removed,
+            OrphansRemoved {
+                tasks: 1,
+                outputs: 1,
+                tombstones: 0,
+            }
+        );
+        let reader = state.reader();
+        assert!(task_exists(&state, &live)?);
+        assert!(!task_exists(&state, &orphan)?);
+        assert_eq!(reader.count_unallocated_tasks(10)?, 0);
+        assert_eq!(reader.get_gc_urls(None)?.len(), 1);
+
+        // Deleting the graph leaves its tasks to compaction.
+        write(&state, delete_graph()).await?;
+        assert!(state.tombstones.covers(live.key().as_bytes()));
+        let mut filter =
+            compaction_filter(&IndexifyObjectsColumns::Tasks, state.tombstones.clone()).unwrap();
+        assert!(matches!(
+            filter(0, live.key().as_bytes(), b""),
+            Decision::Remove
+        ));
+        assert!(matches!(
+            filter(0, b"test_ns|graph_B|invocation|fn_a|id", b""),
+            Decision::Keep
+        ));
+        assert!(
+            compaction_filter(&IndexifyObjectsColumns::FnOutputs, state.tombstones.clone())
+                .is_none()
+        );
+        // Creating the graph again stops compaction from dropping its tasks.
+        write(&state, create_graph()).await?;
+        assert!(!state.tombstones.covers(live.key().as_bytes()));
+
+        // Tombstones survive restarts until reconciled.
+        write(&state, delete_graph()).await?;
+        drop(filter);
+        drop(reader);
+        drop(state);
+        let state = IndexifyState::new(path).await?;
+        assert_eq!(state.tombstones.len(), 1);
+        let removed = state.reconcile_orphans().await?;
+        assert_eq!((removed.tasks, removed.tombstones), (1, 1));
+        assert!(state.tombstones.is_empty());
+        assert!(!task_exists(&state, &live)?);
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::{anyhow, Result};
+use rocksdb::{DBWithThreadMode, MultiThreaded, Options, DB};
+use strum::IntoEnumIterator;
+
+use crate::{
+    scanner::{ReadDb, StateReader},
+    state_machine::IndexifyObjectsColumns,
+};
+
+// Distinguishes the secondaries a process opens, each needs a directory of
+// its own.
+static SECONDARY_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A read-only view of a state store another process has open for writes.
+/// It's backed by a RocksDB secondary instance, which takes no lock on the
+/// store and sees the writes of the primary up to the last `catch_up`.
+pub struct ReadOnlyState {
+    db: Arc<DBWithThreadMode<MultiThreaded>>,
+    secondary_path: PathBuf,
+}
+
+impl ReadOnlyState {
+    pub fn open(path: &Path) -> Result<Self> {
+        let columns = DB::list_cf(&Options::default(), path)
+            .map_err(|e| anyhow!("failed to list the columns of {}: {}", path.display(), e))?;
+        let missing = IndexifyObjectsColumns::iter()
+            .map(|cf| cf.to_string())
+            .filter(|cf| !columns.contains(cf))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "state store at {} is missing columns {:?}, open it with this server version first",
+                path.display(),
+                missing
+            ));
+        }
+
+        let secondary_path = std::env::temp_dir().join(format!(
+            "indexify-secondary-{}-{}",
+            std::process::id(),
+            SECONDARY_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&secondary_path)?;
+        let mut options = Options::default();
+        // Secondaries have to keep all files open to follow the primary.
+        options.set_max_open_files(-1);
+        let db = DBWithThreadMode::<MultiThreaded>::open_cf_as_secondary(
+            &options,
+            path,
+            &secondary_path,
+            columns,
+        )
+        .map_err(|e| anyhow!("failed to open secondary db: {}", e))?;
+        Ok(Self {
+            db: Arc::new(db),
+            secondary_path,
+        })
+    }
+
+    /// Applies the writes the primary made since the last call.
+    pub fn catch_up(&self) -> Result<()> {
+        self.db
+            .try_catch_up_with_primary()
+            .map_err(|e| anyhow!("failed to catch up with primary: {}", e))
+    }
+
+    pub fn reader(&self) -> StateReader<'static> {
+        StateReader::from_read_db(ReadDb::Secondary(self.db.clone()))
+    }
+}
+
+impl Drop for ReadOnlyState {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.secondary_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::{
+        requests::{NamespaceRequest, RequestPayload, StateMachineUpdateRequest},
+        IndexifyState,
+    };
+
+    #[tokio::test]
+    async fn test_read_only_follows_primary() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("state");
+        let indexify_state = IndexifyState::new(path.clone()).await?;
+        let create_namespace = |name: &str| StateMachineUpdateRequest {
+            payload: RequestPayload::CreateNameSpace(NamespaceRequest {
+                name: name.to_string(),
+                blob_storage: None,
+                scheduling_weight: None,
+                reprocessing_policy: Default::default(),
+                near_duplicates: None,
+            }),
+            state_changes_processed: vec![],
+        };
+        indexify_state.write(create_namespace("first")).await?;
+
+        let read_only = IndexifyState::open_read_only(path)?;
+        assert!(read_only.reader().get_namespace("first")?.is_some());
+
+        indexify_state.write(create_namespace("second")).await?;
+        assert!(read_only.reader().get_namespace("second")?.is_none());
+        read_only.catch_up()?;
+        assert!(read_only.reader().get_namespace("second")?.is_some());
+        read_only.reader().check_readable()?;
+        Ok(())
+    }
+}
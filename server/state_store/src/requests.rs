@@ -1,15 +1,31 @@
+use std::fmt;
+
 use data_model::{
+    activity::Activity,
+    artifacts::{Artifact, ArtifactRef},
+    audit::TaskIntervention,
+    near_duplicates::NearDuplicatePolicy,
+    output_checks::{OutputCheckViolation, QuarantinedOutput},
+    subscriptions::Subscription,
+    validation::{validate_blob_storage_placement, validate_name, Validate, ValidationErrors},
+    ArchivedInvocation,
+    BlobStoragePlacement,
     ComputeGraph,
     ExecutorId,
     ExecutorMetadata,
+    ExpiredOutputs,
     GraphVersion,
     InvocationPayload,
     NodeOutput,
     ReduceTask,
+    ReprocessingPolicy,
     StateChangeId,
     Task,
     TaskDiagnostics,
     TaskId,
+    TaskRejectionReason,
+    TraceContext,
+    UrlFetch,
 };
 
 pub struct StateMachineUpdateRequest {
@@ -17,21 +33,198 @@ pub struct StateMachineUpdateRequest {
     pub state_changes_processed: Vec<StateChangeId>,
 }
 
+#[derive(strum::AsRefStr)]
 pub enum RequestPayload {
     InvokeComputeGraph(InvokeComputeGraphRequest),
     RerunComputeGraph(RerunComputeGraphRequest),
     RerunInvocation(RerunInvocationRequest),
     FinalizeTask(FinalizeTaskRequest),
+    RejectTask(RejectTaskRequest),
     CreateNameSpace(NamespaceRequest),
+    DeleteNamespace(DeleteNamespaceRequest),
+    AdvanceNamespaceDeletion(DeleteNamespaceRequest),
     CreateComputeGraph(CreateComputeGraphRequest),
     DeleteComputeGraph(DeleteComputeGraphRequest),
+    StartCanary(StartCanaryRequest),
+    FinishCanary(FinishCanaryRequest),
     DeleteInvocation(DeleteInvocationRequest),
+    /// Deletes a finished invocation and everything it produced.
+    ExpireInvocation(DeleteInvocationRequest),
+    /// Deletes outputs of a finished invocation its graph no longer retains.
+    ExpireOutputs(ExpireOutputsRequest),
+    PinInvocationOutputs(PinInvocationOutputsRequest),
+    /// Records an invocation waiting for the download of its input, or why it
+    /// couldn't be invoked.
+    PutUrlFetch(UrlFetch),
+    ArchiveInvocation(ArchiveInvocationRequest),
     SchedulerUpdate(SchedulerUpdateRequest),
     RegisterExecutor(RegisterExecutorRequest),
     DeregisterExecutor(DeregisterExecutorRequest),
+    /// Blobs which nothing references, e.g. uploaded for an invocation which
+    /// wasn't created.
+    AddGcUrls(Vec<String>),
     RemoveGcUrls(Vec<String>),
+    RemoveAnalyticsRecords(Vec<String>),
+    RemoveAlerts(Vec<String>),
+    RecordActivity(RecordActivityRequest),
+    PruneActivity(PruneActivityRequest),
+    /// Forgets the ids of requests applied before, see
+    /// `IndexifyState::write_once`.
+    PruneAppliedRequests(PruneAppliedRequestsRequest),
     UpdateSystemTask(UpdateSystemTaskRequest),
     RemoveSystemTask(RemoveSystemTaskRequest),
+    RegisterArtifact(RegisterArtifactRequest),
+    DeleteArtifact(DeleteArtifactRequest),
+    UpdateExecutorArtifacts(UpdateExecutorArtifactsRequest),
+    InterveneTask(InterveneTaskRequest),
+    CreateSubscription(CreateSubscriptionRequest),
+    DeleteSubscription(DeleteSubscriptionRequest),
+    AckSubscriptionEvents(AckSubscriptionEventsRequest),
+    ReplaySubscription(ReplaySubscriptionRequest),
+    WriteBatch(WriteBatchRequest),
+}
+
+impl RequestPayload {
+    /// Validates user supplied objects before they are written to the state
+    /// store.
+    pub fn validate(&self) -> Result<(), ValidationErrors> {
+        match self {
+            RequestPayload::CreateNameSpace(request) => {
+                let mut errors = ValidationErrors::default();
+                validate_name(&mut errors, "name", &request.name);
+                if let Some(placement) = &request.blob_storage {
+                    validate_blob_storage_placement(&mut errors, "blob_storage", placement);
+                }
+                if request.scheduling_weight == Some(0) {
+                    errors.add("scheduling_weight", "must be greater than zero");
+                }
+                if let Some(policy) = &request.near_duplicates {
+                    if !(policy.threshold > 0.0 && policy.threshold <= 1.0) {
+                        errors.add("near_duplicates.threshold", "must be in (0, 1]");
+                    }
+                    if policy.window_secs == 0 {
+                        errors.add("near_duplicates.window_secs", "must be greater than zero");
+                    }
+                }
+                errors.into_result()
+            }
+            RequestPayload::CreateComputeGraph(request) => request.compute_graph.validate(),
+            RequestPayload::StartCanary(request) => {
+                let mut errors = match request.compute_graph.validate() {
+                    Ok(()) => ValidationErrors::default(),
+                    Err(errors) => errors,
+                };
+                if request.percent > 100 {
+                    errors.add("percent", "must be at most 100");
+                }
+                errors.into_result()
+            }
+            RequestPayload::InvokeComputeGraph(request) => request.invocation_payload.validate(),
+            RequestPayload::WriteBatch(request) => {
+                let mut errors = ValidationErrors::default();
+                if request.payloads.is_empty() {
+                    errors.add("payloads", "must not be empty");
+                }
+                for payload in &request.payloads {
+                    if let RequestPayload::WriteBatch(_) = payload {
+                        errors.add("payloads", "write batches can't be nested");
+                    } else if let Err(e) = payload.validate() {
+                        errors.0.extend(e.0);
+                    }
+                }
+                errors.into_result()
+            }
+            RequestPayload::RegisterExecutor(request) => request.executor.validate(),
+            RequestPayload::CreateSubscription(request) => {
+                let mut errors = ValidationErrors::default();
+                let url = &request.subscription.url;
+                if !url.starts_with("http://") && !url.starts_with("https://") {
+                    errors.add("url", "must be an http:// or https:// url");
+                }
+                if request.subscription.secret.is_empty() {
+                    errors.add("secret", "must not be empty");
+                }
+                errors.into_result()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The namespace the request adds data to, if any. Such requests are
+    /// refused once the namespace is being deleted.
+    pub fn written_namespace(&self) -> Option<&str> {
+        match self {
+            RequestPayload::CreateNameSpace(request) => Some(&request.name),
+            RequestPayload::CreateComputeGraph(request) => Some(&request.namespace),
+            RequestPayload::StartCanary(request) => Some(&request.namespace),
+            RequestPayload::InvokeComputeGraph(request) => Some(&request.namespace),
+            RequestPayload::RerunComputeGraph(request) => Some(&request.namespace),
+            RequestPayload::RerunInvocation(request) => Some(&request.namespace),
+            RequestPayload::FinalizeTask(request) => Some(&request.namespace),
+            RequestPayload::RegisterArtifact(request) => Some(&request.artifact.namespace),
+            RequestPayload::InterveneTask(request) => Some(&request.namespace),
+            RequestPayload::CreateSubscription(request) => Some(&request.subscription.namespace),
+            _ => None,
+        }
+    }
+
+    /// Whether the request must be written even when the state store is over
+    /// its disk quota: the ones which let accepted work finish, remove data
+    /// or keep executors registered. Requests bringing in new work are not.
+    pub fn is_essential(&self) -> bool {
+        if let RequestPayload::WriteBatch(request) = self {
+            return request.payloads.iter().all(RequestPayload::is_essential);
+        }
+        !matches!(
+            self,
+            RequestPayload::InvokeComputeGraph(_) |
+                RequestPayload::RerunComputeGraph(_) |
+                RequestPayload::RerunInvocation(_) |
+                RequestPayload::CreateNameSpace(_) |
+                RequestPayload::CreateComputeGraph(_) |
+                RequestPayload::StartCanary(_) |
+                RequestPayload::RegisterArtifact(_) |
+                RequestPayload::CreateSubscription(_)
+        )
+    }
+
+    /// The requests written by the request, those of a batch or the request
+    /// itself.
+    pub fn payloads(&self) -> Vec<&RequestPayload> {
+        match self {
+            RequestPayload::WriteBatch(request) => request.payloads.iter().collect(),
+            _ => vec![self],
+        }
+    }
+
+    /// Trace of the invocation the request is made for, if any.
+    pub fn trace(&self) -> Option<&TraceContext> {
+        match self {
+            RequestPayload::InvokeComputeGraph(request) => Some(&request.invocation_payload.trace),
+            RequestPayload::WriteBatch(request) => {
+                request.payloads.iter().find_map(RequestPayload::trace)
+            }
+            RequestPayload::SchedulerUpdate(request) => request
+                .task_requests
+                .iter()
+                .flat_map(|request| request.tasks.iter())
+                .chain(request.allocations.iter().map(|placement| &placement.task))
+                .map(|task| &task.trace)
+                .next(),
+            _ => None,
+        }
+    }
+}
+
+/// Requests written in a single transaction, in order: either all of them
+/// are written or none is. Batches can't be nested.
+pub struct WriteBatchRequest {
+    pub payloads: Vec<RequestPayload>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeleteNamespaceRequest {
+    pub name: String,
 }
 
 #[derive(Debug, Clone)]
@@ -41,6 +234,23 @@ pub struct UpdateSystemTaskRequest {
     pub restart_key: Vec<u8>,
 }
 
+#[derive(Debug, Clone)]
+pub struct RecordActivityRequest {
+    /// None for events of the whole server.
+    pub namespace: Option<String>,
+    pub activity: Activity,
+}
+
+#[derive(Debug, Clone)]
+pub struct PruneActivityRequest {
+    pub recorded_before: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct PruneAppliedRequestsRequest {
+    pub applied_before: u64,
+}
+
 #[derive(Debug, Clone)]
 pub struct RemoveSystemTaskRequest {
     pub namespace: String,
@@ -66,6 +276,28 @@ pub struct FinalizeTaskRequest {
     pub task_outcome: data_model::TaskOutcome,
     pub executor_id: ExecutorId,
     pub diagnostics: Option<TaskDiagnostics>,
+    pub output_checks: Option<OutputCheckReport>,
+}
+
+/// Results of evaluating the output checks of a function on the outputs of a
+/// task. Quarantined outputs aren't part of the task's node outputs.
+#[derive(Debug, Clone, Default)]
+pub struct OutputCheckReport {
+    pub outputs_checked: u64,
+    pub violations: Vec<OutputCheckViolation>,
+    pub quarantined_outputs: Vec<QuarantinedOutput>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RejectTaskRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub compute_fn: String,
+    pub invocation_id: String,
+    pub task_id: TaskId,
+    pub executor_id: ExecutorId,
+    pub reason: TaskRejectionReason,
+    pub message: Option<String>,
 }
 
 pub struct InvokeComputeGraphRequest {
@@ -82,13 +314,238 @@ pub struct RerunComputeGraphRequest {
 
 pub struct NamespaceRequest {
     pub name: String,
+    pub blob_storage: Option<BlobStoragePlacement>,
+    pub scheduling_weight: Option<u32>,
+    pub reprocessing_policy: ReprocessingPolicy,
+    pub near_duplicates: Option<NearDuplicatePolicy>,
 }
 
 pub struct CreateComputeGraphRequest {
     pub namespace: String,
     pub compute_graph: ComputeGraph,
+    /// Checked against the stored graph before it's replaced, the graph is
+    /// written unconditionally when unset.
+    pub precondition: Option<GraphPrecondition>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GraphPrecondition {
+    /// The graph doesn't exist yet.
+    Absent,
+    /// The graph exists, at any version.
+    Exists,
+    /// The graph exists at this version.
+    Version(GraphVersion),
+}
+
+impl GraphPrecondition {
+    pub fn holds(&self, current: Option<GraphVersion>) -> bool {
+        match (self, current) {
+            (GraphPrecondition::Absent, current) => current.is_none(),
+            (GraphPrecondition::Exists, current) => current.is_some(),
+            (GraphPrecondition::Version(expected), current) => current == Some(*expected),
+        }
+    }
+}
+
+/// Returned when writing a graph whose precondition doesn't hold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphVersionConflict {
+    pub name: String,
+    pub current: Option<GraphVersion>,
+}
+
+impl fmt::Display for GraphVersionConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.current {
+            Some(version) => write!(f, "compute graph {} is at version {}", self.name, version),
+            None => write!(f, "compute graph {} does not exist", self.name),
+        }
+    }
+}
+
+impl std::error::Error for GraphVersionConflict {}
+
+/// Returned when a namespace is written again with another blob storage
+/// placement. The blobs already stored are only known by their urls, which
+/// would no longer resolve to the storage holding them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamespacePlacementConflict(pub String);
+
+impl fmt::Display for NamespacePlacementConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "namespace {} already exists with another blob storage placement",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for NamespacePlacementConflict {}
+
+/// Returned when a write is inconsistent with the artifacts of a namespace.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArtifactError {
+    /// A function of the graph references an artifact which isn't registered.
+    Missing {
+        compute_fn: String,
+        artifact: ArtifactRef,
+    },
+    /// The version is already registered with different content.
+    Exists(ArtifactRef),
+    /// Functions of a graph still reference the artifact.
+    InUse {
+        artifact: ArtifactRef,
+        compute_graph: String,
+    },
+}
+
+impl fmt::Display for ArtifactError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArtifactError::Missing {
+                compute_fn,
+                artifact,
+            } => write!(
+                f,
+                "function {} references artifact {} version {} which is not registered",
+                compute_fn, artifact.name, artifact.version
+            ),
+            ArtifactError::Exists(artifact) => write!(
+                f,
+                "artifact {} version {} is already registered with different content",
+                artifact.name, artifact.version
+            ),
+            ArtifactError::InUse {
+                artifact,
+                compute_graph,
+            } => write!(
+                f,
+                "artifact {} version {} is used by compute graph {}",
+                artifact.name, artifact.version, compute_graph
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ArtifactError {}
+
+/// Starts rolling out a new version of a compute graph to `percent` of its
+/// new invocations. Starting the rollout of the version being rolled out
+/// only changes its share of invocations.
+pub struct StartCanaryRequest {
+    pub namespace: String,
+    pub compute_graph: ComputeGraph,
+    pub percent: u32,
+}
+
+pub struct FinishCanaryRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+    /// Makes the new version the stable one, it's dropped otherwise.
+    pub promote: bool,
+}
+
+/// Returned when a write is inconsistent with the canary rollout of a graph.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CanaryError {
+    GraphNotFound(String),
+    /// The graph has no rollout in progress.
+    NotFound(String),
+    /// The graph can't be updated while a rollout is in progress.
+    InProgress(String),
+    /// The rolled out graph is the same as the stable one.
+    Unchanged(String),
+}
+
+impl fmt::Display for CanaryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CanaryError::GraphNotFound(name) => write!(f, "compute graph {} not found", name),
+            CanaryError::NotFound(name) => {
+                write!(f, "compute graph {} has no canary rollout in progress", name)
+            }
+            CanaryError::InProgress(name) => write!(
+                f,
+                "a canary rollout of compute graph {} is in progress, promote or roll it back first",
+                name
+            ),
+            CanaryError::Unchanged(name) => write!(
+                f,
+                "the rolled out version of compute graph {} is the same as the stable one",
+                name
+            ),
+        }
+    }
 }
 
+impl std::error::Error for CanaryError {}
+
+/// Returned when an intervention can't be applied to a task.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskInterventionError {
+    TaskNotFound(TaskId),
+    /// The task already has an outcome.
+    TaskFinished(TaskId),
+    ExecutorNotFound(ExecutorId),
+}
+
+impl fmt::Display for TaskInterventionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskInterventionError::TaskNotFound(task_id) => write!(f, "task {} not found", task_id),
+            TaskInterventionError::TaskFinished(task_id) => {
+                write!(f, "task {} is already finished", task_id)
+            }
+            TaskInterventionError::ExecutorNotFound(executor_id) => {
+                write!(f, "executor {} is not registered", executor_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TaskInterventionError {}
+
+/// Returned when an executor's rejection of a task can't be recorded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskRejectionError {
+    TaskNotFound(TaskId),
+    /// The task isn't allocated to the rejecting executor.
+    NotAllocated(TaskId, ExecutorId),
+}
+
+impl fmt::Display for TaskRejectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskRejectionError::TaskNotFound(task_id) => write!(f, "task {} not found", task_id),
+            TaskRejectionError::NotAllocated(task_id, executor_id) => write!(
+                f,
+                "task {} is not allocated to executor {}",
+                task_id, executor_id
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TaskRejectionError {}
+
+/// Returned for writes which aren't essential while the state store is over
+/// its disk quota.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiskQuotaExceeded;
+
+impl fmt::Display for DiskQuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "state store is over its disk quota, not accepting new work"
+        )
+    }
+}
+
+impl std::error::Error for DiskQuotaExceeded {}
+
 pub struct DeleteComputeGraphRequest {
     pub namespace: String,
     pub name: String,
@@ -131,6 +588,24 @@ pub struct DeleteInvocationRequest {
     pub invocation_id: String,
 }
 
+pub struct ExpireOutputsRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub invocation_id: String,
+    pub outputs: ExpiredOutputs,
+}
+
+pub struct PinInvocationOutputsRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub invocation_id: String,
+    pub pinned: bool,
+}
+
+pub struct ArchiveInvocationRequest {
+    pub archive: ArchivedInvocation,
+}
+
 pub struct RegisterExecutorRequest {
     pub executor: ExecutorMetadata,
 }
@@ -138,3 +613,139 @@ pub struct RegisterExecutorRequest {
 pub struct DeregisterExecutorRequest {
     pub executor_id: ExecutorId,
 }
+
+pub struct RegisterArtifactRequest {
+    pub artifact: Artifact,
+}
+
+pub struct DeleteArtifactRequest {
+    pub namespace: String,
+    pub artifact: ArtifactRef,
+}
+
+pub struct InterveneTaskRequest {
+    pub namespace: String,
+    pub compute_graph: String,
+    pub invocation_id: String,
+    pub compute_fn: String,
+    pub task_id: TaskId,
+    pub intervention: TaskIntervention,
+    /// Recorded in the audit log along with the intervention.
+    pub reason: Option<String>,
+}
+
+pub struct UpdateExecutorArtifactsRequest {
+    pub executor_id: ExecutorId,
+    /// Hashes of the artifacts the executor has fetched.
+    pub artifacts: Vec<String>,
+}
+
+pub struct CreateSubscriptionRequest {
+    pub subscription: Subscription,
+}
+
+pub struct DeleteSubscriptionRequest {
+    pub namespace: String,
+    pub id: String,
+}
+
+/// Moves the cursor of a subscription to the last event the subscriber
+/// acknowledged. Ignored when the cursor is no longer at
+/// `previous_sequence`, e.g. because a replay was requested meanwhile.
+pub struct AckSubscriptionEventsRequest {
+    pub namespace: String,
+    pub id: String,
+    pub previous_sequence: u64,
+    pub sequence: u64,
+}
+
+/// Delivers the events of a subscription again, starting at `from_sequence`.
+pub struct ReplaySubscriptionRequest {
+    pub namespace: String,
+    pub id: String,
+    pub from_sequence: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubscriptionError {
+    NotFound(String),
+    /// The events before `earliest` were removed, the ones after `latest`
+    /// weren't published yet.
+    SequenceOutOfRange {
+        sequence: u64,
+        earliest: u64,
+        latest: u64,
+    },
+}
+
+impl fmt::Display for SubscriptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubscriptionError::NotFound(id) => write!(f, "subscription {} not found", id),
+            SubscriptionError::SequenceOutOfRange {
+                sequence,
+                earliest,
+                latest,
+            } => write!(
+                f,
+                "cannot replay from sequence {}, events {} to {} are available",
+                sequence, earliest, latest
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SubscriptionError {}
+
+/// Refusal to invoke a graph with a version of a source that isn't newer
+/// than its current version, or with content it already ingested.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContentVersionError {
+    Stale {
+        source: String,
+        current_invocation: String,
+    },
+    Unchanged {
+        source: String,
+        current_invocation: String,
+    },
+    /// The content is a near-duplicate of an earlier invocation's input and
+    /// the namespace skips near-duplicates.
+    NearDuplicate {
+        current_invocation: String,
+        similarity: f64,
+    },
+}
+
+impl fmt::Display for ContentVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContentVersionError::Stale {
+                source,
+                current_invocation,
+            } => write!(
+                f,
+                "source {} is older than the version of invocation {}",
+                source, current_invocation
+            ),
+            ContentVersionError::Unchanged {
+                source,
+                current_invocation,
+            } => write!(
+                f,
+                "source {} is unchanged since invocation {}",
+                source, current_invocation
+            ),
+            ContentVersionError::NearDuplicate {
+                current_invocation,
+                similarity,
+            } => write!(
+                f,
+                "content is a near-duplicate of the input of invocation {} (similarity {:.2})",
+                current_invocation, similarity
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ContentVersionError {}
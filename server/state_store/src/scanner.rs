@@ -1,27 +1,64 @@
-use std::{mem, sync::Arc};
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    mem,
+    sync::Arc,
+};
 
 use anyhow::{anyhow, Result};
 use data_model::{
+    activity::ActivityEvent,
+    alerts::Alert,
+    analytics::AnalyticsRecord,
+    artifacts::Artifact,
+    audit::AuditLogEntry,
+    canary::GraphCanary,
+    output_checks::{OutputCheckStats, QuarantinedOutput},
+    subscriptions::{Subscription, SubscriptionEvent},
+    ArchivedInvocation,
     ComputeGraph,
     DataPayload,
     ExecutorId,
     ExecutorMetadata,
+    ExpiredOutputs,
+    GpuDeviceUsage,
     GraphInvocationCtx,
+    GraphVersion,
     InvocationPayload,
     Namespace,
     NodeOutput,
+    OutputRetention,
     ReduceTask,
+    ResourceUsageStats,
     StateChange,
     SystemTask,
     Task,
     TaskAnalytics,
     TaskFinishedEvent,
+    UrlFetch,
+};
+use rocksdb::{
+    DBWithThreadMode,
+    Direction,
+    IteratorMode,
+    MultiThreaded,
+    ReadOptions,
+    SnapshotWithThreadMode,
+    TransactionDB,
 };
-use rocksdb::{Direction, IteratorMode, ReadOptions, TransactionDB};
 use serde::de::DeserializeOwned;
+use strum::IntoEnumIterator;
+use tokio::sync::broadcast;
+use tracing::warn;
 
 use super::state_machine::IndexifyObjectsColumns;
-use crate::serializer::{JsonEncode, JsonEncoder};
+use crate::{
+    change_feed::ObjectChange,
+    counters::{decode_count, Counter, GraphCounts},
+    key_migration::{self, KeyLayoutMigration},
+    metrics::{KeyMigrationLabels, Metrics, ReadTimer},
+    scheduler_index::{SchedulerIndex, SCHEDULER_INDEX_KEY},
+    serializer::{JsonEncode, JsonEncoder},
+};
 #[derive(Debug)]
 pub struct FilterResponse<T> {
     pub items: Vec<T>,
@@ -29,13 +66,166 @@ pub struct FilterResponse<T> {
     pub cursor: Vec<u8>,
 }
 
-pub struct StateReader {
-    db: Arc<TransactionDB>,
+pub const STATE_CHANGE_BATCH_SIZE: usize = 10;
+
+/// An unfinished task of the queue, along with the executor it's allocated to
+/// if it has been placed.
+#[derive(Debug, Clone)]
+pub struct QueuedTask {
+    pub task: Task,
+    pub executor_id: Option<ExecutorId>,
+}
+
+type KeyValue = (Box<[u8]>, Box<[u8]>);
+
+/// The database a reader reads from, either the store opened for writes or a
+/// secondary instance following it from another process.
+#[derive(Clone)]
+pub(crate) enum ReadDb {
+    Primary(Arc<TransactionDB>),
+    Secondary(Arc<DBWithThreadMode<MultiThreaded>>),
+}
+
+impl ReadDb {
+    fn get_cf_opt(
+        &self,
+        column: &IndexifyObjectsColumns,
+        key: impl AsRef<[u8]>,
+        read_options: &ReadOptions,
+    ) -> Result<Option<Vec<u8>>, rocksdb::Error> {
+        match self {
+            ReadDb::Primary(db) => db.get_cf_opt(&column.cf_db(db), key, read_options),
+            ReadDb::Secondary(db) => db.get_cf_opt(&column.cf_secondary(db), key, read_options),
+        }
+    }
+
+    fn iterator_cf_opt<'b>(
+        &'b self,
+        column: &IndexifyObjectsColumns,
+        read_options: ReadOptions,
+        mode: IteratorMode,
+    ) -> Box<dyn Iterator<Item = Result<KeyValue, rocksdb::Error>> + 'b> {
+        match self {
+            ReadDb::Primary(db) => {
+                Box::new(db.iterator_cf_opt(&column.cf_db(db), read_options, mode))
+            }
+            ReadDb::Secondary(db) => {
+                Box::new(db.iterator_cf_opt(&column.cf_secondary(db), read_options, mode))
+            }
+        }
+    }
+
+    fn multi_get_cf_opt(
+        &self,
+        column: &IndexifyObjectsColumns,
+        keys: Vec<Vec<u8>>,
+        read_options: &ReadOptions,
+    ) -> Vec<Result<Option<Vec<u8>>, rocksdb::Error>> {
+        match self {
+            ReadDb::Primary(db) => {
+                let cf = column.cf_db(db);
+                db.multi_get_cf_opt(keys.iter().map(|key| (&cf, key)), read_options)
+            }
+            ReadDb::Secondary(db) => {
+                let cf = column.cf_secondary(db);
+                db.multi_get_cf_opt(keys.iter().map(|key| (&cf, key)), read_options)
+            }
+        }
+    }
+}
+
+pub struct StateReader<'a> {
+    db: ReadDb,
+    snapshot: Option<SnapshotWithThreadMode<'a, TransactionDB>>,
+    change_feed: Option<broadcast::Sender<ObjectChange>>,
+    metrics: Option<Metrics>,
 }
 
-impl StateReader {
+impl<'a> StateReader<'a> {
     pub fn new(db: Arc<TransactionDB>) -> Self {
-        Self { db }
+        Self::from_read_db(ReadDb::Primary(db))
+    }
+
+    pub(crate) fn from_read_db(db: ReadDb) -> Self {
+        Self {
+            db,
+            snapshot: None,
+            change_feed: None,
+            metrics: None,
+        }
+    }
+
+    /// Lets the reader watch the changes sent to `change_feed`.
+    pub(crate) fn with_change_feed(mut self, change_feed: broadcast::Sender<ObjectChange>) -> Self {
+        self.change_feed = Some(change_feed);
+        self
+    }
+
+    /// Lets the reader report the shadow reads of key layout migrations.
+    pub(crate) fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub(crate) fn change_feed(&self) -> Option<&broadcast::Sender<ObjectChange>> {
+        self.change_feed.as_ref()
+    }
+
+    /// A reader which sees the state as of its creation, unaffected by the
+    /// writes made while it's in use. Multi-step reads which must agree with
+    /// each other go through one.
+    pub fn with_snapshot(db: &'a Arc<TransactionDB>) -> Self {
+        Self {
+            db: ReadDb::Primary(db.clone()),
+            snapshot: Some(db.snapshot()),
+            change_feed: None,
+            metrics: None,
+        }
+    }
+
+    fn read_options(&self) -> ReadOptions {
+        let mut read_options = ReadOptions::default();
+        if let Some(snapshot) = &self.snapshot {
+            read_options.set_snapshot(snapshot);
+        }
+        read_options
+    }
+
+    fn read_timer(&self, column: &IndexifyObjectsColumns, op: &str) -> Option<ReadTimer> {
+        self.metrics
+            .as_ref()
+            .map(|metrics| metrics.read(column.as_ref(), op))
+    }
+
+    fn get_raw(
+        &self,
+        column: &IndexifyObjectsColumns,
+        key: impl AsRef<[u8]>,
+    ) -> Result<Option<Vec<u8>>> {
+        let timer = self.read_timer(column, "get");
+        let value = self.db.get_cf_opt(column, key, &self.read_options())?;
+        if let (Some(timer), Some(_)) = (&timer, &value) {
+            timer.records(1);
+        }
+        Ok(value)
+    }
+
+    /// Iterates over a column, the scan is timed until the iterator is
+    /// dropped.
+    fn scan<'b>(
+        &'b self,
+        column: &IndexifyObjectsColumns,
+        read_options: ReadOptions,
+        mode: IteratorMode,
+    ) -> impl Iterator<Item = Result<KeyValue, rocksdb::Error>> + 'b {
+        let timer = self.read_timer(column, "scan");
+        self.db
+            .iterator_cf_opt(column, read_options, mode)
+            .inspect(move |kv| {
+                if let (Some(timer), Ok(_)) = (&timer, kv) {
+                    timer.records(1);
+                }
+            })
     }
 
     pub fn get_rows_from_cf_multi_key<V>(
@@ -46,20 +236,63 @@ impl StateReader {
     where
         V: DeserializeOwned,
     {
-        let cf_handle = self
-            .db
-            .cf_handle(column.as_ref())
-            .ok_or(anyhow::anyhow!("Failed to get column family {}", column))?;
-        let mut items = Vec::new();
-        for key in keys {
-            let value = self.db.get_cf(&cf_handle, key)?.ok_or(anyhow::anyhow!(
-                "Key not found {}",
-                String::from_utf8(key.to_vec()).unwrap_or_default()
-            ))?;
-            let value = JsonEncoder::decode(&value).map_err(|e| anyhow::anyhow!(e.to_string()))?;
-            items.push(value);
+        let values = self.multi_get::<V, _>(&column, &keys)?;
+        keys.iter()
+            .zip(values)
+            .map(|(key, value)| {
+                value.ok_or(anyhow::anyhow!(
+                    "Key not found {}",
+                    String::from_utf8(key.to_vec()).unwrap_or_default()
+                ))
+            })
+            .collect()
+    }
+
+    /// Reads the records of `keys` in a single RocksDB multi-get, in the
+    /// order of the keys, none for the keys without a record.
+    pub fn multi_get<T, K>(
+        &self,
+        column: &IndexifyObjectsColumns,
+        keys: &[K],
+    ) -> Result<Vec<Option<T>>>
+    where
+        T: DeserializeOwned,
+        K: AsRef<[u8]>,
+    {
+        if keys.is_empty() {
+            return Ok(vec![]);
         }
-        Ok(items)
+        let timer = self.read_timer(column, "multi_get");
+        let values = self.db.multi_get_cf_opt(
+            column,
+            keys.iter().map(|key| key.as_ref().to_vec()).collect(),
+            &self.read_options(),
+        );
+        if let Some(timer) = &timer {
+            timer.records(
+                values
+                    .iter()
+                    .filter(|value| matches!(value, Ok(Some(_))))
+                    .count() as u64,
+            );
+        }
+        drop(timer);
+        let migration = key_migration::migrating(column);
+        keys.iter()
+            .zip(values)
+            .map(|(key, value)| {
+                let value = value?;
+                if let Some(migration) = migration {
+                    self.shadow_read(migration, key.as_ref(), value.as_deref());
+                }
+                value
+                    .map(|value| {
+                        JsonEncoder::decode::<T>(column, key, &value)
+                            .map_err(|e| anyhow::anyhow!("Deserialization error: {}", e))
+                    })
+                    .transpose()
+            })
+            .collect()
     }
 
     pub fn get_raw_rows_from_cf_with_limits(
@@ -69,20 +302,13 @@ impl StateReader {
         column: IndexifyObjectsColumns,
         limit: Option<usize>,
     ) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, Option<Vec<u8>>)> {
-        let cf_handle = self
-            .db
-            .cf_handle(column.as_ref())
-            .ok_or(anyhow::anyhow!("Failed to get column family {}", column))?;
-
-        let mut read_options = ReadOptions::default();
+        let mut read_options = self.read_options();
         read_options.set_readahead_size(4_194_304);
         let iterator_mode = match restart_key {
             Some(restart_key) => IteratorMode::From(restart_key, Direction::Forward),
             None => IteratorMode::From(&key_prefix, Direction::Forward),
         };
-        let iter = self
-            .db
-            .iterator_cf_opt(&cf_handle, read_options, iterator_mode);
+        let iter = self.scan(&column, read_options, iterator_mode);
 
         let mut items = Vec::new();
         let limit = limit.unwrap_or(usize::MAX);
@@ -112,20 +338,13 @@ impl StateReader {
     where
         V: DeserializeOwned,
     {
-        let cf_handle = self
-            .db
-            .cf_handle(column.as_ref())
-            .ok_or(anyhow::anyhow!("Failed to get column family {}", column))?;
-
-        let mut read_options = ReadOptions::default();
+        let mut read_options = self.read_options();
         read_options.set_readahead_size(4_194_304);
         let iterator_mode = match restart_key {
             Some(restart_key) => IteratorMode::From(restart_key, Direction::Forward),
             None => IteratorMode::From(&key_prefix, Direction::Forward),
         };
-        let iter = self
-            .db
-            .iterator_cf_opt(&cf_handle, read_options, iterator_mode);
+        let iter = self.scan(&column, read_options, iterator_mode);
 
         let mut items = Vec::new();
         let limit = limit.unwrap_or(usize::MAX);
@@ -135,7 +354,8 @@ impl StateReader {
             if !key.starts_with(key_prefix) {
                 break;
             }
-            let value = JsonEncoder::decode(&value).map_err(|e| anyhow::anyhow!(e.to_string()))?;
+            let value = JsonEncoder::decode(&column, &key, &value)
+                .map_err(|e| anyhow::anyhow!(e.to_string()))?;
             if items.len() < limit {
                 items.push(value);
             } else {
@@ -161,9 +381,7 @@ impl StateReader {
         F: Fn(&T) -> bool,
         K: Fn(&[u8]) -> Result<Vec<u8>, anyhow::Error>,
     {
-        let index_cf = index_column.cf_db(&self.db);
-        let data_cf = data_column.cf_db(&self.db);
-        let mut read_options = ReadOptions::default();
+        let mut read_options = self.read_options();
         read_options.set_readahead_size(4_194_304);
         let mode = match restart_key {
             Some(restart_key) => IteratorMode::From(restart_key, Direction::Forward),
@@ -175,7 +393,7 @@ impl StateReader {
                 }
             }
         };
-        let iter = self.db.iterator_cf_opt(&index_cf, read_options, mode);
+        let iter = self.scan(&index_column, read_options, mode);
         let mut items = Vec::new();
         let mut total = 0;
         let limit = limit.unwrap_or(usize::MAX);
@@ -183,11 +401,23 @@ impl StateReader {
         let mut lookup_keys = Vec::new();
         let mut keys = Vec::<Box<[u8]>>::new();
 
-        let mut get_entries = |lookup_keys, keys: Vec<Box<[u8]>>| -> Result<bool> {
-            let res = &self.db.multi_get_cf(lookup_keys);
+        let mut get_entries = |lookup_keys: Vec<Vec<u8>>, keys: Vec<Box<[u8]>>| -> Result<bool> {
+            let timer = self.read_timer(&data_column, "multi_get");
+            let res =
+                &self
+                    .db
+                    .multi_get_cf_opt(&data_column, lookup_keys.clone(), &self.read_options());
+            if let Some(timer) = &timer {
+                timer.records(
+                    res.iter()
+                        .filter(|value| matches!(value, Ok(Some(_))))
+                        .count() as u64,
+                );
+            }
+            drop(timer);
             for (index, value) in res.into_iter().enumerate() {
                 if let Ok(Some(value)) = value {
-                    let item = JsonEncoder::decode::<T>(&value)?;
+                    let item = JsonEncoder::decode::<T>(&data_column, &lookup_keys[index], &value)?;
                     if filter(&item) {
                         if items.len() < limit {
                             total += 1;
@@ -207,7 +437,7 @@ impl StateReader {
                 if !key.starts_with(key_prefix) {
                     break;
                 }
-                lookup_keys.push((&data_cf, key_reference(&key)?));
+                lookup_keys.push(key_reference(&key)?);
                 keys.push(key);
                 if lookup_keys.len() >= limit {
                     if get_entries(mem::take(&mut lookup_keys), mem::take(&mut keys))? {
@@ -237,21 +467,20 @@ impl StateReader {
         T: DeserializeOwned,
         F: Fn(&T) -> bool,
     {
-        let cf = column.cf_db(&self.db);
-        let mut read_options = ReadOptions::default();
+        let mut read_options = self.read_options();
         read_options.set_readahead_size(4_194_304);
         let mode = match start {
             Some(start) => IteratorMode::From(start, Direction::Forward),
             None => IteratorMode::Start,
         };
-        let iter = self.db.iterator_cf_opt(&cf, read_options, mode);
+        let iter = self.scan(&column, read_options, mode);
         let mut items = Vec::new();
         let mut total = 0;
         let limit = limit.unwrap_or(usize::MAX);
         let mut restart_key = Vec::new();
         for kv in iter {
             if let Ok((key, value)) = kv {
-                let item = JsonEncoder::decode::<T>(&value)?;
+                let item = JsonEncoder::decode::<T>(&column, &key, &value)?;
                 if !filter(&item) {
                     break;
                 }
@@ -285,20 +514,62 @@ impl StateReader {
         T: DeserializeOwned,
         K: AsRef<[u8]>,
     {
-        let result_bytes = match self.db.get_cf(&column.cf_db(&self.db), key)? {
-            Some(bytes) => bytes,
-            None => return Ok(None),
+        let result_bytes = self.get_raw(column, &key)?;
+        if let Some(migration) = key_migration::migrating(column) {
+            self.shadow_read(migration, key.as_ref(), result_bytes.as_deref());
+        }
+        let Some(result_bytes) = result_bytes else {
+            return Ok(None);
         };
-        let result = JsonEncoder::decode::<T>(&result_bytes)
+        let result = JsonEncoder::decode::<T>(column, &key, &result_bytes)
             .map_err(|e| anyhow::anyhow!("Deserialization error: {}", e))?;
 
         Ok(Some(result))
     }
 
+    fn shadow_read(&self, migration: &KeyLayoutMigration, key: &[u8], value: Option<&[u8]>) {
+        let labels = KeyMigrationLabels {
+            migration: migration.name.to_string(),
+        };
+        // Migrations are driven by the primary, a secondary only serves the
+        // layout it was copied with.
+        let ReadDb::Primary(db) = &self.db else {
+            return;
+        };
+        match key_migration::shadow_read(db, migration, key, value) {
+            Ok(diverged) => {
+                if let Some(metrics) = &self.metrics {
+                    metrics
+                        .key_migration_shadow_reads
+                        .get_or_create(&labels)
+                        .inc();
+                    if diverged {
+                        metrics
+                            .key_migration_divergences
+                            .get_or_create(&labels)
+                            .inc();
+                    }
+                }
+            }
+            Err(e) => warn!(
+                "error reading the new key layout of {}: {:?}",
+                migration.name, e
+            ),
+        }
+    }
+
+    /// Reads from the state store to make sure it's open and serving reads.
+    pub fn check_readable(&self) -> Result<()> {
+        self.db.get_cf_opt(
+            &IndexifyObjectsColumns::StateMachineMetadata,
+            b"health",
+            &self.read_options(),
+        )?;
+        Ok(())
+    }
+
     pub fn get_pending_system_tasks(&self) -> Result<usize> {
-        let cf = IndexifyObjectsColumns::Stats.cf_db(&self.db);
-        let key = b"pending_system_tasks";
-        let value = self.db.get_cf(&cf, key)?;
+        let value = self.get_raw(&IndexifyObjectsColumns::Stats, b"pending_system_tasks")?;
         match value {
             Some(value) => {
                 let bytes: [u8; 8] = value
@@ -312,6 +583,58 @@ impl StateReader {
         }
     }
 
+    /// Work accepted but not placed yet: tasks waiting for an executor and
+    /// state changes waiting for the scheduler.
+    pub fn queue_depth(&self) -> Result<u64> {
+        let mut depth = 0;
+        for column in [
+            IndexifyObjectsColumns::UnallocatedTasks,
+            IndexifyObjectsColumns::UnprocessedStateChanges,
+        ] {
+            for row in self.scan(&column, self.read_options(), IteratorMode::Start) {
+                row?;
+                depth += 1;
+            }
+        }
+        Ok(depth)
+    }
+
+    /// Tasks waiting for an executor, counted up to `limit` so that a large
+    /// backlog isn't scanned whole.
+    pub fn count_unallocated_tasks(&self, limit: u64) -> Result<u64> {
+        let mut count = 0;
+        for row in self.scan(
+            &IndexifyObjectsColumns::UnallocatedTasks,
+            self.read_options(),
+            IteratorMode::Start,
+        ) {
+            if count >= limit {
+                break;
+            }
+            row?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// The maintained counters of a graph, none when the graph's objects
+    /// aren't counted yet.
+    pub fn graph_counts(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+    ) -> Result<Option<GraphCounts>> {
+        let mut counts = GraphCounts::default();
+        for counter in Counter::iter() {
+            let key = counter.key(namespace, compute_graph);
+            let Some(value) = self.get_raw(&IndexifyObjectsColumns::Stats, key)? else {
+                return Ok(None);
+            };
+            *counts.get_mut(counter) = decode_count(&value)?;
+        }
+        Ok(Some(counts))
+    }
+
     pub fn get_diagnostic_payload(
         &self,
         ns: &str,
@@ -370,8 +693,11 @@ impl StateReader {
 
     pub fn get_gc_urls(&self, limit: Option<usize>) -> Result<Vec<String>> {
         let limit = limit.unwrap_or(usize::MAX);
-        let cf = IndexifyObjectsColumns::GcUrls.cf_db(&self.db);
-        let iter = self.db.iterator_cf(&cf, IteratorMode::Start);
+        let iter = self.scan(
+            &IndexifyObjectsColumns::GcUrls,
+            self.read_options(),
+            IteratorMode::Start,
+        );
         let mut urls = Vec::new();
         for kv in iter {
             if let Ok((key, _)) = kv {
@@ -385,22 +711,133 @@ impl StateReader {
         Ok(urls)
     }
 
+    /// Oldest records pending the analytics export, with their ids.
+    pub fn analytics_records(&self, limit: usize) -> Result<Vec<(String, AnalyticsRecord)>> {
+        let mut records = Vec::new();
+        for kv in self
+            .scan(
+                &IndexifyObjectsColumns::AnalyticsRecords,
+                self.read_options(),
+                IteratorMode::Start,
+            )
+            .take(limit)
+        {
+            let (key, value) = kv?;
+            records.push((
+                String::from_utf8(key.to_vec())?,
+                JsonEncoder::decode(IndexifyObjectsColumns::AnalyticsRecords, &key, &value)?,
+            ));
+        }
+        Ok(records)
+    }
+
+    /// Oldest alerts pending delivery.
+    pub fn alerts(&self, limit: usize) -> Result<Vec<Alert>> {
+        let mut alerts = Vec::new();
+        for kv in self
+            .scan(
+                &IndexifyObjectsColumns::Alerts,
+                self.read_options(),
+                IteratorMode::Start,
+            )
+            .take(limit)
+        {
+            let (key, value) = kv?;
+            alerts.push(JsonEncoder::decode(
+                IndexifyObjectsColumns::Alerts,
+                &key,
+                &value,
+            )?);
+        }
+        Ok(alerts)
+    }
+
+    /// Next state changes to process. Changes which concern every namespace go
+    /// first, then namespaces take turns in proportion to their scheduling
+    /// weight, so that a namespace with a large backlog doesn't hold back the
+    /// others.
     pub fn get_unprocessed_state_changes(&self) -> Result<Vec<StateChange>> {
-        let cf = IndexifyObjectsColumns::UnprocessedStateChanges.cf_db(&self.db);
-        let iter = self.db.iterator_cf(&cf, IteratorMode::Start);
-        let mut state_changes = Vec::new();
-        let mut count = 0;
-        for kv in iter {
-            if let Ok((_, serialized_sc)) = kv {
-                let state_change = JsonEncoder::decode::<StateChange>(&serialized_sc)?;
-                state_changes.push(state_change);
-                count += 1;
+        self.unprocessed_state_changes(STATE_CHANGE_BATCH_SIZE)
+    }
+
+    /// Next state changes to process, up to `limit`, see
+    /// `get_unprocessed_state_changes`.
+    pub fn unprocessed_state_changes(&self, limit: usize) -> Result<Vec<StateChange>> {
+        let weights: HashMap<String, u32> = self
+            .get_all_namespaces()?
+            .into_iter()
+            .map(|ns| (ns.name, ns.scheduling_weight.unwrap_or(1)))
+            .collect();
+        let (mut global, mut namespaces): (Vec<_>, Vec<_>) = self
+            .state_change_queues(limit)?
+            .into_iter()
+            .partition(|(namespace, _)| namespace.is_empty());
+        let mut state_changes = global
+            .pop()
+            .map(|(_, queue)| Vec::from(queue))
+            .unwrap_or_default();
+        state_changes.truncate(limit);
+        while state_changes.len() < limit && !namespaces.is_empty() {
+            for (namespace, queue) in namespaces.iter_mut() {
+                let weight = weights.get(namespace).copied().unwrap_or(1);
+                for _ in 0..weight {
+                    if state_changes.len() >= limit {
+                        break;
+                    }
+                    match queue.pop_front() {
+                        Some(state_change) => state_changes.push(state_change),
+                        None => break,
+                    }
+                }
             }
-            if count >= 10 {
+            namespaces.retain(|(_, queue)| !queue.is_empty());
+        }
+        Ok(state_changes)
+    }
+
+    /// Oldest unprocessed state changes of every queue, up to the limit per
+    /// queue. The queue of changes concerning every namespace is named "".
+    fn state_change_queues(&self, limit: usize) -> Result<Vec<(String, VecDeque<StateChange>)>> {
+        let mut queues = Vec::new();
+        let mut seek_key = Vec::new();
+        loop {
+            let mut iter = self.scan(
+                &IndexifyObjectsColumns::StateChangeQueues,
+                self.read_options(),
+                IteratorMode::From(&seek_key, Direction::Forward),
+            );
+            let Some(kv) = iter.next() else {
                 break;
+            };
+            let (key, value) = kv?;
+            let pos = key
+                .iter()
+                .position(|&b| b == b'|')
+                .ok_or(anyhow!("invalid state change queue key"))?;
+            let prefix = key[..=pos].to_vec();
+            let mut queue = VecDeque::from([JsonEncoder::decode::<StateChange>(
+                IndexifyObjectsColumns::StateChangeQueues,
+                &key,
+                &value,
+            )?]);
+            for kv in iter {
+                let (key, value) = kv?;
+                if !key.starts_with(&prefix) || queue.len() >= limit {
+                    break;
+                }
+                queue.push_back(JsonEncoder::decode::<StateChange>(
+                    IndexifyObjectsColumns::StateChangeQueues,
+                    &key,
+                    &value,
+                )?);
             }
+            // Names only contain characters ordered before '|', so all keys of
+            // the queue sort before its name followed by '}'.
+            seek_key = key[..pos].to_vec();
+            seek_key.push(b'}');
+            queues.push((String::from_utf8(key[..pos].to_vec())?, queue));
         }
-        Ok(state_changes)
+        Ok(queues)
     }
 
     pub fn get_all_rows_from_cf<V>(
@@ -410,19 +847,15 @@ impl StateReader {
     where
         V: DeserializeOwned,
     {
-        let cf_handle = self
-            .db
-            .cf_handle(column.as_ref())
-            .ok_or(anyhow::anyhow!("Failed to get column family {}", column))?;
-        let iter = self.db.iterator_cf(&cf_handle, IteratorMode::Start);
+        let iter = self.scan(&column, self.read_options(), IteratorMode::Start);
 
         iter.map(|item| {
             item.map_err(|e| anyhow::anyhow!(e.to_string()))
                 .and_then(|(key, value)| {
                     let key = String::from_utf8(key.to_vec())
                         .map_err(|e| anyhow::anyhow!(e.to_string()))?;
-                    let value =
-                        JsonEncoder::decode(&value).map_err(|e| anyhow::anyhow!(e.to_string()))?;
+                    let value = JsonEncoder::decode(&column, &key, &value)
+                        .map_err(|e| anyhow::anyhow!(e.to_string()))?;
                     Ok((key, value))
                 })
         })
@@ -430,13 +863,52 @@ impl StateReader {
     }
 
     pub fn get_all_namespaces(&self) -> Result<Vec<Namespace>> {
-        let (namespaces, _) = self.get_rows_from_cf_with_limits::<Namespace>(
+        let (namespaces, _) = self.list_namespaces(None, None)?;
+        Ok(namespaces)
+    }
+
+    pub fn list_namespaces(
+        &self,
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<Namespace>, Option<Vec<u8>>)> {
+        self.get_rows_from_cf_with_limits::<Namespace>(
             &[],
-            None,
+            cursor,
             IndexifyObjectsColumns::Namespaces,
-            None,
-        )?;
-        Ok(namespaces)
+            limit,
+        )
+    }
+
+    /// Lists only the given namespaces, paging like `list_namespaces`.
+    ///
+    /// The namespaces are read by key, so the cost follows the size of the
+    /// set rather than the number of namespaces in the store.
+    pub fn list_namespaces_in(
+        &self,
+        names: &BTreeSet<String>,
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<Namespace>, Option<Vec<u8>>)> {
+        let start = cursor
+            .map(|cursor| String::from_utf8_lossy(cursor).into_owned())
+            .unwrap_or_default();
+        let keys: Vec<&String> = names.range(start..).collect();
+        let mut namespaces: Vec<Namespace> = self
+            .multi_get(&IndexifyObjectsColumns::Namespaces, &keys)?
+            .into_iter()
+            .flatten()
+            .collect();
+        let limit = limit.unwrap_or(usize::MAX);
+        let restart_key = namespaces
+            .get(limit)
+            .map(|namespace| namespace.name.clone().into_bytes());
+        namespaces.truncate(limit);
+        Ok((namespaces, restart_key))
+    }
+
+    pub fn get_namespace(&self, name: &str) -> Result<Option<Namespace>> {
+        self.get_from_cf(&IndexifyObjectsColumns::Namespaces, name)
     }
 
     pub fn list_invocations(
@@ -455,6 +927,45 @@ impl StateReader {
         )
     }
 
+    /// List invocations of a compute graph which carry all of the given tags.
+    /// The first tag is resolved through the tag index, the rest are matched
+    /// against the invocation payload.
+    pub fn list_invocations_by_tags(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        tags: &HashMap<String, String>,
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<InvocationPayload>, Option<Vec<u8>>)> {
+        let (tag, value) = match tags.iter().next() {
+            Some(tag) => tag,
+            None => return self.list_invocations(namespace, compute_graph, cursor, limit),
+        };
+        let prefix = InvocationPayload::tag_key_prefix(namespace, compute_graph, tag, value);
+        let res = self.filter_join_cf(
+            IndexifyObjectsColumns::InvocationTags,
+            IndexifyObjectsColumns::GraphInvocations,
+            |invocation: &InvocationPayload| invocation.matches_tags(tags),
+            prefix.as_bytes(),
+            |key| {
+                let invocation_id = String::from_utf8(key[prefix.len()..].to_vec())?;
+                Ok(
+                    InvocationPayload::key_from(namespace, compute_graph, &invocation_id)
+                        .into_bytes(),
+                )
+            },
+            cursor,
+            limit,
+        )?;
+        let cursor = if res.cursor.is_empty() {
+            None
+        } else {
+            Some(res.cursor)
+        };
+        Ok((res.items, cursor))
+    }
+
     pub fn list_compute_graphs(
         &self,
         namespace: &str,
@@ -470,12 +981,123 @@ impl StateReader {
         Ok((compute_graphs, cursor))
     }
 
+    /// List compute graphs of a namespace which carry all of the given
+    /// labels. The first label is resolved through the label index, the rest
+    /// are matched against the graph.
+    pub fn list_compute_graphs_by_labels(
+        &self,
+        namespace: &str,
+        labels: &HashMap<String, String>,
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<ComputeGraph>, Option<Vec<u8>>)> {
+        let (label, value) = match labels.iter().next() {
+            Some(label) => label,
+            None => return self.list_compute_graphs(namespace, cursor, limit),
+        };
+        let prefix = ComputeGraph::label_key_prefix(namespace, label, value);
+        let res = self.filter_join_cf(
+            IndexifyObjectsColumns::GraphLabels,
+            IndexifyObjectsColumns::ComputeGraphs,
+            |compute_graph: &ComputeGraph| compute_graph.matches_labels(labels),
+            prefix.as_bytes(),
+            |key| {
+                let name = String::from_utf8(key[prefix.len()..].to_vec())?;
+                Ok(ComputeGraph::key_from(namespace, &name).into_bytes())
+            },
+            cursor,
+            limit,
+        )?;
+        let cursor = if res.cursor.is_empty() {
+            None
+        } else {
+            Some(res.cursor)
+        };
+        Ok((res.items, cursor))
+    }
+
     pub fn get_compute_graph(&self, namespace: &str, name: &str) -> Result<Option<ComputeGraph>> {
         let key = format!("{}|{}", namespace, name);
         let compute_graph = self.get_from_cf(&IndexifyObjectsColumns::ComputeGraphs, key)?;
         Ok(compute_graph)
     }
 
+    /// The graphs of a namespace with the given names, in their order, read
+    /// at once.
+    pub fn get_compute_graphs<N: AsRef<str>>(
+        &self,
+        namespace: &str,
+        names: &[N],
+    ) -> Result<Vec<Option<ComputeGraph>>> {
+        let keys: Vec<String> = names
+            .iter()
+            .map(|name| ComputeGraph::key_from(namespace, name.as_ref()))
+            .collect();
+        self.multi_get(&IndexifyObjectsColumns::ComputeGraphs, &keys)
+    }
+
+    pub fn get_canary(&self, namespace: &str, name: &str) -> Result<Option<GraphCanary>> {
+        self.get_from_cf(
+            &IndexifyObjectsColumns::GraphCanaries,
+            ComputeGraph::key_from(namespace, name),
+        )
+    }
+
+    /// The graph at `version`. Versions replaced before they were kept
+    /// resolve to the version being rolled out to a share of the invocations,
+    /// or the stable version otherwise.
+    pub fn get_compute_graph_version(
+        &self,
+        namespace: &str,
+        name: &str,
+        version: GraphVersion,
+    ) -> Result<Option<ComputeGraph>> {
+        if let Some(graph) = self.get_from_cf(
+            &IndexifyObjectsColumns::ComputeGraphVersions,
+            ComputeGraph::version_key_from(namespace, name, version),
+        )? {
+            return Ok(Some(graph));
+        }
+        match self.get_canary(namespace, name)? {
+            Some(canary) if canary.graph.version == version => Ok(Some(canary.graph)),
+            _ => self.get_compute_graph(namespace, name),
+        }
+    }
+
+    /// Versions of the graph, oldest first.
+    pub fn list_compute_graph_versions(
+        &self,
+        namespace: &str,
+        name: &str,
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<ComputeGraph>, Option<Vec<u8>>)> {
+        self.get_rows_from_cf_with_limits::<ComputeGraph>(
+            ComputeGraph::versions_prefix(namespace, name).as_bytes(),
+            cursor,
+            IndexifyObjectsColumns::ComputeGraphVersions,
+            limit,
+        )
+    }
+
+    /// The graph the invocation runs, the stable version once the
+    /// invocation is gone.
+    pub fn get_compute_graph_for_invocation(
+        &self,
+        namespace: &str,
+        name: &str,
+        invocation_id: &str,
+    ) -> Result<Option<ComputeGraph>> {
+        let ctx: Option<GraphInvocationCtx> = self.get_from_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx,
+            GraphInvocationCtx::key_from(namespace, name, invocation_id),
+        )?;
+        match ctx {
+            Some(ctx) => self.get_compute_graph_version(namespace, name, ctx.graph_version),
+            None => self.get_compute_graph(namespace, name),
+        }
+    }
+
     pub fn list_outputs_by_compute_graph(
         &self,
         namespace: &str,
@@ -519,6 +1141,12 @@ impl StateReader {
         Ok(task)
     }
 
+    /// The tasks with the given keys, see `Task::key`, in their order, read
+    /// at once.
+    pub fn get_tasks<K: AsRef<[u8]>>(&self, keys: &[K]) -> Result<Vec<Option<Task>>> {
+        self.multi_get(&IndexifyObjectsColumns::Tasks, keys)
+    }
+
     pub fn list_tasks_by_namespace(
         &self,
         namespace: &str,
@@ -579,6 +1207,111 @@ impl StateReader {
         Ok(res.items)
     }
 
+    /// The scheduler's index of the allocated tasks, kept up to date with the
+    /// allocations.
+    pub fn scheduler_index(&self) -> Result<SchedulerIndex> {
+        Ok(self
+            .get_from_cf(
+                &IndexifyObjectsColumns::StateMachineMetadata,
+                SCHEDULER_INDEX_KEY,
+            )?
+            .unwrap_or_default())
+    }
+
+    pub fn scheduler_index_persisted(&self) -> Result<bool> {
+        Ok(self
+            .get_from_cf::<SchedulerIndex, _>(
+                &IndexifyObjectsColumns::StateMachineMetadata,
+                SCHEDULER_INDEX_KEY,
+            )?
+            .is_some())
+    }
+
+    /// GPU capacity held by the tasks currently allocated to each device,
+    /// keyed by executor and device id.
+    pub fn gpu_usage(&self) -> Result<HashMap<(ExecutorId, String), GpuDeviceUsage>> {
+        let mut usage = HashMap::new();
+        for (executor_id, devices) in self.scheduler_index()?.gpu_usage {
+            for (device_id, device_usage) in devices {
+                usage.insert((executor_id.clone(), device_id), device_usage);
+            }
+        }
+        Ok(usage)
+    }
+
+    /// Number of allocated tasks counting against each external dependency.
+    pub fn external_dependency_usage(&self) -> Result<HashMap<String, u64>> {
+        Ok(self.scheduler_index()?.dependency_usage)
+    }
+
+    /// Number of tasks currently allocated to each executor.
+    pub fn allocated_task_counts(&self) -> Result<HashMap<ExecutorId, u64>> {
+        Ok(self.scheduler_index()?.allocated_tasks)
+    }
+
+    pub fn get_artifact(
+        &self,
+        namespace: &str,
+        name: &str,
+        version: &str,
+    ) -> Result<Option<Artifact>> {
+        self.get_from_cf(
+            &IndexifyObjectsColumns::Artifacts,
+            Artifact::key_from(namespace, name, version),
+        )
+    }
+
+    pub fn list_artifacts(
+        &self,
+        namespace: &str,
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<Artifact>, Option<Vec<u8>>)> {
+        self.get_rows_from_cf_with_limits::<Artifact>(
+            Artifact::key_prefix(namespace).as_bytes(),
+            cursor,
+            IndexifyObjectsColumns::Artifacts,
+            limit,
+        )
+    }
+
+    pub fn get_subscription(&self, namespace: &str, id: &str) -> Result<Option<Subscription>> {
+        self.get_from_cf(
+            &IndexifyObjectsColumns::Subscriptions,
+            Subscription::key_from(namespace, id),
+        )
+    }
+
+    /// Subscriptions of a namespace, or of every namespace when none is given.
+    pub fn list_subscriptions(&self, namespace: Option<&str>) -> Result<Vec<Subscription>> {
+        let prefix = namespace.map(Subscription::key_prefix).unwrap_or_default();
+        let (subscriptions, _) = self.get_rows_from_cf_with_limits::<Subscription>(
+            prefix.as_bytes(),
+            None,
+            IndexifyObjectsColumns::Subscriptions,
+            None,
+        )?;
+        Ok(subscriptions)
+    }
+
+    /// Events of a subscription from a sequence number on.
+    pub fn list_subscription_events(
+        &self,
+        namespace: &str,
+        subscription_id: &str,
+        from_sequence: u64,
+        limit: Option<usize>,
+    ) -> Result<Vec<SubscriptionEvent>> {
+        let start = SubscriptionEvent::key_from(namespace, subscription_id, from_sequence);
+        let (events, _) = self.get_rows_from_cf_with_limits::<SubscriptionEvent>(
+            SubscriptionEvent::key_prefix(namespace, subscription_id).as_bytes(),
+            Some(start.as_bytes()),
+            IndexifyObjectsColumns::SubscriptionEvents,
+            limit,
+        )?;
+        Ok(events)
+    }
+
     pub fn get_all_executors(&self) -> Result<Vec<ExecutorMetadata>> {
         let (executors, _) = self.get_rows_from_cf_with_limits::<ExecutorMetadata>(
             &[],
@@ -596,12 +1329,13 @@ impl StateReader {
         invocation_id: &str,
     ) -> Result<GraphInvocationCtx> {
         let key = GraphInvocationCtx::key_from(namespace, compute_graph, invocation_id);
-        let value = self.db.get_cf(
-            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&self.db),
-            &key,
-        )?;
+        let value = self.get_raw(&IndexifyObjectsColumns::GraphInvocationCtx, &key)?;
         match value {
-            Some(value) => Ok(JsonEncoder::decode(&value)?),
+            Some(value) => Ok(JsonEncoder::decode(
+                IndexifyObjectsColumns::GraphInvocationCtx,
+                &key,
+                &value,
+            )?),
             None => Err(anyhow!("invocation ctx not found")),
         }
     }
@@ -614,12 +1348,13 @@ impl StateReader {
         compute_fn: &str,
     ) -> Result<Option<TaskAnalytics>> {
         let key = GraphInvocationCtx::key_from(namespace, compute_graph, invocation_id);
-        let value = self.db.get_cf(
-            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&self.db),
-            &key,
-        )?;
+        let value = self.get_raw(&IndexifyObjectsColumns::GraphInvocationCtx, &key)?;
         let ctx = match value {
-            Some(value) => Some(JsonEncoder::decode::<GraphInvocationCtx>(&value)?),
+            Some(value) => Some(JsonEncoder::decode::<GraphInvocationCtx>(
+                IndexifyObjectsColumns::GraphInvocationCtx,
+                &key,
+                &value,
+            )?),
             None => None,
         };
         let task_analytics = match ctx {
@@ -636,16 +1371,437 @@ impl StateReader {
         invocation_id: &str,
     ) -> Result<InvocationPayload> {
         let key = InvocationPayload::key_from(namespace, compute_graph, invocation_id);
-        let value = self.db.get_cf(
-            &IndexifyObjectsColumns::GraphInvocations.cf_db(&self.db),
-            &key,
-        )?;
+        let value = self.get_raw(&IndexifyObjectsColumns::GraphInvocations, &key)?;
         match value {
-            Some(value) => Ok(JsonEncoder::decode(&value)?),
+            Some(value) => Ok(JsonEncoder::decode(
+                IndexifyObjectsColumns::GraphInvocations,
+                &key,
+                &value,
+            )?),
             None => Err(anyhow!("invocation payload not found")),
         }
     }
 
+    /// Invocations created before the cutoff which have finished running and
+    /// can be archived.
+    pub fn archivable_invocations(
+        &self,
+        created_before: u64,
+        limit: usize,
+    ) -> Result<Vec<InvocationPayload>> {
+        let mut read_options = self.read_options();
+        read_options.set_readahead_size(4_194_304);
+        let iter = self.scan(
+            &IndexifyObjectsColumns::GraphInvocations,
+            read_options,
+            IteratorMode::Start,
+        );
+        let mut invocations = Vec::new();
+        for kv in iter {
+            if invocations.len() >= limit {
+                break;
+            }
+            let (key, value) = kv?;
+            let invocation = JsonEncoder::decode::<InvocationPayload>(
+                IndexifyObjectsColumns::GraphInvocations,
+                &key,
+                &value,
+            )?;
+            if invocation.created_at >= created_before {
+                continue;
+            }
+            let ctx: Option<GraphInvocationCtx> = self.get_from_cf(
+                &IndexifyObjectsColumns::GraphInvocationCtx,
+                invocation.invocation_context_key(),
+            )?;
+            if ctx.map(|ctx| ctx.completed).unwrap_or(false) {
+                invocations.push(invocation);
+            }
+        }
+        Ok(invocations)
+    }
+
+    /// Finished invocations of a graph with outputs its retention expires
+    /// at `now` which are still kept, along with the kinds of outputs to
+    /// expire. Pinned invocations are left out.
+    pub fn invocations_with_expired_outputs(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        retention: &OutputRetention,
+        now: u64,
+        limit: usize,
+    ) -> Result<Vec<(InvocationPayload, ExpiredOutputs)>> {
+        let prefix = format!("{}|{}|", namespace, compute_graph);
+        let mut read_options = self.read_options();
+        read_options.set_readahead_size(4_194_304);
+        let iter = self.scan(
+            &IndexifyObjectsColumns::GraphInvocations,
+            read_options,
+            IteratorMode::From(prefix.as_bytes(), Direction::Forward),
+        );
+        let mut invocations = Vec::new();
+        for kv in iter {
+            let (key, value) = kv?;
+            if !key.starts_with(prefix.as_bytes()) || invocations.len() >= limit {
+                break;
+            }
+            let invocation = JsonEncoder::decode::<InvocationPayload>(
+                IndexifyObjectsColumns::GraphInvocations,
+                &key,
+                &value,
+            )?;
+            let expired = retention.expired(invocation.created_at, now);
+            if !expired.any() {
+                continue;
+            }
+            let ctx: Option<GraphInvocationCtx> = self.get_from_cf(
+                &IndexifyObjectsColumns::GraphInvocationCtx,
+                invocation.invocation_context_key(),
+            )?;
+            let Some(ctx) = ctx.filter(|ctx| ctx.completed && !ctx.outputs_pinned) else {
+                continue;
+            };
+            let expired = expired.without(&ctx.expired_outputs);
+            if expired.any() {
+                invocations.push((invocation, expired));
+            }
+        }
+        Ok(invocations)
+    }
+
+    /// Most recent invocations of a graph, newest first, leaving out test
+    /// invocations.
+    pub fn latest_invocations(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        limit: usize,
+    ) -> Result<Vec<InvocationPayload>> {
+        let prefix = format!("{}|{}|", namespace, compute_graph);
+        // Ids sort in creation order, the last keys of the graph are the
+        // newest invocations.
+        let upper_bound = format!("{}|{}}}", namespace, compute_graph);
+        let iter = self.scan(
+            &IndexifyObjectsColumns::GraphInvocations,
+            self.read_options(),
+            IteratorMode::From(upper_bound.as_bytes(), Direction::Reverse),
+        );
+        let mut invocations = Vec::new();
+        for kv in iter {
+            let (key, value) = kv?;
+            if !key.starts_with(prefix.as_bytes()) || invocations.len() >= limit {
+                break;
+            }
+            let invocation = JsonEncoder::decode::<InvocationPayload>(
+                IndexifyObjectsColumns::GraphInvocations,
+                &key,
+                &value,
+            )?;
+            if !invocation.test {
+                invocations.push(invocation);
+            }
+        }
+        Ok(invocations)
+    }
+
+    /// Test invocations which finished more than `retention_ms` ago, or which
+    /// haven't finished within `timeout_ms`, both counted from their
+    /// creation.
+    pub fn expired_test_invocations(
+        &self,
+        now: u64,
+        timeout_ms: u64,
+        retention_ms: u64,
+        limit: usize,
+    ) -> Result<Vec<InvocationPayload>> {
+        let iter = self.scan(
+            &IndexifyObjectsColumns::GraphInvocations,
+            self.read_options(),
+            IteratorMode::Start,
+        );
+        let mut invocations = Vec::new();
+        for kv in iter {
+            if invocations.len() >= limit {
+                break;
+            }
+            let (key, value) = kv?;
+            let invocation = JsonEncoder::decode::<InvocationPayload>(
+                IndexifyObjectsColumns::GraphInvocations,
+                &key,
+                &value,
+            )?;
+            if !invocation.test {
+                continue;
+            }
+            let ctx: Option<GraphInvocationCtx> = self.get_from_cf(
+                &IndexifyObjectsColumns::GraphInvocationCtx,
+                invocation.invocation_context_key(),
+            )?;
+            let ttl_ms = if ctx.is_some_and(|ctx| ctx.completed) {
+                retention_ms
+            } else {
+                timeout_ms
+            };
+            if invocation.created_at.saturating_add(ttl_ms) <= now {
+                invocations.push(invocation);
+            }
+        }
+        Ok(invocations)
+    }
+
+    pub fn list_archived_invocations(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<ArchivedInvocation>, Option<Vec<u8>>)> {
+        let key = format!("{}|{}|", namespace, compute_graph);
+        self.get_rows_from_cf_with_limits::<ArchivedInvocation>(
+            key.as_bytes(),
+            cursor,
+            IndexifyObjectsColumns::ArchivedInvocations,
+            limit,
+        )
+    }
+
+    pub fn archived_invocation(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+    ) -> Result<Option<ArchivedInvocation>> {
+        let key = ArchivedInvocation::key_from(namespace, compute_graph, invocation_id);
+        self.get_from_cf(&IndexifyObjectsColumns::ArchivedInvocations, key)
+    }
+
+    pub fn url_fetch(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+    ) -> Result<Option<UrlFetch>> {
+        let key = InvocationPayload::key_from(namespace, compute_graph, invocation_id);
+        self.get_from_cf(&IndexifyObjectsColumns::UrlFetches, key)
+    }
+
+    /// Invocations still waiting for the download of their input.
+    pub fn pending_url_fetches(&self) -> Result<Vec<UrlFetch>> {
+        let mut fetches = Vec::new();
+        for kv in self.scan(
+            &IndexifyObjectsColumns::UrlFetches,
+            self.read_options(),
+            IteratorMode::Start,
+        ) {
+            let (key, value) = kv?;
+            let fetch: UrlFetch =
+                JsonEncoder::decode(IndexifyObjectsColumns::UrlFetches, &key, &value)?;
+            if fetch.error.is_none() {
+                fetches.push(fetch);
+            }
+        }
+        Ok(fetches)
+    }
+
+    pub fn graph_resource_usage(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+    ) -> Result<Option<ResourceUsageStats>> {
+        self.get_from_cf(
+            &IndexifyObjectsColumns::ResourceUsage,
+            ResourceUsageStats::graph_key(namespace, compute_graph),
+        )
+    }
+
+    /// Resource usage of each function of a compute graph, keyed by function
+    /// name.
+    pub fn fn_resource_usage(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+    ) -> Result<HashMap<String, ResourceUsageStats>> {
+        let prefix = format!("{}|{}|", namespace, compute_graph);
+        let (rows, _) = self.get_raw_rows_from_cf_with_limits(
+            prefix.as_bytes(),
+            None,
+            IndexifyObjectsColumns::ResourceUsage,
+            None,
+        )?;
+        let mut usage = HashMap::new();
+        for (key, value) in rows {
+            let compute_fn = String::from_utf8(key[prefix.len()..].to_vec())?;
+            usage.insert(
+                compute_fn,
+                JsonEncoder::decode(IndexifyObjectsColumns::ResourceUsage, &key, &value)?,
+            );
+        }
+        Ok(usage)
+    }
+
+    pub fn output_check_stats(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+    ) -> Result<HashMap<String, OutputCheckStats>> {
+        let prefix = format!("{}|{}|", namespace, compute_graph);
+        let (rows, _) = self.get_raw_rows_from_cf_with_limits(
+            prefix.as_bytes(),
+            None,
+            IndexifyObjectsColumns::OutputCheckStats,
+            None,
+        )?;
+        let mut stats = HashMap::new();
+        for (key, value) in rows {
+            let compute_fn = String::from_utf8(key[prefix.len()..].to_vec())?;
+            stats.insert(
+                compute_fn,
+                JsonEncoder::decode(IndexifyObjectsColumns::OutputCheckStats, &key, &value)?,
+            );
+        }
+        Ok(stats)
+    }
+
+    pub fn list_quarantined_outputs(
+        &self,
+        namespace: &str,
+        compute_graph: &str,
+        invocation_id: &str,
+    ) -> Result<Vec<QuarantinedOutput>> {
+        let key = format!("{}|{}|{}|", namespace, compute_graph, invocation_id);
+        let (outputs, _) = self.get_rows_from_cf_with_limits::<QuarantinedOutput>(
+            key.as_bytes(),
+            None,
+            IndexifyObjectsColumns::QuarantinedOutputs,
+            None,
+        )?;
+        Ok(outputs)
+    }
+
+    /// Unfinished tasks of a namespace, the ones waiting for placement first in
+    /// the order they're placed in.
+    pub fn task_queue(&self, namespace: &str) -> Result<Vec<QueuedTask>> {
+        let prefix = format!("{}|", namespace);
+        let (unallocated_task_rows, _) = self.get_raw_rows_from_cf_with_limits(
+            prefix.as_bytes(),
+            None,
+            IndexifyObjectsColumns::UnallocatedTasks,
+            None,
+        )?;
+        let keys = unallocated_task_rows
+            .iter()
+            .map(|(key, _)| key.as_slice())
+            .collect();
+        let mut pending: Vec<Task> =
+            self.get_rows_from_cf_multi_key(keys, IndexifyObjectsColumns::Tasks)?;
+        Task::sort_for_placement(&mut pending);
+        let mut queue: Vec<QueuedTask> = pending
+            .into_iter()
+            .map(|task| QueuedTask {
+                task,
+                executor_id: None,
+            })
+            .collect();
+
+        let (allocation_rows, _) = self.get_raw_rows_from_cf_with_limits(
+            &[],
+            None,
+            IndexifyObjectsColumns::TaskAllocations,
+            None,
+        )?;
+        for (key, _) in allocation_rows {
+            let task_key = Task::key_from_allocation_key(&key)?;
+            if !task_key.starts_with(prefix.as_bytes()) {
+                continue;
+            }
+            let allocation_key = String::from_utf8(key)?;
+            let Some((executor_id, _)) = allocation_key.split_once('|') else {
+                continue;
+            };
+            let Some(task) =
+                self.get_from_cf::<Task, _>(&IndexifyObjectsColumns::Tasks, task_key)?
+            else {
+                continue;
+            };
+            queue.push(QueuedTask {
+                task,
+                executor_id: Some(ExecutorId::new(executor_id.to_string())),
+            });
+        }
+        Ok(queue)
+    }
+
+    /// Activity of a namespace merged with the activity of the whole server,
+    /// newest first.
+    pub fn list_activity(
+        &self,
+        namespace: &str,
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<ActivityEvent>, Option<Vec<u8>>)> {
+        let limit = limit.unwrap_or(usize::MAX);
+        // Events are keyed by their scope followed by their position in the
+        // feed, <recorded_at>|<id>, the cursor is the position of the last
+        // event returned.
+        let mut events = Vec::new();
+        for scope in [Some(namespace), None] {
+            let prefix = ActivityEvent::key_prefix(scope);
+            let start = match cursor {
+                Some(cursor) => [prefix.as_bytes(), cursor].concat(),
+                // Right after the keys of the scope, its prefix ends in '|'.
+                None => format!("{}}}", &prefix[..prefix.len() - 1]).into_bytes(),
+            };
+            let iter = self.scan(
+                &IndexifyObjectsColumns::Activity,
+                self.read_options(),
+                IteratorMode::From(&start, Direction::Reverse),
+            );
+            let mut read = 0;
+            for kv in iter {
+                let (key, value) = kv?;
+                if !key.starts_with(prefix.as_bytes()) || read > limit {
+                    break;
+                }
+                if *key == *start {
+                    continue;
+                }
+                let position = key[prefix.len()..].to_vec();
+                events.push((
+                    position,
+                    JsonEncoder::decode::<ActivityEvent>(
+                        IndexifyObjectsColumns::Activity,
+                        &key,
+                        &value,
+                    )?,
+                ));
+                read += 1;
+            }
+        }
+        events.sort_by(|(a, _), (b, _)| b.cmp(a));
+        let cursor = if events.len() > limit {
+            events.truncate(limit);
+            events.last().map(|(position, _)| position.clone())
+        } else {
+            None
+        };
+        Ok((events.into_iter().map(|(_, event)| event).collect(), cursor))
+    }
+
+    pub fn list_audit_log(
+        &self,
+        namespace: &str,
+        cursor: Option<&[u8]>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<AuditLogEntry>, Option<Vec<u8>>)> {
+        self.get_rows_from_cf_with_limits::<AuditLogEntry>(
+            AuditLogEntry::key_prefix(namespace).as_bytes(),
+            cursor,
+            IndexifyObjectsColumns::AuditLog,
+            limit,
+        )
+    }
+
     pub fn unallocated_tasks(&self) -> Result<Vec<Task>> {
         let (unallocated_task_rows, _) = self
             .get_raw_rows_from_cf_with_limits(
@@ -674,21 +1830,26 @@ impl StateReader {
     ) -> Result<Option<NodeOutput>> {
         let key = NodeOutput::key_from(namespace, compute_graph, invocation_id, compute_fn, id);
         let value = self
-            .db
-            .get_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(&self.db), &key)
+            .get_raw(&IndexifyObjectsColumns::FnOutputs, &key)
             .map_err(|e| anyhow!("unable to get output payload: {}", e))?;
         match value {
-            Some(value) => Ok(JsonEncoder::decode(&value)?),
+            Some(value) => Ok(JsonEncoder::decode(
+                IndexifyObjectsColumns::FnOutputs,
+                &key,
+                &value,
+            )?),
             None => Ok(None),
         }
     }
 
     pub fn fn_output_payload_by_key(&self, key: &str) -> Result<NodeOutput> {
-        let value = self
-            .db
-            .get_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(&self.db), &key)?;
+        let value = self.get_raw(&IndexifyObjectsColumns::FnOutputs, key)?;
         match value {
-            Some(value) => Ok(JsonEncoder::decode(&value)?),
+            Some(value) => Ok(JsonEncoder::decode(
+                IndexifyObjectsColumns::FnOutputs,
+                key,
+                &value,
+            )?),
             None => Err(anyhow!("fn output not found")),
         }
     }
@@ -725,7 +1886,18 @@ impl StateReader {
 mod tests {
     use std::path::PathBuf;
 
-    use data_model::Namespace;
+    use data_model::{
+        activity::Activity,
+        test_objects::tests::{
+            mock_executor,
+            mock_graph_a,
+            mock_invocation_payload,
+            TEST_NAMESPACE,
+        },
+        InvocationPayloadBuilder,
+        Namespace,
+    };
+    use prometheus_client::{encoding::text::encode, registry::Registry};
     use tempfile::TempDir;
 
     use super::{
@@ -735,7 +1907,18 @@ mod tests {
         },
         *,
     };
-    use crate::requests::StateMachineUpdateRequest;
+    use crate::{
+        metrics::{ReadLabels, WriteLabels},
+        requests::{
+            CreateComputeGraphRequest,
+            DeleteComputeGraphRequest,
+            InvokeComputeGraphRequest,
+            PruneActivityRequest,
+            RecordActivityRequest,
+            RegisterExecutorRequest,
+            StateMachineUpdateRequest,
+        },
+    };
 
     #[tokio::test]
     async fn test_get_rows_from_cf_with_limits() {
@@ -749,6 +1932,10 @@ mod tests {
                 .write(StateMachineUpdateRequest {
                     payload: RequestPayload::CreateNameSpace(NamespaceRequest {
                         name: name.clone(),
+                        blob_storage: None,
+                        scheduling_weight: None,
+                        reprocessing_policy: Default::default(),
+                        near_duplicates: None,
                     }),
                     state_changes_processed: vec![],
                 })
@@ -782,4 +1969,435 @@ mod tests {
         assert_eq!(result.0.len(), 2);
         assert_eq!(cursor, None);
     }
+
+    #[tokio::test]
+    async fn test_snapshot_reader_ignores_later_writes() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let create_namespace = |name: &str| StateMachineUpdateRequest {
+            payload: RequestPayload::CreateNameSpace(NamespaceRequest {
+                name: name.to_string(),
+                blob_storage: None,
+                scheduling_weight: None,
+                reprocessing_policy: Default::default(),
+                near_duplicates: None,
+            }),
+            state_changes_processed: vec![],
+        };
+        indexify_state.write(create_namespace("first")).await?;
+        let snapshot = indexify_state.snapshot_reader();
+        indexify_state.write(create_namespace("second")).await?;
+
+        assert_eq!(snapshot.get_all_namespaces()?.len(), 1);
+        assert!(snapshot.get_namespace("second")?.is_none());
+        assert_eq!(indexify_state.reader().get_all_namespaces()?.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_and_write_metrics() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        for name in ["first", "second"] {
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateNameSpace(NamespaceRequest {
+                        name: name.to_string(),
+                        blob_storage: None,
+                        scheduling_weight: None,
+                        reprocessing_policy: Default::default(),
+                        near_duplicates: None,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+        let reader = indexify_state.reader();
+        assert!(reader.get_namespace("first")?.is_some());
+        assert!(reader.get_namespace("missing")?.is_none());
+        assert_eq!(reader.get_all_namespaces()?.len(), 2);
+
+        let metrics = &indexify_state.metrics;
+        let labels = |op: &str| ReadLabels {
+            column: "Namespaces".to_string(),
+            op: op.to_string(),
+        };
+        // Misses, including the lookups of the namespaces written, are timed
+        // but return no record.
+        assert_eq!(metrics.records_read.get_or_create(&labels("get")).get(), 1);
+        assert_eq!(metrics.records_read.get_or_create(&labels("scan")).get(), 2);
+        let writes = WriteLabels {
+            request: "CreateNameSpace".to_string(),
+        };
+        assert_eq!(metrics.writes.get_or_create(&writes).get(), 2);
+
+        let mut registry = Registry::default();
+        metrics.register(&mut registry);
+        let mut encoded = String::new();
+        encode(&mut encoded, &registry)?;
+        assert!(encoded.contains(
+            "state_store_read_latency_seconds_count{column=\"Namespaces\",op=\"get\"} 4"
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_state_changes_shared_between_namespaces() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        for (namespace, weight, invocations) in [("backfill", None, 12), ("live", Some(2), 3)] {
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateNameSpace(NamespaceRequest {
+                        name: namespace.to_string(),
+                        blob_storage: None,
+                        scheduling_weight: weight,
+                        reprocessing_policy: Default::default(),
+                        near_duplicates: None,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+            let mut graph = mock_graph_a();
+            graph.namespace = namespace.to_string();
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                        namespace: namespace.to_string(),
+                        compute_graph: graph,
+                        precondition: None,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+            for i in 0..invocations {
+                let invocation_payload = InvocationPayloadBuilder::default()
+                    .namespace(namespace.to_string())
+                    .compute_graph_name("graph_A".to_string())
+                    .payload(DataPayload {
+                        path: format!("path_{}", i),
+                        size: 23,
+                        sha256_hash: format!("hash_{}", i),
+                        content_type: None,
+                    })
+                    .build()?;
+                indexify_state
+                    .write(StateMachineUpdateRequest {
+                        payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                            namespace: namespace.to_string(),
+                            compute_graph_name: "graph_A".to_string(),
+                            invocation_payload,
+                        }),
+                        state_changes_processed: vec![],
+                    })
+                    .await?;
+            }
+        }
+
+        // The live namespace's changes were queued last, but with twice the
+        // weight they all make it into the first batch.
+        let state_changes = indexify_state.reader().get_unprocessed_state_changes()?;
+        let live = state_changes
+            .iter()
+            .filter(|sc| sc.namespace() == Some("live"))
+            .count();
+        assert_eq!(state_changes.len(), 10);
+        assert_eq!(live, 3);
+
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::RemoveGcUrls(vec![]),
+                state_changes_processed: state_changes.iter().map(|sc| sc.id).collect(),
+            })
+            .await?;
+        let state_changes = indexify_state.reader().get_unprocessed_state_changes()?;
+        assert_eq!(state_changes.len(), 5);
+        assert!(state_changes
+            .iter()
+            .all(|sc| sc.namespace() == Some("backfill")));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_invocations_by_tags() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph: mock_graph_a(),
+                    precondition: None,
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        for (i, source) in ["backfill", "backfill", "live"].iter().enumerate() {
+            let invocation_payload = InvocationPayloadBuilder::default()
+                .namespace(TEST_NAMESPACE.to_string())
+                .compute_graph_name("graph_A".to_string())
+                .payload(DataPayload {
+                    path: format!("path_{}", i),
+                    size: 23,
+                    sha256_hash: format!("hash_{}", i),
+                    content_type: None,
+                })
+                .tags(HashMap::from([("source".to_string(), source.to_string())]))
+                .build()?;
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::InvokeComputeGraph(InvokeComputeGraphRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph_name: "graph_A".to_string(),
+                        invocation_payload,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        let reader = indexify_state.reader();
+        let tags = HashMap::from([("source".to_string(), "backfill".to_string())]);
+        let (invocations, cursor) =
+            reader.list_invocations_by_tags(TEST_NAMESPACE, "graph_A", &tags, None, None)?;
+        assert_eq!(invocations.len(), 2);
+        assert!(cursor.is_none());
+
+        let (invocations, cursor) =
+            reader.list_invocations_by_tags(TEST_NAMESPACE, "graph_A", &tags, None, Some(1))?;
+        assert_eq!(invocations.len(), 1);
+        let (next, cursor) = reader.list_invocations_by_tags(
+            TEST_NAMESPACE,
+            "graph_A",
+            &tags,
+            cursor.as_deref(),
+            Some(1),
+        )?;
+        assert_eq!(next.len(), 1);
+        assert_ne!(next[0].id, invocations[0].id);
+        assert!(cursor.is_none());
+
+        let (invocations, _) = reader.list_invocations_by_tags(
+            TEST_NAMESPACE,
+            "graph_A",
+            &HashMap::new(),
+            None,
+            None,
+        )?;
+        assert_eq!(invocations.len(), 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_compute_graphs_by_labels() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let create = |name: &str, labels: &[(&str, &str)]| {
+            let mut compute_graph = mock_graph_a();
+            compute_graph.name = name.to_string();
+            compute_graph.labels = labels
+                .iter()
+                .map(|(label, value)| (label.to_string(), value.to_string()))
+                .collect();
+            indexify_state.write(StateMachineUpdateRequest {
+                payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    compute_graph,
+                    precondition: None,
+                }),
+                state_changes_processed: vec![],
+            })
+        };
+        create("graph_1", &[("team", "ml"), ("tier", "prod")]).await?;
+        create("graph_2", &[("team", "ml")]).await?;
+        create("graph_3", &[("team", "search"), ("tier", "prod")]).await?;
+
+        let reader = indexify_state.reader();
+        let names = |labels: &[(&str, &str)]| -> Result<Vec<String>> {
+            let labels = labels
+                .iter()
+                .map(|(label, value)| (label.to_string(), value.to_string()))
+                .collect();
+            let (graphs, _) =
+                reader.list_compute_graphs_by_labels(TEST_NAMESPACE, &labels, None, None)?;
+            Ok(graphs.into_iter().map(|graph| graph.name).collect())
+        };
+        assert_eq!(names(&[("team", "ml")])?, vec!["graph_1", "graph_2"]);
+        assert_eq!(names(&[("team", "ml"), ("tier", "prod")])?, vec!["graph_1"]);
+        assert_eq!(names(&[])?.len(), 3);
+
+        // Updates and deletes remove the stale index entries.
+        create("graph_1", &[("team", "search")]).await?;
+        assert_eq!(names(&[("team", "ml")])?, vec!["graph_2"]);
+        assert_eq!(names(&[("tier", "prod")])?, vec!["graph_3"]);
+        indexify_state
+            .write(StateMachineUpdateRequest {
+                payload: RequestPayload::DeleteComputeGraph(DeleteComputeGraphRequest {
+                    namespace: TEST_NAMESPACE.to_string(),
+                    name: "graph_3".to_string(),
+                }),
+                state_changes_processed: vec![],
+            })
+            .await?;
+        assert_eq!(names(&[("team", "search")])?, vec!["graph_1"]);
+        let (index_entries, _) = reader.get_raw_rows_from_cf_with_limits(
+            &[],
+            None,
+            IndexifyObjectsColumns::GraphLabels,
+            None,
+        )?;
+        assert_eq!(index_entries.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_multi_get() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        for name in ["graph_1", "graph_2"] {
+            let mut compute_graph = mock_graph_a();
+            compute_graph.name = name.to_string();
+            indexify_state
+                .write(StateMachineUpdateRequest {
+                    payload: RequestPayload::CreateComputeGraph(CreateComputeGraphRequest {
+                        namespace: TEST_NAMESPACE.to_string(),
+                        compute_graph,
+                        precondition: None,
+                    }),
+                    state_changes_processed: vec![],
+                })
+                .await?;
+        }
+
+        let reader = indexify_state.reader();
+        let graphs =
+            reader.get_compute_graphs(TEST_NAMESPACE, &["graph_2", "missing", "graph_1"])?;
+        let names: Vec<Option<String>> = graphs
+            .into_iter()
+            .map(|graph| graph.map(|graph| graph.name))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                Some("graph_2".to_string()),
+                None,
+                Some("graph_1".to_string())
+            ]
+        );
+        assert!(reader.get_tasks::<&str>(&[])?.is_empty());
+        let labels = ReadLabels {
+            column: "ComputeGraphs".to_string(),
+            op: "multi_get".to_string(),
+        };
+        assert_eq!(
+            indexify_state
+                .metrics
+                .records_read
+                .get_or_create(&labels)
+                .get(),
+            2
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_activity() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let indexify_state = IndexifyState::new(temp_dir.path().join("state")).await?;
+        let write = |payload| {
+            indexify_state.write(StateMachineUpdateRequest {
+                payload,
+                state_changes_processed: vec![],
+            })
+        };
+        for namespace in [TEST_NAMESPACE, "other"] {
+            write(RequestPayload::CreateNameSpace(NamespaceRequest {
+                name: namespace.to_string(),
+                blob_storage: None,
+                scheduling_weight: None,
+                reprocessing_policy: Default::default(),
+                near_duplicates: None,
+            }))
+            .await?;
+            let mut compute_graph = mock_graph_a();
+            compute_graph.namespace = namespace.to_string();
+            write(RequestPayload::CreateComputeGraph(
+                CreateComputeGraphRequest {
+                    namespace: namespace.to_string(),
+                    compute_graph,
+                    precondition: None,
+                },
+            ))
+            .await?;
+        }
+        write(RequestPayload::RegisterExecutor(RegisterExecutorRequest {
+            executor: mock_executor(),
+        }))
+        .await?;
+        write(RequestPayload::RecordActivity(RecordActivityRequest {
+            namespace: None,
+            activity: Activity::DiskQuota {
+                message: "over the soft limit".to_string(),
+            },
+        }))
+        .await?;
+        write(RequestPayload::InvokeComputeGraph(
+            InvokeComputeGraphRequest {
+                namespace: TEST_NAMESPACE.to_string(),
+                compute_graph_name: "graph_A".to_string(),
+                invocation_payload: mock_invocation_payload(),
+            },
+        ))
+        .await?;
+
+        let reader = indexify_state.reader();
+        let (events, cursor) = reader.list_activity(TEST_NAMESPACE, None, None)?;
+        assert!(cursor.is_none());
+        let mut kinds = events
+            .iter()
+            .map(|event| match event.activity {
+                Activity::GraphRegistered { .. } => "graph_registered",
+                Activity::InvocationStarted { .. } => "invocation_started",
+                Activity::InvocationFinished { .. } => "invocation_finished",
+                Activity::ExecutorJoined { .. } => "executor_joined",
+                Activity::DiskQuota { .. } => "disk_quota",
+            })
+            .collect::<Vec<_>>();
+        kinds.sort();
+        assert_eq!(
+            kinds,
+            vec![
+                "disk_quota",
+                "executor_joined",
+                "graph_registered",
+                "invocation_started"
+            ]
+        );
+        assert!(events
+            .iter()
+            .all(|event| event.namespace.as_deref() != Some("other")));
+        assert!(events
+            .windows(2)
+            .all(|pair| pair[0].recorded_at >= pair[1].recorded_at));
+
+        // Pages pick up where the previous one stopped.
+        let (first_page, cursor) = reader.list_activity(TEST_NAMESPACE, None, Some(3))?;
+        assert_eq!(first_page.len(), 3);
+        let (second_page, cursor) =
+            reader.list_activity(TEST_NAMESPACE, cursor.as_deref(), Some(3))?;
+        assert!(cursor.is_none());
+        assert_eq!([first_page, second_page].concat(), events);
+
+        write(RequestPayload::PruneActivity(PruneActivityRequest {
+            recorded_before: u64::MAX,
+        }))
+        .await?;
+        assert!(reader
+            .list_activity(TEST_NAMESPACE, None, None)?
+            .0
+            .is_empty());
+        assert!(reader.list_activity("other", None, None)?.0.is_empty());
+        Ok(())
+    }
 }
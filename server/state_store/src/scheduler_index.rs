@@ -0,0 +1,185 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Result;
+use data_model::{ExecutorId, GpuDeviceUsage, Task};
+use rocksdb::{Transaction, TransactionDB};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    scanner::StateReader,
+    serializer::{JsonEncode, JsonEncoder},
+    state_machine::IndexifyObjectsColumns,
+};
+
+pub(crate) const SCHEDULER_INDEX_KEY: &[u8] = b"scheduler_index";
+
+/// What the scheduler needs to know about the allocated tasks to place new
+/// ones. Updated in the transactions allocating and releasing tasks, so that
+/// placement and a restarted server don't scan every allocation to derive it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct SchedulerIndex {
+    /// Number of tasks allocated to each executor.
+    pub allocated_tasks: HashMap<ExecutorId, u64>,
+    /// GPU capacity held on each device, keyed by executor and device id.
+    pub gpu_usage: HashMap<ExecutorId, HashMap<String, GpuDeviceUsage>>,
+    /// Number of allocated tasks counting against each external dependency.
+    pub dependency_usage: HashMap<String, u64>,
+}
+
+impl SchedulerIndex {
+    pub fn add(&mut self, executor_id: &ExecutorId, task: &Task) {
+        *self.allocated_tasks.entry(executor_id.clone()).or_default() += 1;
+        if let Some(allocation) = &task.gpu_allocation {
+            self.gpu_usage
+                .entry(executor_id.clone())
+                .or_default()
+                .entry(allocation.device_id.clone())
+                .or_default()
+                .add(allocation);
+        }
+        if let Some(dependency) = &task.external_dependency {
+            *self.dependency_usage.entry(dependency.clone()).or_default() += 1;
+        }
+    }
+
+    pub fn remove(&mut self, executor_id: &ExecutorId, task: &Task) {
+        if let Some(count) = self.allocated_tasks.get_mut(executor_id) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                self.allocated_tasks.remove(executor_id);
+            }
+        }
+        if let Some(allocation) = &task.gpu_allocation {
+            if let Some(devices) = self.gpu_usage.get_mut(executor_id) {
+                if let Some(usage) = devices.get_mut(&allocation.device_id) {
+                    usage.remove(allocation);
+                    if usage.tasks == 0 {
+                        devices.remove(&allocation.device_id);
+                    }
+                }
+                if devices.is_empty() {
+                    self.gpu_usage.remove(executor_id);
+                }
+            }
+        }
+        if let Some(dependency) = &task.external_dependency {
+            if let Some(count) = self.dependency_usage.get_mut(dependency) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    self.dependency_usage.remove(dependency);
+                }
+            }
+        }
+    }
+
+    /// Derives the index from the allocated tasks, the source of truth it's
+    /// checked against.
+    pub fn from_allocations(reader: &StateReader) -> Result<Self> {
+        let mut index = SchedulerIndex::default();
+        for executor in reader.get_all_executors()? {
+            for task in reader.get_tasks_by_executor(&executor.id, usize::MAX)? {
+                index.add(&executor.id, &task);
+            }
+        }
+        Ok(index)
+    }
+
+    /// Whether two indexes agree, up to the rounding of GPU fractions.
+    pub fn consistent_with(&self, other: &SchedulerIndex) -> bool {
+        let gpu_usage_matches = |a: &Self, b: &Self| {
+            a.gpu_usage.iter().all(|(executor_id, devices)| {
+                devices.iter().all(|(device_id, usage)| {
+                    b.gpu_usage
+                        .get(executor_id)
+                        .and_then(|devices| devices.get(device_id))
+                        .is_some_and(|other| usage.approx_eq(other))
+                })
+            })
+        };
+        self.allocated_tasks == other.allocated_tasks &&
+            self.dependency_usage == other.dependency_usage &&
+            gpu_usage_matches(self, other) &&
+            gpu_usage_matches(other, self)
+    }
+}
+
+/// Reads the index for an update within the transaction.
+pub(crate) fn read(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+) -> Result<SchedulerIndex> {
+    let index = txn.get_for_update_cf(
+        &IndexifyObjectsColumns::StateMachineMetadata.cf_db(db),
+        SCHEDULER_INDEX_KEY,
+        true,
+    )?;
+    match index {
+        Some(index) => Ok(JsonEncoder::decode(
+            IndexifyObjectsColumns::StateMachineMetadata,
+            SCHEDULER_INDEX_KEY,
+            &index,
+        )?),
+        None => Ok(SchedulerIndex::default()),
+    }
+}
+
+pub(crate) fn write(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    index: &SchedulerIndex,
+) -> Result<()> {
+    txn.put_cf(
+        &IndexifyObjectsColumns::StateMachineMetadata.cf_db(db),
+        SCHEDULER_INDEX_KEY,
+        JsonEncoder::encode(
+            IndexifyObjectsColumns::StateMachineMetadata,
+            SCHEDULER_INDEX_KEY,
+            index,
+        )?,
+    )?;
+    Ok(())
+}
+
+/// Applies a change to the index within the transaction.
+pub(crate) fn update(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    change: impl FnOnce(&mut SchedulerIndex),
+) -> Result<()> {
+    let mut index = read(db, txn)?;
+    change(&mut index);
+    write(db, txn, &index)
+}
+
+/// Checks the persisted index against the allocated tasks, both read from
+/// the same snapshot, and rebuilds it when they disagree or it was never
+/// persisted. Returns whether the index was rebuilt.
+pub(crate) fn verify(db: &Arc<TransactionDB>) -> Result<bool> {
+    let reader = StateReader::with_snapshot(db);
+    let persisted: Option<SchedulerIndex> = reader.get_from_cf(
+        &IndexifyObjectsColumns::StateMachineMetadata,
+        SCHEDULER_INDEX_KEY,
+    )?;
+    let derived = SchedulerIndex::from_allocations(&reader)?;
+    if persisted
+        .as_ref()
+        .is_some_and(|persisted| persisted.consistent_with(&derived))
+    {
+        return Ok(false);
+    }
+    // Allocations change along with the index, so locking the index keeps
+    // them stable while the index is derived again.
+    let txn = db.transaction();
+    read(db, &txn)?;
+    let derived = SchedulerIndex::from_allocations(&StateReader::new(db.clone()))?;
+    if persisted.is_some() {
+        warn!(
+            "scheduler index disagrees with the allocated tasks, rebuilding it: {:?} != {:?}",
+            persisted, derived
+        );
+    }
+    write(db, &txn, &derived)?;
+    txn.commit()?;
+    Ok(true)
+}
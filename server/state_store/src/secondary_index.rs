@@ -0,0 +1,50 @@
+use std::{collections::HashSet, sync::Arc};
+
+use anyhow::Result;
+use data_model::ComputeGraph;
+use rocksdb::{Transaction, TransactionDB};
+
+use crate::state_machine::IndexifyObjectsColumns;
+
+/// Record whose index entries are derived from its value. The index is kept
+/// in sync by calling `update` with the record's previous and new value in
+/// the transaction writing it, so it never points at a stale record.
+pub(crate) trait SecondaryIndex {
+    const COLUMN: IndexifyObjectsColumns;
+
+    fn index_keys(&self) -> Vec<String>;
+}
+
+impl SecondaryIndex for ComputeGraph {
+    const COLUMN: IndexifyObjectsColumns = IndexifyObjectsColumns::GraphLabels;
+
+    fn index_keys(&self) -> Vec<String> {
+        self.label_keys()
+    }
+}
+
+/// Replaces the index entries of `previous` with the ones of `current`,
+/// either of which is None when the record is created or deleted.
+pub(crate) fn update<T: SecondaryIndex>(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    previous: Option<&T>,
+    current: Option<&T>,
+) -> Result<()> {
+    let keys = |record: Option<&T>| {
+        record
+            .map(|record| record.index_keys())
+            .unwrap_or_default()
+            .into_iter()
+            .collect::<HashSet<_>>()
+    };
+    let (previous_keys, current_keys) = (keys(previous), keys(current));
+    let cf = T::COLUMN.cf_db(db);
+    for key in previous_keys.difference(&current_keys) {
+        txn.delete_cf(&cf, key)?;
+    }
+    for key in current_keys.difference(&previous_keys) {
+        txn.put_cf(&cf, key, [])?;
+    }
+    Ok(())
+}
@@ -1,19 +1,90 @@
-use std::{any::type_name, fmt::Debug};
+use std::{
+    any::type_name,
+    collections::HashMap,
+    fmt::{self, Debug},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+};
 
-use anyhow::Result;
-use serde::de::DeserializeOwned;
+use anyhow::{anyhow, Result};
+use prost::Message;
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{
+    de::{DeserializeOwned, IgnoredAny},
+    Serialize,
+};
 
-pub struct JsonEncoder;
+/// Encoding of a stored record. Records start with the tag byte of their
+/// format, except JSON records which are stored bare: a JSON document starts
+/// with a printable character, never with a tag, so records written before
+/// tags existed keep decoding and JSON stays readable with any RocksDB tool.
+/// Readers detect the format of each record, so the format records are
+/// written in can change without rewriting the existing ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    /// See `BincodeSerializer`.
+    Bincode,
+    /// See `ProtobufSerializer`.
+    Protobuf,
+    /// Record of another format encrypted, see `Encryption`.
+    Encrypted,
+    /// Record of another format prefixed with its CRC32, see
+    /// `enable_checksums`.
+    Checksummed,
+}
 
-pub trait JsonEncode {
-    fn encode<T: serde::Serialize + Debug>(value: &T) -> Result<Vec<u8>>;
-    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T>;
+impl Format {
+    const BINCODE_TAG: u8 = 0x01;
+    const CHECKSUMMED_TAG: u8 = 0x04;
+    const ENCRYPTED_TAG: u8 = 0x03;
+    const PROTOBUF_TAG: u8 = 0x02;
+
+    fn tag(&self) -> Option<u8> {
+        match self {
+            Format::Json => None,
+            Format::Bincode => Some(Self::BINCODE_TAG),
+            Format::Protobuf => Some(Self::PROTOBUF_TAG),
+            Format::Encrypted => Some(Self::ENCRYPTED_TAG),
+            Format::Checksummed => Some(Self::CHECKSUMMED_TAG),
+        }
+    }
+
+    /// Format of an encoded record.
+    pub fn of(bytes: &[u8]) -> Result<Format> {
+        match bytes.first() {
+            Some(&Self::BINCODE_TAG) => Ok(Format::Bincode),
+            Some(&Self::PROTOBUF_TAG) => Ok(Format::Protobuf),
+            Some(&Self::ENCRYPTED_TAG) => Ok(Format::Encrypted),
+            Some(&Self::CHECKSUMMED_TAG) => Ok(Format::Checksummed),
+            Some(tag) if *tag < b' ' && !tag.is_ascii_whitespace() => {
+                Err(anyhow!("unknown record format tag: {:#04x}", tag))
+            }
+            _ => Ok(Format::Json),
+        }
+    }
 }
 
-impl JsonEncode for JsonEncoder {
-    fn encode<T: serde::Serialize + Debug>(value: &T) -> Result<Vec<u8>> {
+pub trait Serializer {
+    const FORMAT: Format;
+
+    fn serialize<T: Serialize + Debug>(value: &T) -> Result<Vec<u8>>;
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T>;
+}
+
+pub struct JsonSerializer;
+
+impl Serializer for JsonSerializer {
+    const FORMAT: Format = Format::Json;
+
+    fn serialize<T: Serialize + Debug>(value: &T) -> Result<Vec<u8>> {
         serde_json::to_vec(value).map_err(|e| {
-            anyhow::anyhow!(
+            anyhow!(
                 "error serializing into json: {}, type: {}, value: {:?}",
                 e,
                 type_name::<T>(),
@@ -22,9 +93,9 @@ impl JsonEncode for JsonEncoder {
         })
     }
 
-    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
         serde_json::from_slice(bytes).map_err(|e| {
-            anyhow::anyhow!(
+            anyhow!(
                 "error deserializing from json bytes, {}, value: {:?}",
                 e,
                 type_name::<T>()
@@ -32,3 +103,699 @@ impl JsonEncode for JsonEncoder {
         })
     }
 }
+
+/// Compact binary encoding. It isn't self-describing, so records can't be
+/// checked without their type and the serde attributes relying on it, e.g.
+/// internally tagged enums, `flatten` or `skip_serializing_if`, aren't
+/// supported. Records only decode while their type is unchanged: once it
+/// gains a field, the records written before can't be read.
+pub struct BincodeSerializer;
+
+impl Serializer for BincodeSerializer {
+    const FORMAT: Format = Format::Bincode;
+
+    fn serialize<T: Serialize + Debug>(value: &T) -> Result<Vec<u8>> {
+        bincode::serialize(value).map_err(|e| {
+            anyhow!(
+                "error serializing into bincode: {}, type: {}, value: {:?}",
+                e,
+                type_name::<T>(),
+                value
+            )
+        })
+    }
+
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+        bincode::deserialize(bytes).map_err(|e| {
+            anyhow!(
+                "error deserializing from bincode bytes, {}, value: {:?}",
+                e,
+                type_name::<T>()
+            )
+        })
+    }
+}
+
+/// Protobuf encoding of the serde definitions of the data model, which would
+/// otherwise need generated message types kept in step with them. Records
+/// are `protobuf::Value` messages, the protobuf counterpart of a JSON
+/// document, so like JSON they stay readable as their type gains fields.
+pub struct ProtobufSerializer;
+
+impl Serializer for ProtobufSerializer {
+    const FORMAT: Format = Format::Protobuf;
+
+    fn serialize<T: Serialize + Debug>(value: &T) -> Result<Vec<u8>> {
+        let value = serde_json::to_value(value).map_err(|e| {
+            anyhow!(
+                "error serializing into protobuf: {}, type: {}, value: {:?}",
+                e,
+                type_name::<T>(),
+                value
+            )
+        })?;
+        Ok(protobuf::Value::from(value).encode_to_vec())
+    }
+
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T> {
+        let value = protobuf::Value::decode(bytes)
+            .map_err(|e| anyhow!("error decoding protobuf bytes, {}", e))
+            .and_then(serde_json::Value::try_from)?;
+        serde_json::from_value(value).map_err(|e| {
+            anyhow!(
+                "error deserializing from protobuf bytes, {}, value: {:?}",
+                e,
+                type_name::<T>()
+            )
+        })
+    }
+}
+
+/// Messages of protobuf records, in the schema:
+///
+/// ```proto
+/// message Value {
+///   oneof kind {
+///     bool null_value = 1;
+///     bool bool_value = 2;
+///     int64 int_value = 3;
+///     uint64 uint_value = 4;
+///     double float_value = 5;
+///     string string_value = 6;
+///     List list_value = 7;
+///     Map map_value = 8;
+///   }
+/// }
+/// message List { repeated Value values = 1; }
+/// message Map { map<string, Value> entries = 1; }
+/// ```
+///
+/// Unlike `google.protobuf.Value`, integers are kept exact.
+mod protobuf {
+    use std::collections::BTreeMap;
+
+    use anyhow::{anyhow, Result};
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Value {
+        #[prost(oneof = "Kind", tags = "1, 2, 3, 4, 5, 6, 7, 8")]
+        pub kind: Option<Kind>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Kind {
+        #[prost(bool, tag = "1")]
+        NullValue(bool),
+        #[prost(bool, tag = "2")]
+        BoolValue(bool),
+        #[prost(int64, tag = "3")]
+        IntValue(i64),
+        #[prost(uint64, tag = "4")]
+        UintValue(u64),
+        #[prost(double, tag = "5")]
+        FloatValue(f64),
+        #[prost(string, tag = "6")]
+        StringValue(String),
+        #[prost(message, tag = "7")]
+        ListValue(List),
+        #[prost(message, tag = "8")]
+        MapValue(Map),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct List {
+        #[prost(message, repeated, tag = "1")]
+        pub values: Vec<Value>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Map {
+        #[prost(btree_map = "string, message", tag = "1")]
+        pub entries: BTreeMap<String, Value>,
+    }
+
+    impl From<serde_json::Value> for Value {
+        fn from(value: serde_json::Value) -> Self {
+            let kind = match value {
+                serde_json::Value::Null => Kind::NullValue(true),
+                serde_json::Value::Bool(value) => Kind::BoolValue(value),
+                serde_json::Value::Number(number) => {
+                    if let Some(value) = number.as_u64() {
+                        Kind::UintValue(value)
+                    } else if let Some(value) = number.as_i64() {
+                        Kind::IntValue(value)
+                    } else {
+                        Kind::FloatValue(number.as_f64().unwrap_or_default())
+                    }
+                }
+                serde_json::Value::String(value) => Kind::StringValue(value),
+                serde_json::Value::Array(values) => Kind::ListValue(List {
+                    values: values.into_iter().map(Value::from).collect(),
+                }),
+                serde_json::Value::Object(entries) => Kind::MapValue(Map {
+                    entries: entries
+                        .into_iter()
+                        .map(|(key, value)| (key, Value::from(value)))
+                        .collect(),
+                }),
+            };
+            Value { kind: Some(kind) }
+        }
+    }
+
+    impl TryFrom<Value> for serde_json::Value {
+        type Error = anyhow::Error;
+
+        fn try_from(value: Value) -> Result<Self> {
+            let kind = value
+                .kind
+                .ok_or_else(|| anyhow!("protobuf value without a kind"))?;
+            Ok(match kind {
+                Kind::NullValue(_) => serde_json::Value::Null,
+                Kind::BoolValue(value) => serde_json::Value::Bool(value),
+                Kind::IntValue(value) => serde_json::Value::from(value),
+                Kind::UintValue(value) => serde_json::Value::from(value),
+                Kind::FloatValue(value) => serde_json::Number::from_f64(value)
+                    .map(serde_json::Value::Number)
+                    .ok_or_else(|| anyhow!("protobuf value is a non finite float"))?,
+                Kind::StringValue(value) => serde_json::Value::String(value),
+                Kind::ListValue(list) => serde_json::Value::Array(
+                    list.values
+                        .into_iter()
+                        .map(serde_json::Value::try_from)
+                        .collect::<Result<_>>()?,
+                ),
+                Kind::MapValue(map) => serde_json::Value::Object(
+                    map.entries
+                        .into_iter()
+                        .map(|(key, value)| Ok((key, serde_json::Value::try_from(value)?)))
+                        .collect::<Result<_>>()?,
+                ),
+            })
+        }
+    }
+}
+
+/// AES-256-GCM encryption of the records written to the state store. An
+/// encrypted record is the tag of the format, the id of its key, a random
+/// nonce and the record sealed in its own format. The column and the key of
+/// the record are authenticated along with it, a record copied under another
+/// key doesn't decrypt. Records are sealed with the current key and opened
+/// with the key of their id, so keys can be rotated while the records of
+/// previous keys are rewritten.
+pub struct Encryption {
+    key_id: u8,
+    keys: HashMap<u8, LessSafeKey>,
+    rng: SystemRandom,
+}
+
+/// Returned for encrypted records which can't be opened with the configured
+/// keys. The records may well be intact, it's the configuration which is
+/// wrong, so they aren't treated as corrupt.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyError {
+    /// No encryption key is configured.
+    NotConfigured,
+    /// The key the record was sealed with isn't configured.
+    UnknownKey(u8),
+    /// The record doesn't open with the key of its id. It was sealed with
+    /// another key configured under that id, stored under another key, or
+    /// it's corrupt: authenticated encryption can't tell these apart.
+    Mismatch(u8),
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::NotConfigured => write!(
+                f,
+                "record is encrypted but no encryption key is configured"
+            ),
+            KeyError::UnknownKey(key_id) => write!(
+                f,
+                "record is encrypted with key {} which isn't configured",
+                key_id
+            ),
+            KeyError::Mismatch(key_id) => write!(
+                f,
+                "error decrypting a record, is key {} the one it was written with and is it stored under its own key?",
+                key_id
+            ),
+        }
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+static ENCRYPTION: OnceLock<Encryption> = OnceLock::new();
+
+static CHECKSUMS: AtomicBool = AtomicBool::new(false);
+
+fn bind_key(key: &[u8]) -> Result<LessSafeKey> {
+    let key = UnboundKey::new(&AES_256_GCM, key)
+        .map_err(|_| anyhow!("encryption keys are 32 bytes, got {}", key.len()))?;
+    Ok(LessSafeKey::new(key))
+}
+
+fn decode_hex(key: &str) -> Result<Vec<u8>> {
+    hex::decode(key.trim()).map_err(|e| anyhow!("invalid encryption key: {}", e))
+}
+
+/// Additional data of the record stored under `key` of `column`.
+fn aad(column: &str, key: &[u8]) -> Vec<u8> {
+    [column.as_bytes(), b"|", key].concat()
+}
+
+impl Encryption {
+    pub fn new(key_id: u8, key: &[u8]) -> Result<Self> {
+        Ok(Self {
+            key_id,
+            keys: HashMap::from([(key_id, bind_key(key)?)]),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// Key encoded in hex, as generated by `openssl rand -hex 32`.
+    pub fn from_hex(key_id: u8, key: &str) -> Result<Self> {
+        Self::new(key_id, &decode_hex(key)?)
+    }
+
+    /// Adds a key of a previous rotation, encoded in hex. Records it sealed
+    /// are opened with it, new records are sealed with the current key.
+    pub fn with_previous_key(mut self, key_id: u8, key: &str) -> Result<Self> {
+        if self.keys.contains_key(&key_id) {
+            return Err(anyhow!("encryption key id {} is used twice", key_id));
+        }
+        self.keys.insert(key_id, bind_key(&decode_hex(key)?)?);
+        Ok(self)
+    }
+
+    fn seal(&self, column: &str, key: &[u8], record: Vec<u8>) -> Result<Vec<u8>> {
+        let mut nonce = [0; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| anyhow!("error generating a nonce"))?;
+        let mut sealed = record;
+        self.keys[&self.key_id]
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(aad(column, key)),
+                &mut sealed,
+            )
+            .map_err(|_| anyhow!("error encrypting a record"))?;
+        Ok([&[Format::ENCRYPTED_TAG, self.key_id][..], &nonce, &sealed].concat())
+    }
+
+    fn open(&self, column: &str, key: &[u8], envelope: &[u8]) -> Result<Vec<u8>> {
+        if envelope.len() < 2 + NONCE_LEN {
+            return Err(anyhow!("encrypted record is truncated"));
+        }
+        let key_id = envelope[1];
+        let encryption_key = self.keys.get(&key_id).ok_or(KeyError::UnknownKey(key_id))?;
+        let (nonce, sealed) = envelope[2..].split_at(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce)
+            .map_err(|_| anyhow!("invalid nonce of an encrypted record"))?;
+        let mut record = sealed.to_vec();
+        let len = encryption_key
+            .open_in_place(nonce, Aad::from(aad(column, key)), &mut record)
+            .map_err(|_| KeyError::Mismatch(key_id))?
+            .len();
+        record.truncate(len);
+        Ok(record)
+    }
+}
+
+/// Encrypts the records written from now on, the ones written before stay
+/// readable. Enabled once at startup, before the state store is opened.
+pub fn enable_encryption(encryption: Encryption) -> Result<()> {
+    ENCRYPTION
+        .set(encryption)
+        .map_err(|_| anyhow!("encryption of the state store is already enabled"))
+}
+
+/// Prefixes the records written from now on with their checksum, which
+/// readers verify. Encrypted records are authenticated by their encryption
+/// already, the checksum is what detects corruption of the others.
+pub fn enable_checksums() {
+    CHECKSUMS.store(true, Ordering::Relaxed);
+}
+
+/// Encodes the record stored under `key` of `column` in the format of `S`,
+/// tagged with it, checksummed and encrypted when they are enabled.
+pub fn encode_as<S: Serializer, T: Serialize + Debug>(
+    column: &str,
+    key: &[u8],
+    value: &T,
+) -> Result<Vec<u8>> {
+    let mut record = encode_plain::<S, T>(value)?;
+    if CHECKSUMS.load(Ordering::Relaxed) {
+        record = checksum(record);
+    }
+    seal(ENCRYPTION.get(), column, key, record)
+}
+
+fn checksum(record: Vec<u8>) -> Vec<u8> {
+    let crc = crc32fast::hash(&record);
+    [&[Format::CHECKSUMMED_TAG][..], &crc.to_be_bytes(), &record].concat()
+}
+
+fn verify_checksum(envelope: &[u8]) -> Result<&[u8]> {
+    if envelope.len() < 5 {
+        return Err(anyhow!("checksummed record is truncated"));
+    }
+    let (crc, record) = envelope[1..].split_at(4);
+    let crc = u32::from_be_bytes(crc.try_into()?);
+    let actual = crc32fast::hash(record);
+    if crc != actual {
+        return Err(anyhow!(
+            "checksum mismatch, expected {:#010x}, got {:#010x}",
+            crc,
+            actual
+        ));
+    }
+    Ok(record)
+}
+
+fn encode_plain<S: Serializer, T: Serialize + Debug>(value: &T) -> Result<Vec<u8>> {
+    let encoded = S::serialize(value)?;
+    Ok(match S::FORMAT.tag() {
+        Some(tag) => [&[tag][..], &encoded].concat(),
+        None => encoded,
+    })
+}
+
+fn seal(
+    encryption: Option<&Encryption>,
+    column: &str,
+    key: &[u8],
+    record: Vec<u8>,
+) -> Result<Vec<u8>> {
+    match encryption {
+        Some(encryption) => encryption.seal(column, key, record),
+        None => Ok(record),
+    }
+}
+
+/// The record stored under `key` of `column` decrypted, records which aren't
+/// encrypted are returned as they are.
+pub fn unseal(column: &str, key: &[u8], bytes: &[u8]) -> Result<Vec<u8>> {
+    if Format::of(bytes)? != Format::Encrypted {
+        return Ok(bytes.to_vec());
+    }
+    let encryption = ENCRYPTION.get().ok_or(KeyError::NotConfigured)?;
+    encryption.open(column, key, bytes)
+}
+
+/// The record stored under `key` of `column` for another column or key.
+/// Encrypted records are bound to where they're stored, they're sealed again
+/// with the current key.
+pub fn rebind(from: (&str, &[u8]), to: (&str, &[u8]), bytes: &[u8]) -> Result<Vec<u8>> {
+    if Format::of(bytes)? != Format::Encrypted {
+        return Ok(bytes.to_vec());
+    }
+    let record = unseal(from.0, from.1, bytes)?;
+    seal(ENCRYPTION.get(), to.0, to.1, record)
+}
+
+/// Decodes the record stored under `key` of `column`, written in any format.
+pub fn decode<T: DeserializeOwned>(column: &str, key: &[u8], bytes: &[u8]) -> Result<T> {
+    decode_with(ENCRYPTION.get(), column, key, bytes)
+}
+
+fn decode_with<T: DeserializeOwned>(
+    encryption: Option<&Encryption>,
+    column: &str,
+    key: &[u8],
+    bytes: &[u8],
+) -> Result<T> {
+    match Format::of(bytes)? {
+        Format::Json => JsonSerializer::deserialize(bytes),
+        Format::Bincode => BincodeSerializer::deserialize(&bytes[1..]),
+        Format::Protobuf => ProtobufSerializer::deserialize(&bytes[1..]),
+        Format::Encrypted => {
+            let encryption = encryption.ok_or(KeyError::NotConfigured)?;
+            let record = encryption.open(column, key, bytes)?;
+            if Format::of(&record)? == Format::Encrypted {
+                return Err(anyhow!(
+                    "encrypted record contains another encrypted record"
+                ));
+            }
+            decode_with(None, column, key, &record)
+        }
+        Format::Checksummed => {
+            let record = verify_checksum(bytes)?;
+            if matches!(Format::of(record)?, Format::Encrypted | Format::Checksummed) {
+                return Err(anyhow!(
+                    "checksummed record contains an encrypted or checksummed record"
+                ));
+            }
+            decode_with(None, column, key, record)
+        }
+    }
+}
+
+/// Checks that the record stored under `key` of `column` is intact, without
+/// knowing its type: its checksum matches, it decrypts and JSON and protobuf
+/// records parse. Bincode records can't be checked further without their
+/// type.
+pub fn verify(column: &str, key: &[u8], bytes: &[u8]) -> Result<()> {
+    verify_with(ENCRYPTION.get(), column, key, bytes)
+}
+
+fn verify_with(
+    encryption: Option<&Encryption>,
+    column: &str,
+    key: &[u8],
+    bytes: &[u8],
+) -> Result<()> {
+    match Format::of(bytes)? {
+        Format::Encrypted => {
+            let encryption = encryption.ok_or(KeyError::NotConfigured)?;
+            verify_with(None, column, key, &encryption.open(column, key, bytes)?)
+        }
+        Format::Checksummed => verify_with(None, column, key, verify_checksum(bytes)?),
+        Format::Bincode => Ok(()),
+        _ => decode_with::<IgnoredAny>(None, column, key, bytes).map(|_| ()),
+    }
+}
+
+pub struct JsonEncoder;
+
+/// Encodes and decodes the record stored under `key` of `column`, which
+/// encrypted records are bound to.
+pub trait JsonEncode {
+    fn encode<T: serde::Serialize + Debug>(
+        column: impl AsRef<str>,
+        key: impl AsRef<[u8]>,
+        value: &T,
+    ) -> Result<Vec<u8>>;
+    fn decode<T: DeserializeOwned>(
+        column: impl AsRef<str>,
+        key: impl AsRef<[u8]>,
+        bytes: &[u8],
+    ) -> Result<T>;
+}
+
+/// Writes JSON, reads records of any format.
+impl JsonEncode for JsonEncoder {
+    fn encode<T: serde::Serialize + Debug>(
+        column: impl AsRef<str>,
+        key: impl AsRef<[u8]>,
+        value: &T,
+    ) -> Result<Vec<u8>> {
+        encode_as::<JsonSerializer, _>(column.as_ref(), key.as_ref(), value)
+    }
+
+    fn decode<T: DeserializeOwned>(
+        column: impl AsRef<str>,
+        key: impl AsRef<[u8]>,
+        bytes: &[u8],
+    ) -> Result<T> {
+        decode(column.as_ref(), key.as_ref(), bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Record {
+        id: String,
+        sizes: Vec<u64>,
+    }
+
+    const COLUMN: &str = "Records";
+
+    #[test]
+    fn test_format_negotiation() -> Result<()> {
+        let record = Record {
+            id: "id".to_string(),
+            sizes: vec![1, 2],
+        };
+        let json = encode_as::<JsonSerializer, _>(COLUMN, b"id", &record)?;
+        assert_eq!(json, serde_json::to_vec(&record)?);
+        assert_eq!(JsonEncoder::decode::<Record>(COLUMN, b"id", &json)?, record);
+        // Bare JSON, including pretty printed JSON.
+        let pretty = serde_json::to_vec_pretty(&vec![&record])?;
+        assert_eq!(
+            JsonEncoder::decode::<Vec<Record>>(COLUMN, b"id", &pretty)?,
+            vec![record]
+        );
+
+        let bincode = encode_as::<BincodeSerializer, _>(COLUMN, b"id", &record)?;
+        assert_eq!(Format::of(&bincode)?, Format::Bincode);
+        let protobuf = encode_as::<ProtobufSerializer, _>(COLUMN, b"id", &record)?;
+        assert_eq!(Format::of(&protobuf)?, Format::Protobuf);
+        for bytes in [&bincode, &protobuf] {
+            assert_eq!(JsonEncoder::decode::<Record>(COLUMN, b"id", bytes)?, record);
+            verify(COLUMN, b"id", bytes)?;
+        }
+        // Records of another format aren't mistaken for JSON.
+        let tagged = [&[Format::PROTOBUF_TAG][..], &json].concat();
+        assert!(JsonEncoder::decode::<Record>(COLUMN, b"id", &tagged).is_err());
+        assert!(verify(COLUMN, b"id", &tagged).is_err());
+        assert!(Format::of(&[0x1f]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_protobuf() -> Result<()> {
+        // Integers are kept exact and, like JSON, records written before
+        // their type gained a field stay readable.
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Grown {
+            id: String,
+            sizes: Vec<u64>,
+            #[serde(default)]
+            offset: Option<i64>,
+        }
+        let record = Record {
+            id: "id".to_string(),
+            sizes: vec![u64::MAX, 0],
+        };
+        let protobuf = encode_plain::<ProtobufSerializer, _>(&record)?;
+        assert_eq!(
+            JsonEncoder::decode::<Grown>(COLUMN, b"id", &protobuf)?,
+            Grown {
+                id: "id".to_string(),
+                sizes: vec![u64::MAX, 0],
+                offset: None,
+            }
+        );
+        let grown = Grown {
+            id: "id".to_string(),
+            sizes: vec![],
+            offset: Some(i64::MIN),
+        };
+        let protobuf = encode_plain::<ProtobufSerializer, _>(&grown)?;
+        assert_eq!(
+            JsonEncoder::decode::<Grown>(COLUMN, b"id", &protobuf)?,
+            grown
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_encryption() -> Result<()> {
+        let record = Record {
+            id: "id".to_string(),
+            sizes: vec![1, 2],
+        };
+        let encryption = Encryption::from_hex(1, &"ab".repeat(32))?;
+        let plain = encode_plain::<JsonSerializer, _>(&record)?;
+        let sealed = seal(Some(&encryption), COLUMN, b"id", plain.clone())?;
+        assert_eq!(Format::of(&sealed)?, Format::Encrypted);
+        assert_eq!(sealed[1], 1);
+        assert!(!sealed.windows(2).any(|window| window == b"id"));
+        // Nonces are random, the same record seals differently.
+        assert_ne!(
+            sealed,
+            seal(Some(&encryption), COLUMN, b"id", plain.clone())?
+        );
+        assert_eq!(
+            decode_with::<Record>(Some(&encryption), COLUMN, b"id", &sealed)?,
+            record
+        );
+        // Records written before encryption was enabled stay readable.
+        assert_eq!(
+            decode_with::<Record>(Some(&encryption), COLUMN, b"id", &plain)?,
+            record
+        );
+        // Records are bound to where they're stored.
+        assert!(decode_with::<Record>(Some(&encryption), COLUMN, b"other", &sealed).is_err());
+        assert!(decode_with::<Record>(Some(&encryption), "Others", b"id", &sealed).is_err());
+
+        assert!(decode_with::<Record>(None, COLUMN, b"id", &sealed).is_err());
+        let other = Encryption::from_hex(1, &"cd".repeat(32))?;
+        assert!(decode_with::<Record>(Some(&other), COLUMN, b"id", &sealed).is_err());
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(decode_with::<Record>(Some(&encryption), COLUMN, b"id", &tampered).is_err());
+        assert!(Encryption::from_hex(1, "abcd").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_rotation() -> Result<()> {
+        let record = Record {
+            id: "id".to_string(),
+            sizes: vec![1, 2],
+        };
+        let plain = encode_plain::<JsonSerializer, _>(&record)?;
+        let old = Encryption::from_hex(1, &"ab".repeat(32))?;
+        let sealed_by_old = seal(Some(&old), COLUMN, b"id", plain.clone())?;
+
+        let rotated =
+            Encryption::from_hex(2, &"cd".repeat(32))?.with_previous_key(1, &"ab".repeat(32))?;
+        let sealed = seal(Some(&rotated), COLUMN, b"id", plain)?;
+        assert_eq!(sealed[1], 2);
+        for sealed in [&sealed_by_old, &sealed] {
+            assert_eq!(
+                decode_with::<Record>(Some(&rotated), COLUMN, b"id", sealed)?,
+                record
+            );
+        }
+        // Records of keys that were dropped no longer open.
+        let err = decode_with::<Record>(Some(&old), COLUMN, b"id", &sealed).unwrap_err();
+        assert_eq!(err.downcast_ref(), Some(&KeyError::UnknownKey(2)));
+        assert!(Encryption::from_hex(1, &"ab".repeat(32))?
+            .with_previous_key(1, &"cd".repeat(32))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_checksums() -> Result<()> {
+        let record = Record {
+            id: "id".to_string(),
+            sizes: vec![1, 2],
+        };
+        let checksummed = checksum(encode_plain::<JsonSerializer, _>(&record)?);
+        assert_eq!(Format::of(&checksummed)?, Format::Checksummed);
+        assert_eq!(
+            JsonEncoder::decode::<Record>(COLUMN, b"id", &checksummed)?,
+            record
+        );
+        verify(COLUMN, b"id", &checksummed)?;
+
+        let mut corrupted = checksummed.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert!(JsonEncoder::decode::<Record>(COLUMN, b"id", &corrupted).is_err());
+        assert!(verify(COLUMN, b"id", &corrupted).is_err());
+        assert!(verify(COLUMN, b"id", &checksummed[..3]).is_err());
+        // Encrypted records of checksummed ones decode.
+        let encryption = Encryption::from_hex(1, &"ab".repeat(32))?;
+        let sealed = seal(Some(&encryption), COLUMN, b"id", checksummed)?;
+        assert_eq!(
+            decode_with::<Record>(Some(&encryption), COLUMN, b"id", &sealed)?,
+            record
+        );
+
+        verify(COLUMN, b"id", &encode_plain::<JsonSerializer, _>(&record)?)?;
+        assert!(verify(COLUMN, b"id", b"{\"id\":").is_err());
+        Ok(())
+    }
+}
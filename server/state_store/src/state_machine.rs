@@ -2,50 +2,109 @@ use std::{collections::HashMap, sync::Arc};
 
 use anyhow::{anyhow, Result};
 use data_model::{
+    activity::{Activity, ActivityEvent},
+    alerts::{Alert, Problem, Severity},
+    analytics::{AnalyticsRecord, InvocationRecord, TaskRecord},
+    artifacts::Artifact,
+    audit::{AuditLogEntry, TaskIntervention},
+    canary::GraphCanary,
+    id_generator::new_id,
+    near_duplicates::{self, ContentFingerprint, NearDuplicateAction},
+    output_checks::{OutputCheckAction, OutputCheckStats, QuarantinedOutput},
+    provenance::InvocationProvenance,
+    subscriptions::{NamespaceEvent, Subscription, SubscriptionEvent, RETAINED_EVENTS},
+    ArchivedInvocation,
     ChangeType,
     ComputeGraph,
+    ContentChange,
+    ContentSource,
+    ContentVersion,
     ExecutorId,
+    ExecutorMetadata,
     GraphInvocationCtx,
     GraphInvocationCtxBuilder,
+    GraphVersion,
+    InvocationPayload,
     InvokeComputeGraphEvent,
     Namespace,
+    NamespaceDeletion,
+    NamespaceDeletionStage,
     NodeOutput,
     OutputPayload,
+    ReprocessingPolicy,
+    ResourceUsage,
+    ResourceUsageStats,
     StateChange,
     StateChangeBuilder,
     StateChangeId,
     SystemTask,
     Task,
     TaskAnalytics,
+    TaskId,
+    TaskRejection,
+    UrlFetch,
 };
 use indexify_utils::{get_epoch_time_in_ms, OptionInspectNone};
 use rocksdb::{
     AsColumnFamilyRef,
     BoundColumnFamily,
+    DBWithThreadMode,
     Direction,
     IteratorMode,
+    MultiThreaded,
     OptimisticTransactionDB,
     ReadOptions,
     Transaction,
     TransactionDB,
 };
+use serde::{Deserialize, Serialize};
 use strum::AsRefStr;
 use tracing::error;
 
 use super::serializer::{JsonEncode, JsonEncoder};
-use crate::requests::{
-    CreateTasksRequest,
-    DeleteInvocationRequest,
-    DeregisterExecutorRequest,
-    FinalizeTaskRequest,
-    InvokeComputeGraphRequest,
-    NamespaceRequest,
-    ReductionTasks,
-    RegisterExecutorRequest,
-    RemoveSystemTaskRequest,
-    RerunComputeGraphRequest,
-    RerunInvocationRequest,
-    UpdateSystemTaskRequest,
+use crate::{
+    counters::{self, Counter},
+    orphans,
+    requests::{
+        AckSubscriptionEventsRequest,
+        ArchiveInvocationRequest,
+        ArtifactError,
+        CanaryError,
+        ContentVersionError,
+        CreateSubscriptionRequest,
+        CreateTasksRequest,
+        DeleteArtifactRequest,
+        DeleteInvocationRequest,
+        DeleteNamespaceRequest,
+        DeleteSubscriptionRequest,
+        DeregisterExecutorRequest,
+        ExpireOutputsRequest,
+        FinalizeTaskRequest,
+        FinishCanaryRequest,
+        GraphPrecondition,
+        GraphVersionConflict,
+        InterveneTaskRequest,
+        InvokeComputeGraphRequest,
+        NamespacePlacementConflict,
+        NamespaceRequest,
+        PinInvocationOutputsRequest,
+        ReductionTasks,
+        RegisterArtifactRequest,
+        RegisterExecutorRequest,
+        RejectTaskRequest,
+        RemoveSystemTaskRequest,
+        ReplaySubscriptionRequest,
+        RerunComputeGraphRequest,
+        RerunInvocationRequest,
+        StartCanaryRequest,
+        SubscriptionError,
+        TaskInterventionError,
+        TaskRejectionError,
+        UpdateExecutorArtifactsRequest,
+        UpdateSystemTaskRequest,
+    },
+    scheduler_index,
+    secondary_index,
 };
 
 pub type ContentId = String;
@@ -57,35 +116,130 @@ pub type ContentType = String;
 pub type ExtractionGraphId = String;
 pub type SchemaId = String;
 
-#[derive(AsRefStr, strum::Display, strum::EnumIter)]
+#[derive(AsRefStr, strum::Display, strum::EnumIter, PartialEq, Clone)]
 pub enum IndexifyObjectsColumns {
     StateMachineMetadata, //  StateMachineMetadata
     Executors,            //  ExecutorId -> Executor Metadata
     Namespaces,           //  Namespaces
     ComputeGraphs,        //  Ns_ComputeGraphName -> ComputeGraph
+    GraphCanaries,        //  Ns_ComputeGraphName -> GraphCanary
+    ComputeGraphVersions, //  Ns_ComputeGraphName_Version -> ComputeGraph
+    GraphLabels,          //  Ns_Label=Value_ComputeGraphName -> Empty
 
     Tasks,              //  Ns_CG_<Invocation_Id>_Fn_TaskId -> Task
     GraphInvocationCtx, //  Ns_CG_IngestedId -> GraphInvocationCtx
     ReductionTasks,     //  Ns_CG_Fn_TaskId -> ReduceTask
 
     GraphInvocations, //  Ns_Graph_Id -> InvocationPayload
+    InvocationTags,   //  Ns_Graph_Tag=Value_Id -> Empty
     FnOutputs,        //  Ns_Graph_<Ingested_Id>_Fn_Id -> NodeOutput
     TaskOutputs,      //  NS_TaskID -> NodeOutputID
 
     StateChanges, //  StateChangeId -> StateChange
 
     UnprocessedStateChanges, //  StateChangeId -> Empty
+    StateChangeQueues,       //  Ns_StateChangeId -> StateChange
     TaskAllocations,         //  ExecutorId -> Task_Key
     UnallocatedTasks,        //  Task_Key -> Empty
 
     GcUrls, // List of URLs pending deletion
 
+    ArchivedInvocations, //  Ns_Graph_Id -> ArchivedInvocation
+
+    ResourceUsage, //  Ns_Graph[_Fn] -> ResourceUsageStats
+
+    OutputCheckStats,   //  Ns_Graph_Fn -> OutputCheckStats
+    QuarantinedOutputs, //  Ns_Graph_<Ingested_Id>_Fn_Id -> QuarantinedOutput
+
     SystemTasks, // Long running tasks involving multiple invocations
 
+    Artifacts, //  Ns_Name_Version -> Artifact
+
+    AuditLog, //  Ns_RecordedAt_Id -> AuditLogEntry
+    Activity, //  Ns_RecordedAt_Id -> ActivityEvent, empty Ns for the whole server
+
+    Subscriptions,      //  Ns_Id -> Subscription
+    SubscriptionEvents, //  Ns_SubscriptionId_Sequence -> SubscriptionEvent
+
+    ContentVersions,     //  Ns_Graph_SourceId -> ContentVersion
+    ContentFingerprints, //  Ns_Graph_CreatedAt_InvocationId -> ContentFingerprint
+    UrlFetches,          /*  Ns_Graph_InvocationId -> UrlFetch, invocations waiting for their
+                          * input */
+
+    AppliedRequests, //  RequestId -> AppliedRequest, requests written once
+
+    AnalyticsRecords, //  Id -> AnalyticsRecord pending export
+    Alerts,           //  Id -> Alert pending delivery
+
+    Tombstones, //  Ns_Graph_ -> Empty, deleted graphs whose tasks compaction drops
+
+    CorruptRecords, //  Column_Key -> Value, records quarantined by fsck
+
     Stats, // Stats
 }
 
 impl IndexifyObjectsColumns {
+    /// Whether records are mostly read by key rather than scanned by
+    /// prefix.
+    pub fn is_point_lookup(&self) -> bool {
+        matches!(
+            self,
+            IndexifyObjectsColumns::StateMachineMetadata |
+                IndexifyObjectsColumns::Executors |
+                IndexifyObjectsColumns::Namespaces |
+                IndexifyObjectsColumns::ComputeGraphs |
+                IndexifyObjectsColumns::GraphCanaries |
+                IndexifyObjectsColumns::ComputeGraphVersions |
+                IndexifyObjectsColumns::GraphInvocationCtx |
+                IndexifyObjectsColumns::GraphInvocations |
+                IndexifyObjectsColumns::ResourceUsage |
+                IndexifyObjectsColumns::OutputCheckStats |
+                IndexifyObjectsColumns::SystemTasks |
+                IndexifyObjectsColumns::Artifacts |
+                IndexifyObjectsColumns::Subscriptions |
+                IndexifyObjectsColumns::ContentVersions |
+                IndexifyObjectsColumns::UrlFetches |
+                IndexifyObjectsColumns::AppliedRequests |
+                IndexifyObjectsColumns::Stats
+        )
+    }
+
+    /// Whether values are records encoded by the serializer rather than
+    /// empty markers, counters or raw keys.
+    pub fn holds_records(&self) -> bool {
+        matches!(
+            self,
+            IndexifyObjectsColumns::Executors |
+                IndexifyObjectsColumns::Namespaces |
+                IndexifyObjectsColumns::ComputeGraphs |
+                IndexifyObjectsColumns::GraphCanaries |
+                IndexifyObjectsColumns::ComputeGraphVersions |
+                IndexifyObjectsColumns::Tasks |
+                IndexifyObjectsColumns::GraphInvocationCtx |
+                IndexifyObjectsColumns::ReductionTasks |
+                IndexifyObjectsColumns::GraphInvocations |
+                IndexifyObjectsColumns::FnOutputs |
+                IndexifyObjectsColumns::StateChanges |
+                IndexifyObjectsColumns::StateChangeQueues |
+                IndexifyObjectsColumns::ArchivedInvocations |
+                IndexifyObjectsColumns::ResourceUsage |
+                IndexifyObjectsColumns::OutputCheckStats |
+                IndexifyObjectsColumns::QuarantinedOutputs |
+                IndexifyObjectsColumns::SystemTasks |
+                IndexifyObjectsColumns::Artifacts |
+                IndexifyObjectsColumns::AuditLog |
+                IndexifyObjectsColumns::Activity |
+                IndexifyObjectsColumns::Subscriptions |
+                IndexifyObjectsColumns::SubscriptionEvents |
+                IndexifyObjectsColumns::ContentVersions |
+                IndexifyObjectsColumns::ContentFingerprints |
+                IndexifyObjectsColumns::UrlFetches |
+                IndexifyObjectsColumns::AppliedRequests |
+                IndexifyObjectsColumns::AnalyticsRecords |
+                IndexifyObjectsColumns::Alerts
+        )
+    }
+
     pub fn cf<'a>(&'a self, db: &'a OptimisticTransactionDB) -> Arc<BoundColumnFamily> {
         db.cf_handle(self.as_ref())
             .inspect_none(|| {
@@ -101,22 +255,311 @@ impl IndexifyObjectsColumns {
             })
             .unwrap()
     }
+
+    pub fn cf_secondary<'a>(
+        &'a self,
+        db: &'a DBWithThreadMode<MultiThreaded>,
+    ) -> Arc<BoundColumnFamily<'a>> {
+        db.cf_handle(self.as_ref())
+            .inspect_none(|| {
+                tracing::error!("failed to get column family handle for {}", self.as_ref());
+            })
+            .unwrap()
+    }
 }
 
-pub(crate) fn create_namespace(db: Arc<TransactionDB>, req: &NamespaceRequest) -> Result<()> {
+/// Creates the namespace, or updates the settings of an existing one. The
+/// blob storage placement of a namespace can't change.
+pub(crate) fn create_namespace(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &NamespaceRequest,
+) -> Result<()> {
+    let existing = get_namespace_for_update(&db, txn, &req.name)?;
+    if existing.as_ref().is_some_and(|ns| ns.deletion.is_some()) {
+        return Err(anyhow!("namespace {} is being deleted", req.name));
+    }
+    if existing
+        .as_ref()
+        .is_some_and(|ns| ns.blob_storage != req.blob_storage)
+    {
+        return Err(NamespacePlacementConflict(req.name.clone()).into());
+    }
     let ns = Namespace {
         name: req.name.clone(),
-        created_at: get_epoch_time_in_ms(),
+        created_at: existing
+            .map(|ns| ns.created_at)
+            .unwrap_or_else(get_epoch_time_in_ms),
+        blob_storage: req.blob_storage.clone(),
+        scheduling_weight: req.scheduling_weight,
+        deletion: None,
+        reprocessing_policy: req.reprocessing_policy,
+        near_duplicates: req.near_duplicates,
     };
-    let serialized_namespace = JsonEncoder::encode(&ns)?;
-    db.put_cf(
+    txn.put_cf(
         &IndexifyObjectsColumns::Namespaces.cf_db(&db),
         &ns.name,
-        serialized_namespace,
+        JsonEncoder::encode(IndexifyObjectsColumns::Namespaces, &ns.name, &ns)?,
+    )?;
+    Ok(())
+}
+
+/// Number of tasks or state changes removed by one step of a namespace
+/// deletion, which keeps the transaction of every step small.
+const NAMESPACE_DELETION_BATCH_SIZE: usize = 100;
+
+fn get_namespace_for_update(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    name: &str,
+) -> Result<Option<Namespace>> {
+    txn.get_for_update_cf(&IndexifyObjectsColumns::Namespaces.cf_db(db), name, true)?
+        .map(|value| {
+            JsonEncoder::decode::<Namespace>(IndexifyObjectsColumns::Namespaces, name, &value)
+        })
+        .transpose()
+}
+
+pub(crate) fn mark_namespace_deleting(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &DeleteNamespaceRequest,
+) -> Result<()> {
+    let mut namespace = get_namespace_for_update(&db, txn, &req.name)?
+        .ok_or(anyhow!("namespace not found: {}", req.name))?;
+    if namespace.deletion.is_some() {
+        return Ok(());
+    }
+    namespace.deletion = Some(NamespaceDeletion {
+        started_at: get_epoch_time_in_ms(),
+        stage: NamespaceDeletionStage::CancelTasks,
+        tasks_deleted: 0,
+        compute_graphs_deleted: 0,
+    });
+    txn.put_cf(
+        &IndexifyObjectsColumns::Namespaces.cf_db(&db),
+        &req.name,
+        JsonEncoder::encode(IndexifyObjectsColumns::Namespaces, &req.name, &namespace)?,
     )?;
     Ok(())
 }
 
+/// Runs the next step of deleting a namespace and records its progress on
+/// the namespace. Pending work and tasks go first so nothing new is scheduled
+/// while the compute graphs are deleted one by one, and the namespace itself
+/// is removed last. Returns the task allocations that were cancelled.
+pub(crate) fn advance_namespace_deletion(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &DeleteNamespaceRequest,
+) -> Result<Vec<(ExecutorId, TaskId)>> {
+    let Some(mut namespace) = get_namespace_for_update(&db, txn, &req.name)? else {
+        return Ok(vec![]);
+    };
+    let Some(deletion) = namespace.deletion.as_mut() else {
+        return Ok(vec![]);
+    };
+    let prefix = format!("{}|", req.name);
+    let mut cancelled_allocations = Vec::new();
+    match deletion.stage {
+        NamespaceDeletionStage::CancelTasks => {
+            let mut state_change_ids = Vec::new();
+            for iter in make_prefix_iterator(
+                txn,
+                &IndexifyObjectsColumns::StateChangeQueues.cf_db(&db),
+                prefix.as_bytes(),
+                &None,
+            )
+            .take(NAMESPACE_DELETION_BATCH_SIZE)
+            {
+                let (key, value) = iter?;
+                state_change_ids.push(
+                    JsonEncoder::decode::<StateChange>(
+                        IndexifyObjectsColumns::StateChangeQueues,
+                        &key,
+                        &value,
+                    )?
+                    .id,
+                );
+            }
+            mark_state_changes_processed(db.clone(), txn, &state_change_ids)?;
+
+            let iter = txn.iterator_cf_opt(
+                &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+                ReadOptions::default(),
+                IteratorMode::Start,
+            );
+            for kv in iter {
+                let (key, _) = kv?;
+                let task_key = String::from_utf8(Task::key_from_allocation_key(&key)?)?;
+                if !task_key.starts_with(&prefix) {
+                    continue;
+                }
+                txn.delete_cf(&IndexifyObjectsColumns::TaskAllocations.cf_db(&db), &key)?;
+                let allocation_key = String::from_utf8(key.to_vec())?;
+                if let (Some((executor_id, _)), Some((_, task_id))) =
+                    (allocation_key.split_once('|'), task_key.rsplit_once('|'))
+                {
+                    let executor_id = ExecutorId::new(executor_id.to_string());
+                    if let Some(task) =
+                        txn.get_cf(&IndexifyObjectsColumns::Tasks.cf_db(&db), &task_key)?
+                    {
+                        let task = JsonEncoder::decode::<Task>(
+                            IndexifyObjectsColumns::Tasks,
+                            &task_key,
+                            &task,
+                        )?;
+                        scheduler_index::update(&db, txn, |index| {
+                            index.remove(&executor_id, &task)
+                        })?;
+                    }
+                    cancelled_allocations.push((executor_id, TaskId::new(task_id.to_string())));
+                }
+            }
+
+            let mut tasks_deleted = 0;
+            for iter in make_prefix_iterator(
+                txn,
+                &IndexifyObjectsColumns::Tasks.cf_db(&db),
+                prefix.as_bytes(),
+                &None,
+            )
+            .take(NAMESPACE_DELETION_BATCH_SIZE)
+            {
+                let (key, value) = iter?;
+                let task =
+                    JsonEncoder::decode::<Task>(IndexifyObjectsColumns::Tasks, &key, &value)?;
+                delete_cf_prefix(
+                    txn,
+                    &IndexifyObjectsColumns::TaskOutputs.cf_db(&db),
+                    format!("{}|{}|", task.namespace, task.id).as_bytes(),
+                )?;
+                txn.delete_cf(&IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db), &key)?;
+                txn.delete_cf(&IndexifyObjectsColumns::Tasks.cf_db(&db), &key)?;
+                tasks_deleted += 1;
+            }
+            delete_cf_prefix(
+                txn,
+                &IndexifyObjectsColumns::ReductionTasks.cf_db(&db),
+                prefix.as_bytes(),
+            )?;
+            deletion.tasks_deleted += tasks_deleted;
+            if state_change_ids.is_empty() && tasks_deleted == 0 {
+                deletion.stage = NamespaceDeletionStage::DeleteComputeGraphs;
+            }
+        }
+        NamespaceDeletionStage::DeleteComputeGraphs => {
+            let compute_graph = make_prefix_iterator(
+                txn,
+                &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+                prefix.as_bytes(),
+                &None,
+            )
+            .next()
+            .transpose()?;
+            let Some((compute_graph_key, compute_graph)) = compute_graph else {
+                // Tasks created by the scheduler after the first stage ended.
+                for cf in [
+                    IndexifyObjectsColumns::Tasks,
+                    IndexifyObjectsColumns::UnallocatedTasks,
+                    IndexifyObjectsColumns::TaskOutputs,
+                    IndexifyObjectsColumns::ReductionTasks,
+                    IndexifyObjectsColumns::AuditLog,
+                    IndexifyObjectsColumns::Activity,
+                    IndexifyObjectsColumns::Subscriptions,
+                    IndexifyObjectsColumns::SubscriptionEvents,
+                ] {
+                    delete_cf_prefix(txn, &cf.cf_db(&db), prefix.as_bytes())?;
+                }
+                for iter in make_prefix_iterator(
+                    txn,
+                    &IndexifyObjectsColumns::Artifacts.cf_db(&db),
+                    prefix.as_bytes(),
+                    &None,
+                ) {
+                    let (key, value) = iter?;
+                    let artifact = JsonEncoder::decode::<Artifact>(
+                        IndexifyObjectsColumns::Artifacts,
+                        &key,
+                        &value,
+                    )?;
+                    txn.put_cf(
+                        &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+                        artifact.payload.path.as_bytes(),
+                        [],
+                    )?;
+                    txn.delete_cf(&IndexifyObjectsColumns::Artifacts.cf_db(&db), &key)?;
+                }
+                txn.delete_cf(&IndexifyObjectsColumns::Namespaces.cf_db(&db), &req.name)?;
+                return Ok(cancelled_allocations);
+            };
+            let compute_graph = JsonEncoder::decode::<ComputeGraph>(
+                IndexifyObjectsColumns::ComputeGraphs,
+                &compute_graph_key,
+                &compute_graph,
+            )?;
+            let graph_prefix = format!("{}|{}|", req.name, compute_graph.name);
+            for iter in make_prefix_iterator(
+                txn,
+                &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
+                graph_prefix.as_bytes(),
+                &None,
+            ) {
+                let (key, value) = iter?;
+                let invocation = JsonEncoder::decode::<InvocationPayload>(
+                    IndexifyObjectsColumns::GraphInvocations,
+                    &key,
+                    &value,
+                )?;
+                txn.put_cf(
+                    &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+                    invocation.payload.path.as_bytes(),
+                    [],
+                )?;
+            }
+            for iter in make_prefix_iterator(
+                txn,
+                &IndexifyObjectsColumns::ArchivedInvocations.cf_db(&db),
+                graph_prefix.as_bytes(),
+                &None,
+            ) {
+                let (key, value) = iter?;
+                let archive = JsonEncoder::decode::<ArchivedInvocation>(
+                    IndexifyObjectsColumns::ArchivedInvocations,
+                    &key,
+                    &value,
+                )?;
+                txn.put_cf(
+                    &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+                    archive.url.as_bytes(),
+                    [],
+                )?;
+                txn.delete_cf(
+                    &IndexifyObjectsColumns::ArchivedInvocations.cf_db(&db),
+                    &key,
+                )?;
+            }
+            txn.put_cf(
+                &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+                compute_graph.code.path.as_bytes(),
+                [],
+            )?;
+            txn.delete_cf(
+                &IndexifyObjectsColumns::SystemTasks.cf_db(&db),
+                SystemTask::key_from(&req.name, &compute_graph.name),
+            )?;
+            delete_compute_graph(db.clone(), txn, &req.name, &compute_graph.name)?;
+            deletion.compute_graphs_deleted += 1;
+        }
+    }
+    txn.put_cf(
+        &IndexifyObjectsColumns::Namespaces.cf_db(&db),
+        &req.name,
+        JsonEncoder::encode(IndexifyObjectsColumns::Namespaces, &req.name, &namespace)?,
+    )?;
+    Ok(cancelled_allocations)
+}
+
 pub fn remove_system_task(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
@@ -136,9 +579,10 @@ pub fn update_system_task(
     let task = txn
         .get_for_update_cf(&IndexifyObjectsColumns::SystemTasks.cf_db(&db), &key, true)?
         .ok_or(anyhow::anyhow!("Task not found"))?;
-    let mut task = JsonEncoder::decode::<SystemTask>(&task)?;
+    let mut task =
+        JsonEncoder::decode::<SystemTask>(IndexifyObjectsColumns::SystemTasks, &key, &task)?;
     task.restart_key = Some(req.restart_key);
-    let serialized_task = JsonEncoder::encode(&task)?;
+    let serialized_task = JsonEncoder::encode(IndexifyObjectsColumns::SystemTasks, &key, &task)?;
     txn.put_cf(
         &IndexifyObjectsColumns::SystemTasks.cf_db(&db),
         &key,
@@ -160,7 +604,8 @@ pub fn rerun_compute_graph(
             false,
         )?
         .ok_or(anyhow::anyhow!("Compute graph not found"))?;
-    let graph: ComputeGraph = JsonEncoder::decode(&graph).unwrap();
+    let graph: ComputeGraph =
+        JsonEncoder::decode(IndexifyObjectsColumns::ComputeGraphs, &key, &graph).unwrap();
     let task_key = SystemTask::key_from(&req.namespace, &req.compute_graph_name);
     let existing_task = txn.get_for_update_cf(
         &IndexifyObjectsColumns::SystemTasks.cf_db(&db),
@@ -168,7 +613,11 @@ pub fn rerun_compute_graph(
         true,
     )?;
     if let Some(existing_task) = existing_task {
-        let existing_task: SystemTask = JsonEncoder::decode(&existing_task)?;
+        let existing_task: SystemTask = JsonEncoder::decode(
+            IndexifyObjectsColumns::SystemTasks,
+            &task_key,
+            &existing_task,
+        )?;
         if existing_task.graph_version >= graph.version {
             return Err(anyhow::anyhow!("Task already exists"));
         }
@@ -178,7 +627,8 @@ pub fn rerun_compute_graph(
         req.compute_graph_name.clone(),
         graph.version,
     );
-    let serialized_task = JsonEncoder::encode(&task)?;
+    let serialized_task =
+        JsonEncoder::encode(IndexifyObjectsColumns::SystemTasks, &task_key, &task)?;
     txn.put_cf(
         &IndexifyObjectsColumns::SystemTasks.cf_db(&db),
         &task_key,
@@ -202,7 +652,11 @@ pub fn rerun_invocation(
             true,
         )?
         .ok_or(anyhow::anyhow!("Graph context not found"))?;
-    let graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx)?;
+    let graph_ctx: GraphInvocationCtx = JsonEncoder::decode(
+        IndexifyObjectsColumns::GraphInvocationCtx,
+        &graph_ctx_key,
+        &graph_ctx,
+    )?;
     if graph_ctx.graph_version >= req.graph_version {
         tracing::info!(
             "skipping rerun of invocation: {}, already latest version of invocation context",
@@ -221,8 +675,9 @@ pub fn rerun_invocation(
         &None,
     );
     for output in outputs {
-        let (_, value) = output?;
-        let value: NodeOutput = JsonEncoder::decode(&value)?;
+        let (key, value) = output?;
+        let value: NodeOutput =
+            JsonEncoder::decode(IndexifyObjectsColumns::FnOutputs, &key, &value)?;
         if value.graph_version >= req.graph_version {
             tracing::info!(
                 "skipping rerun of invocation: {}, already latest version of outputs",
@@ -240,7 +695,11 @@ pub fn rerun_invocation(
             false,
         )?
         .ok_or(anyhow::anyhow!("Compute graph not found"))?;
-    let graph = JsonEncoder::decode::<ComputeGraph>(&graph)?;
+    let graph = JsonEncoder::decode::<ComputeGraph>(
+        IndexifyObjectsColumns::ComputeGraphs,
+        &compute_graph_key,
+        &graph,
+    )?;
     if graph.version > req.graph_version {
         // Graph was updated after rerun task was created
         return Ok(Vec::new());
@@ -254,15 +713,50 @@ pub fn rerun_invocation(
         output_key.as_bytes(),
         &None,
     );
+    let mut outputs_deleted = 0;
     for output in outputs {
         let (key, _) = output?;
         txn.delete_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(&db), key)?;
+        outputs_deleted += 1;
     }
+    counters::add(
+        &db,
+        txn,
+        &req.namespace,
+        &req.compute_graph_name,
+        Counter::DataObjects,
+        -outputs_deleted,
+    )?;
     txn.delete_cf(
         &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
         graph_ctx_key,
     )?;
 
+    // The invocation runs with the current graph from now on.
+    let invocation_key =
+        InvocationPayload::key_from(&req.namespace, &req.compute_graph_name, &req.invocation_id);
+    if let Some(invocation) = txn.get_for_update_cf(
+        &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
+        &invocation_key,
+        true,
+    )? {
+        let mut invocation = JsonEncoder::decode::<InvocationPayload>(
+            IndexifyObjectsColumns::GraphInvocations,
+            &invocation_key,
+            &invocation,
+        )?;
+        invocation.provenance = Some(invocation_provenance(&db, txn, &graph, &invocation)?);
+        txn.put_cf(
+            &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
+            &invocation_key,
+            JsonEncoder::encode(
+                IndexifyObjectsColumns::GraphInvocations,
+                &invocation_key,
+                &invocation,
+            )?,
+        )?;
+    }
+
     // Create a new invocation context after all checks passed
     let graph_invocation_ctx = GraphInvocationCtxBuilder::default()
         .namespace(req.namespace.to_string())
@@ -275,7 +769,11 @@ pub fn rerun_invocation(
     txn.put_cf(
         &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
         graph_invocation_ctx.key(),
-        &JsonEncoder::encode(&graph_invocation_ctx)?,
+        &JsonEncoder::encode(
+            IndexifyObjectsColumns::GraphInvocationCtx,
+            graph_invocation_ctx.key(),
+            &graph_invocation_ctx,
+        )?,
     )?;
 
     // Increment number of outstanding tasks
@@ -310,6 +808,28 @@ pub fn rerun_invocation(
     Ok(vec![state_change])
 }
 
+fn invocation_provenance(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    graph: &ComputeGraph,
+    invocation: &InvocationPayload,
+) -> Result<InvocationProvenance> {
+    let namespace = txn
+        .get_cf(
+            &IndexifyObjectsColumns::Namespaces.cf_db(db),
+            &invocation.namespace,
+        )?
+        .map(|value| {
+            JsonEncoder::decode::<Namespace>(
+                IndexifyObjectsColumns::Namespaces,
+                &invocation.namespace,
+                &value,
+            )
+        })
+        .transpose()?;
+    InvocationProvenance::new(graph, namespace.as_ref(), &invocation.payload)
+}
+
 pub fn create_graph_input(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
@@ -323,13 +843,94 @@ pub fn create_graph_input(
             false,
         )?
         .ok_or(anyhow::anyhow!("Compute graph not found"))?;
-    let cg: ComputeGraph = JsonEncoder::decode(&cg)?;
-    let serialized_data_object = JsonEncoder::encode(&req.invocation_payload)?;
+    let mut cg: ComputeGraph = JsonEncoder::decode(
+        IndexifyObjectsColumns::ComputeGraphs,
+        &compute_graph_key,
+        &cg,
+    )?;
+    let canary = txn
+        .get_cf(
+            &IndexifyObjectsColumns::GraphCanaries.cf_db(&db),
+            &compute_graph_key,
+        )?
+        .map(|value| {
+            JsonEncoder::decode::<GraphCanary>(
+                IndexifyObjectsColumns::GraphCanaries,
+                &compute_graph_key,
+                &value,
+            )
+        })
+        .transpose()?;
+    if let Some(version) = req.invocation_payload.graph_version {
+        let version_key =
+            ComputeGraph::version_key_from(&req.namespace, &req.compute_graph_name, version);
+        let graph = txn
+            .get_cf(
+                &IndexifyObjectsColumns::ComputeGraphVersions.cf_db(&db),
+                &version_key,
+            )?
+            .ok_or(anyhow::anyhow!(
+                "version {} of the compute graph isn't kept",
+                version
+            ))?;
+        cg = JsonEncoder::decode(
+            IndexifyObjectsColumns::ComputeGraphVersions,
+            &version_key,
+            &graph,
+        )?;
+    } else if let Some(canary) = canary {
+        if canary.routes(&req.invocation_payload.id) {
+            cg = canary.graph;
+        }
+    }
+    let mut invocation = req.invocation_payload.clone();
+    if let Some(source) = &invocation.source {
+        supersede_content(&db, txn, &invocation, source)?;
+    }
+    if let Some(fingerprint) = invocation.fingerprint {
+        check_near_duplicates(&db, txn, &mut invocation, fingerprint)?;
+    }
+    invocation.provenance = Some(invocation_provenance(&db, txn, &cg, &invocation)?);
+    let serialized_data_object = JsonEncoder::encode(
+        IndexifyObjectsColumns::GraphInvocations,
+        invocation.key(),
+        &invocation,
+    )?;
     txn.put_cf(
         &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
-        req.invocation_payload.key(),
+        invocation.key(),
         &serialized_data_object,
     )?;
+    txn.delete_cf(
+        &IndexifyObjectsColumns::UrlFetches.cf_db(&db),
+        invocation.key(),
+    )?;
+    for counter in [Counter::Invocations, Counter::DataObjects] {
+        counters::add(
+            &db,
+            txn,
+            &req.namespace,
+            &req.compute_graph_name,
+            counter,
+            1,
+        )?;
+    }
+    for tag_key in req.invocation_payload.tag_keys() {
+        txn.put_cf(
+            &IndexifyObjectsColumns::InvocationTags.cf_db(&db),
+            tag_key,
+            &[],
+        )?;
+    }
+    record_activity(
+        &db,
+        txn,
+        Some(&req.namespace),
+        Activity::InvocationStarted {
+            compute_graph: req.compute_graph_name.clone(),
+            invocation_id: invocation.id.clone(),
+        },
+    )?;
 
     let graph_invocation_ctx = GraphInvocationCtxBuilder::default()
         .namespace(req.namespace.to_string())
@@ -341,140 +942,1223 @@ pub fn create_graph_input(
     txn.put_cf(
         &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
         graph_invocation_ctx.key(),
-        &JsonEncoder::encode(&graph_invocation_ctx)?,
+        &JsonEncoder::encode(
+            IndexifyObjectsColumns::GraphInvocationCtx,
+            graph_invocation_ctx.key(),
+            &graph_invocation_ctx,
+        )?,
     )?;
     Ok(())
 }
 
-pub(crate) fn delete_input_data_object(
-    db: Arc<TransactionDB>,
-    req: &DeleteInvocationRequest,
-) -> Result<()> {
-    let mut read_options = ReadOptions::default();
-    read_options.set_readahead_size(4_194_304);
-    let prefix = format!(
-        "{}|{}|{}",
-        req.namespace, req.compute_graph, req.invocation_id
-    );
-    let iterator_mode = IteratorMode::From(prefix.as_bytes(), Direction::Forward);
-    let iter = db.iterator_cf_opt(
-        &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
-        read_options,
-        iterator_mode,
-    );
-    for key in iter {
-        let key = key?;
-        db.delete_cf(&IndexifyObjectsColumns::GraphInvocations.cf_db(&db), &key.0)?;
-    }
-
-    // FIXME - Delete the data objects which are outputs of the compute functions of
-    // the invocation
-    Ok(())
-}
-
-pub(crate) fn create_compute_graph(
-    db: Arc<TransactionDB>,
-    mut compute_graph: ComputeGraph,
+/// Records the invocation as the current version of its source. The
+/// invocation of the previous version is superseded according to the
+/// reprocessing policy of the namespace.
+fn supersede_content(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    invocation: &InvocationPayload,
+    source: &ContentSource,
 ) -> Result<()> {
-    let existing_compute_graph = db.get_cf(
-        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
-        compute_graph.key(),
-    )?;
-
-    if let Some(existing_compute_graph) = existing_compute_graph {
-        let existing_compute_graph: ComputeGraph = JsonEncoder::decode(&existing_compute_graph)?;
-        if compute_graph.code.sha256_hash != existing_compute_graph.code.sha256_hash ||
-            compute_graph.edges != existing_compute_graph.edges ||
-            compute_graph.nodes != existing_compute_graph.nodes ||
-            compute_graph.start_fn != existing_compute_graph.start_fn
-        {
-            compute_graph.version = existing_compute_graph.version.next();
+    let key = source.key(&invocation.namespace, &invocation.compute_graph_name);
+    let current = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::ContentVersions.cf_db(db),
+            &key,
+            true,
+        )?
+        .map(|value| {
+            JsonEncoder::decode::<ContentVersion>(
+                IndexifyObjectsColumns::ContentVersions,
+                &key,
+                &value,
+            )
+        })
+        .transpose()?;
+    if let Some(current) = current {
+        let current_invocation = current.invocation_id.clone();
+        match source.compare(&current.source) {
+            ContentChange::Stale => {
+                return Err(ContentVersionError::Stale {
+                    source: source.id.clone(),
+                    current_invocation,
+                }
+                .into())
+            }
+            ContentChange::Unchanged => {
+                return Err(ContentVersionError::Unchanged {
+                    source: source.id.clone(),
+                    current_invocation,
+                }
+                .into())
+            }
+            ContentChange::Newer => supersede_invocation(db, txn, invocation, &current_invocation)?,
         }
+    }
+    let version = ContentVersion {
+        invocation_id: invocation.id.clone(),
+        source: source.clone(),
     };
-
-    let serialized_compute_graph = JsonEncoder::encode(&compute_graph)?;
-    db.put_cf(
-        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
-        compute_graph.key(),
-        &serialized_compute_graph,
+    txn.put_cf(
+        &IndexifyObjectsColumns::ContentVersions.cf_db(db),
+        &key,
+        JsonEncoder::encode(IndexifyObjectsColumns::ContentVersions, &key, &version)?,
     )?;
     Ok(())
 }
 
-fn delete_cf_prefix(
+/// Compares the fingerprint of the invocation's input with the ones ingested
+/// into the graph within the window of the namespace's near-duplicate
+/// policy, dropping the ones that fell out of it. The closest near-duplicate
+/// is recorded on the invocation, or refuses it, according to the policy.
+fn check_near_duplicates(
+    db: &Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
-    cf: &impl AsColumnFamilyRef,
-    prefix: &[u8],
+    invocation: &mut InvocationPayload,
+    fingerprint: u64,
 ) -> Result<()> {
-    let mut read_options = ReadOptions::default();
-    read_options.set_readahead_size(4_194_304);
-    let iterator_mode = IteratorMode::From(prefix, Direction::Forward);
-    let iter = txn.iterator_cf_opt(cf, read_options, iterator_mode);
-    for key in iter {
-        let (key, _) = key?;
-        if !key.starts_with(prefix) {
-            break;
+    let policy = txn
+        .get_cf(
+            &IndexifyObjectsColumns::Namespaces.cf_db(db),
+            &invocation.namespace,
+        )?
+        .map(|value| {
+            JsonEncoder::decode::<Namespace>(
+                IndexifyObjectsColumns::Namespaces,
+                &invocation.namespace,
+                &value,
+            )
+        })
+        .transpose()?
+        .and_then(|ns| ns.near_duplicates);
+    let Some(policy) = policy else {
+        return Ok(());
+    };
+    let cutoff = invocation
+        .created_at
+        .saturating_sub(policy.window_secs.saturating_mul(1000));
+    let prefix =
+        ContentFingerprint::key_prefix(&invocation.namespace, &invocation.compute_graph_name);
+    let mut closest: Option<(f64, String)> = None;
+    for kv in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::ContentFingerprints.cf_db(db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (key, value) = kv?;
+        let earlier = JsonEncoder::decode::<ContentFingerprint>(
+            IndexifyObjectsColumns::ContentFingerprints,
+            &key,
+            &value,
+        )?;
+        if earlier.created_at < cutoff {
+            txn.delete_cf(&IndexifyObjectsColumns::ContentFingerprints.cf_db(db), &key)?;
+            continue;
+        }
+        let similarity = near_duplicates::similarity(fingerprint, earlier.fingerprint);
+        if similarity >= policy.threshold &&
+            closest
+                .as_ref()
+                .map_or(true, |(closest, _)| similarity > *closest)
+        {
+            closest = Some((similarity, earlier.invocation_id));
+        }
+    }
+    if let Some((similarity, current_invocation)) = closest {
+        match policy.action {
+            NearDuplicateAction::Skip => {
+                return Err(ContentVersionError::NearDuplicate {
+                    current_invocation,
+                    similarity,
+                }
+                .into())
+            }
+            NearDuplicateAction::Tag => invocation.near_duplicate_of = Some(current_invocation),
         }
-        txn.delete_cf(cf, &key)?;
     }
+    let record = ContentFingerprint {
+        namespace: invocation.namespace.clone(),
+        compute_graph: invocation.compute_graph_name.clone(),
+        invocation_id: invocation.id.clone(),
+        created_at: invocation.created_at,
+        fingerprint,
+    };
+    txn.put_cf(
+        &IndexifyObjectsColumns::ContentFingerprints.cf_db(db),
+        record.key(),
+        JsonEncoder::encode(
+            IndexifyObjectsColumns::ContentFingerprints,
+            record.key(),
+            &record,
+        )?,
+    )?;
     Ok(())
 }
 
-pub fn delete_compute_graph(
-    db: Arc<TransactionDB>,
+fn supersede_invocation(
+    db: &Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
-    namespace: &str,
-    name: &str,
+    invocation: &InvocationPayload,
+    superseded_id: &str,
 ) -> Result<()> {
-    txn.delete_cf(
-        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
-        format!("{}|{}", namespace, name),
-    )?;
-    let prefix = format!("{}|{}|", namespace, name);
-    delete_cf_prefix(
-        txn,
-        &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
-        prefix.as_bytes(),
+    let (namespace, compute_graph) = (&invocation.namespace, &invocation.compute_graph_name);
+    let key = InvocationPayload::key_from(namespace, compute_graph, superseded_id);
+    // The superseded invocation could have been deleted or archived since.
+    let Some(value) = txn.get_for_update_cf(
+        &IndexifyObjectsColumns::GraphInvocations.cf_db(db),
+        &key,
+        true,
+    )?
+    else {
+        return Ok(());
+    };
+    let mut superseded = JsonEncoder::decode::<InvocationPayload>(
+        IndexifyObjectsColumns::GraphInvocations,
+        &key,
+        &value,
     )?;
-
-    delete_cf_prefix(
-        txn,
-        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
-        prefix.as_bytes(),
+    superseded.superseded_by = Some(invocation.id.clone());
+    txn.put_cf(
+        &IndexifyObjectsColumns::GraphInvocations.cf_db(db),
+        &key,
+        JsonEncoder::encode(IndexifyObjectsColumns::GraphInvocations, &key, &superseded)?,
     )?;
 
+    let policy = txn
+        .get_cf(&IndexifyObjectsColumns::Namespaces.cf_db(db), namespace)?
+        .map(|value| {
+            JsonEncoder::decode::<Namespace>(IndexifyObjectsColumns::Namespaces, namespace, &value)
+        })
+        .transpose()?
+        .map(|ns| ns.reprocessing_policy)
+        .unwrap_or_default();
+    if policy == ReprocessingPolicy::KeepBoth {
+        return Ok(());
+    }
+    let prefix = format!("{}|", key);
+    let mut outputs_deleted = 0;
     for iter in make_prefix_iterator(
         txn,
-        &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
+        &IndexifyObjectsColumns::FnOutputs.cf_db(db),
         prefix.as_bytes(),
         &None,
     ) {
         let (key, value) = iter?;
-        let value = JsonEncoder::decode::<NodeOutput>(&value)?;
-        match &value.payload {
-            OutputPayload::Router(_) => {}
-            OutputPayload::Fn(payload) => {
-                println!("delete_compute_graph: {:?}", value.clone());
-                txn.put_cf(
-                    &IndexifyObjectsColumns::GcUrls.cf_db(&db),
-                    payload.path.as_bytes(),
-                    &[],
-                )?;
-            }
+        let output =
+            JsonEncoder::decode::<NodeOutput>(IndexifyObjectsColumns::FnOutputs, &key, &value)?;
+        if let OutputPayload::Fn(payload) = &output.payload {
+            txn.put_cf(
+                &IndexifyObjectsColumns::GcUrls.cf_db(db),
+                payload.path.as_bytes(),
+                [],
+            )?;
         }
-        txn.delete_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(&db), &key)?;
+        txn.delete_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(db), &key)?;
+        outputs_deleted += 1;
     }
-
+    counters::add(
+        db,
+        txn,
+        namespace,
+        compute_graph,
+        Counter::DataObjects,
+        -outputs_deleted,
+    )?;
     Ok(())
 }
 
-pub fn remove_gc_urls(
-    db: Arc<TransactionDB>,
+/// Deletes the tasks, outputs and context of an invocation, returns the task
+/// allocations that were cancelled.
+fn delete_invocation_data(
+    db: &Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
-    urls: Vec<String>,
-) -> Result<()> {
-    for url in urls {
-        txn.delete_cf(&IndexifyObjectsColumns::GcUrls.cf_db(&db), &url)?;
+    invocation: &InvocationPayload,
+) -> Result<Vec<(ExecutorId, TaskId)>> {
+    let key = invocation.key();
+    let prefix = format!("{}|", key);
+    let mut cancelled_allocations = Vec::new();
+    let iter = txn.iterator_cf_opt(
+        &IndexifyObjectsColumns::TaskAllocations.cf_db(db),
+        ReadOptions::default(),
+        IteratorMode::Start,
+    );
+    for kv in iter {
+        let (allocation_key, _) = kv?;
+        let task_key = String::from_utf8(Task::key_from_allocation_key(&allocation_key)?)?;
+        if !task_key.starts_with(&prefix) {
+            continue;
+        }
+        txn.delete_cf(
+            &IndexifyObjectsColumns::TaskAllocations.cf_db(db),
+            &allocation_key,
+        )?;
+        let Some(task) = txn.get_cf(&IndexifyObjectsColumns::Tasks.cf_db(db), &task_key)? else {
+            continue;
+        };
+        let task = JsonEncoder::decode::<Task>(IndexifyObjectsColumns::Tasks, &task_key, &task)?;
+        let allocation_key = String::from_utf8(allocation_key.to_vec())?;
+        if let Some((executor_id, _)) = allocation_key.split_once('|') {
+            let executor_id = ExecutorId::new(executor_id.to_string());
+            scheduler_index::update(db, txn, |index| index.remove(&executor_id, &task))?;
+            cancelled_allocations.push((executor_id, task.id.clone()));
+        }
+    }
+
+    for iter in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::Tasks.cf_db(db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (task_key, value) = iter?;
+        let task = JsonEncoder::decode::<Task>(IndexifyObjectsColumns::Tasks, &task_key, &value)?;
+        delete_cf_prefix(
+            txn,
+            &IndexifyObjectsColumns::TaskOutputs.cf_db(db),
+            format!("{}|{}|", task.namespace, task.id).as_bytes(),
+        )?;
+        txn.delete_cf(
+            &IndexifyObjectsColumns::UnallocatedTasks.cf_db(db),
+            &task_key,
+        )?;
+        txn.delete_cf(&IndexifyObjectsColumns::Tasks.cf_db(db), &task_key)?;
+        counters::add(
+            db,
+            txn,
+            &invocation.namespace,
+            &invocation.compute_graph_name,
+            Counter::for_task(&task),
+            -1,
+        )?;
+    }
+    let mut outputs_deleted = 0;
+    for iter in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::FnOutputs.cf_db(db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (output_key, value) = iter?;
+        let output = JsonEncoder::decode::<NodeOutput>(
+            IndexifyObjectsColumns::FnOutputs,
+            &output_key,
+            &value,
+        )?;
+        if let OutputPayload::Fn(payload) = &output.payload {
+            txn.put_cf(
+                &IndexifyObjectsColumns::GcUrls.cf_db(db),
+                payload.path.as_bytes(),
+                [],
+            )?;
+        }
+        txn.delete_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(db), &output_key)?;
+        outputs_deleted += 1;
+    }
+    counters::add(
+        db,
+        txn,
+        &invocation.namespace,
+        &invocation.compute_graph_name,
+        Counter::DataObjects,
+        -outputs_deleted,
+    )?;
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::ReductionTasks.cf_db(db),
+        prefix.as_bytes(),
+    )?;
+    txn.delete_cf(&IndexifyObjectsColumns::GraphInvocationCtx.cf_db(db), &key)?;
+    Ok(cancelled_allocations)
+}
+
+/// Returns the task allocations that were cancelled, those of test
+/// invocations.
+pub(crate) fn delete_input_data_object(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &DeleteInvocationRequest,
+) -> Result<Vec<(ExecutorId, TaskId)>> {
+    let mut cancelled_allocations = Vec::new();
+    let key = InvocationPayload::key_from(&req.namespace, &req.compute_graph, &req.invocation_id);
+    if let Some(value) = txn.get_for_update_cf(
+        &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
+        &key,
+        true,
+    )? {
+        let invocation = JsonEncoder::decode::<InvocationPayload>(
+            IndexifyObjectsColumns::GraphInvocations,
+            &key,
+            &value,
+        )?;
+        for tag_key in invocation.tag_keys() {
+            txn.delete_cf(&IndexifyObjectsColumns::InvocationTags.cf_db(&db), tag_key)?;
+        }
+        txn.delete_cf(&IndexifyObjectsColumns::GraphInvocations.cf_db(&db), &key)?;
+        for counter in [Counter::Invocations, Counter::DataObjects] {
+            counters::add(&db, txn, &req.namespace, &req.compute_graph, counter, -1)?;
+        }
+        // Ingesting the source again runs the graph again.
+        if let Some(source) = &invocation.source {
+            let version_key = source.key(&req.namespace, &req.compute_graph);
+            let current = txn
+                .get_for_update_cf(
+                    &IndexifyObjectsColumns::ContentVersions.cf_db(&db),
+                    &version_key,
+                    true,
+                )?
+                .map(|value| {
+                    JsonEncoder::decode::<ContentVersion>(
+                        IndexifyObjectsColumns::ContentVersions,
+                        &version_key,
+                        &value,
+                    )
+                })
+                .transpose()?;
+            if current.is_some_and(|current| current.invocation_id == invocation.id) {
+                txn.delete_cf(
+                    &IndexifyObjectsColumns::ContentVersions.cf_db(&db),
+                    &version_key,
+                )?;
+            }
+        }
+        if let Some(fingerprint) = invocation.fingerprint {
+            let record = ContentFingerprint {
+                namespace: req.namespace.clone(),
+                compute_graph: req.compute_graph.clone(),
+                invocation_id: invocation.id.clone(),
+                created_at: invocation.created_at,
+                fingerprint,
+            };
+            txn.delete_cf(
+                &IndexifyObjectsColumns::ContentFingerprints.cf_db(&db),
+                record.key(),
+            )?;
+        }
+        // Test invocations aren't kept around once deleted, and their inputs
+        // are copies made for them.
+        if invocation.test {
+            cancelled_allocations = delete_invocation_data(&db, txn, &invocation)?;
+            txn.put_cf(
+                &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+                invocation.payload.path.as_bytes(),
+                [],
+            )?;
+        }
+    }
+
+    // FIXME - Delete the data objects which are outputs of the compute functions of
+    // the invocation
+    Ok(cancelled_allocations)
+}
+
+/// Deletes a finished invocation along with its tasks, outputs and context.
+/// Its input is kept since invocations reproducing it share it. Returns
+/// whether the invocation was deleted, it isn't once it runs again.
+pub(crate) fn expire_invocation(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &DeleteInvocationRequest,
+) -> Result<bool> {
+    let key = GraphInvocationCtx::key_from(&req.namespace, &req.compute_graph, &req.invocation_id);
+    let completed = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+            &key,
+            true,
+        )?
+        .map(|value| {
+            JsonEncoder::decode::<GraphInvocationCtx>(
+                IndexifyObjectsColumns::GraphInvocationCtx,
+                &key,
+                &value,
+            )
+        })
+        .transpose()?
+        .is_some_and(|ctx| ctx.completed);
+    let invocation = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
+            &key,
+            true,
+        )?
+        .map(|value| {
+            JsonEncoder::decode::<InvocationPayload>(
+                IndexifyObjectsColumns::GraphInvocations,
+                &key,
+                &value,
+            )
+        })
+        .transpose()?;
+    let Some(invocation) = invocation.filter(|_| completed) else {
+        return Ok(false);
+    };
+    delete_input_data_object(db.clone(), txn, req)?;
+    if !invocation.test {
+        delete_invocation_data(&db, txn, &invocation)?;
+    }
+    Ok(true)
+}
+
+/// Deletes the outputs of a finished invocation its graph no longer retains
+/// and marks the tasks of their functions. Returns the number of outputs
+/// deleted, nothing is once the invocation is pinned or runs again.
+pub(crate) fn expire_outputs(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &ExpireOutputsRequest,
+) -> Result<u64> {
+    let key = GraphInvocationCtx::key_from(&req.namespace, &req.compute_graph, &req.invocation_id);
+    let ctx = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+            &key,
+            true,
+        )?
+        .map(|value| {
+            JsonEncoder::decode::<GraphInvocationCtx>(
+                IndexifyObjectsColumns::GraphInvocationCtx,
+                &key,
+                &value,
+            )
+        })
+        .transpose()?;
+    let Some(mut ctx) = ctx.filter(|ctx| ctx.completed && !ctx.outputs_pinned) else {
+        return Ok(0);
+    };
+    let outputs = req.outputs.without(&ctx.expired_outputs);
+    if !outputs.any() {
+        return Ok(0);
+    }
+    let graph = txn
+        .get_cf(
+            &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+            ComputeGraph::key_from(&req.namespace, &req.compute_graph),
+        )?
+        .map(|value| {
+            JsonEncoder::decode::<ComputeGraph>(
+                IndexifyObjectsColumns::ComputeGraphs,
+                ComputeGraph::key_from(&req.namespace, &req.compute_graph),
+                &value,
+            )
+        })
+        .transpose()?;
+    let Some(graph) = graph else {
+        return Ok(0);
+    };
+    let expires = |compute_fn: &str| {
+        if graph.is_final_fn(compute_fn) {
+            outputs.final_outputs
+        } else {
+            outputs.intermediate_outputs
+        }
+    };
+
+    let prefix = format!("{}|", key);
+    let mut outputs_deleted = 0;
+    for iter in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (output_key, value) = iter?;
+        let output = JsonEncoder::decode::<NodeOutput>(
+            IndexifyObjectsColumns::FnOutputs,
+            &output_key,
+            &value,
+        )?;
+        if !expires(&output.compute_fn_name) {
+            continue;
+        }
+        if let OutputPayload::Fn(payload) = &output.payload {
+            txn.put_cf(
+                &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+                payload.path.as_bytes(),
+                [],
+            )?;
+        }
+        txn.delete_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(&db), &output_key)?;
+        outputs_deleted += 1;
+    }
+    counters::add(
+        &db,
+        txn,
+        &req.namespace,
+        &req.compute_graph,
+        Counter::DataObjects,
+        -outputs_deleted,
+    )?;
+
+    let mut expired_tasks = Vec::new();
+    for iter in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::Tasks.cf_db(&db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (task_key, value) = iter?;
+        let task = JsonEncoder::decode::<Task>(IndexifyObjectsColumns::Tasks, &task_key, &value)?;
+        if !task.outputs_expired && expires(&task.compute_fn_name) {
+            expired_tasks.push((task_key, task));
+        }
+    }
+    for (task_key, mut task) in expired_tasks {
+        task.outputs_expired = true;
+        txn.put_cf(
+            &IndexifyObjectsColumns::Tasks.cf_db(&db),
+            &task_key,
+            JsonEncoder::encode(IndexifyObjectsColumns::Tasks, &task_key, &task)?,
+        )?;
+    }
+
+    ctx.expired_outputs.final_outputs |= outputs.final_outputs;
+    ctx.expired_outputs.intermediate_outputs |= outputs.intermediate_outputs;
+    txn.put_cf(
+        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+        &key,
+        JsonEncoder::encode(IndexifyObjectsColumns::GraphInvocationCtx, &key, &ctx)?,
+    )?;
+    Ok(outputs_deleted as u64)
+}
+
+/// Pins the outputs of an invocation, or unpins them, so that the output
+/// retention of its graph skips it. Outputs expired already stay deleted.
+pub(crate) fn pin_invocation_outputs(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &PinInvocationOutputsRequest,
+) -> Result<()> {
+    let key = GraphInvocationCtx::key_from(&req.namespace, &req.compute_graph, &req.invocation_id);
+    let mut ctx = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+            &key,
+            true,
+        )?
+        .map(|value| {
+            JsonEncoder::decode::<GraphInvocationCtx>(
+                IndexifyObjectsColumns::GraphInvocationCtx,
+                &key,
+                &value,
+            )
+        })
+        .transpose()?
+        .ok_or(anyhow!(
+            "invocation context not found: {}",
+            req.invocation_id
+        ))?;
+    ctx.outputs_pinned = req.pinned;
+    txn.put_cf(
+        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+        &key,
+        JsonEncoder::encode(IndexifyObjectsColumns::GraphInvocationCtx, &key, &ctx)?,
+    )?;
+    Ok(())
+}
+
+pub(crate) fn create_compute_graph(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    mut compute_graph: ComputeGraph,
+    precondition: Option<GraphPrecondition>,
+) -> Result<()> {
+    // Locks the graph so that concurrent writers check their precondition
+    // against the version written here.
+    let existing_compute_graph = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+            compute_graph.key(),
+            true,
+        )?
+        .map(|value| {
+            JsonEncoder::decode::<ComputeGraph>(
+                IndexifyObjectsColumns::ComputeGraphs,
+                compute_graph.key(),
+                &value,
+            )
+        })
+        .transpose()?;
+    if let Some(precondition) = precondition {
+        let current = existing_compute_graph.as_ref().map(|graph| graph.version);
+        if !precondition.holds(current) {
+            return Err(GraphVersionConflict {
+                name: compute_graph.name.clone(),
+                current,
+            }
+            .into());
+        }
+    }
+
+    if existing_compute_graph.is_some() &&
+        get_canary(&db, txn, &compute_graph.namespace, &compute_graph.name)?.is_some()
+    {
+        return Err(CanaryError::InProgress(compute_graph.name.clone()).into());
+    }
+    lock_artifacts(&db, txn, &compute_graph)?;
+
+    let registered = match &existing_compute_graph {
+        Some(existing_compute_graph) => {
+            let changed = compute_graph.definition_changed(existing_compute_graph);
+            if changed {
+                compute_graph.version =
+                    latest_graph_version(&db, txn, &compute_graph.namespace, &compute_graph.name)?
+                        .unwrap_or(existing_compute_graph.version)
+                        .max(existing_compute_graph.version)
+                        .next();
+            }
+            changed
+        }
+        None => {
+            counters::initialize(&db, txn, &compute_graph.namespace, &compute_graph.name)?;
+            orphans::exhume_graph(&db, txn, &compute_graph.namespace, &compute_graph.name)?;
+            true
+        }
+    };
+    if registered {
+        record_activity(
+            &db,
+            txn,
+            Some(&compute_graph.namespace),
+            Activity::GraphRegistered {
+                compute_graph: compute_graph.name.clone(),
+                version: compute_graph.version,
+            },
+        )?;
+    }
+
+    secondary_index::update(
+        &db,
+        txn,
+        existing_compute_graph.as_ref(),
+        Some(&compute_graph),
+    )?;
+    let serialized_compute_graph = JsonEncoder::encode(
+        IndexifyObjectsColumns::ComputeGraphs,
+        compute_graph.key(),
+        &compute_graph,
+    )?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+        compute_graph.key(),
+        &serialized_compute_graph,
+    )?;
+    put_graph_version(&db, txn, &compute_graph)?;
+    Ok(())
+}
+
+/// Keeps the definition of the graph at its version, for the invocations
+/// which run it after the graph is updated.
+pub(crate) fn put_graph_version(
+    db: &TransactionDB,
+    txn: &Transaction<TransactionDB>,
+    compute_graph: &ComputeGraph,
+) -> Result<()> {
+    txn.put_cf(
+        &IndexifyObjectsColumns::ComputeGraphVersions.cf_db(db),
+        compute_graph.version_key(),
+        JsonEncoder::encode(
+            IndexifyObjectsColumns::ComputeGraphVersions,
+            compute_graph.version_key(),
+            compute_graph,
+        )?,
+    )?;
+    Ok(())
+}
+
+/// Latest version the graph had, including the canaries rolled back since.
+fn latest_graph_version(
+    db: &TransactionDB,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    name: &str,
+) -> Result<Option<GraphVersion>> {
+    let prefix = ComputeGraph::versions_prefix(namespace, name);
+    let mut upper_bound = prefix.clone().into_bytes();
+    upper_bound.push(0xff);
+    let mut iter = txn.iterator_cf(
+        &IndexifyObjectsColumns::ComputeGraphVersions.cf_db(db),
+        IteratorMode::From(&upper_bound, Direction::Reverse),
+    );
+    match iter.next().transpose()? {
+        Some((key, value)) if key.starts_with(prefix.as_bytes()) => Ok(Some(
+            JsonEncoder::decode::<ComputeGraph>(
+                IndexifyObjectsColumns::ComputeGraphVersions,
+                &key,
+                &value,
+            )?
+            .version,
+        )),
+        _ => Ok(None),
+    }
+}
+
+/// Locks the artifacts referenced by the graph so that they can't be deleted
+/// before it's written.
+fn lock_artifacts(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    compute_graph: &ComputeGraph,
+) -> Result<()> {
+    for node in compute_graph.nodes.values() {
+        for artifact in node.artifacts() {
+            let registered = txn.get_for_update_cf(
+                &IndexifyObjectsColumns::Artifacts.cf_db(db),
+                artifact.key(&compute_graph.namespace),
+                true,
+            )?;
+            if registered.is_none() {
+                return Err(ArtifactError::Missing {
+                    compute_fn: node.name().to_string(),
+                    artifact: artifact.clone(),
+                }
+                .into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Canary rollout of the graph, locked for the rest of the transaction.
+fn get_canary(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    compute_graph: &str,
+) -> Result<Option<GraphCanary>> {
+    txn.get_for_update_cf(
+        &IndexifyObjectsColumns::GraphCanaries.cf_db(db),
+        ComputeGraph::key_from(namespace, compute_graph),
+        true,
+    )?
+    .map(|value| {
+        JsonEncoder::decode::<GraphCanary>(
+            IndexifyObjectsColumns::GraphCanaries,
+            ComputeGraph::key_from(namespace, compute_graph),
+            &value,
+        )
+    })
+    .transpose()
+}
+
+/// Updates the canary rollout of the graph, if it has one.
+fn update_canary(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    compute_graph: &str,
+    update: impl FnOnce(&mut GraphCanary),
+) -> Result<()> {
+    if let Some(mut canary) = get_canary(db, txn, namespace, compute_graph)? {
+        update(&mut canary);
+        txn.put_cf(
+            &IndexifyObjectsColumns::GraphCanaries.cf_db(db),
+            canary.key(),
+            JsonEncoder::encode(IndexifyObjectsColumns::GraphCanaries, canary.key(), &canary)?,
+        )?;
+    }
+    Ok(())
+}
+
+pub fn start_canary(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &StartCanaryRequest,
+) -> Result<()> {
+    let mut graph = req.compute_graph.clone();
+    let stable = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+            graph.key(),
+            true,
+        )?
+        .map(|value| {
+            JsonEncoder::decode::<ComputeGraph>(
+                IndexifyObjectsColumns::ComputeGraphs,
+                graph.key(),
+                &value,
+            )
+        })
+        .transpose()?
+        .ok_or(CanaryError::GraphNotFound(graph.name.clone()))?;
+    if !graph.definition_changed(&stable) {
+        return Err(CanaryError::Unchanged(graph.name.clone()).into());
+    }
+    lock_artifacts(&db, txn, &graph)?;
+    let canary = match get_canary(&db, txn, &req.namespace, &graph.name)? {
+        Some(mut canary) if !graph.definition_changed(&canary.graph) => {
+            canary.percent = req.percent;
+            canary
+        }
+        existing => {
+            // Versions aren't reused, invocations which ran a replaced
+            // rollout keep its definition.
+            let latest = existing
+                .map(|canary| canary.graph.version)
+                .into_iter()
+                .chain(latest_graph_version(&db, txn, &req.namespace, &graph.name)?)
+                .fold(stable.version, GraphVersion::max);
+            graph.version = latest.next();
+            GraphCanary::new(graph, stable.version, req.percent, get_epoch_time_in_ms())
+        }
+    };
+    txn.put_cf(
+        &IndexifyObjectsColumns::GraphCanaries.cf_db(&db),
+        canary.key(),
+        JsonEncoder::encode(IndexifyObjectsColumns::GraphCanaries, canary.key(), &canary)?,
+    )?;
+    put_graph_version(&db, txn, &canary.graph)?;
+    Ok(())
+}
+
+/// Ends the canary rollout of a graph, returns the graph promoted to the
+/// stable version if any.
+pub fn finish_canary(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &FinishCanaryRequest,
+) -> Result<Option<ComputeGraph>> {
+    let canary = get_canary(&db, txn, &req.namespace, &req.compute_graph)?
+        .ok_or(CanaryError::NotFound(req.compute_graph.clone()))?;
+    txn.delete_cf(
+        &IndexifyObjectsColumns::GraphCanaries.cf_db(&db),
+        canary.key(),
+    )?;
+    if !req.promote {
+        return Ok(None);
+    }
+    let stable = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+            canary.graph.key(),
+            true,
+        )?
+        .map(|value| {
+            JsonEncoder::decode::<ComputeGraph>(
+                IndexifyObjectsColumns::ComputeGraphs,
+                canary.graph.key(),
+                &value,
+            )
+        })
+        .transpose()?;
+    secondary_index::update(&db, txn, stable.as_ref(), Some(&canary.graph))?;
+    record_activity(
+        &db,
+        txn,
+        Some(&req.namespace),
+        Activity::GraphRegistered {
+            compute_graph: canary.graph.name.clone(),
+            version: canary.graph.version,
+        },
+    )?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+        canary.graph.key(),
+        JsonEncoder::encode(
+            IndexifyObjectsColumns::ComputeGraphs,
+            canary.graph.key(),
+            &canary.graph,
+        )?,
+    )?;
+    put_graph_version(&db, txn, &canary.graph)?;
+    Ok(Some(canary.graph))
+}
+
+pub(crate) fn delete_cf_prefix(
+    txn: &Transaction<TransactionDB>,
+    cf: &impl AsColumnFamilyRef,
+    prefix: &[u8],
+) -> Result<()> {
+    let mut read_options = ReadOptions::default();
+    read_options.set_readahead_size(4_194_304);
+    let iterator_mode = IteratorMode::From(prefix, Direction::Forward);
+    let iter = txn.iterator_cf_opt(cf, read_options, iterator_mode);
+    for key in iter {
+        let (key, _) = key?;
+        if !key.starts_with(prefix) {
+            break;
+        }
+        txn.delete_cf(cf, &key)?;
+    }
+    Ok(())
+}
+
+pub fn delete_compute_graph(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    name: &str,
+) -> Result<()> {
+    let compute_graph = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+            ComputeGraph::key_from(namespace, name),
+            true,
+        )?
+        .map(|value| {
+            JsonEncoder::decode::<ComputeGraph>(
+                IndexifyObjectsColumns::ComputeGraphs,
+                ComputeGraph::key_from(namespace, name),
+                &value,
+            )
+        })
+        .transpose()?;
+    secondary_index::update(&db, txn, compute_graph.as_ref(), None)?;
+    txn.delete_cf(
+        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+        format!("{}|{}", namespace, name),
+    )?;
+    txn.delete_cf(
+        &IndexifyObjectsColumns::GraphCanaries.cf_db(&db),
+        ComputeGraph::key_from(namespace, name),
+    )?;
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::ComputeGraphVersions.cf_db(&db),
+        ComputeGraph::versions_prefix(namespace, name).as_bytes(),
+    )?;
+    let prefix = format!("{}|{}|", namespace, name);
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::GraphInvocations.cf_db(&db),
+        prefix.as_bytes(),
+    )?;
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::ContentVersions.cf_db(&db),
+        prefix.as_bytes(),
+    )?;
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::ContentFingerprints.cf_db(&db),
+        prefix.as_bytes(),
+    )?;
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::UrlFetches.cf_db(&db),
+        prefix.as_bytes(),
+    )?;
+
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::InvocationTags.cf_db(&db),
+        prefix.as_bytes(),
+    )?;
+
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+        prefix.as_bytes(),
+    )?;
+
+    txn.delete_cf(
+        &IndexifyObjectsColumns::ResourceUsage.cf_db(&db),
+        ResourceUsageStats::graph_key(namespace, name),
+    )?;
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::ResourceUsage.cf_db(&db),
+        prefix.as_bytes(),
+    )?;
+
+    for iter in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (key, value) = iter?;
+        let value =
+            JsonEncoder::decode::<NodeOutput>(IndexifyObjectsColumns::FnOutputs, &key, &value)?;
+        match &value.payload {
+            OutputPayload::Router(_) => {}
+            OutputPayload::Fn(payload) => {
+                println!("delete_compute_graph: {:?}", value.clone());
+                txn.put_cf(
+                    &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+                    payload.path.as_bytes(),
+                    &[],
+                )?;
+            }
+        }
+        txn.delete_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(&db), &key)?;
+    }
+
+    for iter in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::QuarantinedOutputs.cf_db(&db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (key, value) = iter?;
+        let quarantined = JsonEncoder::decode::<QuarantinedOutput>(
+            IndexifyObjectsColumns::QuarantinedOutputs,
+            &key,
+            &value,
+        )?;
+        if let OutputPayload::Fn(payload) = &quarantined.output.payload {
+            txn.put_cf(
+                &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+                payload.path.as_bytes(),
+                [],
+            )?;
+        }
+        txn.delete_cf(&IndexifyObjectsColumns::QuarantinedOutputs.cf_db(&db), &key)?;
+    }
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::OutputCheckStats.cf_db(&db),
+        prefix.as_bytes(),
+    )?;
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::Stats.cf_db(&db),
+        Counter::graph_prefix(namespace, name).as_bytes(),
+    )?;
+    // The graph's tasks are dropped by compaction, see `orphans`.
+    orphans::bury_graph(&db, txn, namespace, name)?;
+
+    Ok(())
+}
+
+/// Removes the records of a finished invocation once they have been written
+/// to its archive, keeping only the archive's index entry. Blobs referenced by
+/// the outputs are left in place since the archive points at them.
+pub fn archive_invocation(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &ArchiveInvocationRequest,
+) -> Result<()> {
+    let archive = &req.archive;
+    let key = GraphInvocationCtx::key_from(
+        &archive.namespace,
+        &archive.compute_graph_name,
+        &archive.invocation_id,
+    );
+    let ctx = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+            &key,
+            true,
+        )?
+        .ok_or(anyhow!("invocation ctx not found: {}", key))?;
+    let ctx = JsonEncoder::decode::<GraphInvocationCtx>(
+        IndexifyObjectsColumns::GraphInvocationCtx,
+        &key,
+        &ctx,
+    )?;
+    if !ctx.completed {
+        return Err(anyhow!("invocation {} is not completed", key));
+    }
+    txn.delete_cf(&IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db), &key)?;
+
+    if let Some(invocation) =
+        txn.get_cf(&IndexifyObjectsColumns::GraphInvocations.cf_db(&db), &key)?
+    {
+        let invocation = JsonEncoder::decode::<InvocationPayload>(
+            IndexifyObjectsColumns::GraphInvocations,
+            &key,
+            &invocation,
+        )?;
+        for tag_key in invocation.tag_keys() {
+            txn.delete_cf(&IndexifyObjectsColumns::InvocationTags.cf_db(&db), tag_key)?;
+        }
+        txn.delete_cf(&IndexifyObjectsColumns::GraphInvocations.cf_db(&db), &key)?;
+        for counter in [Counter::Invocations, Counter::DataObjects] {
+            counters::add(
+                &db,
+                txn,
+                &archive.namespace,
+                &archive.compute_graph_name,
+                counter,
+                -1,
+            )?;
+        }
+    }
+
+    let prefix = format!("{}|", key);
+    for iter in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::Tasks.cf_db(&db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (key, value) = iter?;
+        let task = JsonEncoder::decode::<Task>(IndexifyObjectsColumns::Tasks, &key, &value)?;
+        delete_cf_prefix(
+            txn,
+            &IndexifyObjectsColumns::TaskOutputs.cf_db(&db),
+            format!("{}|{}|", task.namespace, task.id).as_bytes(),
+        )?;
+        txn.delete_cf(&IndexifyObjectsColumns::Tasks.cf_db(&db), &key)?;
+        counters::add(
+            &db,
+            txn,
+            &archive.namespace,
+            &archive.compute_graph_name,
+            Counter::for_task(&task),
+            -1,
+        )?;
+    }
+    let mut outputs_deleted = 0;
+    for iter in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (key, _) = iter?;
+        txn.delete_cf(&IndexifyObjectsColumns::FnOutputs.cf_db(&db), &key)?;
+        outputs_deleted += 1;
+    }
+    counters::add(
+        &db,
+        txn,
+        &archive.namespace,
+        &archive.compute_graph_name,
+        Counter::DataObjects,
+        -outputs_deleted,
+    )?;
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::ReductionTasks.cf_db(&db),
+        prefix.as_bytes(),
+    )?;
+
+    txn.put_cf(
+        &IndexifyObjectsColumns::ArchivedInvocations.cf_db(&db),
+        archive.key(),
+        JsonEncoder::encode(
+            IndexifyObjectsColumns::ArchivedInvocations,
+            archive.key(),
+            archive,
+        )?,
+    )?;
+    Ok(())
+}
+
+pub fn add_gc_urls(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    urls: &[String],
+) -> Result<()> {
+    for url in urls {
+        txn.put_cf(
+            &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+            url.as_bytes(),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn remove_gc_urls(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    urls: Vec<String>,
+) -> Result<()> {
+    for url in urls {
+        txn.delete_cf(&IndexifyObjectsColumns::GcUrls.cf_db(&db), &url)?;
     }
     Ok(())
 }
@@ -509,7 +2193,8 @@ pub(crate) fn processed_reduction_tasks(
 ) -> Result<()> {
     let cf = &IndexifyObjectsColumns::ReductionTasks.cf_db(&db);
     for task in &task.new_reduction_tasks {
-        let serialized_task = JsonEncoder::encode(&task)?;
+        let serialized_task =
+            JsonEncoder::encode(IndexifyObjectsColumns::ReductionTasks, task.key(), &task)?;
         txn.put_cf(cf, task.key(), &serialized_task)?;
     }
     for key in &task.processed_reduction_tasks {
@@ -542,9 +2227,14 @@ pub(crate) fn create_tasks(
     if graph_ctx.is_none() {
         error!("Graph context not found for graph: {}", req.compute_graph);
     }
-    let mut graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx.unwrap())?;
+    let mut graph_ctx: GraphInvocationCtx = JsonEncoder::decode(
+        IndexifyObjectsColumns::GraphInvocationCtx,
+        &ctx_key,
+        &graph_ctx.unwrap(),
+    )?;
     for task in &req.tasks {
-        let serialized_task = JsonEncoder::encode(&task)?;
+        let serialized_task =
+            JsonEncoder::encode(IndexifyObjectsColumns::Tasks, task.key(), &task)?;
         txn.put_cf(
             &IndexifyObjectsColumns::Tasks.cf_db(&db),
             task.key(),
@@ -563,9 +2253,21 @@ pub(crate) fn create_tasks(
         analytics.pending();
     }
     graph_ctx.outstanding_tasks += req.tasks.len() as u64;
+    counters::add(
+        &db,
+        txn,
+        &req.namespace,
+        &req.compute_graph,
+        Counter::PendingTasks,
+        req.tasks.len() as i64,
+    )?;
     // Subtract reference for completed state change event
     graph_ctx.outstanding_tasks -= 1;
-    let serialized_analytics = JsonEncoder::encode(&graph_ctx)?;
+    let serialized_analytics = JsonEncoder::encode(
+        IndexifyObjectsColumns::GraphInvocationCtx,
+        &ctx_key,
+        &graph_ctx,
+    )?;
     txn.put_cf(
         &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
         ctx_key,
@@ -595,6 +2297,16 @@ pub fn allocate_tasks(
         task.make_allocation_key(executor_id),
         &[],
     )?;
+    scheduler_index::update(&db, txn, |index| index.add(executor_id, task))?;
+    // The device a task was packed onto and the external dependency it counts
+    // against are only recorded once it's placed.
+    if task.gpu_allocation.is_some() || task.external_dependency.is_some() {
+        txn.put_cf(
+            &IndexifyObjectsColumns::Tasks.cf_db(&db),
+            task.key(),
+            JsonEncoder::encode(IndexifyObjectsColumns::Tasks, task.key(), task)?,
+        )?;
+    }
     txn.delete_cf(
         &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
         task.key(),
@@ -602,11 +2314,271 @@ pub fn allocate_tasks(
     Ok(())
 }
 
+/// Returns a task rejected by its executor to the unallocated tasks, recording
+/// the rejection so that it isn't placed on the same executor again. Returns
+/// true instead when every executor able to run the task has rejected it for
+/// good, leaving the task for the caller to fail.
+pub fn reject_task(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &RejectTaskRequest,
+) -> Result<bool> {
+    let task_key = format!(
+        "{}|{}|{}|{}|{}",
+        req.namespace, req.compute_graph, req.invocation_id, req.compute_fn, req.task_id
+    );
+    let task = txn
+        .get_for_update_cf(&IndexifyObjectsColumns::Tasks.cf_db(&db), &task_key, true)?
+        .ok_or(TaskRejectionError::TaskNotFound(req.task_id.clone()))?;
+    let mut task = JsonEncoder::decode::<Task>(IndexifyObjectsColumns::Tasks, &task_key, &task)?;
+    let allocation_key = task.make_allocation_key(&req.executor_id);
+    if txn
+        .get_cf(
+            &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+            &allocation_key,
+        )?
+        .is_none()
+    {
+        return Err(
+            TaskRejectionError::NotAllocated(req.task_id.clone(), req.executor_id.clone()).into(),
+        );
+    }
+    txn.delete_cf(
+        &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+        &allocation_key,
+    )?;
+    scheduler_index::update(&db, txn, |index| index.remove(&req.executor_id, &task))?;
+    task.gpu_allocation = None;
+    task.rejections.push(TaskRejection {
+        executor_id: req.executor_id.clone(),
+        reason: req.reason.clone(),
+        message: req.message.clone(),
+        rejected_at: get_epoch_time_in_ms(),
+    });
+    txn.put_cf(
+        &IndexifyObjectsColumns::Tasks.cf_db(&db),
+        &task_key,
+        JsonEncoder::encode(IndexifyObjectsColumns::Tasks, &task_key, &task)?,
+    )?;
+    if rejected_by_all(&db, txn, &task)? {
+        return Ok(true);
+    }
+    txn.put_cf(
+        &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
+        &task_key,
+        &[],
+    )?;
+    Ok(false)
+}
+
+fn rejected_by_all(
+    db: &TransactionDB,
+    txn: &Transaction<TransactionDB>,
+    task: &Task,
+) -> Result<bool> {
+    let mut graph = None;
+    for (column, key) in [
+        (
+            IndexifyObjectsColumns::ComputeGraphVersions,
+            ComputeGraph::version_key_from(
+                &task.namespace,
+                &task.compute_graph_name,
+                task.graph_version,
+            ),
+        ),
+        (
+            IndexifyObjectsColumns::ComputeGraphs,
+            ComputeGraph::key_from(&task.namespace, &task.compute_graph_name),
+        ),
+    ] {
+        if let Some(value) = txn.get_cf(&column.cf_db(db), &key)? {
+            graph = Some(JsonEncoder::decode::<ComputeGraph>(column, &key, &value)?);
+            break;
+        }
+    }
+    let Some(graph) = graph else {
+        return Ok(false);
+    };
+    let Some(node) = graph.nodes.get(&task.compute_fn_name) else {
+        return Ok(false);
+    };
+    let mut executors = Vec::new();
+    for iter in txn.iterator_cf(
+        &IndexifyObjectsColumns::Executors.cf_db(db),
+        IteratorMode::Start,
+    ) {
+        let (key, value) = iter?;
+        executors.push(JsonEncoder::decode::<ExecutorMetadata>(
+            IndexifyObjectsColumns::Executors,
+            &key,
+            &value,
+        )?);
+    }
+    Ok(task.rejected_by_all(node, &executors))
+}
+
+/// Applies an operator's intervention to an unfinished task and records it in
+/// the audit log. Returns the executor the task was allocated to, whose
+/// allocation is removed unless only the priority changes. Failing the task
+/// is completed by finalizing it.
+pub(crate) fn intervene_task(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &InterveneTaskRequest,
+) -> Result<Option<ExecutorId>> {
+    let task_key = format!(
+        "{}|{}|{}|{}|{}",
+        req.namespace, req.compute_graph, req.invocation_id, req.compute_fn, req.task_id
+    );
+    let task = txn
+        .get_for_update_cf(&IndexifyObjectsColumns::Tasks.cf_db(&db), &task_key, true)?
+        .ok_or(TaskInterventionError::TaskNotFound(req.task_id.clone()))?;
+    let mut task = JsonEncoder::decode::<Task>(IndexifyObjectsColumns::Tasks, &task_key, &task)?;
+    if task.terminal_state() {
+        return Err(TaskInterventionError::TaskFinished(req.task_id.clone()).into());
+    }
+    let mut allocated_to = None;
+    for iter in txn.iterator_cf(
+        &IndexifyObjectsColumns::Executors.cf_db(&db),
+        IteratorMode::Start,
+    ) {
+        let (key, _) = iter?;
+        let executor_id = ExecutorId::new(String::from_utf8(key.to_vec())?);
+        if txn
+            .get_cf(
+                &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+                task.make_allocation_key(&executor_id),
+            )?
+            .is_some()
+        {
+            allocated_to = Some(executor_id);
+            break;
+        }
+    }
+    if let TaskIntervention::Assign { executor_id } = &req.intervention {
+        let registered = txn.get_cf(
+            &IndexifyObjectsColumns::Executors.cf_db(&db),
+            executor_id.get(),
+        )?;
+        if registered.is_none() {
+            return Err(TaskInterventionError::ExecutorNotFound(executor_id.clone()).into());
+        }
+    }
+
+    match &req.intervention {
+        TaskIntervention::SetPriority { priority } => {
+            task.priority = *priority;
+        }
+        _ => {
+            if let Some(executor_id) = &allocated_to {
+                txn.delete_cf(
+                    &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+                    task.make_allocation_key(executor_id),
+                )?;
+                scheduler_index::update(&db, txn, |index| index.remove(executor_id, &task))?;
+            }
+            task.gpu_allocation = None;
+            task.external_dependency = None;
+        }
+    }
+    txn.put_cf(
+        &IndexifyObjectsColumns::Tasks.cf_db(&db),
+        &task_key,
+        JsonEncoder::encode(IndexifyObjectsColumns::Tasks, &task_key, &task)?,
+    )?;
+    match &req.intervention {
+        TaskIntervention::Requeue => {
+            txn.put_cf(
+                &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
+                &task_key,
+                [],
+            )?;
+        }
+        TaskIntervention::Assign { executor_id } => {
+            allocate_tasks(db.clone(), txn, &task, executor_id)?;
+        }
+        TaskIntervention::Fail => {
+            txn.delete_cf(
+                &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
+                &task_key,
+            )?;
+        }
+        TaskIntervention::SetPriority { .. } => {}
+    }
+
+    let entry = AuditLogEntry {
+        id: new_id(),
+        namespace: req.namespace.clone(),
+        recorded_at: get_epoch_time_in_ms(),
+        compute_graph: req.compute_graph.clone(),
+        invocation_id: req.invocation_id.clone(),
+        compute_fn: req.compute_fn.clone(),
+        task_id: req.task_id.clone(),
+        intervention: req.intervention.clone(),
+        executor_id: allocated_to.clone(),
+        reason: req.reason.clone(),
+    };
+    txn.put_cf(
+        &IndexifyObjectsColumns::AuditLog.cf_db(&db),
+        entry.key(),
+        JsonEncoder::encode(IndexifyObjectsColumns::AuditLog, entry.key(), &entry)?,
+    )?;
+    Ok(allocated_to)
+}
+
+/// Key in `StateMachineMetadata` of the last sequence number acknowledging a
+/// task result.
+const RESULT_SEQUENCE_KEY: &[u8] = b"result_sequence";
+
+fn next_result_sequence(db: &Arc<TransactionDB>, txn: &Transaction<TransactionDB>) -> Result<u64> {
+    let cf = IndexifyObjectsColumns::StateMachineMetadata.cf_db(db);
+    let sequence = match txn.get_for_update_cf(&cf, RESULT_SEQUENCE_KEY, true)? {
+        Some(sequence) => {
+            JsonEncoder::decode::<u64>(
+                IndexifyObjectsColumns::StateMachineMetadata,
+                RESULT_SEQUENCE_KEY,
+                &sequence,
+            )? + 1
+        }
+        None => 1,
+    };
+    txn.put_cf(
+        &cf,
+        RESULT_SEQUENCE_KEY,
+        JsonEncoder::encode(
+            IndexifyObjectsColumns::StateMachineMetadata,
+            RESULT_SEQUENCE_KEY,
+            &sequence,
+        )?,
+    )?;
+    Ok(sequence)
+}
+
+/// Whether the result of the task was already committed, executors report it
+/// again when they didn't get the acknowledgement.
+pub fn task_result_acknowledged(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &FinalizeTaskRequest,
+) -> Result<bool> {
+    let task_key = format!(
+        "{}|{}|{}|{}|{}",
+        req.namespace, req.compute_graph, req.invocation_id, req.compute_fn, req.task_id
+    );
+    let task = txn.get_for_update_cf(&IndexifyObjectsColumns::Tasks.cf_db(&db), &task_key, true)?;
+    Ok(match task {
+        Some(task) => JsonEncoder::decode::<Task>(IndexifyObjectsColumns::Tasks, &task_key, &task)?
+            .result_sequence
+            .is_some(),
+        None => false,
+    })
+}
+
 pub fn mark_task_completed(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
     req: FinalizeTaskRequest,
-) -> Result<()> {
+) -> Result<Task> {
     let task_key = format!(
         "{}|{}|{}|{}|{}",
         req.namespace, req.compute_graph, req.invocation_id, req.compute_fn, req.task_id
@@ -614,7 +2586,7 @@ pub fn mark_task_completed(
     let task = txn
         .get_cf(&IndexifyObjectsColumns::Tasks.cf_db(&db), &task_key)?
         .ok_or(anyhow!("Task not found: {}", &req.task_id))?;
-    let mut task = JsonEncoder::decode::<Task>(&task)?;
+    let mut task = JsonEncoder::decode::<Task>(IndexifyObjectsColumns::Tasks, &task_key, &task)?;
     let graph_ctx_key = format!(
         "{}|{}|{}",
         req.namespace, req.compute_graph, req.invocation_id
@@ -629,14 +2601,27 @@ pub fn mark_task_completed(
             "Graph context not found for task: {}",
             &req.task_id
         ))?;
-    let mut graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx)?;
+    let mut graph_ctx: GraphInvocationCtx = JsonEncoder::decode(
+        IndexifyObjectsColumns::GraphInvocationCtx,
+        &graph_ctx_key,
+        &graph_ctx,
+    )?;
+    counters::add(
+        &db,
+        txn,
+        &req.namespace,
+        &req.compute_graph,
+        Counter::DataObjects,
+        req.node_outputs.len() as i64,
+    )?;
     for mut output in req.node_outputs {
         // Update with correct graph version
         output.graph_version = graph_ctx.graph_version;
 
-        let serialized_output = JsonEncoder::encode(&output)?;
         // Create an output key
         let output_key = output.key(&req.invocation_id);
+        let serialized_output =
+            JsonEncoder::encode(IndexifyObjectsColumns::FnOutputs, &output_key, &output)?;
         txn.put_cf(
             &IndexifyObjectsColumns::FnOutputs.cf_db(&db),
             &output_key,
@@ -646,42 +2631,344 @@ pub fn mark_task_completed(
         // Create a key to store the pointer to the node output to the task
         // NS_TASK_ID_<OutputID> -> Output Key
         let task_output_key = task.key_output(&output.id);
-        let node_output_id = JsonEncoder::encode(&output_key)?;
+        let node_output_id = JsonEncoder::encode(
+            IndexifyObjectsColumns::TaskOutputs,
+            &task_output_key,
+            &output_key,
+        )?;
+        txn.put_cf(
+            &IndexifyObjectsColumns::TaskOutputs.cf_db(&db),
+            task_output_key,
+            node_output_id,
+        )?;
+    }
+    let analytics = graph_ctx
+        .fn_task_analytics
+        .entry(req.compute_fn.to_string())
+        .or_insert_with(|| TaskAnalytics::default());
+    match req.task_outcome {
+        data_model::TaskOutcome::Success => analytics.success(),
+        data_model::TaskOutcome::Failure => analytics.fail(),
+        _ => {}
+    }
+    let serialized_analytics = JsonEncoder::encode(
+        IndexifyObjectsColumns::GraphInvocationCtx,
+        &graph_ctx_key,
+        &graph_ctx,
+    )?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
+        graph_ctx_key,
+        serialized_analytics,
+    )?;
+
+    let allocation_key = task.make_allocation_key(&req.executor_id);
+    if txn
+        .get_cf(
+            &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+            &allocation_key,
+        )?
+        .is_some()
+    {
+        txn.delete_cf(
+            &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
+            &allocation_key,
+        )?;
+        scheduler_index::update(&db, txn, |index| index.remove(&req.executor_id, &task))?;
+    }
+
+    if let Some(usage) = req
+        .diagnostics
+        .as_ref()
+        .and_then(|diagnostics| diagnostics.resource_usage.as_ref())
+    {
+        let graph_key = ResourceUsageStats::graph_key(&req.namespace, &req.compute_graph);
+        let fn_key =
+            ResourceUsageStats::fn_key(&req.namespace, &req.compute_graph, &req.compute_fn);
+        for key in [graph_key, fn_key] {
+            record_resource_usage(db.clone(), txn, &key, usage)?;
+        }
+    }
+
+    if let Some(report) = &req.output_checks {
+        for quarantined in &report.quarantined_outputs {
+            let mut quarantined = quarantined.clone();
+            quarantined.output.graph_version = graph_ctx.graph_version;
+            txn.put_cf(
+                &IndexifyObjectsColumns::QuarantinedOutputs.cf_db(&db),
+                quarantined.key(),
+                JsonEncoder::encode(
+                    IndexifyObjectsColumns::QuarantinedOutputs,
+                    quarantined.key(),
+                    &quarantined,
+                )?,
+            )?;
+        }
+        let cf = IndexifyObjectsColumns::OutputCheckStats.cf_db(&db);
+        let key = OutputCheckStats::key(&req.namespace, &req.compute_graph, &req.compute_fn);
+        let mut stats = match txn.get_for_update_cf(&cf, &key, true)? {
+            Some(stats) => JsonEncoder::decode::<OutputCheckStats>(
+                IndexifyObjectsColumns::OutputCheckStats,
+                &key,
+                &stats,
+            )?,
+            None => OutputCheckStats::default(),
+        };
+        stats.outputs_checked += report.outputs_checked;
+        for violation in &report.violations {
+            *stats.violations.entry(violation.check.clone()).or_default() += 1;
+        }
+        stats.quarantined_outputs += report.quarantined_outputs.len() as u64;
+        if report
+            .violations
+            .iter()
+            .any(|v| v.action == OutputCheckAction::FailTask)
+        {
+            stats.failed_tasks += 1;
+        }
+        txn.put_cf(
+            &cf,
+            &key,
+            JsonEncoder::encode(IndexifyObjectsColumns::OutputCheckStats, &key, &stats)?,
+        )?;
+    }
+
+    task.diagnostics = req.diagnostics.clone();
+    task.result_sequence = Some(next_result_sequence(&db, txn)?);
+
+    let counted_as = Counter::for_task(&task);
+    task.outcome = req.task_outcome.clone();
+    if Counter::for_task(&task) != counted_as {
+        counters::add(&db, txn, &req.namespace, &req.compute_graph, counted_as, -1)?;
+        counters::add(
+            &db,
+            txn,
+            &req.namespace,
+            &req.compute_graph,
+            Counter::for_task(&task),
+            1,
+        )?;
+    }
+    let task_bytes = JsonEncoder::encode(IndexifyObjectsColumns::Tasks, task.key(), &task)?;
+    txn.put_cf(
+        &IndexifyObjectsColumns::Tasks.cf_db(&db),
+        task.key(),
+        task_bytes,
+    )?;
+    let created_at = task
+        .creation_time
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default();
+    let duration_ms = get_epoch_time_in_ms().saturating_sub(created_at);
+    update_canary(&db, txn, &req.namespace, &req.compute_graph, |canary| {
+        if let Some(stats) = canary.stats_mut(graph_ctx.graph_version) {
+            stats.task_finished(&req.compute_fn, &task.outcome, duration_ms);
+        }
+    })?;
+    Ok(task)
+}
+
+fn put_analytics_record(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    record: &AnalyticsRecord,
+) -> Result<()> {
+    // Ids are monotonic, records are exported in the order they were made.
+    let key = new_id();
+    txn.put_cf(
+        &IndexifyObjectsColumns::AnalyticsRecords.cf_db(db),
+        &key,
+        JsonEncoder::encode(IndexifyObjectsColumns::AnalyticsRecords, &key, record)?,
+    )?;
+    Ok(())
+}
+
+/// Records a finished task for the analytics export.
+pub fn record_task_analytics(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    task: &Task,
+    executor_id: &ExecutorId,
+) -> Result<()> {
+    let record = TaskRecord::new(task, executor_id, get_epoch_time_in_ms());
+    put_analytics_record(&db, txn, &AnalyticsRecord::Task(record))
+}
+
+/// Records a finished invocation for the analytics export.
+pub fn record_invocation_analytics(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    compute_graph: &str,
+    invocation_id: &str,
+) -> Result<()> {
+    let key = InvocationPayload::key_from(namespace, compute_graph, invocation_id);
+    let invocation = txn
+        .get_cf(&IndexifyObjectsColumns::GraphInvocations.cf_db(&db), &key)?
+        .ok_or(anyhow!("invocation not found: {}", invocation_id))?;
+    let invocation: InvocationPayload =
+        JsonEncoder::decode(IndexifyObjectsColumns::GraphInvocations, &key, &invocation)?;
+    let ctx = txn
+        .get_cf(&IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db), &key)?
+        .ok_or(anyhow!("invocation context not found: {}", invocation_id))?;
+    let ctx: GraphInvocationCtx =
+        JsonEncoder::decode(IndexifyObjectsColumns::GraphInvocationCtx, &key, &ctx)?;
+    let finished_at = get_epoch_time_in_ms();
+    let record = InvocationRecord {
+        namespace: namespace.to_string(),
+        compute_graph: compute_graph.to_string(),
+        graph_version: ctx.graph_version,
+        invocation_id: invocation_id.to_string(),
+        created_at: invocation.created_at,
+        finished_at,
+        duration_ms: finished_at.saturating_sub(invocation.created_at),
+        successful_tasks: ctx
+            .fn_task_analytics
+            .values()
+            .map(|a| a.successful_tasks)
+            .sum(),
+        failed_tasks: ctx.fn_task_analytics.values().map(|a| a.failed_tasks).sum(),
+    };
+    put_analytics_record(&db, txn, &AnalyticsRecord::Invocation(record))
+}
+
+pub fn remove_analytics_records(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    ids: &[String],
+) -> Result<()> {
+    for id in ids {
+        txn.delete_cf(&IndexifyObjectsColumns::AnalyticsRecords.cf_db(&db), id)?;
+    }
+    Ok(())
+}
+
+/// Raises the alerts of a finished task, for the graphs with owners, or for
+/// every graph when alerts without owners go to a default channel.
+pub fn raise_task_alerts(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &FinalizeTaskRequest,
+    default_channel: bool,
+) -> Result<()> {
+    let graph_key = ComputeGraph::key_from(&req.namespace, &req.compute_graph);
+    let Some(graph) = txn.get_cf(
+        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+        &graph_key,
+    )?
+    else {
+        return Ok(());
+    };
+    if !default_channel &&
+        JsonEncoder::decode::<ComputeGraph>(
+            IndexifyObjectsColumns::ComputeGraphs,
+            &graph_key,
+            &graph,
+        )?
+        .owners
+        .is_empty()
+    {
+        return Ok(());
+    }
+    let mut problems = vec![];
+    if req.task_outcome == data_model::TaskOutcome::Failure {
+        // Retries may still get the invocation through.
+        let retryable = req
+            .diagnostics
+            .as_ref()
+            .and_then(|diagnostics| diagnostics.failure.as_ref())
+            .is_some_and(|failure| failure.retryable);
+        problems.push((
+            if retryable {
+                Severity::Warning
+            } else {
+                Severity::Critical
+            },
+            Problem::TaskFailed {
+                invocation_id: req.invocation_id.clone(),
+                compute_fn: req.compute_fn.clone(),
+                task_id: req.task_id.clone(),
+            },
+        ));
+    }
+    let violations = req
+        .output_checks
+        .as_ref()
+        .map_or(0, |report| report.violations.len());
+    if violations > 0 {
+        problems.push((
+            Severity::Warning,
+            Problem::OutputChecksViolated {
+                invocation_id: req.invocation_id.clone(),
+                compute_fn: req.compute_fn.clone(),
+                task_id: req.task_id.clone(),
+                violations,
+            },
+        ));
+    }
+    for (severity, problem) in problems {
+        let alert = Alert {
+            id: new_id(),
+            namespace: req.namespace.clone(),
+            compute_graph: req.compute_graph.clone(),
+            severity,
+            raised_at: get_epoch_time_in_ms(),
+            problem,
+        };
         txn.put_cf(
-            &IndexifyObjectsColumns::TaskOutputs.cf_db(&db),
-            task_output_key,
-            node_output_id,
+            &IndexifyObjectsColumns::Alerts.cf_db(&db),
+            &alert.id,
+            JsonEncoder::encode(IndexifyObjectsColumns::Alerts, &alert.id, &alert)?,
         )?;
     }
-    let analytics = graph_ctx
-        .fn_task_analytics
-        .entry(req.compute_fn.to_string())
-        .or_insert_with(|| TaskAnalytics::default());
-    match req.task_outcome {
-        data_model::TaskOutcome::Success => analytics.success(),
-        data_model::TaskOutcome::Failure => analytics.fail(),
-        _ => {}
-    }
-    let serialized_analytics = JsonEncoder::encode(&graph_ctx)?;
-    txn.put_cf(
-        &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
-        graph_ctx_key,
-        serialized_analytics,
-    )?;
+    Ok(())
+}
 
-    txn.delete_cf(
-        &IndexifyObjectsColumns::TaskAllocations.cf_db(&db),
-        &task.make_allocation_key(&req.executor_id),
+pub fn put_url_fetch(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    fetch: &UrlFetch,
+) -> Result<()> {
+    txn.put_cf(
+        &IndexifyObjectsColumns::UrlFetches.cf_db(&db),
+        fetch.key(),
+        JsonEncoder::encode(IndexifyObjectsColumns::UrlFetches, fetch.key(), fetch)?,
     )?;
+    Ok(())
+}
 
-    task.diagnostics = req.diagnostics.clone();
+pub fn remove_alerts(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    ids: &[String],
+) -> Result<()> {
+    for id in ids {
+        txn.delete_cf(&IndexifyObjectsColumns::Alerts.cf_db(&db), id)?;
+    }
+    Ok(())
+}
 
-    task.outcome = req.task_outcome.clone();
-    let task_bytes = JsonEncoder::encode(&task)?;
+fn record_resource_usage(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    key: &str,
+    usage: &ResourceUsage,
+) -> Result<()> {
+    let cf = IndexifyObjectsColumns::ResourceUsage.cf_db(&db);
+    let mut stats = match txn.get_for_update_cf(&cf, key, true)? {
+        Some(stats) => JsonEncoder::decode::<ResourceUsageStats>(
+            IndexifyObjectsColumns::ResourceUsage,
+            key,
+            &stats,
+        )?,
+        None => ResourceUsageStats::default(),
+    };
+    stats.record(usage);
     txn.put_cf(
-        &IndexifyObjectsColumns::Tasks.cf_db(&db),
-        task.key(),
-        task_bytes,
+        &cf,
+        key,
+        JsonEncoder::encode(IndexifyObjectsColumns::ResourceUsage, key, &stats)?,
     )?;
     Ok(())
 }
@@ -692,29 +2979,73 @@ pub(crate) fn save_state_changes(
     state_changes: &Vec<StateChange>,
 ) -> Result<()> {
     for state_change in state_changes {
-        let serialized_state_change = JsonEncoder::encode(&state_change)?;
+        let key = state_change.id.to_key();
         txn.put_cf(
             &IndexifyObjectsColumns::StateChanges.cf_db(&db),
-            &state_change.id.to_key(),
-            serialized_state_change.clone(),
+            &key,
+            JsonEncoder::encode(IndexifyObjectsColumns::StateChanges, &key, state_change)?,
         )?;
 
         if state_change.processed_at.is_none() {
             txn.put_cf(
                 &IndexifyObjectsColumns::UnprocessedStateChanges.cf_db(&db),
-                &state_change.id.to_key(),
-                serialized_state_change,
+                &key,
+                JsonEncoder::encode(
+                    IndexifyObjectsColumns::UnprocessedStateChanges,
+                    &key,
+                    state_change,
+                )?,
+            )?;
+            let queue_key = state_change.queue_key();
+            txn.put_cf(
+                &IndexifyObjectsColumns::StateChangeQueues.cf_db(&db),
+                &queue_key,
+                JsonEncoder::encode(
+                    IndexifyObjectsColumns::StateChangeQueues,
+                    &queue_key,
+                    state_change,
+                )?,
             )?;
         } else {
             txn.delete_cf(
                 &IndexifyObjectsColumns::UnprocessedStateChanges.cf_db(&db),
                 &state_change.id.to_key(),
             )?;
+            txn.delete_cf(
+                &IndexifyObjectsColumns::StateChangeQueues.cf_db(&db),
+                state_change.queue_key(),
+            )?;
         }
     }
     Ok(())
 }
 
+/// Adds unprocessed state changes recorded before they were queued per
+/// namespace to their queues.
+pub(crate) fn queue_unprocessed_state_changes(db: Arc<TransactionDB>) -> Result<()> {
+    let unprocessed = IndexifyObjectsColumns::UnprocessedStateChanges.cf_db(&db);
+    let queues = IndexifyObjectsColumns::StateChangeQueues.cf_db(&db);
+    for kv in db.iterator_cf(&unprocessed, IteratorMode::Start) {
+        let (key, value) = kv?;
+        let state_change: StateChange = JsonEncoder::decode(
+            IndexifyObjectsColumns::UnprocessedStateChanges,
+            &key,
+            &value,
+        )?;
+        let queue_key = state_change.queue_key();
+        db.put_cf(
+            &queues,
+            &queue_key,
+            JsonEncoder::encode(
+                IndexifyObjectsColumns::StateChangeQueues,
+                &queue_key,
+                &state_change,
+            )?,
+        )?;
+    }
+    Ok(())
+}
+
 pub(crate) fn mark_state_changes_processed(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
@@ -731,7 +3062,11 @@ pub(crate) fn mark_state_changes_processed(
             continue;
         }
         let state_change = state_change.unwrap();
-        let mut state_change: StateChange = JsonEncoder::decode(&state_change)?;
+        let mut state_change: StateChange = JsonEncoder::decode(
+            IndexifyObjectsColumns::StateChanges,
+            state_change_id.to_key(),
+            &state_change,
+        )?;
         state_change.processed_at = Some(get_epoch_time_in_ms());
         state_changes.push(state_change);
     }
@@ -760,12 +3095,14 @@ fn mark_invocation_finished(
             "Graph context not found for invocation: {}",
             &invocation_id
         ))?;
-    let mut graph_ctx: GraphInvocationCtx = JsonEncoder::decode(&graph_ctx)?;
+    let mut graph_ctx: GraphInvocationCtx =
+        JsonEncoder::decode(IndexifyObjectsColumns::GraphInvocationCtx, &key, &graph_ctx)?;
     graph_ctx.completed = true;
-    let serialized_graph_ctx = JsonEncoder::encode(&graph_ctx)?;
+    let serialized_graph_ctx =
+        JsonEncoder::encode(IndexifyObjectsColumns::GraphInvocationCtx, &key, &graph_ctx)?;
     txn.put_cf(
         &IndexifyObjectsColumns::GraphInvocationCtx.cf_db(&db),
-        key,
+        &key,
         serialized_graph_ctx,
     )?;
     if graph_ctx.is_system_task {
@@ -786,6 +3123,28 @@ fn mark_invocation_finished(
         txn.put_cf(&cf, key, &pending_system_tasks.to_be_bytes())?;
         Ok(InvocationCompletion::System)
     } else {
+        let invocation = txn
+            .get_cf(&IndexifyObjectsColumns::GraphInvocations.cf_db(&db), &key)?
+            .map(|value| {
+                JsonEncoder::decode::<InvocationPayload>(
+                    IndexifyObjectsColumns::GraphInvocations,
+                    &key,
+                    &value,
+                )
+            })
+            .transpose()?;
+        let duration_ms = invocation
+            .map(|invocation| get_epoch_time_in_ms().saturating_sub(invocation.created_at))
+            .unwrap_or_default();
+        let failed = graph_ctx
+            .fn_task_analytics
+            .values()
+            .any(|analytics| analytics.failed_tasks > 0);
+        update_canary(&db, txn, namespace, compute_graph, |canary| {
+            if let Some(stats) = canary.stats_mut(graph_ctx.graph_version) {
+                stats.invocation_finished(failed, duration_ms);
+            }
+        })?;
         Ok(InvocationCompletion::User)
     }
 }
@@ -795,15 +3154,145 @@ pub(crate) fn register_executor(
     txn: &Transaction<TransactionDB>,
     req: &RegisterExecutorRequest,
 ) -> Result<()> {
-    let serialized_executor_metadata = JsonEncoder::encode(&req.executor)?;
+    let serialized_executor_metadata = JsonEncoder::encode(
+        IndexifyObjectsColumns::Executors,
+        req.executor.key(),
+        &req.executor,
+    )?;
     txn.put_cf(
         &IndexifyObjectsColumns::Executors.cf_db(&db),
         req.executor.key(),
         serialized_executor_metadata,
     )?;
+    record_activity(
+        &db,
+        txn,
+        None,
+        Activity::ExecutorJoined {
+            executor_id: req.executor.id.clone(),
+        },
+    )?;
+    Ok(())
+}
+
+/// Registers a version of an artifact. Registering the same content again is
+/// a no-op, versions can't be changed once registered.
+pub(crate) fn register_artifact(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &RegisterArtifactRequest,
+) -> Result<()> {
+    let artifact = &req.artifact;
+    let existing = txn.get_for_update_cf(
+        &IndexifyObjectsColumns::Artifacts.cf_db(&db),
+        artifact.key(),
+        true,
+    )?;
+    if let Some(existing) = existing {
+        let existing = JsonEncoder::decode::<Artifact>(
+            IndexifyObjectsColumns::Artifacts,
+            artifact.key(),
+            &existing,
+        )?;
+        if existing.payload.sha256_hash != artifact.payload.sha256_hash {
+            return Err(ArtifactError::Exists(artifact.reference()).into());
+        }
+        // The blob just uploaded duplicates the registered one.
+        if existing.payload.path != artifact.payload.path {
+            txn.put_cf(
+                &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+                artifact.payload.path.as_bytes(),
+                [],
+            )?;
+        }
+        return Ok(());
+    }
+    txn.put_cf(
+        &IndexifyObjectsColumns::Artifacts.cf_db(&db),
+        artifact.key(),
+        JsonEncoder::encode(IndexifyObjectsColumns::Artifacts, artifact.key(), artifact)?,
+    )?;
+    Ok(())
+}
+
+/// Deletes a version of an artifact which no function references anymore,
+/// its blob is garbage collected.
+pub(crate) fn delete_artifact(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &DeleteArtifactRequest,
+) -> Result<()> {
+    let key = req.artifact.key(&req.namespace);
+    let Some(artifact) =
+        txn.get_for_update_cf(&IndexifyObjectsColumns::Artifacts.cf_db(&db), &key, true)?
+    else {
+        return Ok(());
+    };
+    let artifact =
+        JsonEncoder::decode::<Artifact>(IndexifyObjectsColumns::Artifacts, &key, &artifact)?;
+    let prefix = format!("{}|", req.namespace);
+    for iter in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::ComputeGraphs.cf_db(&db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (key, value) = iter?;
+        let compute_graph = JsonEncoder::decode::<ComputeGraph>(
+            IndexifyObjectsColumns::ComputeGraphs,
+            &key,
+            &value,
+        )?;
+        let in_use = compute_graph
+            .nodes
+            .values()
+            .any(|node| node.artifacts().contains(&req.artifact));
+        if in_use {
+            return Err(ArtifactError::InUse {
+                artifact: req.artifact.clone(),
+                compute_graph: compute_graph.name,
+            }
+            .into());
+        }
+    }
+    txn.put_cf(
+        &IndexifyObjectsColumns::GcUrls.cf_db(&db),
+        artifact.payload.path.as_bytes(),
+        [],
+    )?;
+    txn.delete_cf(&IndexifyObjectsColumns::Artifacts.cf_db(&db), &key)?;
     Ok(())
 }
 
+/// Records the artifacts an executor has fetched, returns false when the
+/// executor isn't registered.
+pub(crate) fn update_executor_artifacts(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &UpdateExecutorArtifactsRequest,
+) -> Result<bool> {
+    let Some(executor) = txn.get_for_update_cf(
+        &IndexifyObjectsColumns::Executors.cf_db(&db),
+        req.executor_id.get(),
+        true,
+    )?
+    else {
+        return Ok(false);
+    };
+    let mut executor = JsonEncoder::decode::<ExecutorMetadata>(
+        IndexifyObjectsColumns::Executors,
+        req.executor_id.get(),
+        &executor,
+    )?;
+    executor.artifacts = req.artifacts.clone();
+    txn.put_cf(
+        &IndexifyObjectsColumns::Executors.cf_db(&db),
+        executor.key(),
+        JsonEncoder::encode(IndexifyObjectsColumns::Executors, executor.key(), &executor)?,
+    )?;
+    Ok(true)
+}
+
 pub(crate) fn deregister_executor(
     db: Arc<TransactionDB>,
     txn: &Transaction<TransactionDB>,
@@ -818,19 +3307,378 @@ pub(crate) fn deregister_executor(
         read_options,
         iterator_mode,
     );
+    let mut index = scheduler_index::read(&db, txn)?;
     for key in iter {
         let (key, _) = key?;
+        if !key.starts_with(prefix.as_bytes()) {
+            break;
+        }
         txn.delete_cf(&IndexifyObjectsColumns::TaskAllocations.cf_db(&db), &key)?;
         let task_key = Task::key_from_allocation_key(&key)?;
+        if let Some(task) = txn.get_cf(&IndexifyObjectsColumns::Tasks.cf_db(&db), &task_key)? {
+            let task =
+                JsonEncoder::decode::<Task>(IndexifyObjectsColumns::Tasks, &task_key, &task)?;
+            index.remove(&req.executor_id, &task);
+        }
         txn.put_cf(
             &IndexifyObjectsColumns::UnallocatedTasks.cf_db(&db),
             &task_key,
             &[],
         )?;
     }
+    scheduler_index::write(&db, txn, &index)?;
     txn.delete_cf(
         &IndexifyObjectsColumns::Executors.cf_db(&db),
         req.executor_id.to_string(),
     )?;
     Ok(())
 }
+
+pub(crate) fn create_subscription(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &CreateSubscriptionRequest,
+) -> Result<()> {
+    txn.put_cf(
+        &IndexifyObjectsColumns::Subscriptions.cf_db(&db),
+        req.subscription.key(),
+        JsonEncoder::encode(
+            IndexifyObjectsColumns::Subscriptions,
+            req.subscription.key(),
+            &req.subscription,
+        )?,
+    )?;
+    Ok(())
+}
+
+pub(crate) fn delete_subscription(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &DeleteSubscriptionRequest,
+) -> Result<()> {
+    get_subscription_for_update(&db, txn, &req.namespace, &req.id)?;
+    txn.delete_cf(
+        &IndexifyObjectsColumns::Subscriptions.cf_db(&db),
+        Subscription::key_from(&req.namespace, &req.id),
+    )?;
+    delete_cf_prefix(
+        txn,
+        &IndexifyObjectsColumns::SubscriptionEvents.cf_db(&db),
+        SubscriptionEvent::key_prefix(&req.namespace, &req.id).as_bytes(),
+    )?;
+    Ok(())
+}
+
+fn get_subscription_for_update(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    id: &str,
+) -> Result<Subscription> {
+    let subscription = txn
+        .get_for_update_cf(
+            &IndexifyObjectsColumns::Subscriptions.cf_db(db),
+            Subscription::key_from(namespace, id),
+            true,
+        )?
+        .ok_or(SubscriptionError::NotFound(id.to_string()))?;
+    JsonEncoder::decode::<Subscription>(
+        IndexifyObjectsColumns::Subscriptions,
+        Subscription::key_from(namespace, id),
+        &subscription,
+    )
+}
+
+fn put_subscription(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    subscription: &Subscription,
+) -> Result<()> {
+    txn.put_cf(
+        &IndexifyObjectsColumns::Subscriptions.cf_db(db),
+        subscription.key(),
+        JsonEncoder::encode(
+            IndexifyObjectsColumns::Subscriptions,
+            subscription.key(),
+            subscription,
+        )?,
+    )?;
+    Ok(())
+}
+
+/// Removes the acknowledged events older than the retained ones.
+fn prune_subscription_events(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    subscription: &Subscription,
+) -> Result<()> {
+    let last_removed = subscription
+        .delivered_sequence
+        .min(subscription.last_sequence.saturating_sub(RETAINED_EVENTS));
+    let prefix = SubscriptionEvent::key_prefix(&subscription.namespace, &subscription.id);
+    let end =
+        SubscriptionEvent::key_from(&subscription.namespace, &subscription.id, last_removed + 1);
+    let cf = IndexifyObjectsColumns::SubscriptionEvents.cf_db(db);
+    for iter in make_prefix_iterator(txn, &cf, prefix.as_bytes(), &None) {
+        let (key, _) = iter?;
+        if *key >= *end.as_bytes() {
+            break;
+        }
+        txn.delete_cf(&cf, &key)?;
+    }
+    Ok(())
+}
+
+/// Adds an event to the activity feed of a namespace, or of every namespace
+/// without one.
+pub(crate) fn record_activity(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: Option<&str>,
+    activity: Activity,
+) -> Result<()> {
+    let event = ActivityEvent {
+        id: new_id(),
+        namespace: namespace.map(str::to_string),
+        recorded_at: get_epoch_time_in_ms(),
+        activity,
+    };
+    txn.put_cf(
+        &IndexifyObjectsColumns::Activity.cf_db(db),
+        event.key(),
+        JsonEncoder::encode(IndexifyObjectsColumns::Activity, event.key(), &event)?,
+    )?;
+    Ok(())
+}
+
+/// Deletes the activity recorded before `recorded_before`, in the feed of
+/// every namespace and of the whole server.
+pub(crate) fn prune_activity(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    recorded_before: u64,
+) -> Result<()> {
+    let mut scopes = vec![ActivityEvent::key_prefix(None)];
+    for iter in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::Namespaces.cf_db(&db),
+        &[],
+        &None,
+    ) {
+        let (key, value) = iter?;
+        let namespace =
+            JsonEncoder::decode::<Namespace>(IndexifyObjectsColumns::Namespaces, &key, &value)?;
+        scopes.push(ActivityEvent::key_prefix(Some(&namespace.name)));
+    }
+    let cf = IndexifyObjectsColumns::Activity.cf_db(&db);
+    for prefix in scopes {
+        let end = format!("{}{:020}", prefix, recorded_before);
+        for iter in make_prefix_iterator(txn, &cf, prefix.as_bytes(), &None) {
+            let (key, _) = iter?;
+            if *key >= *end.as_bytes() {
+                break;
+            }
+            txn.delete_cf(&cf, &key)?;
+        }
+    }
+    Ok(())
+}
+
+/// Request written with an id by `IndexifyState::write_once`, kept long
+/// enough to recognize the retries of the client that sent it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AppliedRequest {
+    pub request_id: String,
+    /// Kind of the request, an id is never reused for another kind.
+    pub request: String,
+    pub applied_at: u64,
+}
+
+/// Records the id of a request applied in the transaction, returns false
+/// when a request with the id was applied before and this one is a retry.
+pub(crate) fn record_applied_request(
+    db: &Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    request_id: &str,
+    request: &str,
+) -> Result<bool> {
+    let cf = IndexifyObjectsColumns::AppliedRequests.cf_db(db);
+    // Locking the id makes a concurrent retry wait for this transaction and
+    // see its record.
+    if let Some(value) = txn.get_for_update_cf(&cf, request_id, true)? {
+        let applied = JsonEncoder::decode::<AppliedRequest>(
+            IndexifyObjectsColumns::AppliedRequests,
+            request_id,
+            &value,
+        )?;
+        if applied.request != request {
+            return Err(anyhow!(
+                "request id {} was used for another request, {}",
+                request_id,
+                applied.request
+            ));
+        }
+        return Ok(false);
+    }
+    let applied = AppliedRequest {
+        request_id: request_id.to_string(),
+        request: request.to_string(),
+        applied_at: get_epoch_time_in_ms(),
+    };
+    txn.put_cf(
+        &cf,
+        request_id,
+        JsonEncoder::encode(
+            IndexifyObjectsColumns::AppliedRequests,
+            request_id,
+            &applied,
+        )?,
+    )?;
+    Ok(true)
+}
+
+pub(crate) fn prune_applied_requests(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    applied_before: u64,
+) -> Result<()> {
+    let cf = IndexifyObjectsColumns::AppliedRequests.cf_db(&db);
+    for iter in make_prefix_iterator(txn, &cf, &[], &None) {
+        let (key, value) = iter?;
+        if JsonEncoder::decode::<AppliedRequest>(
+            IndexifyObjectsColumns::AppliedRequests,
+            &key,
+            &value,
+        )?
+        .applied_at <
+            applied_before
+        {
+            txn.delete_cf(&cf, &key)?;
+        }
+    }
+    Ok(())
+}
+
+/// Appends the event to every subscription of the namespace, returns whether
+/// there was any.
+pub(crate) fn publish_event(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    namespace: &str,
+    event: NamespaceEvent,
+) -> Result<bool> {
+    let prefix = Subscription::key_prefix(namespace);
+    let mut subscription_ids = Vec::new();
+    for iter in make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::Subscriptions.cf_db(&db),
+        prefix.as_bytes(),
+        &None,
+    ) {
+        let (key, value) = iter?;
+        subscription_ids.push(
+            JsonEncoder::decode::<Subscription>(
+                IndexifyObjectsColumns::Subscriptions,
+                &key,
+                &value,
+            )?
+            .id,
+        );
+    }
+    for id in subscription_ids.iter() {
+        let mut subscription = get_subscription_for_update(&db, txn, namespace, id)?;
+        subscription.last_sequence += 1;
+        let event = SubscriptionEvent {
+            namespace: namespace.to_string(),
+            subscription_id: id.clone(),
+            sequence: subscription.last_sequence,
+            created_at: get_epoch_time_in_ms(),
+            event: event.clone(),
+        };
+        txn.put_cf(
+            &IndexifyObjectsColumns::SubscriptionEvents.cf_db(&db),
+            event.key(),
+            JsonEncoder::encode(
+                IndexifyObjectsColumns::SubscriptionEvents,
+                event.key(),
+                &event,
+            )?,
+        )?;
+        put_subscription(&db, txn, &subscription)?;
+        prune_subscription_events(&db, txn, &subscription)?;
+    }
+    Ok(!subscription_ids.is_empty())
+}
+
+pub(crate) fn publish_task_completed(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &FinalizeTaskRequest,
+) -> Result<bool> {
+    publish_event(
+        db,
+        txn,
+        &req.namespace,
+        NamespaceEvent::TaskCompleted {
+            compute_graph: req.compute_graph.clone(),
+            invocation_id: req.invocation_id.clone(),
+            compute_fn: req.compute_fn.clone(),
+            task_id: req.task_id.clone(),
+            outcome: req.task_outcome.clone(),
+        },
+    )
+}
+
+pub(crate) fn ack_subscription_events(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &AckSubscriptionEventsRequest,
+) -> Result<()> {
+    let mut subscription = get_subscription_for_update(&db, txn, &req.namespace, &req.id)?;
+    if subscription.delivered_sequence != req.previous_sequence ||
+        req.sequence > subscription.last_sequence
+    {
+        return Ok(());
+    }
+    subscription.delivered_sequence = req.sequence;
+    put_subscription(&db, txn, &subscription)?;
+    prune_subscription_events(&db, txn, &subscription)
+}
+
+pub(crate) fn replay_subscription(
+    db: Arc<TransactionDB>,
+    txn: &Transaction<TransactionDB>,
+    req: &ReplaySubscriptionRequest,
+) -> Result<()> {
+    let mut subscription = get_subscription_for_update(&db, txn, &req.namespace, &req.id)?;
+    let prefix = SubscriptionEvent::key_prefix(&req.namespace, &req.id);
+    let earliest = match make_prefix_iterator(
+        txn,
+        &IndexifyObjectsColumns::SubscriptionEvents.cf_db(&db),
+        prefix.as_bytes(),
+        &None,
+    )
+    .next()
+    .transpose()?
+    {
+        Some((key, value)) => {
+            JsonEncoder::decode::<SubscriptionEvent>(
+                IndexifyObjectsColumns::SubscriptionEvents,
+                &key,
+                &value,
+            )?
+            .sequence
+        }
+        None => subscription.last_sequence + 1,
+    };
+    if req.from_sequence < earliest.max(1) || req.from_sequence > subscription.last_sequence + 1 {
+        return Err(SubscriptionError::SequenceOutOfRange {
+            sequence: req.from_sequence,
+            earliest,
+            latest: subscription.last_sequence,
+        }
+        .into());
+    }
+    subscription.delivered_sequence = req.from_sequence - 1;
+    put_subscription(&db, txn, &subscription)
+}
@@ -18,7 +18,6 @@ pub mod tests {
         TaskId,
         TaskOutcome,
     };
-    use tempfile::TempDir;
 
     use crate::{
         requests::{
@@ -37,10 +36,7 @@ pub mod tests {
 
     impl TestStateStore {
         pub async fn new() -> Result<Self> {
-            let temp_dir = TempDir::new()?;
-            let indexify_state = IndexifyState::new(temp_dir.path().join("state"))
-                .await
-                .unwrap();
+            let indexify_state = IndexifyState::in_memory().await?;
             Ok(Self { indexify_state })
         }
 
@@ -48,6 +44,7 @@ pub mod tests {
             let cg_request = CreateComputeGraphRequest {
                 namespace: TEST_NAMESPACE.to_string(),
                 compute_graph: tests::mock_graph_a(),
+                precondition: None,
             };
             self.indexify_state
                 .write(StateMachineUpdateRequest {
@@ -76,6 +73,7 @@ pub mod tests {
             let cg_request = CreateComputeGraphRequest {
                 namespace: TEST_NAMESPACE.to_string(),
                 compute_graph: tests::mock_graph_b(),
+                precondition: None,
             };
             self.indexify_state
                 .write(StateMachineUpdateRequest {
@@ -105,6 +103,7 @@ pub mod tests {
             let cg_request = CreateComputeGraphRequest {
                 namespace: TEST_NAMESPACE.to_string(),
                 compute_graph: tests::mock_graph_with_reducer(),
+                precondition: None,
             };
             self.indexify_state
                 .write(StateMachineUpdateRequest {
@@ -162,6 +161,7 @@ pub mod tests {
                 node_outputs,
                 executor_id: ExecutorId::new(TEST_EXECUTOR_ID.to_string()),
                 diagnostics: None,
+                output_checks: None,
             };
 
             self.indexify_state
@@ -187,6 +187,7 @@ pub mod tests {
                 task_outcome: TaskOutcome::Success,
                 executor_id: ExecutorId::new(TEST_EXECUTOR_ID.to_string()),
                 diagnostics: None,
+                output_checks: None,
             };
             self.indexify_state
                 .write(StateMachineUpdateRequest {
@@ -207,6 +208,7 @@ pub mod tests {
                 task_outcome: TaskOutcome::Success,
                 executor_id: ExecutorId::new(TEST_EXECUTOR_ID.to_string()),
                 diagnostics: None,
+                output_checks: None,
             };
             self.indexify_state
                 .write(StateMachineUpdateRequest {
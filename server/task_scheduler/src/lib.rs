@@ -1,7 +1,20 @@
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
 
 use anyhow::{anyhow, Result};
-use data_model::{ExecutorId, Node, ReduceTask, Task};
+use data_model::{
+    ExecutorId,
+    ExecutorMetadata,
+    GpuAllocation,
+    GpuDeviceUsage,
+    GpuRequest,
+    Node,
+    ReduceTask,
+    Task,
+};
+use indexify_utils::get_epoch_time_in_ms;
 use rand::seq::SliceRandom;
 use state_store::{requests::TaskPlacement, IndexifyState};
 use tracing::info;
@@ -19,13 +32,51 @@ pub struct TaskCreationResult {
     pub invocation_id: String,
 }
 
+/// Why a task can't be placed on an executor.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Exclusion {
+    /// The executor runs another image.
+    ImageMismatch { image_name: String },
+    /// Labels of the executor don't satisfy these placement constraints.
+    UnmetConstraints { constraints: Vec<String> },
+    /// The executor already holds as many tasks as it takes.
+    AtCapacity {
+        allocated_tasks: u64,
+        max_allocated_tasks: u32,
+    },
+    /// The function needs a GPU and the executor has none.
+    NoGpu,
+    /// None of the executor's GPU devices has the share left that the
+    /// function needs.
+    GpuExhausted,
+}
+
+/// Where a task of a function could be placed right now.
+#[derive(Debug, Clone, Default)]
+pub struct PlacementExplanation {
+    pub eligible: Vec<ExecutorId>,
+    pub excluded: Vec<(ExecutorId, Vec<Exclusion>)>,
+    /// Set when the external dependency of the function has no calls left,
+    /// the task then waits for one even with eligible executors.
+    pub saturated_dependency: Option<String>,
+}
+
+struct ExecutorCatalog {
+    version: u64,
+    executors: Arc<Vec<ExecutorMetadata>>,
+}
+
 pub struct TaskScheduler {
     indexify_state: Arc<IndexifyState>,
+    executor_catalog: RwLock<Option<ExecutorCatalog>>,
 }
 
 impl TaskScheduler {
     pub fn new(indexify_state: Arc<IndexifyState>) -> Self {
-        Self { indexify_state }
+        Self {
+            indexify_state,
+            executor_catalog: RwLock::new(None),
+        }
     }
 
     pub fn schedule_unplaced_tasks(&self) -> Result<Vec<TaskPlacement>> {
@@ -33,43 +84,285 @@ impl TaskScheduler {
         self.schedule_tasks(tasks)
     }
 
-    pub fn schedule_tasks(&self, tasks: Vec<Task>) -> Result<Vec<TaskPlacement>> {
+    pub fn schedule_tasks(&self, mut tasks: Vec<Task>) -> Result<Vec<TaskPlacement>> {
+        Task::sort_for_placement(&mut tasks);
         let mut task_allocations = Vec::new();
-        for task in tasks {
+        // Only read when a task needs a GPU or calls an external dependency,
+        // and updated as tasks are placed.
+        let mut gpu_usage = None;
+        let mut dependency_usage = None;
+        let mut allocated_tasks = if self
+            .executors()?
+            .iter()
+            .any(|e| e.max_allocated_tasks().is_some())
+        {
+            self.indexify_state.reader().allocated_task_counts()?
+        } else {
+            HashMap::new()
+        };
+        for mut task in tasks {
             let cg = self
                 .indexify_state
                 .reader()
-                .get_compute_graph(&task.namespace, &task.compute_graph_name)?
+                .get_compute_graph_version(
+                    &task.namespace,
+                    &task.compute_graph_name,
+                    task.graph_version,
+                )?
                 .ok_or(anyhow!("Compute graph not found"))?;
             let compute_fn = cg
                 .nodes
                 .get(&task.compute_fn_name)
                 .ok_or(anyhow!("Compute fn not found"))?;
-            let executor_ids = self.filter_executors(&compute_fn)?;
-            let executor_id = executor_ids.choose(&mut rand::thread_rng());
+            if let Some(dependency) = compute_fn.external_dependency() {
+                let dependency_usage = match dependency_usage.as_mut() {
+                    Some(usage) => usage,
+                    None => dependency_usage
+                        .insert(self.indexify_state.reader().external_dependency_usage()?),
+                };
+                let in_flight = dependency_usage
+                    .get(&dependency.name)
+                    .copied()
+                    .unwrap_or_default();
+                if in_flight >= dependency.max_concurrent_calls as u64 {
+                    continue;
+                }
+            }
+            let executor_ids = self.filter_executors(&compute_fn, &task, &allocated_tasks)?;
+            let mut executor_id = None;
+            for executor_ids in self.prefer_artifact_holders(&task, compute_fn, executor_ids)? {
+                executor_id = match compute_fn.gpu() {
+                    Some(request) => {
+                        let gpu_usage = match gpu_usage.as_mut() {
+                            Some(gpu_usage) => gpu_usage,
+                            None => gpu_usage.insert(self.indexify_state.reader().gpu_usage()?),
+                        };
+                        let placement = self.pack_gpu_task(&executor_ids, &request, gpu_usage)?;
+                        placement.map(|(executor_id, allocation)| {
+                            task.gpu_allocation = Some(allocation);
+                            executor_id
+                        })
+                    }
+                    None => executor_ids.choose(&mut rand::thread_rng()).cloned(),
+                };
+                if executor_id.is_some() {
+                    break;
+                }
+            }
             if let Some(executor_id) = executor_id {
                 info!("Assigning task {:?} to executor {:?}", task.id, executor_id);
+                *allocated_tasks.entry(executor_id.clone()).or_default() += 1;
+                if let Some(dependency) = compute_fn.external_dependency() {
+                    if let Some(usage) = dependency_usage.as_mut() {
+                        *usage.entry(dependency.name.clone()).or_default() += 1;
+                    }
+                    task.external_dependency = Some(dependency.name.clone());
+                }
                 task_allocations.push(TaskPlacement {
                     task,
-                    executor: executor_id.clone(),
+                    executor: executor_id,
                 });
             }
         }
         Ok(task_allocations)
     }
 
-    fn filter_executors(&self, node: &Node) -> Result<Vec<ExecutorId>> {
-        let executors = self.indexify_state.reader().get_all_executors()?;
+    /// Explains which executors a task of the function would be placed on
+    /// with the current load, and why the others are excluded. Nothing is
+    /// allocated.
+    pub fn explain_placement(&self, node: &Node) -> Result<PlacementExplanation> {
+        let reader = self.indexify_state.reader();
+        let mut explanation = PlacementExplanation::default();
+        if let Some(dependency) = node.external_dependency() {
+            let in_flight = reader
+                .external_dependency_usage()?
+                .get(&dependency.name)
+                .copied()
+                .unwrap_or_default();
+            if in_flight >= dependency.max_concurrent_calls as u64 {
+                explanation.saturated_dependency = Some(dependency.name.clone());
+            }
+        }
+        let allocated_tasks = reader.allocated_task_counts()?;
+        let gpu_usage = match node.gpu() {
+            Some(_) => reader.gpu_usage()?,
+            None => HashMap::new(),
+        };
+        for executor in self.executors()?.iter() {
+            let mut exclusions = self.exclusions(node, executor, &allocated_tasks);
+            if let Some(request) = node.gpu() {
+                let fits = executor.gpus.iter().any(|device| {
+                    gpu_usage
+                        .get(&(executor.id.clone(), device.id.clone()))
+                        .cloned()
+                        .unwrap_or_default()
+                        .fits(device, &request)
+                });
+                if executor.gpus.is_empty() {
+                    exclusions.push(Exclusion::NoGpu);
+                } else if !fits {
+                    exclusions.push(Exclusion::GpuExhausted);
+                }
+            }
+            if exclusions.is_empty() {
+                explanation.eligible.push(executor.id.clone());
+            } else {
+                explanation.excluded.push((executor.id.clone(), exclusions));
+            }
+        }
+        Ok(explanation)
+    }
+
+    /// Returns the registered executors, only reading them from the state
+    /// store when executors were registered or deregistered since the last
+    /// call.
+    pub fn executors(&self) -> Result<Arc<Vec<ExecutorMetadata>>> {
+        let version = self.indexify_state.executors_version();
+        if let Some(catalog) = self.executor_catalog.read().unwrap().as_ref() {
+            if catalog.version == version {
+                return Ok(catalog.executors.clone());
+            }
+        }
+        let executors = Arc::new(self.indexify_state.reader().get_all_executors()?);
+        *self.executor_catalog.write().unwrap() = Some(ExecutorCatalog {
+            version,
+            executors: executors.clone(),
+        });
+        Ok(executors)
+    }
+
+    /// Picks the GPU device with the least memory left over after placing the
+    /// task, so that partially used devices fill up before empty ones and
+    /// larger requests still find room.
+    fn pack_gpu_task(
+        &self,
+        executor_ids: &[ExecutorId],
+        request: &GpuRequest,
+        gpu_usage: &mut HashMap<(ExecutorId, String), GpuDeviceUsage>,
+    ) -> Result<Option<(ExecutorId, GpuAllocation)>> {
+        let executors = self.executors()?;
+        let mut candidates = Vec::new();
+        for executor in executors.iter().filter(|e| executor_ids.contains(&e.id)) {
+            for device in &executor.gpus {
+                let usage = gpu_usage
+                    .get(&(executor.id.clone(), device.id.clone()))
+                    .cloned()
+                    .unwrap_or_default();
+                if usage.fits(device, request) {
+                    let remaining = device.memory_bytes - usage.memory_bytes - request.memory_bytes;
+                    candidates.push((remaining, executor.id.clone(), device.id.clone()));
+                }
+            }
+        }
+        let Some((_, executor_id, device_id)) = candidates
+            .into_iter()
+            .min_by_key(|(remaining, ..)| *remaining)
+        else {
+            return Ok(None);
+        };
+        let allocation = GpuAllocation {
+            device_id: device_id.clone(),
+            fraction: request.fraction,
+            memory_bytes: request.memory_bytes,
+        };
+        gpu_usage
+            .entry((executor_id.clone(), device_id))
+            .or_default()
+            .add(&allocation);
+        Ok(Some((executor_id, allocation)))
+    }
+
+    /// Splits the candidates so that executors which have already fetched all
+    /// artifacts of the function are tried before the others.
+    fn prefer_artifact_holders(
+        &self,
+        task: &Task,
+        node: &Node,
+        executor_ids: Vec<ExecutorId>,
+    ) -> Result<Vec<Vec<ExecutorId>>> {
+        if node.artifacts().is_empty() {
+            return Ok(vec![executor_ids]);
+        }
+        let mut hashes = Vec::new();
+        for artifact in node.artifacts() {
+            let Some(artifact) = self.indexify_state.reader().get_artifact(
+                &task.namespace,
+                &artifact.name,
+                &artifact.version,
+            )?
+            else {
+                return Ok(vec![executor_ids]);
+            };
+            hashes.push(artifact.payload.sha256_hash);
+        }
+        let executors = self.executors()?;
+        let holders: Vec<ExecutorId> = executors
+            .iter()
+            .filter(|executor| executor_ids.contains(&executor.id))
+            .filter(|executor| hashes.iter().all(|hash| executor.artifacts.contains(hash)))
+            .map(|executor| executor.id.clone())
+            .collect();
+        if holders.is_empty() || holders.len() == executor_ids.len() {
+            return Ok(vec![executor_ids]);
+        }
+        Ok(vec![holders, executor_ids])
+    }
+
+    fn filter_executors(
+        &self,
+        node: &Node,
+        task: &Task,
+        allocated_tasks: &HashMap<ExecutorId, u64>,
+    ) -> Result<Vec<ExecutorId>> {
+        let executors = self.executors()?;
         let mut filtered_executors = Vec::new();
+        let now = get_epoch_time_in_ms();
 
-        for executor in &executors {
-            if executor.image_name != node.image_name() {
+        for executor in executors.iter() {
+            if task.rejected_by(&executor.id, now) {
                 continue;
             }
-            if node.matches_executor(executor) {
+            if self.exclusions(node, executor, allocated_tasks).is_empty() {
                 filtered_executors.push(executor.id.clone());
             }
         }
         Ok(filtered_executors)
     }
+
+    /// Everything keeping a task of the function off the executor, GPUs
+    /// aside as those are only known while packing.
+    fn exclusions(
+        &self,
+        node: &Node,
+        executor: &ExecutorMetadata,
+        allocated_tasks: &HashMap<ExecutorId, u64>,
+    ) -> Vec<Exclusion> {
+        let mut exclusions = Vec::new();
+        if executor.image_name != node.image_name() {
+            exclusions.push(Exclusion::ImageMismatch {
+                image_name: executor.image_name.clone(),
+            });
+        }
+        if let Node::Compute(compute_fn) = node {
+            let unmet = compute_fn.placement_constraints.unmet(&executor.labels);
+            if !unmet.is_empty() {
+                exclusions.push(Exclusion::UnmetConstraints {
+                    constraints: unmet.iter().map(ToString::to_string).collect(),
+                });
+            }
+        }
+        if let Some(capacity) = executor.max_allocated_tasks() {
+            let allocated = allocated_tasks
+                .get(&executor.id)
+                .copied()
+                .unwrap_or_default();
+            if allocated >= capacity as u64 {
+                exclusions.push(Exclusion::AtCapacity {
+                    allocated_tasks: allocated,
+                    max_allocated_tasks: capacity,
+                });
+            }
+        }
+        exclusions
+    }
 }
@@ -1,7 +1,15 @@
 use std::sync::Arc;
 
 use anyhow::{anyhow, Result};
-use data_model::{ComputeGraph, InvokeComputeGraphEvent, Node, OutputPayload, Task, TaskOutcome};
+use data_model::{
+    ComputeGraph,
+    InvokeComputeGraphEvent,
+    Node,
+    OutputPayload,
+    Task,
+    TaskOutcome,
+    TEST_TASK_PRIORITY,
+};
 use state_store::IndexifyState;
 use tracing::{error, info};
 
@@ -11,9 +19,11 @@ pub async fn handle_invoke_compute_graph(
     indexify_state: Arc<IndexifyState>,
     event: InvokeComputeGraphEvent,
 ) -> Result<TaskCreationResult> {
-    let compute_graph = indexify_state
-        .reader()
-        .get_compute_graph(&event.namespace, &event.compute_graph)?;
+    let compute_graph = indexify_state.reader().get_compute_graph_for_invocation(
+        &event.namespace,
+        &event.compute_graph,
+        &event.invocation_id,
+    )?;
     if compute_graph.is_none() {
         error!(
             "compute graph not found: {:?} {:?}",
@@ -30,8 +40,31 @@ pub async fn handle_invoke_compute_graph(
         });
     }
     let compute_graph = compute_graph.unwrap();
+    let invocation = indexify_state.reader().invocation_payload(
+        &event.namespace,
+        &event.compute_graph,
+        &event.invocation_id,
+    )?;
+    if !compute_graph
+        .start_fn
+        .accepts_content_type(invocation.payload.content_type.as_deref())
+    {
+        info!(
+            "invocation {} of {:?} skips the graph, the start function doesn't accept {:?}",
+            event.invocation_id, event.compute_graph, invocation.payload.content_type
+        );
+        return Ok(TaskCreationResult {
+            namespace: event.namespace.clone(),
+            compute_graph: event.compute_graph.clone(),
+            invocation_id: event.invocation_id.clone(),
+            tasks: vec![],
+            new_reduction_tasks: vec![],
+            processed_reduction_tasks: vec![],
+            invocation_finished: true,
+        });
+    }
     // Crate a task for the compute graph
-    let task = compute_graph.start_fn.create_task(
+    let mut task = compute_graph.start_fn.create_task(
         &event.namespace,
         &event.compute_graph,
         &event.invocation_id,
@@ -39,6 +72,10 @@ pub async fn handle_invoke_compute_graph(
         None,
         compute_graph.version,
     )?;
+    task.trace = invocation.trace;
+    if invocation.test {
+        task.priority = TEST_TASK_PRIORITY;
+    }
     Ok(TaskCreationResult {
         namespace: event.namespace.clone(),
         compute_graph: event.compute_graph.clone(),
@@ -101,7 +138,7 @@ pub async fn handle_task_finished(
                 .nodes
                 .get(edge)
                 .ok_or(anyhow!("compute node not found: {:?}", edge))?;
-            let new_task = compute_fn.create_task(
+            let mut new_task = compute_fn.create_task(
                 &task.namespace,
                 &task.compute_graph_name,
                 &task.invocation_id,
@@ -109,6 +146,8 @@ pub async fn handle_task_finished(
                 None,
                 invocation_ctx.graph_version,
             )?;
+            new_task.trace = task.trace.clone();
+            new_task.priority = task.priority;
             new_tasks.push(new_task);
         }
         return Ok(TaskCreationResult {
@@ -137,7 +176,7 @@ pub async fn handle_task_finished(
                 if let Some(reduction_task) = reduction_task {
                     // Create a new task for the queued reduction_task
                     let output = outputs.first().unwrap();
-                    let new_task = compute_node.create_task(
+                    let mut new_task = compute_node.create_task(
                         &task.namespace,
                         &task.compute_graph_name,
                         &task.invocation_id,
@@ -145,6 +184,8 @@ pub async fn handle_task_finished(
                         Some(output.id.clone()),
                         invocation_ctx.graph_version,
                     )?;
+                    new_task.trace = task.trace.clone();
+                    new_task.priority = task.priority;
 
                     return Ok(TaskCreationResult {
                         namespace: task.namespace.clone(),
@@ -189,6 +230,9 @@ pub async fn handle_task_finished(
                 .nodes
                 .get(edge)
                 .ok_or(anyhow!("compute node not found: {:?}", edge))?;
+            if !compute_node.accepts_content_type(output.content_type()) {
+                continue;
+            }
             let task_analytics_edge = indexify_state.reader().task_analytics(
                 &task.namespace,
                 &task.compute_graph_name,
@@ -213,7 +257,7 @@ pub async fn handle_task_finished(
                 new_reduction_tasks.push(new_task);
                 continue;
             }
-            let new_task = compute_node.create_task(
+            let mut new_task = compute_node.create_task(
                 &task.namespace,
                 &task.compute_graph_name,
                 &task.invocation_id,
@@ -221,6 +265,8 @@ pub async fn handle_task_finished(
                 None,
                 invocation_ctx.graph_version,
             )?;
+            new_task.trace = task.trace.clone();
+            new_task.priority = task.priority;
             new_tasks.push(new_task);
         }
     }
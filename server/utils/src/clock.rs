@@ -0,0 +1,71 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::get_epoch_time_in_ms;
+
+/// Source of the current time for time-dependent behavior such as executor
+/// heartbeats and retention, so that tests can control it.
+pub trait Clock: Send + Sync {
+    /// Milliseconds since the epoch.
+    fn now_ms(&self) -> u64;
+
+    fn now_secs(&self) -> u64 {
+        self.now_ms() / 1000
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        get_epoch_time_in_ms()
+    }
+}
+
+/// Clock which only moves when told to.
+#[derive(Debug, Default)]
+pub struct TestClock {
+    now_ms: AtomicU64,
+}
+
+impl TestClock {
+    pub fn new(now_ms: u64) -> Self {
+        Self {
+            now_ms: AtomicU64::new(now_ms),
+        }
+    }
+
+    pub fn set(&self, now_ms: u64) {
+        self.now_ms.store(now_ms, Ordering::SeqCst);
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.now_ms
+            .fetch_add(by.as_millis() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for TestClock {
+    fn now_ms(&self) -> u64 {
+        self.now_ms.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_test_clock() {
+        let clock = TestClock::new(1_500);
+        assert_eq!(clock.now_secs(), 1);
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(clock.now_ms(), 3_500);
+        clock.set(10);
+        assert_eq!(clock.now_ms(), 10);
+        assert!(SystemClock.now_ms() > 0);
+    }
+}